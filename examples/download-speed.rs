@@ -3,6 +3,7 @@ use std::io::Write;
 use anyhow::{Error};
 
 use proxmox_backup::api2::types::Authid;
+use proxmox_backup::backup::BackupNamespace;
 use proxmox_backup::client::{HttpClient, HttpClientOptions, BackupReader};
 
 pub struct DummyWriter {
@@ -36,7 +37,7 @@ async fn run() -> Result<(), Error> {
 
     let backup_time = proxmox::tools::time::parse_rfc3339("2019-06-28T10:49:48Z")?;
 
-    let client = BackupReader::start(client, None, "store2", "host", "elsa", backup_time, true)
+    let client = BackupReader::start(client, None, "store2", &BackupNamespace::root(), "host", "elsa", backup_time, true)
         .await?;
 
     let start = std::time::SystemTime::now();
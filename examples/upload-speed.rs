@@ -1,6 +1,7 @@
 use anyhow::{Error};
 
 use proxmox_backup::api2::types::Authid;
+use proxmox_backup::backup::BackupNamespace;
 use proxmox_backup::client::*;
 
 async fn upload_speed() -> Result<f64, Error> {
@@ -18,7 +19,7 @@ async fn upload_speed() -> Result<f64, Error> {
 
     let backup_time = proxmox::tools::time::epoch_i64();
 
-    let client = BackupWriter::start(client, None, datastore, "host", "speedtest", backup_time, false, true).await?;
+    let client = BackupWriter::start(client, None, datastore, &BackupNamespace::root(), "host", "speedtest", backup_time, false, true).await?;
 
     println!("start upload speed test");
     let res = client.upload_speedtest(true).await?;
@@ -11,6 +11,7 @@ pub mod types;
 pub mod version;
 pub mod ping;
 pub mod pull;
+pub mod push;
 pub mod tape;
 mod helpers;
 
@@ -28,6 +29,7 @@ const SUBDIRS: SubdirMap = &[
     ("nodes", &NODES_ROUTER),
     ("ping", &ping::ROUTER),
     ("pull", &pull::ROUTER),
+    ("push", &push::ROUTER),
     ("reader", &reader::ROUTER),
     ("status", &status::ROUTER),
     ("tape", &tape::ROUTER),
@@ -23,6 +23,8 @@ use crate::config::tfa::TfaChallenge;
 
 pub mod acl;
 pub mod domain;
+pub mod group;
+pub mod openid;
 pub mod role;
 pub mod tfa;
 pub mod user;
@@ -424,6 +426,8 @@ const SUBDIRS: SubdirMap = &sorted!([
     ),
     ("ticket", &Router::new().post(&API_METHOD_CREATE_TICKET)),
     ("domains", &domain::ROUTER),
+    ("groups", &group::ROUTER),
+    ("openid", &openid::ROUTER),
     ("roles", &role::ROUTER),
     ("users", &user::ROUTER),
     ("tfa", &tfa::ROUTER),
@@ -213,8 +213,17 @@ pub fn update_acl(
 
     let delete = delete.unwrap_or(false);
 
-    if let Some(ref _group) = group {
-        bail!("parameter 'group' - groups are currently not supported.");
+    if let Some(ref group) = group {
+        if auth_id.is_some() {
+            bail!("parameters 'auth-id' and 'group' are mutually exclusive.");
+        }
+        if !delete { // Note: we allow to delete non-existent groups
+            let user_cfg = crate::config::user::cached_config()?;
+            match user_cfg.sections.get(group) {
+                Some((section_type, _)) if section_type == "group" => {},
+                _ => bail!("no such group."),
+            }
+        }
     } else if let Some(ref auth_id) = auth_id {
         if !delete { // Note: we allow to delete non-existent users
             let user_cfg = crate::config::user::cached_config()?;
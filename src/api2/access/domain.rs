@@ -4,10 +4,14 @@ use anyhow::{Error};
 
 use serde_json::{json, Value};
 
-use proxmox::api::{api, Permission};
-use proxmox::api::router::Router;
+use proxmox::api::{api, Permission, RpcEnvironment, RpcEnvironmentType};
+use proxmox::api::router::{Router, SubdirMap};
+use proxmox::{list_subdirs_api_method, sortable};
 
 use crate::api2::types::*;
+use crate::config::acl::PRIV_PERMISSIONS_MODIFY;
+use crate::config::domains::{self, LdapRealmConfig};
+use crate::server::{do_realm_sync_job, jobstate::Job};
 
 #[api(
     returns: {
@@ -21,6 +25,10 @@ use crate::api2::types::*;
                     description: "Realm ID.",
                     type: String,
                 },
+                "type": {
+                    description: "Realm type ('pam', 'pbs', 'ldap' or 'openid').",
+                    type: String,
+                },
                 comment: {
                     schema: SINGLE_LINE_COMMENT_SCHEMA,
                     optional: true,
@@ -28,6 +36,7 @@ use crate::api2::types::*;
                 default: {
                     description: "Default realm.",
                     type: bool,
+                    optional: true,
                 }
             },
         }
@@ -40,10 +49,62 @@ use crate::api2::types::*;
 /// Authentication domain/realm index.
 fn list_domains() -> Result<Value, Error> {
     let mut list = Vec::new();
-    list.push(json!({ "realm": "pam", "comment": "Linux PAM standard authentication", "default": true }));
-    list.push(json!({ "realm": "pbs", "comment": "Proxmox Backup authentication server" }));
+    list.push(json!({ "realm": "pam", "type": "pam", "comment": "Linux PAM standard authentication", "default": true }));
+    list.push(json!({ "realm": "pbs", "type": "pbs", "comment": "Proxmox Backup authentication server" }));
+
+    let (config, _digest) = domains::config()?;
+    for (realm, (section_type, data)) in config.sections.iter() {
+        let mut entry = json!({ "realm": realm, "type": section_type });
+        if let Some(comment) = data["comment"].as_str() {
+            entry["comment"] = comment.into();
+        }
+        list.push(entry);
+    }
+
     Ok(list.into())
 }
 
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Sync users of a LDAP realm into the user configuration.
+fn sync_realm(
+    realm: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<String, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    let config: LdapRealmConfig = domains::lookup_ldap_realm(&realm)?;
+
+    let job = Job::new("realm-sync", &realm)?;
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = do_realm_sync_job(job, config, &auth_id, None, to_stdout)?;
+
+    Ok(upid_str)
+}
+
+#[sortable]
+const REALM_SUBDIRS: SubdirMap = &[("sync", &Router::new().post(&API_METHOD_SYNC_REALM))];
+
+const REALM_ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(REALM_SUBDIRS))
+    .subdirs(REALM_SUBDIRS);
+
 pub const ROUTER: Router = Router::new()
-    .get(&API_METHOD_LIST_DOMAINS);
+    .get(&API_METHOD_LIST_DOMAINS)
+    .match_all("realm", &REALM_ROUTER);
@@ -0,0 +1,270 @@
+//! User Group Management
+
+use anyhow::{bail, Error};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use proxmox::api::{api, ApiMethod, Router, RpcEnvironment, Permission};
+use proxmox::api::section_config::SectionConfigData;
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::acl::{self, PRIV_SYS_AUDIT, PRIV_PERMISSIONS_MODIFY};
+use crate::config::user::{self, UserGroup, GROUP_MEMBER_LIST_SCHEMA};
+
+// Members must be existing users (API tokens cannot be group members)
+fn check_members(config: &SectionConfigData, group: &mut UserGroup) -> Result<(), Error> {
+    let mut members = group.member_list()?;
+    for member in &members {
+        match config.sections.get(member.as_str()) {
+            Some((section_type, _)) if section_type == "user" => {},
+            _ => bail!("no such user '{}'.", member),
+        }
+    }
+    members.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+    members.dedup();
+    group.set_member_list(&members);
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List user groups (with config digest).",
+        type: Array,
+        items: { type: UserGroup },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "groups"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List user groups
+pub fn list_groups(
+    _param: Value,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<UserGroup>, Error> {
+    let (config, digest) = user::config()?;
+
+    let list = config.convert_to_typed_array("group")?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            groupid: {
+                schema: PROXMOX_GROUP_ID_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            members: {
+                optional: true,
+                schema: GROUP_MEMBER_LIST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "groups"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Create new user group.
+pub fn create_group(param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(user::USER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let mut group: UserGroup = serde_json::from_value(param)?;
+
+    let (mut config, _digest) = user::config()?;
+
+    if config.sections.get(&group.groupid).is_some() {
+        bail!("group '{}' already exists.", group.groupid);
+    }
+
+    check_members(&config, &mut group)?;
+
+    config.set_data(&group.groupid, "group", &group)?;
+
+    user::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            groupid: {
+                schema: PROXMOX_GROUP_ID_SCHEMA,
+            },
+         },
+    },
+    returns: { type: UserGroup },
+    access: {
+        permission: &Permission::Privilege(&["access", "groups"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read user group configuration data.
+pub fn read_group(groupid: String, mut rpcenv: &mut dyn RpcEnvironment) -> Result<UserGroup, Error> {
+    let (config, digest) = user::config()?;
+    let group = config.lookup("group", &groupid)?;
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(group)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+#[allow(non_camel_case_types)]
+pub enum DeletableProperty {
+    /// Delete the comment property.
+    comment,
+    /// Remove all members.
+    members,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            groupid: {
+                schema: PROXMOX_GROUP_ID_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            members: {
+                optional: true,
+                schema: GROUP_MEMBER_LIST_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "groups"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Update user group configuration. The member list replaces the current members.
+pub fn update_group(
+    groupid: String,
+    comment: Option<String>,
+    members: Option<String>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(user::USER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = user::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: UserGroup = config.lookup("group", &groupid)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::comment => data.comment = None,
+                DeletableProperty::members => data.members = None,
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+
+    if members.is_some() {
+        data.members = members;
+    }
+
+    check_members(&config, &mut data)?;
+
+    config.set_data(&groupid, "group", &data)?;
+
+    user::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            groupid: {
+                schema: PROXMOX_GROUP_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "groups"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Remove a user group from the configuration file, including its ACL entries.
+pub fn delete_group(groupid: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = open_file_locked(user::USER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = user::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&groupid) {
+        Some((section_type, _)) if section_type == "group" => { config.sections.remove(&groupid); },
+        _ => bail!("group '{}' does not exist.", groupid),
+    }
+
+    user::save_config(&config)?;
+
+    // a new group with the same name must not inherit the permissions
+    let _acl_lock = open_file_locked(acl::ACL_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+    let (mut tree, _digest) = acl::config()?;
+    tree.delete_group(&groupid);
+    acl::save_config(&tree)?;
+
+    Ok(())
+}
+
+const GROUP_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_GROUP)
+    .put(&API_METHOD_UPDATE_GROUP)
+    .delete(&API_METHOD_DELETE_GROUP);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_GROUPS)
+    .post(&API_METHOD_CREATE_GROUP)
+    .match_all("groupid", &GROUP_ROUTER);
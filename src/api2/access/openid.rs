@@ -0,0 +1,269 @@
+//! OpenID Connect login
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use nix::sys::stat::Mode;
+use serde_json::{json, Value};
+
+use proxmox::api::router::{Router, SubdirMap};
+use proxmox::api::schema::{parse_simple_value, Schema, StringSchema};
+use proxmox::api::{api, Permission, RpcEnvironment};
+use proxmox::tools::fs::{file_read_optional_string, open_file_locked, replace_file, CreateOptions};
+use proxmox::{http_err, list_subdirs_api_method, sortable};
+
+use crate::api2::types::*;
+use crate::auth_helpers::*;
+use crate::config::cached_user_info::CachedUserInfo;
+use crate::config::domains::{self, OpenIdRealmConfig, OpenIdUsernameClaim};
+use crate::config::user::{self, User, EMAIL_SCHEMA, FIRST_NAME_SCHEMA, LAST_NAME_SCHEMA};
+use crate::server::ticket::ApiTicket;
+use crate::tools::openid::{OpenIdAuthenticator, PrivateAuthState};
+use crate::tools::ticket::Ticket;
+
+const LOGIN_STATE_FILENAME: &str = rundir!("/openid-login-state.json");
+const LOGIN_STATE_LOCKFILE: &str = rundir!("/.openid-login-state.lck");
+
+/// Pending logins time out after 10 minutes.
+const LOGIN_STATE_TIMEOUT: i64 = 10 * 60;
+
+const REDIRECT_URL_SCHEMA: Schema = StringSchema::new(
+    "Redirection URL. The client should set this to the URL of the login page.")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .max_length(1024)
+    .schema();
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct PendingLogin {
+    realm: String,
+    redirect_url: String,
+    state: PrivateAuthState,
+}
+
+// Read the pending logins, dropping expired ones. Requires the lock to be held.
+fn load_pending_logins() -> Result<HashMap<String, PendingLogin>, Error> {
+    let mut map: HashMap<String, PendingLogin> = match file_read_optional_string(LOGIN_STATE_FILENAME)? {
+        Some(data) => serde_json::from_str(&data).unwrap_or_default(),
+        None => HashMap::new(),
+    };
+
+    let now = proxmox::tools::time::epoch_i64();
+    map.retain(|_, login| (now - login.state.ctime) < LOGIN_STATE_TIMEOUT);
+
+    Ok(map)
+}
+
+fn save_pending_logins(map: &HashMap<String, PendingLogin>) -> Result<(), Error> {
+    let options = CreateOptions::new().perm(Mode::from_bits_truncate(0o0600));
+
+    replace_file(LOGIN_STATE_FILENAME, serde_json::to_string(map)?.as_bytes(), options)
+}
+
+fn lock_pending_logins() -> Result<std::fs::File, Error> {
+    open_file_locked(LOGIN_STATE_LOCKFILE, Duration::new(10, 0), true)
+}
+
+fn store_pending_login(login: PendingLogin) -> Result<(), Error> {
+    let _lock = lock_pending_logins()?;
+    let mut map = load_pending_logins()?;
+    map.insert(login.state.csrf_token.clone(), login);
+    save_pending_logins(&map)
+}
+
+// Every login state can only be used once
+fn take_pending_login(state: &str) -> Result<PendingLogin, Error> {
+    let _lock = lock_pending_logins()?;
+    let mut map = load_pending_logins()?;
+    let login = map.remove(state)
+        .ok_or_else(|| format_err!("invalid or expired login state"))?;
+    save_pending_logins(&map)?;
+    Ok(login)
+}
+
+fn claim_value(claims: &Value, name: &str, schema: &Schema) -> Option<String> {
+    let value = claims[name].as_str()?.trim();
+    parse_simple_value(value, schema).ok()?;
+    Some(value.to_string())
+}
+
+// Map the ID token to a user, creating it if allowed by the realm configuration
+fn lookup_or_create_user(config: &OpenIdRealmConfig, claims: &Value) -> Result<Userid, Error> {
+    let claim = config.username_claim.unwrap_or(OpenIdUsernameClaim::Subject);
+    let name = claims[claim.claim_name()].as_str()
+        .ok_or_else(|| format_err!("ID token contains no '{}' claim", claim.claim_name()))?;
+
+    let userid: Userid = format!("{}@{}", name, config.realm).parse()
+        .map_err(|err| format_err!("unable to use '{}' as user name - {}", name, err))?;
+
+    let _lock = open_file_locked(user::USER_CFG_LOCKFILE, Duration::new(10, 0), true)?;
+    let (mut user_config, _digest) = user::config()?;
+
+    if user_config.sections.get(userid.as_str()).is_none() {
+        if !config.autocreate.unwrap_or(false) {
+            bail!("user '{}' does not exist", userid);
+        }
+
+        let user = User {
+            userid: userid.clone(),
+            comment: None,
+            enable: None,
+            expire: None,
+            firstname: claim_value(claims, "given_name", &FIRST_NAME_SCHEMA),
+            lastname: claim_value(claims, "family_name", &LAST_NAME_SCHEMA),
+            email: claim_value(claims, "email", &EMAIL_SCHEMA),
+        };
+        user_config.set_data(userid.as_str(), "user", &user)?;
+        user::save_config(&user_config)?;
+
+        crate::server::rest::auth_logger()?
+            .log(format!("created user '{}' on OpenID login", userid));
+    }
+
+    Ok(userid)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            "redirect-url": {
+                schema: REDIRECT_URL_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        description: "Redirection URL.",
+        type: String,
+    },
+    access: {
+        description: "Anyone can access this (before the user is authenticated).",
+        permission: &Permission::World,
+    },
+)]
+/// Create the OpenID authorization URL.
+pub async fn auth_url(realm: String, redirect_url: String) -> Result<String, Error> {
+    let config = domains::lookup_openid_realm(&realm)?;
+    let authenticator = OpenIdAuthenticator::discover(config.client_config()).await?;
+
+    let state = PrivateAuthState::new()?;
+    let url = authenticator.authorize_url(&redirect_url, &state)?;
+
+    store_pending_login(PendingLogin { realm, redirect_url, state })?;
+
+    Ok(url)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            state: {
+                description: "OpenID state.",
+                type: String,
+                max_length: 1024,
+            },
+            code: {
+                description: "OpenID authorization code.",
+                type: String,
+                max_length: 4096,
+            },
+            "redirect-url": {
+                schema: REDIRECT_URL_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        properties: {
+            username: {
+                type: String,
+                description: "User name.",
+            },
+            ticket: {
+                type: String,
+                description: "Auth ticket.",
+            },
+            CSRFPreventionToken: {
+                type: String,
+                description: "Cross Site Request Forgery Prevention Token.",
+            },
+        },
+    },
+    access: {
+        description: "Anyone can access this (before the user is authenticated).",
+        permission: &Permission::World,
+    },
+)]
+/// Verify the OpenID authorization code and create a ticket.
+///
+/// Returns: An authentication ticket with additional infos.
+pub async fn login(
+    state: String,
+    code: String,
+    redirect_url: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let result: Result<Userid, Error> = async {
+        let login = take_pending_login(&state)?;
+        if login.redirect_url != redirect_url {
+            bail!("redirect URL does not match the login request");
+        }
+
+        let config = domains::lookup_openid_realm(&login.realm)?;
+        let authenticator = OpenIdAuthenticator::discover(config.client_config()).await?;
+        let claims = authenticator
+            .verify_authorization_code(&code, &redirect_url, &login.state)
+            .await?;
+
+        let userid = lookup_or_create_user(&config, &claims)?;
+
+        let user_info = CachedUserInfo::new()?;
+        if !user_info.is_active_auth_id(&Authid::from(userid.clone())) {
+            bail!("user account '{}' disabled or expired.", userid);
+        }
+
+        Ok::<_, Error>(userid)
+    }.await;
+
+    match result {
+        Ok(userid) => {
+            let api_ticket = ApiTicket::full(userid.clone());
+            let ticket = Ticket::new("PBS", &api_ticket)?.sign(private_auth_key(), None)?;
+            let token = assemble_csrf_prevention_token(csrf_secret(), &userid);
+
+            crate::server::rest::auth_logger()?
+                .log(format!("successful OpenID auth for user '{}'", userid));
+
+            Ok(json!({
+                "username": userid,
+                "ticket": ticket,
+                "CSRFPreventionToken": token,
+            }))
+        }
+        Err(err) => {
+            let client_ip = match rpcenv.get_client_ip().map(|addr| addr.ip()) {
+                Some(ip) => format!("{}", ip),
+                None => "unknown".into(),
+            };
+
+            let msg = format!("OpenID authentication failure; rhost={} msg={}", client_ip, err);
+            crate::server::rest::auth_logger()?.log(&msg);
+            log::error!("{}", msg);
+
+            Err(http_err!(UNAUTHORIZED, "OpenID login failed."))
+        }
+    }
+}
+
+#[sortable]
+const SUBDIRS: SubdirMap = &sorted!([
+    ("auth-url", &Router::new().post(&API_METHOD_AUTH_URL)),
+    ("login", &Router::new().post(&API_METHOD_LOGIN)),
+]);
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
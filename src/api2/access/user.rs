@@ -475,19 +475,23 @@ pub fn delete_user(userid: Userid, digest: Option<String>) -> Result<(), Error>
         None => bail!("user '{}' does not exist.", userid),
     }
 
+    let groups: Vec<user::UserGroup> = config.convert_to_typed_array("group")?;
+    for mut group in groups {
+        let members = group.member_list()?;
+        if members.contains(&userid) {
+            let members: Vec<Userid> = members.into_iter().filter(|member| *member != userid).collect();
+            group.set_member_list(&members);
+            config.set_data(&group.groupid, "group", &group)?;
+        }
+    }
+
     user::save_config(&config)?;
 
-    match crate::config::tfa::read().and_then(|mut cfg| {
-        let _: bool = cfg.remove_user(&userid);
-        crate::config::tfa::write(&cfg)
-    }) {
-        Ok(()) => (),
-        Err(err) => {
-            eprintln!(
-                "error updating TFA config after deleting user {:?}: {}",
-                userid, err
-            );
-        }
+    if let Err(err) = user::cleanup_deleted_users(&[userid.clone()]) {
+        eprintln!(
+            "error updating ACL/TFA config after deleting user {:?}: {}",
+            userid, err
+        );
     }
 
     Ok(())
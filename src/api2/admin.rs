@@ -4,11 +4,14 @@ use proxmox::api::router::{Router, SubdirMap};
 use proxmox::list_subdirs_api_method;
 
 pub mod datastore;
+pub mod namespace;
+pub mod prune;
 pub mod sync;
 pub mod verify;
 
 const SUBDIRS: SubdirMap = &[
     ("datastore", &datastore::ROUTER),
+    ("prune", &prune::ROUTER),
     ("sync", &sync::ROUTER),
     ("verify", &verify::ROUTER)
 ];
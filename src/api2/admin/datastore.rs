@@ -17,7 +17,7 @@ use proxmox::api::{
 };
 use proxmox::api::router::{ReturnType, SubdirMap};
 use proxmox::api::schema::*;
-use proxmox::tools::fs::{replace_file, CreateOptions};
+use proxmox::tools::fs::{open_file_locked, replace_file, CreateOptions};
 use proxmox::{http_err, identity, list_subdirs_api_method, sortable};
 
 use pxar::accessor::aio::Accessor;
@@ -38,6 +38,7 @@ use crate::tools::{
 };
 
 use crate::config::acl::{
+    PRIV_DATASTORE_ALLOCATE,
     PRIV_DATASTORE_AUDIT,
     PRIV_DATASTORE_MODIFY,
     PRIV_DATASTORE_READ,
@@ -46,6 +47,27 @@ use crate::config::acl::{
     PRIV_DATASTORE_VERIFY,
 };
 
+// Check that the user has any of the requested privileges on the
+// namespace ACL path and return the full privilege set.
+fn check_ns_privs(
+    store: &str,
+    ns: &BackupNamespace,
+    auth_id: &Authid,
+    privs: u64,
+) -> Result<u64, Error> {
+    let user_info = CachedUserInfo::new()?;
+    let acl_path = ns.acl_path(store);
+    let user_privs = user_info.lookup_privs(&auth_id, &acl_path);
+
+    if user_privs & privs == 0 {
+        // printing the path doesn't leak any information as long as we
+        // always check privilege before resource existence
+        bail!("no permissions on '/{}'", acl_path.join("/"));
+    }
+
+    Ok(user_privs)
+}
+
 fn check_priv_or_backup_owner(
     store: &DataStore,
     group: &BackupGroup,
@@ -53,7 +75,7 @@ fn check_priv_or_backup_owner(
     required_privs: u64,
 ) -> Result<(), Error> {
     let user_info = CachedUserInfo::new()?;
-    let privs = user_info.lookup_privs(&auth_id, &["datastore", store.name()]);
+    let privs = user_info.lookup_privs(&auth_id, &group.ns().acl_path(store.name()));
 
     if privs & required_privs == 0 {
         let owner = store.get_owner(group)?;
@@ -74,6 +96,16 @@ fn check_backup_owner(
     Ok(())
 }
 
+// parse the (namespaced) snapshot referenced by a manual API handler's parameters
+fn snapshot_from_param(param: &Value) -> Result<BackupDir, Error> {
+    let ns = BackupNamespace::from_opt(param["ns"].as_str())?;
+    let backup_type = tools::required_string_param(param, "backup-type")?;
+    let backup_id = tools::required_string_param(param, "backup-id")?;
+    let backup_time = tools::required_integer_param(param, "backup-time")?;
+
+    BackupDir::with_group(BackupGroup::with_ns(ns, backup_type, backup_id), backup_time)
+}
+
 fn read_backup_index(
     store: &DataStore,
     backup_dir: &BackupDir,
@@ -132,6 +164,10 @@ fn get_all_snapshot_files(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
         },
     },
     returns: {
@@ -142,26 +178,31 @@ fn get_all_snapshot_files(
         }
     },
     access: {
-        permission: &Permission::Privilege(
-            &["datastore", "{store}"],
-            PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP,
-            true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup only lists owned groups.",
     },
 )]
 /// List backup groups.
 pub fn list_groups(
     store: String,
+    ns: Option<BackupNamespace>,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Vec<GroupListItem>, Error> {
 
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
-    let user_info = CachedUserInfo::new()?;
-    let user_privs = user_info.lookup_privs(&auth_id, &["datastore", &store]);
+    let ns = ns.unwrap_or_default();
+    let user_privs = check_ns_privs(
+        &store,
+        &ns,
+        &auth_id,
+        PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP,
+    )?;
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
     let list_all = (user_privs & PRIV_DATASTORE_AUDIT) != 0;
 
-    let backup_groups = BackupInfo::list_backup_groups(&datastore.base_path())?;
+    let backup_groups = BackupInfo::list_backup_groups(&datastore.base_path(), &ns)?;
 
     let group_info = backup_groups
         .into_iter()
@@ -225,6 +266,10 @@ pub fn list_groups(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
             },
@@ -244,15 +289,15 @@ pub fn list_groups(
         }
     },
     access: {
-        permission: &Permission::Privilege(
-            &["datastore", "{store}"],
-            PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
-            true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit, Datastore.Read or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
     },
 )]
 /// List snapshot files.
 pub fn list_snapshot_files(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: String,
     backup_id: String,
     backup_time: i64,
@@ -261,9 +306,20 @@ pub fn list_snapshot_files(
 ) -> Result<Vec<BackupContent>, Error> {
 
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(
+        &store,
+        &ns,
+        &auth_id,
+        PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
+    )?;
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
 
-    let snapshot = BackupDir::new(backup_type, backup_id, backup_time)?;
+    let snapshot = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
 
     check_priv_or_backup_owner(&datastore, snapshot.group(), &auth_id, PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_READ)?;
 
@@ -280,6 +336,10 @@ pub fn list_snapshot_files(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
             },
@@ -292,15 +352,15 @@ pub fn list_snapshot_files(
         },
     },
     access: {
-        permission: &Permission::Privilege(
-            &["datastore", "{store}"],
-            PRIV_DATASTORE_MODIFY| PRIV_DATASTORE_PRUNE,
-            true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify or Datastore.Prune on \
+            /datastore/{store}[/{namespace}]. Datastore.Prune requires ownership of the group.",
     },
 )]
 /// Delete backup snapshot.
 pub fn delete_snapshot(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: String,
     backup_id: String,
     backup_time: i64,
@@ -309,9 +369,14 @@ pub fn delete_snapshot(
 ) -> Result<Value, Error> {
 
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_PRUNE)?;
 
-    let snapshot = BackupDir::new(backup_type, backup_id, backup_time)?;
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let snapshot = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
 
     check_priv_or_backup_owner(&datastore, snapshot.group(), &auth_id, PRIV_DATASTORE_MODIFY)?;
 
@@ -326,6 +391,60 @@ pub fn delete_snapshot(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "backup-type": {
+                schema: BACKUP_TYPE_SCHEMA,
+            },
+            "backup-id": {
+                schema: BACKUP_ID_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify or Datastore.Prune on \
+            /datastore/{store}[/{namespace}]. Datastore.Prune requires ownership of the group.",
+    },
+)]
+/// Delete backup group including all snapshots. Protected snapshots are kept.
+pub fn delete_group(
+    store: String,
+    ns: Option<BackupNamespace>,
+    backup_type: String,
+    backup_id: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_PRUNE)?;
+
+    let group = BackupGroup::with_ns(ns, backup_type, backup_id);
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+
+    check_priv_or_backup_owner(&datastore, &group, &auth_id, PRIV_DATASTORE_MODIFY)?;
+
+    if !datastore.remove_backup_group(&group)? {
+        bail!("did not delete whole group because of protected snapshots");
+    }
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 optional: true,
                 schema: BACKUP_TYPE_SCHEMA,
@@ -344,15 +463,15 @@ pub fn delete_snapshot(
         }
     },
     access: {
-        permission: &Permission::Privilege(
-            &["datastore", "{store}"],
-            PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP,
-            true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup only lists owned groups.",
     },
 )]
 /// List backup snapshots.
 pub fn list_snapshots (
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: Option<String>,
     backup_id: Option<String>,
     _param: Value,
@@ -361,40 +480,46 @@ pub fn list_snapshots (
 ) -> Result<Vec<SnapshotListItem>, Error> {
 
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
-    let user_info = CachedUserInfo::new()?;
-    let user_privs = user_info.lookup_privs(&auth_id, &["datastore", &store]);
+    let ns = ns.unwrap_or_default();
+    let user_privs = check_ns_privs(
+        &store,
+        &ns,
+        &auth_id,
+        PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP,
+    )?;
 
     let list_all = (user_privs & PRIV_DATASTORE_AUDIT) != 0;
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
 
     let base_path = datastore.base_path();
 
     let groups = match (backup_type, backup_id) {
         (Some(backup_type), Some(backup_id)) => {
             let mut groups = Vec::with_capacity(1);
-            groups.push(BackupGroup::new(backup_type, backup_id));
+            groups.push(BackupGroup::with_ns(ns, backup_type, backup_id));
             groups
         },
         (Some(backup_type), None) => {
-            BackupInfo::list_backup_groups(&base_path)?
+            BackupInfo::list_backup_groups(&base_path, &ns)?
                 .into_iter()
                 .filter(|group| group.backup_type() == backup_type)
                 .collect()
         },
         (None, Some(backup_id)) => {
-            BackupInfo::list_backup_groups(&base_path)?
+            BackupInfo::list_backup_groups(&base_path, &ns)?
                 .into_iter()
                 .filter(|group| group.backup_id() == backup_id)
                 .collect()
         },
-        _ => BackupInfo::list_backup_groups(&base_path)?,
+        _ => BackupInfo::list_backup_groups(&base_path, &ns)?,
     };
 
     let info_to_snapshot_list_item = |group: &BackupGroup, owner, info: BackupInfo| {
         let backup_type = group.backup_type().to_string();
         let backup_id = group.backup_id().to_string();
         let backup_time = info.backup_dir.backup_time();
+        let protected = info.protected;
 
         match get_all_snapshot_files(&datastore, &info) {
             Ok((manifest, files)) => {
@@ -433,6 +558,7 @@ pub fn list_snapshots (
                     files,
                     size,
                     owner,
+                    protected,
                 }
             },
             Err(err) => {
@@ -457,6 +583,7 @@ pub fn list_snapshots (
                     files,
                     size: None,
                     owner,
+                    protected,
                 }
             },
         }
@@ -494,7 +621,7 @@ pub fn list_snapshots (
 
 fn get_snapshots_count(store: &DataStore, filter_owner: Option<&Authid>) -> Result<Counts, Error> {
     let base_path = store.base_path();
-    let groups = BackupInfo::list_backup_groups(&base_path)?;
+    let groups = BackupInfo::list_backup_groups_recursive(&base_path, &BackupNamespace::root(), None)?;
 
     groups.iter()
         .filter(|group| {
@@ -541,7 +668,7 @@ fn get_snapshots_count(store: &DataStore, filter_owner: Option<&Authid>) -> Resu
                 type: bool,
                 default: false,
                 optional: true,
-                description: "Include additional information like snapshot counts and GC status.",
+                description: "Include additional information like snapshot counts, GC status and usage.",
             },
         },
 
@@ -560,9 +687,9 @@ pub fn status(
     _info: &ApiMethod,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<DataStoreStatus, Error> {
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
     let storage = crate::tools::disks::disk_usage(&datastore.base_path())?;
-    let (counts, gc_status) = if verbose {
+    let (counts, gc_status, usage) = if verbose {
         let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
         let user_info = CachedUserInfo::new()?;
 
@@ -576,9 +703,31 @@ pub fn status(
         let counts = Some(get_snapshots_count(&datastore, filter_owner)?);
         let gc_status = Some(datastore.last_gc_status());
 
-        (counts, gc_status)
+        let usage = match read_usage_status(&datastore)? {
+            Some(status) => {
+                let mut usage = status.usage;
+                if filter_owner.is_some() {
+                    let groups = user_info.user_groups(auth_id.user());
+                    usage = filter_user_usage(usage, auth_id.user(), &groups);
+                }
+
+                let (quota_config, _digest) = crate::config::quota::config()?;
+                let quotas = crate::config::quota::store_quotas(&quota_config, &store)?;
+                for entry in usage.iter_mut() {
+                    if let Some(quota) = quotas.iter().find(|quota| quota.subject == entry.subject) {
+                        entry.soft_limit = quota.soft_limit;
+                        entry.hard_limit = quota.hard_limit;
+                    }
+                }
+
+                Some(usage)
+            }
+            None => None,
+        };
+
+        (counts, gc_status, usage)
     } else {
-        (None, None)
+        (None, None, None)
     };
 
     Ok(DataStoreStatus {
@@ -587,6 +736,7 @@ pub fn status(
         avail: storage.avail,
         gc_status,
         counts,
+        usage,
     })
 }
 
@@ -596,6 +746,10 @@ pub fn status(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
                 optional: true,
@@ -614,7 +768,9 @@ pub fn status(
         schema: UPID_SCHEMA,
     },
     access: {
-        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_VERIFY | PRIV_DATASTORE_BACKUP, true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Verify or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
     },
 )]
 /// Verify backups.
@@ -623,14 +779,18 @@ pub fn status(
 /// or all backups in the datastore.
 pub fn verify(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: Option<String>,
     backup_id: Option<String>,
     backup_time: Option<i64>,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Value, Error> {
-    let datastore = DataStore::lookup_datastore(&store)?;
-
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_VERIFY | PRIV_DATASTORE_BACKUP)?;
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
     let worker_id;
 
     let mut backup_dir = None;
@@ -639,8 +799,11 @@ pub fn verify(
 
     match (backup_type, backup_id, backup_time) {
         (Some(backup_type), Some(backup_id), Some(backup_time)) => {
-            worker_id = format!("{}:{}/{}/{:08X}", store, backup_type, backup_id, backup_time);
-            let dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+            let dir = BackupDir::with_group(
+                BackupGroup::with_ns(ns, backup_type, backup_id),
+                backup_time,
+            )?;
+            worker_id = format!("{}:{}/{:08X}", store, dir.group(), backup_time);
 
             check_priv_or_backup_owner(&datastore, dir.group(), &auth_id, PRIV_DATASTORE_VERIFY)?;
 
@@ -648,8 +811,8 @@ pub fn verify(
             worker_type = "verify_snapshot";
         }
         (Some(backup_type), Some(backup_id), None) => {
-            worker_id = format!("{}:{}/{}", store, backup_type, backup_id);
-            let group = BackupGroup::new(backup_type, backup_id);
+            let group = BackupGroup::with_ns(ns, backup_type, backup_id);
+            worker_id = format!("{}:{}", store, group);
 
             check_priv_or_backup_owner(&datastore, &group, &auth_id, PRIV_DATASTORE_VERIFY)?;
 
@@ -777,14 +940,15 @@ pub const API_METHOD_PRUNE: ApiMethod = ApiMethod::new(
              .schema()
             ),
         ],[
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("store", false, &DATASTORE_SCHEMA),
         ])
     ))
     .returns(ReturnType::new(false, &API_RETURN_SCHEMA_PRUNE))
-    .access(None, &Permission::Privilege(
-    &["datastore", "{store}"],
-    PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_PRUNE,
-    true)
+    .access(
+        Some("Requires Datastore.Modify or Datastore.Prune on /datastore/{store}[/{namespace}]. \
+              Datastore.Prune requires ownership of the group."),
+        &Permission::Anybody,
 );
 
 pub fn prune(
@@ -797,13 +961,17 @@ pub fn prune(
     let backup_type = tools::required_string_param(&param, "backup-type")?;
     let backup_id = tools::required_string_param(&param, "backup-id")?;
 
+    let ns = BackupNamespace::from_opt(param["ns"].as_str())?;
+
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_PRUNE)?;
+
     let dry_run = param["dry-run"].as_bool().unwrap_or(false);
 
-    let group = BackupGroup::new(backup_type, backup_id);
+    let group = BackupGroup::with_ns(ns, backup_type, backup_id);
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
 
     check_priv_or_backup_owner(&datastore, &group, &auth_id, PRIV_DATASTORE_MODIFY)?;
 
@@ -816,7 +984,7 @@ pub fn prune(
         keep_yearly: param["keep-yearly"].as_u64(),
     };
 
-    let worker_id = format!("{}:{}/{}", store, backup_type, backup_id);
+    let worker_id = format!("{}:{}", store, group);
 
     let mut prune_result = Vec::new();
 
@@ -840,6 +1008,7 @@ pub fn prune(
                 "backup-id": group.backup_id(),
                 "backup-time": backup_time,
                 "keep": keep,
+                "protected": info.protected,
             }));
         }
         return Ok(json!(prune_result));
@@ -853,8 +1022,8 @@ pub fn prune(
         worker.log("No prune selection - keeping all files.");
     } else {
         worker.log(format!("retention options: {}", prune_options.cli_options_string()));
-        worker.log(format!("Starting prune on store \"{}\" group \"{}/{}\"",
-                            store, backup_type, backup_id));
+        worker.log(format!("Starting prune on store \"{}\" group \"{}\"",
+                            store, group));
     }
 
     for (info, mut keep) in prune_info {
@@ -870,7 +1039,7 @@ pub fn prune(
             group.backup_type(),
             group.backup_id(),
             timestamp,
-            if keep { "keep" } else { "remove" },
+            if info.protected { "keep (protected)" } else if keep { "keep" } else { "remove" },
         );
 
         worker.log(msg);
@@ -880,6 +1049,7 @@ pub fn prune(
             "backup-id": group.backup_id(),
             "backup-time": backup_time,
             "keep": keep,
+            "protected": info.protected,
         }));
 
         if !(dry_run || keep) {
@@ -921,7 +1091,7 @@ pub fn start_garbage_collection(
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Value, Error> {
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
     let job =  Job::new("garbage_collection", &store)
@@ -957,7 +1127,7 @@ pub fn garbage_collection_status(
     _rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<GarbageCollectionStatus, Error> {
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
 
     let status = datastore.last_gc_status();
 
@@ -989,8 +1159,12 @@ pub fn get_datastore_list(
     let mut list = Vec::new();
 
     for (store, (_, data)) in &config.sections {
-        let user_privs = user_info.lookup_privs(&auth_id, &["datastore", &store]);
-        let allowed = (user_privs & (PRIV_DATASTORE_AUDIT| PRIV_DATASTORE_BACKUP)) != 0;
+        // privileges on any namespace below the datastore make it visible
+        let allowed = user_info.any_privs_below(
+            &auth_id,
+            &["datastore", &store],
+            PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP,
+        );
         if allowed {
             list.push(
                 DataStoreListItem {
@@ -1011,16 +1185,17 @@ pub const API_METHOD_DOWNLOAD_FILE: ApiMethod = ApiMethod::new(
         "Download single raw file from backup snapshot.",
         &sorted!([
             ("store", false, &DATASTORE_SCHEMA),
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("backup-type", false, &BACKUP_TYPE_SCHEMA),
             ("backup-id", false,  &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
             ("file-name", false, &BACKUP_ARCHIVE_NAME_SCHEMA),
         ]),
     )
-).access(None, &Permission::Privilege(
-    &["datastore", "{store}"],
-    PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
-    true)
+).access(
+    Some("Requires Datastore.Read or Datastore.Backup on /datastore/{store}[/{namespace}]. \
+          Datastore.Backup requires ownership of the group."),
+    &Permission::Anybody,
 );
 
 pub fn download_file(
@@ -1033,18 +1208,20 @@ pub fn download_file(
 
     async move {
         let store = tools::required_string_param(&param, "store")?;
-        let datastore = DataStore::lookup_datastore(store)?;
 
         let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
         let file_name = tools::required_string_param(&param, "file-name")?.to_owned();
 
-        let backup_type = tools::required_string_param(&param, "backup-type")?;
-        let backup_id = tools::required_string_param(&param, "backup-id")?;
-        let backup_time = tools::required_integer_param(&param, "backup-time")?;
-
-        let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+        let backup_dir = snapshot_from_param(&param)?;
 
+        check_ns_privs(
+            &store,
+            backup_dir.group().ns(),
+            &auth_id,
+            PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
+        )?;
+        let datastore = DataStore::lookup_datastore(store, Some(Operation::Read))?;
         check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_READ)?;
 
         println!("Download {} from {} ({}/{})", file_name, store, backup_dir, file_name);
@@ -1081,16 +1258,17 @@ pub const API_METHOD_DOWNLOAD_FILE_DECODED: ApiMethod = ApiMethod::new(
         "Download single decoded file from backup snapshot. Only works if it's not encrypted.",
         &sorted!([
             ("store", false, &DATASTORE_SCHEMA),
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("backup-type", false, &BACKUP_TYPE_SCHEMA),
             ("backup-id", false,  &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
             ("file-name", false, &BACKUP_ARCHIVE_NAME_SCHEMA),
         ]),
     )
-).access(None, &Permission::Privilege(
-    &["datastore", "{store}"],
-    PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
-    true)
+).access(
+    Some("Requires Datastore.Read or Datastore.Backup on /datastore/{store}[/{namespace}]. \
+          Datastore.Backup requires ownership of the group."),
+    &Permission::Anybody,
 );
 
 pub fn download_file_decoded(
@@ -1103,18 +1281,20 @@ pub fn download_file_decoded(
 
     async move {
         let store = tools::required_string_param(&param, "store")?;
-        let datastore = DataStore::lookup_datastore(store)?;
 
         let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
         let file_name = tools::required_string_param(&param, "file-name")?.to_owned();
 
-        let backup_type = tools::required_string_param(&param, "backup-type")?;
-        let backup_id = tools::required_string_param(&param, "backup-id")?;
-        let backup_time = tools::required_integer_param(&param, "backup-time")?;
-
-        let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+        let backup_dir = snapshot_from_param(&param)?;
 
+        check_ns_privs(
+            &store,
+            backup_dir.group().ns(),
+            &auth_id,
+            PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
+        )?;
+        let datastore = DataStore::lookup_datastore(store, Some(Operation::Read))?;
         check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_READ)?;
 
         let (manifest, files) = read_backup_index(&datastore, &backup_dir)?;
@@ -1197,14 +1377,16 @@ pub const API_METHOD_UPLOAD_BACKUP_LOG: ApiMethod = ApiMethod::new(
         "Upload the client backup log file into a backup snapshot ('client.log.blob').",
         &sorted!([
             ("store", false, &DATASTORE_SCHEMA),
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("backup-type", false, &BACKUP_TYPE_SCHEMA),
             ("backup-id", false, &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
         ]),
     )
 ).access(
-    Some("Only the backup creator/owner is allowed to do this."),
-    &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_BACKUP, false)
+    Some("Only the backup creator/owner is allowed to do this. \
+          Requires Datastore.Backup on /datastore/{store}[/{namespace}]."),
+    &Permission::Anybody,
 );
 
 pub fn upload_backup_log(
@@ -1217,17 +1399,14 @@ pub fn upload_backup_log(
 
     async move {
         let store = tools::required_string_param(&param, "store")?;
-        let datastore = DataStore::lookup_datastore(store)?;
 
         let file_name =  CLIENT_LOG_BLOB_NAME;
 
-        let backup_type = tools::required_string_param(&param, "backup-type")?;
-        let backup_id = tools::required_string_param(&param, "backup-id")?;
-        let backup_time = tools::required_integer_param(&param, "backup-time")?;
-
-        let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+        let backup_dir = snapshot_from_param(&param)?;
 
         let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+        check_ns_privs(&store, backup_dir.group().ns(), &auth_id, PRIV_DATASTORE_BACKUP)?;
+        let datastore = DataStore::lookup_datastore(store, Some(Operation::Write))?;
         let owner = datastore.get_owner(backup_dir.group())?;
         check_backup_owner(&owner, &auth_id)?;
 
@@ -1266,6 +1445,10 @@ pub fn upload_backup_log(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
             },
@@ -1282,23 +1465,31 @@ pub fn upload_backup_log(
         },
     },
     access: {
-        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP, true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Read or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
     },
 )]
 /// Get the entries of the given path of the catalog
 pub fn catalog(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: String,
     backup_id: String,
     backup_time: i64,
     filepath: String,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Vec<ArchiveEntry>, Error> {
-    let datastore = DataStore::lookup_datastore(&store)?;
-
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP)?;
 
-    let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
+    let backup_dir = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
 
     check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_READ)?;
 
@@ -1342,16 +1533,17 @@ pub const API_METHOD_PXAR_FILE_DOWNLOAD: ApiMethod = ApiMethod::new(
         "Download single file from pxar file of a backup snapshot. Only works if it's not encrypted.",
         &sorted!([
             ("store", false, &DATASTORE_SCHEMA),
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("backup-type", false, &BACKUP_TYPE_SCHEMA),
             ("backup-id", false,  &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
             ("filepath", false, &StringSchema::new("Base64 encoded path").schema()),
         ]),
     )
-).access(None, &Permission::Privilege(
-    &["datastore", "{store}"],
-    PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
-    true)
+).access(
+    Some("Requires Datastore.Read or Datastore.Backup on /datastore/{store}[/{namespace}]. \
+          Datastore.Backup requires ownership of the group."),
+    &Permission::Anybody,
 );
 
 pub fn pxar_file_download(
@@ -1364,18 +1556,20 @@ pub fn pxar_file_download(
 
     async move {
         let store = tools::required_string_param(&param, "store")?;
-        let datastore = DataStore::lookup_datastore(&store)?;
 
         let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
         let filepath = tools::required_string_param(&param, "filepath")?.to_owned();
 
-        let backup_type = tools::required_string_param(&param, "backup-type")?;
-        let backup_id = tools::required_string_param(&param, "backup-id")?;
-        let backup_time = tools::required_integer_param(&param, "backup-time")?;
-
-        let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+        let backup_dir = snapshot_from_param(&param)?;
 
+        check_ns_privs(
+            &store,
+            backup_dir.group().ns(),
+            &auth_id,
+            PRIV_DATASTORE_READ | PRIV_DATASTORE_BACKUP,
+        )?;
+        let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
         check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_READ)?;
 
         let mut components = base64::decode(&filepath)?;
@@ -1500,6 +1694,10 @@ pub fn get_rrd_stats(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
             },
@@ -1512,21 +1710,30 @@ pub fn get_rrd_stats(
         },
     },
     access: {
-        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP, true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
     },
 )]
 /// Get "notes" for a specific backup
 pub fn get_notes(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: String,
     backup_id: String,
     backup_time: i64,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<String, Error> {
-    let datastore = DataStore::lookup_datastore(&store)?;
-
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
-    let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP)?;
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
+    let backup_dir = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
 
     check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_AUDIT)?;
 
@@ -1545,6 +1752,10 @@ pub fn get_notes(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
             },
@@ -1560,24 +1771,31 @@ pub fn get_notes(
         },
     },
     access: {
-        permission: &Permission::Privilege(&["datastore", "{store}"],
-                                           PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_BACKUP,
-                                           true),
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
     },
 )]
 /// Set "notes" for a specific backup
 pub fn set_notes(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: String,
     backup_id: String,
     backup_time: i64,
     notes: String,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<(), Error> {
-    let datastore = DataStore::lookup_datastore(&store)?;
-
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
-    let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_BACKUP)?;
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+
+    let backup_dir = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
 
     check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_MODIFY)?;
 
@@ -1594,6 +1812,292 @@ pub fn set_notes(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+        },
+    },
+    returns: {
+        type: ActiveOperationStats,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_AUDIT, true),
+    },
+)]
+/// Number of read and write operations currently running on the datastore.
+///
+/// Once these dropped to zero, a newly set maintenance mode is fully in effect.
+pub fn get_active_operations(store: String) -> Result<ActiveOperationStats, Error> {
+    // make sure the datastore exists, independent of its maintenance mode or mount state
+    let (config, _digest) = datastore::config()?;
+    let _: datastore::DataStoreConfig = config.lookup("datastore", &store)?;
+
+    crate::backup::get_active_operations(&store)
+}
+
+// Sets the maintenance mode of a datastore, returning the previous one
+fn replace_maintenance_mode(store: &str, mode: Option<String>) -> Result<Option<String>, Error> {
+    let _lock = open_file_locked(datastore::DATASTORE_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, _digest) = datastore::config()?;
+    let mut data: datastore::DataStoreConfig = config.lookup("datastore", store)?;
+
+    let old_mode = std::mem::replace(&mut data.maintenance_mode, mode);
+
+    config.set_data(store, "datastore", &data)?;
+    datastore::save_config(&config)?;
+
+    Ok(old_mode)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+    },
+)]
+/// Mount the removable device of a datastore.
+pub fn mount(
+    store: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let (config, _digest) = datastore::config()?;
+    let store_config: datastore::DataStoreConfig = config.lookup("datastore", &store)?;
+
+    let uuid = match store_config.backing_device {
+        Some(ref uuid) => uuid.clone(),
+        None => bail!("datastore '{}' is not located on a removable device", store),
+    };
+
+    if store_config.is_mounted() {
+        bail!("datastore '{}' is already mounted", store);
+    }
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = WorkerTask::new_thread("mount-device", Some(store.clone()), auth_id, to_stdout, move |worker| {
+        let path = std::path::PathBuf::from(&store_config.path);
+
+        worker.log(format!("mounting device '{}' of datastore '{}' at {:?}", uuid, store, path));
+        tools::disks::mount_by_fs_uuid(&uuid, &path)?;
+
+        // make sure the device actually contains the datastore
+        if let Err(err) = ChunkStore::open(&store, &path) {
+            let _ = tools::disks::unmount_path(&path);
+            bail!("device '{}' does not contain datastore '{}' - {}", uuid, store, err);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(json!(upid_str))
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+    },
+)]
+/// Unmount the removable device of a datastore.
+///
+/// The datastore is set offline first, and the device gets unmounted once all
+/// running operations on it are finished.
+pub fn unmount(
+    store: String,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let (config, _digest) = datastore::config()?;
+    let store_config: datastore::DataStoreConfig = config.lookup("datastore", &store)?;
+
+    if !store_config.is_removable() {
+        bail!("datastore '{}' is not located on a removable device", store);
+    }
+
+    if !store_config.is_mounted() {
+        bail!("datastore '{}' is not mounted", store);
+    }
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = WorkerTask::new_thread("unmount-device", Some(store.clone()), auth_id, to_stdout, move |worker| {
+        let path = std::path::PathBuf::from(&store_config.path);
+
+        let old_mode = replace_maintenance_mode(&store, Some("offline".to_string()))?;
+
+        let result = proxmox::try_block!({
+            let mut last_active = None;
+            loop {
+                let active = crate::backup::get_active_operations(&store)?;
+                if active.read + active.write == 0 {
+                    break;
+                }
+                if last_active != Some((active.read, active.write)) {
+                    worker.log(format!(
+                        "waiting for {} read and {} write operations to finish",
+                        active.read, active.write,
+                    ));
+                    last_active = Some((active.read, active.write));
+                }
+                worker.fail_on_abort()?;
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+
+            worker.log(format!("unmounting datastore '{}' from {:?}", store, path));
+            tools::disks::unmount_path(&path)
+        });
+
+        if let Err(err) = replace_maintenance_mode(&store, old_mode) {
+            worker.warn(format!("could not restore maintenance mode - {}", err));
+        }
+
+        result
+    })?;
+
+    Ok(json!(upid_str))
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "backup-type": {
+                schema: BACKUP_TYPE_SCHEMA,
+            },
+            "backup-id": {
+                schema: BACKUP_ID_SCHEMA,
+            },
+            "backup-time": {
+                schema: BACKUP_TIME_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        type: bool,
+        description: "Whether the snapshot is protected.",
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
+    },
+)]
+/// Query protection for a specific backup
+pub fn get_protection(
+    store: String,
+    ns: Option<BackupNamespace>,
+    backup_type: String,
+    backup_id: String,
+    backup_time: i64,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<bool, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP)?;
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
+    let backup_dir = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
+
+    check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_AUDIT)?;
+
+    Ok(backup_dir.is_protected(&datastore.base_path()))
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "backup-type": {
+                schema: BACKUP_TYPE_SCHEMA,
+            },
+            "backup-id": {
+                schema: BACKUP_ID_SCHEMA,
+            },
+            "backup-time": {
+                schema: BACKUP_TIME_SCHEMA,
+            },
+            protected: {
+                description: "Enable/disable protection.",
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify or Datastore.Backup on \
+            /datastore/{store}[/{namespace}]. Datastore.Backup requires ownership of the group.",
+    },
+)]
+/// En- or disable protection for a specific backup
+pub fn set_protection(
+    store: String,
+    ns: Option<BackupNamespace>,
+    backup_type: String,
+    backup_id: String,
+    backup_time: i64,
+    protected: bool,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let ns = ns.unwrap_or_default();
+    check_ns_privs(&store, &ns, &auth_id, PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_BACKUP)?;
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+
+    let backup_dir = BackupDir::with_group(
+        BackupGroup::with_ns(ns, backup_type, backup_id),
+        backup_time,
+    )?;
+
+    check_priv_or_backup_owner(&datastore, backup_dir.group(), &auth_id, PRIV_DATASTORE_MODIFY)?;
+
+    datastore.update_protection(&backup_dir, protected)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "backup-type": {
                 schema: BACKUP_TYPE_SCHEMA,
             },
@@ -1607,27 +2111,28 @@ pub fn set_notes(
     },
     access: {
         permission: &Permission::Anybody,
-        description: "Datastore.Modify on whole datastore, or changing ownership between user and a user's token for owned backups with Datastore.Backup"
+        description: "Datastore.Modify on whole datastore or namespace, or changing ownership between user and a user's token for owned backups with Datastore.Backup"
     },
 )]
 /// Change owner of a backup group
 pub fn set_backup_owner(
     store: String,
+    ns: Option<BackupNamespace>,
     backup_type: String,
     backup_id: String,
     new_owner: Authid,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<(), Error> {
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
 
-    let backup_group = BackupGroup::new(backup_type, backup_id);
+    let backup_group = BackupGroup::with_ns(ns.unwrap_or_default(), backup_type, backup_id);
 
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
     let user_info = CachedUserInfo::new()?;
 
-    let privs = user_info.lookup_privs(&auth_id, &["datastore", &store]);
+    let privs = user_info.lookup_privs(&auth_id, &backup_group.ns().acl_path(&store));
 
     let allowed = if (privs & PRIV_DATASTORE_MODIFY) != 0 {
         // High-privilege user/token
@@ -1685,8 +2190,275 @@ pub fn set_backup_owner(
     Ok(())
 }
 
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "max-samples": {
+                description: "Maximum number of chunks to use as training samples.",
+                type: Integer,
+                minimum: 10,
+                maximum: 100_000,
+                default: 1000,
+                optional: true,
+            },
+            size: {
+                description: "Maximum size of the dictionary in bytes.",
+                type: Integer,
+                minimum: 4096,
+                maximum: MAX_DICTIONARY_SIZE as isize,
+                default: 112_640,
+                optional: true,
+            },
+            activate: {
+                description: "Use the new dictionary for new chunks of this datastore.",
+                type: bool,
+                default: false,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+    },
+)]
+/// Train a zstd dictionary from the (unencrypted) chunks of a datastore.
+///
+/// Dictionaries improve the compression ratio for small chunks of similar
+/// data. Note that the dictionary contains parts of the sampled data, and
+/// can be downloaded by every user with access to the datastore.
+pub fn train_dictionary(
+    store: String,
+    max_samples: Option<u64>,
+    size: Option<u64>,
+    activate: bool,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
+    let max_samples = max_samples.unwrap_or(1000) as usize;
+    let size = size.unwrap_or(112_640) as usize;
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = WorkerTask::new_thread("zstd-dict-train", Some(store.clone()), auth_id, to_stdout, move |worker| {
+        let dict = datastore.train_zstd_dictionary(&worker, max_samples, size)?;
+
+        if activate {
+            let _lock = open_file_locked(datastore::DATASTORE_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+            let (mut config, _digest) = datastore::config()?;
+            let mut data: datastore::DataStoreConfig = config.lookup("datastore", &store)?;
+
+            let compression = data.get_compression()?;
+            data.compression = Some(match compression.level {
+                Some(level) => format!("level={},dictionary={}", level, dict.id_str()),
+                None => format!("dictionary={}", dict.id_str()),
+            });
+
+            config.set_data(&store, "datastore", &data)?;
+            datastore::save_config(&config)?;
+
+            worker.log(format!("activated dictionary {} on datastore '{}'", dict.id_str(), store));
+        }
+
+        Ok(())
+    })?;
+
+    Ok(json!(upid_str))
+}
+
+// Waits until all write operations on the datastore are finished
+fn wait_for_write_operations(worker: &WorkerTask, store: &str) -> Result<(), Error> {
+    let mut last_active = None;
+    loop {
+        let active = crate::backup::get_active_operations(store)?;
+        if active.write == 0 {
+            return Ok(());
+        }
+        if last_active != Some(active.write) {
+            worker.log(format!("waiting for {} write operations to finish", active.write));
+            last_active = Some(active.write);
+        }
+        worker.fail_on_abort()?;
+        std::thread::sleep(std::time::Duration::from_secs(1));
+    }
+}
+
+// Switches the path of a datastore and restores its maintenance mode
+fn switch_datastore_path(
+    store: &str,
+    old_path: &str,
+    new_path: &str,
+    mode: Option<String>,
+) -> Result<(), Error> {
+    let _lock = open_file_locked(datastore::DATASTORE_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, _digest) = datastore::config()?;
+    let mut data: datastore::DataStoreConfig = config.lookup("datastore", store)?;
+
+    switch_migrated_datastore(&mut data, old_path, new_path, mode)?;
+
+    config.set_data(store, "datastore", &data)?;
+    datastore::save_config(&config)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "target-path": {
+                schema: datastore::DIR_NAME_SCHEMA,
+                optional: true,
+            },
+            "target-store": {
+                schema: DATASTORE_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::And(&[
+            &Permission::Privilege(&["datastore", "{store}"], PRIV_DATASTORE_MODIFY, false),
+            &Permission::Privilege(&["datastore"], PRIV_DATASTORE_ALLOCATE, false),
+        ]),
+        description: "Merging into another datastore also requires Datastore.Modify on the target.",
+    },
+)]
+/// Copy the content of a datastore to a new path or into another datastore.
+///
+/// All chunks are verified while copying. Backups can continue during the
+/// first pass, the datastore is set read-only for a final pass afterwards.
+/// When migrating to a new path, the datastore then gets switched over to
+/// it, the old data is kept and can be removed manually.
+pub fn migrate_datastore(
+    store: String,
+    target_path: Option<String>,
+    target_store: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    let (config, _digest) = datastore::config()?;
+    let store_config: datastore::DataStoreConfig = config.lookup("datastore", &store)?;
+
+    if store_config.is_removable() {
+        bail!("cannot migrate datastore '{}' - located on a removable device", store);
+    }
+    if S3ChunkBackend::from_datastore_config(&store_config)?.is_some() {
+        bail!("cannot migrate datastore '{}' - chunks are stored in an S3 bucket", store);
+    }
+
+    match (&target_path, &target_store) {
+        (Some(path), None) => {
+            let path = std::path::Path::new(path);
+            let list: Vec<datastore::DataStoreConfig> = config.convert_to_typed_array("datastore")?;
+            check_migration_target_path(path, &list)?;
+            // allow continuing an aborted migration
+            if !path.join(".chunks").exists() {
+                if let Ok(mut entries) = std::fs::read_dir(path) {
+                    if entries.next().is_some() {
+                        bail!("target path {:?} is not empty", path);
+                    }
+                }
+            }
+        }
+        (None, Some(target)) => {
+            let user_info = CachedUserInfo::new()?;
+            user_info.check_privs(&auth_id, &["datastore", target], PRIV_DATASTORE_MODIFY, false)?;
+
+            let target_config: datastore::DataStoreConfig = config.lookup("datastore", target)?;
+            check_migration_target_store(&store_config, &target_config)?;
+            if S3ChunkBackend::from_datastore_config(&target_config)?.is_some() {
+                bail!("cannot migrate into datastore '{}' - chunks are stored in an S3 bucket", target);
+            }
+        }
+        _ => bail!("exactly one of 'target-path' or 'target-store' is required"),
+    }
+
+    let source = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let upid_str = WorkerTask::new_thread("datastore-migrate", Some(store.clone()), auth_id, to_stdout, move |worker| {
+        let target = match (&target_path, &target_store) {
+            (Some(path), _) => {
+                let path = std::path::Path::new(path);
+                if path.join(".chunks").exists() {
+                    worker.log(format!("continuing migration into existing chunk store at {:?}", path));
+                } else {
+                    worker.log(format!("creating chunk store at {:?}", path));
+                    let backup_user = crate::backup::backup_user()?;
+                    ChunkStore::create(&store, path, backup_user.uid, backup_user.gid)?;
+                }
+                DataStore::open_path(&store, path)?
+            }
+            (None, Some(target)) => {
+                worker.log(format!("merging datastore '{}' into datastore '{}'", store, target));
+                DataStore::lookup_datastore(target, Some(Operation::Write))?
+            }
+            (None, None) => unreachable!(),
+        };
+
+        let mut migrate_worker = MigrateWorker::new(worker.clone(), source, target, target_path.is_some())?;
+
+        worker.log("first pass, datastore stays writable");
+        migrate_worker.sync()?;
+
+        worker.log(format!("setting datastore '{}' read-only for the final pass", store));
+        let old_mode = replace_maintenance_mode(&store, Some("read-only".to_string()))?;
+
+        let result = proxmox::try_block!({
+            wait_for_write_operations(&worker, &store)?;
+
+            worker.log("final pass");
+            let errors = migrate_worker.sync()?;
+            if errors > 0 {
+                bail!("unable to copy {} groups or snapshots, see above", errors);
+            }
+
+            if let Some(ref path) = target_path {
+                switch_datastore_path(&store, &store_config.path, path, old_mode.clone())?;
+                worker.log(format!(
+                    "switched datastore '{}' to {:?}, old data at {:?} can be removed",
+                    store, path, store_config.path,
+                ));
+            }
+            Ok(())
+        });
+
+        if result.is_err() || target_path.is_none() {
+            if let Err(err) = replace_maintenance_mode(&store, old_mode) {
+                worker.warn(format!("could not restore maintenance mode - {}", err));
+            }
+        }
+
+        result
+    })?;
+
+    Ok(json!(upid_str))
+}
+
 #[sortable]
 const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
+    (
+        "active-operations",
+        &Router::new()
+            .get(&API_METHOD_GET_ACTIVE_OPERATIONS)
+    ),
     (
         "catalog",
         &Router::new()
@@ -1722,6 +2494,21 @@ const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
         "groups",
         &Router::new()
             .get(&API_METHOD_LIST_GROUPS)
+            .delete(&API_METHOD_DELETE_GROUP)
+    ),
+    (
+        "migrate",
+        &Router::new()
+            .post(&API_METHOD_MIGRATE_DATASTORE)
+    ),
+    (
+        "mount",
+        &Router::new()
+            .post(&API_METHOD_MOUNT)
+    ),
+    (
+        "namespace",
+        &super::namespace::ROUTER
     ),
     (
         "notes",
@@ -1729,6 +2516,12 @@ const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
             .get(&API_METHOD_GET_NOTES)
             .put(&API_METHOD_SET_NOTES)
     ),
+    (
+        "protected",
+        &Router::new()
+            .get(&API_METHOD_GET_PROTECTION)
+            .put(&API_METHOD_SET_PROTECTION)
+    ),
     (
         "prune",
         &Router::new()
@@ -1755,6 +2548,16 @@ const DATASTORE_INFO_SUBDIRS: SubdirMap = &[
         &Router::new()
             .get(&API_METHOD_STATUS)
     ),
+    (
+        "train-dictionary",
+        &Router::new()
+            .post(&API_METHOD_TRAIN_DICTIONARY)
+    ),
+    (
+        "unmount",
+        &Router::new()
+            .post(&API_METHOD_UNMOUNT)
+    ),
     (
         "upload-backup-log",
         &Router::new()
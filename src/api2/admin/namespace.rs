@@ -0,0 +1,186 @@
+//! Datastore Namespace Management
+
+use anyhow::{bail, Error};
+use serde_json::Value;
+
+use proxmox::api::{api, ApiMethod, Permission, Router, RpcEnvironment};
+
+use crate::api2::types::*;
+use crate::backup::{BackupNamespace, DataStore};
+use crate::config::cached_user_info::CachedUserInfo;
+use crate::config::acl::{
+    PRIV_DATASTORE_AUDIT,
+    PRIV_DATASTORE_BACKUP,
+    PRIV_DATASTORE_MODIFY,
+};
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            parent: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            name: {
+                schema: BACKUP_NS_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: {
+        type: NamespaceListItem,
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify or Datastore.Backup on \
+            /datastore/{store}[/{parent}].",
+    },
+)]
+/// Create a new datastore namespace.
+pub fn create_namespace(
+    store: String,
+    parent: Option<BackupNamespace>,
+    name: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<NamespaceListItem, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let parent = parent.unwrap_or_default();
+
+    let user_info = CachedUserInfo::new()?;
+    let privs = user_info.lookup_privs(&auth_id, &parent.acl_path(&store));
+    if privs & (PRIV_DATASTORE_MODIFY | PRIV_DATASTORE_BACKUP) == 0 {
+        bail!("no permissions on '/{}'", parent.acl_path(&store).join("/"));
+    }
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+
+    let ns = datastore.create_namespace(&parent, &name)?;
+
+    Ok(NamespaceListItem { ns })
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            parent: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        description: "List the namespaces of a datastore.",
+        type: Array,
+        items: { type: NamespaceListItem },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Only namespaces with Datastore.Audit or Datastore.Backup \
+            privileges are listed.",
+    },
+)]
+/// List the namespaces below `parent` (including `parent` itself).
+pub fn list_namespaces(
+    store: String,
+    parent: Option<BackupNamespace>,
+    max_depth: Option<usize>,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<NamespaceListItem>, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let parent = parent.unwrap_or_default();
+
+    let user_info = CachedUserInfo::new()?;
+    let required_privs = PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_BACKUP;
+
+    if !user_info.any_privs_below(&auth_id, &parent.acl_path(&store), required_privs) {
+        bail!("no permissions on '/{}'", parent.acl_path(&store).join("/"));
+    }
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
+
+    if !datastore.namespace_exists(&parent) {
+        bail!("namespace '{}' does not exist", parent);
+    }
+
+    let list = parent
+        .list_recursive(&datastore.base_path(), max_depth)?
+        .into_iter()
+        .filter(|ns| {
+            // keep the path to namespaces the user may access visible
+            user_info.any_privs_below(&auth_id, &ns.acl_path(&store), required_privs)
+        })
+        .map(|ns| NamespaceListItem { ns })
+        .collect();
+
+    Ok(list)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+            },
+            "delete-groups": {
+                description: "Also remove all backup groups contained in the namespaces.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the parent of the namespace.",
+    },
+)]
+/// Remove a namespace and all namespaces below it.
+pub fn delete_namespace(
+    store: String,
+    ns: BackupNamespace,
+    delete_groups: bool,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+
+    let parent = match ns.parent() {
+        Some(parent) => parent,
+        None => bail!("cannot remove the root namespace"),
+    };
+
+    let user_info = CachedUserInfo::new()?;
+    let privs = user_info.lookup_privs(&auth_id, &parent.acl_path(&store));
+    if privs & PRIV_DATASTORE_MODIFY == 0 {
+        bail!("no permissions on '/{}'", parent.acl_path(&store).join("/"));
+    }
+
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
+
+    if !datastore.namespace_exists(&ns) {
+        bail!("namespace '{}' does not exist", ns);
+    }
+
+    datastore.remove_namespace_recursive(&ns, delete_groups)?;
+
+    Ok(Value::Null)
+}
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_NAMESPACES)
+    .post(&API_METHOD_CREATE_NAMESPACE)
+    .delete(&API_METHOD_DELETE_NAMESPACE);
@@ -0,0 +1,148 @@
+//! Datastore Prune Job Management
+
+use anyhow::{format_err, Error};
+use serde_json::Value;
+
+use proxmox::api::router::SubdirMap;
+use proxmox::{list_subdirs_api_method, sortable};
+use proxmox::api::{api, ApiMethod, Permission, Router, RpcEnvironment};
+
+use crate::{
+    api2::types::{
+        DATASTORE_SCHEMA,
+        JOB_ID_SCHEMA,
+        Authid,
+    },
+    server::{
+        do_prune_job_config,
+        jobstate::{
+            Job,
+            JobState,
+            compute_schedule_status,
+        },
+    },
+    config::{
+        acl::{
+            PRIV_DATASTORE_AUDIT,
+            PRIV_DATASTORE_MODIFY,
+        },
+        cached_user_info::CachedUserInfo,
+        prune::{
+            self,
+            PruneJobConfig,
+            PruneJobStatus,
+        },
+    },
+};
+
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        description: "List configured jobs and their status (filtered by access)",
+        type: Array,
+        items: { type: prune::PruneJobStatus },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Modify on datastore.",
+    },
+)]
+/// List all prune jobs
+pub fn list_prune_jobs(
+    store: Option<String>,
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<PruneJobStatus>, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let required_privs = PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_MODIFY;
+
+    let (config, digest) = prune::config()?;
+
+    let job_config_iter = config
+        .convert_to_typed_array("prune")?
+        .into_iter()
+        .filter(|job: &PruneJobConfig| {
+            let ns = job.ns.clone().unwrap_or_default();
+            let privs = user_info.lookup_privs(&auth_id, &ns.acl_path(&job.store));
+            if privs & required_privs == 0 {
+                return false;
+            }
+
+            if let Some(store) = &store {
+                &job.store == store
+            } else {
+                true
+            }
+        });
+
+    let mut list = Vec::new();
+
+    for job in job_config_iter {
+        let last_state = JobState::load("prunejob", &job.id)
+            .map_err(|err| format_err!("could not open statefile for {}: {}", &job.id, err))?;
+
+        let status = compute_schedule_status(&last_state, job.schedule.as_deref())?;
+
+        list.push(PruneJobStatus { config: job, status });
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: JOB_ID_SCHEMA,
+            }
+        }
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on job's datastore.",
+    },
+)]
+/// Runs a prune job manually.
+pub fn run_prune_job(
+    id: String,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<String, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let (config, _digest) = prune::config()?;
+    let prune_job: PruneJobConfig = config.lookup("prune", &id)?;
+
+    let ns = prune_job.ns.clone().unwrap_or_default();
+    user_info.check_privs(&auth_id, &ns.acl_path(&prune_job.store), PRIV_DATASTORE_MODIFY, true)?;
+
+    let job = Job::new("prunejob", &id)?;
+
+    let upid_str = do_prune_job_config(job, prune_job, &auth_id, None)?;
+
+    Ok(upid_str)
+}
+
+#[sortable]
+const PRUNE_INFO_SUBDIRS: SubdirMap = &[("run", &Router::new().post(&API_METHOD_RUN_PRUNE_JOB))];
+
+const PRUNE_INFO_ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(PRUNE_INFO_SUBDIRS))
+    .subdirs(PRUNE_INFO_SUBDIRS);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_PRUNE_JOBS)
+    .match_all("id", &PRUNE_INFO_ROUTER);
@@ -2,7 +2,7 @@
 
 use anyhow::{bail, format_err, Error};
 use futures::*;
-use hyper::header::{HeaderValue, UPGRADE};
+use hyper::header::{self, HeaderValue, UPGRADE};
 use hyper::http::request::Parts;
 use hyper::{Body, Response, Request, StatusCode};
 use serde_json::{json, Value};
@@ -36,6 +36,7 @@ pub const API_METHOD_UPGRADE_BACKUP: ApiMethod = ApiMethod::new(
         concat!("Upgraded to backup protocol ('", PROXMOX_BACKUP_PROTOCOL_ID_V1!(), "')."),
         &sorted!([
             ("store", false, &DATASTORE_SCHEMA),
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("backup-type", false, &BACKUP_TYPE_SCHEMA),
             ("backup-id", false, &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
@@ -45,7 +46,7 @@ pub const API_METHOD_UPGRADE_BACKUP: ApiMethod = ApiMethod::new(
     )
 ).access(
     // Note: parameter 'store' is no uri parameter, so we need to test inside function body
-    Some("The user needs Datastore.Backup privilege on /datastore/{store} and needs to own the backup group."),
+    Some("The user needs Datastore.Backup privilege on /datastore/{store}[/{namespace}] and needs to own the backup group."),
     &Permission::Anybody
 );
 
@@ -64,11 +65,12 @@ async move {
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
 
     let store = tools::required_string_param(&param, "store")?.to_owned();
+    let backup_ns = BackupNamespace::from_opt(param["ns"].as_str())?;
 
     let user_info = CachedUserInfo::new()?;
-    user_info.check_privs(&auth_id, &["datastore", &store], PRIV_DATASTORE_BACKUP, false)?;
+    user_info.check_privs(&auth_id, &backup_ns.acl_path(&store), PRIV_DATASTORE_BACKUP, false)?;
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
 
     let backup_type = tools::required_string_param(&param, "backup-type")?;
     let backup_id = tools::required_string_param(&param, "backup-id")?;
@@ -88,11 +90,11 @@ async move {
         bail!("unexpected http version '{:?}' (expected version < 2)", parts.version);
     }
 
-    let worker_id = format!("{}:{}/{}", store, backup_type, backup_id);
-
     let env_type = rpcenv.env_type();
 
-    let backup_group = BackupGroup::new(backup_type, backup_id);
+    let backup_group = BackupGroup::with_ns(backup_ns, backup_type, backup_id);
+
+    let worker_id = format!("{}:{}", store, backup_group);
 
     let worker_type = if backup_type == "host" && backup_id == "benchmark" {
         if !benchmark {
@@ -106,6 +108,13 @@ async move {
         "backup"
     };
 
+    // usage is accounted to the user, even for backups owned by API tokens
+    let quota_warnings = if worker_type != "benchmark" {
+        check_backup_quota(&datastore, auth_id.user())?
+    } else {
+        Vec::new()
+    };
+
     // lock backup group to only allow one backup per group at a time
     let (owner, _group_guard) = datastore.create_locked_backup_group(&backup_group, &auth_id)?;
 
@@ -167,6 +176,10 @@ async move {
 
         env.log(format!("starting new {} on datastore '{}': {:?}", worker_type, store, path));
 
+        for warning in quota_warnings {
+            worker.warn(warning);
+        }
+
         let service = H2Service::new(env.clone(), worker.clone(), &BACKUP_API_ROUTER, debug);
 
         let abort_future = worker.abort_future();
@@ -273,6 +286,19 @@ const BACKUP_API_SUBDIRS: SubdirMap = &[
         "blob", &Router::new()
             .upload(&API_METHOD_UPLOAD_BLOB)
     ),
+    (
+        "compression", &Router::new()
+            .get(&API_METHOD_GET_CHUNK_COMPRESSION)
+    ),
+    (
+        "dictionaries", &Router::new()
+            .get(&API_METHOD_LIST_DICTIONARIES)
+    ),
+    (
+        "dictionary", &Router::new()
+            .download(&API_METHOD_DOWNLOAD_DICTIONARY)
+            .upload(&API_METHOD_UPLOAD_DICTIONARY)
+    ),
     (
         "dynamic_chunk", &Router::new()
             .upload(&API_METHOD_UPLOAD_DYNAMIC_CHUNK)
@@ -780,3 +806,84 @@ fn download_previous(
         crate::api2::helpers::create_download_response(path).await
     }.boxed()
 }
+
+#[sortable]
+pub const API_METHOD_GET_CHUNK_COMPRESSION: ApiMethod = ApiMethod::new(
+    &ApiHandler::Sync(&get_chunk_compression),
+    &ObjectSchema::new(
+        "Get the chunk compression settings of the datastore.",
+        &[],
+    )
+);
+
+fn get_chunk_compression(
+    _param: Value,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+
+    let env: &BackupEnvironment = rpcenv.as_ref();
+
+    Ok(serde_json::to_value(env.datastore.chunk_compression())?)
+}
+
+#[sortable]
+pub const API_METHOD_LIST_DICTIONARIES: ApiMethod = ApiMethod::new(
+    &ApiHandler::Sync(&list_dictionaries),
+    &ObjectSchema::new(
+        "List the IDs of the zstd dictionaries stored on the datastore.",
+        &[],
+    )
+);
+
+fn list_dictionaries(
+    _param: Value,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Value, Error> {
+
+    let env: &BackupEnvironment = rpcenv.as_ref();
+
+    let list: Vec<String> = env.datastore
+        .list_zstd_dictionaries()?
+        .iter()
+        .map(|dict| dict.id_str())
+        .collect();
+
+    Ok(list.into())
+}
+
+#[sortable]
+pub const API_METHOD_DOWNLOAD_DICTIONARY: ApiMethod = ApiMethod::new(
+    &ApiHandler::AsyncHttp(&download_dictionary),
+    &ObjectSchema::new(
+        "Download a zstd dictionary used for chunk compression.",
+        &sorted!([
+            ("id", false, &ZSTD_DICTIONARY_ID_SCHEMA),
+        ]),
+    )
+);
+
+fn download_dictionary(
+    _parts: Parts,
+    _req_body: Body,
+    param: Value,
+    _info: &ApiMethod,
+    rpcenv: Box<dyn RpcEnvironment>,
+) -> ApiResponseFuture {
+
+    async move {
+        let env: &BackupEnvironment = rpcenv.as_ref();
+
+        let id = parse_dictionary_id(tools::required_string_param(&param, "id")?)?;
+        let dict = env.datastore.load_zstd_dictionary(&id)?;
+
+        env.debug(format!("download zstd dictionary {}", dict.id_str()));
+
+        Ok(Response::builder()
+           .status(StatusCode::OK)
+           .header(header::CONTENT_TYPE, "application/octet-stream")
+           .body(Body::from(dict.data().to_vec()))
+           .unwrap())
+    }.boxed()
+}
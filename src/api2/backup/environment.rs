@@ -13,6 +13,7 @@ use proxmox::api::{RpcEnvironment, RpcEnvironmentType};
 use crate::api2::types::Authid;
 use crate::backup::*;
 use crate::server::WorkerTask;
+use crate::server::metric_server::{send_task_metrics, MetricDataPoint};
 use crate::server::formatter::*;
 use hyper::{Body, Response};
 
@@ -494,6 +495,22 @@ impl BackupEnvironment {
         // marks the backup as successful
         state.finished = true;
 
+        let duration = proxmox::tools::time::epoch_i64() - self.worker.upid().starttime;
+
+        let mut point = MetricDataPoint::new("backup")
+            .tag("datastore", self.datastore.name())
+            .tag("backup-type", self.backup_dir.group().backup_type());
+        point.add_field("duration", duration as f64);
+        point.add_field("size", state.backup_size as f64);
+        point.add_field("chunks", state.backup_stat.count as f64);
+        point.add_field("uploaded", state.backup_stat.size as f64);
+        point.add_field("uploaded_compressed", state.backup_stat.compressed_size as f64);
+        point.add_field("duplicates", state.backup_stat.duplicates as f64);
+        if duration > 0 {
+            point.add_field("throughput", state.backup_size as f64 / duration as f64);
+        }
+        send_task_metrics(vec![point]);
+
         Ok(())
     }
 
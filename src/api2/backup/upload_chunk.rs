@@ -289,3 +289,52 @@ fn upload_blob(
         Ok(env.format_response(Ok(Value::Null)))
     }.boxed()
 }
+
+#[sortable]
+pub const API_METHOD_UPLOAD_DICTIONARY: ApiMethod = ApiMethod::new(
+    &ApiHandler::AsyncHttp(&upload_dictionary),
+    &ObjectSchema::new(
+        "Upload a zstd dictionary needed to decompress chunks.",
+        &sorted!([
+            ("id", false, &ZSTD_DICTIONARY_ID_SCHEMA),
+        ]),
+    )
+);
+
+fn upload_dictionary(
+    _parts: Parts,
+    req_body: Body,
+    param: Value,
+    _info: &ApiMethod,
+    rpcenv: Box<dyn RpcEnvironment>,
+) -> ApiResponseFuture {
+
+    async move {
+        let id = parse_dictionary_id(tools::required_string_param(&param, "id")?)?;
+
+        let env: &BackupEnvironment = rpcenv.as_ref();
+
+        let data = req_body
+            .map_err(Error::from)
+            .try_fold(Vec::new(), |mut acc, chunk| {
+                acc.extend_from_slice(&*chunk);
+                if acc.len() > MAX_DICTIONARY_SIZE {
+                    return future::err(format_err!("zstd dictionary too large"));
+                }
+                future::ok::<_, Error>(acc)
+            })
+            .await?;
+
+        let dict = ZstdDictionary::new(data);
+        if dict.id() != &id {
+            bail!("uploaded zstd dictionary has wrong checksum");
+        }
+
+        env.datastore.insert_zstd_dictionary(&dict)?;
+        let dict = register_zstd_dictionary(dict);
+
+        env.debug(format!("upload zstd dictionary {}", dict.id_str()));
+
+        Ok(env.format_response(Ok(Value::Null)))
+    }.boxed()
+}
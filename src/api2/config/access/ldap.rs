@@ -0,0 +1,456 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, ApiMethod, Router, RpcEnvironment, Permission};
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::domains::{
+    self,
+    LdapMode,
+    LdapRealmConfig,
+    LDAP_ATTRIBUTE_SCHEMA,
+    LDAP_DN_SCHEMA,
+    LDAP_FILTER_SCHEMA,
+    LDAP_PASSWORD_SCHEMA,
+    REALM_SYNC_SCHEDULE_SCHEMA,
+    REMOVE_VANISHED_USERS_SCHEMA,
+};
+use crate::config::acl::{PRIV_PERMISSIONS_MODIFY, PRIV_SYS_AUDIT};
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "The list of configured LDAP realms (with config digest).",
+        type: Array,
+        items: { type: LdapRealmConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List LDAP realms
+pub fn list_ldap_realms(
+    _param: Value,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<LdapRealmConfig>, Error> {
+    let (config, digest) = domains::config()?;
+
+    let mut list: Vec<LdapRealmConfig> = config.convert_to_typed_array("ldap")?;
+    // don't return bind passwords
+    for realm in &mut list {
+        realm.password = "".to_string();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            server1: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            server2: {
+                optional: true,
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "The (optional) port.",
+                type: u16,
+                optional: true,
+            },
+            mode: {
+                optional: true,
+                type: LdapMode,
+            },
+            verify: {
+                optional: true,
+                description: "Verify the server certificate.",
+                type: bool,
+            },
+            fingerprint: {
+                optional: true,
+                schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+            },
+            "base-dn": {
+                schema: LDAP_DN_SCHEMA,
+            },
+            "user-attr": {
+                schema: LDAP_ATTRIBUTE_SCHEMA,
+            },
+            "bind-dn": {
+                optional: true,
+                schema: LDAP_DN_SCHEMA,
+            },
+            password: {
+                optional: true,
+                schema: LDAP_PASSWORD_SCHEMA,
+            },
+            filter: {
+                optional: true,
+                schema: LDAP_FILTER_SCHEMA,
+            },
+            "group-dn": {
+                optional: true,
+                schema: LDAP_DN_SCHEMA,
+            },
+            "group-filter": {
+                optional: true,
+                schema: LDAP_FILTER_SCHEMA,
+            },
+            "group-name-attr": {
+                optional: true,
+                schema: LDAP_ATTRIBUTE_SCHEMA,
+            },
+            "sync-schedule": {
+                optional: true,
+                schema: REALM_SYNC_SCHEDULE_SCHEMA,
+            },
+            "remove-vanished": {
+                optional: true,
+                schema: REMOVE_VANISHED_USERS_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Create a new LDAP realm.
+pub fn create_ldap_realm(password: Option<String>, param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(domains::DOMAINS_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let mut data = param;
+    if let Some(password) = password {
+        data["password"] = Value::from(base64::encode(password.as_bytes()));
+    }
+    let realm: LdapRealmConfig = serde_json::from_value(data)?;
+
+    if realm.realm == "pam" || realm.realm == "pbs" {
+        bail!("realm '{}' is reserved for built-in authentication", realm.realm);
+    }
+
+    let (mut config, _digest) = domains::config()?;
+
+    if config.sections.get(&realm.realm).is_some() {
+        bail!("realm '{}' already exists.", realm.realm);
+    }
+
+    config.set_data(&realm.realm, "ldap", &realm)?;
+
+    domains::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+        },
+    },
+    returns: { type: LdapRealmConfig },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_SYS_AUDIT, false),
+    }
+)]
+/// Read LDAP realm configuration.
+pub fn read_ldap_realm(
+    realm: String,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<LdapRealmConfig, Error> {
+    let (config, digest) = domains::config()?;
+    let mut data: LdapRealmConfig = config.lookup("ldap", &realm)?;
+    data.password = "".to_string(); // do not return the bind password in api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the comment property.
+    comment,
+    /// Delete the server2 property.
+    server2,
+    /// Delete the port property.
+    port,
+    /// Delete the mode property.
+    mode,
+    /// Delete the verify property.
+    verify,
+    /// Delete the fingerprint property.
+    fingerprint,
+    /// Delete the bind-dn and password properties.
+    bind_dn,
+    /// Delete the filter property.
+    filter,
+    /// Delete the group-dn property.
+    group_dn,
+    /// Delete the group-filter property.
+    group_filter,
+    /// Delete the group-name-attr property.
+    group_name_attr,
+    /// Delete the sync-schedule property.
+    sync_schedule,
+    /// Delete the remove-vanished property.
+    remove_vanished,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            server1: {
+                optional: true,
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            server2: {
+                optional: true,
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "The (optional) port.",
+                type: u16,
+                optional: true,
+            },
+            mode: {
+                optional: true,
+                type: LdapMode,
+            },
+            verify: {
+                optional: true,
+                description: "Verify the server certificate.",
+                type: bool,
+            },
+            fingerprint: {
+                optional: true,
+                schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+            },
+            "base-dn": {
+                optional: true,
+                schema: LDAP_DN_SCHEMA,
+            },
+            "user-attr": {
+                optional: true,
+                schema: LDAP_ATTRIBUTE_SCHEMA,
+            },
+            "bind-dn": {
+                optional: true,
+                schema: LDAP_DN_SCHEMA,
+            },
+            password: {
+                optional: true,
+                schema: LDAP_PASSWORD_SCHEMA,
+            },
+            filter: {
+                optional: true,
+                schema: LDAP_FILTER_SCHEMA,
+            },
+            "group-dn": {
+                optional: true,
+                schema: LDAP_DN_SCHEMA,
+            },
+            "group-filter": {
+                optional: true,
+                schema: LDAP_FILTER_SCHEMA,
+            },
+            "group-name-attr": {
+                optional: true,
+                schema: LDAP_ATTRIBUTE_SCHEMA,
+            },
+            "sync-schedule": {
+                optional: true,
+                schema: REALM_SYNC_SCHEDULE_SCHEMA,
+            },
+            "remove-vanished": {
+                optional: true,
+                schema: REMOVE_VANISHED_USERS_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Update LDAP realm configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_ldap_realm(
+    realm: String,
+    comment: Option<String>,
+    server1: Option<String>,
+    server2: Option<String>,
+    port: Option<u16>,
+    mode: Option<LdapMode>,
+    verify: Option<bool>,
+    fingerprint: Option<String>,
+    base_dn: Option<String>,
+    user_attr: Option<String>,
+    bind_dn: Option<String>,
+    password: Option<String>,
+    filter: Option<String>,
+    group_dn: Option<String>,
+    group_filter: Option<String>,
+    group_name_attr: Option<String>,
+    sync_schedule: Option<String>,
+    remove_vanished: Option<bool>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(domains::DOMAINS_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = domains::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: LdapRealmConfig = config.lookup("ldap", &realm)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::comment => { data.comment = None; },
+                DeletableProperty::server2 => { data.server2 = None; },
+                DeletableProperty::port => { data.port = None; },
+                DeletableProperty::mode => { data.mode = None; },
+                DeletableProperty::verify => { data.verify = None; },
+                DeletableProperty::fingerprint => { data.fingerprint = None; },
+                DeletableProperty::bind_dn => {
+                    data.bind_dn = None;
+                    data.password = String::new();
+                },
+                DeletableProperty::filter => { data.filter = None; },
+                DeletableProperty::group_dn => { data.group_dn = None; },
+                DeletableProperty::group_filter => { data.group_filter = None; },
+                DeletableProperty::group_name_attr => { data.group_name_attr = None; },
+                DeletableProperty::sync_schedule => { data.sync_schedule = None; },
+                DeletableProperty::remove_vanished => { data.remove_vanished = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(server1) = server1 { data.server1 = server1; }
+    if server2.is_some() { data.server2 = server2; }
+    if port.is_some() { data.port = port; }
+    if mode.is_some() { data.mode = mode; }
+    if verify.is_some() { data.verify = verify; }
+    if fingerprint.is_some() { data.fingerprint = fingerprint; }
+    if let Some(base_dn) = base_dn { data.base_dn = base_dn; }
+    if let Some(user_attr) = user_attr { data.user_attr = user_attr; }
+    if bind_dn.is_some() { data.bind_dn = bind_dn; }
+    if let Some(password) = password { data.password = password; }
+    if filter.is_some() { data.filter = filter; }
+    if group_dn.is_some() { data.group_dn = group_dn; }
+    if group_filter.is_some() { data.group_filter = group_filter; }
+    if group_name_attr.is_some() { data.group_name_attr = group_name_attr; }
+    if sync_schedule.is_some() { data.sync_schedule = sync_schedule; }
+    if remove_vanished.is_some() { data.remove_vanished = remove_vanished; }
+
+    config.set_data(&realm, "ldap", &data)?;
+
+    domains::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Remove a LDAP realm.
+pub fn delete_ldap_realm(realm: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = open_file_locked(domains::DOMAINS_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = domains::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&realm) {
+        Some((section_type, _)) if section_type == "ldap" => { config.sections.remove(&realm); },
+        _ => bail!("LDAP realm '{}' does not exist.", realm),
+    }
+
+    domains::save_config(&config)?;
+
+    crate::server::jobstate::remove_state_file("realm-sync", &realm)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_LDAP_REALM)
+    .put(&API_METHOD_UPDATE_LDAP_REALM)
+    .delete(&API_METHOD_DELETE_LDAP_REALM);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_LDAP_REALMS)
+    .post(&API_METHOD_CREATE_LDAP_REALM)
+    .match_all("realm", &ITEM_ROUTER);
@@ -1,9 +1,15 @@
 use proxmox::api::{Router, SubdirMap};
 use proxmox::list_subdirs_api_method;
 
+pub mod ldap;
+pub mod openid;
 pub mod tfa;
 
-const SUBDIRS: SubdirMap = &[("tfa", &tfa::ROUTER)];
+const SUBDIRS: SubdirMap = &[
+    ("ldap", &ldap::ROUTER),
+    ("openid", &openid::ROUTER),
+    ("tfa", &tfa::ROUTER),
+];
 
 pub const ROUTER: Router = Router::new()
     .get(&list_subdirs_api_method!(SUBDIRS))
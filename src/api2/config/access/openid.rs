@@ -0,0 +1,323 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, ApiMethod, Router, RpcEnvironment, Permission};
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::domains::{
+    self,
+    OpenIdRealmConfig,
+    OpenIdUsernameClaim,
+    OPENID_AUTOCREATE_SCHEMA,
+    OPENID_CLIENT_ID_SCHEMA,
+    OPENID_CLIENT_KEY_SCHEMA,
+    OPENID_ISSUER_URL_SCHEMA,
+    OPENID_SCOPES_SCHEMA,
+};
+use crate::config::acl::{PRIV_PERMISSIONS_MODIFY, PRIV_SYS_AUDIT};
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "The list of configured OpenID realms (with config digest).",
+        type: Array,
+        items: { type: OpenIdRealmConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List OpenID realms
+pub fn list_openid_realms(
+    _param: Value,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<OpenIdRealmConfig>, Error> {
+    let (config, digest) = domains::config()?;
+
+    let mut list: Vec<OpenIdRealmConfig> = config.convert_to_typed_array("openid")?;
+    // don't return client secrets
+    for realm in &mut list {
+        realm.client_key = "".to_string();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            "issuer-url": {
+                schema: OPENID_ISSUER_URL_SCHEMA,
+            },
+            "client-id": {
+                schema: OPENID_CLIENT_ID_SCHEMA,
+            },
+            "client-key": {
+                optional: true,
+                schema: OPENID_CLIENT_KEY_SCHEMA,
+            },
+            scopes: {
+                optional: true,
+                schema: OPENID_SCOPES_SCHEMA,
+            },
+            "username-claim": {
+                optional: true,
+                type: OpenIdUsernameClaim,
+            },
+            autocreate: {
+                optional: true,
+                schema: OPENID_AUTOCREATE_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Create a new OpenID realm.
+pub fn create_openid_realm(client_key: Option<String>, param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(domains::DOMAINS_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let mut data = param;
+    if let Some(client_key) = client_key {
+        data["client-key"] = Value::from(base64::encode(client_key.as_bytes()));
+    }
+    let realm: OpenIdRealmConfig = serde_json::from_value(data)?;
+
+    if realm.realm == "pam" || realm.realm == "pbs" {
+        bail!("realm '{}' is reserved for built-in authentication", realm.realm);
+    }
+
+    let (mut config, _digest) = domains::config()?;
+
+    if config.sections.get(&realm.realm).is_some() {
+        bail!("realm '{}' already exists.", realm.realm);
+    }
+
+    config.set_data(&realm.realm, "openid", &realm)?;
+
+    domains::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+        },
+    },
+    returns: { type: OpenIdRealmConfig },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_SYS_AUDIT, false),
+    }
+)]
+/// Read OpenID realm configuration.
+pub fn read_openid_realm(
+    realm: String,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<OpenIdRealmConfig, Error> {
+    let (config, digest) = domains::config()?;
+    let mut data: OpenIdRealmConfig = config.lookup("openid", &realm)?;
+    data.client_key = "".to_string(); // do not return the client secret in api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+#[serde(rename_all = "kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the comment property.
+    comment,
+    /// Delete the client-key property.
+    client_key,
+    /// Delete the scopes property.
+    scopes,
+    /// Delete the username-claim property.
+    username_claim,
+    /// Delete the autocreate property.
+    autocreate,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            "issuer-url": {
+                optional: true,
+                schema: OPENID_ISSUER_URL_SCHEMA,
+            },
+            "client-id": {
+                optional: true,
+                schema: OPENID_CLIENT_ID_SCHEMA,
+            },
+            "client-key": {
+                optional: true,
+                schema: OPENID_CLIENT_KEY_SCHEMA,
+            },
+            scopes: {
+                optional: true,
+                schema: OPENID_SCOPES_SCHEMA,
+            },
+            "username-claim": {
+                optional: true,
+                type: OpenIdUsernameClaim,
+            },
+            autocreate: {
+                optional: true,
+                schema: OPENID_AUTOCREATE_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Update OpenID realm configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_openid_realm(
+    realm: String,
+    comment: Option<String>,
+    issuer_url: Option<String>,
+    client_id: Option<String>,
+    client_key: Option<String>,
+    scopes: Option<String>,
+    username_claim: Option<OpenIdUsernameClaim>,
+    autocreate: Option<bool>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(domains::DOMAINS_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = domains::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: OpenIdRealmConfig = config.lookup("openid", &realm)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::comment => { data.comment = None; },
+                DeletableProperty::client_key => { data.client_key = String::new(); },
+                DeletableProperty::scopes => { data.scopes = None; },
+                DeletableProperty::username_claim => { data.username_claim = None; },
+                DeletableProperty::autocreate => { data.autocreate = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(issuer_url) = issuer_url { data.issuer_url = issuer_url; }
+    if let Some(client_id) = client_id { data.client_id = client_id; }
+    if let Some(client_key) = client_key { data.client_key = client_key; }
+    if scopes.is_some() { data.scopes = scopes; }
+    if username_claim.is_some() { data.username_claim = username_claim; }
+    if autocreate.is_some() { data.autocreate = autocreate; }
+
+    config.set_data(&realm, "openid", &data)?;
+
+    domains::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            realm: {
+                schema: PROXMOX_AUTH_REALM_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["access", "domains"], PRIV_PERMISSIONS_MODIFY, false),
+    },
+)]
+/// Remove an OpenID realm.
+pub fn delete_openid_realm(realm: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = open_file_locked(domains::DOMAINS_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = domains::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&realm) {
+        Some((section_type, _)) if section_type == "openid" => { config.sections.remove(&realm); },
+        _ => bail!("OpenID realm '{}' does not exist.", realm),
+    }
+
+    domains::save_config(&config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_OPENID_REALM)
+    .put(&API_METHOD_UPDATE_OPENID_REALM)
+    .delete(&API_METHOD_DELETE_OPENID_REALM);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_OPENID_REALMS)
+    .post(&API_METHOD_CREATE_OPENID_REALM)
+    .match_all("realm", &ITEM_ROUTER);
@@ -0,0 +1,798 @@
+use anyhow::{bail, format_err, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, Permission, Router, RpcEnvironment};
+use proxmox::api::router::SubdirMap;
+use proxmox::list_subdirs_api_method;
+
+use crate::api2::types::*;
+use crate::config::acl::{PRIV_SYS_AUDIT, PRIV_SYS_MODIFY};
+use crate::config::acme::{
+    self,
+    AcmeDomain,
+    DnsPlugin,
+    ACME_ACCOUNT_NAME_SCHEMA,
+    ACME_CONTACT_LIST_SCHEMA,
+    ACME_DIRECTORY_URL_SCHEMA,
+    ACME_DNS_DATA_SCHEMA,
+    ACME_DNS_HOOK_SCHEMA,
+    ACME_DOMAIN_ALIAS_SCHEMA,
+    ACME_DOMAIN_SCHEMA,
+    ACME_PLUGIN_ID_SCHEMA,
+    ACME_VALIDATION_DELAY_SCHEMA,
+    DEFAULT_ACME_ACCOUNT_NAME,
+};
+use crate::tools::acme::{
+    AcmeClient,
+    LETSENCRYPT_DIRECTORY,
+    LETSENCRYPT_STAGING_DIRECTORY,
+};
+
+#[api(
+    properties: {
+        name: {
+            schema: ACME_ACCOUNT_NAME_SCHEMA,
+        },
+        directory: {
+            schema: ACME_DIRECTORY_URL_SCHEMA,
+        },
+        location: {
+            description: "Account URL.",
+            type: String,
+        },
+        contact: {
+            description: "Contact email addresses.",
+            type: Array,
+            items: {
+                description: "Contact URL.",
+                type: String,
+            },
+        },
+        tos: {
+            description: "URL of the accepted terms of service.",
+            type: String,
+            optional: true,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// ACME account information (without the account key)
+pub struct AcmeAccountInfo {
+    pub name: String,
+    pub directory: String,
+    pub location: String,
+    pub contact: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tos: Option<String>,
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+/// A known ACME directory
+pub struct KnownAcmeDirectory {
+    /// Name of the certificate authority
+    pub name: String,
+    /// Directory URL
+    pub url: String,
+}
+
+const KNOWN_ACME_DIRECTORIES: &[(&str, &str)] = &[
+    ("Let's Encrypt V2", LETSENCRYPT_DIRECTORY),
+    ("Let's Encrypt V2 Staging", LETSENCRYPT_STAGING_DIRECTORY),
+];
+
+#[api(
+    returns: {
+        description: "List of known ACME directories.",
+        type: Array,
+        items: { type: KnownAcmeDirectory },
+    },
+    access: {
+        permission: &Permission::Anybody,
+    },
+)]
+/// List known ACME directories
+pub fn list_directories() -> Result<Vec<KnownAcmeDirectory>, Error> {
+    Ok(KNOWN_ACME_DIRECTORIES.iter()
+        .map(|(name, url)| KnownAcmeDirectory { name: name.to_string(), url: url.to_string() })
+        .collect())
+}
+
+#[api(
+    input: {
+        properties: {
+            directory: {
+                schema: ACME_DIRECTORY_URL_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        description: "The terms of service URL, if the certificate authority has one.",
+        type: String,
+        optional: true,
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Get the terms of service URL of an ACME directory (default: Let's Encrypt)
+pub async fn get_tos(directory: Option<String>) -> Result<Option<String>, Error> {
+    let directory = directory.unwrap_or_else(|| LETSENCRYPT_DIRECTORY.to_string());
+    AcmeClient::new(directory).terms_of_service_url().await
+}
+
+#[api(
+    returns: {
+        description: "List of ACME account names.",
+        type: Array,
+        items: { schema: ACME_ACCOUNT_NAME_SCHEMA },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List ACME accounts
+pub fn list_accounts() -> Result<Vec<String>, Error> {
+    acme::list_accounts()
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+                optional: true,
+            },
+            contact: {
+                schema: ACME_CONTACT_LIST_SCHEMA,
+            },
+            directory: {
+                schema: ACME_DIRECTORY_URL_SCHEMA,
+                optional: true,
+            },
+            "tos-url": {
+                description: "URL of the terms of service the user agreed to. Required if the \
+                    certificate authority has terms of service.",
+                type: String,
+                optional: true,
+            },
+        },
+    },
+    returns: { type: AcmeAccountInfo },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Register a new ACME account (default directory: Let's Encrypt)
+pub async fn register_account(
+    name: Option<String>,
+    contact: String,
+    directory: Option<String>,
+    tos_url: Option<String>,
+) -> Result<AcmeAccountInfo, Error> {
+    let name = name.unwrap_or_else(|| DEFAULT_ACME_ACCOUNT_NAME.to_string());
+    let directory = directory.unwrap_or_else(|| LETSENCRYPT_DIRECTORY.to_string());
+
+    let contact: Vec<String> = contact.split(',')
+        .map(|mail| mail.trim())
+        .filter(|mail| !mail.is_empty())
+        .map(|mail| format!("mailto:{}", mail))
+        .collect();
+    if contact.is_empty() {
+        bail!("at least one contact email address is required");
+    }
+
+    let _lock = acme::lock_config()?;
+
+    if acme::list_accounts()?.contains(&name) {
+        bail!("ACME account '{}' already exists", name);
+    }
+
+    let mut client = AcmeClient::new(directory);
+
+    if let Some(tos) = client.terms_of_service_url().await? {
+        if tos_url.as_deref() != Some(tos.as_str()) {
+            bail!("the terms of service '{}' need to be accepted (tos-url)", tos);
+        }
+    }
+
+    let account = client.new_account(contact, tos_url).await?;
+    acme::save_account(&name, &account)?;
+
+    Ok(AcmeAccountInfo {
+        name,
+        directory: account.directory,
+        location: account.location,
+        contact: account.contact,
+        tos: account.tos,
+    })
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: { type: AcmeAccountInfo },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Get ACME account information
+pub fn get_account(name: String) -> Result<AcmeAccountInfo, Error> {
+    let account = acme::load_account(&name)?;
+
+    Ok(AcmeAccountInfo {
+        name,
+        directory: account.directory,
+        location: account.location,
+        contact: account.contact,
+        tos: account.tos,
+    })
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+            },
+            force: {
+                description: "Remove the local account even if the deactivation at the \
+                    certificate authority fails.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Deactivate an ACME account and remove it
+pub async fn deactivate_account(name: String, force: bool) -> Result<(), Error> {
+    let _lock = acme::lock_config()?;
+
+    let account = acme::load_account(&name)?;
+
+    let result = match AcmeClient::with_account(&account) {
+        Ok(mut client) => client.deactivate_account().await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = result {
+        if !force {
+            bail!("unable to deactivate ACME account '{}' - {}", name, err);
+        }
+        log::warn!("unable to deactivate ACME account '{}', removing it anyway - {}", name, err);
+    }
+
+    acme::remove_account(&name)
+}
+
+#[api(
+    returns: {
+        description: "List of configured DNS challenge plugins.",
+        type: Array,
+        items: { type: DnsPlugin },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List DNS challenge plugins
+pub fn list_plugins(mut rpcenv: &mut dyn RpcEnvironment) -> Result<Vec<DnsPlugin>, Error> {
+    let (config, digest) = acme::plugin_config()?;
+
+    let mut list: Vec<DnsPlugin> = config.convert_to_typed_array("dns")?;
+    // the plugin data usually contains credentials
+    for plugin in &mut list {
+        plugin.data = None;
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(list)
+}
+
+fn check_hook(hook: &str) -> Result<(), Error> {
+    if !hook.starts_with('/') {
+        bail!("hook '{}' is not an absolute path", hook);
+    }
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: ACME_PLUGIN_ID_SCHEMA,
+            },
+            hook: {
+                schema: ACME_DNS_HOOK_SCHEMA,
+            },
+            data: {
+                optional: true,
+                schema: ACME_DNS_DATA_SCHEMA,
+            },
+            "validation-delay": {
+                optional: true,
+                schema: ACME_VALIDATION_DELAY_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Add a DNS challenge plugin
+pub fn add_plugin(param: Value) -> Result<(), Error> {
+    let plugin: DnsPlugin = serde_json::from_value(param)?;
+
+    check_hook(&plugin.hook)?;
+    plugin.environment()?;
+
+    let _lock = acme::lock_config()?;
+
+    let (mut config, _digest) = acme::plugin_config()?;
+
+    if config.sections.get(&plugin.id).is_some() {
+        bail!("ACME plugin '{}' already exists.", plugin.id);
+    }
+
+    config.set_data(&plugin.id, "dns", &plugin)?;
+
+    acme::save_plugin_config(&config)
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: ACME_PLUGIN_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: DnsPlugin },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read a DNS challenge plugin (without the plugin data)
+pub fn read_plugin(id: String, mut rpcenv: &mut dyn RpcEnvironment) -> Result<DnsPlugin, Error> {
+    let (config, digest) = acme::plugin_config()?;
+
+    let mut plugin: DnsPlugin = config.lookup("dns", &id)?;
+    plugin.data = None;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(plugin)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+/// Deletable property name
+pub enum DeletablePluginProperty {
+    /// Delete the plugin data.
+    Data,
+    /// Delete the validation delay (reset to default).
+    ValidationDelay,
+    /// Delete the comment property.
+    Comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: ACME_PLUGIN_ID_SCHEMA,
+            },
+            hook: {
+                optional: true,
+                schema: ACME_DNS_HOOK_SCHEMA,
+            },
+            data: {
+                optional: true,
+                schema: ACME_DNS_DATA_SCHEMA,
+            },
+            "validation-delay": {
+                optional: true,
+                schema: ACME_VALIDATION_DELAY_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletablePluginProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update a DNS challenge plugin
+#[allow(clippy::too_many_arguments)]
+pub fn update_plugin(
+    id: String,
+    hook: Option<String>,
+    data: Option<String>,
+    validation_delay: Option<u32>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletablePluginProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let _lock = acme::lock_config()?;
+
+    let (mut config, expected_digest) = acme::plugin_config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut plugin: DnsPlugin = config.lookup("dns", &id)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletablePluginProperty::Data => { plugin.data = None; },
+                DeletablePluginProperty::ValidationDelay => { plugin.validation_delay = None; },
+                DeletablePluginProperty::Comment => { plugin.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            plugin.comment = None;
+        } else {
+            plugin.comment = Some(comment);
+        }
+    }
+
+    if let Some(hook) = hook {
+        check_hook(&hook)?;
+        plugin.hook = hook;
+    }
+    if data.is_some() { plugin.data = data; }
+    if validation_delay.is_some() { plugin.validation_delay = validation_delay; }
+
+    plugin.environment()?;
+
+    config.set_data(&id, "dns", &plugin)?;
+
+    acme::save_plugin_config(&config)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: ACME_PLUGIN_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a DNS challenge plugin
+pub fn delete_plugin(id: String, digest: Option<String>) -> Result<(), Error> {
+    let _lock = acme::lock_config()?;
+
+    let (mut config, expected_digest) = acme::plugin_config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let (domains, _digest) = acme::domain_config()?;
+    let domains: Vec<AcmeDomain> = domains.convert_to_typed_array("domain")?;
+    if let Some(domain) = domains.iter().find(|domain| domain.plugin.as_deref() == Some(&id)) {
+        bail!("ACME plugin '{}' is still used by domain '{}'", id, domain.domain);
+    }
+
+    match config.sections.get(&id) {
+        Some(_) => { config.sections.remove(&id); },
+        None => bail!("ACME plugin '{}' does not exist.", id),
+    }
+
+    acme::save_plugin_config(&config)
+}
+
+#[api(
+    returns: {
+        description: "List of domains of the node certificate.",
+        type: Array,
+        items: { type: AcmeDomain },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List the domains of the node certificate
+pub fn list_domains(mut rpcenv: &mut dyn RpcEnvironment) -> Result<Vec<AcmeDomain>, Error> {
+    let (config, digest) = acme::domain_config()?;
+
+    let list = config.convert_to_typed_array("domain")?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(list)
+}
+
+fn check_domain_plugin(domain: &AcmeDomain) -> Result<(), Error> {
+    match domain.plugin {
+        Some(ref plugin) => {
+            let (config, _digest) = acme::plugin_config()?;
+            if config.sections.get(plugin).is_none() {
+                bail!("ACME plugin '{}' does not exist", plugin);
+            }
+        }
+        None => {
+            if domain.domain.starts_with("*.") {
+                bail!("wildcard domain '{}' requires a DNS plugin", domain.domain);
+            }
+            if domain.alias.is_some() {
+                bail!("an alias requires a DNS plugin");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            domain: {
+                schema: ACME_DOMAIN_SCHEMA,
+            },
+            plugin: {
+                optional: true,
+                schema: ACME_PLUGIN_ID_SCHEMA,
+            },
+            alias: {
+                optional: true,
+                schema: ACME_DOMAIN_ALIAS_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Add a domain to the node certificate
+pub fn add_domain(param: Value) -> Result<(), Error> {
+    let mut domain: AcmeDomain = serde_json::from_value(param)?;
+    domain.domain = domain.domain.to_lowercase();
+
+    let _lock = acme::lock_config()?;
+
+    check_domain_plugin(&domain)?;
+
+    let (mut config, _digest) = acme::domain_config()?;
+
+    if config.sections.get(&domain.domain).is_some() {
+        bail!("domain '{}' already exists.", domain.domain);
+    }
+
+    config.set_data(&domain.domain, "domain", &domain)?;
+
+    acme::save_domain_config(&config)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+/// Deletable property name
+pub enum DeletableDomainProperty {
+    /// Delete the plugin (use the http-01 challenge).
+    Plugin,
+    /// Delete the alias.
+    Alias,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            domain: {
+                schema: ACME_DOMAIN_SCHEMA,
+            },
+            plugin: {
+                optional: true,
+                schema: ACME_PLUGIN_ID_SCHEMA,
+            },
+            alias: {
+                optional: true,
+                schema: ACME_DOMAIN_ALIAS_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableDomainProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update a domain of the node certificate
+pub fn update_domain(
+    domain: String,
+    plugin: Option<String>,
+    alias: Option<String>,
+    delete: Option<Vec<DeletableDomainProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+    let domain = domain.to_lowercase();
+
+    let _lock = acme::lock_config()?;
+
+    let (mut config, expected_digest) = acme::domain_config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: AcmeDomain = config.lookup("domain", &domain)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableDomainProperty::Plugin => { data.plugin = None; },
+                DeletableDomainProperty::Alias => { data.alias = None; },
+            }
+        }
+    }
+
+    if plugin.is_some() { data.plugin = plugin; }
+    if alias.is_some() { data.alias = alias; }
+
+    check_domain_plugin(&data)?;
+
+    config.set_data(&domain, "domain", &data)?;
+
+    acme::save_domain_config(&config)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            domain: {
+                schema: ACME_DOMAIN_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a domain from the node certificate
+pub fn delete_domain(domain: String, digest: Option<String>) -> Result<(), Error> {
+    let domain = domain.to_lowercase();
+
+    let _lock = acme::lock_config()?;
+
+    let (mut config, expected_digest) = acme::domain_config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&domain) {
+        Some(_) => { config.sections.remove(&domain); },
+        None => bail!("domain '{}' does not exist.", domain),
+    }
+
+    acme::save_domain_config(&config)
+}
+
+/// Load the configured domains and their plugins, sorted by domain name.
+pub fn certificate_domains() -> Result<Vec<(AcmeDomain, Option<DnsPlugin>)>, Error> {
+    let (domains, _digest) = acme::domain_config()?;
+    let (plugins, _digest) = acme::plugin_config()?;
+
+    let mut domains: Vec<AcmeDomain> = domains.convert_to_typed_array("domain")?;
+    domains.sort_by(|a, b| a.domain.cmp(&b.domain));
+
+    domains.into_iter()
+        .map(|domain| {
+            let plugin = match domain.plugin {
+                Some(ref id) => Some(plugins.lookup::<DnsPlugin>("dns", id).map_err(|_| {
+                    format_err!("ACME plugin '{}' of domain '{}' does not exist", id, domain.domain)
+                })?),
+                None => None,
+            };
+            Ok((domain, plugin))
+        })
+        .collect()
+}
+
+const ACCOUNT_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_GET_ACCOUNT)
+    .delete(&API_METHOD_DEACTIVATE_ACCOUNT);
+
+const ACCOUNT_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_ACCOUNTS)
+    .post(&API_METHOD_REGISTER_ACCOUNT)
+    .match_all("name", &ACCOUNT_ITEM_ROUTER);
+
+const PLUGIN_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_PLUGIN)
+    .put(&API_METHOD_UPDATE_PLUGIN)
+    .delete(&API_METHOD_DELETE_PLUGIN);
+
+const PLUGIN_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_PLUGINS)
+    .post(&API_METHOD_ADD_PLUGIN)
+    .match_all("id", &PLUGIN_ITEM_ROUTER);
+
+const DOMAIN_ITEM_ROUTER: Router = Router::new()
+    .put(&API_METHOD_UPDATE_DOMAIN)
+    .delete(&API_METHOD_DELETE_DOMAIN);
+
+const DOMAIN_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_DOMAINS)
+    .post(&API_METHOD_ADD_DOMAIN)
+    .match_all("domain", &DOMAIN_ITEM_ROUTER);
+
+const SUBDIRS: SubdirMap = &[
+    ("account", &ACCOUNT_ROUTER),
+    ("directories", &Router::new().get(&API_METHOD_LIST_DIRECTORIES)),
+    ("domains", &DOMAIN_ROUTER),
+    ("plugins", &PLUGIN_ROUTER),
+    ("tos", &Router::new().get(&API_METHOD_GET_TOS)),
+];
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
@@ -1,6 +1,6 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use anyhow::{bail, Error};
+use anyhow::{bail, format_err, Error};
 use serde_json::Value;
 use ::serde::{Deserialize, Serialize};
 
@@ -14,6 +14,7 @@ use crate::config::cached_user_info::CachedUserInfo;
 use crate::config::datastore::{self, DataStoreConfig, DIR_NAME_SCHEMA};
 use crate::config::acl::{PRIV_DATASTORE_ALLOCATE, PRIV_DATASTORE_AUDIT, PRIV_DATASTORE_MODIFY};
 use crate::server::jobstate;
+use crate::tools::disks::mount_by_fs_uuid;
 
 #[api(
     input: {
@@ -109,6 +110,18 @@ pub fn list_datastores(
                 optional: true,
                 schema: PRUNE_SCHEMA_KEEP_YEARLY,
             },
+            "backing-device": {
+                optional: true,
+                schema: BACKING_DEVICE_SCHEMA,
+            },
+            backend: {
+                optional: true,
+                schema: DATASTORE_BACKEND_SCHEMA,
+            },
+            compression: {
+                optional: true,
+                schema: CHUNK_COMPRESSION_SCHEMA,
+            },
         },
     },
     access: {
@@ -128,8 +141,25 @@ pub fn create_datastore(param: Value) -> Result<(), Error> {
         bail!("datastore '{}' already exists.", datastore.name);
     }
 
+    if datastore.get_compression()?.dictionary.is_some() {
+        bail!("unable to use a zstd dictionary on a new datastore - train one first");
+    }
+
     let path: PathBuf = datastore.path.clone().into();
 
+    if let Some(ref uuid) = datastore.backing_device {
+        // the chunk store gets created on the removable device, which stays mounted
+        if !datastore.is_mounted() {
+            mount_by_fs_uuid(uuid, &path)?;
+        }
+    }
+
+    // make sure the bucket is reachable before creating anything
+    if let Some(s3) = S3ChunkBackend::from_datastore_config(&datastore)? {
+        s3.check_bucket()
+            .map_err(|err| format_err!("unable to access bucket '{}' - {}", s3.bucket(), err))?;
+    }
+
     let backup_user = crate::backup::backup_user()?;
     let _store = ChunkStore::create(&datastore.name, path, backup_user.uid, backup_user.gid)?;
 
@@ -199,6 +229,10 @@ pub enum DeletableProperty {
     notify_user,
     /// Delete the notify property
     notify,
+    /// Delete the maintenance-mode property
+    maintenance_mode,
+    /// Delete the compression property
+    compression,
 }
 
 #[api(
@@ -258,6 +292,14 @@ pub enum DeletableProperty {
                 optional: true,
                 default: false,
             },
+            "maintenance-mode": {
+                optional: true,
+                schema: MAINTENANCE_MODE_SCHEMA,
+            },
+            compression: {
+                optional: true,
+                schema: CHUNK_COMPRESSION_SCHEMA,
+            },
             delete: {
                 description: "List of properties to delete.",
                 type: Array,
@@ -292,6 +334,8 @@ pub fn update_datastore(
     verify_new: Option<bool>,
     notify: Option<String>,
     notify_user: Option<Userid>,
+    maintenance_mode: Option<String>,
+    compression: Option<String>,
     delete: Option<Vec<DeletableProperty>>,
     digest: Option<String>,
 ) -> Result<(), Error> {
@@ -323,6 +367,8 @@ pub fn update_datastore(
                 DeletableProperty::verify_new => { data.verify_new = None; },
                 DeletableProperty::notify => { data.notify = None; },
                 DeletableProperty::notify_user => { data.notify_user = None; },
+                DeletableProperty::maintenance_mode => { data.maintenance_mode = None; },
+                DeletableProperty::compression => { data.compression = None; },
             }
         }
     }
@@ -368,6 +414,24 @@ pub fn update_datastore(
 
     if notify_user.is_some() { data.notify_user = notify_user; }
 
+    if maintenance_mode.is_some() { data.maintenance_mode = maintenance_mode; }
+
+    if let Some(compression_str) = compression {
+        let value = parse_property_string(&compression_str, &ChunkCompressionConfig::API_SCHEMA)?;
+        let compression: ChunkCompressionConfig = serde_json::from_value(value)?;
+        if let Some(ref id) = compression.dictionary {
+            let id = parse_dictionary_id(id)?;
+            if !zstd_dictionary_exists(Path::new(&data.path), &id) {
+                bail!(
+                    "zstd dictionary '{}' does not exist on datastore '{}'",
+                    proxmox::tools::bin_to_hex(&id),
+                    name,
+                );
+            }
+        }
+        data.compression = Some(compression_str);
+    }
+
     config.set_data(&name, "datastore", &data)?;
 
     datastore::save_config(&config)?;
@@ -0,0 +1,711 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, Permission, Router, RpcEnvironment};
+use proxmox::api::router::SubdirMap;
+use proxmox::list_subdirs_api_method;
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::acl::{PRIV_SYS_AUDIT, PRIV_SYS_MODIFY};
+use crate::config::metrics::{
+    self,
+    GraphiteProtocol,
+    GraphiteServer,
+    InfluxDbProtocol,
+    InfluxDbServer,
+    GRAPHITE_PATH_SCHEMA,
+    INFLUXDB_BUCKET_SCHEMA,
+    INFLUXDB_ORGANIZATION_SCHEMA,
+    INFLUXDB_TOKEN_SCHEMA,
+    METRIC_SERVER_ID_SCHEMA,
+    METRIC_SERVER_MTU_SCHEMA,
+};
+
+#[api(
+    properties: {
+        name: {
+            schema: METRIC_SERVER_ID_SCHEMA,
+        },
+        "type": {
+            description: "Metric server type ('influxdb' or 'graphite').",
+            type: String,
+        },
+        enable: {
+            description: "Send data to this server.",
+            type: bool,
+        },
+        server: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            description: "Server port.",
+            type: u16,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Metric server list entry.
+pub struct MetricServerListItem {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub enable: bool,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured metric servers.",
+        type: Array,
+        items: { type: MetricServerListItem },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List all metric servers.
+pub fn list_metric_servers(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<MetricServerListItem>, Error> {
+    let (config, digest) = metrics::config()?;
+
+    let mut list = Vec::new();
+
+    let influxdb_list: Vec<InfluxDbServer> = config.convert_to_typed_array("influxdb")?;
+    for server in influxdb_list {
+        list.push(MetricServerListItem {
+            name: server.name,
+            ty: "influxdb".to_string(),
+            enable: server.enable.unwrap_or(true),
+            server: server.server,
+            port: server.port,
+            comment: server.comment,
+        });
+    }
+
+    let graphite_list: Vec<GraphiteServer> = config.convert_to_typed_array("graphite")?;
+    for server in graphite_list {
+        list.push(MetricServerListItem {
+            name: server.name,
+            ty: "graphite".to_string(),
+            enable: server.enable.unwrap_or(true),
+            server: server.server,
+            port: server.port,
+            comment: server.comment,
+        });
+    }
+
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured InfluxDB servers.",
+        type: Array,
+        items: { type: InfluxDbServer },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List InfluxDB servers.
+pub fn list_influxdb_servers(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<InfluxDbServer>, Error> {
+    let (config, digest) = metrics::config()?;
+
+    let mut list: Vec<InfluxDbServer> = config.convert_to_typed_array("influxdb")?;
+    // don't return the token in the api
+    for server in &mut list {
+        server.token = String::new();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            enable: {
+                description: "Send data to this server.",
+                type: bool,
+                optional: true,
+                default: true,
+            },
+            server: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "Server port.",
+                type: u16,
+            },
+            proto: {
+                type: InfluxDbProtocol,
+                optional: true,
+            },
+            organization: {
+                schema: INFLUXDB_ORGANIZATION_SCHEMA,
+                optional: true,
+            },
+            bucket: {
+                schema: INFLUXDB_BUCKET_SCHEMA,
+                optional: true,
+            },
+            token: {
+                schema: INFLUXDB_TOKEN_SCHEMA,
+                optional: true,
+            },
+            mtu: {
+                schema: METRIC_SERVER_MTU_SCHEMA,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new InfluxDB server.
+pub fn create_influxdb_server(param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(metrics::METRIC_SERVER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let mut data = param;
+    if let Some(token) = data["token"].as_str() {
+        data["token"] = Value::from(base64::encode(token.as_bytes()));
+    }
+    let server: InfluxDbServer = serde_json::from_value(data)?;
+
+    let (mut config, _digest) = metrics::config()?;
+
+    if config.sections.get(&server.name).is_some() {
+        bail!("metric server '{}' already exists.", server.name);
+    }
+
+    config.set_data(&server.name, "influxdb", &server)?;
+
+    metrics::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: InfluxDbServer },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read InfluxDB server configuration.
+pub fn read_influxdb_server(
+    name: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<InfluxDbServer, Error> {
+    let (config, digest) = metrics::config()?;
+    let mut data: InfluxDbServer = config.lookup("influxdb", &name)?;
+    data.token = String::new(); // do not return the token in the api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableInfluxDbProperty {
+    /// Delete the enable property.
+    enable,
+    /// Delete the proto property.
+    proto,
+    /// Delete the organization property.
+    organization,
+    /// Delete the bucket property.
+    bucket,
+    /// Delete the token property.
+    token,
+    /// Delete the mtu property.
+    mtu,
+    /// Delete the comment property.
+    comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            enable: {
+                description: "Send data to this server.",
+                type: bool,
+                optional: true,
+            },
+            server: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+                optional: true,
+            },
+            port: {
+                description: "Server port.",
+                type: u16,
+                optional: true,
+            },
+            proto: {
+                type: InfluxDbProtocol,
+                optional: true,
+            },
+            organization: {
+                schema: INFLUXDB_ORGANIZATION_SCHEMA,
+                optional: true,
+            },
+            bucket: {
+                schema: INFLUXDB_BUCKET_SCHEMA,
+                optional: true,
+            },
+            token: {
+                schema: INFLUXDB_TOKEN_SCHEMA,
+                optional: true,
+            },
+            mtu: {
+                schema: METRIC_SERVER_MTU_SCHEMA,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableInfluxDbProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update InfluxDB server configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_influxdb_server(
+    name: String,
+    enable: Option<bool>,
+    server: Option<String>,
+    port: Option<u16>,
+    proto: Option<InfluxDbProtocol>,
+    organization: Option<String>,
+    bucket: Option<String>,
+    token: Option<String>,
+    mtu: Option<u16>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableInfluxDbProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(metrics::METRIC_SERVER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = metrics::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: InfluxDbServer = config.lookup("influxdb", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableInfluxDbProperty::enable => { data.enable = None; },
+                DeletableInfluxDbProperty::proto => { data.proto = None; },
+                DeletableInfluxDbProperty::organization => { data.organization = None; },
+                DeletableInfluxDbProperty::bucket => { data.bucket = None; },
+                DeletableInfluxDbProperty::token => { data.token = String::new(); },
+                DeletableInfluxDbProperty::mtu => { data.mtu = None; },
+                DeletableInfluxDbProperty::comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if enable.is_some() { data.enable = enable; }
+    if let Some(server) = server { data.server = server; }
+    if let Some(port) = port { data.port = port; }
+    if proto.is_some() { data.proto = proto; }
+    if organization.is_some() { data.organization = organization; }
+    if bucket.is_some() { data.bucket = bucket; }
+    if let Some(token) = token { data.token = token; }
+    if mtu.is_some() { data.mtu = mtu; }
+
+    config.set_data(&name, "influxdb", &data)?;
+
+    metrics::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured Graphite servers.",
+        type: Array,
+        items: { type: GraphiteServer },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List Graphite servers.
+pub fn list_graphite_servers(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<GraphiteServer>, Error> {
+    let (config, digest) = metrics::config()?;
+
+    let list: Vec<GraphiteServer> = config.convert_to_typed_array("graphite")?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            enable: {
+                description: "Send data to this server.",
+                type: bool,
+                optional: true,
+                default: true,
+            },
+            server: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "Server port.",
+                type: u16,
+            },
+            proto: {
+                type: GraphiteProtocol,
+                optional: true,
+            },
+            path: {
+                schema: GRAPHITE_PATH_SCHEMA,
+                optional: true,
+            },
+            mtu: {
+                schema: METRIC_SERVER_MTU_SCHEMA,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new Graphite server.
+pub fn create_graphite_server(param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(metrics::METRIC_SERVER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let server: GraphiteServer = serde_json::from_value(param)?;
+
+    let (mut config, _digest) = metrics::config()?;
+
+    if config.sections.get(&server.name).is_some() {
+        bail!("metric server '{}' already exists.", server.name);
+    }
+
+    config.set_data(&server.name, "graphite", &server)?;
+
+    metrics::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: GraphiteServer },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read Graphite server configuration.
+pub fn read_graphite_server(
+    name: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<GraphiteServer, Error> {
+    let (config, digest) = metrics::config()?;
+    let data: GraphiteServer = config.lookup("graphite", &name)?;
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableGraphiteProperty {
+    /// Delete the enable property.
+    enable,
+    /// Delete the proto property.
+    proto,
+    /// Delete the path property.
+    path,
+    /// Delete the mtu property.
+    mtu,
+    /// Delete the comment property.
+    comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            enable: {
+                description: "Send data to this server.",
+                type: bool,
+                optional: true,
+            },
+            server: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+                optional: true,
+            },
+            port: {
+                description: "Server port.",
+                type: u16,
+                optional: true,
+            },
+            proto: {
+                type: GraphiteProtocol,
+                optional: true,
+            },
+            path: {
+                schema: GRAPHITE_PATH_SCHEMA,
+                optional: true,
+            },
+            mtu: {
+                schema: METRIC_SERVER_MTU_SCHEMA,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableGraphiteProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update Graphite server configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_graphite_server(
+    name: String,
+    enable: Option<bool>,
+    server: Option<String>,
+    port: Option<u16>,
+    proto: Option<GraphiteProtocol>,
+    path: Option<String>,
+    mtu: Option<u16>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableGraphiteProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(metrics::METRIC_SERVER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = metrics::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: GraphiteServer = config.lookup("graphite", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableGraphiteProperty::enable => { data.enable = None; },
+                DeletableGraphiteProperty::proto => { data.proto = None; },
+                DeletableGraphiteProperty::path => { data.path = None; },
+                DeletableGraphiteProperty::mtu => { data.mtu = None; },
+                DeletableGraphiteProperty::comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if enable.is_some() { data.enable = enable; }
+    if let Some(server) = server { data.server = server; }
+    if let Some(port) = port { data.port = port; }
+    if proto.is_some() { data.proto = proto; }
+    if path.is_some() { data.path = path; }
+    if mtu.is_some() { data.mtu = mtu; }
+
+    config.set_data(&name, "graphite", &data)?;
+
+    metrics::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "metrics"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a metric server (of any type) from the configuration file.
+pub fn delete_metric_server(name: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = open_file_locked(metrics::METRIC_SERVER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = metrics::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&name) {
+        Some(_) => { config.sections.remove(&name); },
+        None => bail!("metric server '{}' does not exist.", name),
+    }
+
+    metrics::save_config(&config)?;
+
+    Ok(())
+}
+
+const INFLUXDB_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_INFLUXDB_SERVER)
+    .put(&API_METHOD_UPDATE_INFLUXDB_SERVER)
+    .delete(&API_METHOD_DELETE_METRIC_SERVER);
+
+const INFLUXDB_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_INFLUXDB_SERVERS)
+    .post(&API_METHOD_CREATE_INFLUXDB_SERVER)
+    .match_all("name", &INFLUXDB_ITEM_ROUTER);
+
+const GRAPHITE_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_GRAPHITE_SERVER)
+    .put(&API_METHOD_UPDATE_GRAPHITE_SERVER)
+    .delete(&API_METHOD_DELETE_METRIC_SERVER);
+
+const GRAPHITE_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_GRAPHITE_SERVERS)
+    .post(&API_METHOD_CREATE_GRAPHITE_SERVER)
+    .match_all("name", &GRAPHITE_ITEM_ROUTER);
+
+const SUBDIRS: SubdirMap = &[
+    ("graphite", &GRAPHITE_ROUTER),
+    ("influxdb", &INFLUXDB_ROUTER),
+    ("server", &Router::new().get(&API_METHOD_LIST_METRIC_SERVERS)),
+];
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
@@ -0,0 +1,1319 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, Permission, Router, RpcEnvironment};
+use proxmox::api::router::SubdirMap;
+use proxmox::api::section_config::SectionConfigData;
+use proxmox::list_subdirs_api_method;
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::acl::{PRIV_SYS_AUDIT, PRIV_SYS_MODIFY};
+use crate::config::user::EMAIL_SCHEMA;
+use crate::config::notifications::{
+    self,
+    split_list,
+    GotifyTarget,
+    NotificationMatcher,
+    SmtpMode,
+    SmtpTarget,
+    WebhookMethod,
+    WebhookTarget,
+    EMAIL_LIST_SCHEMA,
+    HTTP_URL_SCHEMA,
+    MAILTO_USER_LIST_SCHEMA,
+    MATCH_DATASTORE_LIST_SCHEMA,
+    MATCH_SEVERITY_LIST_SCHEMA,
+    MATCH_TYPE_LIST_SCHEMA,
+    NOTIFICATION_NAME_SCHEMA,
+    NOTIFICATION_SECRET_SCHEMA,
+    TARGET_LIST_SCHEMA,
+    TARGET_TYPES,
+    WEBHOOK_BODY_SCHEMA,
+    WEBHOOK_HEADERS_SCHEMA,
+};
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_NAME_SCHEMA,
+        },
+        "type": {
+            description: "Target type ('smtp', 'webhook' or 'gotify').",
+            type: String,
+        },
+        disable: {
+            description: "Notifications to this target are disabled.",
+            type: bool,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Notification target list entry.
+pub struct NotificationTargetListItem {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub disable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn lock_config() -> Result<std::fs::File, Error> {
+    open_file_locked(notifications::NOTIFICATION_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)
+}
+
+fn check_digest(digest: Option<String>, expected_digest: &[u8; 32]) -> Result<(), Error> {
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, expected_digest)?;
+    }
+    Ok(())
+}
+
+fn check_targets_exist(config: &SectionConfigData, targets: &str) -> Result<(), Error> {
+    for name in split_list(Some(targets)) {
+        match config.sections.get(name) {
+            Some((section_type, _)) if TARGET_TYPES.contains(&section_type.as_str()) => (),
+            _ => bail!("notification target '{}' does not exist.", name),
+        }
+    }
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured notification targets.",
+        type: Array,
+        items: { type: NotificationTargetListItem },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List all notification targets.
+pub fn list_targets(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<NotificationTargetListItem>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list = Vec::new();
+
+    let smtp_list: Vec<SmtpTarget> = config.convert_to_typed_array("smtp")?;
+    for target in smtp_list {
+        list.push(NotificationTargetListItem {
+            name: target.name,
+            ty: "smtp".to_string(),
+            disable: target.disable.unwrap_or(false),
+            comment: target.comment,
+        });
+    }
+
+    let webhook_list: Vec<WebhookTarget> = config.convert_to_typed_array("webhook")?;
+    for target in webhook_list {
+        list.push(NotificationTargetListItem {
+            name: target.name,
+            ty: "webhook".to_string(),
+            disable: target.disable.unwrap_or(false),
+            comment: target.comment,
+        });
+    }
+
+    let gotify_list: Vec<GotifyTarget> = config.convert_to_typed_array("gotify")?;
+    for target in gotify_list {
+        list.push(NotificationTargetListItem {
+            name: target.name,
+            ty: "gotify".to_string(),
+            disable: target.disable.unwrap_or(false),
+            comment: target.comment,
+        });
+    }
+
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a notification target (of any type) from the configuration file.
+pub fn delete_target(name: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let (mut config, expected_digest) = notifications::config()?;
+
+    check_digest(digest, &expected_digest)?;
+
+    match config.sections.get(&name) {
+        Some((section_type, _)) if TARGET_TYPES.contains(&section_type.as_str()) => (),
+        _ => bail!("notification target '{}' does not exist.", name),
+    }
+
+    let matchers: Vec<NotificationMatcher> = config.convert_to_typed_array("matcher")?;
+    for matcher in matchers {
+        if split_list(Some(matcher.target.as_str())).contains(&name.as_str()) {
+            bail!("notification target '{}' is used by matcher '{}'.", name, matcher.name);
+        }
+    }
+
+    config.sections.remove(&name);
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Send a test notification to a target.
+pub fn test_target(name: String) -> Result<(), Error> {
+    crate::server::notifications::send_test_notification(&name)
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured SMTP targets.",
+        type: Array,
+        items: { type: SmtpTarget },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List SMTP targets.
+pub fn list_smtp_targets(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<SmtpTarget>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list: Vec<SmtpTarget> = config.convert_to_typed_array("smtp")?;
+    // don't return the password in the api
+    for target in &mut list {
+        target.password = String::new();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            server: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "Server port (defaults to the port of the connection mode).",
+                type: u16,
+                optional: true,
+            },
+            mode: {
+                type: SmtpMode,
+                optional: true,
+            },
+            username: {
+                description: "User name for the SMTP authentication.",
+                type: String,
+                optional: true,
+                max_length: 256,
+            },
+            password: {
+                schema: NOTIFICATION_SECRET_SCHEMA,
+                optional: true,
+            },
+            "from-address": {
+                schema: EMAIL_SCHEMA,
+            },
+            mailto: {
+                schema: EMAIL_LIST_SCHEMA,
+                optional: true,
+            },
+            "mailto-user": {
+                schema: MAILTO_USER_LIST_SCHEMA,
+                optional: true,
+            },
+            author: {
+                description: "Author of the mail (defaults to 'Proxmox Backup Server - <nodename>').",
+                type: String,
+                optional: true,
+                max_length: 256,
+            },
+            disable: {
+                description: "Do not send notifications to this target.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new SMTP target.
+pub fn create_smtp_target(param: Value) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let mut data = param;
+    if let Some(password) = data["password"].as_str() {
+        data["password"] = Value::from(base64::encode(password.as_bytes()));
+    }
+    let target: SmtpTarget = serde_json::from_value(data)?;
+
+    if target.mailto.is_none() && target.mailto_user.is_none() {
+        bail!("either 'mailto' or 'mailto-user' is required.");
+    }
+
+    let (mut config, _digest) = notifications::config()?;
+
+    if config.sections.get(&target.name).is_some() {
+        bail!("notification target or matcher '{}' already exists.", target.name);
+    }
+
+    config.set_data(&target.name, "smtp", &target)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: { type: SmtpTarget },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read SMTP target configuration.
+pub fn read_smtp_target(
+    name: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<SmtpTarget, Error> {
+    let (config, digest) = notifications::config()?;
+    let mut data: SmtpTarget = config.lookup("smtp", &name)?;
+    data.password = String::new(); // do not return the password in the api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableSmtpProperty {
+    /// Delete the port property.
+    port,
+    /// Delete the mode property.
+    mode,
+    /// Delete the username property.
+    username,
+    /// Delete the password property.
+    password,
+    /// Delete the mailto property.
+    mailto,
+    /// Delete the mailto-user property.
+    mailto_user,
+    /// Delete the author property.
+    author,
+    /// Delete the disable property.
+    disable,
+    /// Delete the comment property.
+    comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            server: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+                optional: true,
+            },
+            port: {
+                description: "Server port (defaults to the port of the connection mode).",
+                type: u16,
+                optional: true,
+            },
+            mode: {
+                type: SmtpMode,
+                optional: true,
+            },
+            username: {
+                description: "User name for the SMTP authentication.",
+                type: String,
+                optional: true,
+                max_length: 256,
+            },
+            password: {
+                schema: NOTIFICATION_SECRET_SCHEMA,
+                optional: true,
+            },
+            "from-address": {
+                schema: EMAIL_SCHEMA,
+                optional: true,
+            },
+            mailto: {
+                schema: EMAIL_LIST_SCHEMA,
+                optional: true,
+            },
+            "mailto-user": {
+                schema: MAILTO_USER_LIST_SCHEMA,
+                optional: true,
+            },
+            author: {
+                description: "Author of the mail (defaults to 'Proxmox Backup Server - <nodename>').",
+                type: String,
+                optional: true,
+                max_length: 256,
+            },
+            disable: {
+                description: "Do not send notifications to this target.",
+                type: bool,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableSmtpProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update SMTP target configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_smtp_target(
+    name: String,
+    server: Option<String>,
+    port: Option<u16>,
+    mode: Option<SmtpMode>,
+    username: Option<String>,
+    password: Option<String>,
+    from_address: Option<String>,
+    mailto: Option<String>,
+    mailto_user: Option<String>,
+    author: Option<String>,
+    disable: Option<bool>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableSmtpProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let (mut config, expected_digest) = notifications::config()?;
+
+    check_digest(digest, &expected_digest)?;
+
+    let mut data: SmtpTarget = config.lookup("smtp", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableSmtpProperty::port => { data.port = None; },
+                DeletableSmtpProperty::mode => { data.mode = None; },
+                DeletableSmtpProperty::username => { data.username = None; },
+                DeletableSmtpProperty::password => { data.password = String::new(); },
+                DeletableSmtpProperty::mailto => { data.mailto = None; },
+                DeletableSmtpProperty::mailto_user => { data.mailto_user = None; },
+                DeletableSmtpProperty::author => { data.author = None; },
+                DeletableSmtpProperty::disable => { data.disable = None; },
+                DeletableSmtpProperty::comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(server) = server { data.server = server; }
+    if port.is_some() { data.port = port; }
+    if mode.is_some() { data.mode = mode; }
+    if username.is_some() { data.username = username; }
+    if let Some(password) = password { data.password = password; }
+    if let Some(from_address) = from_address { data.from_address = from_address; }
+    if mailto.is_some() { data.mailto = mailto; }
+    if mailto_user.is_some() { data.mailto_user = mailto_user; }
+    if author.is_some() { data.author = author; }
+    if disable.is_some() { data.disable = disable; }
+
+    if data.mailto.is_none() && data.mailto_user.is_none() {
+        bail!("either 'mailto' or 'mailto-user' is required.");
+    }
+
+    config.set_data(&name, "smtp", &data)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured webhook targets.",
+        type: Array,
+        items: { type: WebhookTarget },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List webhook targets.
+pub fn list_webhook_targets(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<WebhookTarget>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list: Vec<WebhookTarget> = config.convert_to_typed_array("webhook")?;
+    // headers may contain authentication tokens, don't return them in the api
+    for target in &mut list {
+        target.headers = String::new();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            url: {
+                schema: HTTP_URL_SCHEMA,
+            },
+            method: {
+                type: WebhookMethod,
+                optional: true,
+            },
+            headers: {
+                schema: WEBHOOK_HEADERS_SCHEMA,
+                optional: true,
+            },
+            body: {
+                schema: WEBHOOK_BODY_SCHEMA,
+                optional: true,
+            },
+            disable: {
+                description: "Do not send notifications to this target.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new webhook target.
+pub fn create_webhook_target(param: Value) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let mut data = param;
+    for property in &["headers", "body"] {
+        if let Some(value) = data[property].as_str() {
+            data[property] = Value::from(base64::encode(value.as_bytes()));
+        }
+    }
+    let target: WebhookTarget = serde_json::from_value(data)?;
+
+    let (mut config, _digest) = notifications::config()?;
+
+    if config.sections.get(&target.name).is_some() {
+        bail!("notification target or matcher '{}' already exists.", target.name);
+    }
+
+    config.set_data(&target.name, "webhook", &target)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: { type: WebhookTarget },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read webhook target configuration.
+pub fn read_webhook_target(
+    name: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<WebhookTarget, Error> {
+    let (config, digest) = notifications::config()?;
+    let mut data: WebhookTarget = config.lookup("webhook", &name)?;
+    data.headers = String::new(); // do not return the headers in the api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableWebhookProperty {
+    /// Delete the method property.
+    method,
+    /// Delete the headers property.
+    headers,
+    /// Delete the body property.
+    body,
+    /// Delete the disable property.
+    disable,
+    /// Delete the comment property.
+    comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            url: {
+                schema: HTTP_URL_SCHEMA,
+                optional: true,
+            },
+            method: {
+                type: WebhookMethod,
+                optional: true,
+            },
+            headers: {
+                schema: WEBHOOK_HEADERS_SCHEMA,
+                optional: true,
+            },
+            body: {
+                schema: WEBHOOK_BODY_SCHEMA,
+                optional: true,
+            },
+            disable: {
+                description: "Do not send notifications to this target.",
+                type: bool,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableWebhookProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update webhook target configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_webhook_target(
+    name: String,
+    url: Option<String>,
+    method: Option<WebhookMethod>,
+    headers: Option<String>,
+    body: Option<String>,
+    disable: Option<bool>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableWebhookProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let (mut config, expected_digest) = notifications::config()?;
+
+    check_digest(digest, &expected_digest)?;
+
+    let mut data: WebhookTarget = config.lookup("webhook", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableWebhookProperty::method => { data.method = None; },
+                DeletableWebhookProperty::headers => { data.headers = String::new(); },
+                DeletableWebhookProperty::body => { data.body = String::new(); },
+                DeletableWebhookProperty::disable => { data.disable = None; },
+                DeletableWebhookProperty::comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(url) = url { data.url = url; }
+    if method.is_some() { data.method = method; }
+    if let Some(headers) = headers { data.headers = headers; }
+    if let Some(body) = body { data.body = body; }
+    if disable.is_some() { data.disable = disable; }
+
+    config.set_data(&name, "webhook", &data)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured Gotify targets.",
+        type: Array,
+        items: { type: GotifyTarget },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List Gotify targets.
+pub fn list_gotify_targets(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<GotifyTarget>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let mut list: Vec<GotifyTarget> = config.convert_to_typed_array("gotify")?;
+    // don't return the token in the api
+    for target in &mut list {
+        target.token = String::new();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            server: {
+                description: "Base URL of the Gotify server.",
+                schema: HTTP_URL_SCHEMA,
+            },
+            token: {
+                schema: NOTIFICATION_SECRET_SCHEMA,
+            },
+            disable: {
+                description: "Do not send notifications to this target.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new Gotify target.
+pub fn create_gotify_target(param: Value) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let mut data = param;
+    if let Some(token) = data["token"].as_str() {
+        data["token"] = Value::from(base64::encode(token.as_bytes()));
+    }
+    let target: GotifyTarget = serde_json::from_value(data)?;
+
+    let (mut config, _digest) = notifications::config()?;
+
+    if config.sections.get(&target.name).is_some() {
+        bail!("notification target or matcher '{}' already exists.", target.name);
+    }
+
+    config.set_data(&target.name, "gotify", &target)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: { type: GotifyTarget },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read Gotify target configuration.
+pub fn read_gotify_target(
+    name: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<GotifyTarget, Error> {
+    let (config, digest) = notifications::config()?;
+    let mut data: GotifyTarget = config.lookup("gotify", &name)?;
+    data.token = String::new(); // do not return the token in the api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableGotifyProperty {
+    /// Delete the disable property.
+    disable,
+    /// Delete the comment property.
+    comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            server: {
+                description: "Base URL of the Gotify server.",
+                schema: HTTP_URL_SCHEMA,
+                optional: true,
+            },
+            token: {
+                schema: NOTIFICATION_SECRET_SCHEMA,
+                optional: true,
+            },
+            disable: {
+                description: "Do not send notifications to this target.",
+                type: bool,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableGotifyProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update Gotify target configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_gotify_target(
+    name: String,
+    server: Option<String>,
+    token: Option<String>,
+    disable: Option<bool>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableGotifyProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let (mut config, expected_digest) = notifications::config()?;
+
+    check_digest(digest, &expected_digest)?;
+
+    let mut data: GotifyTarget = config.lookup("gotify", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableGotifyProperty::disable => { data.disable = None; },
+                DeletableGotifyProperty::comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(server) = server { data.server = server; }
+    if let Some(token) = token { data.token = token; }
+    if disable.is_some() { data.disable = disable; }
+
+    config.set_data(&name, "gotify", &data)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List of configured notification matchers.",
+        type: Array,
+        items: { type: NotificationMatcher },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List notification matchers.
+pub fn list_matchers(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<NotificationMatcher>, Error> {
+    let (config, digest) = notifications::config()?;
+
+    let list: Vec<NotificationMatcher> = config.convert_to_typed_array("matcher")?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            "match-type": {
+                schema: MATCH_TYPE_LIST_SCHEMA,
+                optional: true,
+            },
+            "match-severity": {
+                schema: MATCH_SEVERITY_LIST_SCHEMA,
+                optional: true,
+            },
+            "match-datastore": {
+                schema: MATCH_DATASTORE_LIST_SCHEMA,
+                optional: true,
+            },
+            target: {
+                schema: TARGET_LIST_SCHEMA,
+            },
+            disable: {
+                description: "Do not use this matcher.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create a new notification matcher.
+pub fn create_matcher(param: Value) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let matcher: NotificationMatcher = serde_json::from_value(param)?;
+
+    let (mut config, _digest) = notifications::config()?;
+
+    if config.sections.get(&matcher.name).is_some() {
+        bail!("notification target or matcher '{}' already exists.", matcher.name);
+    }
+
+    check_targets_exist(&config, &matcher.target)?;
+
+    config.set_data(&matcher.name, "matcher", &matcher)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+        },
+    },
+    returns: { type: NotificationMatcher },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Read notification matcher configuration.
+pub fn read_matcher(
+    name: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<NotificationMatcher, Error> {
+    let (config, digest) = notifications::config()?;
+    let data: NotificationMatcher = config.lookup("matcher", &name)?;
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableMatcherProperty {
+    /// Delete the match-type property.
+    match_type,
+    /// Delete the match-severity property.
+    match_severity,
+    /// Delete the match-datastore property.
+    match_datastore,
+    /// Delete the disable property.
+    disable,
+    /// Delete the comment property.
+    comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            "match-type": {
+                schema: MATCH_TYPE_LIST_SCHEMA,
+                optional: true,
+            },
+            "match-severity": {
+                schema: MATCH_SEVERITY_LIST_SCHEMA,
+                optional: true,
+            },
+            "match-datastore": {
+                schema: MATCH_DATASTORE_LIST_SCHEMA,
+                optional: true,
+            },
+            target: {
+                schema: TARGET_LIST_SCHEMA,
+                optional: true,
+            },
+            disable: {
+                description: "Do not use this matcher.",
+                type: bool,
+                optional: true,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableMatcherProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update notification matcher configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_matcher(
+    name: String,
+    match_type: Option<String>,
+    match_severity: Option<String>,
+    match_datastore: Option<String>,
+    target: Option<String>,
+    disable: Option<bool>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableMatcherProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let (mut config, expected_digest) = notifications::config()?;
+
+    check_digest(digest, &expected_digest)?;
+
+    let mut data: NotificationMatcher = config.lookup("matcher", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableMatcherProperty::match_type => { data.match_type = None; },
+                DeletableMatcherProperty::match_severity => { data.match_severity = None; },
+                DeletableMatcherProperty::match_datastore => { data.match_datastore = None; },
+                DeletableMatcherProperty::disable => { data.disable = None; },
+                DeletableMatcherProperty::comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if match_type.is_some() { data.match_type = match_type; }
+    if match_severity.is_some() { data.match_severity = match_severity; }
+    if match_datastore.is_some() { data.match_datastore = match_datastore; }
+    if let Some(target) = target {
+        check_targets_exist(&config, &target)?;
+        data.target = target;
+    }
+    if disable.is_some() { data.disable = disable; }
+
+    config.set_data(&name, "matcher", &data)?;
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "notifications"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a notification matcher from the configuration file.
+pub fn delete_matcher(name: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = lock_config()?;
+
+    let (mut config, expected_digest) = notifications::config()?;
+
+    check_digest(digest, &expected_digest)?;
+
+    match config.sections.get(&name) {
+        Some((section_type, _)) if section_type == "matcher" => { config.sections.remove(&name); },
+        _ => bail!("notification matcher '{}' does not exist.", name),
+    }
+
+    notifications::save_config(&config)?;
+
+    Ok(())
+}
+
+const TARGET_ITEM_SUBDIRS: SubdirMap = &[
+    ("test", &Router::new().post(&API_METHOD_TEST_TARGET)),
+];
+
+const TARGET_ITEM_ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(TARGET_ITEM_SUBDIRS))
+    .delete(&API_METHOD_DELETE_TARGET)
+    .subdirs(TARGET_ITEM_SUBDIRS);
+
+const TARGETS_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_TARGETS)
+    .match_all("name", &TARGET_ITEM_ROUTER);
+
+const SMTP_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_SMTP_TARGET)
+    .put(&API_METHOD_UPDATE_SMTP_TARGET)
+    .delete(&API_METHOD_DELETE_TARGET);
+
+const SMTP_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_SMTP_TARGETS)
+    .post(&API_METHOD_CREATE_SMTP_TARGET)
+    .match_all("name", &SMTP_ITEM_ROUTER);
+
+const WEBHOOK_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_WEBHOOK_TARGET)
+    .put(&API_METHOD_UPDATE_WEBHOOK_TARGET)
+    .delete(&API_METHOD_DELETE_TARGET);
+
+const WEBHOOK_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_WEBHOOK_TARGETS)
+    .post(&API_METHOD_CREATE_WEBHOOK_TARGET)
+    .match_all("name", &WEBHOOK_ITEM_ROUTER);
+
+const GOTIFY_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_GOTIFY_TARGET)
+    .put(&API_METHOD_UPDATE_GOTIFY_TARGET)
+    .delete(&API_METHOD_DELETE_TARGET);
+
+const GOTIFY_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_GOTIFY_TARGETS)
+    .post(&API_METHOD_CREATE_GOTIFY_TARGET)
+    .match_all("name", &GOTIFY_ITEM_ROUTER);
+
+const MATCHER_ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_MATCHER)
+    .put(&API_METHOD_UPDATE_MATCHER)
+    .delete(&API_METHOD_DELETE_MATCHER);
+
+const MATCHERS_ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_MATCHERS)
+    .post(&API_METHOD_CREATE_MATCHER)
+    .match_all("name", &MATCHER_ITEM_ROUTER);
+
+const SUBDIRS: SubdirMap = &[
+    ("gotify", &GOTIFY_ROUTER),
+    ("matchers", &MATCHERS_ROUTER),
+    ("smtp", &SMTP_ROUTER),
+    ("targets", &TARGETS_ROUTER),
+    ("webhook", &WEBHOOK_ROUTER),
+];
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
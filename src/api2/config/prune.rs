@@ -0,0 +1,456 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, Permission, Router, RpcEnvironment};
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+
+use crate::backup::BackupNamespace;
+
+use crate::config::acl::{
+    PRIV_DATASTORE_AUDIT,
+    PRIV_DATASTORE_MODIFY,
+};
+
+use crate::config::cached_user_info::CachedUserInfo;
+
+use crate::config::prune::{self, PruneJobConfig};
+
+fn check_prune_job_privs(
+    user_info: &CachedUserInfo,
+    auth_id: &Authid,
+    job: &PruneJobConfig,
+    privs: u64,
+) -> Result<(), Error> {
+    let ns = job.ns.clone().unwrap_or_default();
+    user_info.check_privs(auth_id, &ns.acl_path(&job.store), privs, true)
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List configured jobs.",
+        type: Array,
+        items: { type: prune::PruneJobConfig },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Modify on datastore.",
+    },
+)]
+/// List all prune jobs
+pub fn list_prune_jobs(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<PruneJobConfig>, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let required_privs = PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_MODIFY;
+
+    let (config, digest) = prune::config()?;
+
+    let list = config.convert_to_typed_array("prune")?;
+
+    let list = list.into_iter()
+        .filter(|job: &PruneJobConfig| {
+            let ns = job.ns.clone().unwrap_or_default();
+            let privs = user_info.lookup_privs(&auth_id, &ns.acl_path(&job.store));
+
+            privs & required_privs != 0
+        }).collect();
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: JOB_ID_SCHEMA,
+            },
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "keep-last": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_LAST,
+            },
+            "keep-hourly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_HOURLY,
+            },
+            "keep-daily": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_DAILY,
+            },
+            "keep-weekly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_WEEKLY,
+            },
+            "keep-monthly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_MONTHLY,
+            },
+            "keep-yearly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_YEARLY,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            schedule: {
+                optional: true,
+                schema: PRUNE_SCHEDULE_SCHEMA,
+            },
+        }
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on job's datastore.",
+    },
+)]
+/// Create a new prune job.
+pub fn create_prune_job(
+    param: Value,
+    rpcenv: &mut dyn RpcEnvironment
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let prune_job: prune::PruneJobConfig = serde_json::from_value(param)?;
+
+    check_prune_job_privs(&user_info, &auth_id, &prune_job, PRIV_DATASTORE_MODIFY)?;
+
+    if !prune_job.prune_options().keeps_something() {
+        bail!("prune job needs at least one 'keep-*' option");
+    }
+
+    let _lock = open_file_locked(prune::PRUNE_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, _digest) = prune::config()?;
+
+    if config.sections.get(&prune_job.id).is_some() {
+        bail!("job '{}' already exists.", prune_job.id);
+    }
+
+    config.set_data(&prune_job.id, "prune", &prune_job)?;
+
+    prune::save_config(&config)?;
+
+    crate::server::jobstate::create_state_file("prunejob", &prune_job.id)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            id: {
+                schema: JOB_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: prune::PruneJobConfig },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Modify on job's datastore.",
+    },
+)]
+/// Read a prune job configuration.
+pub fn read_prune_job(
+    id: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<PruneJobConfig, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let (config, digest) = prune::config()?;
+
+    let prune_job: prune::PruneJobConfig = config.lookup("prune", &id)?;
+
+    let required_privs = PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_MODIFY;
+    check_prune_job_privs(&user_info, &auth_id, &prune_job, required_privs)?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(prune_job)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the namespace property.
+    Ns,
+    /// Delete the max-depth property.
+    MaxDepth,
+    /// Delete the group-filter property.
+    GroupFilter,
+    /// Delete the keep-last property.
+    KeepLast,
+    /// Delete the keep-hourly property.
+    KeepHourly,
+    /// Delete the keep-daily property.
+    KeepDaily,
+    /// Delete the keep-weekly property.
+    KeepWeekly,
+    /// Delete the keep-monthly property.
+    KeepMonthly,
+    /// Delete the keep-yearly property.
+    KeepYearly,
+    /// Delete the comment property.
+    Comment,
+    /// Delete the job schedule.
+    Schedule,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: JOB_ID_SCHEMA,
+            },
+            store: {
+                optional: true,
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "keep-last": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_LAST,
+            },
+            "keep-hourly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_HOURLY,
+            },
+            "keep-daily": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_DAILY,
+            },
+            "keep-weekly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_WEEKLY,
+            },
+            "keep-monthly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_MONTHLY,
+            },
+            "keep-yearly": {
+                optional: true,
+                schema: PRUNE_SCHEMA_KEEP_YEARLY,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            schedule: {
+                optional: true,
+                schema: PRUNE_SCHEDULE_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on job's datastore.",
+    },
+)]
+/// Update prune job config.
+#[allow(clippy::too_many_arguments)]
+pub fn update_prune_job(
+    id: String,
+    store: Option<String>,
+    ns: Option<BackupNamespace>,
+    max_depth: Option<usize>,
+    group_filter: Option<String>,
+    keep_last: Option<u64>,
+    keep_hourly: Option<u64>,
+    keep_daily: Option<u64>,
+    keep_weekly: Option<u64>,
+    keep_monthly: Option<u64>,
+    keep_yearly: Option<u64>,
+    comment: Option<String>,
+    schedule: Option<String>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let _lock = open_file_locked(prune::PRUNE_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    // pass/compare digest
+    let (mut config, expected_digest) = prune::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: prune::PruneJobConfig = config.lookup("prune", &id)?;
+
+    // check existing store and namespace
+    check_prune_job_privs(&user_info, &auth_id, &data, PRIV_DATASTORE_MODIFY)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::Ns => { data.ns = None; },
+                DeletableProperty::MaxDepth => { data.max_depth = None; },
+                DeletableProperty::GroupFilter => { data.group_filter = None; },
+                DeletableProperty::KeepLast => { data.keep_last = None; },
+                DeletableProperty::KeepHourly => { data.keep_hourly = None; },
+                DeletableProperty::KeepDaily => { data.keep_daily = None; },
+                DeletableProperty::KeepWeekly => { data.keep_weekly = None; },
+                DeletableProperty::KeepMonthly => { data.keep_monthly = None; },
+                DeletableProperty::KeepYearly => { data.keep_yearly = None; },
+                DeletableProperty::Comment => { data.comment = None; },
+                DeletableProperty::Schedule => { data.schedule = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+
+    if let Some(store) = store { data.store = store; }
+    if ns.is_some() { data.ns = ns; }
+    if max_depth.is_some() { data.max_depth = max_depth; }
+    if group_filter.is_some() { data.group_filter = group_filter; }
+
+    if keep_last.is_some() { data.keep_last = keep_last; }
+    if keep_hourly.is_some() { data.keep_hourly = keep_hourly; }
+    if keep_daily.is_some() { data.keep_daily = keep_daily; }
+    if keep_weekly.is_some() { data.keep_weekly = keep_weekly; }
+    if keep_monthly.is_some() { data.keep_monthly = keep_monthly; }
+    if keep_yearly.is_some() { data.keep_yearly = keep_yearly; }
+
+    if schedule.is_some() { data.schedule = schedule; }
+
+    // check new store and namespace
+    check_prune_job_privs(&user_info, &auth_id, &data, PRIV_DATASTORE_MODIFY)?;
+
+    if !data.prune_options().keeps_something() {
+        bail!("prune job needs at least one 'keep-*' option");
+    }
+
+    config.set_data(&id, "prune", &data)?;
+
+    prune::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: JOB_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on job's datastore.",
+    },
+)]
+/// Remove a prune job configuration
+pub fn delete_prune_job(
+    id: String,
+    digest: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let _lock = open_file_locked(prune::PRUNE_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = prune::config()?;
+
+    let job: prune::PruneJobConfig = config.lookup("prune", &id)?;
+    check_prune_job_privs(&user_info, &auth_id, &job, PRIV_DATASTORE_MODIFY)?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&id) {
+        Some(_) => { config.sections.remove(&id); },
+        None => bail!("job '{}' does not exist.", id),
+    }
+
+    prune::save_config(&config)?;
+
+    crate::server::jobstate::remove_state_file("prunejob", &id)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_PRUNE_JOB)
+    .put(&API_METHOD_UPDATE_PRUNE_JOB)
+    .delete(&API_METHOD_DELETE_PRUNE_JOB);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_PRUNE_JOBS)
+    .post(&API_METHOD_CREATE_PRUNE_JOB)
+    .match_all("id", &ITEM_ROUTER);
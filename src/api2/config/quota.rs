@@ -0,0 +1,384 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, Permission, Router, RpcEnvironment};
+use proxmox::api::section_config::SectionConfigData;
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+
+use crate::config::acl::{
+    PRIV_DATASTORE_AUDIT,
+    PRIV_DATASTORE_MODIFY,
+};
+
+use crate::config::cached_user_info::CachedUserInfo;
+
+use crate::config::quota::{
+    self,
+    QuotaConfig,
+    QuotaSubject,
+    QUOTA_GRACE_PERIOD_SCHEMA,
+    QUOTA_HARD_LIMIT_SCHEMA,
+    QUOTA_ID_SCHEMA,
+    QUOTA_SOFT_LIMIT_SCHEMA,
+    QUOTA_SUBJECT_SCHEMA,
+};
+
+// There can only be a single quota per subject and datastore
+fn check_unique_subject(config: &SectionConfigData, quota: &QuotaConfig) -> Result<(), Error> {
+    for other in quota::store_quotas(config, &quota.store)? {
+        if other.id != quota.id && other.subject == quota.subject {
+            bail!(
+                "quota '{}' already limits '{}' on datastore '{}'",
+                other.id,
+                quota.subject,
+                quota.store,
+            );
+        }
+    }
+    Ok(())
+}
+
+// Store the subject in its canonical form, so that it can be compared to the usage entries
+fn normalize_subject(subject: &str) -> Result<String, Error> {
+    Ok(subject.parse::<QuotaSubject>()?.to_string())
+}
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "List configured quotas.",
+        type: Array,
+        items: { type: QuotaConfig },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Modify on datastore.",
+    },
+)]
+/// List all datastore quotas
+pub fn list_quotas(
+    _param: Value,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<QuotaConfig>, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let required_privs = PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_MODIFY;
+
+    let (config, digest) = quota::config()?;
+
+    let list = config.convert_to_typed_array("quota")?;
+
+    let list = list.into_iter()
+        .filter(|quota: &QuotaConfig| {
+            let privs = user_info.lookup_privs(&auth_id, &["datastore", &quota.store]);
+
+            privs & required_privs != 0
+        }).collect();
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            subject: {
+                schema: QUOTA_SUBJECT_SCHEMA,
+            },
+            "soft-limit": {
+                optional: true,
+                schema: QUOTA_SOFT_LIMIT_SCHEMA,
+            },
+            "hard-limit": {
+                optional: true,
+                schema: QUOTA_HARD_LIMIT_SCHEMA,
+            },
+            "grace-period": {
+                optional: true,
+                schema: QUOTA_GRACE_PERIOD_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+        }
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the quota's datastore.",
+    },
+)]
+/// Create a new datastore quota.
+pub fn create_quota(
+    param: Value,
+    rpcenv: &mut dyn RpcEnvironment
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let mut quota: QuotaConfig = serde_json::from_value(param)?;
+
+    user_info.check_privs(&auth_id, &["datastore", &quota.store], PRIV_DATASTORE_MODIFY, false)?;
+
+    quota.subject = normalize_subject(&quota.subject)?;
+
+    let _lock = open_file_locked(quota::QUOTA_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, _digest) = quota::config()?;
+
+    if config.sections.get(&quota.id).is_some() {
+        bail!("quota '{}' already exists.", quota.id);
+    }
+
+    check_unique_subject(&config, &quota)?;
+
+    config.set_data(&quota.id, "quota", &quota)?;
+
+    quota::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: QuotaConfig },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Audit or Datastore.Modify on the quota's datastore.",
+    },
+)]
+/// Read a datastore quota.
+pub fn read_quota(
+    id: String,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<QuotaConfig, Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let (config, digest) = quota::config()?;
+
+    let quota: QuotaConfig = config.lookup("quota", &id)?;
+
+    let required_privs = PRIV_DATASTORE_AUDIT | PRIV_DATASTORE_MODIFY;
+    user_info.check_privs(&auth_id, &["datastore", &quota.store], required_privs, true)?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+
+    Ok(quota)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the soft limit.
+    SoftLimit,
+    /// Delete the hard limit.
+    HardLimit,
+    /// Delete the grace period (reset to default).
+    GracePeriod,
+    /// Delete the comment property.
+    Comment,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+            store: {
+                optional: true,
+                schema: DATASTORE_SCHEMA,
+            },
+            subject: {
+                optional: true,
+                schema: QUOTA_SUBJECT_SCHEMA,
+            },
+            "soft-limit": {
+                optional: true,
+                schema: QUOTA_SOFT_LIMIT_SCHEMA,
+            },
+            "hard-limit": {
+                optional: true,
+                schema: QUOTA_HARD_LIMIT_SCHEMA,
+            },
+            "grace-period": {
+                optional: true,
+                schema: QUOTA_GRACE_PERIOD_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the quota's datastore.",
+    },
+)]
+/// Update a datastore quota.
+#[allow(clippy::too_many_arguments)]
+pub fn update_quota(
+    id: String,
+    store: Option<String>,
+    subject: Option<String>,
+    soft_limit: Option<u64>,
+    hard_limit: Option<u64>,
+    grace_period: Option<u64>,
+    comment: Option<String>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let _lock = open_file_locked(quota::QUOTA_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    // pass/compare digest
+    let (mut config, expected_digest) = quota::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: QuotaConfig = config.lookup("quota", &id)?;
+
+    // check existing store
+    user_info.check_privs(&auth_id, &["datastore", &data.store], PRIV_DATASTORE_MODIFY, true)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::SoftLimit => { data.soft_limit = None; },
+                DeletableProperty::HardLimit => { data.hard_limit = None; },
+                DeletableProperty::GracePeriod => { data.grace_period = None; },
+                DeletableProperty::Comment => { data.comment = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+
+    if let Some(store) = store {
+        // check new store
+        user_info.check_privs(&auth_id, &["datastore", &store], PRIV_DATASTORE_MODIFY, true)?;
+        data.store = store;
+    }
+
+    if let Some(subject) = subject { data.subject = normalize_subject(&subject)?; }
+    if soft_limit.is_some() { data.soft_limit = soft_limit; }
+    if hard_limit.is_some() { data.hard_limit = hard_limit; }
+    if grace_period.is_some() { data.grace_period = grace_period; }
+
+    check_unique_subject(&config, &data)?;
+
+    config.set_data(&id, "quota", &data)?;
+
+    quota::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: QUOTA_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Anybody,
+        description: "Requires Datastore.Modify on the quota's datastore.",
+    },
+)]
+/// Remove a datastore quota.
+pub fn delete_quota(
+    id: String,
+    digest: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<(), Error> {
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let user_info = CachedUserInfo::new()?;
+
+    let _lock = open_file_locked(quota::QUOTA_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = quota::config()?;
+
+    let quota: QuotaConfig = config.lookup("quota", &id)?;
+    user_info.check_privs(&auth_id, &["datastore", &quota.store], PRIV_DATASTORE_MODIFY, true)?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&id) {
+        Some(_) => { config.sections.remove(&id); },
+        None => bail!("quota '{}' does not exist.", id),
+    }
+
+    quota::save_config(&config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_QUOTA)
+    .put(&API_METHOD_UPDATE_QUOTA)
+    .delete(&API_METHOD_DELETE_QUOTA);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_QUOTAS)
+    .post(&API_METHOD_CREATE_QUOTA)
+    .match_all("id", &ITEM_ROUTER);
@@ -85,6 +85,22 @@ pub fn list_remotes(
                 optional: true,
                 schema: CERT_FINGERPRINT_SHA256_SCHEMA,
             },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
         },
     },
     access: {
@@ -150,6 +166,14 @@ pub enum DeletableProperty {
     fingerprint,
     /// Delete the port property.
     port,
+    /// Delete the rate-in property.
+    rate_in,
+    /// Delete the burst-in property.
+    burst_in,
+    /// Delete the rate-out property.
+    rate_out,
+    /// Delete the burst-out property.
+    burst_out,
 }
 
 #[api(
@@ -184,6 +208,22 @@ pub enum DeletableProperty {
                 optional: true,
                 schema: CERT_FINGERPRINT_SHA256_SCHEMA,
             },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
             delete: {
                 description: "List of properties to delete.",
                 type: Array,
@@ -212,6 +252,10 @@ pub fn update_remote(
     auth_id: Option<Authid>,
     password: Option<String>,
     fingerprint: Option<String>,
+    rate_in: Option<u64>,
+    burst_in: Option<u64>,
+    rate_out: Option<u64>,
+    burst_out: Option<u64>,
     delete: Option<Vec<DeletableProperty>>,
     digest: Option<String>,
 ) -> Result<(), Error> {
@@ -233,6 +277,10 @@ pub fn update_remote(
                 DeletableProperty::comment => { data.comment = None; },
                 DeletableProperty::fingerprint => { data.fingerprint = None; },
                 DeletableProperty::port => { data.port = None; },
+                DeletableProperty::rate_in => { data.rate_in = None; },
+                DeletableProperty::burst_in => { data.burst_in = None; },
+                DeletableProperty::rate_out => { data.rate_out = None; },
+                DeletableProperty::burst_out => { data.burst_out = None; },
             }
         }
     }
@@ -251,6 +299,10 @@ pub fn update_remote(
     if let Some(password) = password { data.password = password; }
 
     if let Some(fingerprint) = fingerprint { data.fingerprint = Some(fingerprint); }
+    if rate_in.is_some() { data.rate_in = rate_in; }
+    if burst_in.is_some() { data.burst_in = burst_in; }
+    if rate_out.is_some() { data.rate_out = rate_out; }
+    if burst_out.is_some() { data.burst_out = burst_out; }
 
     config.set_data(&name, "remote", &data)?;
 
@@ -310,8 +362,19 @@ pub fn delete_remote(name: String, digest: Option<String>) -> Result<(), Error>
 }
 
 /// Helper to get client for remote.cfg entry
-pub async fn remote_client(remote: remote::Remote) -> Result<HttpClient, Error> {
-    let options = HttpClientOptions::new_non_interactive(remote.password.clone(), remote.fingerprint.clone());
+///
+/// The rate limits of the remote are used for all directions not configured in `limit`.
+pub async fn remote_client(
+    remote: remote::Remote,
+    limit: Option<RateLimitConfig>,
+) -> Result<HttpClient, Error> {
+    let rate_limit = match limit {
+        Some(limit) => limit.or(&remote.rate_limit()),
+        None => remote.rate_limit(),
+    };
+
+    let options = HttpClientOptions::new_non_interactive(remote.password.clone(), remote.fingerprint.clone())
+        .rate_limit(rate_limit);
 
     let client = HttpClient::new(
         &remote.host,
@@ -355,7 +418,7 @@ pub async fn scan_remote_datastores(name: String) -> Result<Vec<DataStoreListIte
                   api_err)
     };
 
-    let client = remote_client(remote)
+    let client = remote_client(remote, None)
         .await
         .map_err(map_remote_err)?;
     let api_res = client
@@ -0,0 +1,337 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, ApiMethod, Router, RpcEnvironment, Permission};
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::s3::{
+    self,
+    S3ClientConfig,
+    S3_ACCESS_KEY_SCHEMA,
+    S3_REGION_SCHEMA,
+    S3_SECRET_KEY_SCHEMA,
+};
+use crate::config::acl::{PRIV_SYS_AUDIT, PRIV_SYS_MODIFY};
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "The list of configured S3 clients (with config digest).",
+        type: Array,
+        items: { type: S3ClientConfig },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "s3"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List S3 client configurations
+pub fn list_s3_clients(
+    _param: Value,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<S3ClientConfig>, Error> {
+    let (config, digest) = s3::config()?;
+
+    let mut list: Vec<S3ClientConfig> = config.convert_to_typed_array("s3")?;
+    // don't return secret keys
+    for client in &mut list {
+        client.secret_key = "".to_string();
+    }
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: S3_CLIENT_ID_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            endpoint: {
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "The (optional) port.",
+                type: u16,
+                optional: true,
+            },
+            region: {
+                optional: true,
+                schema: S3_REGION_SCHEMA,
+            },
+            "access-key": {
+                schema: S3_ACCESS_KEY_SCHEMA,
+            },
+            "secret-key": {
+                schema: S3_SECRET_KEY_SCHEMA,
+            },
+            fingerprint: {
+                optional: true,
+                schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+            },
+            "plain-http": {
+                optional: true,
+                description: "Use plain HTTP instead of HTTPS (only for local test setups).",
+                type: bool,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "s3"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create new S3 client configuration.
+pub fn create_s3_client(secret_key: String, param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(s3::S3_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let mut data = param;
+    data["secret-key"] = Value::from(base64::encode(secret_key.as_bytes()));
+    let client: S3ClientConfig = serde_json::from_value(data)?;
+
+    let (mut config, _digest) = s3::config()?;
+
+    if config.sections.get(&client.id).is_some() {
+        bail!("S3 client '{}' already exists.", client.id);
+    }
+
+    config.set_data(&client.id, "s3", &client)?;
+
+    s3::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            id: {
+                schema: S3_CLIENT_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: S3ClientConfig },
+    access: {
+        permission: &Permission::Privilege(&["system", "s3"], PRIV_SYS_AUDIT, false),
+    }
+)]
+/// Read S3 client configuration.
+pub fn read_s3_client(
+    id: String,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<S3ClientConfig, Error> {
+    let (config, digest) = s3::config()?;
+    let mut data: S3ClientConfig = config.lookup("s3", &id)?;
+    data.secret_key = "".to_string(); // do not return secret key in api
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the comment property.
+    comment,
+    /// Delete the port property.
+    port,
+    /// Delete the region property.
+    region,
+    /// Delete the fingerprint property.
+    fingerprint,
+    /// Delete the plain-http property.
+    plain_http,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: S3_CLIENT_ID_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            endpoint: {
+                optional: true,
+                schema: DNS_NAME_OR_IP_SCHEMA,
+            },
+            port: {
+                description: "The (optional) port.",
+                type: u16,
+                optional: true,
+            },
+            region: {
+                optional: true,
+                schema: S3_REGION_SCHEMA,
+            },
+            "access-key": {
+                optional: true,
+                schema: S3_ACCESS_KEY_SCHEMA,
+            },
+            "secret-key": {
+                optional: true,
+                schema: S3_SECRET_KEY_SCHEMA,
+            },
+            fingerprint: {
+                optional: true,
+                schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+            },
+            "plain-http": {
+                optional: true,
+                description: "Use plain HTTP instead of HTTPS (only for local test setups).",
+                type: bool,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "s3"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update S3 client configuration.
+#[allow(clippy::too_many_arguments)]
+pub fn update_s3_client(
+    id: String,
+    comment: Option<String>,
+    endpoint: Option<String>,
+    port: Option<u16>,
+    region: Option<String>,
+    access_key: Option<String>,
+    secret_key: Option<String>,
+    fingerprint: Option<String>,
+    plain_http: Option<bool>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(s3::S3_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = s3::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: S3ClientConfig = config.lookup("s3", &id)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::comment => { data.comment = None; },
+                DeletableProperty::port => { data.port = None; },
+                DeletableProperty::region => { data.region = None; },
+                DeletableProperty::fingerprint => { data.fingerprint = None; },
+                DeletableProperty::plain_http => { data.plain_http = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(endpoint) = endpoint { data.endpoint = endpoint; }
+    if port.is_some() { data.port = port; }
+    if region.is_some() { data.region = region; }
+    if let Some(access_key) = access_key { data.access_key = access_key; }
+    if let Some(secret_key) = secret_key { data.secret_key = secret_key; }
+    if fingerprint.is_some() { data.fingerprint = fingerprint; }
+    if plain_http.is_some() { data.plain_http = plain_http; }
+
+    config.set_data(&id, "s3", &data)?;
+
+    s3::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            id: {
+                schema: S3_CLIENT_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "s3"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a S3 client configuration.
+pub fn delete_s3_client(id: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = open_file_locked(s3::S3_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = s3::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let (datastore_config, _digest) = crate::config::datastore::config()?;
+    let datastores: Vec<crate::config::datastore::DataStoreConfig> =
+        datastore_config.convert_to_typed_array("datastore")?;
+    for store in datastores {
+        if let Some(backend) = store.get_backend()? {
+            if backend.client.as_deref() == Some(id.as_str()) {
+                bail!("S3 client '{}' is used by datastore '{}'", id, store.name);
+            }
+        }
+    }
+
+    match config.sections.get(&id) {
+        Some(_) => { config.sections.remove(&id); },
+        None => bail!("S3 client '{}' does not exist.", id),
+    }
+
+    s3::save_config(&config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_S3_CLIENT)
+    .put(&API_METHOD_UPDATE_S3_CLIENT)
+    .delete(&API_METHOD_DELETE_S3_CLIENT);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_S3_CLIENTS)
+    .post(&API_METHOD_CREATE_S3_CLIENT)
+    .match_all("id", &ITEM_ROUTER);
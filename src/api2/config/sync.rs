@@ -12,19 +12,24 @@ use crate::config::acl::{
     PRIV_DATASTORE_BACKUP,
     PRIV_DATASTORE_MODIFY,
     PRIV_DATASTORE_PRUNE,
+    PRIV_DATASTORE_READ,
     PRIV_REMOTE_AUDIT,
     PRIV_REMOTE_READ,
+    PRIV_REMOTE_DATASTORE_BACKUP,
+    PRIV_REMOTE_DATASTORE_PRUNE,
 };
 
 use crate::config::cached_user_info::CachedUserInfo;
 use crate::config::sync::{self, SyncJobConfig};
+use crate::backup::BackupNamespace;
 
 pub fn check_sync_job_read_access(
     user_info: &CachedUserInfo,
     auth_id: &Authid,
     job: &SyncJobConfig,
 ) -> bool {
-    let datastore_privs = user_info.lookup_privs(&auth_id, &["datastore", &job.store]);
+    let ns = job.ns.clone().unwrap_or_default();
+    let datastore_privs = user_info.lookup_privs(&auth_id, &ns.acl_path(&job.store));
     if datastore_privs & PRIV_DATASTORE_AUDIT == 0 {
         return false;
     }
@@ -33,13 +38,18 @@ pub fn check_sync_job_read_access(
     remote_privs & PRIV_REMOTE_AUDIT != 0
 }
 
-// user can run the corresponding pull job
+// user can run the corresponding pull or push job
 pub fn check_sync_job_modify_access(
     user_info: &CachedUserInfo,
     auth_id: &Authid,
     job: &SyncJobConfig,
 ) -> bool {
-    let datastore_privs = user_info.lookup_privs(&auth_id, &["datastore", &job.store]);
+    if job.sync_direction.unwrap_or_default() == SyncDirection::Push {
+        return check_push_job_modify_access(user_info, auth_id, job);
+    }
+
+    let ns = job.ns.clone().unwrap_or_default();
+    let datastore_privs = user_info.lookup_privs(&auth_id, &ns.acl_path(&job.store));
     if datastore_privs & PRIV_DATASTORE_BACKUP == 0 {
         return false;
     }
@@ -70,6 +80,30 @@ pub fn check_sync_job_modify_access(
     remote_privs & PRIV_REMOTE_READ != 0
 }
 
+// user can read all local backups, and create (and prune) snapshots on the remote
+fn check_push_job_modify_access(
+    user_info: &CachedUserInfo,
+    auth_id: &Authid,
+    job: &SyncJobConfig,
+) -> bool {
+    let ns = job.ns.clone().unwrap_or_default();
+    let datastore_privs = user_info.lookup_privs(&auth_id, &ns.acl_path(&job.store));
+    if datastore_privs & PRIV_DATASTORE_READ == 0 {
+        return false;
+    }
+
+    let remote_privs = user_info.lookup_privs(&auth_id, &["remote", &job.remote, &job.remote_store]);
+    if remote_privs & PRIV_REMOTE_DATASTORE_BACKUP == 0 {
+        return false;
+    }
+
+    if job.remove_vanished.unwrap_or(true) {
+        return remote_privs & PRIV_REMOTE_DATASTORE_PRUNE != 0;
+    }
+
+    true
+}
+
 #[api(
     input: {
         properties: {},
@@ -115,6 +149,10 @@ pub fn list_sync_jobs(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             owner: {
                 type: Authid,
                 optional: true,
@@ -122,13 +160,49 @@ pub fn list_sync_jobs(
             remote: {
                 schema: REMOTE_ID_SCHEMA,
             },
+            "sync-direction": {
+                type: SyncDirection,
+                optional: true,
+            },
             "remote-store": {
                 schema: DATASTORE_SCHEMA,
             },
+            "remote-ns": {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
             "remove-vanished": {
                 schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
                 optional: true,
             },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "transfer-last": {
+                schema: TRANSFER_LAST_SCHEMA,
+                optional: true,
+            },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
             comment: {
                 optional: true,
                 schema: SINGLE_LINE_COMMENT_SCHEMA,
@@ -140,7 +214,7 @@ pub fn list_sync_jobs(
         },
     },
     access: {
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "For pull jobs, the user needs Datastore.Backup on the target datastore, and Remote.Read on the source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify. For push jobs, the user needs Datastore.Read on the source datastore, and Remote.DatastoreBackup on the target remote (and Remote.DatastorePrune for remove_vanished).",
         permission: &Permission::Anybody,
     },
 )]
@@ -222,6 +296,26 @@ pub enum DeletableProperty {
     schedule,
     /// Delete the remove-vanished flag.
     remove_vanished,
+    /// Delete the target namespace.
+    ns,
+    /// Delete the remote namespace.
+    remote_ns,
+    /// Delete the max-depth property.
+    max_depth,
+    /// Delete the sync-direction property.
+    sync_direction,
+    /// Delete the group-filter property.
+    group_filter,
+    /// Delete the transfer-last property.
+    transfer_last,
+    /// Delete the rate-in property.
+    rate_in,
+    /// Delete the burst-in property.
+    burst_in,
+    /// Delete the rate-out property.
+    rate_out,
+    /// Delete the burst-out property.
+    burst_out,
 }
 
 #[api(
@@ -235,6 +329,10 @@ pub enum DeletableProperty {
                 schema: DATASTORE_SCHEMA,
                 optional: true,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             owner: {
                 type: Authid,
                 optional: true,
@@ -243,14 +341,50 @@ pub enum DeletableProperty {
                 schema: REMOTE_ID_SCHEMA,
                 optional: true,
             },
+            "sync-direction": {
+                type: SyncDirection,
+                optional: true,
+            },
             "remote-store": {
                 schema: DATASTORE_SCHEMA,
                 optional: true,
             },
+            "remote-ns": {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
             "remove-vanished": {
                 schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
                 optional: true,
             },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "transfer-last": {
+                schema: TRANSFER_LAST_SCHEMA,
+                optional: true,
+            },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
             comment: {
                 optional: true,
                 schema: SINGLE_LINE_COMMENT_SCHEMA,
@@ -275,7 +409,7 @@ pub enum DeletableProperty {
     },
     access: {
         permission: &Permission::Anybody,
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "For pull jobs, the user needs Datastore.Backup on the target datastore, and Remote.Read on the source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify. For push jobs, the user needs Datastore.Read on the source datastore, and Remote.DatastoreBackup on the target remote (and Remote.DatastorePrune for remove_vanished).",
     },
 )]
 /// Update sync job config.
@@ -283,10 +417,20 @@ pub enum DeletableProperty {
 pub fn update_sync_job(
     id: String,
     store: Option<String>,
+    ns: Option<BackupNamespace>,
     owner: Option<Authid>,
     remote: Option<String>,
+    sync_direction: Option<SyncDirection>,
     remote_store: Option<String>,
+    remote_ns: Option<BackupNamespace>,
+    max_depth: Option<usize>,
     remove_vanished: Option<bool>,
+    group_filter: Option<String>,
+    transfer_last: Option<usize>,
+    rate_in: Option<u64>,
+    burst_in: Option<u64>,
+    rate_out: Option<u64>,
+    burst_out: Option<u64>,
     comment: Option<String>,
     schedule: Option<String>,
     delete: Option<Vec<DeletableProperty>>,
@@ -315,6 +459,16 @@ pub fn update_sync_job(
                 DeletableProperty::comment => { data.comment = None; },
                 DeletableProperty::schedule => { data.schedule = None; },
                 DeletableProperty::remove_vanished => { data.remove_vanished = None; },
+                DeletableProperty::ns => { data.ns = None; },
+                DeletableProperty::remote_ns => { data.remote_ns = None; },
+                DeletableProperty::max_depth => { data.max_depth = None; },
+                DeletableProperty::sync_direction => { data.sync_direction = None; },
+                DeletableProperty::group_filter => { data.group_filter = None; },
+                DeletableProperty::transfer_last => { data.transfer_last = None; },
+                DeletableProperty::rate_in => { data.rate_in = None; },
+                DeletableProperty::burst_in => { data.burst_in = None; },
+                DeletableProperty::rate_out => { data.rate_out = None; },
+                DeletableProperty::burst_out => { data.burst_out = None; },
             }
         }
     }
@@ -330,11 +484,21 @@ pub fn update_sync_job(
 
     if let Some(store) = store { data.store = store; }
     if let Some(remote) = remote { data.remote = remote; }
+    if sync_direction.is_some() { data.sync_direction = sync_direction; }
     if let Some(remote_store) = remote_store { data.remote_store = remote_store; }
     if let Some(owner) = owner { data.owner = Some(owner); }
+    if ns.is_some() { data.ns = ns; }
+    if remote_ns.is_some() { data.remote_ns = remote_ns; }
+    if max_depth.is_some() { data.max_depth = max_depth; }
 
     if schedule.is_some() { data.schedule = schedule; }
     if remove_vanished.is_some() { data.remove_vanished = remove_vanished; }
+    if group_filter.is_some() { data.group_filter = group_filter; }
+    if transfer_last.is_some() { data.transfer_last = transfer_last; }
+    if rate_in.is_some() { data.rate_in = rate_in; }
+    if burst_in.is_some() { data.burst_in = burst_in; }
+    if rate_out.is_some() { data.rate_out = rate_out; }
+    if burst_out.is_some() { data.burst_out = burst_out; }
 
     if !check_sync_job_modify_access(&user_info, &auth_id, &data) {
         bail!("permission check failed");
@@ -362,7 +526,7 @@ pub fn update_sync_job(
     },
     access: {
         permission: &Permission::Anybody,
-        description: "User needs Datastore.Backup on target datastore, and Remote.Read on source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify",
+        description: "For pull jobs, the user needs Datastore.Backup on the target datastore, and Remote.Read on the source remote. Additionally, remove_vanished requires Datastore.Prune, and any owner other than the user themselves requires Datastore.Modify. For push jobs, the user needs Datastore.Read on the source datastore, and Remote.DatastoreBackup on the target remote (and Remote.DatastorePrune for remove_vanished).",
     },
 )]
 /// Remove a sync job configuration
@@ -420,6 +584,8 @@ user: read@pbs
 
 user: write@pbs
 
+user: push@pbs
+
 "###).expect("test user.cfg is not parsable");
     let acl_tree = crate::config::acl::AclTree::from_raw(r###"
 acl:1:/datastore/localstore1:read@pbs,write@pbs:DatastoreAudit
@@ -428,6 +594,9 @@ acl:1:/datastore/localstore2:write@pbs:DatastorePowerUser
 acl:1:/datastore/localstore3:write@pbs:DatastoreAdmin
 acl:1:/remote/remote1:read@pbs,write@pbs:RemoteAudit
 acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
+acl:1:/datastore/localstore4:push@pbs:DatastoreReader
+acl:1:/remote/remote2:push@pbs:RemoteAudit
+acl:1:/remote/remote2/remotestore1:push@pbs:RemoteSyncPushOperator
 "###).expect("test acl.cfg is not parsable");
 
     let user_info = CachedUserInfo::test_new(user_cfg, acl_tree);
@@ -441,11 +610,21 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
     let mut job = SyncJobConfig {
         id: "regular".to_string(),
         remote: "remote0".to_string(),
+        sync_direction: None,
         remote_store: "remotestore1".to_string(),
         store: "localstore0".to_string(),
+        ns: None,
         owner: Some(write_auth_id.clone()),
+        remote_ns: None,
+        max_depth: None,
         comment: None,
         remove_vanished: None,
+        group_filter: None,
+        transfer_last: None,
+        rate_in: None,
+        burst_in: None,
+        rate_out: None,
+        burst_out: None,
         schedule: None,
     };
 
@@ -520,5 +699,18 @@ acl:1:/remote/remote1/remotestore1:write@pbs:RemoteSyncOperator
     job.owner = None;
     assert_eq!(check_sync_job_modify_access(&user_info, &write_auth_id, &job), true);
 
+    // push jobs need read access on the local and backup access on the remote end
+    let push_auth_id: Authid = "push@pbs".parse()?;
+    job.sync_direction = Some(SyncDirection::Push);
+    job.remove_vanished = Some(false);
+    assert_eq!(check_sync_job_modify_access(&user_info, &write_auth_id, &job), false);
+    assert_eq!(check_sync_job_modify_access(&user_info, &push_auth_id, &job), false);
+    job.store = "localstore4".to_string();
+    assert_eq!(check_sync_job_modify_access(&user_info, &push_auth_id, &job), false);
+    job.remote = "remote2".to_string();
+    assert_eq!(check_sync_job_modify_access(&user_info, &push_auth_id, &job), true);
+    job.remove_vanished = None;
+    assert_eq!(check_sync_job_modify_access(&user_info, &push_auth_id, &job), true);
+
     Ok(())
 }
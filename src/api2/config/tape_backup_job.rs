@@ -11,12 +11,16 @@ use crate::{
         Userid,
         JOB_ID_SCHEMA,
         DATASTORE_SCHEMA,
+        BACKUP_NAMESPACE_SCHEMA,
+        NS_MAX_DEPTH_SCHEMA,
+        GROUP_FILTER_LIST_SCHEMA,
         DRIVE_NAME_SCHEMA,
         PROXMOX_CONFIG_DIGEST_SCHEMA,
         SINGLE_LINE_COMMENT_SCHEMA,
         MEDIA_POOL_NAME_SCHEMA,
         SYNC_SCHEDULE_SCHEMA,
     },
+    backup::BackupNamespace,
     config::{
         self,
         cached_user_info::CachedUserInfo,
@@ -152,6 +156,12 @@ pub enum DeletableProperty {
     LatestOnly,
     /// Delete the 'notify-user' property
     NotifyUser,
+    /// Delete the 'ns' property
+    Ns,
+    /// Delete the 'max-depth' property
+    MaxDepth,
+    /// Delete the 'group-filter' property
+    GroupFilter,
 }
 
 #[api(
@@ -192,6 +202,18 @@ pub enum DeletableProperty {
                 optional: true,
                 type: Userid,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
             comment: {
                 optional: true,
                 schema: SINGLE_LINE_COMMENT_SCHEMA,
@@ -219,6 +241,7 @@ pub enum DeletableProperty {
     },
 )]
 /// Update the tape backup job
+#[allow(clippy::too_many_arguments)]
 pub fn update_tape_backup_job(
     id: String,
     store: Option<String>,
@@ -228,6 +251,9 @@ pub fn update_tape_backup_job(
     export_media_set: Option<bool>,
     latest_only: Option<bool>,
     notify_user: Option<Userid>,
+    ns: Option<BackupNamespace>,
+    max_depth: Option<usize>,
+    group_filter: Option<String>,
     comment: Option<String>,
     schedule: Option<String>,
     delete: Option<Vec<DeletableProperty>>,
@@ -251,6 +277,9 @@ pub fn update_tape_backup_job(
                 DeletableProperty::ExportMediaSet => { data.setup.export_media_set = None; },
                 DeletableProperty::LatestOnly => { data.setup.latest_only = None; },
                 DeletableProperty::NotifyUser => { data.setup.notify_user = None; },
+                DeletableProperty::Ns => { data.setup.ns = None; },
+                DeletableProperty::MaxDepth => { data.setup.max_depth = None; },
+                DeletableProperty::GroupFilter => { data.setup.group_filter = None; },
                 DeletableProperty::Schedule => { data.schedule = None; },
                 DeletableProperty::Comment => { data.comment = None; },
             }
@@ -265,6 +294,9 @@ pub fn update_tape_backup_job(
     if export_media_set.is_some() { data.setup.export_media_set = export_media_set; }
     if latest_only.is_some() { data.setup.latest_only = latest_only; }
     if notify_user.is_some() { data.setup.notify_user = notify_user; }
+    if ns.is_some() { data.setup.ns = ns; }
+    if max_depth.is_some() { data.setup.max_depth = max_depth; }
+    if group_filter.is_some() { data.setup.group_filter = group_filter; }
 
     if schedule.is_some() { data.schedule = schedule; }
 
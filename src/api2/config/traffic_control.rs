@@ -0,0 +1,296 @@
+use anyhow::{bail, Error};
+use serde_json::Value;
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::{api, ApiMethod, Router, RpcEnvironment, Permission};
+use proxmox::tools::fs::open_file_locked;
+
+use crate::api2::types::*;
+use crate::config::traffic_control::{
+    self,
+    TrafficControlRule,
+    NETWORK_LIST_SCHEMA,
+    TRAFFIC_CONTROL_ID_SCHEMA,
+};
+use crate::config::acl::{PRIV_SYS_AUDIT, PRIV_SYS_MODIFY};
+
+#[api(
+    input: {
+        properties: {},
+    },
+    returns: {
+        description: "The list of configured traffic control rules (with config digest).",
+        type: Array,
+        items: { type: TrafficControlRule },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "traffic-control"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// List traffic control rules
+pub fn list_traffic_controls(
+    _param: Value,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<Vec<TrafficControlRule>, Error> {
+    let (config, digest) = traffic_control::config()?;
+
+    let list: Vec<TrafficControlRule> = config.convert_to_typed_array("rule")?;
+
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(list)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: TRAFFIC_CONTROL_ID_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            network: {
+                schema: NETWORK_LIST_SCHEMA,
+            },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "traffic-control"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Create new traffic control rule.
+pub fn create_traffic_control(param: Value) -> Result<(), Error> {
+
+    let _lock = open_file_locked(traffic_control::TRAFFIC_CONTROL_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let rule: TrafficControlRule = serde_json::from_value(param)?;
+
+    let (mut config, _digest) = traffic_control::config()?;
+
+    if config.sections.get(&rule.name).is_some() {
+        bail!("traffic control rule '{}' already exists.", rule.name);
+    }
+
+    config.set_data(&rule.name, "rule", &rule)?;
+
+    traffic_control::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+   input: {
+        properties: {
+            name: {
+                schema: TRAFFIC_CONTROL_ID_SCHEMA,
+            },
+        },
+    },
+    returns: { type: TrafficControlRule },
+    access: {
+        permission: &Permission::Privilege(&["system", "traffic-control"], PRIV_SYS_AUDIT, false),
+    }
+)]
+/// Read traffic control rule.
+pub fn read_traffic_control(
+    name: String,
+    _info: &ApiMethod,
+    mut rpcenv: &mut dyn RpcEnvironment,
+) -> Result<TrafficControlRule, Error> {
+    let (config, digest) = traffic_control::config()?;
+    let data: TrafficControlRule = config.lookup("rule", &name)?;
+    rpcenv["digest"] = proxmox::tools::digest_to_hex(&digest).into();
+    Ok(data)
+}
+
+#[api()]
+#[derive(Serialize, Deserialize)]
+#[allow(non_camel_case_types)]
+/// Deletable property name
+pub enum DeletableProperty {
+    /// Delete the comment property.
+    comment,
+    /// Delete the rate-in property.
+    rate_in,
+    /// Delete the burst-in property.
+    burst_in,
+    /// Delete the rate-out property.
+    rate_out,
+    /// Delete the burst-out property.
+    burst_out,
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: TRAFFIC_CONTROL_ID_SCHEMA,
+            },
+            comment: {
+                optional: true,
+                schema: SINGLE_LINE_COMMENT_SCHEMA,
+            },
+            network: {
+                optional: true,
+                schema: NETWORK_LIST_SCHEMA,
+            },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            delete: {
+                description: "List of properties to delete.",
+                type: Array,
+                optional: true,
+                items: {
+                    type: DeletableProperty,
+                }
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "traffic-control"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Update traffic control rule.
+#[allow(clippy::too_many_arguments)]
+pub fn update_traffic_control(
+    name: String,
+    comment: Option<String>,
+    network: Option<String>,
+    rate_in: Option<u64>,
+    burst_in: Option<u64>,
+    rate_out: Option<u64>,
+    burst_out: Option<u64>,
+    delete: Option<Vec<DeletableProperty>>,
+    digest: Option<String>,
+) -> Result<(), Error> {
+
+    let _lock = open_file_locked(traffic_control::TRAFFIC_CONTROL_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = traffic_control::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    let mut data: TrafficControlRule = config.lookup("rule", &name)?;
+
+    if let Some(delete) = delete {
+        for delete_prop in delete {
+            match delete_prop {
+                DeletableProperty::comment => { data.comment = None; },
+                DeletableProperty::rate_in => { data.rate_in = None; },
+                DeletableProperty::burst_in => { data.burst_in = None; },
+                DeletableProperty::rate_out => { data.rate_out = None; },
+                DeletableProperty::burst_out => { data.burst_out = None; },
+            }
+        }
+    }
+
+    if let Some(comment) = comment {
+        let comment = comment.trim().to_string();
+        if comment.is_empty() {
+            data.comment = None;
+        } else {
+            data.comment = Some(comment);
+        }
+    }
+    if let Some(network) = network { data.network = network; }
+    if rate_in.is_some() { data.rate_in = rate_in; }
+    if burst_in.is_some() { data.burst_in = burst_in; }
+    if rate_out.is_some() { data.rate_out = rate_out; }
+    if burst_out.is_some() { data.burst_out = burst_out; }
+
+    config.set_data(&name, "rule", &data)?;
+
+    traffic_control::save_config(&config)?;
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            name: {
+                schema: TRAFFIC_CONTROL_ID_SCHEMA,
+            },
+            digest: {
+                optional: true,
+                schema: PROXMOX_CONFIG_DIGEST_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "traffic-control"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Remove a traffic control rule from the configuration file.
+pub fn delete_traffic_control(name: String, digest: Option<String>) -> Result<(), Error> {
+
+    let _lock = open_file_locked(traffic_control::TRAFFIC_CONTROL_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+
+    let (mut config, expected_digest) = traffic_control::config()?;
+
+    if let Some(ref digest) = digest {
+        let digest = proxmox::tools::hex_to_digest(digest)?;
+        crate::tools::detect_modified_configuration_file(&digest, &expected_digest)?;
+    }
+
+    match config.sections.get(&name) {
+        Some(_) => { config.sections.remove(&name); },
+        None => bail!("traffic control rule '{}' does not exist.", name),
+    }
+
+    traffic_control::save_config(&config)?;
+
+    Ok(())
+}
+
+const ITEM_ROUTER: Router = Router::new()
+    .get(&API_METHOD_READ_TRAFFIC_CONTROL)
+    .put(&API_METHOD_UPDATE_TRAFFIC_CONTROL)
+    .delete(&API_METHOD_DELETE_TRAFFIC_CONTROL);
+
+pub const ROUTER: Router = Router::new()
+    .get(&API_METHOD_LIST_TRAFFIC_CONTROLS)
+    .post(&API_METHOD_CREATE_TRAFFIC_CONTROL)
+    .match_all("name", &ITEM_ROUTER);
@@ -0,0 +1,653 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::rsa::Rsa;
+use openssl::stack::Stack;
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509NameBuilder, X509ReqBuilder};
+use ::serde::{Deserialize, Serialize};
+
+use proxmox::api::router::SubdirMap;
+use proxmox::api::{api, Permission, Router, RpcEnvironment, RpcEnvironmentType};
+use proxmox::list_subdirs_api_method;
+
+use crate::api2::types::*;
+use crate::config::acl::{PRIV_SYS_AUDIT, PRIV_SYS_MODIFY};
+use crate::config::acme::{
+    self,
+    AcmeDomain,
+    DnsPlugin,
+    ACME_ACCOUNT_NAME_SCHEMA,
+    DEFAULT_ACME_ACCOUNT_NAME,
+};
+use crate::server::{acme_challenge, WorkerTask};
+use crate::tools::acme::{dns_01_txt_value, AcmeClient, Authorization, Order};
+use crate::tools::cert::CertInfo;
+use crate::{task_log, task_warn};
+
+/// Certificates are renewed once they expire within this many days.
+pub const ACME_RENEW_DAYS: i64 = 30;
+
+// maximum number of status polls (2 seconds apart) for authorizations and orders
+const MAX_POLL_COUNT: usize = 60;
+
+#[api(
+    properties: {
+        san: {
+            type: Array,
+            items: {
+                description: "Subject alternative name.",
+                type: String,
+            },
+        },
+    },
+)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Information about the proxy certificate
+pub struct CertificateInfo {
+    /// Certificate subject
+    pub subject: String,
+    /// Certificate issuer
+    pub issuer: String,
+    /// Start of the validity period (epoch)
+    pub not_before: i64,
+    /// End of the validity period (epoch)
+    pub not_after: i64,
+    /// SHA256 fingerprint
+    pub fingerprint: String,
+    /// DNS names and IP addresses the certificate is valid for
+    pub san: Vec<String>,
+}
+
+impl CertificateInfo {
+    fn from_cert_info(cert: &CertInfo) -> Result<Self, Error> {
+        Ok(Self {
+            subject: cert.subject_name()?,
+            issuer: cert.issuer_name()?,
+            not_before: cert.not_before_unix()?,
+            not_after: cert.not_after_unix()?,
+            fingerprint: cert.fingerprint()?,
+            san: cert.subject_alt_name_list(),
+        })
+    }
+}
+
+#[api(
+    input: {
+        properties: {
+            node: {
+                schema: NODE_SCHEMA,
+            },
+        },
+    },
+    returns: { type: CertificateInfo },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_AUDIT, false),
+    },
+)]
+/// Get information about the proxy certificate
+pub fn get_info() -> Result<CertificateInfo, Error> {
+    CertificateInfo::from_cert_info(&CertInfo::new()?)
+}
+
+// The proxy certificate is replaced in any case, so only warn if the proxy can't be told
+async fn reload_proxy_certificate() {
+    if let Err(err) = crate::server::reload_proxy_certificate().await {
+        log::warn!("unable to reload the proxy certificate - {}", err);
+    }
+}
+
+// Check that the certificate is currently valid and names the hosts it is valid for.
+fn check_custom_certificate(cert: &CertInfo) -> Result<(), Error> {
+    let now = proxmox::tools::time::epoch_i64();
+
+    if cert.not_before_unix()? > now {
+        bail!("certificate is not valid yet (not before {})", cert.not_before());
+    }
+    if cert.is_expired_after_epoch(now)? {
+        bail!("certificate expired (not after {})", cert.not_after());
+    }
+    // clients ignore the common name nowadays
+    if cert.subject_alt_name_list().is_empty() {
+        bail!("certificate has no subject alternative names (DNS names or IP addresses)");
+    }
+
+    Ok(())
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            node: {
+                schema: NODE_SCHEMA,
+            },
+            certificates: {
+                description: "PEM encoded certificate, optionally followed by its chain.",
+                type: String,
+            },
+            key: {
+                description: "PEM encoded private key. The current key is kept if not given.",
+                type: String,
+                optional: true,
+            },
+        },
+    },
+    returns: { type: CertificateInfo },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Upload a custom certificate and reload the proxy
+///
+/// Established connections (like running backups) keep using the previous certificate.
+pub async fn upload_custom_cert(
+    certificates: String,
+    key: Option<String>,
+) -> Result<CertificateInfo, Error> {
+    let cert = CertInfo::from_pem(certificates.as_bytes())
+        .map_err(|err| format_err!("unable to parse certificate - {}", err))?;
+
+    check_custom_certificate(&cert)?;
+
+    let key = match key {
+        Some(key) => key.into_bytes(),
+        None => proxmox::tools::fs::file_get_contents(configdir!("/proxy.key"))?,
+    };
+
+    // also checks that the key matches the certificate
+    crate::config::set_proxy_certificate(certificates.as_bytes(), &key)?;
+
+    reload_proxy_certificate().await;
+
+    CertificateInfo::from_cert_info(&cert)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            node: {
+                schema: NODE_SCHEMA,
+            },
+        },
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Replace the custom certificate with a newly generated self-signed one
+pub async fn delete_custom_cert() -> Result<(), Error> {
+    crate::config::update_self_signed_cert(true)?;
+
+    reload_proxy_certificate().await;
+
+    Ok(())
+}
+
+// Key (PEM) and DER encoded certificate signing request for `domains`
+fn create_csr(domains: &[String]) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    let key = PKey::from_rsa(Rsa::generate(4096)?)?;
+
+    let mut csr = X509ReqBuilder::new()?;
+
+    // the common name is limited to 64 characters, the names are in the SAN anyway
+    let mut name = X509NameBuilder::new()?;
+    if domains[0].len() <= 64 {
+        name.append_entry_by_text("CN", &domains[0])?;
+    }
+    csr.set_subject_name(&name.build())?;
+    csr.set_pubkey(&key)?;
+
+    let mut alt_names = SubjectAlternativeName::new();
+    for domain in domains {
+        alt_names.dns(domain);
+    }
+    let alt_names = alt_names.build(&csr.x509v3_context(None))?;
+
+    let mut extensions = Stack::new()?;
+    extensions.push(alt_names)?;
+    csr.add_extensions(&extensions)?;
+
+    csr.sign(&key, MessageDigest::sha256())?;
+
+    Ok((key.private_key_to_pem_pkcs8()?, csr.build().to_der()?))
+}
+
+async fn run_dns_hook(
+    plugin: &DnsPlugin,
+    action: &str,
+    domain: &str,
+    record: &str,
+    value: &str,
+) -> Result<(), Error> {
+    let output = tokio::process::Command::new(&plugin.hook)
+        .args(&[action, domain, record, value])
+        .envs(plugin.environment()?)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .map_err(|err| format_err!("unable to execute hook '{}' - {}", plugin.hook, err))?;
+
+    if !output.status.success() {
+        bail!(
+            "hook '{} {}' failed ({}) - {}",
+            plugin.hook,
+            action,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim(),
+        );
+    }
+
+    Ok(())
+}
+
+// A challenge response which needs to be removed again after validation
+enum ChallengeSetup {
+    Http { token: String },
+    Dns { plugin: DnsPlugin, domain: String, record: String, value: String },
+}
+
+impl ChallengeSetup {
+    async fn teardown(&self) -> Result<(), Error> {
+        match self {
+            ChallengeSetup::Http { token } => acme_challenge::remove_http_challenge(token).await,
+            ChallengeSetup::Dns { plugin, domain, record, value } => {
+                run_dns_hook(plugin, "teardown", domain, record, value).await
+            }
+        }
+    }
+}
+
+fn authorization_error(authorization: &Authorization) -> String {
+    authorization.challenges.iter()
+        .find_map(|challenge| challenge.error.as_ref())
+        .map(|error| error.to_string())
+        .unwrap_or_else(|| "unknown error".to_string())
+}
+
+async fn setup_challenges(
+    worker: &WorkerTask,
+    client: &mut AcmeClient,
+    order: &Order,
+    domains: &[(AcmeDomain, Option<DnsPlugin>)],
+    setups: &mut Vec<ChallengeSetup>,
+) -> Result<Vec<(String, String)>, Error> {
+    // (authorization URL, challenge URL)
+    let mut pending = Vec::new();
+
+    for url in &order.authorizations {
+        let authorization = client.get_authorization(url).await?;
+        let identifier = &authorization.identifier.value;
+
+        if authorization.status == "valid" {
+            task_log!(worker, "{} is already validated", identifier);
+            continue;
+        }
+
+        let name = if authorization.wildcard {
+            format!("*.{}", identifier)
+        } else {
+            identifier.to_string()
+        };
+        let (domain, plugin) = domains.iter()
+            .find(|(domain, _)| domain.domain == name)
+            .ok_or_else(|| format_err!("got authorization for unknown domain '{}'", name))?;
+
+        match plugin {
+            Some(plugin) => {
+                let challenge = authorization.challenge("dns-01")
+                    .ok_or_else(|| format_err!("no dns-01 challenge for '{}'", name))?;
+                let token = challenge.token.as_deref()
+                    .ok_or_else(|| format_err!("dns-01 challenge without token"))?;
+
+                let record = format!(
+                    "_acme-challenge.{}",
+                    domain.alias.as_deref().unwrap_or(identifier),
+                );
+                let value = dns_01_txt_value(&client.key_authorization(token)?);
+
+                task_log!(worker, "setting up TXT record {} using plugin '{}'", record, plugin.id);
+                run_dns_hook(plugin, "setup", identifier, &record, &value).await?;
+                setups.push(ChallengeSetup::Dns {
+                    plugin: plugin.clone(),
+                    domain: identifier.to_string(),
+                    record,
+                    value,
+                });
+                pending.push((url.clone(), challenge.url.clone()));
+            }
+            None => {
+                let challenge = authorization.challenge("http-01")
+                    .ok_or_else(|| format_err!("no http-01 challenge for '{}'", name))?;
+                let token = challenge.token.as_deref()
+                    .ok_or_else(|| format_err!("http-01 challenge without token"))?;
+
+                task_log!(worker, "setting up http-01 challenge for {}", identifier);
+                acme_challenge::add_http_challenge(token, &client.key_authorization(token)?).await?;
+                setups.push(ChallengeSetup::Http { token: token.to_string() });
+                pending.push((url.clone(), challenge.url.clone()));
+            }
+        }
+    }
+
+    Ok(pending)
+}
+
+async fn validate_challenges(
+    worker: &WorkerTask,
+    client: &mut AcmeClient,
+    order: &Order,
+    domains: &[(AcmeDomain, Option<DnsPlugin>)],
+    setups: &mut Vec<ChallengeSetup>,
+) -> Result<(), Error> {
+    let pending = setup_challenges(worker, client, order, domains, setups).await?;
+
+    let validation_delay = setups.iter()
+        .filter_map(|setup| match setup {
+            ChallengeSetup::Dns { plugin, .. } => Some(plugin.validation_delay.unwrap_or(30)),
+            ChallengeSetup::Http { .. } => None,
+        })
+        .max();
+
+    if let Some(delay) = validation_delay {
+        task_log!(worker, "waiting {} seconds for the DNS records to propagate", delay);
+        tokio::time::sleep(Duration::from_secs(u64::from(delay))).await;
+    }
+
+    for (authorization_url, challenge_url) in pending {
+        client.request_challenge_validation(&challenge_url).await?;
+
+        let mut count = 0;
+        loop {
+            tokio::time::sleep(Duration::from_secs(2)).await;
+
+            let authorization = client.get_authorization(&authorization_url).await?;
+            match authorization.status.as_str() {
+                "valid" => {
+                    task_log!(worker, "{} validated", authorization.identifier.value);
+                    break;
+                }
+                "pending" => (),
+                status => bail!(
+                    "validation of {} failed ({}) - {}",
+                    authorization.identifier.value,
+                    status,
+                    authorization_error(&authorization),
+                ),
+            }
+
+            count += 1;
+            if count >= MAX_POLL_COUNT {
+                bail!("validation of {} timed out", authorization.identifier.value);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn poll_order(client: &mut AcmeClient, url: &str, wanted: &str) -> Result<Order, Error> {
+    for _ in 0..MAX_POLL_COUNT {
+        let order = client.get_order(url).await?;
+        if order.status == wanted {
+            return Ok(order);
+        }
+        if order.status == "invalid" {
+            let error = order.error.map(|error| error.to_string())
+                .unwrap_or_else(|| "unknown error".to_string());
+            bail!("order failed - {}", error);
+        }
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    bail!("order did not become {} in time", wanted);
+}
+
+async fn order_certificate(worker: Arc<WorkerTask>, account_name: &str) -> Result<(), Error> {
+    let domains = crate::api2::config::acme::certificate_domains()?;
+    if domains.is_empty() {
+        bail!("no ACME domains configured");
+    }
+    let domain_names: Vec<String> = domains.iter().map(|(domain, _)| domain.domain.clone()).collect();
+
+    let account = acme::load_account(account_name)?;
+    let mut client = AcmeClient::with_account(&account)?;
+
+    task_log!(worker, "placing ACME order for {}", domain_names.join(", "));
+    let (order_url, order) = client.new_order(&domain_names).await?;
+
+    let mut setups = Vec::new();
+    let result = validate_challenges(&worker, &mut client, &order, &domains, &mut setups).await;
+
+    for setup in setups {
+        if let Err(err) = setup.teardown().await {
+            task_warn!(worker, "challenge cleanup failed - {}", err);
+        }
+    }
+    result?;
+
+    let order = poll_order(&mut client, &order_url, "ready").await?;
+
+    task_log!(worker, "creating CSR");
+    let (key_pem, csr) = create_csr(&domain_names)?;
+    client.finalize(&order.finalize, &csr).await?;
+
+    let order = poll_order(&mut client, &order_url, "valid").await?;
+    let certificate_url = order.certificate
+        .ok_or_else(|| format_err!("order is valid, but has no certificate URL"))?;
+
+    task_log!(worker, "downloading certificate");
+    let cert_pem = client.get_certificate(&certificate_url).await?;
+
+    task_log!(worker, "installing certificate");
+    crate::config::set_proxy_certificate(cert_pem.as_bytes(), &key_pem)?;
+
+    if let Err(err) = crate::server::reload_proxy_certificate().await {
+        task_warn!(worker, "unable to reload the proxy certificate - {}", err);
+    }
+
+    Ok(())
+}
+
+fn spawn_order_worker(
+    worker_type: &str,
+    account: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<String, Error> {
+    let auth_id = rpcenv.get_auth_id().unwrap();
+    let to_stdout = rpcenv.env_type() == RpcEnvironmentType::CLI;
+
+    let account = account.unwrap_or_else(|| DEFAULT_ACME_ACCOUNT_NAME.to_string());
+
+    WorkerTask::spawn(worker_type, None, auth_id.parse()?, to_stdout, move |worker| async move {
+        order_certificate(worker, &account).await
+    })
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            node: {
+                schema: NODE_SCHEMA,
+            },
+            account: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Order a new certificate for the configured domains
+pub fn new_acme_cert(
+    account: Option<String>,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<String, Error> {
+    spawn_order_worker("acme-new-cert", account, rpcenv)
+}
+
+#[api(
+    protected: true,
+    input: {
+        properties: {
+            node: {
+                schema: NODE_SCHEMA,
+            },
+            account: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+                optional: true,
+            },
+            force: {
+                description: "Renew the certificate even if it does not expire soon.",
+                type: bool,
+                optional: true,
+                default: false,
+            },
+        },
+    },
+    returns: {
+        schema: UPID_SCHEMA,
+    },
+    access: {
+        permission: &Permission::Privilege(&["system", "certificates"], PRIV_SYS_MODIFY, false),
+    },
+)]
+/// Renew the certificate if it expires within 30 days
+pub fn renew_acme_cert(
+    account: Option<String>,
+    force: bool,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<String, Error> {
+    if !force && !certificate_needs_renewal()? {
+        bail!("certificate does not expire within the next {} days", ACME_RENEW_DAYS);
+    }
+
+    spawn_order_worker("acme-renew-cert", account, rpcenv)
+}
+
+/// Check whether the proxy certificate expires within [ACME_RENEW_DAYS].
+pub fn certificate_needs_renewal() -> Result<bool, Error> {
+    let renew_before = proxmox::tools::time::epoch_i64() + ACME_RENEW_DAYS * 24 * 3600;
+    CertInfo::new()?.is_expired_after_epoch(renew_before)
+}
+
+const ACME_SUBDIRS: SubdirMap = &[
+    (
+        "certificate",
+        &Router::new()
+            .post(&API_METHOD_NEW_ACME_CERT)
+            .put(&API_METHOD_RENEW_ACME_CERT),
+    ),
+];
+
+const ACME_ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(ACME_SUBDIRS))
+    .subdirs(ACME_SUBDIRS);
+
+const SUBDIRS: SubdirMap = &[
+    ("acme", &ACME_ROUTER),
+    (
+        "custom",
+        &Router::new()
+            .post(&API_METHOD_UPLOAD_CUSTOM_CERT)
+            .delete(&API_METHOD_DELETE_CUSTOM_CERT),
+    ),
+    ("info", &Router::new().get(&API_METHOD_GET_INFO)),
+];
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::nid::Nid;
+    use openssl::pkey::Private;
+    use openssl::x509::X509Builder;
+
+    const DAY: i64 = 86400;
+
+    fn generate_key() -> Result<PKey<Private>, Error> {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        Ok(PKey::from_ec_key(EcKey::generate(&group)?)?)
+    }
+
+    // PEM encoded self-signed certificate for backup.example.com
+    fn self_signed_cert(key: &PKey<Private>, not_before: i64, not_after: i64) -> Result<Vec<u8>, Error> {
+        let mut x509 = X509Builder::new()?;
+        x509.set_version(2)?;
+        x509.set_not_before(&Asn1Time::from_unix(not_before)?)?;
+        x509.set_not_after(&Asn1Time::from_unix(not_after)?)?;
+
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_text("CN", "backup.example.com")?;
+        let name = name.build();
+        x509.set_subject_name(&name)?;
+        x509.set_issuer_name(&name)?;
+        x509.set_pubkey(key)?;
+
+        let context = x509.x509v3_context(None, None);
+        let san = SubjectAlternativeName::new().dns("backup.example.com").build(&context)?;
+        x509.append_extension(san)?;
+
+        x509.sign(key, MessageDigest::sha256())?;
+        Ok(x509.build().to_pem()?)
+    }
+
+    #[test]
+    fn test_valid_certificate() -> Result<(), Error> {
+        let now = proxmox::tools::time::epoch_i64();
+        let key = generate_key()?;
+        let cert_pem = self_signed_cert(&key, now - DAY, now + 30 * DAY)?;
+
+        let cert = CertInfo::from_pem(&cert_pem)?;
+        check_custom_certificate(&cert)?;
+        assert_eq!(cert.subject_alt_name_list(), vec!["backup.example.com".to_string()]);
+
+        crate::config::check_certificate_key(&cert_pem, &key.private_key_to_pem_pkcs8()?)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mismatched_key() -> Result<(), Error> {
+        let now = proxmox::tools::time::epoch_i64();
+        let key = generate_key()?;
+        let other_key = generate_key()?;
+        let cert_pem = self_signed_cert(&key, now - DAY, now + 30 * DAY)?;
+
+        let other_key_pem = other_key.private_key_to_pem_pkcs8()?;
+        assert!(crate::config::check_certificate_key(&cert_pem, &other_key_pem).is_err());
+        assert!(crate::config::check_certificate_key(&cert_pem, b"garbage").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_certificate() -> Result<(), Error> {
+        let now = proxmox::tools::time::epoch_i64();
+        let key = generate_key()?;
+
+        let expired = self_signed_cert(&key, now - 30 * DAY, now - DAY)?;
+        assert!(check_custom_certificate(&CertInfo::from_pem(&expired)?).is_err());
+
+        let not_yet_valid = self_signed_cert(&key, now + DAY, now + 30 * DAY)?;
+        assert!(check_custom_certificate(&CertInfo::from_pem(&not_yet_valid)?).is_err());
+
+        Ok(())
+    }
+}
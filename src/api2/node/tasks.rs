@@ -12,16 +12,30 @@ use crate::tools;
 
 use crate::api2::types::*;
 use crate::api2::pull::check_pull_privs;
+use crate::api2::push::check_push_privs;
+use crate::config::prune::PruneJobConfig;
+use crate::config::sync::SyncJobConfig;
 
 use crate::server::{self, UPID, TaskState, TaskListInfoIterator};
 use crate::config::acl::{
     PRIV_DATASTORE_MODIFY,
     PRIV_DATASTORE_VERIFY,
+    PRIV_PERMISSIONS_MODIFY,
     PRIV_SYS_AUDIT,
     PRIV_SYS_MODIFY,
 };
 use crate::config::cached_user_info::CachedUserInfo;
 
+fn lookup_sync_job(job_id: &str) -> Option<SyncJobConfig> {
+    let (config, _digest) = crate::config::sync::config().ok()?;
+    config.lookup("sync", job_id).ok()
+}
+
+fn lookup_prune_job(job_id: &str) -> Option<PruneJobConfig> {
+    let (config, _digest) = crate::config::prune::config().ok()?;
+    config.lookup("prune", job_id).ok()
+}
+
 // matches respective job execution privileges
 fn check_job_privs(auth_id: &Authid, user_info: &CachedUserInfo, upid: &UPID) -> Result<(), Error> {
     match (upid.worker_type.as_str(), &upid.worker_id) {
@@ -44,20 +58,42 @@ fn check_job_privs(auth_id: &Authid, user_info: &CachedUserInfo, upid: &UPID) ->
                 if let (Some(remote), Some(remote_store), Some(local_store)) =
                     (remote, remote_store, local_store) {
 
+                    let job_id = &workerid[captures.get(0).unwrap().end()..];
+                    let job = lookup_sync_job(job_id);
+                    let ns = job.as_ref().and_then(|job| job.ns.clone()).unwrap_or_default();
+
+                    if job.map(|job| job.sync_direction == Some(SyncDirection::Push)).unwrap_or(false) {
+                        return check_push_privs(&auth_id,
+                                                local_store.as_str(),
+                                                &ns,
+                                                remote.as_str(),
+                                                remote_store.as_str(),
+                                                false);
+                    }
+
                     return check_pull_privs(&auth_id,
                                             local_store.as_str(),
+                                            &ns,
                                             remote.as_str(),
                                             remote_store.as_str(),
                                             false);
                 }
             }
         },
+        ("mount-device", Some(workerid)) | ("unmount-device", Some(workerid)) |
+        ("datastore-migrate", Some(workerid)) |
         ("garbage_collection", Some(workerid)) => {
             return user_info.check_privs(&auth_id,
                                          &["datastore", &workerid],
                                          PRIV_DATASTORE_MODIFY,
                                          true)
         },
+        ("realm-sync", Some(_)) => {
+            return user_info.check_privs(&auth_id,
+                                         &["access", "domains"],
+                                         PRIV_PERMISSIONS_MODIFY,
+                                         true);
+        },
         ("prune", Some(workerid)) => {
             return user_info.check_privs(&auth_id,
                                          &["datastore",
@@ -65,6 +101,18 @@ fn check_job_privs(auth_id: &Authid, user_info: &CachedUserInfo, upid: &UPID) ->
                                          PRIV_DATASTORE_MODIFY,
                                          true);
         },
+        ("prunejob", Some(workerid)) => {
+            if let Some(captures) = PRUNE_JOB_WORKER_ID_REGEX.captures(&workerid) {
+                if let Some(store) = captures.get(1) {
+                    let job_id = &workerid[captures.get(0).unwrap().end()..];
+                    let ns = lookup_prune_job(job_id).and_then(|job| job.ns).unwrap_or_default();
+                    return user_info.check_privs(&auth_id,
+                                                 &ns.acl_path(store.as_str()),
+                                                 PRIV_DATASTORE_MODIFY,
+                                                 true);
+                }
+            }
+        },
         _ => bail!("not a scheduled job task"),
     };
 
@@ -83,6 +131,13 @@ fn check_job_store(upid: &UPID, store: &str) -> bool {
                 return workerid == store;
             }
         }
+        ("prunejob", Some(workerid)) => {
+            if let Some(captures) = PRUNE_JOB_WORKER_ID_REGEX.captures(&workerid) {
+                if let Some(jobstore) = captures.get(1) {
+                    return store == jobstore.as_str();
+                }
+            }
+        }
         ("syncjob", Some(workerid)) => {
             if let Some(captures) = SYNC_JOB_WORKER_ID_REGEX.captures(&workerid) {
                 if let Some(local_store) = captures.get(3) {
@@ -8,8 +8,13 @@ use proxmox::api::api;
 use proxmox::api::{ApiMethod, Router, RpcEnvironment, Permission};
 
 use crate::server::{WorkerTask, jobstate::Job};
-use crate::backup::DataStore;
-use crate::client::{HttpClient, BackupRepository, pull::pull_store};
+use crate::backup::{BackupNamespace, DataStore, GroupFilter};
+use crate::client::{
+    HttpClient,
+    BackupRepository,
+    pull::{pull_store, PullParameters},
+    push::{push_store, PushParameters},
+};
 use crate::api2::types::*;
 use crate::config::{
     remote,
@@ -22,6 +27,7 @@ use crate::config::{
 pub fn check_pull_privs(
     auth_id: &Authid,
     store: &str,
+    ns: &BackupNamespace,
     remote: &str,
     remote_store: &str,
     delete: bool,
@@ -29,11 +35,11 @@ pub fn check_pull_privs(
 
     let user_info = CachedUserInfo::new()?;
 
-    user_info.check_privs(auth_id, &["datastore", store], PRIV_DATASTORE_BACKUP, false)?;
+    user_info.check_privs(auth_id, &ns.acl_path(store), PRIV_DATASTORE_BACKUP, false)?;
     user_info.check_privs(auth_id, &["remote", remote, remote_store], PRIV_REMOTE_READ, false)?;
 
     if delete {
-        user_info.check_privs(auth_id, &["datastore", store], PRIV_DATASTORE_PRUNE, false)?;
+        user_info.check_privs(auth_id, &ns.acl_path(store), PRIV_DATASTORE_PRUNE, false)?;
     }
 
     Ok(())
@@ -43,16 +49,18 @@ pub async fn get_pull_parameters(
     store: &str,
     remote: &str,
     remote_store: &str,
+    limit: Option<RateLimitConfig>,
+    operation: Operation,
 ) -> Result<(HttpClient, BackupRepository, Arc<DataStore>), Error> {
 
-    let tgt_store = DataStore::lookup_datastore(store)?;
+    let tgt_store = DataStore::lookup_datastore(store, Some(operation))?;
 
     let (remote_config, _digest) = remote::config()?;
     let remote: remote::Remote = remote_config.lookup("remote", remote)?;
 
     let src_repo = BackupRepository::new(Some(remote.auth_id.clone()), Some(remote.host.clone()), remote.port, remote_store.to_string());
 
-    let client = crate::api2::config::remote::remote_client(remote).await?;
+    let client = crate::api2::config::remote::remote_client(remote, limit).await?;
 
     Ok((client, src_repo, tgt_store))
 }
@@ -87,18 +95,57 @@ pub fn do_sync_job(
 
             let worker_future = async move {
 
-                let delete = sync_job.remove_vanished.unwrap_or(true);
-                let sync_owner = sync_job.owner.unwrap_or_else(|| Authid::root_auth_id().clone());
-                let (client, src_repo, tgt_store) = get_pull_parameters(&sync_job.store, &sync_job.remote, &sync_job.remote_store).await?;
-
                 worker.log(format!("Starting datastore sync job '{}'", job_id));
                 if let Some(event_str) = schedule {
                     worker.log(format!("task triggered by schedule '{}'", event_str));
                 }
-                worker.log(format!("Sync datastore '{}' from '{}/{}'",
-                        sync_job.store, sync_job.remote, sync_job.remote_store));
 
-                crate::client::pull::pull_store(&worker, &client, &src_repo, tgt_store.clone(), delete, sync_owner).await?;
+                let group_filter = match sync_job.group_filter {
+                    Some(ref list) => Some(GroupFilter::parse_list(list)?),
+                    None => None,
+                };
+
+                match sync_job.sync_direction.unwrap_or_default() {
+                    SyncDirection::Pull => {
+                        let params = PullParameters {
+                            remote_ns: sync_job.remote_ns.clone().unwrap_or_default(),
+                            ns: sync_job.ns.clone().unwrap_or_default(),
+                            max_depth: sync_job.max_depth,
+                            delete: sync_job.remove_vanished.unwrap_or(true),
+                            owner: sync_job.owner.unwrap_or_else(|| Authid::root_auth_id().clone()),
+                            group_filter,
+                            transfer_last: sync_job.transfer_last,
+                        };
+                        let limit = RateLimitConfig {
+        rate_in,
+        burst_in,
+        ..Default::default()
+    };
+
+    let (client, src_repo, tgt_store) = get_pull_parameters(&sync_job.store, &sync_job.remote, &sync_job.remote_store, Some(sync_job.rate_limit()), Operation::Write).await?;
+
+                        worker.log(format!("Sync datastore '{}' from '{}/{}'",
+                                sync_job.store, sync_job.remote, sync_job.remote_store));
+
+                        pull_store(&worker, &client, &src_repo, tgt_store.clone(), &params).await?;
+                    }
+                    SyncDirection::Push => {
+                        let params = PushParameters {
+                            ns: sync_job.ns.clone().unwrap_or_default(),
+                            remote_ns: sync_job.remote_ns.clone().unwrap_or_default(),
+                            max_depth: sync_job.max_depth,
+                            delete: sync_job.remove_vanished.unwrap_or(true),
+                            group_filter,
+                            transfer_last: sync_job.transfer_last,
+                        };
+                        let (mut client, tgt_repo, src_store) = get_pull_parameters(&sync_job.store, &sync_job.remote, &sync_job.remote_store, Some(sync_job.rate_limit()), Operation::Read).await?;
+
+                        worker.log(format!("Sync datastore '{}' to '{}/{}'",
+                                sync_job.store, sync_job.remote, sync_job.remote_store));
+
+                        push_store(&worker, &mut client, &tgt_repo, src_store, &params).await?;
+                    }
+                }
 
                 worker.log(format!("sync job '{}' end", &job_id));
 
@@ -121,10 +168,8 @@ pub fn do_sync_job(
                 }
             }
 
-            if let Some(email) = email {
-                if let Err(err) = crate::server::send_sync_status(&email, notify, &sync_job2, &result) {
-                    eprintln!("send sync notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_sync_status(email.as_deref(), notify, &sync_job2, &result) {
+                eprintln!("send sync notification failed: {}", err);
             }
 
             result
@@ -139,50 +184,97 @@ pub fn do_sync_job(
             store: {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             remote: {
                 schema: REMOTE_ID_SCHEMA,
             },
             "remote-store": {
                 schema: DATASTORE_SCHEMA,
             },
+            "remote-ns": {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
             "remove-vanished": {
                 schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
                 optional: true,
             },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "transfer-last": {
+                schema: TRANSFER_LAST_SCHEMA,
+                optional: true,
+            },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
         },
     },
     access: {
         // Note: used parameters are no uri parameters, so we need to test inside function body
-        description: r###"The user needs Datastore.Backup privilege on '/datastore/{store}',
+        description: r###"The user needs Datastore.Backup privilege on '/datastore/{store}[/{ns}]',
 and needs to own the backup group. Remote.Read is required on '/remote/{remote}/{remote-store}'.
-The delete flag additionally requires the Datastore.Prune privilege on '/datastore/{store}'.
+The delete flag additionally requires the Datastore.Prune privilege on '/datastore/{store}[/{ns}]'.
 "###,
         permission: &Permission::Anybody,
     },
 )]
 /// Sync store from other repository
+#[allow(clippy::too_many_arguments)]
 async fn pull (
     store: String,
+    ns: Option<BackupNamespace>,
     remote: String,
     remote_store: String,
+    remote_ns: Option<BackupNamespace>,
+    max_depth: Option<usize>,
     remove_vanished: Option<bool>,
+    group_filter: Option<String>,
+    transfer_last: Option<usize>,
+    rate_in: Option<u64>,
+    burst_in: Option<u64>,
     _info: &ApiMethod,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<String, Error> {
 
     let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
     let delete = remove_vanished.unwrap_or(true);
+    let ns = ns.unwrap_or_default();
+
+    check_pull_privs(&auth_id, &store, &ns, &remote, &remote_store, delete)?;
 
-    check_pull_privs(&auth_id, &store, &remote, &remote_store, delete)?;
+    let params = PullParameters {
+        remote_ns: remote_ns.unwrap_or_default(),
+        ns,
+        max_depth,
+        delete,
+        owner: auth_id.clone(),
+        group_filter: group_filter.as_deref().map(GroupFilter::parse_list).transpose()?,
+        transfer_last,
+    };
 
-    let (client, src_repo, tgt_store) = get_pull_parameters(&store, &remote, &remote_store).await?;
+    let (client, src_repo, tgt_store) = get_pull_parameters(&store, &remote, &remote_store, Some(limit), Operation::Write).await?;
 
     // fixme: set to_stdout to false?
     let upid_str = WorkerTask::spawn("sync", Some(store.clone()), auth_id.clone(), true, move |worker| async move {
 
         worker.log(format!("sync datastore '{}' start", store));
 
-        let pull_future = pull_store(&worker, &client, &src_repo, tgt_store.clone(), delete, auth_id);
+        let pull_future = pull_store(&worker, &client, &src_repo, tgt_store.clone(), &params);
         let future = select!{
             success = pull_future.fuse() => success,
             abort = worker.abort_future().map(|_| Err(format_err!("pull aborted"))) => abort,
@@ -0,0 +1,157 @@
+//! Sync datastore to remote server
+use anyhow::{format_err, Error};
+use futures::{select, future::FutureExt};
+
+use proxmox::api::api;
+use proxmox::api::{ApiMethod, Router, RpcEnvironment, Permission};
+
+use crate::server::WorkerTask;
+use crate::backup::{BackupNamespace, GroupFilter};
+use crate::client::push::{push_store, PushParameters};
+use crate::api2::pull::get_pull_parameters;
+use crate::api2::types::*;
+use crate::config::{
+    acl::{PRIV_DATASTORE_READ, PRIV_REMOTE_DATASTORE_BACKUP, PRIV_REMOTE_DATASTORE_PRUNE},
+    cached_user_info::CachedUserInfo,
+};
+
+pub fn check_push_privs(
+    auth_id: &Authid,
+    store: &str,
+    ns: &BackupNamespace,
+    remote: &str,
+    remote_store: &str,
+    delete: bool,
+) -> Result<(), Error> {
+
+    let user_info = CachedUserInfo::new()?;
+
+    user_info.check_privs(auth_id, &ns.acl_path(store), PRIV_DATASTORE_READ, false)?;
+    user_info.check_privs(auth_id, &["remote", remote, remote_store], PRIV_REMOTE_DATASTORE_BACKUP, false)?;
+
+    if delete {
+        user_info.check_privs(auth_id, &["remote", remote, remote_store], PRIV_REMOTE_DATASTORE_PRUNE, false)?;
+    }
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            remote: {
+                schema: REMOTE_ID_SCHEMA,
+            },
+            "remote-store": {
+                schema: DATASTORE_SCHEMA,
+            },
+            "remote-ns": {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "remove-vanished": {
+                schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
+                optional: true,
+            },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "transfer-last": {
+                schema: TRANSFER_LAST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+        },
+    },
+    access: {
+        // Note: used parameters are no uri parameters, so we need to test inside function body
+        description: r###"The user needs Datastore.Read privilege on '/datastore/{store}[/{ns}]',
+and Remote.DatastoreBackup on '/remote/{remote}/{remote-store}'.
+The delete flag additionally requires the Remote.DatastorePrune privilege on '/remote/{remote}/{remote-store}'.
+"###,
+        permission: &Permission::Anybody,
+    },
+)]
+/// Sync store to other repository
+#[allow(clippy::too_many_arguments)]
+async fn push (
+    store: String,
+    ns: Option<BackupNamespace>,
+    remote: String,
+    remote_store: String,
+    remote_ns: Option<BackupNamespace>,
+    max_depth: Option<usize>,
+    remove_vanished: Option<bool>,
+    group_filter: Option<String>,
+    transfer_last: Option<usize>,
+    rate_out: Option<u64>,
+    burst_out: Option<u64>,
+    _info: &ApiMethod,
+    rpcenv: &mut dyn RpcEnvironment,
+) -> Result<String, Error> {
+
+    let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
+    let delete = remove_vanished.unwrap_or(true);
+    let ns = ns.unwrap_or_default();
+
+    check_push_privs(&auth_id, &store, &ns, &remote, &remote_store, delete)?;
+
+    let params = PushParameters {
+        ns,
+        remote_ns: remote_ns.unwrap_or_default(),
+        max_depth,
+        delete,
+        group_filter: group_filter.as_deref().map(GroupFilter::parse_list).transpose()?,
+        transfer_last,
+    };
+
+    let limit = RateLimitConfig {
+        rate_out,
+        burst_out,
+        ..Default::default()
+    };
+
+    let (mut client, tgt_repo, src_store) = get_pull_parameters(&store, &remote, &remote_store, Some(limit), Operation::Read).await?;
+
+    // fixme: set to_stdout to false?
+    let upid_str = WorkerTask::spawn("sync", Some(store.clone()), auth_id.clone(), true, move |worker| async move {
+
+        worker.log(format!("sync datastore '{}' to remote start", store));
+
+        let push_future = push_store(&worker, &mut client, &tgt_repo, src_store.clone(), &params);
+        let future = select!{
+            success = push_future.fuse() => success,
+            abort = worker.abort_future().map(|_| Err(format_err!("push aborted"))) => abort,
+        };
+
+        let _ = future?;
+
+        worker.log(format!("sync datastore '{}' to remote end", store));
+
+        Ok(())
+    })?;
+
+    Ok(upid_str)
+}
+
+pub const ROUTER: Router = Router::new()
+    .post(&API_METHOD_PUSH);
@@ -32,19 +32,25 @@ use crate::{
         helpers,
         types::{
             DATASTORE_SCHEMA,
+            BACKUP_NAMESPACE_SCHEMA,
             BACKUP_TYPE_SCHEMA,
             BACKUP_TIME_SCHEMA,
             BACKUP_ID_SCHEMA,
             CHUNK_DIGEST_SCHEMA,
+            ZSTD_DICTIONARY_ID_SCHEMA,
             Authid,
+            Operation,
         },
     },
     backup::{
         DataStore,
         ArchiveType,
         BackupDir,
+        BackupGroup,
+        BackupNamespace,
         IndexFile,
         archive_type,
+        parse_dictionary_id,
     },
     server::{
         WorkerTask,
@@ -76,6 +82,7 @@ pub const API_METHOD_UPGRADE_BACKUP: ApiMethod = ApiMethod::new(
         concat!("Upgraded to backup protocol ('", PROXMOX_BACKUP_READER_PROTOCOL_ID_V1!(), "')."),
         &sorted!([
             ("store", false, &DATASTORE_SCHEMA),
+            ("ns", true, &BACKUP_NAMESPACE_SCHEMA),
             ("backup-type", false, &BACKUP_TYPE_SCHEMA),
             ("backup-id", false, &BACKUP_ID_SCHEMA),
             ("backup-time", false, &BACKUP_TIME_SCHEMA),
@@ -84,7 +91,7 @@ pub const API_METHOD_UPGRADE_BACKUP: ApiMethod = ApiMethod::new(
     )
 ).access(
     // Note: parameter 'store' is no uri parameter, so we need to test inside function body
-    Some("The user needs Datastore.Read privilege on /datastore/{store}."),
+    Some("The user needs Datastore.Read privilege on /datastore/{store}[/{namespace}]."),
     &Permission::Anybody
 );
 
@@ -101,19 +108,21 @@ fn upgrade_to_backup_reader_protocol(
 
         let auth_id: Authid = rpcenv.get_auth_id().unwrap().parse()?;
         let store = tools::required_string_param(&param, "store")?.to_owned();
+        let backup_ns = BackupNamespace::from_opt(param["ns"].as_str())?;
 
         let user_info = CachedUserInfo::new()?;
-        let privs = user_info.lookup_privs(&auth_id, &["datastore", &store]);
+        let acl_path = backup_ns.acl_path(&store);
+        let privs = user_info.lookup_privs(&auth_id, &acl_path);
 
         let priv_read = privs & PRIV_DATASTORE_READ != 0;
         let priv_backup = privs & PRIV_DATASTORE_BACKUP != 0;
 
         // priv_backup needs owner check further down below!
         if !priv_read && !priv_backup {
-            bail!("no permissions on /datastore/{}", store);
+            bail!("no permissions on /{}", acl_path.join("/"));
         }
 
-        let datastore = DataStore::lookup_datastore(&store)?;
+        let datastore = DataStore::lookup_datastore(&store, Some(Operation::Read))?;
 
         let backup_type = tools::required_string_param(&param, "backup-type")?;
         let backup_id = tools::required_string_param(&param, "backup-id")?;
@@ -135,7 +144,10 @@ fn upgrade_to_backup_reader_protocol(
 
         let env_type = rpcenv.env_type();
 
-        let backup_dir = BackupDir::new(backup_type, backup_id, backup_time)?;
+        let backup_dir = BackupDir::with_group(
+            BackupGroup::with_ns(backup_ns, backup_type, backup_id),
+            backup_time,
+        )?;
         if !priv_read {
             let owner = datastore.get_owner(backup_dir.group())?;
             let correct_owner = owner == auth_id
@@ -155,7 +167,7 @@ fn upgrade_to_backup_reader_protocol(
 
         //let files = BackupInfo::list_files(&path, &backup_dir)?;
 
-        let worker_id = format!("{}:{}/{}/{:08X}", store, backup_type, backup_id, backup_dir.backup_time());
+        let worker_id = format!("{}:{}/{:08X}", store, backup_dir.group(), backup_dir.backup_time());
 
         WorkerTask::spawn("reader", Some(worker_id), auth_id.clone(), true, move |worker| async move {
             let _guard = _guard;
@@ -218,6 +230,10 @@ const READER_API_SUBDIRS: SubdirMap = &[
         "chunk", &Router::new()
             .download(&API_METHOD_DOWNLOAD_CHUNK)
     ),
+    (
+        "dictionary", &Router::new()
+            .download(&API_METHOD_DOWNLOAD_DICTIONARY)
+    ),
     (
         "download", &Router::new()
             .download(&API_METHOD_DOWNLOAD_FILE)
@@ -318,12 +334,11 @@ fn download_chunk(
         }
 
         let (path, _) = env.datastore.chunk_path(&digest);
-        let path2 = path.clone();
 
         env.debug(format!("download chunk {:?}", path));
 
-        let data = tools::runtime::block_in_place(|| std::fs::read(path))
-            .map_err(move |err| http_err!(BAD_REQUEST, "reading file {:?} failed: {}", path2, err))?;
+        let data = tools::runtime::block_in_place(|| env.datastore.load_raw_chunk(&digest))
+            .map_err(move |err| http_err!(BAD_REQUEST, "reading chunk {:?} failed: {}", path, err))?;
 
         let body = Body::from(data);
 
@@ -336,6 +351,42 @@ fn download_chunk(
     }.boxed()
 }
 
+#[sortable]
+pub const API_METHOD_DOWNLOAD_DICTIONARY: ApiMethod = ApiMethod::new(
+    &ApiHandler::AsyncHttp(&download_dictionary),
+    &ObjectSchema::new(
+        "Download a zstd dictionary needed to decompress chunks.",
+        &sorted!([
+            ("id", false, &ZSTD_DICTIONARY_ID_SCHEMA),
+        ]),
+    )
+);
+
+fn download_dictionary(
+    _parts: Parts,
+    _req_body: Body,
+    param: Value,
+    _info: &ApiMethod,
+    rpcenv: Box<dyn RpcEnvironment>,
+) -> ApiResponseFuture {
+
+    async move {
+        let env: &ReaderEnvironment = rpcenv.as_ref();
+
+        let id = parse_dictionary_id(tools::required_string_param(&param, "id")?)?;
+        let dict = env.datastore.load_zstd_dictionary(&id)
+            .map_err(|err| http_err!(BAD_REQUEST, "{}", err))?;
+
+        env.debug(format!("download zstd dictionary {}", dict.id_str()));
+
+        Ok(Response::builder()
+           .status(StatusCode::OK)
+           .header(header::CONTENT_TYPE, "application/octet-stream")
+           .body(Body::from(dict.data().to_vec()))
+           .unwrap())
+    }.boxed()
+}
+
 /* this is too slow
 fn download_chunk_old(
     _parts: Parts,
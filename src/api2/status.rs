@@ -19,6 +19,7 @@ use crate::api2::types::{
     RRDMode,
     RRDTimeFrameResolution,
     Authid,
+    Operation,
 };
 
 use crate::backup::{DataStore};
@@ -43,16 +44,24 @@ use crate::config::acl::{
                 },
                 total: {
                     type: Integer,
+                    optional: true,
                     description: "The Size of the underlying storage in bytes",
                 },
                 used: {
                     type: Integer,
+                    optional: true,
                     description: "The used bytes of the underlying storage",
                 },
                 avail: {
                     type: Integer,
+                    optional: true,
                     description: "The available bytes of the underlying storage",
                 },
+                error: {
+                    type: String,
+                    optional: true,
+                    description: "Set if the datastore is not accessible, e.g. in maintenance mode.",
+                },
                 history: {
                     type: Array,
                     description: "A list of usages of the past (last Month).",
@@ -97,7 +106,16 @@ pub fn datastore_status(
             continue;
         }
 
-        let datastore = DataStore::lookup_datastore(&store)?;
+        let datastore = match DataStore::lookup_datastore(&store, Some(Operation::Read)) {
+            Ok(datastore) => datastore,
+            Err(err) => {
+                list.push(json!({
+                    "store": store,
+                    "error": err.to_string(),
+                }));
+                continue;
+            }
+        };
         let status = crate::tools::disks::disk_usage(&datastore.base_path())?;
 
         let mut entry = json!({
@@ -43,6 +43,8 @@ use crate::{
         DataStore,
         BackupDir,
         BackupInfo,
+        BackupNamespace,
+        GroupFilter,
         StoreProgress,
     },
     api2::types::{
@@ -50,6 +52,7 @@ use crate::{
         UPID_SCHEMA,
         JOB_ID_SCHEMA,
         MediaPoolConfig,
+        Operation,
         Userid,
     },
     server::WorkerTask,
@@ -169,7 +172,7 @@ pub fn do_tape_backup_job(
 
     let worker_type = job.jobtype().to_string();
 
-    let datastore = DataStore::lookup_datastore(&setup.store)?;
+    let datastore = DataStore::lookup_datastore(&setup.store, Some(Operation::Read))?;
 
     let (config, _digest) = config::media_pool::config()?;
     let pool_config: MediaPoolConfig = config.lookup("pool", &setup.pool)?;
@@ -208,15 +211,13 @@ pub fn do_tape_backup_job(
 
             let status = worker.create_state(&job_result);
 
-            if let Some(email) = email {
-                if let Err(err) = crate::server::send_tape_backup_status(
-                    &email,
-                    Some(job.jobname()),
-                    &setup,
-                    &job_result,
-                ) {
-                    eprintln!("send tape backup notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_tape_backup_status(
+                email.as_deref(),
+                Some(job.jobname()),
+                &setup,
+                &job_result,
+            ) {
+                eprintln!("send tape backup notification failed: {}", err);
             }
 
             if let Err(err) = job.finish(status) {
@@ -315,7 +316,7 @@ pub fn backup(
         &setup.drive,
     )?;
 
-    let datastore = DataStore::lookup_datastore(&setup.store)?;
+    let datastore = DataStore::lookup_datastore(&setup.store, Some(Operation::Read))?;
 
     let (config, _digest) = config::media_pool::config()?;
     let pool_config: MediaPoolConfig = config.lookup("pool", &setup.pool)?;
@@ -348,15 +349,13 @@ pub fn backup(
                 email.clone(),
             );
 
-            if let Some(email) = email {
-                if let Err(err) = crate::server::send_tape_backup_status(
-                    &email,
-                    None,
-                    &setup,
-                    &job_result,
-                ) {
-                    eprintln!("send tape backup notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_tape_backup_status(
+                email.as_deref(),
+                None,
+                &setup,
+                &job_result,
+            ) {
+                eprintln!("send tape backup notification failed: {}", err);
             }
 
             // ignore errors
@@ -387,12 +386,36 @@ fn backup_worker(
 
     let mut pool_writer = PoolWriter::new(pool, &setup.drive, worker, email)?;
 
-    let mut group_list = BackupInfo::list_backup_groups(&datastore.base_path())?;
+    let ns = setup.ns.clone().unwrap_or_else(BackupNamespace::root);
+    if !datastore.namespace_exists(&ns) {
+        bail!("namespace '{}' does not exist", ns);
+    }
+
+    let mut group_list = BackupInfo::list_backup_groups_recursive(
+        &datastore.base_path(),
+        &ns,
+        setup.max_depth,
+    )?;
 
     group_list.sort_unstable();
 
-    let group_count = group_list.len();
-    task_log!(worker, "found {} groups", group_count);
+    let group_filter = setup.group_filter
+        .as_deref()
+        .map(GroupFilter::parse_list)
+        .transpose()?;
+
+    let group_count = match group_filter {
+        Some(ref group_filter) => {
+            let total = group_list.len();
+            group_list.retain(|group| group.apply_filters(group_filter));
+            task_log!(worker, "found {} groups (out of {} total)", group_list.len(), total);
+            group_list.len()
+        }
+        None => {
+            task_log!(worker, "found {} groups", group_list.len());
+            group_list.len()
+        }
+    };
 
     let mut progress = StoreProgress::new(group_count as u64);
 
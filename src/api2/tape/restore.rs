@@ -1,5 +1,6 @@
 use std::path::Path;
 use std::ffi::OsStr;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::convert::TryFrom;
 
 use anyhow::{bail, format_err, Error};
@@ -31,8 +32,10 @@ use crate::{
     api2::types::{
         DATASTORE_SCHEMA,
         DRIVE_NAME_SCHEMA,
+        TAPE_RESTORE_SNAPSHOT_SCHEMA,
         UPID_SCHEMA,
         Authid,
+        Operation,
         Userid,
     },
     config::{
@@ -110,6 +113,14 @@ pub const ROUTER: Router = Router::new()
                 type: Authid,
                 optional: true,
             },
+            snapshots: {
+                description: "List of snapshots or groups to restore (default: restore the whole media set).",
+                type: Array,
+                optional: true,
+                items: {
+                    schema: TAPE_RESTORE_SNAPSHOT_SCHEMA,
+                },
+            },
         },
     },
     returns: {
@@ -123,12 +134,17 @@ pub const ROUTER: Router = Router::new()
     },
 )]
 /// Restore data from media-set
+///
+/// If `snapshots` is given, only the selected snapshots (or groups) are
+/// restored, and only the media containing them are loaded.
+#[allow(clippy::too_many_arguments)]
 pub fn restore(
     store: String,
     drive: String,
     media_set: String,
     notify_user: Option<Userid>,
     owner: Option<Authid>,
+    snapshots: Option<Vec<String>>,
     rpcenv: &mut dyn RpcEnvironment,
 ) -> Result<Value, Error> {
 
@@ -169,7 +185,7 @@ pub fn restore(
         bail!("no permissions on /tape/pool/{}", pool);
     }
 
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
 
     let (drive_config, _digest) = config::drive::config()?;
 
@@ -222,26 +238,44 @@ pub fn restore(
             task_log!(worker, "Pool: {}", pool);
             task_log!(worker, "Datastore: {}", store);
             task_log!(worker, "Drive: {}", drive);
-            task_log!(
-                worker,
-                "Required media list: {}",
-                media_id_list.iter()
-                    .map(|media_id| media_id.label.label_text.as_str())
-                    .collect::<Vec<&str>>()
-                    .join(";")
-            );
-
-            for media_id in media_id_list.iter() {
-                request_and_restore_media(
-                    &worker,
-                    media_id,
-                    &drive_config,
-                    &drive,
-                    &datastore,
-                    &auth_id,
-                    &notify_user,
-                    &owner,
-                )?;
+
+            match snapshots {
+                Some(ref snapshots) if !snapshots.is_empty() => {
+                    restore_list_worker(
+                        &worker,
+                        snapshots,
+                        &media_id_list,
+                        &drive_config,
+                        &drive,
+                        &datastore,
+                        &auth_id,
+                        &notify_user,
+                        &owner,
+                    )?;
+                }
+                _ => {
+                    task_log!(
+                        worker,
+                        "Required media list: {}",
+                        media_id_list.iter()
+                            .map(|media_id| media_id.label.label_text.as_str())
+                            .collect::<Vec<&str>>()
+                            .join(";")
+                    );
+
+                    for media_id in media_id_list.iter() {
+                        request_and_restore_media(
+                            &worker,
+                            media_id,
+                            &drive_config,
+                            &drive,
+                            &datastore,
+                            &auth_id,
+                            &notify_user,
+                            &owner,
+                        )?;
+                    }
+                }
             }
 
             task_log!(worker, "Restore mediaset '{}' done", media_set);
@@ -274,17 +308,33 @@ pub fn request_and_restore_media(
     owner: &Option<Authid>,
 ) -> Result<(), Error> {
 
-    let media_set_uuid = match media_id.media_set_label {
-        None => bail!("restore_media: no media set - internal error"),
-        Some(ref set) => &set.uuid,
-    };
-
     let email = notify_user
         .as_ref()
         .and_then(|userid| lookup_user_email(userid))
         .or_else(|| lookup_user_email(&authid.clone().into()));
 
-    let (mut drive, info) = request_and_load_media(worker, &drive_config, &drive_name, &media_id.label, &email)?;
+    let (mut drive, info) = request_and_load_set_media(worker, media_id, drive_config, drive_name, &email)?;
+
+    let restore_owner = owner.as_ref().unwrap_or(authid);
+
+    restore_media(worker, &mut drive, &info, Some((datastore, restore_owner)), false)
+}
+
+/// Request and load a media set member, and setup drive encryption
+fn request_and_load_set_media(
+    worker: &WorkerTask,
+    media_id: &MediaId,
+    drive_config: &SectionConfigData,
+    drive_name: &str,
+    email: &Option<String>,
+) -> Result<(Box<dyn TapeDriver>, MediaId), Error> {
+
+    let media_set_uuid = match media_id.media_set_label {
+        None => bail!("restore_media: no media set - internal error"),
+        Some(ref set) => &set.uuid,
+    };
+
+    let (mut drive, info) = request_and_load_media(worker, drive_config, drive_name, &media_id.label, email)?;
 
     match info.media_set_label {
         None => {
@@ -304,9 +354,371 @@ pub fn request_and_restore_media(
         }
     }
 
+    Ok((drive, info))
+}
+
+/// Test if a snapshot is selected by a list of snapshot or group paths
+fn snapshot_is_selected(backup_dir: &BackupDir, snapshot: &str, selection: &[String]) -> bool {
+    let group = backup_dir.group().to_string();
+    selection.iter().any(|path| path == snapshot || path == &group)
+}
+
+/// Restore selected snapshots (or groups) from a media set
+///
+/// Uses the media catalogs to find the snapshot and chunk archives
+/// needed, so that only the media containing them get loaded. Snapshot
+/// archives are restored into a temporary directory first, and only
+/// moved into the datastore after all referenced chunks are restored.
+#[allow(clippy::too_many_arguments)]
+fn restore_list_worker(
+    worker: &WorkerTask,
+    selection: &[String],
+    media_id_list: &[&MediaId],
+    drive_config: &SectionConfigData,
+    drive_name: &str,
+    datastore: &DataStore,
+    authid: &Authid,
+    notify_user: &Option<Userid>,
+    owner: &Option<Authid>,
+) -> Result<(), Error> {
+
+    let status_path = Path::new(TAPE_STATUS_DIR);
+
+    let mut catalog_list = Vec::new();
+    for media_id in media_id_list.iter() {
+        let catalog = MediaCatalog::open(status_path, &media_id.label.uuid, false, false)
+            .map_err(|err| format_err!(
+                "{} - please catalog media '{}' first", err, media_id.label.label_text))?;
+        catalog_list.push(catalog);
+    }
+
+    // media index => snapshot archive file numbers
+    let mut snapshot_file_list: BTreeMap<usize, BTreeMap<u64, String>> = BTreeMap::new();
+    let mut found_snapshots = HashSet::new();
+    let mut found_selection = HashSet::new();
+
+    for (media_index, catalog) in catalog_list.iter().enumerate() {
+        for (snapshot, file_number) in catalog.snapshot_index() {
+            let backup_dir: BackupDir = snapshot.parse()?;
+            if !snapshot_is_selected(&backup_dir, snapshot, selection) {
+                continue;
+            }
+            found_selection.insert(snapshot.clone());
+            found_selection.insert(backup_dir.group().to_string());
+
+            if !found_snapshots.insert(snapshot.clone()) {
+                continue; // already found on another media
+            }
+
+            let mut path = datastore.base_path();
+            path.push(backup_dir.relative_path());
+            if path.exists() {
+                task_log!(worker, "skip snapshot {} - already exists in datastore", snapshot);
+                continue;
+            }
+
+            snapshot_file_list
+                .entry(media_index)
+                .or_default()
+                .insert(*file_number, snapshot.clone());
+        }
+    }
+
+    for path in selection {
+        if !found_selection.contains(path) {
+            bail!("unable to find '{}' in media set", path);
+        }
+    }
+
+    if snapshot_file_list.is_empty() {
+        task_log!(worker, "nothing to restore");
+        return Ok(());
+    }
+
+    let email = notify_user
+        .as_ref()
+        .and_then(|userid| lookup_user_email(userid))
+        .or_else(|| lookup_user_email(&authid.clone().into()));
+
     let restore_owner = owner.as_ref().unwrap_or(authid);
 
-    restore_media(worker, &mut drive, &info, Some((datastore, restore_owner)), false)
+    let mut tmp_path = datastore.base_path();
+    tmp_path.push(format!(".tape-restore-{}-{}", worker.upid().pid, worker.upid().task_id));
+    std::fs::create_dir_all(&tmp_path)
+        .map_err(|err| format_err!("unable to create directory {:?} - {}", tmp_path, err))?;
+
+    let result: Result<(), Error> = proxmox::try_block!({
+        let mut needed_chunks = HashSet::new();
+        let mut restored_snapshots = Vec::new();
+
+        for (media_index, file_list) in snapshot_file_list.iter() {
+            let media_id = media_id_list[*media_index];
+            task_log!(worker, "Restore snapshot archives from media '{}'", media_id.label.label_text);
+
+            let (mut drive, _info) = request_and_load_set_media(
+                worker, media_id, drive_config, drive_name, &email)?;
+
+            for (file_number, snapshot) in file_list.iter() {
+                worker.check_abort()?;
+
+                let mut snapshot_path = tmp_path.clone();
+                snapshot_path.push(snapshot);
+                std::fs::create_dir_all(&snapshot_path)?;
+
+                restore_snapshot_archive_file(worker, &mut drive, *file_number, snapshot, &snapshot_path)?;
+
+                collect_missing_chunks(datastore, &snapshot_path, &mut needed_chunks)?;
+
+                restored_snapshots.push(snapshot.clone());
+            }
+        }
+
+        // media index => chunk archive file numbers
+        let mut chunk_file_list: BTreeMap<usize, BTreeSet<u64>> = BTreeMap::new();
+        for digest in needed_chunks.iter() {
+            let location = catalog_list.iter().enumerate().find_map(|(media_index, catalog)| {
+                catalog.lookup_chunk(digest).map(|file_number| (media_index, file_number))
+            });
+            match location {
+                Some((media_index, file_number)) => {
+                    chunk_file_list.entry(media_index).or_default().insert(file_number);
+                }
+                None => bail!("unable to find chunk {} in media set catalog",
+                              proxmox::tools::digest_to_hex(digest)),
+            }
+        }
+
+        task_log!(worker, "need to restore {} chunks", needed_chunks.len());
+
+        for (media_index, file_list) in chunk_file_list.iter() {
+            let media_id = media_id_list[*media_index];
+            task_log!(worker, "Restore chunk archives from media '{}'", media_id.label.label_text);
+
+            let (mut drive, _info) = request_and_load_set_media(
+                worker, media_id, drive_config, drive_name, &email)?;
+
+            for file_number in file_list.iter() {
+                worker.check_abort()?;
+                drive.move_to_file(*file_number)?;
+                let mut reader = match drive.read_next_file()? {
+                    None => bail!("missing chunk archive at file {}", file_number),
+                    Some(reader) => reader,
+                };
+
+                let header: MediaContentHeader = unsafe { reader.read_le_value()? };
+                if header.magic != PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0
+                    || header.content_magic != PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_0
+                {
+                    bail!("file {} on media '{}' is no chunk archive", file_number, media_id.label.label_text);
+                }
+
+                let count = restore_partial_chunk_archive(worker, reader, datastore, &mut needed_chunks)?;
+                task_log!(worker, "restored {} chunks from file {}", count, file_number);
+            }
+        }
+
+        if !needed_chunks.is_empty() {
+            bail!("unable to restore {} chunks", needed_chunks.len());
+        }
+
+        for snapshot in restored_snapshots {
+            let backup_dir: BackupDir = snapshot.parse()?;
+
+            let mut tmp_snapshot_path = tmp_path.clone();
+            tmp_snapshot_path.push(&snapshot);
+
+            move_snapshot_into_datastore(worker, datastore, &backup_dir, restore_owner, &tmp_snapshot_path)?;
+        }
+
+        Ok(())
+    });
+
+    if let Err(err) = std::fs::remove_dir_all(&tmp_path) {
+        task_log!(worker, "unable to cleanup directory {:?} - {}", tmp_path, err);
+    }
+
+    result
+}
+
+/// Restore a single snapshot archive into `snapshot_path`
+fn restore_snapshot_archive_file(
+    worker: &WorkerTask,
+    drive: &mut Box<dyn TapeDriver>,
+    file_number: u64,
+    snapshot: &str,
+    snapshot_path: &Path,
+) -> Result<(), Error> {
+
+    drive.move_to_file(file_number)?;
+
+    let mut reader = match drive.read_next_file()? {
+        None => bail!("missing snapshot archive at file {}", file_number),
+        Some(reader) => reader,
+    };
+
+    let header: MediaContentHeader = unsafe { reader.read_le_value()? };
+    if header.magic != PROXMOX_BACKUP_CONTENT_HEADER_MAGIC_1_0 {
+        bail!("missing MediaContentHeader");
+    }
+    if header.content_magic != PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_0 {
+        bail!("file {} is no snapshot archive", file_number);
+    }
+
+    let archive_name = reader.read_exact_allocated(header.size as usize)?;
+    if archive_name != snapshot.as_bytes() {
+        bail!("found unexpected snapshot archive at file {} (expected {})", file_number, snapshot);
+    }
+
+    task_log!(worker, "restore snapshot archive {} {}", file_number, snapshot);
+
+    if !restore_snapshot_archive(worker, reader, snapshot_path)? {
+        bail!("snapshot archive {} is incomplete", snapshot);
+    }
+
+    Ok(())
+}
+
+/// Add all chunks referenced by a snapshot which are missing in the datastore
+fn collect_missing_chunks(
+    datastore: &DataStore,
+    snapshot_path: &Path,
+    needed_chunks: &mut HashSet<[u8;32]>,
+) -> Result<(), Error> {
+
+    let mut manifest_path = snapshot_path.to_owned();
+    manifest_path.push(MANIFEST_BLOB_NAME);
+    let mut file = std::fs::File::open(&manifest_path)?;
+    let manifest = BackupManifest::try_from(DataBlob::load_from_reader(&mut file)?)?;
+
+    for item in manifest.files() {
+        let mut archive_path = snapshot_path.to_owned();
+        archive_path.push(&item.filename);
+
+        let index: Box<dyn IndexFile> = match archive_type(&item.filename)? {
+            ArchiveType::DynamicIndex => Box::new(DynamicIndexReader::open(&archive_path)?),
+            ArchiveType::FixedIndex => Box::new(FixedIndexReader::open(&archive_path)?),
+            ArchiveType::Blob => continue,
+        };
+
+        for pos in 0..index.index_count() {
+            let digest = index.index_digest(pos).unwrap();
+            if needed_chunks.contains(digest) {
+                continue;
+            }
+            if !datastore.cond_touch_chunk(digest, false)? {
+                needed_chunks.insert(*digest);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Restore the chunks in `needed_chunks` from a chunk archive
+///
+/// Restored chunks get removed from `needed_chunks`. Returns the number
+/// of restored chunks.
+fn restore_partial_chunk_archive<'a>(
+    worker: &WorkerTask,
+    reader: Box<dyn 'a + TapeRead>,
+    datastore: &DataStore,
+    needed_chunks: &mut HashSet<[u8;32]>,
+) -> Result<usize, Error> {
+
+    let mut decoder = ChunkArchiveDecoder::new(reader);
+
+    let mut count = 0;
+
+    let result: Result<_, Error> = proxmox::try_block!({
+        while let Some((digest, blob)) = decoder.next_chunk()? {
+
+            worker.check_abort()?;
+
+            if !needed_chunks.contains(&digest) {
+                continue;
+            }
+
+            blob.verify_crc()?;
+            if blob.crypt_mode()? == CryptMode::None {
+                blob.decode(None, Some(&digest))?; // verify digest
+            }
+            if let Some(id) = blob.dictionary_id() {
+                // registered by the decoder when reading the dictionary entry
+                datastore.ensure_zstd_dictionary(&id)?;
+            }
+            datastore.insert_chunk(&blob, &digest)?;
+
+            needed_chunks.remove(&digest);
+            count += 1;
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => Ok(count),
+        Err(err) => {
+            // chunks of incomplete archives are still registered in the catalog
+            if let Ok(true) = decoder.reader().is_incomplete() {
+                return Ok(count);
+            }
+            Err(err)
+        }
+    }
+}
+
+/// Move a snapshot restored to a temporary directory into the datastore
+fn move_snapshot_into_datastore(
+    worker: &WorkerTask,
+    datastore: &DataStore,
+    backup_dir: &BackupDir,
+    authid: &Authid,
+    tmp_snapshot_path: &Path,
+) -> Result<(), Error> {
+
+    datastore.create_namespace_recursive(backup_dir.group().ns())?;
+
+    let (owner, _group_lock) = datastore.create_locked_backup_group(backup_dir.group(), authid)?;
+    if authid != &owner { // only the owner is allowed to create additional snapshots
+        bail!("restore '{}' failed - owner check failed ({} != {})", backup_dir, authid, owner);
+    }
+
+    let (rel_path, is_new, _snap_lock) = datastore.create_locked_backup_dir(backup_dir)?;
+    if !is_new {
+        task_log!(worker, "skip snapshot {} - already exists in datastore", backup_dir);
+        return Ok(());
+    }
+
+    let mut path = datastore.base_path();
+    path.push(rel_path);
+
+    let move_file = |name: &OsStr| -> Result<(), Error> {
+        let mut source = tmp_snapshot_path.to_owned();
+        source.push(name);
+        let mut target = path.clone();
+        target.push(name);
+        std::fs::rename(&source, &target)
+            .map_err(|err| format_err!("unable to move {:?} to {:?} - {}", source, target, err))
+    };
+
+    let result: Result<(), Error> = proxmox::try_block!({
+        // move the manifest last, so that the snapshot is complete once it shows up
+        for entry in std::fs::read_dir(tmp_snapshot_path)? {
+            let name = entry?.file_name();
+            if name != OsStr::new(MANIFEST_BLOB_NAME) {
+                move_file(&name)?;
+            }
+        }
+        move_file(OsStr::new(MANIFEST_BLOB_NAME))
+    });
+
+    if let Err(err) = result {
+        std::fs::remove_dir_all(&path)?;
+        bail!("restore snapshot {} failed - {}", backup_dir, err);
+    }
+
+    task_log!(worker, "restored snapshot {}", backup_dir);
+
+    Ok(())
 }
 
 /// Restore complete media content and catalog
@@ -371,6 +783,8 @@ fn restore_archive<'a>(
 
             if let Some((datastore, authid)) = target.as_ref() {
 
+                datastore.create_namespace_recursive(backup_dir.group().ns())?;
+
                 let (owner, _group_lock) = datastore.create_locked_backup_group(backup_dir.group(), authid)?;
                 if *authid != &owner { // only the owner is allowed to create additional snapshots
                     bail!("restore '{}' failed - owner check failed ({} != {})", snapshot, authid, owner);
@@ -454,6 +868,9 @@ fn restore_chunk_archive<'a>(
                     if blob.crypt_mode()? == CryptMode::None {
                         blob.decode(None, Some(&digest))?; // verify digest
                     }
+                    if let Some(id) = blob.dictionary_id() {
+                        datastore.ensure_zstd_dictionary(&id)?;
+                    }
                     if verbose {
                         task_log!(worker, "Insert chunk: {}", proxmox::tools::digest_to_hex(&digest));
                     }
@@ -1,6 +1,6 @@
 //! API Type Definitions
 
-use anyhow::bail;
+use anyhow::{bail, Error};
 use serde::{Deserialize, Serialize};
 
 use proxmox::api::{api, schema::*};
@@ -12,6 +12,9 @@ use crate::{
         CryptMode,
         Fingerprint,
         BACKUP_ID_REGEX,
+        BACKUP_NAMESPACE_REGEX,
+        BACKUP_NS_NAME_REGEX,
+        MAX_NAMESPACE_DEPTH,
         DirEntryAttribute,
         CatalogEntryType,
     },
@@ -34,6 +37,9 @@ pub use userid::{PROXMOX_TOKEN_ID_SCHEMA, PROXMOX_TOKEN_NAME_SCHEMA, PROXMOX_GRO
 mod tape;
 pub use tape::*;
 
+mod file_restore;
+pub use file_restore::*;
+
 // File names: may not contain slashes, may not start with "."
 pub const FILENAME_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|name| {
     if name.starts_with('.') {
@@ -75,6 +81,8 @@ const_regex!{
 
     /// Regex for verification jobs 'DATASTORE:ACTUAL_JOB_ID'
     pub VERIFICATION_JOB_WORKER_ID_REGEX = concat!(r"^(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):");
+    /// Regex for prune jobs 'DATASTORE:ACTUAL_JOB_ID'
+    pub PRUNE_JOB_WORKER_ID_REGEX = concat!(r"^(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):");
     /// Regex for sync jobs 'REMOTE:REMOTE_DATASTORE:LOCAL_DATASTORE:ACTUAL_JOB_ID'
     pub SYNC_JOB_WORKER_ID_REGEX = concat!(r"^(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):(", PROXMOX_SAFE_ID_REGEX_STR!(), r"):");
 
@@ -99,6 +107,10 @@ const_regex!{
     pub ZPOOL_NAME_REGEX = r"^[a-zA-Z][a-z0-9A-Z\-_.:]+$";
 
     pub UUID_REGEX = r"^[0-9a-f]{8}(?:-[0-9a-f]{4}){3}-[0-9a-f]{12}$";
+
+    pub S3_BUCKET_NAME_REGEX = r"^[a-z0-9][a-z0-9.\-]{1,61}[a-z0-9]$";
+
+    pub ZSTD_DICTIONARY_ID_REGEX = r"^[a-f0-9]{16}$";
 }
 
 pub const SYSTEMD_DATETIME_FORMAT: ApiStringFormat =
@@ -125,9 +137,18 @@ pub const PROXMOX_SAFE_ID_FORMAT: ApiStringFormat =
 pub const BACKUP_ID_FORMAT: ApiStringFormat =
     ApiStringFormat::Pattern(&BACKUP_ID_REGEX);
 
+pub const BACKUP_NAMESPACE_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&BACKUP_NAMESPACE_REGEX);
+
+pub const BACKUP_NS_NAME_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&BACKUP_NS_NAME_REGEX);
+
 pub const UUID_FORMAT: ApiStringFormat =
     ApiStringFormat::Pattern(&UUID_REGEX);
 
+pub const S3_BUCKET_NAME_FORMAT: ApiStringFormat =
+    ApiStringFormat::Pattern(&S3_BUCKET_NAME_REGEX);
+
 pub const SINGLE_LINE_COMMENT_FORMAT: ApiStringFormat =
     ApiStringFormat::Pattern(&SINGLE_LINE_COMMENT_REGEX);
 
@@ -341,6 +362,39 @@ pub const BACKUP_ID_SCHEMA: Schema =
     .format(&BACKUP_ID_FORMAT)
     .schema();
 
+pub const BACKUP_NAMESPACE_SCHEMA: Schema =
+    StringSchema::new("Namespace, a list of names separated by slashes ('' is the root namespace).")
+    .format(&BACKUP_NAMESPACE_FORMAT)
+    .max_length(256)
+    .schema();
+
+pub const BACKUP_NS_NAME_SCHEMA: Schema =
+    StringSchema::new("A single namespace component name.")
+    .format(&BACKUP_NS_NAME_FORMAT)
+    .min_length(1)
+    .max_length(32)
+    .schema();
+
+pub const NS_MAX_DEPTH_SCHEMA: Schema =
+    IntegerSchema::new("How many levels of namespaces should be operated on (0 == no recursion, unset == unlimited)")
+    .minimum(0)
+    .maximum(MAX_NAMESPACE_DEPTH as isize)
+    .schema();
+
+pub const GROUP_FILTER_LIST_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|list| {
+    crate::backup::GroupFilter::parse_list(list)?;
+    Ok(())
+});
+
+pub const GROUP_FILTER_LIST_SCHEMA: Schema = StringSchema::new(
+    "Semicolon separated list of group filters. Groups can be matched by type \
+    ('type:vm'), exactly ('group:vm/100') or with a regex on the backup-id \
+    ('regex:^prod-'). Filters prefixed with 'exclude:' exclude matching groups. \
+    A group is included if it matches any include filter (or there is none), \
+    and no exclude filter.")
+    .format(&GROUP_FILTER_LIST_FORMAT)
+    .schema();
+
 pub const BACKUP_TIME_SCHEMA: Schema =
     IntegerSchema::new("Backup time (Unix epoch.)")
     .minimum(1_547_797_308)
@@ -356,6 +410,11 @@ pub const DATASTORE_SCHEMA: Schema = StringSchema::new("Datastore name.")
     .max_length(32)
     .schema();
 
+pub const BACKING_DEVICE_SCHEMA: Schema =
+    StringSchema::new("File system UUID of the removable device the datastore is located on.")
+    .format(&UUID_FORMAT)
+    .schema();
+
 pub const MEDIA_SET_UUID_SCHEMA: Schema =
     StringSchema::new("MediaSet Uuid (We use the all-zero Uuid to reseve an empty media for a specific pool).")
     .format(&UUID_FORMAT)
@@ -366,6 +425,19 @@ pub const MEDIA_UUID_SCHEMA: Schema =
     .format(&UUID_FORMAT)
     .schema();
 
+pub const TAPE_RESTORE_SNAPSHOT_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|path| {
+    if path.parse::<crate::backup::BackupDir>().is_err() {
+        path.parse::<crate::backup::BackupGroup>()?;
+    }
+    Ok(())
+});
+
+pub const TAPE_RESTORE_SNAPSHOT_SCHEMA: Schema = StringSchema::new(
+    "Snapshot ('vm/100/2021-01-01T00:00:00Z') or group ('vm/100') path, optionally prefixed \
+    with a namespace path ('ns/tenant/vm/100').")
+    .format(&TAPE_RESTORE_SNAPSHOT_FORMAT)
+    .schema();
+
 pub const SYNC_SCHEDULE_SCHEMA: Schema = StringSchema::new(
     "Run sync job at specified schedule.")
     .format(&ApiStringFormat::VerifyFn(crate::tools::systemd::time::verify_calendar_event))
@@ -403,10 +475,25 @@ pub const JOB_ID_SCHEMA: Schema = StringSchema::new("Job ID.")
     .schema();
 
 pub const REMOVE_VANISHED_BACKUPS_SCHEMA: Schema = BooleanSchema::new(
-    "Delete vanished backups. This remove the local copy if the remote backup was deleted.")
+    "Delete vanished backups. This removes the copy on the sync target if the source backup was deleted.")
     .default(true)
     .schema();
 
+pub const TRANSFER_LAST_SCHEMA: Schema = IntegerSchema::new(
+    "Limit the transfer to the last N snapshots (per group), skipping older ones.")
+    .minimum(1)
+    .schema();
+
+pub const RATE_LIMIT_SCHEMA: Schema = IntegerSchema::new(
+    "Rate limit (for token bucket filter) in bytes/second.")
+    .minimum(1)
+    .schema();
+
+pub const BURST_SCHEMA: Schema = IntegerSchema::new(
+    "Size of the token bucket filter in bytes (defaults to the rate, i.e. one second of traffic).")
+    .minimum(1)
+    .schema();
+
 pub const IGNORE_VERIFIED_BACKUPS_SCHEMA: Schema = BooleanSchema::new(
     "Do not verify backups that are already verified if their verification is not outdated.")
     .default(true)
@@ -462,6 +549,20 @@ pub struct DataStoreListItem {
     pub comment: Option<String>,
 }
 
+#[api(
+    properties: {
+        ns: {
+            schema: BACKUP_NAMESPACE_SCHEMA,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all="kebab-case")]
+/// A namespace inside a datastore.
+pub struct NamespaceListItem {
+    pub ns: crate::backup::BackupNamespace,
+}
+
 #[api(
     properties: {
         "backup-type": {
@@ -503,6 +604,23 @@ pub struct GroupListItem {
     pub owner: Option<Authid>,
 }
 
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// How to detect changed files when creating pxar archives.
+pub enum PxarChangeDetectionMode {
+    /// Read and chunk the contents of all files.
+    Data,
+    /// Reuse the chunks of the previous snapshot for files with unchanged size and mtime.
+    Metadata,
+}
+
+impl Default for PxarChangeDetectionMode {
+    fn default() -> Self {
+        PxarChangeDetectionMode::Data
+    }
+}
+
 #[api()]
 #[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -591,6 +709,9 @@ pub struct SnapshotListItem {
     /// The owner of the snapshots group
     #[serde(skip_serializing_if="Option::is_none")]
     pub owner: Option<Authid>,
+    /// Protection from prunes and manual removal
+    #[serde(default)]
+    pub protected: bool,
 }
 
 #[api(
@@ -780,6 +901,30 @@ pub struct Counts {
     pub other: Option<TypeCounts>,
 }
 
+#[api()]
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all="kebab-case")]
+/// Datastore usage of a user or user group.
+///
+/// Backups owned by API tokens are accounted to the token's user.
+pub struct DataStoreUsage {
+    /// The user, or the user group prefixed with '@'.
+    pub subject: String,
+    /// Sum of the logical size of all index files (bytes).
+    pub logical_bytes: u64,
+    /// Size of all referenced chunks, counting each chunk once (bytes).
+    pub unique_bytes: u64,
+    /// Time the soft limit got exceeded (epoch).
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub soft_exceeded_since: Option<i64>,
+    /// Configured soft limit (bytes).
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub soft_limit: Option<u64>,
+    /// Configured hard limit (bytes).
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub hard_limit: Option<u64>,
+}
+
 #[api(
     properties: {
         "gc-status": {
@@ -790,6 +935,14 @@ pub struct Counts {
             type: Counts,
             optional: true,
         },
+        usage: {
+            description: "Usage per user and user group, as computed by the last GC.",
+            type: Array,
+            optional: true,
+            items: {
+                type: DataStoreUsage,
+            },
+        },
     },
 )]
 #[derive(Serialize, Deserialize)]
@@ -808,6 +961,9 @@ pub struct DataStoreStatus {
     /// Group/Snapshot counts
     #[serde(skip_serializing_if="Option::is_none")]
     pub counts: Option<Counts>,
+    /// Usage per user and user group
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub usage: Option<Vec<DataStoreUsage>>,
 }
 
 #[api(
@@ -1278,6 +1434,84 @@ pub enum Notify {
     Error,
 }
 
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Direction of a sync job
+pub enum SyncDirection {
+    /// Pull the contents of the remote datastore into the local datastore
+    Pull,
+    /// Push the contents of the local datastore to the remote datastore
+    Push,
+}
+
+impl Default for SyncDirection {
+    fn default() -> Self {
+        SyncDirection::Pull
+    }
+}
+
+#[api(
+    properties: {
+        "rate-in": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-in": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
+        "rate-out": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-out": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
+    },
+)]
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+/// Rate limits for incoming and outgoing traffic.
+pub struct RateLimitConfig {
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_out: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_out: Option<u64>,
+}
+
+impl RateLimitConfig {
+    /// Use the same rate and burst for both directions.
+    pub fn with_same_inout(rate: Option<u64>, burst: Option<u64>) -> Self {
+        Self {
+            rate_in: rate,
+            burst_in: burst,
+            rate_out: rate,
+            burst_out: burst,
+        }
+    }
+
+    /// Returns a copy where the limits of each unconfigured direction are taken from `fallback`.
+    pub fn or(&self, fallback: &RateLimitConfig) -> Self {
+        let (rate_in, burst_in) = if self.rate_in.is_some() {
+            (self.rate_in, self.burst_in)
+        } else {
+            (fallback.rate_in, fallback.burst_in)
+        };
+        let (rate_out, burst_out) = if self.rate_out.is_some() {
+            (self.rate_out, self.burst_out)
+        } else {
+            (fallback.rate_out, fallback.burst_out)
+        };
+        Self { rate_in, burst_in, rate_out, burst_out }
+    }
+}
+
 #[api(
     properties: {
         gc: {
@@ -1351,6 +1585,197 @@ pub const DATASTORE_NOTIFY_STRING_SCHEMA: Schema = StringSchema::new(
     .format(&ApiStringFormat::PropertyString(&DatastoreNotify::API_SCHEMA))
     .schema();
 
+#[api]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Kind of access a datastore operation needs, checked against the maintenance mode.
+pub enum Operation {
+    /// Read data from the datastore (restore, verify, tape backup, ...).
+    Read,
+    /// Write data to the datastore (backup, prune, garbage collection, ...).
+    Write,
+    /// Only look at the configuration or metadata, always allowed.
+    Lookup,
+}
+
+#[api]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Maintenance type
+pub enum MaintenanceType {
+    /// Only read operations are allowed on the datastore.
+    ReadOnly,
+    /// Neither read nor write operations are allowed on the datastore.
+    Offline,
+}
+
+pub const MAINTENANCE_MESSAGE_SCHEMA: Schema = StringSchema::new(
+    "Message describing the reason for the maintenance.")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .max_length(64)
+    .schema();
+
+#[api(
+    default_key: "type",
+    properties: {
+        type: {
+            type: MaintenanceType,
+        },
+        message: {
+            optional: true,
+            schema: MAINTENANCE_MESSAGE_SCHEMA,
+        }
+    },
+)]
+#[derive(Debug, Serialize, Deserialize)]
+/// Maintenance mode
+pub struct MaintenanceMode {
+    /// Type of maintenance ("read-only" or "offline").
+    #[serde(rename = "type")]
+    pub ty: MaintenanceType,
+    /// Reason for maintenance.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl MaintenanceMode {
+    /// Check if `operation` is allowed while this maintenance mode is active.
+    pub fn check(&self, operation: Option<Operation>) -> Result<(), Error> {
+        if let Some(Operation::Lookup) = operation {
+            return Ok(());
+        }
+
+        let message = self.message.as_deref().unwrap_or("no reason given");
+
+        match (self.ty, operation) {
+            (MaintenanceType::Offline, _) => {
+                bail!("datastore is offline for maintenance: {}", message);
+            }
+            (MaintenanceType::ReadOnly, Some(Operation::Write)) => {
+                bail!("datastore is in read-only maintenance mode: {}", message);
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub const MAINTENANCE_MODE_SCHEMA: Schema = StringSchema::new(
+    "Maintenance mode of the datastore")
+    .format(&ApiStringFormat::PropertyString(&MaintenanceMode::API_SCHEMA))
+    .schema();
+
+pub const S3_CLIENT_ID_SCHEMA: Schema = StringSchema::new("ID of an S3 client configuration.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+pub const S3_BUCKET_NAME_SCHEMA: Schema = StringSchema::new("Name of an S3 bucket.")
+    .format(&S3_BUCKET_NAME_FORMAT)
+    .schema();
+
+#[api]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Where the chunks of a datastore are stored
+pub enum DatastoreBackendType {
+    /// Chunks are stored in the local `.chunks` directory.
+    Filesystem,
+    /// Chunks are stored in an S3 bucket, the local `.chunks` directory is used as cache.
+    S3,
+}
+
+#[api(
+    default_key: "type",
+    properties: {
+        type: {
+            type: DatastoreBackendType,
+        },
+        client: {
+            optional: true,
+            schema: S3_CLIENT_ID_SCHEMA,
+        },
+        bucket: {
+            optional: true,
+            schema: S3_BUCKET_NAME_SCHEMA,
+        },
+        "cache-size": {
+            optional: true,
+            minimum: 1,
+        },
+        "cache-max-age": {
+            optional: true,
+            minimum: 1,
+        },
+    },
+)]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Chunk backend of a datastore
+pub struct DatastoreBackendConfig {
+    /// Type of the backend.
+    #[serde(rename = "type")]
+    pub ty: DatastoreBackendType,
+    /// S3 client configuration to use (S3 only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Bucket to store the chunks in (S3 only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    /// Maximum size of the local chunk cache in MiB, unlimited by default (S3 only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_size: Option<u64>,
+    /// Drop cached chunk contents this many days after they got cached (S3 only).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_max_age: Option<u64>,
+}
+
+pub const DATASTORE_BACKEND_SCHEMA: Schema = StringSchema::new(
+    "Chunk backend of the datastore, defaults to the local file system.")
+    .format(&ApiStringFormat::PropertyString(&DatastoreBackendConfig::API_SCHEMA))
+    .schema();
+
+pub const ZSTD_LEVEL_SCHEMA: Schema = IntegerSchema::new(
+    "Zstd compression level for chunks (0 disables compression).")
+    .minimum(0)
+    .maximum(19)
+    .default(1)
+    .schema();
+
+pub const ZSTD_DICTIONARY_ID_SCHEMA: Schema = StringSchema::new(
+    "ID of a trained zstd dictionary (first 8 bytes of its SHA256 sum, hex encoded).")
+    .format(&ApiStringFormat::Pattern(&ZSTD_DICTIONARY_ID_REGEX))
+    .schema();
+
+#[api(
+    default_key: "level",
+    properties: {
+        level: {
+            schema: ZSTD_LEVEL_SCHEMA,
+            optional: true,
+        },
+        dictionary: {
+            schema: ZSTD_DICTIONARY_ID_SCHEMA,
+            optional: true,
+        },
+    },
+)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+/// Chunk compression settings
+pub struct ChunkCompressionConfig {
+    /// Compression level.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub level: Option<u64>,
+    /// Dictionary to compress chunks with.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dictionary: Option<String>,
+}
+
+pub const CHUNK_COMPRESSION_SCHEMA: Schema = StringSchema::new(
+    "Chunk compression settings, defaults to zstd level 1 without dictionary.")
+    .format(&ApiStringFormat::PropertyString(&ChunkCompressionConfig::API_SCHEMA))
+    .schema();
+
 
 pub const PASSWORD_HINT_SCHEMA: Schema = StringSchema::new("Password hint.")
     .format(&SINGLE_LINE_COMMENT_FORMAT)
@@ -10,6 +10,8 @@ use anyhow::{bail, format_err, Error};
 use serde_json::json;
 
 use crate::api2::types::{Userid, UsernameRef, RealmRef};
+use crate::config::domains::LdapRealmConfig;
+use crate::tools::ldap::{escape_filter_value, LdapConnection, SearchScope};
 
 pub trait ProxmoxAuthenticator {
     fn authenticate_user(&self, username: &UsernameRef, password: &str) -> Result<(), Error>;
@@ -134,12 +136,97 @@ impl ProxmoxAuthenticator for PBS {
     }
 }
 
+/// Authenticates users against a LDAP server (or Active Directory)
+///
+/// The user entry is searched (bound as the configured bind DN, or
+/// anonymously), then the password is verified by binding as that entry.
+pub struct LdapAuthenticator {
+    config: LdapRealmConfig,
+}
+
+impl LdapAuthenticator {
+
+    pub fn new(config: LdapRealmConfig) -> Self {
+        Self { config }
+    }
+
+    /// Connect to the server, bound as the configured bind DN (if any).
+    pub fn connect(&self) -> Result<LdapConnection, Error> {
+        let mut conn = LdapConnection::connect(&self.config.connection_config())?;
+        if let Some(bind_dn) = &self.config.bind_dn {
+            conn.simple_bind(bind_dn, &self.config.password)
+                .map_err(|err| format_err!("bind as '{}' failed - {}", bind_dn, err))?;
+        }
+        Ok(conn)
+    }
+
+    /// Returns the search filter matching the user entry of `username`.
+    pub fn user_filter(&self, username: &str) -> String {
+        let user = format!("({}={})", self.config.user_attr, escape_filter_value(username));
+        match &self.config.filter {
+            Some(filter) => format!("(&{}{})", user, filter),
+            None => user,
+        }
+    }
+}
+
+impl ProxmoxAuthenticator for LdapAuthenticator {
+
+    fn authenticate_user(&self, username: &UsernameRef, password: &str) -> Result<(), Error> {
+        crate::tools::runtime::block_in_place(|| {
+            let mut conn = self.connect()?;
+
+            // "1.1" requests no attributes, we only need the DN
+            let filter = self.user_filter(username.as_str());
+            let entries = conn.search(&self.config.base_dn, SearchScope::Subtree, &filter, &["1.1"])?;
+
+            let dn = match entries.as_slice() {
+                [entry] => &entry.dn,
+                [] => bail!("no such user"),
+                _ => bail!("user name is not unique"),
+            };
+
+            conn.simple_bind(dn, password)
+        })
+    }
+
+    fn store_password(&self, _username: &UsernameRef, _password: &str) -> Result<(), Error> {
+        bail!("cannot change passwords of LDAP users");
+    }
+}
+
+/// Users of OpenID realms log in at the identity provider (see
+/// `api2::access::openid`), so there are no passwords to check or store.
+pub struct OpenIdPasswordless();
+
+impl ProxmoxAuthenticator for OpenIdPasswordless {
+
+    fn authenticate_user(&self, _username: &UsernameRef, _password: &str) -> Result<(), Error> {
+        bail!("OpenID realms do not support password authentication");
+    }
+
+    fn store_password(&self, _username: &UsernameRef, _password: &str) -> Result<(), Error> {
+        bail!("cannot set passwords of OpenID users");
+    }
+}
+
 /// Lookup the autenticator for the specified realm
 pub fn lookup_authenticator(realm: &RealmRef) -> Result<Box<dyn ProxmoxAuthenticator>, Error> {
     match realm.as_str() {
         "pam" => Ok(Box::new(PAM())),
         "pbs" => Ok(Box::new(PBS())),
-        _ => bail!("unknown realm '{}'", realm.as_str()),
+        realm => {
+            let (config, _digest) = crate::config::domains::config()?;
+            match config.sections.get(realm) {
+                Some((section_type, _)) if section_type == "ldap" => {
+                    Ok(Box::new(LdapAuthenticator::new(config.lookup("ldap", realm)?)))
+                }
+                Some((section_type, _)) if section_type == "openid" => {
+                    Ok(Box::new(OpenIdPasswordless()))
+                }
+                _ => bail!("unknown realm '{}'", realm),
+            }
+        }
     }
 }
 
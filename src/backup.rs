@@ -208,6 +208,9 @@ pub use chunker::*;
 mod data_blob;
 pub use data_blob::*;
 
+mod zstd_dict;
+pub use zstd_dict::*;
+
 mod data_blob_reader;
 pub use data_blob_reader::*;
 
@@ -229,6 +232,9 @@ pub use read_chunk::*;
 mod chunk_store;
 pub use chunk_store::*;
 
+mod s3_chunk_backend;
+pub use s3_chunk_backend::*;
+
 mod index;
 pub use index::*;
 
@@ -247,12 +253,21 @@ pub use prune::*;
 mod datastore;
 pub use datastore::*;
 
+mod task_tracking;
+pub use task_tracking::*;
+
 mod store_progress;
 pub use store_progress::*;
 
 mod verify;
 pub use verify::*;
 
+mod migrate;
+pub use migrate::*;
+
+mod quota;
+pub use quota::*;
+
 mod catalog_shell;
 pub use catalog_shell::*;
 
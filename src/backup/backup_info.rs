@@ -5,10 +5,14 @@ use std::os::unix::io::RawFd;
 
 use std::path::{Path, PathBuf};
 
+use proxmox::api::schema::Schema;
 use proxmox::const_regex;
 
 use super::manifest::MANIFEST_BLOB_NAME;
 
+/// Name of the marker file inside a snapshot directory flagging it as protected
+pub const PROTECTED_MARKER_FILENAME: &str = ".protected";
+
 macro_rules! BACKUP_ID_RE {
     () => {
         r"[A-Za-z0-9_][A-Za-z0-9._\-]*"
@@ -24,6 +28,11 @@ macro_rules! BACKUP_TIME_RE {
         r"[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9]{2}:[0-9]{2}:[0-9]{2}Z"
     };
 }
+macro_rules! BACKUP_NS_RE {
+    () => {
+        r"[A-Za-z0-9_][A-Za-z0-9._\-]*"
+    };
+}
 
 const_regex! {
     BACKUP_FILE_REGEX = r"^.*\.([fd]idx|blob)$";
@@ -32,6 +41,10 @@ const_regex! {
 
     pub BACKUP_ID_REGEX = concat!(r"^", BACKUP_ID_RE!(), r"$");
 
+    pub BACKUP_NS_NAME_REGEX = concat!(r"^", BACKUP_NS_RE!(), r"$");
+
+    pub BACKUP_NAMESPACE_REGEX = concat!(r"^(?:", BACKUP_NS_RE!(), r"(?:/", BACKUP_NS_RE!(), r")*)?$");
+
     BACKUP_DATE_REGEX = concat!(r"^", BACKUP_TIME_RE!() ,r"$");
 
     GROUP_PATH_REGEX = concat!(r"^(", BACKUP_TYPE_RE!(), ")/(", BACKUP_ID_RE!(), r")$");
@@ -40,9 +53,231 @@ const_regex! {
         r"^(", BACKUP_TYPE_RE!(), ")/(", BACKUP_ID_RE!(), ")/(", BACKUP_TIME_RE!(), r")$");
 }
 
+/// Maximum depth of nested backup namespaces (the root namespace has depth 0)
+pub const MAX_NAMESPACE_DEPTH: usize = 7;
+
+/// Name of the directory holding the child namespaces of a namespace
+const NAMESPACE_DIR_NAME: &str = "ns";
+
+/// A (possibly nested) namespace inside a datastore
+///
+/// Namespaces are stored as `ns/<name>` sub directories, so the
+/// namespace `a/b` is located at `<base>/ns/a/ns/b`. The root namespace
+/// is the datastore base directory itself, which keeps the layout
+/// compatible with datastores created before namespaces existed.
+#[derive(Debug, Default, Eq, PartialEq, Hash, Clone, Ord, PartialOrd)]
+pub struct BackupNamespace {
+    inner: Vec<String>,
+}
+
+impl BackupNamespace {
+    pub const API_SCHEMA: Schema = crate::api2::types::BACKUP_NAMESPACE_SCHEMA;
+
+    /// Returns the root namespace
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Parse a namespace like `a/b/c`. An empty string is the root namespace.
+    pub fn new(name: &str) -> Result<Self, Error> {
+        let mut ns = Self::root();
+        for component in name.split('/') {
+            if component.is_empty() {
+                continue;
+            }
+            ns.push(component.to_string())?;
+        }
+        Ok(ns)
+    }
+
+    /// Parse an optional API parameter, defaulting to the root namespace
+    pub fn from_opt(name: Option<&str>) -> Result<Self, Error> {
+        match name {
+            Some(name) => Self::new(name),
+            None => Ok(Self::root()),
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// The name of the last component, empty for the root namespace
+    pub fn name(&self) -> &str {
+        self.inner.last().map(String::as_str).unwrap_or("")
+    }
+
+    pub fn components(&self) -> impl Iterator<Item = &str> + '_ {
+        self.inner.iter().map(String::as_str)
+    }
+
+    /// Returns the parent namespace, or `None` for the root namespace
+    pub fn parent(&self) -> Option<Self> {
+        if self.is_root() {
+            return None;
+        }
+        let mut parent = self.clone();
+        parent.inner.pop();
+        Some(parent)
+    }
+
+    /// Append a child component
+    pub fn push(&mut self, name: String) -> Result<(), Error> {
+        if !BACKUP_NS_NAME_REGEX.is_match(&name) {
+            bail!("invalid backup namespace component '{}'", name);
+        }
+        if self.inner.len() >= MAX_NAMESPACE_DEPTH {
+            bail!("namespace too deep, maximum depth is {}", MAX_NAMESPACE_DEPTH);
+        }
+        self.inner.push(name);
+        Ok(())
+    }
+
+    /// Returns a new namespace for the child `name`
+    pub fn create_child(&self, name: &str) -> Result<Self, Error> {
+        let mut child = self.clone();
+        child.push(name.to_string())?;
+        Ok(child)
+    }
+
+    /// Returns true if `other` is this namespace or one of its descendants
+    pub fn contains(&self, other: &BackupNamespace) -> bool {
+        other.inner.len() >= self.inner.len() && other.inner[..self.inner.len()] == self.inner[..]
+    }
+
+    /// Returns the path relative to the datastore base directory.
+    pub fn path(&self) -> PathBuf {
+        let mut path = PathBuf::new();
+        for component in &self.inner {
+            path.push(NAMESPACE_DIR_NAME);
+            path.push(component);
+        }
+        path
+    }
+
+    /// Returns the ACL path components for this namespace on datastore `store`
+    pub fn acl_path<'a>(&'a self, store: &'a str) -> Vec<&'a str> {
+        let mut path = vec!["datastore", store];
+        path.extend(self.components());
+        path
+    }
+
+    /// List the direct child namespaces
+    pub fn list_children(&self, base_path: &Path) -> Result<Vec<BackupNamespace>, Error> {
+        let mut path = base_path.to_owned();
+        path.push(self.path());
+        path.push(NAMESPACE_DIR_NAME);
+
+        let mut list = Vec::new();
+
+        if !path.exists() {
+            return Ok(list);
+        }
+
+        tools::scandir(
+            libc::AT_FDCWD,
+            &path,
+            &BACKUP_NS_NAME_REGEX,
+            |_, name, file_type| {
+                if file_type == nix::dir::Type::Directory {
+                    list.push(self.create_child(name)?);
+                }
+                Ok(())
+            },
+        )?;
+
+        list.sort();
+
+        Ok(list)
+    }
+
+    /// List this namespace and all descendants up to `max_depth` levels below it
+    ///
+    /// Parents are always listed before their children.
+    pub fn list_recursive(
+        &self,
+        base_path: &Path,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<BackupNamespace>, Error> {
+        let mut list = vec![self.clone()];
+        let mut pos = 0;
+        while pos < list.len() {
+            let ns = list[pos].clone();
+            pos += 1;
+            if let Some(max_depth) = max_depth {
+                if ns.depth() - self.depth() >= max_depth {
+                    continue;
+                }
+            }
+            list.extend(ns.list_children(base_path)?);
+        }
+        Ok(list)
+    }
+
+    /// Map this namespace from below `source` to below `target`
+    ///
+    /// Used by sync jobs to place the content of a remote namespace into a
+    /// different local namespace.
+    pub fn map_prefix(
+        &self,
+        source: &BackupNamespace,
+        target: &BackupNamespace,
+    ) -> Result<BackupNamespace, Error> {
+        if !source.contains(self) {
+            bail!("namespace '{}' is not below '{}'", self, source);
+        }
+        let mut mapped = target.clone();
+        for component in &self.inner[source.inner.len()..] {
+            mapped.push(component.clone())?;
+        }
+        Ok(mapped)
+    }
+
+    /// Split a relative path like `ns/a/ns/b/vm/100` into the namespace and the remainder
+    fn split_path_prefix(path: &str) -> Result<(Self, &str), Error> {
+        let mut ns = Self::root();
+        let mut rest = path;
+        while let Some(stripped) = rest.strip_prefix("ns/") {
+            let mut parts = stripped.splitn(2, '/');
+            let name = parts.next().unwrap();
+            match parts.next() {
+                Some(tail) => {
+                    ns.push(name.to_string())?;
+                    rest = tail;
+                }
+                None => break,
+            }
+        }
+        Ok((ns, rest))
+    }
+}
+
+impl std::fmt::Display for BackupNamespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.inner.join("/"))
+    }
+}
+
+impl std::str::FromStr for BackupNamespace {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::new(name)
+    }
+}
+
+proxmox::forward_deserialize_to_from_str!(BackupNamespace);
+proxmox::forward_serialize_to_display!(BackupNamespace);
+
 /// BackupGroup is a directory containing a list of BackupDir
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct BackupGroup {
+    /// Namespace the group lives in
+    ns: BackupNamespace,
     /// Type of backup
     backup_type: String,
     /// Unique (for this type) ID
@@ -51,6 +286,10 @@ pub struct BackupGroup {
 
 impl std::cmp::Ord for BackupGroup {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        let ns_order = self.ns.cmp(&other.ns);
+        if ns_order != std::cmp::Ordering::Equal {
+            return ns_order;
+        }
         let type_order = self.backup_type.cmp(&other.backup_type);
         if type_order != std::cmp::Ordering::Equal {
             return type_order;
@@ -75,12 +314,25 @@ impl std::cmp::PartialOrd for BackupGroup {
 
 impl BackupGroup {
     pub fn new<T: Into<String>, U: Into<String>>(backup_type: T, backup_id: U) -> Self {
+        Self::with_ns(BackupNamespace::root(), backup_type, backup_id)
+    }
+
+    pub fn with_ns<T: Into<String>, U: Into<String>>(
+        ns: BackupNamespace,
+        backup_type: T,
+        backup_id: U,
+    ) -> Self {
         Self {
+            ns,
             backup_type: backup_type.into(),
             backup_id: backup_id.into(),
         }
     }
 
+    pub fn ns(&self) -> &BackupNamespace {
+        &self.ns
+    }
+
     pub fn backup_type(&self) -> &str {
         &self.backup_type
     }
@@ -90,7 +342,7 @@ impl BackupGroup {
     }
 
     pub fn group_path(&self) -> PathBuf {
-        let mut relative_path = PathBuf::new();
+        let mut relative_path = self.ns.path();
 
         relative_path.push(&self.backup_type);
 
@@ -114,11 +366,15 @@ impl BackupGroup {
                     return Ok(());
                 }
 
-                let backup_dir =
-                    BackupDir::with_rfc3339(&self.backup_type, &self.backup_id, backup_time)?;
+                let backup_dir = BackupDir {
+                    group: self.clone(),
+                    backup_time: proxmox::tools::time::parse_rfc3339(backup_time)?,
+                    backup_time_string: backup_time.to_owned(),
+                };
                 let files = list_backup_files(l2_fd, backup_time)?;
+                let protected = backup_dir.is_protected(base_path);
 
-                list.push(BackupInfo { backup_dir, files });
+                list.push(BackupInfo { backup_dir, files, protected });
 
                 Ok(())
             },
@@ -185,7 +441,11 @@ impl std::fmt::Display for BackupGroup {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let backup_type = self.backup_type();
         let id = self.backup_id();
-        write!(f, "{}/{}", backup_type, id)
+        if self.ns.is_root() {
+            write!(f, "{}/{}", backup_type, id)
+        } else {
+            write!(f, "{}/{}/{}", self.ns.path().display(), backup_type, id)
+        }
     }
 }
 
@@ -194,19 +454,146 @@ impl std::str::FromStr for BackupGroup {
 
     /// Parse a backup group path
     ///
-    /// This parses strings like `vm/100".
+    /// This parses strings like `vm/100", optionally prefixed with a
+    /// namespace path like `ns/tenant/vm/100`.
     fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let (ns, group_path) = BackupNamespace::split_path_prefix(path)?;
+
         let cap = GROUP_PATH_REGEX
-            .captures(path)
+            .captures(group_path)
             .ok_or_else(|| format_err!("unable to parse backup group path '{}'", path))?;
 
         Ok(Self {
+            ns,
             backup_type: cap.get(1).unwrap().as_str().to_owned(),
             backup_id: cap.get(2).unwrap().as_str().to_owned(),
         })
     }
 }
 
+/// What a [GroupFilter] matches against
+#[derive(Clone, Debug)]
+pub enum FilterType {
+    /// Matches all groups of a backup type (`type:vm`)
+    BackupType(String),
+    /// Matches exactly one group, regardless of its namespace (`group:vm/100`)
+    Group(String, String),
+    /// Matches groups whose backup-id matches the regex (`regex:^prod-`)
+    Regex(regex::Regex),
+}
+
+/// Include or exclude backup groups, e.g. when syncing a datastore
+///
+/// Filters are written as `<type>:<value>`, optionally prefixed with
+/// `exclude:` (or `include:`, the default).
+#[derive(Clone, Debug)]
+pub struct GroupFilter {
+    pub is_exclude: bool,
+    pub filter_type: FilterType,
+}
+
+impl std::str::FromStr for GroupFilter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (is_exclude, filter) = if let Some(filter) = s.strip_prefix("exclude:") {
+            (true, filter)
+        } else {
+            (false, s.strip_prefix("include:").unwrap_or(s))
+        };
+
+        let mut parts = filter.splitn(2, ':');
+        let kind = parts.next().unwrap();
+        let value = parts
+            .next()
+            .ok_or_else(|| format_err!("group filter '{}' has no value", s))?;
+
+        let filter_type = match kind {
+            "type" => {
+                if !BACKUP_TYPE_REGEX.is_match(value) {
+                    bail!("invalid backup type '{}' in group filter", value);
+                }
+                FilterType::BackupType(value.to_string())
+            }
+            "group" => {
+                let cap = GROUP_PATH_REGEX
+                    .captures(value)
+                    .ok_or_else(|| format_err!("invalid backup group '{}' in group filter", value))?;
+                FilterType::Group(
+                    cap.get(1).unwrap().as_str().to_owned(),
+                    cap.get(2).unwrap().as_str().to_owned(),
+                )
+            }
+            "regex" => FilterType::Regex(
+                regex::Regex::new(value)
+                    .map_err(|err| format_err!("invalid regex in group filter - {}", err))?,
+            ),
+            _ => bail!("unknown group filter type '{}'", kind),
+        };
+
+        Ok(Self { is_exclude, filter_type })
+    }
+}
+
+impl std::fmt::Display for GroupFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_exclude {
+            write!(f, "exclude:")?;
+        }
+        match &self.filter_type {
+            FilterType::BackupType(backup_type) => write!(f, "type:{}", backup_type),
+            FilterType::Group(backup_type, backup_id) => write!(f, "group:{}/{}", backup_type, backup_id),
+            FilterType::Regex(regex) => write!(f, "regex:{}", regex.as_str()),
+        }
+    }
+}
+
+impl GroupFilter {
+    /// Parse a semicolon separated list of group filters
+    ///
+    /// Commas are part of regex syntax (e.g. `\d{1,3}`), but a `;` can never match
+    /// a backup ID, so it is safe to split on it.
+    pub fn parse_list(list: &str) -> Result<Vec<Self>, Error> {
+        list.split(';')
+            .map(str::trim)
+            .filter(|filter| !filter.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl BackupGroup {
+    /// Test if the group matches `filter_type`
+    pub fn matches(&self, filter_type: &FilterType) -> bool {
+        match filter_type {
+            FilterType::BackupType(backup_type) => self.backup_type == *backup_type,
+            FilterType::Group(backup_type, backup_id) => {
+                self.backup_type == *backup_type && self.backup_id == *backup_id
+            }
+            FilterType::Regex(regex) => regex.is_match(&self.backup_id),
+        }
+    }
+
+    /// Test if the group passes `filters`
+    ///
+    /// A group passes if it matches any include filter (or there is none),
+    /// and no exclude filter.
+    pub fn apply_filters(&self, filters: &[GroupFilter]) -> bool {
+        let mut is_included = filters.iter().all(|filter| filter.is_exclude);
+
+        for filter in filters {
+            if self.matches(&filter.filter_type) {
+                if filter.is_exclude {
+                    return false;
+                }
+                is_included = true;
+            }
+        }
+
+        is_included
+    }
+}
+
 /// Uniquely identify a Backup (relative to data store)
 ///
 /// We also call this a backup snaphost.
@@ -279,6 +666,19 @@ impl BackupDir {
         relative_path
     }
 
+    /// Returns the path of the file marking this snapshot as protected
+    pub fn protected_file(&self, base_path: &Path) -> PathBuf {
+        let mut path = base_path.to_owned();
+        path.push(self.relative_path());
+        path.push(PROTECTED_MARKER_FILENAME);
+        path
+    }
+
+    /// Protected snapshots are never removed by prune, manual delete or sync
+    pub fn is_protected(&self, base_path: &Path) -> bool {
+        self.protected_file(base_path).exists()
+    }
+
     pub fn backup_time_to_string(backup_time: i64) -> Result<String, Error> {
         // fixme: can this fail? (avoid unwrap)
         proxmox::tools::time::epoch_to_rfc3339_utc(backup_time)
@@ -290,25 +690,33 @@ impl std::str::FromStr for BackupDir {
 
     /// Parse a snapshot path
     ///
-    /// This parses strings like `host/elsa/2020-06-15T05:18:33Z".
+    /// This parses strings like `host/elsa/2020-06-15T05:18:33Z", optionally
+    /// prefixed with a namespace path like `ns/tenant/host/elsa/...`.
     fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let (ns, snapshot_path) = BackupNamespace::split_path_prefix(path)?;
+
         let cap = SNAPSHOT_PATH_REGEX
-            .captures(path)
+            .captures(snapshot_path)
             .ok_or_else(|| format_err!("unable to parse backup snapshot path '{}'", path))?;
 
-        BackupDir::with_rfc3339(
-            cap.get(1).unwrap().as_str(),
-            cap.get(2).unwrap().as_str(),
-            cap.get(3).unwrap().as_str(),
-        )
+        let backup_time_string = cap.get(3).unwrap().as_str().to_owned();
+        let backup_time = proxmox::tools::time::parse_rfc3339(&backup_time_string)?;
+
+        Ok(Self {
+            group: BackupGroup::with_ns(
+                ns,
+                cap.get(1).unwrap().as_str(),
+                cap.get(2).unwrap().as_str(),
+            ),
+            backup_time,
+            backup_time_string,
+        })
     }
 }
 
 impl std::fmt::Display for BackupDir {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let backup_type = self.group.backup_type();
-        let id = self.group.backup_id();
-        write!(f, "{}/{}/{}", backup_type, id, self.backup_time_string)
+        write!(f, "{}/{}", self.group, self.backup_time_string)
     }
 }
 
@@ -319,6 +727,8 @@ pub struct BackupInfo {
     pub backup_dir: BackupDir,
     /// List of data files
     pub files: Vec<String>,
+    /// Protection Status
+    pub protected: bool,
 }
 
 impl BackupInfo {
@@ -327,8 +737,9 @@ impl BackupInfo {
         path.push(backup_dir.relative_path());
 
         let files = list_backup_files(libc::AT_FDCWD, &path)?;
+        let protected = backup_dir.is_protected(base_path);
 
-        Ok(BackupInfo { backup_dir, files })
+        Ok(BackupInfo { backup_dir, files, protected })
     }
 
     /// Finds the latest backup inside a backup group
@@ -363,12 +774,19 @@ impl BackupInfo {
         Ok(files)
     }
 
-    pub fn list_backup_groups(base_path: &Path) -> Result<Vec<BackupGroup>, Error> {
+    /// List the backup groups of namespace `ns` (not recursive)
+    pub fn list_backup_groups(
+        base_path: &Path,
+        ns: &BackupNamespace,
+    ) -> Result<Vec<BackupGroup>, Error> {
         let mut list = Vec::new();
 
+        let mut ns_path = base_path.to_owned();
+        ns_path.push(ns.path());
+
         tools::scandir(
             libc::AT_FDCWD,
-            base_path,
+            &ns_path,
             &BACKUP_TYPE_REGEX,
             |l0_fd, backup_type, file_type| {
                 if file_type != nix::dir::Type::Directory {
@@ -383,7 +801,7 @@ impl BackupInfo {
                             return Ok(());
                         }
 
-                        list.push(BackupGroup::new(backup_type, backup_id));
+                        list.push(BackupGroup::with_ns(ns.clone(), backup_type, backup_id));
 
                         Ok(())
                     },
@@ -394,6 +812,19 @@ impl BackupInfo {
         Ok(list)
     }
 
+    /// List the backup groups of namespace `ns` and the namespaces below it
+    pub fn list_backup_groups_recursive(
+        base_path: &Path,
+        ns: &BackupNamespace,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<BackupGroup>, Error> {
+        let mut list = Vec::new();
+        for ns in ns.list_recursive(base_path, max_depth)? {
+            list.extend(Self::list_backup_groups(base_path, &ns)?);
+        }
+        Ok(list)
+    }
+
     pub fn is_finished(&self) -> bool {
         // backup is considered unfinished if there is no manifest
         self.files
@@ -418,3 +849,165 @@ fn list_backup_files<P: ?Sized + nix::NixPath>(
 
     Ok(files)
 }
+
+#[test]
+fn test_group_filter_parse() -> Result<(), Error> {
+    let filter: GroupFilter = "type:vm".parse()?;
+    assert!(!filter.is_exclude);
+    assert!(matches!(filter.filter_type, FilterType::BackupType(ref ty) if ty == "vm"));
+
+    let filter: GroupFilter = "include:group:ct/100".parse()?;
+    assert!(!filter.is_exclude);
+    assert!(matches!(filter.filter_type, FilterType::Group(ref ty, ref id) if ty == "ct" && id == "100"));
+
+    let filter: GroupFilter = "exclude:regex:^vm-\\d{1,3}$".parse()?;
+    assert!(filter.is_exclude);
+    assert_eq!(filter.to_string(), "exclude:regex:^vm-\\d{1,3}$");
+
+    for invalid in &[
+        "vm",
+        "type:",
+        "type:invalid",
+        "group:vm",
+        "group:vm/",
+        "regex:(",
+        "exclude:",
+        "unknown:vm",
+    ] {
+        assert!(invalid.parse::<GroupFilter>().is_err(), "'{}' should not parse", invalid);
+    }
+
+    let list = GroupFilter::parse_list("type:vm; regex:^vm-\\d{1,3}$;;exclude:group:vm/100")?;
+    assert_eq!(list.len(), 3);
+    assert_eq!(list[1].to_string(), "regex:^vm-\\d{1,3}$");
+
+    assert!(GroupFilter::parse_list("type:vm;type:invalid").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_group_filter_apply() -> Result<(), Error> {
+    let vm100 = BackupGroup::new("vm", "100");
+    let vm1000 = BackupGroup::new("vm", "1000");
+    let ct100 = BackupGroup::new("ct", "100");
+    let host = BackupGroup::new("host", "prod-web");
+
+    // no filters, everything passes
+    assert!(vm100.apply_filters(&[]));
+
+    let filters = GroupFilter::parse_list("type:vm")?;
+    assert!(vm100.apply_filters(&filters));
+    assert!(!ct100.apply_filters(&filters));
+
+    let filters = GroupFilter::parse_list("group:ct/100")?;
+    assert!(ct100.apply_filters(&filters));
+    assert!(!vm100.apply_filters(&filters));
+
+    let filters = GroupFilter::parse_list("regex:^\\d{1,3}$")?;
+    assert!(vm100.apply_filters(&filters));
+    assert!(ct100.apply_filters(&filters));
+    assert!(!vm1000.apply_filters(&filters));
+    assert!(!host.apply_filters(&filters));
+
+    // only exclude filters, everything else passes
+    let filters = GroupFilter::parse_list("exclude:type:ct")?;
+    assert!(vm100.apply_filters(&filters));
+    assert!(host.apply_filters(&filters));
+    assert!(!ct100.apply_filters(&filters));
+
+    // any include filter matches, exclude filters win
+    let filters = GroupFilter::parse_list("type:vm;regex:^prod-;exclude:group:vm/1000")?;
+    assert!(vm100.apply_filters(&filters));
+    assert!(host.apply_filters(&filters));
+    assert!(!vm1000.apply_filters(&filters));
+    assert!(!ct100.apply_filters(&filters));
+
+    Ok(())
+}
+
+#[test]
+fn test_backup_namespace_parse() -> Result<(), Error> {
+    let root = BackupNamespace::new("")?;
+    assert!(root.is_root());
+    assert_eq!(root, BackupNamespace::from_opt(None)?);
+    assert_eq!(root.path(), PathBuf::new());
+    assert_eq!(root.acl_path("store"), vec!["datastore", "store"]);
+
+    let ns: BackupNamespace = "a/b_1//c-2.x/".parse()?;
+    assert_eq!(ns.depth(), 3);
+    assert_eq!(ns.name(), "c-2.x");
+    assert_eq!(ns.to_string(), "a/b_1/c-2.x");
+    assert_eq!(ns.path(), PathBuf::from("ns/a/ns/b_1/ns/c-2.x"));
+    assert_eq!(ns.acl_path("store"), vec!["datastore", "store", "a", "b_1", "c-2.x"]);
+    assert_eq!(ns.parent(), Some("a/b_1".parse()?));
+    assert_eq!(root.parent(), None);
+
+    for invalid in &[".hidden", "a/-b", "a/b c", "a/ü"] {
+        assert!(BackupNamespace::new(invalid).is_err(), "'{}' should not parse", invalid);
+    }
+
+    let a: BackupNamespace = "a".parse()?;
+    assert!(root.contains(&ns));
+    assert!(a.contains(&ns));
+    assert!(ns.contains(&ns));
+    assert!(!ns.contains(&a));
+    assert!(!a.contains(&"ab".parse()?));
+
+    let mapped = ns.map_prefix(&a, &"x/y".parse()?)?;
+    assert_eq!(mapped.to_string(), "x/y/b_1/c-2.x");
+    assert!(a.map_prefix(&ns, &root).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_backup_namespace_depth() -> Result<(), Error> {
+    let max: Vec<String> = (0..MAX_NAMESPACE_DEPTH).map(|i| format!("n{}", i)).collect();
+    let max = max.join("/");
+
+    let mut ns = BackupNamespace::new(&max)?;
+    assert_eq!(ns.depth(), MAX_NAMESPACE_DEPTH);
+    assert!(ns.create_child("deeper").is_err());
+    assert!(ns.push("deeper".to_string()).is_err());
+    assert_eq!(ns.depth(), MAX_NAMESPACE_DEPTH);
+    assert!(BackupNamespace::new(&format!("{}/deeper", max)).is_err());
+
+    // mapping into a deeper target must not exceed the limit either
+    let source: BackupNamespace = "n0".parse()?;
+    let target: BackupNamespace = "t0/t1".parse()?;
+    assert!(ns.map_prefix(&source, &target).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_backup_namespace_path_prefix() -> Result<(), Error> {
+    let (ns, rest) = BackupNamespace::split_path_prefix("vm/100")?;
+    assert!(ns.is_root());
+    assert_eq!(rest, "vm/100");
+
+    let (ns, rest) = BackupNamespace::split_path_prefix("ns/a/ns/b/vm/100/2020-06-15T05:18:33Z")?;
+    assert_eq!(ns.to_string(), "a/b");
+    assert_eq!(rest, "vm/100/2020-06-15T05:18:33Z");
+
+    // a trailing 'ns/<name>' without remainder is not a namespace prefix
+    let (ns, rest) = BackupNamespace::split_path_prefix("ns/a")?;
+    assert!(ns.is_root());
+    assert_eq!(rest, "ns/a");
+
+    assert!(BackupNamespace::split_path_prefix("ns/.a/vm/100").is_err());
+
+    let too_deep = "ns/x/".repeat(MAX_NAMESPACE_DEPTH + 1) + "vm/100";
+    assert!(BackupNamespace::split_path_prefix(&too_deep).is_err());
+
+    let group: BackupGroup = "ns/a/vm/100".parse()?;
+    assert_eq!(group.ns().to_string(), "a");
+    assert_eq!(group.backup_id(), "100");
+
+    let snapshot: BackupDir = "ns/a/ns/b/host/elsa/2020-06-15T05:18:33Z".parse()?;
+    assert_eq!(snapshot.group().ns().to_string(), "a/b");
+    assert_eq!(snapshot.group().backup_type(), "host");
+
+    Ok(())
+}
@@ -10,16 +10,20 @@ use proxmox::tools::fs::{CreateOptions, create_path, create_dir};
 use crate::tools;
 use crate::api2::types::GarbageCollectionStatus;
 
-use super::DataBlob;
+use super::{DataBlob, S3ChunkBackend};
 use crate::task::TaskState;
 
 /// File system based chunk store
+///
+/// With an S3 backend, the chunks are stored in a bucket and the chunk
+/// directory only holds empty marker files or cached chunk contents.
 pub struct ChunkStore {
     name: String, // used for error reporting
     pub (crate) base: PathBuf,
     chunk_dir: PathBuf,
     mutex: Mutex<()>,
     locker: Arc<Mutex<tools::ProcessLocker>>,
+    s3: Option<S3ChunkBackend>,
 }
 
 // TODO: what about sysctl setting vm.vfs_cache_pressure (0 - 100) ?
@@ -143,10 +147,20 @@ impl ChunkStore {
             base,
             chunk_dir,
             locker,
-            mutex: Mutex::new(())
+            mutex: Mutex::new(()),
+            s3: None,
         })
     }
 
+    /// Store the chunks in an S3 bucket instead of the chunk directory.
+    pub fn set_s3_backend(&mut self, backend: S3ChunkBackend) {
+        self.s3 = Some(backend);
+    }
+
+    pub fn s3_backend(&self) -> Option<&S3ChunkBackend> {
+        self.s3.as_ref()
+    }
+
     pub fn touch_chunk(&self, digest: &[u8; 32]) -> Result<(), Error> {
         self.cond_touch_chunk(digest, true)?;
         Ok(())
@@ -189,6 +203,18 @@ impl ChunkStore {
     ) -> Result<
         impl Iterator<Item = (Result<tools::fs::ReadDirEntry, Error>, usize, bool)> + std::iter::FusedIterator,
         Error
+    > {
+        self.chunk_iterator(false)
+    }
+
+    // like get_chunk_iterator, but optionally also returns the upload markers
+    // of an S3 backend
+    fn chunk_iterator(
+        &self,
+        with_upload_markers: bool,
+    ) -> Result<
+        impl Iterator<Item = (Result<tools::fs::ReadDirEntry, Error>, usize, bool)> + std::iter::FusedIterator,
+        Error
     > {
         use nix::dir::Dir;
         use nix::fcntl::OFlag;
@@ -219,7 +245,10 @@ impl ChunkStore {
                         Some(Ok(entry)) => {
                             // skip files if they're not a hash
                             let bytes = entry.file_name().to_bytes();
-                            if bytes.len() != 64 && bytes.len() != 64 + ".0.bad".len() {
+                            let upload_marker = with_upload_markers
+                                && bytes.len() == 64 + ".uploading".len()
+                                && bytes.ends_with(b".uploading");
+                            if bytes.len() != 64 && bytes.len() != 64 + ".0.bad".len() && !upload_marker {
                                 continue;
                             }
                             if !bytes.iter().take(64).all(u8::is_ascii_hexdigit) {
@@ -295,7 +324,14 @@ impl ChunkStore {
         let mut last_percentage = 0;
         let mut chunk_count = 0;
 
-        for (entry, percentage, bad) in self.get_chunk_iterator()? {
+        // with an S3 backend, the sizes are taken from the bucket listing below
+        let count_bytes = self.s3.is_none();
+
+        let cache_policy = self.s3.as_ref().map(|s3| s3.cache_policy().clone());
+        let now = proxmox::tools::time::epoch_i64();
+        let mut cached_chunks = Vec::new();
+
+        for (entry, percentage, bad) in self.chunk_iterator(self.s3.is_some())? {
             if last_percentage != percentage {
                 last_percentage = percentage;
                 crate::task_log!(
@@ -322,10 +358,24 @@ impl ChunkStore {
                 continue;
             }
 
-            chunk_count += 1;
-
             let filename = entry.file_name();
 
+            if filename.to_bytes().ends_with(b".uploading") {
+                // a marker of an upload which got interrupted (e.g. by a crash)
+                // protects the object forever, so expire it like an unused chunk
+                let _lock = self.mutex.lock();
+                if let Ok(stat) = fstatat(dirfd, filename, nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW) {
+                    if stat.st_mtime < min_atime {
+                        if let Err(err) = unlinkat(Some(dirfd), filename, UnlinkatFlags::NoRemoveDir) {
+                            crate::task_warn!(worker, "unable to remove stale upload marker {:?} - {}", filename, err);
+                        }
+                    }
+                }
+                continue;
+            }
+
+            chunk_count += 1;
+
             let lock = self.mutex.lock();
 
             if let Ok(stat) = fstatat(dirfd, filename, nix::fcntl::AtFlags::AT_SYMLINK_NOFOLLOW) {
@@ -348,24 +398,120 @@ impl ChunkStore {
                     } else {
                         status.removed_chunks += 1;
                     }
-                    status.removed_bytes += stat.st_size as u64;
-                } else if stat.st_atime < oldest_writer {
-                    if bad {
-                        status.still_bad += 1;
-                    } else {
-                        status.pending_chunks += 1;
+                    if count_bytes {
+                        status.removed_bytes += stat.st_size as u64;
                     }
-                    status.pending_bytes += stat.st_size as u64;
                 } else {
-                    if !bad {
-                        status.disk_chunks += 1;
+                    if stat.st_atime < oldest_writer {
+                        if bad {
+                            status.still_bad += 1;
+                        } else {
+                            status.pending_chunks += 1;
+                        }
+                        if count_bytes {
+                            status.pending_bytes += stat.st_size as u64;
+                        }
+                    } else {
+                        if !bad {
+                            status.disk_chunks += 1;
+                        }
+                        if count_bytes {
+                            status.disk_bytes += stat.st_size as u64;
+                        }
+                    }
+
+                    // the content of cached chunks was written when it got cached
+                    if let Some(ref cache_policy) = cache_policy {
+                        if !bad && stat.st_size > 0 {
+                            let name = filename.to_string_lossy().into_owned();
+                            if cache_policy.is_expired(stat.st_mtime, now) {
+                                if let Err(err) = self.replace_with_marker(&name) {
+                                    crate::task_warn!(worker, "unable to drop cached chunk {} - {}", name, err);
+                                }
+                            } else if cache_policy.max_size.is_some() {
+                                cached_chunks.push((stat.st_mtime, stat.st_size as u64, name));
+                            }
+                        }
                     }
-                    status.disk_bytes += stat.st_size as u64;
                 }
             }
             drop(lock);
         }
 
+        if let Some(ref cache_policy) = cache_policy {
+            let evict_list = cache_policy.select_evictions(cached_chunks);
+            if !evict_list.is_empty() {
+                crate::task_log!(worker, "dropping {} chunks from the cache to reduce its size", evict_list.len());
+            }
+            for name in evict_list {
+                worker.check_abort()?;
+                let _lock = self.mutex.lock();
+                if let Err(err) = self.replace_with_marker(&name) {
+                    crate::task_warn!(worker, "unable to drop cached chunk {} - {}", name, err);
+                }
+            }
+        }
+
+        if let Some(ref s3) = self.s3 {
+            crate::task_log!(worker, "sweeping unused chunks in bucket '{}'", s3.bucket());
+            s3.for_each_chunk(|digest_str, object| {
+                worker.check_abort()?;
+                tools::fail_on_shutdown()?;
+
+                let (chunk_path, uploading_path) = self.chunk_paths_from_str(digest_str);
+
+                let _lock = self.mutex.lock();
+
+                if chunk_path.exists() || uploading_path.exists() {
+                    status.disk_bytes += object.size;
+                } else if object.last_modified < min_atime {
+                    s3.remove_chunk(digest_str)?;
+                    status.removed_bytes += object.size;
+                } else {
+                    status.pending_bytes += object.size;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // returns the paths of the marker and of the in-progress upload marker
+    fn chunk_paths_from_str(&self, digest_str: &str) -> (PathBuf, PathBuf) {
+        let mut chunk_path = self.chunk_dir.clone();
+        chunk_path.push(&digest_str[..4]);
+        chunk_path.push(digest_str);
+        let mut uploading_path = chunk_path.clone();
+        uploading_path.set_extension("uploading");
+        (chunk_path, uploading_path)
+    }
+
+    // atomically replaces a cached chunk by an empty marker with the same atime,
+    // the caller holds the mutex
+    fn replace_with_marker(&self, digest_str: &str) -> Result<(), Error> {
+        use nix::sys::time::{TimeVal, TimeValLike};
+
+        let (chunk_path, _) = self.chunk_paths_from_str(digest_str);
+
+        let atime = match nix::sys::stat::stat(&chunk_path) {
+            Ok(stat) if stat.st_size > 0 => stat.st_atime,
+            Ok(_) => return Ok(()), // already a marker
+            Err(nix::Error::Sys(nix::errno::Errno::ENOENT)) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut tmp_path = chunk_path.clone();
+        tmp_path.set_extension("tmp");
+
+        std::fs::File::create(&tmp_path)?;
+        let now = proxmox::tools::time::epoch_i64();
+        nix::sys::stat::utimes(&tmp_path, &TimeVal::seconds(atime), &TimeVal::seconds(now))?;
+
+        if let Err(err) = std::fs::rename(&tmp_path, &chunk_path) {
+            let _ = std::fs::remove_file(&tmp_path);
+            return Err(err.into());
+        }
         Ok(())
     }
 
@@ -379,17 +525,41 @@ impl ChunkStore {
 
         let (chunk_path, digest_str) = self.chunk_path(digest);
 
-        let lock = self.mutex.lock();
+        let mut lock = self.mutex.lock();
 
+        let mut is_marker = false;
         if let Ok(metadata) = std::fs::metadata(&chunk_path) {
             if metadata.is_file() {
                 self.touch_chunk(digest)?;
-                return Ok((true, metadata.len()));
+                // with an S3 backend, an empty file only marks a chunk in the bucket
+                if metadata.len() > 0 || self.s3.is_none() {
+                    return Ok((true, metadata.len()));
+                }
+                is_marker = true;
             } else {
                 bail!("Got unexpected file type on store '{}' for chunk {}", self.name, digest_str);
             }
         }
 
+        if let Some(ref s3) = self.s3 {
+            if is_marker {
+                drop(lock);
+                if let Some(size) = s3.chunk_size(&digest_str)? {
+                    return Ok((true, size));
+                }
+                // the object is missing in the bucket, upload it again
+                lock = self.mutex.lock();
+            }
+
+            // protects the chunk from garbage collection while the upload is running
+            let mut uploading_path = chunk_path.clone();
+            uploading_path.set_extension("uploading");
+            std::fs::File::create(&uploading_path)?;
+            drop(lock);
+
+            return self.upload_chunk(s3, chunk, &chunk_path, &uploading_path, &digest_str);
+        }
+
         let mut tmp_path = chunk_path.clone();
         tmp_path.set_extension("tmp");
 
@@ -415,6 +585,72 @@ impl ChunkStore {
         Ok((false, encoded_size))
     }
 
+    // uploads the chunk, then replaces the upload marker by the chunk marker
+    fn upload_chunk(
+        &self,
+        s3: &S3ChunkBackend,
+        chunk: &DataBlob,
+        chunk_path: &Path,
+        uploading_path: &Path,
+        digest_str: &str,
+    ) -> Result<(bool, u64), Error> {
+        let raw_data = chunk.raw_data();
+
+        if let Err(err) = s3.upload_chunk(digest_str, raw_data) {
+            let _lock = self.mutex.lock();
+            let _ = std::fs::remove_file(uploading_path);
+            bail!("store '{}' - {}", self.name, err);
+        }
+
+        let _lock = self.mutex.lock();
+        if let Err(err) = std::fs::rename(uploading_path, chunk_path) {
+            // a concurrent upload of the same chunk may have finished first
+            if !chunk_path.exists() {
+                bail!(
+                    "unable to create marker on store '{}' for chunk {} - {}",
+                    self.name,
+                    digest_str,
+                    err,
+                );
+            }
+        }
+
+        Ok((false, raw_data.len() as u64))
+    }
+
+    /// Returns the raw data of a chunk.
+    ///
+    /// With an S3 backend, chunks which are not cached locally get downloaded
+    /// and added to the cache.
+    pub fn load_raw_chunk(&self, digest: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        let (chunk_path, digest_str) = self.chunk_path(digest);
+
+        let s3 = match self.s3 {
+            Some(ref s3) => s3,
+            None => return Ok(std::fs::read(&chunk_path)?),
+        };
+
+        match std::fs::read(&chunk_path) {
+            Ok(data) if !data.is_empty() => return Ok(data),
+            Ok(_) => (), // just a marker
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err.into()),
+        }
+
+        let data = s3.download_chunk(&digest_str)?;
+
+        let mut tmp_path = chunk_path.clone();
+        tmp_path.set_extension("tmp");
+
+        let _lock = self.mutex.lock();
+        // caching is best effort, the data was loaded anyway
+        if std::fs::write(&tmp_path, &data).is_err() || std::fs::rename(&tmp_path, &chunk_path).is_err() {
+            let _ = std::fs::remove_file(&tmp_path);
+        }
+
+        Ok(data)
+    }
+
     pub fn chunk_path(&self, digest:&[u8; 32]) -> (PathBuf, String) {
         let mut chunk_path = self.chunk_dir.clone();
         let prefix = digest_to_prefix(digest);
@@ -1,24 +1,130 @@
 use std::pin::Pin;
+use std::sync::mpsc;
 use std::task::{Context, Poll};
 
 use bytes::BytesMut;
-use anyhow::{Error};
+use anyhow::{format_err, Error};
 use futures::ready;
 use futures::stream::{Stream, TryStream};
 
 use super::Chunker;
 
+/// Chunks of a previous archive, reused at a given offset of the archive stream
+///
+/// The data of the reused chunks is not part of the input stream, it gets
+/// referenced by digest instead.
+pub struct InjectChunks {
+    /// Stream offset at which the reused chunks start
+    pub boundary: u64,
+    /// Size and digest of the reused chunks
+    pub chunks: Vec<(u64, [u8; 32])>,
+    /// Sum of the chunk sizes
+    pub size: u64,
+}
+
+/// Channels to inject chunks into a [`ChunkStream`]
+///
+/// Injections are received on `boundaries`, where a chunk boundary gets
+/// forced. They get passed on via `injections` to the uploader, once all data
+/// in front of them has been chunked.
+pub struct InjectionData {
+    boundaries: mpsc::Receiver<InjectChunks>,
+    injections: mpsc::Sender<InjectChunks>,
+    next_boundary: Option<InjectChunks>,
+    consumed: u64,
+}
+
+impl InjectionData {
+    pub fn new(
+        boundaries: mpsc::Receiver<InjectChunks>,
+        injections: mpsc::Sender<InjectChunks>,
+    ) -> Self {
+        Self {
+            boundaries,
+            injections,
+            next_boundary: None,
+            consumed: 0,
+        }
+    }
+}
+
 /// Split input stream into dynamic sized chunks
 pub struct ChunkStream<S: Unpin> {
     input: S,
     chunker: Chunker,
     buffer: BytesMut,
     scan_pos: usize,
+    injection_data: Option<InjectionData>,
 }
 
 impl<S: Unpin> ChunkStream<S> {
     pub fn new(input: S, chunk_size: Option<usize>) -> Self {
-        Self { input, chunker: Chunker::new(chunk_size.unwrap_or(4*1024*1024)), buffer: BytesMut::new(), scan_pos: 0}
+        Self {
+            input,
+            chunker: Chunker::new(chunk_size.unwrap_or(4*1024*1024)),
+            buffer: BytesMut::new(),
+            scan_pos: 0,
+            injection_data: None,
+        }
+    }
+
+    /// Create a chunk stream which forces chunk boundaries where chunks of a
+    /// previous archive get injected.
+    pub fn with_injection(input: S, chunk_size: Option<usize>, injection_data: InjectionData) -> Self {
+        let mut stream = Self::new(input, chunk_size);
+        stream.injection_data = Some(injection_data);
+        stream
+    }
+
+    // Returns the number of buffered bytes in front of the next injection, if
+    // its boundary is within the buffer.
+    fn injection_boundary(&mut self) -> Result<Option<usize>, Error> {
+        let data = match self.injection_data {
+            Some(ref mut data) => data,
+            None => return Ok(None),
+        };
+
+        if data.next_boundary.is_none() {
+            data.next_boundary = data.boundaries.try_recv().ok();
+        }
+
+        match data.next_boundary {
+            Some(ref next) => {
+                if next.boundary < data.consumed {
+                    return Err(format_err!("got injection boundary {} in the past", next.boundary));
+                }
+                let pos = next.boundary - data.consumed;
+                if pos <= self.buffer.len() as u64 {
+                    Ok(Some(pos as usize))
+                } else {
+                    Ok(None)
+                }
+            }
+            None => Ok(None),
+        }
+    }
+
+    // Passes the pending injection on to the uploader, the data in front of
+    // it has been consumed.
+    fn forward_injection(&mut self) -> Result<(), Error> {
+        if let Some(ref mut data) = self.injection_data {
+            if let Some(next) = data.next_boundary.take() {
+                data.consumed += next.size;
+                data.injections
+                    .send(next)
+                    .map_err(|_| format_err!("unable to forward chunk injection - channel closed"))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Removes a chunk of `len` bytes from the buffer.
+    fn split_chunk(&mut self, len: usize) -> BytesMut {
+        if let Some(ref mut data) = self.injection_data {
+            data.consumed += len as u64;
+        }
+        self.scan_pos = 0;
+        self.buffer.split_to(len)
     }
 }
 
@@ -36,23 +142,43 @@ where
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
         loop {
-            if this.scan_pos < this.buffer.len() {
-                let boundary = this.chunker.scan(&this.buffer[this.scan_pos..]);
+            let injection_boundary = match this.injection_boundary() {
+                Ok(boundary) => boundary,
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            };
+
+            // only scan up to a forced boundary
+            let scan_end = injection_boundary.unwrap_or_else(|| this.buffer.len());
+
+            if this.scan_pos < scan_end {
+                let boundary = this.chunker.scan(&this.buffer[this.scan_pos..scan_end]);
 
                 let chunk_size = this.scan_pos + boundary;
 
                 if boundary == 0 {
-                    this.scan_pos = this.buffer.len();
+                    this.scan_pos = scan_end;
                     // continue poll
-                } else if chunk_size <= this.buffer.len() {
-                    let result = this.buffer.split_to(chunk_size);
-                    this.scan_pos = 0;
+                } else if chunk_size <= scan_end {
+                    let result = this.split_chunk(chunk_size);
                     return Poll::Ready(Some(Ok(result)));
                 } else {
                     panic!("got unexpected chunk boundary from chunker");
                 }
             }
 
+            if let Some(pos) = injection_boundary {
+                // force a boundary, the injected chunks follow this chunk
+                this.chunker.reset();
+                let result = this.split_chunk(pos);
+                if let Err(err) = this.forward_injection() {
+                    return Poll::Ready(Some(Err(err)));
+                }
+                if !result.is_empty() {
+                    return Poll::Ready(Some(Ok(result)));
+                }
+                continue;
+            }
+
             match ready!(Pin::new(&mut this.input).try_poll_next(cx)) {
                 Some(Err(err)) => {
                     return Poll::Ready(Some(Err(err.into())));
@@ -60,7 +186,8 @@ where
                 None => {
                     this.scan_pos = 0;
                     if !this.buffer.is_empty() {
-                        return Poll::Ready(Some(Ok(this.buffer.split())));
+                        let len = this.buffer.len();
+                        return Poll::Ready(Some(Ok(this.split_chunk(len))));
                     } else {
                         return Poll::Ready(None);
                     }
@@ -121,3 +248,83 @@ where
         }
     }
 }
+
+#[cfg(test)]
+fn test_data(len: usize) -> Vec<u8> {
+    let mut state: u32 = 0x1234_5678;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+// Feeds `data` in 4 KiB pieces and returns the chunk sizes.
+#[cfg(test)]
+fn test_chunk_sizes(data: &[u8], injection_data: Option<InjectionData>) -> Result<Vec<usize>, Error> {
+    use futures::stream::TryStreamExt;
+
+    let input = futures::stream::iter(
+        data.chunks(4096).map(|piece| Ok::<_, Error>(piece.to_vec())).collect::<Vec<_>>()
+    );
+    let stream = match injection_data {
+        Some(injection_data) => ChunkStream::with_injection(input, Some(64 * 1024), injection_data),
+        None => ChunkStream::new(input, Some(64 * 1024)),
+    };
+
+    let chunks: Vec<BytesMut> = crate::tools::runtime::block_on(stream.try_collect())?;
+    Ok(chunks.iter().map(|chunk| chunk.len()).collect())
+}
+
+#[test]
+fn test_chunk_stream_injection() -> Result<(), Error> {
+    let data = test_data(1024 * 1024);
+
+    // 40000 bytes of the stream are replaced by injected chunks at offset 300000,
+    // they are not part of the input
+    let (boundaries_tx, boundaries_rx) = mpsc::channel();
+    let (injections_tx, injections_rx) = mpsc::channel();
+    boundaries_tx.send(InjectChunks {
+        boundary: 300_000,
+        chunks: vec![(16_000, [1u8; 32]), (24_000, [2u8; 32])],
+        size: 40_000,
+    })?;
+
+    let sizes = test_chunk_sizes(&data, Some(InjectionData::new(boundaries_rx, injections_tx)))?;
+    assert_eq!(sizes.iter().sum::<usize>(), data.len());
+
+    // the data in front of and behind the injection is chunked on its own,
+    // as if the stream was split at the forced boundary
+    let mut expected = test_chunk_sizes(&data[..300_000], None)?;
+    expected.append(&mut test_chunk_sizes(&data[300_000..], None)?);
+    assert_eq!(sizes, expected);
+
+    let injection = injections_rx.try_recv()?;
+    assert_eq!(injection.boundary, 300_000);
+    assert_eq!(injection.size, 40_000);
+    assert_eq!(injection.chunks, vec![(16_000, [1u8; 32]), (24_000, [2u8; 32])]);
+    assert!(injections_rx.try_recv().is_err());
+
+    // injections at the very start do not produce an empty chunk
+    let (boundaries_tx, boundaries_rx) = mpsc::channel();
+    let (injections_tx, injections_rx) = mpsc::channel();
+    boundaries_tx.send(InjectChunks { boundary: 0, chunks: vec![(10, [1u8; 32])], size: 10 })?;
+
+    let sizes = test_chunk_sizes(&data, Some(InjectionData::new(boundaries_rx, injections_tx)))?;
+    assert_eq!(sizes, test_chunk_sizes(&data, None)?);
+    assert_eq!(injections_rx.try_recv()?.boundary, 0);
+
+    // boundaries are stream offsets including the injected chunks, so the second
+    // injection lies within the first one
+    let (boundaries_tx, boundaries_rx) = mpsc::channel();
+    let (injections_tx, _injections_rx) = mpsc::channel();
+    boundaries_tx.send(InjectChunks { boundary: 50_000, chunks: vec![(10_000, [1u8; 32])], size: 10_000 })?;
+    boundaries_tx.send(InjectChunks { boundary: 55_000, chunks: vec![(10_000, [2u8; 32])], size: 10_000 })?;
+
+    assert!(test_chunk_sizes(&data, Some(InjectionData::new(boundaries_rx, injections_tx))).is_err());
+
+    Ok(())
+}
@@ -1,13 +1,70 @@
-use anyhow::{bail, Error};
+use anyhow::{bail, format_err, Error};
 use std::convert::TryInto;
+use std::io::{Read, Write};
+use std::sync::Arc;
 
 use proxmox::tools::io::{ReadExt, WriteExt};
 
+use crate::api2::types::ChunkCompressionConfig;
+
 use super::file_formats::*;
-use super::{CryptConfig, CryptMode};
+use super::{lookup_zstd_dictionary, parse_dictionary_id, CryptConfig, CryptMode, ZstdDictionary};
 
 const MAX_BLOB_SIZE: usize = 128*1024*1024;
 
+/// Zstd compression level used if nothing else is configured
+pub const DEFAULT_ZSTD_LEVEL: i32 = 1;
+
+/// Compression settings for data blobs
+#[derive(Clone)]
+pub enum BlobCompression {
+    /// Store the data uncompressed
+    None,
+    /// Compress the data using zstd, optionally with a trained dictionary
+    Zstd {
+        level: i32,
+        dictionary: Option<Arc<ZstdDictionary>>,
+    },
+}
+
+impl Default for BlobCompression {
+    fn default() -> Self {
+        BlobCompression::Zstd { level: DEFAULT_ZSTD_LEVEL, dictionary: None }
+    }
+}
+
+impl BlobCompression {
+    /// Create compression settings from a (datastore or client) configuration.
+    ///
+    /// The configured dictionary needs to be registered already.
+    pub fn from_config(config: &ChunkCompressionConfig) -> Result<Self, Error> {
+        let level = match config.level {
+            Some(0) => return Ok(BlobCompression::None),
+            Some(level) => level as i32,
+            None => DEFAULT_ZSTD_LEVEL,
+        };
+
+        let dictionary = match config.dictionary {
+            Some(ref id) => {
+                let dict = lookup_zstd_dictionary(&parse_dictionary_id(id)?)
+                    .ok_or_else(|| format_err!("zstd dictionary '{}' not available", id))?;
+                Some(dict)
+            }
+            None => None,
+        };
+
+        Ok(BlobCompression::Zstd { level, dictionary })
+    }
+
+    /// The ID of the dictionary used, if any.
+    pub fn dictionary_id(&self) -> Option<&[u8; 8]> {
+        match self {
+            BlobCompression::Zstd { dictionary: Some(dict), .. } => Some(dict.id()),
+            _ => None,
+        }
+    }
+}
+
 /// Encoded data chunk with digest and positional information
 pub struct ChunkInfo {
     pub chunk: DataBlob,
@@ -81,70 +138,116 @@ impl DataBlob {
     }
 
     /// Create a DataBlob, optionally compressed and/or encrypted
+    ///
+    /// Compression uses zstd with the default level and no dictionary.
     pub fn encode(
         data: &[u8],
         config: Option<&CryptConfig>,
         compress: bool,
     ) -> Result<Self, Error> {
+        let compression = if compress {
+            BlobCompression::default()
+        } else {
+            BlobCompression::None
+        };
+        Self::encode_with(data, config, &compression)
+    }
+
+    /// Create a DataBlob with the given compression settings, optionally encrypted
+    pub fn encode_with(
+        data: &[u8],
+        config: Option<&CryptConfig>,
+        compression: &BlobCompression,
+    ) -> Result<Self, Error> {
 
         if data.len() > MAX_BLOB_SIZE {
             bail!("data blob too large ({} bytes).", data.len());
         }
 
+        let (level, dictionary) = match compression {
+            BlobCompression::None => (None, None),
+            BlobCompression::Zstd { level, dictionary } => (Some(*level), dictionary.as_ref()),
+        };
+
         let mut blob = if let Some(config) = config {
 
             let compr_data;
-            let (_compress, data, magic) = if compress {
-                compr_data = zstd::block::compress(data, 1)?;
+            let (data, magic) = if let Some(level) = level {
+                compr_data = match dictionary {
+                    Some(dict) => zstd::block::Compressor::with_dict(dict.data().to_vec())
+                        .compress(data, level)?,
+                    None => zstd::block::compress(data, level)?,
+                };
                 // Note: We only use compression if result is shorter
                 if compr_data.len() < data.len() {
-                    (true, &compr_data[..], ENCR_COMPR_BLOB_MAGIC_1_0)
+                    if dictionary.is_some() {
+                        (&compr_data[..], ENCR_COMPR_DICT_BLOB_MAGIC_1_0)
+                    } else {
+                        (&compr_data[..], ENCR_COMPR_BLOB_MAGIC_1_0)
+                    }
                 } else {
-                    (false, data, ENCRYPTED_BLOB_MAGIC_1_0)
+                    (data, ENCRYPTED_BLOB_MAGIC_1_0)
                 }
             } else {
-                (false, data, ENCRYPTED_BLOB_MAGIC_1_0)
+                (data, ENCRYPTED_BLOB_MAGIC_1_0)
             };
 
-            let header_len = std::mem::size_of::<EncryptedDataBlobHeader>();
+            let header_len = header_size(&magic);
             let mut raw_data = Vec::with_capacity(data.len() + header_len);
-
-            let dummy_head = EncryptedDataBlobHeader {
-                head: DataBlobHeader { magic: [0u8; 8], crc: [0; 4] },
-                iv: [0u8; 16],
-                tag: [0u8; 16],
-            };
-            unsafe {
-                raw_data.write_le_value(dummy_head)?;
-            }
+            raw_data.resize(header_len, 0u8); // reserve space for the header
 
             let (iv, tag) = config.encrypt_to(data, &mut raw_data)?;
 
-            let head = EncryptedDataBlobHeader {
-                head: DataBlobHeader { magic, crc: [0; 4] }, iv, tag,
-            };
-
-            unsafe {
-                (&mut raw_data[0..header_len]).write_le_value(head)?;
+            let head = DataBlobHeader { magic, crc: [0; 4] };
+
+            match dictionary {
+                Some(dict) if magic == ENCR_COMPR_DICT_BLOB_MAGIC_1_0 => unsafe {
+                    let head = EncryptedDictDataBlobHeader {
+                        head, dict_id: *dict.id(), iv, tag,
+                    };
+                    (&mut raw_data[0..header_len]).write_le_value(head)?;
+                },
+                _ => unsafe {
+                    let head = EncryptedDataBlobHeader { head, iv, tag };
+                    (&mut raw_data[0..header_len]).write_le_value(head)?;
+                },
             }
 
             DataBlob { raw_data }
         } else {
 
             let max_data_len = data.len() + std::mem::size_of::<DataBlobHeader>();
-            if compress {
+            if let Some(level) = level {
                 let mut comp_data = Vec::with_capacity(max_data_len);
 
-                let head =  DataBlobHeader {
-                    magic: COMPRESSED_BLOB_MAGIC_1_0,
-                    crc: [0; 4],
-                };
-                unsafe {
-                    comp_data.write_le_value(head)?;
+                if let Some(dict) = dictionary {
+                    let head = DictDataBlobHeader {
+                        head: DataBlobHeader { magic: COMPRESSED_DICT_BLOB_MAGIC_1_0, crc: [0; 4] },
+                        dict_id: *dict.id(),
+                    };
+                    unsafe {
+                        comp_data.write_le_value(head)?;
+                    }
+
+                    let mut encoder = zstd::stream::Encoder::with_dictionary(
+                        &mut comp_data,
+                        level,
+                        dict.data(),
+                    )?;
+                    encoder.write_all(data)?;
+                    encoder.finish()?;
+                } else {
+                    let head =  DataBlobHeader {
+                        magic: COMPRESSED_BLOB_MAGIC_1_0,
+                        crc: [0; 4],
+                    };
+                    unsafe {
+                        comp_data.write_le_value(head)?;
+                    }
+
+                    zstd::stream::copy_encode(data, &mut comp_data, level)?;
                 }
 
-                zstd::stream::copy_encode(data, &mut comp_data, 1)?;
-
                 if comp_data.len() < max_data_len {
                     let mut blob = DataBlob { raw_data: comp_data };
                     blob.set_crc(blob.compute_crc());
@@ -175,9 +278,12 @@ impl DataBlob {
     pub fn crypt_mode(&self) -> Result<CryptMode, Error> {
         let magic = self.magic();
 
-        Ok(if magic == &UNCOMPRESSED_BLOB_MAGIC_1_0 || magic == &COMPRESSED_BLOB_MAGIC_1_0 {
+        Ok(if magic == &UNCOMPRESSED_BLOB_MAGIC_1_0
+            || magic == &COMPRESSED_BLOB_MAGIC_1_0
+            || magic == &COMPRESSED_DICT_BLOB_MAGIC_1_0
+        {
             CryptMode::None
-        } else if magic == &ENCR_COMPR_BLOB_MAGIC_1_0 || magic == &ENCRYPTED_BLOB_MAGIC_1_0 {
+        } else if self.is_encrypted() {
             CryptMode::Encrypt
         } else {
             bail!("Invalid blob magic number.");
@@ -206,6 +312,38 @@ impl DataBlob {
                 Self::verify_digest(&data, None, digest)?;
             }
             Ok(data)
+        } else if magic == &COMPRESSED_DICT_BLOB_MAGIC_1_0 {
+            let header_len = std::mem::size_of::<DictDataBlobHeader>();
+            let head = unsafe {
+                (&self.raw_data[..header_len]).read_le_value::<DictDataBlobHeader>()?
+            };
+            let dict = Self::lookup_dictionary(&head.dict_id)?;
+            let data = Self::decompress_with_dictionary(&self.raw_data[header_len..], &dict)?;
+            if let Some(digest) = digest {
+                Self::verify_digest(&data, None, digest)?;
+            }
+            Ok(data)
+        } else if magic == &ENCR_COMPR_DICT_BLOB_MAGIC_1_0 {
+            let header_len = std::mem::size_of::<EncryptedDictDataBlobHeader>();
+            let head = unsafe {
+                (&self.raw_data[..header_len]).read_le_value::<EncryptedDictDataBlobHeader>()?
+            };
+
+            if let Some(config) = config  {
+                let dict = Self::lookup_dictionary(&head.dict_id)?;
+                let compr_data = config.decode_uncompressed_chunk(
+                    &self.raw_data[header_len..],
+                    &head.iv,
+                    &head.tag,
+                )?;
+                let data = Self::decompress_with_dictionary(&compr_data, &dict)?;
+                if let Some(digest) = digest {
+                    Self::verify_digest(&data, Some(config), digest)?;
+                }
+                Ok(data)
+            } else {
+                bail!("unable to decrypt blob - missing CryptConfig");
+            }
         } else if magic == &ENCR_COMPR_BLOB_MAGIC_1_0 || magic == &ENCRYPTED_BLOB_MAGIC_1_0 {
             let header_len = std::mem::size_of::<EncryptedDataBlobHeader>();
             let head = unsafe {
@@ -230,6 +368,33 @@ impl DataBlob {
         }
     }
 
+    /// Returns the ID of the zstd dictionary needed to decode this blob, if any.
+    pub fn dictionary_id(&self) -> Option<[u8; 8]> {
+        let magic = self.magic();
+        if magic == &COMPRESSED_DICT_BLOB_MAGIC_1_0 || magic == &ENCR_COMPR_DICT_BLOB_MAGIC_1_0 {
+            let id_o = proxmox::offsetof!(DictDataBlobHeader, dict_id);
+            Some(self.raw_data[id_o..id_o+8].try_into().unwrap())
+        } else {
+            None
+        }
+    }
+
+    fn lookup_dictionary(id: &[u8; 8]) -> Result<Arc<ZstdDictionary>, Error> {
+        lookup_zstd_dictionary(id).ok_or_else(|| {
+            format_err!(
+                "unable to decode blob - zstd dictionary {} not available",
+                proxmox::tools::bin_to_hex(id),
+            )
+        })
+    }
+
+    fn decompress_with_dictionary(data: &[u8], dict: &ZstdDictionary) -> Result<Vec<u8>, Error> {
+        let mut decoder = zstd::stream::Decoder::with_dictionary(data, dict.data())?;
+        let mut result = Vec::with_capacity(4*1024*1024);
+        decoder.read_to_end(&mut result)?;
+        Ok(result)
+    }
+
     /// Load blob from ``reader``, verify CRC
     pub fn load_from_reader(reader: &mut dyn std::io::Read) -> Result<Self, Error> {
 
@@ -260,6 +425,24 @@ impl DataBlob {
 
             let blob = DataBlob { raw_data: data };
 
+            Ok(blob)
+        } else if magic == ENCR_COMPR_DICT_BLOB_MAGIC_1_0 {
+
+            if data.len() < std::mem::size_of::<EncryptedDictDataBlobHeader>() {
+                bail!("encrypted blob too small ({} bytes).", data.len());
+            }
+
+            let blob = DataBlob { raw_data: data };
+
+            Ok(blob)
+        } else if magic == COMPRESSED_DICT_BLOB_MAGIC_1_0 {
+
+            if data.len() < std::mem::size_of::<DictDataBlobHeader>() {
+                bail!("blob too small ({} bytes).", data.len());
+            }
+
+            let blob = DataBlob { raw_data: data };
+
             Ok(blob)
         } else if magic == COMPRESSED_BLOB_MAGIC_1_0 || magic == UNCOMPRESSED_BLOB_MAGIC_1_0 {
 
@@ -274,7 +457,9 @@ impl DataBlob {
     /// Returns if chunk is encrypted
     pub fn is_encrypted(&self) -> bool {
         let magic = self.magic();
-        magic == &ENCR_COMPR_BLOB_MAGIC_1_0 || magic == &ENCRYPTED_BLOB_MAGIC_1_0
+        magic == &ENCR_COMPR_BLOB_MAGIC_1_0
+            || magic == &ENCRYPTED_BLOB_MAGIC_1_0
+            || magic == &ENCR_COMPR_DICT_BLOB_MAGIC_1_0
     }
 
     /// Verify digest and data length for unencrypted chunks.
@@ -289,9 +474,7 @@ impl DataBlob {
         expected_digest: &[u8; 32],
     ) -> Result<(), Error> {
 
-        let magic = self.magic();
-
-        if magic == &ENCR_COMPR_BLOB_MAGIC_1_0 || magic == &ENCRYPTED_BLOB_MAGIC_1_0 {
+        if self.is_encrypted() {
             return Ok(());
         }
 
@@ -333,7 +516,7 @@ pub struct DataChunkBuilder<'a, 'b> {
     orig_data: &'a [u8],
     digest_computed: bool,
     digest: [u8; 32],
-    compress: bool,
+    compression: BlobCompression,
 }
 
 impl <'a, 'b> DataChunkBuilder<'a, 'b> {
@@ -345,7 +528,7 @@ impl <'a, 'b> DataChunkBuilder<'a, 'b> {
             config: None,
             digest_computed: false,
             digest: [0u8; 32],
-            compress: true,
+            compression: BlobCompression::default(),
         }
     }
 
@@ -353,7 +536,17 @@ impl <'a, 'b> DataChunkBuilder<'a, 'b> {
     ///
     /// If true, chunk data is compressed using zstd (level 1).
     pub fn compress(mut self, value: bool) -> Self {
-        self.compress = value;
+        self.compression = if value {
+            BlobCompression::default()
+        } else {
+            BlobCompression::None
+        };
+        self
+    }
+
+    /// Set compression settings (level and dictionary).
+    pub fn compression(mut self, value: BlobCompression) -> Self {
+        self.compression = value;
         self
     }
 
@@ -398,7 +591,7 @@ impl <'a, 'b> DataChunkBuilder<'a, 'b> {
             self.compute_digest();
         }
 
-        let chunk = DataBlob::encode(self.orig_data, self.config, self.compress)?;
+        let chunk = DataBlob::encode_with(self.orig_data, self.config, &self.compression)?;
         Ok((chunk, self.digest))
     }
 
@@ -12,65 +12,189 @@ use lazy_static::lazy_static;
 
 use proxmox::tools::fs::{replace_file, file_read_optional_string, CreateOptions, open_file_locked};
 
-use super::backup_info::{BackupGroup, BackupDir};
+use super::backup_info::{BackupGroup, BackupDir, BackupInfo, BackupNamespace};
 use super::chunk_store::ChunkStore;
+use super::s3_chunk_backend::S3ChunkBackend;
 use super::dynamic_index::{DynamicIndexReader, DynamicIndexWriter};
 use super::fixed_index::{FixedIndexReader, FixedIndexWriter};
 use super::manifest::{MANIFEST_BLOB_NAME, MANIFEST_LOCK_NAME, CLIENT_LOG_BLOB_NAME, BackupManifest};
 use super::index::*;
+use super::task_tracking::update_active_operations;
+use super::quota::{read_usage_status, save_usage_status, UsageAccounting, UsageStatus};
 use super::{DataBlob, ArchiveType, archive_type};
+use super::zstd_dict::{
+    load_zstd_dictionaries, lookup_zstd_dictionary, register_zstd_dictionary,
+    store_zstd_dictionary, zstd_dictionary_exists, ZstdDictionary,
+};
 use crate::config::datastore::{self, DataStoreConfig};
 use crate::task::TaskState;
 use crate::tools;
 use crate::tools::format::HumanByte;
 use crate::tools::fs::{lock_dir_noblock, DirLockGuard};
-use crate::api2::types::{Authid, GarbageCollectionStatus};
+use crate::api2::types::{Authid, ChunkCompressionConfig, GarbageCollectionStatus, Operation};
 use crate::server::UPID;
 
 lazy_static! {
-    static ref DATASTORE_MAP: Mutex<HashMap<String, Arc<DataStore>>> = Mutex::new(HashMap::new());
+    static ref DATASTORE_MAP: Mutex<HashMap<String, Arc<DataStoreImpl>>> = Mutex::new(HashMap::new());
 }
 
 /// Datastore Management
 ///
 /// A Datastore can store severals backups, and provides the
 /// management interface for backup.
+///
+/// Each instance accounts for the operation it was looked up for, so the
+/// number of active read/write operations can be queried while the
+/// datastore is in maintenance mode.
 pub struct DataStore {
+    inner: Arc<DataStoreImpl>,
+    operation: Option<Operation>,
+}
+
+/// Shared state of a datastore, see [DataStore].
+pub struct DataStoreImpl {
     chunk_store: Arc<ChunkStore>,
     gc_mutex: Mutex<()>,
     last_gc_status: Mutex<GarbageCollectionStatus>,
     verify_new: bool,
+    compression: Option<String>,
+    compression_config: ChunkCompressionConfig,
+}
+
+impl std::ops::Deref for DataStore {
+    type Target = DataStoreImpl;
+
+    fn deref(&self) -> &DataStoreImpl {
+        &self.inner
+    }
+}
+
+impl Clone for DataStore {
+    fn clone(&self) -> Self {
+        if let Some(operation) = self.operation {
+            if let Err(err) = update_active_operations(self.name(), operation, 1) {
+                log::error!("could not update active operations - {}", err);
+            }
+        }
+        Self {
+            inner: self.inner.clone(),
+            operation: self.operation,
+        }
+    }
+}
+
+impl Drop for DataStore {
+    fn drop(&mut self) {
+        if let Some(operation) = self.operation {
+            if let Err(err) = update_active_operations(self.name(), operation, -1) {
+                log::error!("could not update active operations - {}", err);
+            }
+        }
+    }
 }
 
 impl DataStore {
 
-    pub fn lookup_datastore(name: &str) -> Result<Arc<DataStore>, Error> {
+    /// Opens the datastore `name` for `operation`
+    ///
+    /// Fails if the configured maintenance mode does not allow the operation, or if the
+    /// removable device of the datastore is not mounted.
+    pub fn lookup_datastore(
+        name: &str,
+        operation: Option<Operation>,
+    ) -> Result<Arc<DataStore>, Error> {
 
         let (config, _digest) = datastore::config()?;
         let config: datastore::DataStoreConfig = config.lookup("datastore", name)?;
         let path = PathBuf::from(&config.path);
 
+        if let Some(maintenance_mode) = config.get_maintenance_mode() {
+            maintenance_mode.check(operation)
+                .map_err(|err| format_err!("datastore '{}': {}", name, err))?;
+        }
+
+        if !config.is_mounted() {
+            bail!("datastore '{}' is not mounted", name);
+        }
+        let removable = config.is_removable();
+
+        if let Some(operation) = operation {
+            update_active_operations(name, operation, 1)?;
+        }
+
         let mut map = DATASTORE_MAP.lock().unwrap();
 
         if let Some(datastore) = map.get(name) {
             // Compare Config - if changed, create new Datastore object!
             if datastore.chunk_store.base == path &&
-                datastore.verify_new == config.verify_new.unwrap_or(false)
+                datastore.verify_new == config.verify_new.unwrap_or(false) &&
+                datastore.compression == config.compression
             {
-                return Ok(datastore.clone());
+                return Ok(Arc::new(Self {
+                    inner: datastore.clone(),
+                    operation,
+                }));
             }
         }
 
-        let datastore = DataStore::open_with_path(name, &path, config)?;
+        let datastore = match DataStoreImpl::open_with_path(name, &path, config) {
+            Ok(datastore) => Arc::new(datastore),
+            Err(err) => {
+                if let Some(operation) = operation {
+                    let _ = update_active_operations(name, operation, -1);
+                }
+                return Err(err);
+            }
+        };
+        if removable {
+            // do not keep the chunk store lock file open, so that the device can be unmounted
+            map.remove(name);
+        } else {
+            map.insert(name.to_string(), datastore.clone());
+        }
+
+        Ok(Arc::new(Self {
+            inner: datastore,
+            operation,
+        }))
+    }
+
+    /// Opens a copy of datastore `name` located at `path`, bypassing the cache
+    ///
+    /// Used to fill the new location of a datastore before it gets activated, so
+    /// neither the maintenance mode is checked nor active operations are accounted.
+    pub fn open_path(name: &str, path: &Path) -> Result<Arc<DataStore>, Error> {
+        let (config, _digest) = datastore::config()?;
+        let mut config: datastore::DataStoreConfig = config.lookup("datastore", name)?;
+        config.path = path.to_string_lossy().into_owned();
+
+        Self::open_with_config(config)
+    }
 
-        let datastore = Arc::new(datastore);
-        map.insert(name.to_string(), datastore.clone());
+    /// Opens the datastore described by `config`, bypassing the cache
+    pub(crate) fn open_with_config(config: DataStoreConfig) -> Result<Arc<DataStore>, Error> {
+        let name = config.name.clone();
+        let path = PathBuf::from(&config.path);
+        let datastore = DataStoreImpl::open_with_path(&name, &path, config)?;
 
-        Ok(datastore)
+        Ok(Arc::new(Self {
+            inner: Arc::new(datastore),
+            operation: None,
+        }))
     }
+}
+
+impl DataStoreImpl {
 
     fn open_with_path(store_name: &str, path: &Path, config: DataStoreConfig) -> Result<Self, Error> {
-        let chunk_store = ChunkStore::open(store_name, path)?;
+        let mut chunk_store = ChunkStore::open(store_name, path)?;
+
+        if let Some(backend) = S3ChunkBackend::from_datastore_config(&config)? {
+            chunk_store.set_s3_backend(backend);
+        }
+
+        load_zstd_dictionaries(&chunk_store.base_path())?;
+        let compression_config = config.get_compression()?;
 
         let mut gc_status_path = chunk_store.base_path();
         gc_status_path.push(".gc-status");
@@ -92,6 +216,8 @@ impl DataStore {
             gc_mutex: Mutex::new(()),
             last_gc_status: Mutex::new(gc_status),
             verify_new: config.verify_new.unwrap_or(false),
+            compression: config.compression,
+            compression_config,
         })
     }
 
@@ -195,6 +321,104 @@ impl DataStore {
         Ok(())
     }
 
+    /// Returns the absolute path for a namespace
+    pub fn namespace_path(&self, ns: &BackupNamespace) -> PathBuf {
+        let mut full_path = self.base_path();
+        full_path.push(ns.path());
+        full_path
+    }
+
+    /// Returns true if the namespace exists
+    pub fn namespace_exists(&self, ns: &BackupNamespace) -> bool {
+        ns.is_root() || self.namespace_path(ns).exists()
+    }
+
+    /// Create a new namespace `name` below `parent`
+    pub fn create_namespace(
+        &self,
+        parent: &BackupNamespace,
+        name: &str,
+    ) -> Result<BackupNamespace, Error> {
+        if !self.namespace_exists(parent) {
+            bail!("cannot create namespace, parent namespace '{}' does not exist", parent);
+        }
+
+        let ns = parent.create_child(name)?;
+        let full_path = self.namespace_path(&ns);
+
+        if let Some(ns_dir) = full_path.parent() {
+            std::fs::create_dir_all(ns_dir)?;
+        }
+
+        match std::fs::create_dir(&full_path) {
+            Ok(_) => Ok(ns),
+            Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                bail!("namespace '{}' already exists", ns)
+            }
+            Err(err) => bail!("unable to create namespace {:?} - {}", full_path, err),
+        }
+    }
+
+    /// Create namespace `ns` including all missing parent namespaces
+    pub fn create_namespace_recursive(&self, ns: &BackupNamespace) -> Result<(), Error> {
+        let mut current = BackupNamespace::root();
+        for name in ns.components() {
+            let child = current.create_child(name)?;
+            if !self.namespace_exists(&child) {
+                self.create_namespace(&current, name)?;
+            }
+            current = child;
+        }
+        Ok(())
+    }
+
+    /// Remove a namespace and all its child namespaces
+    ///
+    /// Fails if any of them still contains backup groups, unless `delete_groups` is set.
+    pub fn remove_namespace_recursive(
+        &self,
+        ns: &BackupNamespace,
+        delete_groups: bool,
+    ) -> Result<(), Error> {
+        if ns.is_root() {
+            bail!("cannot remove the root namespace");
+        }
+
+        let base_path = self.base_path();
+        let mut namespaces = ns.list_recursive(&base_path, None)?;
+
+        // remove the deepest namespaces first
+        namespaces.reverse();
+
+        for ns in namespaces {
+            let groups = BackupInfo::list_backup_groups(&base_path, &ns)?;
+            if !groups.is_empty() && !delete_groups {
+                bail!("namespace '{}' is not empty", ns);
+            }
+            for group in groups {
+                if !self.remove_backup_group(&group)? {
+                    bail!("namespace '{}' contains protected snapshots", ns);
+                }
+            }
+
+            let ns_path = self.namespace_path(&ns);
+            for entry in &["ct", "host", "vm", "ns"] {
+                let mut path = ns_path.clone();
+                path.push(entry);
+                match std::fs::remove_dir(&path) {
+                    Ok(()) => {}
+                    Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => bail!("removing namespace directory {:?} failed - {}", path, err),
+                }
+            }
+
+            std::fs::remove_dir(&ns_path)
+                .map_err(|err| format_err!("removing namespace {:?} failed - {}", ns_path, err))?;
+        }
+
+        Ok(())
+    }
+
     /// Returns the absolute path for a backup_group
     pub fn group_path(&self, backup_group: &BackupGroup) -> PathBuf {
         let mut full_path = self.base_path();
@@ -210,7 +434,9 @@ impl DataStore {
     }
 
     /// Remove a complete backup group including all snapshots
-    pub fn remove_backup_group(&self, backup_group: &BackupGroup) ->  Result<(), Error> {
+    ///
+    /// Returns false if the group was kept because it contains protected snapshots.
+    pub fn remove_backup_group(&self, backup_group: &BackupGroup) ->  Result<bool, Error> {
 
         let full_path = self.group_path(backup_group);
 
@@ -218,11 +444,22 @@ impl DataStore {
 
         log::info!("removing backup group {:?}", full_path);
 
+        let mut removed_all = true;
+
         // remove all individual backup dirs first to ensure nothing is using them
         for snap in backup_group.list_backups(&self.base_path())? {
+            if snap.protected {
+                removed_all = false;
+                continue;
+            }
             self.remove_backup_dir(&snap.backup_dir, false)?;
         }
 
+        if !removed_all {
+            log::info!("keeping backup group {:?} - it contains protected snapshots", full_path);
+            return Ok(false);
+        }
+
         // no snapshots left, we can now safely remove the empty folder
         std::fs::remove_dir_all(&full_path)
             .map_err(|err| {
@@ -233,10 +470,12 @@ impl DataStore {
                 )
             })?;
 
-        Ok(())
+        Ok(true)
     }
 
     /// Remove a backup directory including all content
+    ///
+    /// Fails for protected snapshots, even if `force` is set.
     pub fn remove_backup_dir(&self, backup_dir: &BackupDir, force: bool) ->  Result<(), Error> {
 
         let full_path = self.snapshot_path(backup_dir);
@@ -247,6 +486,10 @@ impl DataStore {
             _manifest_guard = self.lock_manifest(backup_dir)?;
         }
 
+        if backup_dir.is_protected(&self.base_path()) {
+            bail!("cannot remove protected snapshot '{}'", backup_dir);
+        }
+
         log::info!("removing backup snapshot {:?}", full_path);
         std::fs::remove_dir_all(&full_path)
             .map_err(|err| {
@@ -333,8 +576,12 @@ impl DataStore {
         backup_group: &BackupGroup,
         auth_id: &Authid,
     ) -> Result<(Authid, DirLockGuard), Error> {
+        if !self.namespace_exists(backup_group.ns()) {
+            bail!("namespace '{}' does not exist", backup_group.ns());
+        }
+
         // create intermediate path first:
-        let mut full_path = self.base_path();
+        let mut full_path = self.namespace_path(backup_group.ns());
         full_path.push(backup_group.backup_type());
         std::fs::create_dir_all(&full_path)?;
 
@@ -435,16 +682,25 @@ impl DataStore {
         index: I,
         file_name: &Path, // only used for error reporting
         status: &mut GarbageCollectionStatus,
+        accounting: &mut Option<UsageAccounting>,
         worker: &dyn TaskState,
     ) -> Result<(), Error> {
 
         status.index_file_count += 1;
         status.index_data_bytes += index.index_bytes();
 
+        if let Some(accounting) = accounting {
+            accounting.add_index(index.index_bytes());
+        }
+
         for pos in 0..index.index_count() {
             worker.check_abort()?;
             tools::fail_on_shutdown()?;
             let digest = index.index_digest(pos).unwrap();
+            if let Some(accounting) = accounting {
+                let size = index.chunk_info(pos).map(|info| info.size()).unwrap_or(0);
+                accounting.add_chunk(digest, size);
+            }
             if !self.chunk_store.cond_touch_chunk(digest, false)? {
                 crate::task_warn!(
                     worker,
@@ -471,6 +727,7 @@ impl DataStore {
     fn mark_used_chunks(
         &self,
         status: &mut GarbageCollectionStatus,
+        accounting: &mut Option<UsageAccounting>,
         worker: &dyn TaskState,
     ) -> Result<(), Error> {
 
@@ -486,15 +743,21 @@ impl DataStore {
             worker.check_abort()?;
             tools::fail_on_shutdown()?;
 
+            let mut backup_dir = None;
             if let Some(backup_dir_path) = img.parent() {
                 let backup_dir_path = backup_dir_path.strip_prefix(self.base_path())?;
                 if let Some(backup_dir_str) = backup_dir_path.to_str() {
-                    if BackupDir::from_str(backup_dir_str).is_err() {
-                        strange_paths_count += 1;
+                    match BackupDir::from_str(backup_dir_str) {
+                        Ok(dir) => backup_dir = Some(dir),
+                        Err(_) => strange_paths_count += 1,
                     }
                 }
             }
 
+            if let Some(accounting) = accounting {
+                accounting.select_group(self, backup_dir.as_ref().map(|dir| dir.group()));
+            }
+
             match std::fs::File::open(&img) {
                 Ok(file) => {
                     if let Ok(archive_type) = archive_type(&img) {
@@ -502,12 +765,12 @@ impl DataStore {
                             let index = FixedIndexReader::new(file).map_err(|e| {
                                 format_err!("can't read index '{}' - {}", img.to_string_lossy(), e)
                             })?;
-                            self.index_mark_used_chunks(index, &img, status, worker)?;
+                            self.index_mark_used_chunks(index, &img, status, accounting, worker)?;
                         } else if archive_type == ArchiveType::DynamicIndex {
                             let index = DynamicIndexReader::new(file).map_err(|e| {
                                 format_err!("can't read index '{}' - {}", img.to_string_lossy(), e)
                             })?;
-                            self.index_mark_used_chunks(index, &img, status, worker)?;
+                            self.index_mark_used_chunks(index, &img, status, accounting, worker)?;
                         }
                     }
                 }
@@ -563,9 +826,17 @@ impl DataStore {
             let mut gc_status = GarbageCollectionStatus::default();
             gc_status.upid = Some(upid.to_string());
 
+            let mut accounting = match UsageAccounting::new(self.name()) {
+                Ok(accounting) => Some(accounting),
+                Err(err) => {
+                    crate::task_warn!(worker, "unable to setup usage accounting - {}", err);
+                    None
+                }
+            };
+
             crate::task_log!(worker, "Start GC phase1 (mark used chunks)");
 
-            self.mark_used_chunks(&mut gc_status, worker)?;
+            self.mark_used_chunks(&mut gc_status, &mut accounting, worker)?;
 
             crate::task_log!(worker, "Start GC phase2 (sweep unused chunks)");
             self.chunk_store.sweep_unused_chunks(
@@ -645,6 +916,18 @@ impl DataStore {
                 let _ = replace_file(path, serialized.as_bytes(), options);
             }
 
+            if let Some(accounting) = accounting {
+                let result = read_usage_status(self)
+                    .and_then(|previous| accounting.finish(self.name(), previous))
+                    .and_then(|usage| {
+                        let status = UsageStatus { upid: Some(upid.to_string()), usage };
+                        save_usage_status(self, &status)
+                    });
+                if let Err(err) = result {
+                    crate::task_warn!(worker, "unable to save usage status - {}", err);
+                }
+            }
+
             *self.last_gc_status.lock().unwrap() = gc_status;
 
         } else {
@@ -686,13 +969,18 @@ impl DataStore {
     }
 
 
+    /// Returns the raw data of a chunk, which might need to be downloaded first.
+    pub fn load_raw_chunk(&self, digest: &[u8; 32]) -> Result<Vec<u8>, Error> {
+        self.chunk_store.load_raw_chunk(digest)
+    }
+
     pub fn load_chunk(&self, digest: &[u8; 32]) -> Result<DataBlob, Error> {
 
-        let (chunk_path, digest_str) = self.chunk_store.chunk_path(digest);
+        let digest_str = proxmox::tools::digest_to_hex(digest);
 
         proxmox::try_block!({
-            let mut file = std::fs::File::open(&chunk_path)?;
-            DataBlob::load_from_reader(&mut file)
+            let raw_data = self.chunk_store.load_raw_chunk(digest)?;
+            DataBlob::load_from_reader(&mut &raw_data[..])
         }).map_err(|err| format_err!(
             "store '{}', unable to load chunk '{}' - {}",
             self.name(),
@@ -704,16 +992,15 @@ impl DataStore {
     /// Returns the filename to lock a manifest
     ///
     /// Also creates the basedir. The lockfile is located in
-    /// '/run/proxmox-backup/locks/{datastore}/{type}/{id}/{timestamp}.index.json.lck'
+    /// '/run/proxmox-backup/locks/{datastore}/[ns/{ns}/]{type}/{id}/{timestamp}.index.json.lck'
     fn manifest_lock_path(
         &self,
         backup_dir: &BackupDir,
     ) -> Result<String, Error> {
         let mut path = format!(
-            "/run/proxmox-backup/locks/{}/{}/{}",
+            "/run/proxmox-backup/locks/{}/{}",
             self.name(),
-            backup_dir.group().backup_type(),
-            backup_dir.group().backup_id(),
+            backup_dir.group().group_path().display(),
         );
         std::fs::create_dir_all(&path)?;
         use std::fmt::Write;
@@ -738,6 +1025,32 @@ impl DataStore {
             })
     }
 
+    /// Set or clear the protection flag of a snapshot
+    pub fn update_protection(
+        &self,
+        backup_dir: &BackupDir,
+        protection: bool,
+    ) -> Result<(), Error> {
+        let full_path = self.snapshot_path(backup_dir);
+
+        let _guard = tools::fs::lock_dir_noblock_shared(&full_path, "snapshot", "possibly running or in use")?;
+        let _manifest_guard = self.lock_manifest(backup_dir)?;
+
+        let protected_path = backup_dir.protected_file(&self.base_path());
+        if protection {
+            std::fs::File::create(&protected_path)
+                .map_err(|err| format_err!("could not create protection file {:?} - {}", protected_path, err))?;
+        } else {
+            match std::fs::remove_file(&protected_path) {
+                Ok(()) => {}
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => bail!("could not remove protection file {:?} - {}", protected_path, err),
+            }
+        }
+
+        Ok(())
+    }
+
     /// Load the manifest without a lock. Must not be written back.
     pub fn load_manifest(
         &self,
@@ -780,5 +1093,139 @@ impl DataStore {
     pub fn verify_new(&self) -> bool {
         self.verify_new
     }
+
+    /// Compression settings for new chunks, as configured for this datastore
+    pub fn chunk_compression(&self) -> &ChunkCompressionConfig {
+        &self.compression_config
+    }
+
+    /// Returns true if the zstd dictionary is stored in this datastore.
+    pub fn has_zstd_dictionary(&self, id: &[u8; 8]) -> bool {
+        zstd_dictionary_exists(&self.base_path(), id)
+    }
+
+    /// Store a zstd dictionary in this datastore.
+    ///
+    /// Note: this does not register the dictionary for decoding.
+    pub fn insert_zstd_dictionary(&self, dict: &ZstdDictionary) -> Result<(), Error> {
+        let backup_user = crate::backup::backup_user()?;
+        let mode = nix::sys::stat::Mode::from_bits_truncate(0o0644);
+        let options = CreateOptions::new()
+            .perm(mode)
+            .owner(backup_user.uid)
+            .group(backup_user.gid);
+
+        store_zstd_dictionary(&self.base_path(), dict, options)
+    }
+
+    /// Make sure a (registered) zstd dictionary is stored in this datastore, e.g. when
+    /// inserting chunks compressed with it from another source.
+    pub fn ensure_zstd_dictionary(&self, id: &[u8; 8]) -> Result<(), Error> {
+        if self.has_zstd_dictionary(id) {
+            return Ok(());
+        }
+        let dict = lookup_zstd_dictionary(id).ok_or_else(|| {
+            format_err!("zstd dictionary {} not available", proxmox::tools::bin_to_hex(id))
+        })?;
+        self.insert_zstd_dictionary(&dict)
+    }
+
+    /// Train a zstd dictionary from the unencrypted chunks of this datastore.
+    ///
+    /// Uses (the start of) up to `max_samples` chunks as samples. The
+    /// dictionary is stored in the datastore and registered, but not
+    /// activated in the datastore configuration.
+    pub fn train_zstd_dictionary(
+        &self,
+        worker: &dyn TaskState,
+        max_samples: usize,
+        dict_size: usize,
+    ) -> Result<Arc<ZstdDictionary>, Error> {
+        const MAX_SAMPLE_SIZE: usize = 128*1024;
+
+        let mut samples = Vec::new();
+        let mut encrypted = 0;
+
+        for (entry, _percentage, bad) in self.get_chunk_iterator()? {
+            worker.check_abort()?;
+            tools::fail_on_shutdown()?;
+
+            let entry = entry?;
+            if bad {
+                continue;
+            }
+            let digest = match entry.file_name().to_str().ok().map(proxmox::tools::hex_to_digest) {
+                Some(Ok(digest)) => digest,
+                _ => continue,
+            };
+
+            let blob = match self.load_chunk(&digest) {
+                Ok(blob) => blob,
+                Err(err) => {
+                    crate::task_warn!(worker, "{}", err);
+                    continue;
+                }
+            };
+            if blob.is_encrypted() {
+                encrypted += 1;
+                continue;
+            }
+
+            let mut data = match blob.decode(None, Some(&digest)) {
+                Ok(data) => data,
+                Err(err) => {
+                    crate::task_warn!(worker, "unable to decode chunk - {}", err);
+                    continue;
+                }
+            };
+            data.truncate(MAX_SAMPLE_SIZE);
+            samples.push(data);
+
+            if samples.len() >= max_samples {
+                break;
+            }
+        }
+
+        crate::task_log!(
+            worker,
+            "training dictionary from {} chunks (skipped {} encrypted chunks)",
+            samples.len(),
+            encrypted,
+        );
+
+        let dict = ZstdDictionary::train(&samples, dict_size)?;
+        self.insert_zstd_dictionary(&dict)?;
+        let dict = register_zstd_dictionary(dict);
+
+        crate::task_log!(worker, "stored dictionary {} ({} bytes)", dict.id_str(), dict.data().len());
+
+        Ok(dict)
+    }
+
+    /// Returns all zstd dictionaries stored in this datastore.
+    pub fn list_zstd_dictionaries(&self) -> Result<Vec<Arc<ZstdDictionary>>, Error> {
+        let ids = load_zstd_dictionaries(&self.base_path())?;
+        Ok(ids.iter().filter_map(lookup_zstd_dictionary).collect())
+    }
+
+    /// Load the raw data of a zstd dictionary stored in this datastore.
+    pub fn load_zstd_dictionary(&self, id: &[u8; 8]) -> Result<Arc<ZstdDictionary>, Error> {
+        if !self.has_zstd_dictionary(id) {
+            bail!(
+                "zstd dictionary {} not found on datastore '{}'",
+                proxmox::tools::bin_to_hex(id),
+                self.name(),
+            );
+        }
+        // all dictionaries of the datastore are registered on open, but
+        // might have been added by another process since then
+        if let Some(dict) = lookup_zstd_dictionary(id) {
+            return Ok(dict);
+        }
+        load_zstd_dictionaries(&self.base_path())?;
+        lookup_zstd_dictionary(id).ok_or_else(|| {
+            format_err!("unable to load zstd dictionary {}", proxmox::tools::bin_to_hex(id))
+        })
+    }
 }
 
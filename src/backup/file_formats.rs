@@ -17,6 +17,12 @@ pub const ENCRYPTED_BLOB_MAGIC_1_0: [u8; 8] = [123, 103, 133, 190, 34, 45, 76, 2
 // openssl::sha::sha256(b"Proxmox Backup zstd compressed encrypted blob v1.0")[0..8]
 pub const ENCR_COMPR_BLOB_MAGIC_1_0: [u8; 8] = [230, 89, 27, 191, 11, 191, 216, 11];
 
+// openssl::sha::sha256(b"Proxmox Backup zstd dictionary compressed blob v1.0")[0..8]
+pub const COMPRESSED_DICT_BLOB_MAGIC_1_0: [u8; 8] = [156, 117, 112, 165, 250, 90, 167, 127];
+
+// openssl::sha::sha256(b"Proxmox Backup zstd dictionary compressed encrypted blob v1.0")[0..8]
+pub const ENCR_COMPR_DICT_BLOB_MAGIC_1_0: [u8; 8] = [104, 58, 4, 146, 175, 189, 36, 186];
+
 // openssl::sha::sha256(b"Proxmox Backup fixed sized chunk index v1.0")[0..8]
 pub const FIXED_SIZED_CHUNK_INDEX_1_0: [u8; 8] = [47, 127, 65, 237, 145, 253, 15, 205];
 
@@ -59,6 +65,31 @@ pub struct EncryptedDataBlobHeader {
     pub tag: [u8; 16],
 }
 
+/// Data blob compressed with a zstd dictionary
+///
+/// The ``DataBlobHeader`` is followed by the 8 byte ID of the
+/// dictionary needed to decompress the data:
+///
+/// (MAGIC || CRC32 || DICT_ID || Data)
+#[derive(Endian)]
+#[repr(C,packed)]
+pub struct DictDataBlobHeader {
+    pub head: DataBlobHeader,
+    pub dict_id: [u8; 8],
+}
+
+/// Encrypted data blob compressed with a zstd dictionary
+///
+/// (MAGIC || CRC32 || DICT_ID || IV || TAG || EncryptedData)
+#[derive(Endian)]
+#[repr(C,packed)]
+pub struct EncryptedDictDataBlobHeader {
+    pub head: DataBlobHeader,
+    pub dict_id: [u8; 8],
+    pub iv: [u8; 16],
+    pub tag: [u8; 16],
+}
+
 /// Header size for different file types
 ///
 /// Panics on unknown magic numbers.
@@ -68,6 +99,8 @@ pub fn header_size(magic: &[u8; 8]) -> usize {
         COMPRESSED_BLOB_MAGIC_1_0 => std::mem::size_of::<DataBlobHeader>(),
         ENCRYPTED_BLOB_MAGIC_1_0 => std::mem::size_of::<EncryptedDataBlobHeader>(),
         ENCR_COMPR_BLOB_MAGIC_1_0 => std::mem::size_of::<EncryptedDataBlobHeader>(),
+        COMPRESSED_DICT_BLOB_MAGIC_1_0 => std::mem::size_of::<DictDataBlobHeader>(),
+        ENCR_COMPR_DICT_BLOB_MAGIC_1_0 => std::mem::size_of::<EncryptedDictDataBlobHeader>(),
         _ => panic!("unknown blob magic"),
     }
 }
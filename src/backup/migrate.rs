@@ -0,0 +1,617 @@
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, format_err, Error};
+
+use nix::unistd::{Gid, Uid};
+
+use proxmox::tools::fs::{replace_file, CreateOptions};
+
+use crate::{
+    backup::{
+        archive_type,
+        ArchiveType,
+        BackupDir,
+        BackupGroup,
+        BackupInfo,
+        BackupNamespace,
+        CryptMode,
+        DataStore,
+        ZstdDictionary,
+        DICTIONARY_DIR_NAME,
+        MANIFEST_BLOB_NAME,
+    },
+    config::datastore::DataStoreConfig,
+    task::TaskState,
+    task_log,
+    task_warn,
+    tools::format::HumanByte,
+    tools::fs::lock_dir_noblock_shared,
+};
+
+/// A MigrateWorker copies namespaces, backup groups (including their owner),
+/// snapshots and the referenced chunks from one datastore to another.
+///
+/// Each chunk is verified before it gets inserted into the target. Running
+/// `sync` again only copies what changed since the last run, so a first pass
+/// can run while the source is in use, followed by a short final pass once
+/// the source has been set read-only.
+///
+/// Everything created in the target is owned by the backup user, even if
+/// running as root.
+pub struct MigrateWorker {
+    worker: Arc<dyn TaskState + Send + Sync>,
+    source: Arc<DataStore>,
+    target: Arc<DataStore>,
+    mirror: bool,
+    uid: Uid,
+    gid: Gid,
+    known_chunks: HashSet<[u8; 32]>,
+    copied_chunks: usize,
+    copied_bytes: u64,
+    copied_snapshots: usize,
+}
+
+impl MigrateWorker {
+    /// Creates a new MigrateWorker.
+    ///
+    /// If `mirror` is set, the target is made an exact copy of the source:
+    /// group owners get replaced, and content which vanished from the source
+    /// is removed. Otherwise the source content is merged into the target,
+    /// skipping groups owned by someone else.
+    pub fn new(
+        worker: Arc<dyn TaskState + Send + Sync>,
+        source: Arc<DataStore>,
+        target: Arc<DataStore>,
+        mirror: bool,
+    ) -> Result<Self, Error> {
+        let backup_user = crate::backup::backup_user()?;
+        Ok(Self::with_owner(worker, source, target, mirror, backup_user.uid, backup_user.gid))
+    }
+
+    fn with_owner(
+        worker: Arc<dyn TaskState + Send + Sync>,
+        source: Arc<DataStore>,
+        target: Arc<DataStore>,
+        mirror: bool,
+        uid: Uid,
+        gid: Gid,
+    ) -> Self {
+        Self {
+            worker,
+            source,
+            target,
+            mirror,
+            uid,
+            gid,
+            // start with 64k chunks == up to 256G data
+            known_chunks: HashSet::with_capacity(64*1024),
+            copied_chunks: 0,
+            copied_bytes: 0,
+            copied_snapshots: 0,
+        }
+    }
+
+    fn create_options(&self) -> CreateOptions {
+        CreateOptions::new().owner(self.uid).group(self.gid)
+    }
+
+    // Set the owner of `path` and all its parents below the target base path
+    fn fix_owner(&self, path: &Path) -> Result<(), Error> {
+        let base = self.target.base_path();
+        let relative = path.strip_prefix(&base)
+            .map_err(|_| format_err!("{:?} is not below {:?}", path, base))?;
+
+        let mut current = PathBuf::from(&base);
+        for component in relative.components() {
+            current.push(component);
+            nix::unistd::chown(&current, Some(self.uid), Some(self.gid))
+                .map_err(|err| format_err!("unable to set owner of {:?} - {}", current, err))?;
+        }
+        Ok(())
+    }
+
+    fn insert_dictionary(&self, dict: &ZstdDictionary) -> Result<(), Error> {
+        if !self.target.has_zstd_dictionary(dict.id()) {
+            self.target.insert_zstd_dictionary(dict)?;
+            self.fix_owner(&self.target.base_path().join(DICTIONARY_DIR_NAME))?;
+        }
+        Ok(())
+    }
+
+    /// Copy everything not yet present in the target.
+    ///
+    /// Returns the number of groups and snapshots which could not be copied.
+    pub fn sync(&mut self) -> Result<usize, Error> {
+        for dict in self.source.list_zstd_dictionaries()? {
+            self.insert_dictionary(&dict)?;
+        }
+
+        let source_base = self.source.base_path();
+        let mut errors = 0;
+
+        for ns in BackupNamespace::root().list_recursive(&source_base, None)? {
+            self.target.create_namespace_recursive(&ns)?;
+            self.fix_owner(&self.target.namespace_path(&ns))?;
+
+            let mut groups = BackupInfo::list_backup_groups(&source_base, &ns)?;
+            groups.sort_unstable_by(|a, b| a.group_path().cmp(&b.group_path()));
+
+            for group in groups {
+                self.worker.check_abort()?;
+                errors += self.sync_group(&group)?;
+            }
+        }
+
+        if self.mirror {
+            self.remove_vanished()?;
+        }
+
+        task_log!(
+            self.worker,
+            "copied {} snapshots and {} chunks ({}) so far",
+            self.copied_snapshots,
+            self.copied_chunks,
+            HumanByte::from(self.copied_bytes),
+        );
+
+        Ok(errors)
+    }
+
+    fn sync_group(&mut self, group: &BackupGroup) -> Result<usize, Error> {
+        let owner = self.source.get_owner(group)?;
+
+        let (target_owner, _group_lock) = match self.target.create_locked_backup_group(group, &owner) {
+            Ok(res) => res,
+            Err(err) => {
+                task_warn!(self.worker, "sync group {} failed - {}", group, err);
+                return Ok(1);
+            }
+        };
+
+        if target_owner != owner {
+            if !self.mirror {
+                task_warn!(
+                    self.worker,
+                    "skipping group {} - owned by {} on datastore '{}' (source owner is {})",
+                    group,
+                    target_owner,
+                    self.target.name(),
+                    owner,
+                );
+                return Ok(1);
+            }
+            self.target.set_owner(group, &owner, true)?;
+        }
+        self.fix_owner(&self.target.group_path(group).join("owner"))?;
+
+        let mut list = group.list_backups(&self.source.base_path())?;
+        BackupInfo::sort_list(&mut list, true);
+
+        let mut errors = 0;
+        for info in list {
+            self.worker.check_abort()?;
+            if !info.is_finished() {
+                continue; // still running, picked up by the next pass
+            }
+            if let Err(err) = self.sync_snapshot(&info.backup_dir) {
+                task_warn!(self.worker, "sync snapshot {} failed - {}", info.backup_dir, err);
+                errors += 1;
+            }
+        }
+
+        Ok(errors)
+    }
+
+    fn sync_snapshot(&mut self, snapshot: &BackupDir) -> Result<(), Error> {
+        let source_path = self.source.snapshot_path(snapshot);
+        let _snap_lock = lock_dir_noblock_shared(&source_path, "snapshot", "locked by another operation")?;
+
+        let (relative_path, _is_new, _target_lock) = self.target.create_locked_backup_dir(snapshot)?;
+        let target_path = self.target.snapshot_path(snapshot);
+        self.fix_owner(&target_path)?;
+
+        let manifest_data = std::fs::read(source_path.join(MANIFEST_BLOB_NAME))?;
+        let unchanged = match std::fs::read(target_path.join(MANIFEST_BLOB_NAME)) {
+            Ok(data) => data == manifest_data,
+            Err(_) => false,
+        };
+
+        if !unchanged {
+            let (manifest, _) = self.source.load_manifest(snapshot)?;
+            for item in manifest.files() {
+                match archive_type(&item.filename)? {
+                    ArchiveType::FixedIndex | ArchiveType::DynamicIndex => {
+                        let index = self.source.open_index(relative_path.join(&item.filename))?;
+                        for pos in 0..index.index_count() {
+                            let digest = index.index_digest(pos).unwrap();
+                            self.copy_chunk(digest)?;
+                        }
+                    }
+                    ArchiveType::Blob => (),
+                }
+            }
+        }
+
+        // indexes and blobs first, the manifest marks the snapshot as finished
+        for filename in BackupInfo::list_files(&self.source.base_path(), snapshot)? {
+            if filename == MANIFEST_BLOB_NAME {
+                continue;
+            }
+            let target_file = target_path.join(&filename);
+            if unchanged && target_file.exists() {
+                continue;
+            }
+            copy_file(&source_path.join(&filename), &target_file, self.create_options())?;
+        }
+
+        if !unchanged {
+            replace_file(target_path.join(MANIFEST_BLOB_NAME), &manifest_data, self.create_options())?;
+            self.copied_snapshots += 1;
+            task_log!(self.worker, "copied snapshot {}", snapshot);
+        }
+
+        let protected = snapshot.is_protected(&self.source.base_path());
+        if protected != snapshot.is_protected(&self.target.base_path()) {
+            self.target.update_protection(snapshot, protected)?;
+            if protected {
+                self.fix_owner(&snapshot.protected_file(&self.target.base_path()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn copy_chunk(&mut self, digest: &[u8; 32]) -> Result<(), Error> {
+        if self.known_chunks.contains(digest) {
+            return Ok(());
+        }
+
+        if !self.target.cond_touch_chunk(digest, false)? {
+            let blob = self.source.load_chunk(digest)?;
+
+            if let Some(id) = blob.dictionary_id() {
+                let dict = self.source.load_zstd_dictionary(&id)?;
+                self.insert_dictionary(&dict)?;
+            }
+
+            let verify_result = match blob.crypt_mode()? {
+                // we cannot decrypt, but can at least check the CRC
+                CryptMode::Encrypt => blob.verify_crc(),
+                _ => blob.decode(None, Some(digest)).map(drop),
+            };
+            verify_result.map_err(|err| {
+                format_err!("chunk {} is corrupt - {}", proxmox::tools::digest_to_hex(digest), err)
+            })?;
+
+            let (is_duplicate, size) = self.target.insert_chunk(&blob, digest)?;
+            if !is_duplicate {
+                let (chunk_path, _) = self.target.chunk_path(digest);
+                nix::unistd::chown(&chunk_path, Some(self.uid), Some(self.gid))?;
+            }
+            self.copied_chunks += 1;
+            self.copied_bytes += size;
+        }
+
+        self.known_chunks.insert(*digest);
+
+        Ok(())
+    }
+
+    // Remove snapshots, groups and namespaces which were removed from the source
+    fn remove_vanished(&self) -> Result<(), Error> {
+        let target_base = self.target.base_path();
+
+        let root = BackupNamespace::root();
+        for group in BackupInfo::list_backup_groups_recursive(&target_base, &root, None)? {
+            if !self.source.group_path(&group).exists() {
+                task_log!(self.worker, "remove vanished group {}", group);
+                for info in group.list_backups(&target_base)? {
+                    if info.protected {
+                        self.target.update_protection(&info.backup_dir, false)?;
+                    }
+                }
+                self.target.remove_backup_group(&group)?;
+                continue;
+            }
+
+            for info in group.list_backups(&target_base)? {
+                let snapshot = &info.backup_dir;
+                if self.source.snapshot_path(snapshot).exists() {
+                    continue;
+                }
+                task_log!(self.worker, "remove vanished snapshot {}", snapshot);
+                if info.protected {
+                    self.target.update_protection(snapshot, false)?;
+                }
+                self.target.remove_backup_dir(snapshot, false)?;
+            }
+        }
+
+        // deepest namespaces first
+        let mut namespaces = root.list_recursive(&target_base, None)?;
+        namespaces.reverse();
+        for ns in namespaces {
+            if !ns.is_root() && !self.source.namespace_exists(&ns) {
+                task_log!(self.worker, "remove vanished namespace '{}'", ns);
+                self.target.remove_namespace_recursive(&ns, true)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Absolute path with symlinks of the existing part resolved, the
+// remaining components may not exist yet.
+fn resolve_path(path: &Path) -> Result<PathBuf, Error> {
+    if !path.is_absolute() {
+        bail!("expected absolute path - got {:?}", path);
+    }
+    if path.components().any(|c| matches!(c, Component::CurDir | Component::ParentDir)) {
+        bail!("path {:?} must not contain '.' or '..' components", path);
+    }
+
+    let mut existing = path.to_path_buf();
+    let mut missing = Vec::new();
+    while !existing.exists() {
+        match existing.file_name() {
+            Some(name) => missing.push(name.to_owned()),
+            None => break,
+        }
+        existing.pop();
+    }
+
+    let mut resolved = std::fs::canonicalize(&existing)
+        .map_err(|err| format_err!("unable to resolve path {:?} - {}", existing, err))?;
+    for name in missing.iter().rev() {
+        resolved.push(name);
+    }
+    Ok(resolved)
+}
+
+fn paths_overlap(a: &Path, b: &Path) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// Check that `path` is usable as new location of a datastore.
+///
+/// The path must not be inside of, or contain, any configured datastore
+/// (including the one to migrate).
+pub fn check_migration_target_path(path: &Path, datastores: &[DataStoreConfig]) -> Result<(), Error> {
+    let target = resolve_path(path)?;
+
+    for other in datastores {
+        let other_path = Path::new(&other.path);
+        let resolved = resolve_path(other_path).unwrap_or_else(|_| other_path.to_path_buf());
+        if paths_overlap(&target, other_path) || paths_overlap(&target, &resolved) {
+            bail!("target path {:?} overlaps with datastore '{}'", path, other.name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Check that the content of datastore `source` can be merged into `target`.
+pub fn check_migration_target_store(source: &DataStoreConfig, target: &DataStoreConfig) -> Result<(), Error> {
+    if source.name == target.name {
+        bail!("source and target datastore are identical");
+    }
+
+    let source_path = resolve_path(Path::new(&source.path))?;
+    let target_path = resolve_path(Path::new(&target.path))?;
+    if paths_overlap(&source_path, &target_path) {
+        bail!("datastores '{}' and '{}' overlap", source.name, target.name);
+    }
+
+    Ok(())
+}
+
+/// Switch the configured path of a migrated datastore and restore its maintenance mode.
+///
+/// Fails if the path was changed by someone else while migrating.
+pub fn switch_migrated_datastore(
+    config: &mut DataStoreConfig,
+    old_path: &str,
+    new_path: &str,
+    maintenance_mode: Option<String>,
+) -> Result<(), Error> {
+    if config.path != old_path {
+        bail!("path of datastore '{}' changed during migration", config.name);
+    }
+    config.path = new_path.to_string();
+    config.maintenance_mode = maintenance_mode;
+    Ok(())
+}
+
+fn copy_file(source: &Path, target: &Path, options: CreateOptions) -> Result<(), Error> {
+    let data = std::fs::read(source)
+        .map_err(|err| format_err!("unable to read {:?} - {}", source, err))?;
+    replace_file(target, &data, options)
+        .map_err(|err| format_err!("unable to write {:?} - {}", target, err))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use serde_json::json;
+
+    use crate::api2::types::Authid;
+    use crate::backup::{BackupManifest, ChunkStore, DataBlob, DataChunkBuilder};
+
+    struct TestTask;
+
+    impl TaskState for TestTask {
+        fn check_abort(&self) -> Result<(), Error> {
+            Ok(())
+        }
+
+        fn log(&self, _level: log::Level, message: &std::fmt::Arguments) {
+            println!("{}", message);
+        }
+    }
+
+    fn test_config(name: &str, path: &Path) -> DataStoreConfig {
+        serde_json::from_value(json!({ "name": name, "path": path })).unwrap()
+    }
+
+    fn create_datastore(name: &str, path: &Path) -> Result<Arc<DataStore>, Error> {
+        let uid = nix::unistd::Uid::current();
+        let gid = nix::unistd::Gid::current();
+        ChunkStore::create(name, path, uid, gid)?;
+        DataStore::open_with_config(test_config(name, path))
+    }
+
+    fn migrate_worker(source: &Arc<DataStore>, target: &Arc<DataStore>) -> MigrateWorker {
+        MigrateWorker::with_owner(
+            Arc::new(TestTask),
+            Arc::clone(source),
+            Arc::clone(target),
+            true,
+            nix::unistd::Uid::current(),
+            nix::unistd::Gid::current(),
+        )
+    }
+
+    // Create a finished snapshot with a fixed index referencing `chunks`,
+    // the chunks are stored with the digest of their (unmodified) content
+    fn create_snapshot(
+        store: &DataStore,
+        snapshot: &BackupDir,
+        chunks: &[(&[u8], Option<&[u8]>)],
+    ) -> Result<(), Error> {
+        let owner: Authid = "root@pam".parse()?;
+        let (_owner, _group_lock) = store.create_locked_backup_group(snapshot.group(), &owner)?;
+        let (relative_path, _is_new, _lock) = store.create_locked_backup_dir(snapshot)?;
+
+        let chunk_size = 4096;
+        let index_name = "disk.img.fidx";
+        let mut index = store.create_fixed_writer(
+            relative_path.join(index_name),
+            chunks.len() * chunk_size,
+            chunk_size,
+        )?;
+        for (pos, &(data, stored_data)) in chunks.iter().enumerate() {
+            let (_, digest) = DataChunkBuilder::new(data).build()?;
+            let (blob, _) = DataChunkBuilder::new(stored_data.unwrap_or(data)).build()?;
+            store.insert_chunk(&blob, &digest)?;
+            index.add_digest(pos, &digest)?;
+        }
+        let csum = index.close()?;
+
+        let mut manifest = BackupManifest::new(snapshot.clone());
+        manifest.add_file(index_name.to_string(), (chunks.len() * chunk_size) as u64, csum, CryptMode::None)?;
+        let manifest = serde_json::to_string_pretty(&serde_json::to_value(manifest)?)?;
+        let blob = DataBlob::encode(manifest.as_bytes(), None, true)?;
+        std::fs::write(store.snapshot_path(snapshot).join(MANIFEST_BLOB_NAME), blob.raw_data())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_migrate_copy_and_verify() -> Result<(), Error> {
+        let base = std::env::temp_dir().join(format!("proxmox-backup-migrate-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base)?;
+
+        let result: Result<(), Error> = proxmox::try_block!({
+            let source = create_datastore("source", &base.join("source"))?;
+            let target = create_datastore("target", &base.join("target"))?;
+
+            let chunk1 = vec![1u8; 4096];
+            let chunk2 = vec![2u8; 4096];
+            let snapshot1 = BackupDir::new("vm", "100", 1_600_000_000)?;
+            create_snapshot(&source, &snapshot1, &[(&chunk1[..], None), (&chunk2[..], None)])?;
+
+            // first pass copies everything
+            let mut worker = migrate_worker(&source, &target);
+            assert_eq!(worker.sync()?, 0);
+            assert_eq!(worker.copied_snapshots, 1);
+            assert_eq!(worker.copied_chunks, 2);
+            assert!(target.snapshot_path(&snapshot1).join(MANIFEST_BLOB_NAME).exists());
+            assert_eq!(target.get_owner(snapshot1.group())?, "root@pam".parse::<Authid>()?);
+
+            // a second pass only copies what changed in the meantime
+            let chunk3 = vec![3u8; 4096];
+            let snapshot2 = BackupDir::new("vm", "100", 1_600_000_100)?;
+            create_snapshot(&source, &snapshot2, &[(&chunk1[..], None), (&chunk3[..], None)])?;
+
+            let mut worker = migrate_worker(&source, &target);
+            assert_eq!(worker.sync()?, 0);
+            assert_eq!(worker.copied_snapshots, 1);
+            assert_eq!(worker.copied_chunks, 1);
+
+            // corrupt chunks are not copied, neither is the snapshot referencing them
+            let chunk4 = vec![4u8; 4096];
+            let snapshot3 = BackupDir::new("vm", "101", 1_600_000_200)?;
+            create_snapshot(&source, &snapshot3, &[(&chunk4[..], Some(&chunk1[..]))])?;
+
+            let mut worker = migrate_worker(&source, &target);
+            assert_eq!(worker.sync()?, 1);
+            assert_eq!(worker.copied_chunks, 0);
+            assert!(!target.snapshot_path(&snapshot3).join(MANIFEST_BLOB_NAME).exists());
+
+            // mirroring removes snapshots which vanished from the source
+            source.remove_backup_dir(&snapshot1, false)?;
+            let mut worker = migrate_worker(&source, &target);
+            worker.sync()?;
+            assert!(!target.snapshot_path(&snapshot1).exists());
+            assert!(target.snapshot_path(&snapshot2).exists());
+
+            Ok(())
+        });
+
+        let _ = std::fs::remove_dir_all(&base);
+        result
+    }
+
+    #[test]
+    fn test_migration_target_path() -> Result<(), Error> {
+        let datastores = vec![
+            test_config("store1", Path::new("/mnt/datastore/store1")),
+            test_config("store2", Path::new("/srv/backup")),
+        ];
+
+        check_migration_target_path(Path::new("/mnt/datastore/store10"), &datastores)?;
+        check_migration_target_path(Path::new("/mnt/newdisk/store1"), &datastores)?;
+
+        // relative, or with relative components
+        assert!(check_migration_target_path(Path::new("newdisk/store1"), &datastores).is_err());
+        assert!(check_migration_target_path(Path::new("/mnt/datastore/x/../store1"), &datastores).is_err());
+
+        // the datastore itself, inside of or containing a datastore
+        assert!(check_migration_target_path(Path::new("/mnt/datastore/store1"), &datastores).is_err());
+        assert!(check_migration_target_path(Path::new("/mnt/datastore/store1/new"), &datastores).is_err());
+        assert!(check_migration_target_path(Path::new("/srv/backup/.chunks/new"), &datastores).is_err());
+        assert!(check_migration_target_path(Path::new("/srv"), &datastores).is_err());
+
+        let source = test_config("store1", Path::new("/mnt/datastore/store1"));
+        check_migration_target_store(&source, &test_config("store2", Path::new("/srv/backup")))?;
+        assert!(check_migration_target_store(&source, &source).is_err());
+        assert!(check_migration_target_store(
+            &source,
+            &test_config("store3", Path::new("/mnt/datastore/store1/nested")),
+        ).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_switch_migrated_datastore() -> Result<(), Error> {
+        let mut config = test_config("store1", Path::new("/mnt/old"));
+        config.maintenance_mode = Some("read-only".to_string());
+
+        // path changed by someone else - keep everything as it is
+        assert!(switch_migrated_datastore(&mut config, "/mnt/other", "/mnt/new", None).is_err());
+        assert_eq!(config.path, "/mnt/old");
+        assert_eq!(config.maintenance_mode.as_deref(), Some("read-only"));
+
+        switch_migrated_datastore(&mut config, "/mnt/old", "/mnt/new", None)?;
+        assert_eq!(config.path, "/mnt/new");
+        assert_eq!(config.maintenance_mode, None);
+
+        Ok(())
+    }
+}
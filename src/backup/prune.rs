@@ -4,7 +4,7 @@ use std::path::PathBuf;
 
 use super::BackupInfo;
 
-enum PruneMark { Keep, KeepPartial, Remove }
+enum PruneMark { Protected, Keep, KeepPartial, Remove }
 
 fn mark_selections<F: Fn(&BackupInfo) -> Result<String, Error>> (
     mark: &mut HashMap<PathBuf, PruneMark>,
@@ -180,6 +180,11 @@ pub fn compute_prune_info(
 
     remove_incomplete_snapshots(&mut mark, &list);
 
+    // protected snapshots are always kept, but do not count towards the keep-* limits
+    for info in list.iter().filter(|info| info.protected) {
+        mark.insert(info.backup_dir.relative_path(), PruneMark::Protected);
+    }
+
     if let Some(keep_last) = options.keep_last {
         mark_selections(&mut mark, &list, keep_last as usize, |info| {
             Ok(info.backup_dir.backup_time_string().to_owned())
@@ -224,6 +229,7 @@ pub fn compute_prune_info(
         .map(|info| {
             let backup_id = info.backup_dir.relative_path();
             let keep = match mark.get(&backup_id) {
+                Some(PruneMark::Protected) => true,
                 Some(PruneMark::Keep) => true,
                 Some(PruneMark::KeepPartial) => true,
                _ => false,
@@ -0,0 +1,481 @@
+//! Per user and user group datastore usage accounting and quota checks
+//!
+//! The usage is computed during garbage collection phase 1, while all index files are read
+//! anyways, and saved to `<datastore>/.usage-status`. Backup sessions are checked against
+//! that (possibly slightly outdated) usage.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+use proxmox::tools::fs::{file_read_optional_string, replace_file, CreateOptions};
+
+use crate::api2::types::{DataStoreUsage, Userid};
+use crate::config::cached_user_info::CachedUserInfo;
+use crate::config::quota::{self, QuotaConfig, QuotaSubject};
+use crate::config::user::{self, UserGroup};
+use crate::tools::format::HumanByte;
+
+use super::{BackupGroup, DataStoreImpl};
+
+const USAGE_STATUS_FILENAME: &str = ".usage-status";
+
+/// Usage computed by the last garbage collection run
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct UsageStatus {
+    /// UPID of the garbage collection task
+    pub upid: Option<String>,
+    pub usage: Vec<DataStoreUsage>,
+}
+
+/// Read the usage status saved by the last garbage collection.
+pub fn read_usage_status(datastore: &DataStoreImpl) -> Result<Option<UsageStatus>, Error> {
+    let mut path = datastore.base_path();
+    path.push(USAGE_STATUS_FILENAME);
+
+    match file_read_optional_string(path)? {
+        Some(data) => Ok(Some(serde_json::from_str(&data)?)),
+        None => Ok(None),
+    }
+}
+
+struct OwnerAccount {
+    userid: Userid,
+    logical_bytes: u64,
+}
+
+/// Accumulates the usage of all backup owners. The usage of the user groups having a quota
+/// on the datastore is derived from the usage of their members.
+///
+/// Every referenced chunk is stored once, together with the (few) owners referencing it, so
+/// memory usage grows with the number of chunks in the datastore, not with the number of
+/// users and groups.
+pub struct UsageAccounting {
+    // user groups with a quota on this datastore, and their members
+    groups: Vec<(String, Vec<Userid>)>,
+    // owner cache, indexed by group path
+    owners: HashMap<PathBuf, Option<Userid>>,
+    accounts: Vec<OwnerAccount>,
+    account_map: HashMap<Userid, usize>,
+    // chunk size and the accounts referencing the chunk
+    chunks: HashMap<[u8; 32], (u64, Vec<usize>)>,
+    // account the currently processed index file is accounted to
+    current: Option<usize>,
+}
+
+impl UsageAccounting {
+    pub fn new(store: &str) -> Result<Self, Error> {
+        let (quota_config, _digest) = quota::config()?;
+        let (user_config, _digest) = user::config()?;
+
+        let mut groups = Vec::new();
+        for quota in quota::store_quotas(&quota_config, store)? {
+            if let Ok(QuotaSubject::Group(group)) = quota.subject.parse() {
+                if let Ok(data) = user_config.lookup::<UserGroup>("group", &group) {
+                    groups.push((group, data.member_list()?));
+                }
+            }
+        }
+
+        Ok(Self::with_groups(groups))
+    }
+
+    fn with_groups(groups: Vec<(String, Vec<Userid>)>) -> Self {
+        Self {
+            groups,
+            owners: HashMap::new(),
+            accounts: Vec::new(),
+            account_map: HashMap::new(),
+            chunks: HashMap::new(),
+            current: None,
+        }
+    }
+
+    /// Select the backup group the following index files belong to.
+    ///
+    /// Index files outside of a backup group (`None`) are not accounted.
+    pub fn select_group(&mut self, datastore: &DataStoreImpl, group: Option<&BackupGroup>) {
+        let owner = group.and_then(|group| {
+            self.owners
+                .entry(group.group_path())
+                .or_insert_with(|| datastore.get_owner(group).ok().map(|owner| owner.user().clone()))
+                .clone()
+        });
+
+        self.select_owner(owner);
+    }
+
+    fn select_owner(&mut self, owner: Option<Userid>) {
+        let userid = match owner {
+            Some(userid) => userid,
+            None => {
+                self.current = None;
+                return;
+            }
+        };
+
+        let accounts = &mut self.accounts;
+        let index = *self.account_map.entry(userid.clone()).or_insert_with(|| {
+            accounts.push(OwnerAccount { userid, logical_bytes: 0 });
+            accounts.len() - 1
+        });
+        self.current = Some(index);
+    }
+
+    /// Account an index file of the selected backup group.
+    pub fn add_index(&mut self, index_bytes: u64) {
+        if let Some(index) = self.current {
+            self.accounts[index].logical_bytes += index_bytes;
+        }
+    }
+
+    /// Account a chunk referenced by the current index file.
+    pub fn add_chunk(&mut self, digest: &[u8; 32], size: u64) {
+        let index = match self.current {
+            Some(index) => index,
+            None => return,
+        };
+
+        let (_size, accounts) = self.chunks.entry(*digest).or_insert_with(|| (size, Vec::new()));
+        if !accounts.contains(&index) {
+            accounts.push(index);
+        }
+    }
+
+    /// Compute the final usage, updating the soft limit state of the configured quotas.
+    pub fn finish(self, store: &str, previous: Option<UsageStatus>) -> Result<Vec<DataStoreUsage>, Error> {
+        let (quota_config, _digest) = quota::config()?;
+        let quotas = quota::store_quotas(&quota_config, store)?;
+
+        let previous = previous.map(|status| status.usage).unwrap_or_default();
+
+        let now = proxmox::tools::time::epoch_i64();
+
+        Ok(self.compute_usage(&quotas, &previous, now))
+    }
+
+    fn compute_usage(
+        self,
+        quotas: &[QuotaConfig],
+        previous: &[DataStoreUsage],
+        now: i64,
+    ) -> Vec<DataStoreUsage> {
+        let mut list: Vec<DataStoreUsage> = self.accounts
+            .iter()
+            .map(|account| DataStoreUsage {
+                subject: account.userid.to_string(),
+                logical_bytes: account.logical_bytes,
+                ..Default::default()
+            })
+            .collect();
+
+        // the entries in `list` each owner account is accounted to
+        let mut subjects: Vec<Vec<usize>> = (0..self.accounts.len()).map(|index| vec![index]).collect();
+
+        for (group, members) in &self.groups {
+            let mut group_index = None;
+            for (index, account) in self.accounts.iter().enumerate() {
+                if !members.contains(&account.userid) {
+                    continue;
+                }
+                let list_index = *group_index.get_or_insert_with(|| {
+                    list.push(DataStoreUsage {
+                        subject: format!("@{}", group),
+                        ..Default::default()
+                    });
+                    list.len() - 1
+                });
+                list[list_index].logical_bytes += account.logical_bytes;
+                subjects[index].push(list_index);
+            }
+        }
+
+        let mut chunk_subjects = Vec::new();
+        for (size, accounts) in self.chunks.values() {
+            chunk_subjects.clear();
+            for index in accounts {
+                chunk_subjects.extend_from_slice(&subjects[*index]);
+            }
+            // members of a group share the chunk, but it counts only once for the group
+            chunk_subjects.sort_unstable();
+            chunk_subjects.dedup();
+            for index in &chunk_subjects {
+                list[*index].unique_bytes += size;
+            }
+        }
+
+        for usage in list.iter_mut() {
+            let soft_limit = quotas.iter()
+                .find(|quota| quota.subject == usage.subject)
+                .and_then(|quota| quota.soft_limit);
+
+            if let Some(soft_limit) = soft_limit {
+                if usage.unique_bytes > soft_limit {
+                    let since = previous.iter()
+                        .find(|old| old.subject == usage.subject)
+                        .and_then(|old| old.soft_exceeded_since);
+                    usage.soft_exceeded_since = Some(since.unwrap_or(now));
+                }
+            }
+        }
+
+        list.sort_unstable_by(|a, b| a.subject.cmp(&b.subject));
+
+        list
+    }
+}
+
+/// Save the usage status computed by garbage collection.
+pub fn save_usage_status(datastore: &DataStoreImpl, status: &UsageStatus) -> Result<(), Error> {
+    let mut path = datastore.base_path();
+    path.push(USAGE_STATUS_FILENAME);
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0644);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = backup, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(backup_user.uid)
+        .group(backup_user.gid);
+
+    replace_file(path, serde_json::to_string(status)?.as_bytes(), options)
+}
+
+fn quota_applies(quota: &QuotaConfig, userid: &Userid, groups: &[String]) -> bool {
+    match quota.subject.parse() {
+        Ok(QuotaSubject::User(ref user)) => user == userid,
+        Ok(QuotaSubject::Group(ref group)) => groups.contains(group),
+        Err(_) => false,
+    }
+}
+
+/// Check if `userid` may start a new backup on `datastore`.
+///
+/// Fails if a hard limit is exceeded, or a soft limit is exceeded for longer than its grace
+/// period. Returns warnings for soft limits which are exceeded, but still within their grace
+/// period.
+pub fn check_backup_quota(datastore: &DataStoreImpl, userid: &Userid) -> Result<Vec<String>, Error> {
+    let (quota_config, _digest) = quota::config()?;
+    let quotas = quota::store_quotas(&quota_config, datastore.name())?;
+    if quotas.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let status = read_usage_status(datastore)?;
+
+    let user_info = CachedUserInfo::new()?;
+    let groups = user_info.user_groups(userid);
+
+    let now = proxmox::tools::time::epoch_i64();
+
+    check_quota_usage(datastore.name(), &quotas, status.as_ref(), userid, &groups, now)
+}
+
+fn check_quota_usage(
+    store: &str,
+    quotas: &[QuotaConfig],
+    status: Option<&UsageStatus>,
+    userid: &Userid,
+    groups: &[String],
+    now: i64,
+) -> Result<Vec<String>, Error> {
+    let status = match status {
+        Some(status) => status,
+        None => return Ok(Vec::new()), // no usage computed yet
+    };
+
+    let mut warnings = Vec::new();
+
+    for quota in quotas.iter().filter(|quota| quota_applies(quota, userid, groups)) {
+        let usage = match status.usage.iter().find(|usage| usage.subject == quota.subject) {
+            Some(usage) => usage,
+            None => continue,
+        };
+
+        if let Some(hard_limit) = quota.hard_limit {
+            if usage.unique_bytes > hard_limit {
+                bail!(
+                    "quota '{}' exceeded - '{}' uses {} on datastore '{}' (hard limit {})",
+                    quota.id,
+                    quota.subject,
+                    HumanByte::from(usage.unique_bytes),
+                    store,
+                    HumanByte::from(hard_limit),
+                );
+            }
+        }
+
+        if let Some(soft_limit) = quota.soft_limit {
+            if usage.unique_bytes > soft_limit {
+                // the limit may have been lowered after the last GC run
+                let since = usage.soft_exceeded_since.unwrap_or(now);
+                let deadline = since + quota.grace_period_secs();
+                if now >= deadline {
+                    bail!(
+                        "quota '{}' exceeded - '{}' uses {} on datastore '{}' (soft limit {}, grace period expired)",
+                        quota.id,
+                        quota.subject,
+                        HumanByte::from(usage.unique_bytes),
+                        store,
+                        HumanByte::from(soft_limit),
+                    );
+                }
+                warnings.push(format!(
+                    "quota '{}' - '{}' uses {} (soft limit {}), new backups will be rejected after {}",
+                    quota.id,
+                    quota.subject,
+                    HumanByte::from(usage.unique_bytes),
+                    HumanByte::from(soft_limit),
+                    proxmox::tools::time::epoch_to_rfc3339(deadline)?,
+                ));
+            }
+        }
+    }
+
+    Ok(warnings)
+}
+
+/// Filter `usage` to the entries visible to `userid`, i.e. the user itself and its `groups`.
+pub fn filter_user_usage(
+    usage: Vec<DataStoreUsage>,
+    userid: &Userid,
+    groups: &[String],
+) -> Vec<DataStoreUsage> {
+    usage.into_iter()
+        .filter(|usage| match usage.subject.strip_prefix('@') {
+            Some(group) => groups.iter().any(|g| g == group),
+            None => usage.subject == userid.as_str(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quota(subject: &str, soft_limit: Option<u64>, hard_limit: Option<u64>) -> QuotaConfig {
+        QuotaConfig {
+            id: "q1".to_string(),
+            store: "store1".to_string(),
+            subject: subject.to_string(),
+            soft_limit,
+            hard_limit,
+            grace_period: Some(1),
+            comment: None,
+        }
+    }
+
+    fn status(subject: &str, unique_bytes: u64, soft_exceeded_since: Option<i64>) -> UsageStatus {
+        UsageStatus {
+            upid: None,
+            usage: vec![DataStoreUsage {
+                subject: subject.to_string(),
+                unique_bytes,
+                soft_exceeded_since,
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[test]
+    fn test_usage_accounting() -> Result<(), Error> {
+        let john: Userid = "john@pbs".parse()?;
+        let jane: Userid = "jane@pbs".parse()?;
+        let bob: Userid = "bob@pbs".parse()?;
+
+        let mut accounting = UsageAccounting::with_groups(vec![
+            ("team".to_string(), vec![john.clone(), jane.clone()]),
+        ]);
+
+        accounting.select_owner(Some(john.clone()));
+        accounting.add_index(1000);
+        accounting.add_chunk(&[1; 32], 100);
+        accounting.add_chunk(&[2; 32], 200);
+        accounting.add_chunk(&[1; 32], 100); // referenced twice by john
+
+        accounting.select_owner(Some(jane.clone()));
+        accounting.add_index(500);
+        accounting.add_chunk(&[2; 32], 200); // shared with john
+        accounting.add_chunk(&[3; 32], 50);
+
+        accounting.select_owner(Some(bob.clone()));
+        accounting.add_index(10);
+        accounting.add_chunk(&[3; 32], 50);
+
+        accounting.select_owner(None); // not accounted
+        accounting.add_index(1);
+        accounting.add_chunk(&[4; 32], 1);
+
+        let quotas = vec![quota("@team", Some(300), None), quota("bob@pbs", Some(100), None)];
+        let previous = vec![DataStoreUsage {
+            subject: "@team".to_string(),
+            soft_exceeded_since: Some(42),
+            ..Default::default()
+        }];
+
+        let usage = accounting.compute_usage(&quotas, &previous, 1000);
+        let usage: Vec<(&str, u64, u64, Option<i64>)> = usage
+            .iter()
+            .map(|u| (u.subject.as_str(), u.logical_bytes, u.unique_bytes, u.soft_exceeded_since))
+            .collect();
+
+        assert_eq!(usage, vec![
+            ("@team", 1500, 350, Some(42)),
+            ("bob@pbs", 10, 50, None),
+            ("jane@pbs", 500, 250, None),
+            ("john@pbs", 1000, 300, None),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_quota_usage() -> Result<(), Error> {
+        let userid: Userid = "john@pbs".parse()?;
+        let groups = vec!["team".to_string()];
+        let now = 10 * 24 * 3600;
+        let check = |quotas: &[QuotaConfig], status: Option<&UsageStatus>| {
+            check_quota_usage("store1", quotas, status, &userid, &groups, now)
+        };
+
+        // no usage computed yet
+        let quotas = vec![quota("john@pbs", Some(10), Some(20))];
+        assert!(check(&quotas, None)?.is_empty());
+
+        // no usage for the subject
+        assert!(check(&quotas, Some(&status("jane@pbs", 100, None)))?.is_empty());
+
+        // below the limits
+        assert!(check(&quotas, Some(&status("john@pbs", 10, None)))?.is_empty());
+
+        // hard limit
+        let err = check(&quotas, Some(&status("john@pbs", 21, Some(now)))).unwrap_err();
+        assert!(err.to_string().contains("hard limit"));
+
+        // hard limit of a group the user is member of
+        let group_quotas = vec![quota("@team", None, Some(20))];
+        assert!(check(&group_quotas, Some(&status("@team", 21, None))).is_err());
+
+        // quotas of other subjects do not apply
+        let other_quotas = vec![quota("@other", None, Some(20))];
+        assert!(check(&other_quotas, Some(&status("@other", 21, None)))?.is_empty());
+
+        // soft limit within the grace period
+        let warnings = check(&quotas, Some(&status("john@pbs", 15, Some(now - 3600))))?;
+        assert_eq!(warnings.len(), 1);
+
+        // soft limit after the grace period
+        let err = check(&quotas, Some(&status("john@pbs", 15, Some(now - 24 * 3600)))).unwrap_err();
+        assert!(err.to_string().contains("grace period expired"));
+
+        // soft limit exceeded since the last GC run, e.g. after lowering the limit
+        let warnings = check(&quotas, Some(&status("john@pbs", 15, None)))?;
+        assert_eq!(warnings.len(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use anyhow::{bail, format_err, Error};
+
+use crate::api2::types::DatastoreBackendType;
+use crate::config::datastore::DataStoreConfig;
+use crate::config::s3::{self, S3ClientConfig};
+use crate::tools::runtime::block_on;
+use crate::tools::s3::{S3Client, S3Object};
+
+const CHUNK_PREFIX: &str = "chunks/";
+
+/// Stores the chunks of a datastore in an S3 bucket
+///
+/// Every chunk is stored as object `chunks/<digest>`. The local chunk
+/// directory of the datastore is still used, but only contains empty marker
+/// files (to mark chunks in use during garbage collection, S3 has no atime)
+/// or cached chunk contents.
+pub struct S3ChunkBackend {
+    client: S3Client,
+    bucket: String,
+    cache_policy: ChunkCachePolicy,
+}
+
+/// When garbage collection drops cached chunk contents
+///
+/// Without limits, cached contents are kept until the chunk itself is removed.
+#[derive(Clone, Default)]
+pub struct ChunkCachePolicy {
+    /// Maximum size of all cached chunk contents in bytes, the oldest get dropped first.
+    pub max_size: Option<u64>,
+    /// Maximum time in seconds to keep a cached chunk content.
+    pub max_age: Option<i64>,
+}
+
+impl ChunkCachePolicy {
+    /// Returns true if a chunk content cached at `cache_time` has to be dropped.
+    pub fn is_expired(&self, cache_time: i64, now: i64) -> bool {
+        match self.max_age {
+            Some(max_age) => cache_time < now - max_age,
+            None => false,
+        }
+    }
+
+    /// Selects the cached chunks to drop so that the cache fits into `max_size`.
+    ///
+    /// `cached` holds the cache time, size and file name of the cached chunks.
+    pub fn select_evictions(&self, mut cached: Vec<(i64, u64, String)>) -> Vec<String> {
+        let max_size = match self.max_size {
+            Some(max_size) => max_size,
+            None => return Vec::new(),
+        };
+
+        let mut size: u64 = cached.iter().map(|(_, chunk_size, _)| chunk_size).sum();
+
+        cached.sort_unstable_by_key(|(cache_time, _, _)| *cache_time);
+
+        let mut list = Vec::new();
+        for (_, chunk_size, name) in cached {
+            if size <= max_size {
+                break;
+            }
+            size -= chunk_size;
+            list.push(name);
+        }
+        list
+    }
+}
+
+impl S3ChunkBackend {
+    pub fn new(config: &S3ClientConfig, bucket: &str) -> Result<Self, Error> {
+        Ok(Self {
+            client: S3Client::new(config.client_options())?,
+            bucket: bucket.to_string(),
+            cache_policy: ChunkCachePolicy::default(),
+        })
+    }
+
+    /// Create a backend using the S3 client configuration `client_id`.
+    pub fn open(client_id: &str, bucket: &str) -> Result<Self, Error> {
+        let (config, _digest) = s3::config()?;
+        let client_config: S3ClientConfig = config.lookup("s3", client_id)?;
+        Self::new(&client_config, bucket)
+    }
+
+    /// Create the backend configured for a datastore, `None` if it uses the local file system.
+    pub fn from_datastore_config(config: &DataStoreConfig) -> Result<Option<Self>, Error> {
+        let backend = match config.get_backend()? {
+            Some(backend) => backend,
+            None => return Ok(None),
+        };
+
+        match backend.ty {
+            DatastoreBackendType::Filesystem => Ok(None),
+            DatastoreBackendType::S3 => {
+                let client_id = backend.client.as_deref()
+                    .ok_or_else(|| format_err!("datastore '{}': S3 backend without client", config.name))?;
+                let bucket = backend.bucket.as_deref()
+                    .ok_or_else(|| format_err!("datastore '{}': S3 backend without bucket", config.name))?;
+                let mut s3 = Self::open(client_id, bucket)?;
+                s3.cache_policy = ChunkCachePolicy {
+                    max_size: backend.cache_size.map(|size| size * 1024 * 1024),
+                    max_age: backend.cache_max_age.map(|days| (days * 24 * 3600) as i64),
+                };
+                Ok(Some(s3))
+            }
+        }
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    pub fn cache_policy(&self) -> &ChunkCachePolicy {
+        &self.cache_policy
+    }
+
+    /// Fails if the bucket does not exist or is not accessible.
+    pub fn check_bucket(&self) -> Result<(), Error> {
+        block_on(self.client.head_bucket(&self.bucket))
+    }
+
+    pub fn upload_chunk(&self, digest_str: &str, data: &[u8]) -> Result<(), Error> {
+        let key = format!("{}{}", CHUNK_PREFIX, digest_str);
+        block_on(self.client.put_object(&self.bucket, &key, data.to_vec()))
+            .map_err(|err| format_err!("unable to upload chunk {} - {}", digest_str, err))
+    }
+
+    pub fn download_chunk(&self, digest_str: &str) -> Result<Vec<u8>, Error> {
+        let key = format!("{}{}", CHUNK_PREFIX, digest_str);
+        match block_on(self.client.get_object(&self.bucket, &key))? {
+            Some(data) => Ok(data),
+            None => bail!("chunk {} missing in bucket '{}'", digest_str, self.bucket),
+        }
+    }
+
+    /// Returns the size of a chunk object, or `None` if it is missing in the bucket.
+    pub fn chunk_size(&self, digest_str: &str) -> Result<Option<u64>, Error> {
+        let key = format!("{}{}", CHUNK_PREFIX, digest_str);
+        block_on(self.client.head_object(&self.bucket, &key))
+            .map_err(|err| format_err!("unable to query chunk {} - {}", digest_str, err))
+    }
+
+    pub fn remove_chunk(&self, digest_str: &str) -> Result<(), Error> {
+        let key = format!("{}{}", CHUNK_PREFIX, digest_str);
+        block_on(self.client.delete_object(&self.bucket, &key))
+    }
+
+    /// Calls `callback` with the digest and object info of all chunks in the bucket.
+    pub fn for_each_chunk<F>(&self, mut callback: F) -> Result<(), Error>
+    where
+        F: FnMut(&str, &S3Object) -> Result<(), Error>,
+    {
+        let mut continuation_token = None;
+        loop {
+            let (list, next_token) = block_on(self.client.list_objects(
+                &self.bucket,
+                CHUNK_PREFIX,
+                continuation_token.as_deref(),
+            ))?;
+
+            for object in list.iter() {
+                let digest_str = match object.key.strip_prefix(CHUNK_PREFIX) {
+                    Some(digest_str) => digest_str,
+                    None => continue,
+                };
+                if digest_str.len() != 64 || !digest_str.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    continue; // not a chunk
+                }
+                callback(digest_str, object)?;
+            }
+
+            match next_token {
+                Some(token) => continuation_token = Some(token),
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_chunk_cache_policy() {
+    let cached = || vec![
+        (300, 100, "c".to_string()),
+        (100, 100, "a".to_string()),
+        (200, 50, "b".to_string()),
+        (400, 100, "d".to_string()),
+    ];
+
+    let policy = ChunkCachePolicy::default();
+    assert!(!policy.is_expired(0, 1_000_000));
+    assert!(policy.select_evictions(cached()).is_empty());
+
+    let policy = ChunkCachePolicy { max_size: Some(350), max_age: None };
+    assert!(policy.select_evictions(cached()).is_empty());
+
+    let policy = ChunkCachePolicy { max_size: Some(250), max_age: None };
+    assert_eq!(policy.select_evictions(cached()), ["a"]);
+
+    let policy = ChunkCachePolicy { max_size: Some(249), max_age: None };
+    assert_eq!(policy.select_evictions(cached()), ["a", "b"]);
+
+    let policy = ChunkCachePolicy { max_size: Some(199), max_age: None };
+    assert_eq!(policy.select_evictions(cached()), ["a", "b", "c"]);
+
+    let policy = ChunkCachePolicy { max_size: None, max_age: Some(100) };
+    assert!(policy.is_expired(199, 300));
+    assert!(!policy.is_expired(200, 300));
+}
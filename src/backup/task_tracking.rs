@@ -0,0 +1,119 @@
+//! Track the number of active read/write operations per datastore
+//!
+//! Every process keeps its own counters in a shared, locked state file below
+//! `/run/proxmox-backup/active-operations/`, so that the sum over all (still
+//! running) processes can be queried, e.g. to see when a maintenance mode is
+//! fully in effect.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+
+use proxmox::api::api;
+use proxmox::sys::linux::procfs;
+use proxmox::tools::fs::{file_read_optional_string, open_file_locked, replace_file, CreateOptions};
+
+use crate::api2::types::Operation;
+
+const ACTIVE_OPERATIONS_DIR: &str = "/run/proxmox-backup/active-operations";
+
+#[api]
+#[derive(Default, Clone, Copy, Serialize, Deserialize)]
+/// Number of active operations of one or all processes
+pub struct ActiveOperationStats {
+    /// Number of active read operations
+    pub read: i64,
+    /// Number of active write operations
+    pub write: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TaskOperations {
+    pid: u32,
+    starttime: u64,
+    active_operations: ActiveOperationStats,
+}
+
+fn state_paths(name: &str) -> (PathBuf, PathBuf) {
+    let mut path = PathBuf::from(ACTIVE_OPERATIONS_DIR);
+    path.push(name);
+    let mut lock_path = PathBuf::from(ACTIVE_OPERATIONS_DIR);
+    lock_path.push(format!(".{}.lock", name));
+    (path, lock_path)
+}
+
+// returns the entries of still running processes
+fn read_task_operations(path: &Path) -> Result<Vec<TaskOperations>, Error> {
+    let list = match file_read_optional_string(path)? {
+        Some(data) => serde_json::from_str::<Vec<TaskOperations>>(&data)?
+            .into_iter()
+            .filter(|task| {
+                procfs::check_process_running_pstart(task.pid as i32, task.starttime).is_some()
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    Ok(list)
+}
+
+/// Returns the summed up operation counters of all running processes for datastore `name`.
+pub fn get_active_operations(name: &str) -> Result<ActiveOperationStats, Error> {
+    let (path, _lock_path) = state_paths(name);
+
+    let mut stats = ActiveOperationStats::default();
+    for task in read_task_operations(&path)? {
+        stats.read += task.active_operations.read;
+        stats.write += task.active_operations.write;
+    }
+    Ok(stats)
+}
+
+/// Adds `count` (may be negative) to the counter for `operation` of this process.
+pub fn update_active_operations(name: &str, operation: Operation, count: i64) -> Result<(), Error> {
+    if operation == Operation::Lookup {
+        return Ok(()); // not tracked
+    }
+
+    let (path, lock_path) = state_paths(name);
+
+    let backup_user = crate::backup::backup_user()?;
+    let options = CreateOptions::new()
+        .group(backup_user.gid)
+        .owner(backup_user.uid)
+        .perm(nix::sys::stat::Mode::from_bits_truncate(0o660));
+
+    std::fs::create_dir_all(ACTIVE_OPERATIONS_DIR)?;
+
+    let _lock = open_file_locked(&lock_path, Duration::new(10, 0), true)?;
+
+    let pid = std::process::id();
+    let starttime = crate::server::pstart();
+
+    let mut updated = false;
+    let mut list = read_task_operations(&path)?;
+    for task in list.iter_mut() {
+        if task.pid == pid && task.starttime == starttime {
+            match operation {
+                Operation::Read => task.active_operations.read += count,
+                Operation::Write => task.active_operations.write += count,
+                Operation::Lookup => (),
+            }
+            updated = true;
+        }
+    }
+
+    if !updated {
+        let mut active_operations = ActiveOperationStats::default();
+        match operation {
+            Operation::Read => active_operations.read = count,
+            Operation::Write => active_operations.write = count,
+            Operation::Lookup => (),
+        }
+        list.push(TaskOperations { pid, starttime, active_operations });
+    }
+
+    let data = serde_json::to_string(&list)?;
+    replace_file(&path, data.as_bytes(), options)
+}
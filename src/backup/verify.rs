@@ -15,6 +15,7 @@ use crate::{
         BackupGroup,
         BackupDir,
         BackupInfo,
+        BackupNamespace,
         BackupManifest,
         IndexFile,
         CryptMode,
@@ -514,7 +515,11 @@ pub fn verify_all_backups(
         }
     };
 
-    let mut list = match BackupInfo::list_backup_groups(&verify_worker.datastore.base_path()) {
+    let mut list = match BackupInfo::list_backup_groups_recursive(
+        &verify_worker.datastore.base_path(),
+        &BackupNamespace::root(),
+        None,
+    ) {
         Ok(list) => list
             .into_iter()
             .filter(|group| !(group.backup_type() == "host" && group.backup_id() == "benchmark"))
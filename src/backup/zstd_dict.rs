@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use anyhow::{bail, format_err, Error};
+use lazy_static::lazy_static;
+
+use proxmox::tools::fs::{replace_file, CreateOptions};
+
+/// Subdirectory of a datastore containing the trained dictionaries
+pub const DICTIONARY_DIR_NAME: &str = ".dictionaries";
+
+const DICTIONARY_EXT: &str = "zdict";
+
+/// Upper limit for the size of a dictionary (zstd uses 110 KiB by default)
+pub const MAX_DICTIONARY_SIZE: usize = 1024*1024;
+
+lazy_static! {
+    static ref DICTIONARY_REGISTRY: RwLock<HashMap<[u8; 8], Arc<ZstdDictionary>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Zstd dictionary used to compress chunks
+///
+/// A dictionary is identified by the first 8 bytes of the SHA256 sum of
+/// its content. That ID is stored in the header of every blob compressed
+/// with it, so the dictionary needs to be available (see
+/// ``register_zstd_dictionary``) to decompress such a blob.
+pub struct ZstdDictionary {
+    id: [u8; 8],
+    data: Vec<u8>,
+}
+
+impl ZstdDictionary {
+    pub fn new(data: Vec<u8>) -> Self {
+        let id = openssl::sha::sha256(&data)[0..8].try_into().unwrap();
+        Self { id, data }
+    }
+
+    /// Train a new dictionary of up to `max_size` bytes from sample data.
+    pub fn train<S: AsRef<[u8]>>(samples: &[S], max_size: usize) -> Result<Self, Error> {
+        if samples.is_empty() {
+            bail!("unable to train zstd dictionary - no samples");
+        }
+        if max_size > MAX_DICTIONARY_SIZE {
+            bail!("zstd dictionary size too large ({} > {})", max_size, MAX_DICTIONARY_SIZE);
+        }
+        let data = zstd::dict::from_samples(samples, max_size)
+            .map_err(|err| format_err!("unable to train zstd dictionary - {}", err))?;
+        Ok(Self::new(data))
+    }
+
+    pub fn id(&self) -> &[u8; 8] {
+        &self.id
+    }
+
+    /// The ID as hex string, as used in configuration and file names.
+    pub fn id_str(&self) -> String {
+        proxmox::tools::bin_to_hex(&self.id)
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Parse a dictionary ID from its hex representation.
+pub fn parse_dictionary_id(id: &str) -> Result<[u8; 8], Error> {
+    let bytes = proxmox::tools::hex_to_bin(id)?;
+    bytes[..].try_into()
+        .map_err(|_| format_err!("invalid zstd dictionary id '{}'", id))
+}
+
+/// Make a dictionary known to this process, so that blobs referencing it can be decoded.
+pub fn register_zstd_dictionary(dict: ZstdDictionary) -> Arc<ZstdDictionary> {
+    let mut registry = DICTIONARY_REGISTRY.write().unwrap();
+    registry.entry(dict.id).or_insert_with(|| Arc::new(dict)).clone()
+}
+
+/// Lookup a registered dictionary by its ID.
+pub fn lookup_zstd_dictionary(id: &[u8; 8]) -> Option<Arc<ZstdDictionary>> {
+    DICTIONARY_REGISTRY.read().unwrap().get(id).cloned()
+}
+
+fn dictionary_dir(base: &Path) -> PathBuf {
+    let mut path = base.to_owned();
+    path.push(DICTIONARY_DIR_NAME);
+    path
+}
+
+fn dictionary_path(base: &Path, id: &[u8; 8]) -> PathBuf {
+    let mut path = dictionary_dir(base);
+    path.push(format!("{}.{}", proxmox::tools::bin_to_hex(id), DICTIONARY_EXT));
+    path
+}
+
+/// Load and register all dictionaries stored below `base`.
+///
+/// Returns the IDs of the loaded dictionaries.
+pub fn load_zstd_dictionaries(base: &Path) -> Result<Vec<[u8; 8]>, Error> {
+    let dir = dictionary_dir(base);
+
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => bail!("unable to read dictionary directory {:?} - {}", dir, err),
+    };
+
+    let mut list = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some(DICTIONARY_EXT) {
+            continue;
+        }
+        let data = std::fs::read(&path)
+            .map_err(|err| format_err!("unable to read zstd dictionary {:?} - {}", path, err))?;
+        let dict = ZstdDictionary::new(data);
+
+        let expected = path.file_stem().and_then(|stem| stem.to_str());
+        if expected != Some(dict.id_str().as_str()) {
+            bail!("zstd dictionary {:?} has wrong checksum", path);
+        }
+        list.push(*register_zstd_dictionary(dict).id());
+    }
+
+    Ok(list)
+}
+
+/// Store a dictionary below `base`, does nothing if it already exists.
+pub fn store_zstd_dictionary(
+    base: &Path,
+    dict: &ZstdDictionary,
+    options: CreateOptions,
+) -> Result<(), Error> {
+    let path = dictionary_path(base, dict.id());
+    if path.exists() {
+        return Ok(());
+    }
+
+    let dir = dictionary_dir(base);
+    if let Err(err) = std::fs::create_dir(&dir) {
+        if err.kind() != std::io::ErrorKind::AlreadyExists {
+            bail!("unable to create dictionary directory {:?} - {}", dir, err);
+        }
+    }
+
+    replace_file(&path, dict.data(), options)
+}
+
+/// Returns true if the dictionary with the given ID is stored below `base`.
+pub fn zstd_dictionary_exists(base: &Path, id: &[u8; 8]) -> bool {
+    dictionary_path(base, id).exists()
+}
@@ -1,3 +1,5 @@
+use std::time::{SystemTime, Instant, Duration, UNIX_EPOCH};
+
 use anyhow::{bail, Error};
 use futures::*;
 
@@ -12,6 +14,9 @@ use proxmox_backup::tools::daemon;
 use proxmox_backup::auth_helpers::*;
 use proxmox_backup::config;
 use proxmox_backup::buildcfg;
+use proxmox_backup::api2::types::Authid;
+use proxmox_backup::server::jobstate::{self, Job};
+use proxmox_backup::tools::systemd::time::{compute_next_event, parse_calendar_event};
 
 fn main() {
     proxmox_backup::tools::setup_safe_path_env();
@@ -96,6 +101,8 @@ async fn run() -> Result<(), Error> {
         bail!("unable to start daemon - {}", err);
     }
 
+    start_task_scheduler();
+
     server.await?;
     log::info!("server shutting down, waiting for active workers to complete");
     proxmox_backup::server::last_worker_future().await?;
@@ -104,3 +111,114 @@ async fn run() -> Result<(), Error> {
 
     Ok(())
 }
+
+// The proxy schedules most jobs, but jobs writing root owned configuration
+// files (like the realm sync) need to run here.
+fn start_task_scheduler() {
+    let abort_future = server::shutdown_future();
+    let future = Box::pin(run_task_scheduler());
+    let task = futures::future::select(future, abort_future);
+    tokio::spawn(task.map(|_| ()));
+}
+
+fn next_minute() -> Result<Instant, Error> {
+    let now = SystemTime::now();
+    let epoch_now = now.duration_since(UNIX_EPOCH)?;
+    let epoch_next = Duration::from_secs((epoch_now.as_secs()/60  + 1)*60);
+    Ok(Instant::now() + epoch_next - epoch_now)
+}
+
+async fn run_task_scheduler() {
+
+    let mut count: usize = 0;
+
+    loop {
+        count += 1;
+
+        let delay_target = match next_minute() {  // try to run very minute
+            Ok(d) => d,
+            Err(err) => {
+                log::error!("task scheduler: compute next minute failed - {}", err);
+                tokio::time::sleep_until(tokio::time::Instant::from_std(Instant::now() + Duration::from_secs(60))).await;
+                continue;
+            }
+        };
+
+        if count > 2 { // wait 1..2 minutes before starting
+            schedule_realm_sync_jobs();
+        }
+
+        tokio::time::sleep_until(tokio::time::Instant::from_std(delay_target)).await;
+    }
+}
+
+fn schedule_realm_sync_jobs() {
+
+    use proxmox_backup::config::domains::{self, LdapRealmConfig};
+
+    let config = match domains::config() {
+        Err(err) => {
+            log::error!("unable to read realm config - {}", err);
+            return;
+        }
+        Ok((config, _digest)) => config,
+    };
+    for (realm, (section_type, realm_config)) in config.sections {
+        if section_type != "ldap" {
+            continue;
+        }
+        let realm_config: LdapRealmConfig = match serde_json::from_value(realm_config) {
+            Ok(c) => c,
+            Err(err) => {
+                log::error!("realm config from_value failed - {}", err);
+                continue;
+            }
+        };
+        let event_str = match realm_config.sync_schedule {
+            Some(ref event_str) => event_str.clone(),
+            None => continue,
+        };
+
+        let worker_type = "realm-sync";
+        let auth_id = Authid::root_auth_id().clone();
+        if check_schedule(worker_type, &event_str, &realm) {
+            let job = match Job::new(worker_type, &realm) {
+                Ok(job) => job,
+                Err(_) => continue, // could not get lock
+            };
+            if let Err(err) = server::do_realm_sync_job(job, realm_config, &auth_id, Some(event_str), false) {
+                log::error!("unable to start realm sync job {} - {}", &realm, err);
+            }
+        };
+    }
+}
+
+fn check_schedule(worker_type: &str, event_str: &str, id: &str) -> bool {
+    let event = match parse_calendar_event(event_str) {
+        Ok(event) => event,
+        Err(err) => {
+            log::error!("unable to parse schedule '{}' - {}", event_str, err);
+            return false;
+        }
+    };
+
+    let last = match jobstate::last_run_time(worker_type, id) {
+        Ok(time) => time,
+        Err(err) => {
+            log::error!("could not get last run time of {} {}: {}", worker_type, id, err);
+            return false;
+        }
+    };
+
+    let next = match compute_next_event(&event, last, false) {
+        Ok(Some(next)) => next,
+        Ok(None) => return false,
+        Err(err) => {
+            log::error!("compute_next_event for '{}' failed - {}", event_str, err);
+            return false;
+        }
+    };
+
+    let now = proxmox::tools::time::epoch_i64();
+    next <= now
+}
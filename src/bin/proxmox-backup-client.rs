@@ -1,6 +1,7 @@
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::{FromRawFd, RawFd};
 use std::path::{Path, PathBuf};
 use std::pin::Pin;
@@ -47,6 +48,8 @@ use proxmox_backup::backup::{
     BackupDir,
     BackupGroup,
     BackupManifest,
+    BackupNamespace,
+    BlobCompression,
     BufferedDynamicReader,
     CATALOG_NAME,
     CatalogReader,
@@ -58,6 +61,8 @@ use proxmox_backup::backup::{
     ENCRYPTED_KEY_BLOB_NAME,
     FixedChunkStream,
     FixedIndexReader,
+    InjectChunks,
+    InjectionData,
     KeyConfig,
     IndexFile,
     MANIFEST_BLOB_NAME,
@@ -121,12 +126,16 @@ fn record_repository(repo: &BackupRepository) {
 async fn api_datastore_list_snapshots(
     client: &HttpClient,
     store: &str,
+    ns: &BackupNamespace,
     group: Option<BackupGroup>,
 ) -> Result<Value, Error> {
 
     let path = format!("api2/json/admin/datastore/{}/snapshots", store);
 
     let mut args = json!({});
+    if !ns.is_root() {
+        args["ns"] = ns.to_string().into();
+    }
     if let Some(group) = group {
         args["backup-type"] = group.backup_type().into();
         args["backup-id"] = group.backup_id().into();
@@ -141,9 +150,9 @@ pub async fn api_datastore_latest_snapshot(
     client: &HttpClient,
     store: &str,
     group: BackupGroup,
-) -> Result<(String, String, i64), Error> {
+) -> Result<BackupDir, Error> {
 
-    let list = api_datastore_list_snapshots(client, store, Some(group.clone())).await?;
+    let list = api_datastore_list_snapshots(client, store, group.ns(), Some(group.clone())).await?;
     let mut list: Vec<SnapshotListItem> = serde_json::from_value(list)?;
 
     if list.is_empty() {
@@ -154,9 +163,25 @@ pub async fn api_datastore_latest_snapshot(
 
     let backup_time = list[0].backup_time;
 
-    Ok((group.backup_type().to_owned(), group.backup_id().to_owned(), backup_time))
+    BackupDir::with_group(group, backup_time)
 }
 
+/// Parse a snapshot path, or use the latest snapshot if it only names a group.
+pub async fn parse_group_or_snapshot(
+    client: &HttpClient,
+    store: &str,
+    path: &str,
+) -> Result<BackupDir, Error> {
+    match path.parse::<BackupDir>() {
+        Ok(snapshot) => Ok(snapshot),
+        Err(_) => {
+            let group: BackupGroup = path.parse()?;
+            api_datastore_latest_snapshot(client, store, group).await
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn backup_directory<P: AsRef<Path>>(
     client: &BackupWriter,
     dir_path: P,
@@ -165,14 +190,40 @@ async fn backup_directory<P: AsRef<Path>>(
     catalog: Arc<Mutex<CatalogWriter<crate::tools::StdChannelWriter>>>,
     pxar_create_options: proxmox_backup::pxar::PxarCreateOptions,
     upload_options: UploadOptions,
+    previous: Option<PreviousPxarSnapshot<'_>>,
 ) -> Result<BackupStats, Error> {
 
+    let (previous_archive, injections) = match previous {
+        Some(previous) => {
+            let (boundaries_tx, boundaries_rx) = std::sync::mpsc::channel();
+            let (injections_tx, injections_rx) = std::sync::mpsc::channel();
+            match open_previous_pxar_archive(previous, archive_name, boundaries_tx).await {
+                Ok(archive) => (
+                    Some(archive),
+                    Some((InjectionData::new(boundaries_rx, injections_tx), injections_rx)),
+                ),
+                Err(err) => {
+                    println!("{}: unable to reuse previous archive - {}", archive_name, err);
+                    (None, None)
+                }
+            }
+        }
+        None => (None, None),
+    };
+
     let pxar_stream = PxarBackupStream::open(
         dir_path.as_ref(),
         catalog,
         pxar_create_options,
+        previous_archive,
     )?;
-    let mut chunk_stream = ChunkStream::new(pxar_stream, chunk_size);
+    let (mut chunk_stream, injections) = match injections {
+        Some((injection_data, injections)) => (
+            ChunkStream::with_injection(pxar_stream, chunk_size, injection_data),
+            Some(injections),
+        ),
+        None => (ChunkStream::new(pxar_stream, chunk_size), None),
+    };
 
     let (tx, rx) = mpsc::channel(10); // allow to buffer 10 chunks
 
@@ -191,12 +242,88 @@ async fn backup_directory<P: AsRef<Path>>(
     }
 
     let stats = client
-        .upload_stream(archive_name, stream, upload_options)
+        .upload_stream(archive_name, stream, upload_options, injections)
         .await?;
 
     Ok(stats)
 }
 
+/// Previous snapshot to reuse the chunks of unchanged files from.
+struct PreviousPxarSnapshot<'a> {
+    reader: &'a Arc<BackupReader>,
+    manifest: &'a BackupManifest,
+    crypt_config: Option<Arc<CryptConfig>>,
+    catalog: &'a std::fs::File,
+}
+
+/// Open archive `archive_name` of the previous snapshot for reading.
+async fn open_previous_pxar_archive(
+    previous: PreviousPxarSnapshot<'_>,
+    archive_name: &str,
+    boundaries: std::sync::mpsc::Sender<InjectChunks>,
+) -> Result<proxmox_backup::pxar::PreviousArchive, Error> {
+
+    let file_info = previous.manifest.lookup_file_info(archive_name)?;
+
+    let index = previous.reader.download_dynamic_index(previous.manifest, archive_name).await?;
+    let most_used = index.find_most_used_chunks(8);
+    let chunk_reader = RemoteChunkReader::new(
+        previous.reader.clone(),
+        previous.crypt_config.clone(),
+        file_info.chunk_crypt_mode(),
+        most_used,
+    );
+    let reader = BufferedDynamicReader::new(index, chunk_reader);
+    let archive_size = reader.archive_size();
+    let reader: proxmox_backup::pxar::fuse::Reader = Arc::new(BufferedDynamicReadAt::new(reader));
+    let accessor = proxmox_backup::pxar::fuse::Accessor::new(reader, archive_size).await?;
+
+    // the reader above owns its copy of the index
+    let index = previous.reader.download_dynamic_index(previous.manifest, archive_name).await?;
+
+    let mut catalog = CatalogReader::new(previous.catalog.try_clone()?);
+    let catalog_root = catalog.root()?;
+    let catalog_root = catalog
+        .lookup(&catalog_root, archive_name.as_bytes())?
+        .ok_or_else(|| format_err!("archive '{}' not found in previous catalog", archive_name))?;
+
+    Ok(proxmox_backup::pxar::PreviousArchive {
+        accessor,
+        index,
+        catalog,
+        catalog_root,
+        boundaries,
+    })
+}
+
+/// Download the catalog of a snapshot into a temporary file.
+async fn download_catalog(
+    reader: &Arc<BackupReader>,
+    manifest: &BackupManifest,
+    crypt_config: Option<Arc<CryptConfig>>,
+) -> Result<std::fs::File, Error> {
+
+    let index = reader.download_dynamic_index(manifest, CATALOG_NAME).await?;
+    let most_used = index.find_most_used_chunks(8);
+
+    let file_info = manifest.lookup_file_info(CATALOG_NAME)?;
+    let chunk_reader = RemoteChunkReader::new(reader.clone(), crypt_config, file_info.chunk_crypt_mode(), most_used);
+    let mut reader = BufferedDynamicReader::new(index, chunk_reader);
+
+    let mut catalogfile = std::fs::OpenOptions::new()
+        .write(true)
+        .read(true)
+        .custom_flags(libc::O_TMPFILE)
+        .open("/tmp")?;
+
+    std::io::copy(&mut reader, &mut catalogfile)
+        .map_err(|err| format_err!("unable to download catalog - {}", err))?;
+
+    catalogfile.seek(SeekFrom::Start(0))?;
+
+    Ok(catalogfile)
+}
+
 async fn backup_image<P: AsRef<Path>>(
     client: &BackupWriter,
     image_path: P,
@@ -219,7 +346,7 @@ async fn backup_image<P: AsRef<Path>>(
     }
 
     let stats = client
-        .upload_stream(archive_name, stream, upload_options)
+        .upload_stream(archive_name, stream, upload_options, None)
         .await?;
 
     Ok(stats)
@@ -232,6 +359,10 @@ async fn backup_image<P: AsRef<Path>>(
                 schema: REPO_URL_SCHEMA,
                 optional: true,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "output-format": {
                 schema: OUTPUT_FORMAT,
                 optional: true,
@@ -246,11 +377,15 @@ async fn list_backup_groups(param: Value) -> Result<Value, Error> {
 
     let repo = extract_repository_from_value(&param)?;
 
+    let ns = BackupNamespace::from_opt(param["ns"].as_str())?;
+
     let client = connect(&repo)?;
 
     let path = format!("api2/json/admin/datastore/{}/groups", repo.store());
 
-    let mut result = client.get(&path, None).await?;
+    let args = if ns.is_root() { None } else { Some(json!({ "ns": ns.to_string() })) };
+
+    let mut result = client.get(&path, args).await?;
 
     record_repository(&repo);
 
@@ -323,6 +458,9 @@ async fn change_backup_owner(group: String, mut param: Value) -> Result<(), Erro
 
     param["backup-type"] = group.backup_type().into();
     param["backup-id"] = group.backup_id().into();
+    if !group.ns().is_root() {
+        param["ns"] = group.ns().to_string().into();
+    }
 
     let path = format!("api2/json/admin/datastore/{}/change-owner", repo.store());
     client.post(&path, Some(param)).await?;
@@ -485,7 +623,7 @@ fn spawn_catalog_upload(
 
     tokio::spawn(async move {
         let catalog_upload_result = client
-            .upload_stream(CATALOG_NAME, catalog_chunk_stream, upload_options)
+            .upload_stream(CATALOG_NAME, catalog_chunk_stream, upload_options, None)
             .await;
 
         if let Err(ref err) = catalog_upload_result {
@@ -982,6 +1120,10 @@ fn test_crypto_parameters_handling() -> Result<(), Error> {
                description: "Skip lost+found directory.",
                optional: true,
            },
+           ns: {
+               schema: BACKUP_NAMESPACE_SCHEMA,
+               optional: true,
+           },
            "backup-type": {
                schema: BACKUP_TYPE_SCHEMA,
                optional: true,
@@ -1007,12 +1149,28 @@ fn test_crypto_parameters_handling() -> Result<(), Error> {
                    description: "Path or match pattern.",
                 }
            },
+           rate: {
+               schema: RATE_LIMIT_SCHEMA,
+               optional: true,
+           },
+           burst: {
+               schema: BURST_SCHEMA,
+               optional: true,
+           },
            "entries-max": {
                type: Integer,
                description: "Max number of entries to hold in memory.",
                optional: true,
                default: proxmox_backup::pxar::ENCODER_MAX_ENTRIES as isize,
            },
+           "change-detection-mode": {
+               type: PxarChangeDetectionMode,
+               optional: true,
+           },
+           "compression-level": {
+               schema: ZSTD_LEVEL_SCHEMA,
+               optional: true,
+           },
            "verbose": {
                type: Boolean,
                description: "Verbose output.",
@@ -1052,11 +1210,20 @@ async fn create_backup(
 
     let backup_type = param["backup-type"].as_str().unwrap_or("host");
 
+    let backup_ns = BackupNamespace::from_opt(param["ns"].as_str())?;
+
     let include_dev = param["include-dev"].as_array();
 
     let entries_max = param["entries-max"].as_u64()
         .unwrap_or(proxmox_backup::pxar::ENCODER_MAX_ENTRIES as u64);
 
+    let change_detection_mode: PxarChangeDetectionMode = match param.get("change-detection-mode") {
+        Some(mode) => serde_json::from_value(mode.clone())?,
+        None => PxarChangeDetectionMode::default(),
+    };
+
+    let compression_level = param["compression-level"].as_u64();
+
     let empty = Vec::new();
     let exclude_args = param["exclude"].as_array().unwrap_or(&empty);
 
@@ -1140,10 +1307,18 @@ async fn create_backup(
 
     let backup_time = backup_time_opt.unwrap_or_else(epoch_i64);
 
-    let client = connect(&repo)?;
+    let rate = param["rate"].as_u64();
+    let burst = param["burst"].as_u64();
+
+    let client = connect_rate_limited(&repo, rate, burst)?;
     record_repository(&repo);
 
-    println!("Starting backup: {}/{}/{}", backup_type, backup_id, BackupDir::backup_time_to_string(backup_time)?);
+    let snapshot = BackupDir::with_group(
+        BackupGroup::with_ns(backup_ns, backup_type, backup_id),
+        backup_time,
+    )?;
+
+    println!("Starting backup: {}", snapshot);
 
     println!("Client name: {}", proxmox::tools::nodename());
 
@@ -1187,6 +1362,7 @@ async fn create_backup(
         client,
         crypt_config.clone(),
         repo.store(),
+        snapshot.group().ns(),
         backup_type,
         &backup_id,
         backup_time,
@@ -1194,12 +1370,26 @@ async fn create_backup(
         false
     ).await?;
 
+    // the level can be overridden, but the dictionary always comes from the datastore
+    let compression = match (compression_level, client.chunk_compression().await?) {
+        (Some(0), _) => BlobCompression::None,
+        (Some(level), BlobCompression::Zstd { dictionary, .. }) => {
+            BlobCompression::Zstd { level: level as i32, dictionary }
+        }
+        (Some(level), BlobCompression::None) => {
+            BlobCompression::Zstd { level: level as i32, dictionary: None }
+        }
+        (None, compression) => compression,
+    };
+
+    let mut previous_backup_time = None;
     let download_previous_manifest = match client.previous_backup_time().await {
         Ok(Some(backup_time)) => {
             println!(
                 "Downloading previous manifest ({})",
                 strftime_local("%c", backup_time)?
             );
+            previous_backup_time = Some(backup_time);
             true
         }
         Ok(None) => {
@@ -1232,7 +1422,39 @@ async fn create_backup(
         None
     };
 
-    let snapshot = BackupDir::new(backup_type, backup_id, backup_time)?;
+    // reader and catalog of the previous snapshot, to detect unchanged files
+    let previous_reader = match (change_detection_mode, previous_backup_time, &previous_manifest) {
+        (PxarChangeDetectionMode::Metadata, Some(backup_time), Some(previous_manifest)) => {
+            let result = async {
+                let reader = BackupReader::start(
+                    connect_rate_limited(&repo, rate, burst)?,
+                    crypt_config.clone(),
+                    repo.store(),
+                    snapshot.group().ns(),
+                    backup_type,
+                    backup_id,
+                    backup_time,
+                    verbose,
+                ).await?;
+                let catalog = download_catalog(&reader, previous_manifest, crypt_config.clone()).await?;
+                Ok::<_, Error>((reader, catalog))
+            }.await;
+
+            match result {
+                Ok(previous_reader) => Some(previous_reader),
+                Err(err) => {
+                    println!("Couldn't open previous snapshot, reading all files - {}", err);
+                    None
+                }
+            }
+        }
+        (PxarChangeDetectionMode::Metadata, _, _) => {
+            println!("No previous snapshot to detect unchanged files, reading all files.");
+            None
+        }
+        _ => None,
+    };
+
     let mut manifest = BackupManifest::new(snapshot);
 
     let mut catalog = None;
@@ -1289,10 +1511,27 @@ async fn create_backup(
                 let upload_options = UploadOptions {
                     previous_manifest: previous_manifest.clone(),
                     compress: true,
+                    compression: Some(compression.clone()),
                     encrypt: crypto.mode == CryptMode::Encrypt,
                     ..UploadOptions::default()
                 };
 
+                // only reuse chunks with the same encryption
+                let previous = match (&previous_reader, &previous_manifest) {
+                    (Some((reader, previous_catalog)), Some(previous_manifest)) => {
+                        match previous_manifest.lookup_file_info(&target) {
+                            Ok(info) if info.crypt_mode == crypto.mode => Some(PreviousPxarSnapshot {
+                                reader,
+                                manifest: previous_manifest,
+                                crypt_config: crypt_config.clone(),
+                                catalog: previous_catalog,
+                            }),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+
                 let stats = backup_directory(
                     &client,
                     &filename,
@@ -1301,6 +1540,7 @@ async fn create_backup(
                     catalog.clone(),
                     pxar_options,
                     upload_options,
+                    previous,
                 ).await?;
                 manifest.add_file(target, stats.size, stats.csum, crypto.mode)?;
                 catalog.lock().unwrap().end_directory()?;
@@ -1312,6 +1552,7 @@ async fn create_backup(
                     previous_manifest: previous_manifest.clone(),
                     fixed_size: Some(size),
                     compress: true,
+                    compression: Some(compression.clone()),
                     encrypt: crypto.mode == CryptMode::Encrypt,
                 };
 
@@ -1474,6 +1715,14 @@ We do not extract '.pxar' archives when writing to standard output.
                type: CryptMode,
                optional: true,
            },
+           rate: {
+               schema: RATE_LIMIT_SCHEMA,
+               optional: true,
+           },
+           burst: {
+               schema: BURST_SCHEMA,
+               optional: true,
+           },
        }
    }
 )]
@@ -1487,19 +1736,16 @@ async fn restore(param: Value) -> Result<Value, Error> {
 
     let archive_name = tools::required_string_param(&param, "archive-name")?;
 
-    let client = connect(&repo)?;
+    let rate = param["rate"].as_u64();
+    let burst = param["burst"].as_u64();
+
+    let client = connect_rate_limited(&repo, rate, burst)?;
 
     record_repository(&repo);
 
     let path = tools::required_string_param(&param, "snapshot")?;
 
-    let (backup_type, backup_id, backup_time) = if path.matches('/').count() == 1 {
-        let group: BackupGroup = path.parse()?;
-        api_datastore_latest_snapshot(&client, repo.store(), group).await?
-    } else {
-        let snapshot: BackupDir = path.parse()?;
-        (snapshot.group().backup_type().to_owned(), snapshot.group().backup_id().to_owned(), snapshot.backup_time())
-    };
+    let snapshot = parse_group_or_snapshot(&client, repo.store(), path).await?;
 
     let target = tools::required_string_param(&param, "target")?;
     let target = if target == "-" { None } else { Some(target) };
@@ -1522,9 +1768,10 @@ async fn restore(param: Value) -> Result<Value, Error> {
         client,
         crypt_config.clone(),
         repo.store(),
-        &backup_type,
-        &backup_id,
-        backup_time,
+        snapshot.group().ns(),
+        snapshot.group().backup_type(),
+        snapshot.group().backup_id(),
+        snapshot.backup_time(),
         true,
     ).await?;
 
@@ -1696,6 +1943,9 @@ async fn prune_async(mut param: Value) -> Result<Value, Error> {
 
     param["backup-type"] = group.backup_type().into();
     param["backup-id"] = group.backup_id().into();
+    if !group.ns().is_root() {
+        param["ns"] = group.ns().to_string().into();
+    }
 
     let mut result = client.post(&path, Some(param)).await?;
 
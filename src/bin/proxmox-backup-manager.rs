@@ -216,16 +216,44 @@ fn task_mgmt_cli() -> CommandLineInterface {
             "local-store": {
                 schema: DATASTORE_SCHEMA,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             remote: {
                 schema: REMOTE_ID_SCHEMA,
             },
             "remote-store": {
                 schema: DATASTORE_SCHEMA,
             },
+            "remote-ns": {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
             "remove-vanished": {
                 schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
                 optional: true,
             },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "transfer-last": {
+                schema: TRANSFER_LAST_SCHEMA,
+                optional: true,
+            },
+            "rate-in": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-in": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
             "output-format": {
                 schema: OUTPUT_FORMAT,
                 optional: true,
@@ -252,6 +280,12 @@ async fn pull_datastore(
         "remote-store": remote_store,
     });
 
+    for key in &["ns", "remote-ns", "max-depth", "group-filter", "transfer-last", "rate-in", "burst-in"] {
+        if !param[key].is_null() {
+            args[key] = param[key].clone();
+        }
+    }
+
     if let Some(remove_vanished) = remove_vanished {
         args["remove-vanished"] = Value::from(remove_vanished);
     }
@@ -263,6 +297,94 @@ async fn pull_datastore(
     Ok(Value::Null)
 }
 
+// fixme: avoid API redefinition
+#[api(
+   input: {
+        properties: {
+            "local-store": {
+                schema: DATASTORE_SCHEMA,
+            },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            remote: {
+                schema: REMOTE_ID_SCHEMA,
+            },
+            "remote-store": {
+                schema: DATASTORE_SCHEMA,
+            },
+            "remote-ns": {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "remove-vanished": {
+                schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
+                optional: true,
+            },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
+            "transfer-last": {
+                schema: TRANSFER_LAST_SCHEMA,
+                optional: true,
+            },
+            "rate-out": {
+                schema: RATE_LIMIT_SCHEMA,
+                optional: true,
+            },
+            "burst-out": {
+                schema: BURST_SCHEMA,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+   }
+)]
+/// Sync datastore to another repository
+async fn push_datastore(
+    local_store: String,
+    remote: String,
+    remote_store: String,
+    remove_vanished: Option<bool>,
+    param: Value,
+) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let mut client = connect_to_localhost()?;
+
+    let mut args = json!({
+        "store": local_store,
+        "remote": remote,
+        "remote-store": remote_store,
+    });
+
+    for key in &["ns", "remote-ns", "max-depth", "group-filter", "transfer-last", "rate-out", "burst-out"] {
+        if !param[key].is_null() {
+            args[key] = param[key].clone();
+        }
+    }
+
+    if let Some(remove_vanished) = remove_vanished {
+        args["remove-vanished"] = Value::from(remove_vanished);
+    }
+
+    let result = client.post("api2/json/push", Some(args)).await?;
+
+    view_task_result(&mut client, result, &output_format).await?;
+
+    Ok(Value::Null)
+}
+
 #[api(
    input: {
         properties: {
@@ -348,17 +470,27 @@ fn main() {
 
     let cmd_def = CliCommandMap::new()
         .insert("acl", acl_commands())
+        .insert("acme", acme_mgmt_cli())
         .insert("datastore", datastore_commands())
         .insert("disk", disk_commands())
         .insert("dns", dns_commands())
         .insert("network", network_commands())
         .insert("user", user_commands())
+        .insert("group", group_commands())
+        .insert("ldap", ldap_commands())
+        .insert("openid", openid_commands())
         .insert("remote", remote_commands())
         .insert("garbage-collection", garbage_collection_commands())
         .insert("cert", cert_mgmt_cli())
         .insert("subscription", subscription_commands())
         .insert("sync-job", sync_job_commands())
         .insert("verify-job", verify_job_commands())
+        .insert("prune-job", prune_job_commands())
+        .insert("quota", quota_commands())
+        .insert("metric-server", metric_server_commands())
+        .insert("notification", notification_commands())
+        .insert("traffic-control", traffic_control_commands())
+        .insert("s3-client", s3_client_commands())
         .insert("task", task_mgmt_cli())
         .insert(
             "pull",
@@ -368,6 +500,14 @@ fn main() {
                 .completion_cb("remote", config::remote::complete_remote_name)
                 .completion_cb("remote-store", complete_remote_datastore_name)
         )
+        .insert(
+            "push",
+            CliCommand::new(&API_METHOD_PUSH_DATASTORE)
+                .arg_param(&["local-store", "remote", "remote-store"])
+                .completion_cb("local-store", config::datastore::complete_datastore_name)
+                .completion_cb("remote", config::remote::complete_remote_name)
+                .completion_cb("remote-store", complete_remote_datastore_name)
+        )
         .insert(
             "verify",
             CliCommand::new(&API_METHOD_VERIFY)
@@ -1,11 +1,11 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::path::{Path, PathBuf};
 use std::os::unix::io::AsRawFd;
 
 use anyhow::{bail, format_err, Error};
 use futures::*;
 
-use openssl::ssl::{SslMethod, SslAcceptor, SslFiletype};
+use openssl::ssl::SslAcceptor;
 use tokio_stream::wrappers::ReceiverStream;
 
 use proxmox::try_block;
@@ -22,6 +22,7 @@ use proxmox_backup::{
             Job,
         },
         rotate_task_log_archive,
+        traffic_control,
     },
     tools::systemd::time::{
         parse_calendar_event,
@@ -30,8 +31,7 @@ use proxmox_backup::{
 };
 
 
-use proxmox_backup::api2::types::Authid;
-use proxmox_backup::configdir;
+use proxmox_backup::api2::types::{Authid, Operation, SyncDirection};
 use proxmox_backup::buildcfg;
 use proxmox_backup::server;
 use proxmox_backup::auth_helpers::*;
@@ -42,6 +42,7 @@ use proxmox_backup::tools::{
         zfs_pool_stats,
     },
     logrotate::LogRotate,
+    RateLimitedStream,
     socket::{
         set_tcp_keepalive,
         PROXMOX_BACKUP_TCP_KEEPALIVE_TIME,
@@ -52,6 +53,7 @@ use proxmox_backup::api2::pull::do_sync_job;
 use proxmox_backup::api2::tape::backup::do_tape_backup_job;
 use proxmox_backup::server::do_verification_job;
 use proxmox_backup::server::do_prune_job;
+use proxmox_backup::server::do_prune_job_config;
 
 fn main() -> Result<(), Error> {
     proxmox_backup::tools::setup_safe_path_env();
@@ -111,24 +113,14 @@ async fn run() -> Result<(), Error> {
 
     let rest_server = RestServer::new(config);
 
-    //openssl req -x509 -newkey rsa:4096 -keyout /etc/proxmox-backup/proxy.key -out /etc/proxmox-backup/proxy.pem -nodes
-    let key_path = configdir!("/proxy.key");
-    let cert_path = configdir!("/proxy.pem");
-
-    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
-    acceptor.set_private_key_file(key_path, SslFiletype::PEM)
-        .map_err(|err| format_err!("unable to read proxy key {} - {}", key_path, err))?;
-    acceptor.set_certificate_chain_file(cert_path)
-        .map_err(|err| format_err!("unable to read proxy cert {} - {}", cert_path, err))?;
-    acceptor.check_private_key().unwrap();
-
-    let acceptor = Arc::new(acceptor.build());
+    let acceptor = Arc::new(Mutex::new(make_tls_acceptor()?));
+    let connection_acceptor = Arc::clone(&acceptor);
 
     let server = daemon::create_daemon(
         ([0,0,0,0,0,0,0,0], 8007).into(),
-        |listener, ready| {
+        move |listener, ready| {
 
-            let connections = accept_connections(listener, acceptor, debug);
+            let connections = accept_connections(listener, connection_acceptor, debug);
             let connections = hyper::server::accept::from_stream(ReceiverStream::new(connections));
 
             Ok(ready
@@ -149,6 +141,13 @@ async fn run() -> Result<(), Error> {
 
     let init_result: Result<(), Error> = try_block!({
         server::register_task_control_commands(&mut commando_sock)?;
+        server::acme_challenge::register_challenge_commands(&mut commando_sock)?;
+        commando_sock.register_command("reload-certificate".into(), move |_| {
+            log::info!("reloading certificate");
+            let new_acceptor = make_tls_acceptor()?;
+            *acceptor.lock().unwrap() = new_acceptor;
+            Ok(serde_json::Value::Null)
+        })?;
         commando_sock.spawn()?;
         server::server_state_init()?;
         Ok(())
@@ -169,11 +168,15 @@ async fn run() -> Result<(), Error> {
     Ok(())
 }
 
+fn make_tls_acceptor() -> Result<Arc<SslAcceptor>, Error> {
+    Ok(Arc::new(proxmox_backup::config::proxy_tls_acceptor()?))
+}
+
 fn accept_connections(
     listener: tokio::net::TcpListener,
-    acceptor: Arc<openssl::ssl::SslAcceptor>,
+    acceptor: Arc<Mutex<Arc<SslAcceptor>>>,
     debug: bool,
-) -> tokio::sync::mpsc::Receiver<Result<std::pin::Pin<Box<tokio_openssl::SslStream<tokio::net::TcpStream>>>, Error>> {
+) -> tokio::sync::mpsc::Receiver<Result<std::pin::Pin<Box<tokio_openssl::SslStream<RateLimitedStream<tokio::net::TcpStream>>>>, Error>> {
 
     const MAX_PENDING_ACCEPTS: usize = 1024;
 
@@ -187,10 +190,15 @@ fn accept_connections(
                 Err(err) => {
                     eprintln!("error accepting tcp connection: {}", err);
                 }
-                Ok((sock, _addr)) =>  {
+                Ok((sock, addr)) =>  {
                     sock.set_nodelay(true).unwrap();
                     let _ = set_tcp_keepalive(sock.as_raw_fd(), PROXMOX_BACKUP_TCP_KEEPALIVE_TIME);
-                    let acceptor = Arc::clone(&acceptor);
+
+                    let (read_limiter, write_limiter) = traffic_control::lookup_rate_limiter(&addr.ip());
+                    let sock = RateLimitedStream::with_limiter(sock, read_limiter, write_limiter);
+
+                    // clone the current acceptor, so a certificate reload affects new connections only
+                    let acceptor = Arc::clone(&acceptor.lock().unwrap());
 
                     let ssl = match openssl::ssl::Ssl::new(acceptor.context()) {
                         Ok(ssl) => ssl,
@@ -316,6 +324,7 @@ async fn schedule_tasks() -> Result<(), Error> {
 
     schedule_datastore_garbage_collection().await;
     schedule_datastore_prune().await;
+    schedule_datastore_prune_jobs().await;
     schedule_datastore_sync_jobs().await;
     schedule_datastore_verify_jobs().await;
     schedule_tape_backup_jobs().await;
@@ -324,6 +333,33 @@ async fn schedule_tasks() -> Result<(), Error> {
     Ok(())
 }
 
+// Scheduled jobs are not started while the maintenance mode of their datastore
+// forbids the operation, or while its removable device is not mounted - they run
+// as soon as the datastore is available again.
+fn datastore_allows_operation(store: &str, operation: Operation) -> bool {
+
+    use proxmox_backup::config::datastore::{self, DataStoreConfig};
+
+    let store_config: DataStoreConfig = match datastore::config()
+        .and_then(|(config, _digest)| config.lookup("datastore", store))
+    {
+        Ok(store_config) => store_config,
+        Err(err) => {
+            eprintln!("unable to read config of datastore '{}' - {}", store, err);
+            return false;
+        }
+    };
+
+    if !store_config.is_mounted() {
+        return false;
+    }
+
+    match store_config.get_maintenance_mode() {
+        Some(maintenance_mode) => maintenance_mode.check(Some(operation)).is_ok(),
+        None => true,
+    }
+}
+
 async fn schedule_datastore_garbage_collection() {
 
     use proxmox_backup::config::{
@@ -342,13 +378,6 @@ async fn schedule_datastore_garbage_collection() {
     };
 
     for (store, (_, store_config)) in config.sections {
-        let datastore = match DataStore::lookup_datastore(&store) {
-            Ok(datastore) => datastore,
-            Err(err) => {
-                eprintln!("lookup_datastore failed - {}", err);
-                continue;
-            }
-        };
 
         let store_config: DataStoreConfig = match serde_json::from_value(store_config) {
             Ok(c) => c,
@@ -358,11 +387,31 @@ async fn schedule_datastore_garbage_collection() {
             }
         };
 
+        // skip datastores in maintenance mode or with their removable device absent,
+        // the job runs once the datastore is available again
+        if !store_config.is_mounted() {
+            continue;
+        }
+
+        if let Some(maintenance_mode) = store_config.get_maintenance_mode() {
+            if maintenance_mode.check(Some(Operation::Write)).is_err() {
+                continue;
+            }
+        }
+
         let event_str = match store_config.gc_schedule {
             Some(event_str) => event_str,
             None => continue,
         };
 
+        let datastore = match DataStore::lookup_datastore(&store, Some(Operation::Write)) {
+            Ok(datastore) => datastore,
+            Err(err) => {
+                eprintln!("lookup_datastore failed - {}", err);
+                continue;
+            }
+        };
+
         let event = match parse_calendar_event(&event_str) {
             Ok(event) => event,
             Err(err) => {
@@ -439,6 +488,16 @@ async fn schedule_datastore_prune() {
             }
         };
 
+        if !store_config.is_mounted() {
+            continue;
+        }
+
+        if let Some(maintenance_mode) = store_config.get_maintenance_mode() {
+            if maintenance_mode.check(Some(Operation::Write)).is_err() {
+                continue;
+            }
+        }
+
         let event_str = match store_config.prune_schedule {
             Some(event_str) => event_str,
             None => continue,
@@ -472,6 +531,55 @@ async fn schedule_datastore_prune() {
     }
 }
 
+async fn schedule_datastore_prune_jobs() {
+
+    use proxmox_backup::config::prune::{
+        self,
+        PruneJobConfig,
+    };
+
+    let config = match prune::config() {
+        Err(err) => {
+            eprintln!("unable to read prune job config - {}", err);
+            return;
+        }
+        Ok((config, _digest)) => config,
+    };
+    for (job_id, (_, job_config)) in config.sections {
+        let job_config: PruneJobConfig = match serde_json::from_value(job_config) {
+            Ok(c) => c,
+            Err(err) => {
+                eprintln!("prune job config from_value failed - {}", err);
+                continue;
+            }
+        };
+        let event_str = match job_config.schedule {
+            Some(ref event_str) => event_str.clone(),
+            None => continue,
+        };
+
+        if !job_config.prune_options().keeps_something() { // no prune settings - keep all
+            continue;
+        }
+
+        if !datastore_allows_operation(&job_config.store, Operation::Write) {
+            continue;
+        }
+
+        let worker_type = "prunejob";
+        let auth_id = Authid::root_auth_id().clone();
+        if check_schedule(worker_type, &event_str, &job_id) {
+            let job = match Job::new(&worker_type, &job_id) {
+                Ok(job) => job,
+                Err(_) => continue, // could not get lock
+            };
+            if let Err(err) = do_prune_job_config(job, job_config, &auth_id, Some(event_str)) {
+                eprintln!("unable to start datastore prune job {} - {}", &job_id, err);
+            }
+        };
+    }
+}
+
 async fn schedule_datastore_sync_jobs() {
 
     use proxmox_backup::config::sync::{
@@ -501,6 +609,14 @@ async fn schedule_datastore_sync_jobs() {
             None => continue,
         };
 
+        let operation = match job_config.sync_direction {
+            Some(SyncDirection::Push) => Operation::Read,
+            _ => Operation::Write,
+        };
+        if !datastore_allows_operation(&job_config.store, operation) {
+            continue;
+        }
+
         let worker_type = "syncjob";
         if check_schedule(worker_type, &event_str, &job_id) {
             let job = match Job::new(worker_type, &job_id) {
@@ -543,6 +659,10 @@ async fn schedule_datastore_verify_jobs() {
             None => continue,
         };
 
+        if !datastore_allows_operation(&job_config.store, Operation::Read) {
+            continue;
+        }
+
         let worker_type = "verificationjob";
         let auth_id = Authid::root_auth_id().clone();
         if check_schedule(worker_type, &event_str, &job_id) {
@@ -584,6 +704,10 @@ async fn schedule_tape_backup_jobs() {
             None => continue,
         };
 
+        if !datastore_allows_operation(&job_config.setup.store, Operation::Read) {
+            continue;
+        }
+
         let worker_type = "tape-backup-job";
         let auth_id = Authid::root_auth_id().clone();
         if check_schedule(worker_type, &event_str, &job_id) {
@@ -738,14 +862,22 @@ async fn generate_host_stats(save: bool) {
     use proxmox::sys::linux::procfs::{
         read_meminfo, read_proc_stat, read_proc_net_dev, read_loadavg};
     use proxmox_backup::config::datastore;
+    use proxmox_backup::server::metric_server::{self, MetricDataPoint};
+
 
+    let metrics = proxmox_backup::tools::runtime::block_in_place(move || {
 
-    proxmox_backup::tools::runtime::block_in_place(move || {
+        let mut metrics = Vec::new();
 
         match read_proc_stat() {
             Ok(stat) => {
                 rrd_update_gauge("host/cpu", stat.cpu, save);
                 rrd_update_gauge("host/iowait", stat.iowait_percent, save);
+
+                let mut point = MetricDataPoint::new("cpustat");
+                point.add_field("cpu", stat.cpu);
+                point.add_field("iowait", stat.iowait_percent);
+                metrics.push(point);
             }
             Err(err) => {
                 eprintln!("read_proc_stat failed - {}", err);
@@ -758,6 +890,13 @@ async fn generate_host_stats(save: bool) {
                 rrd_update_gauge("host/memused", meminfo.memused as f64, save);
                 rrd_update_gauge("host/swaptotal", meminfo.swaptotal as f64, save);
                 rrd_update_gauge("host/swapused", meminfo.swapused as f64, save);
+
+                let mut point = MetricDataPoint::new("memory");
+                point.add_field("memtotal", meminfo.memtotal as f64);
+                point.add_field("memused", meminfo.memused as f64);
+                point.add_field("swaptotal", meminfo.swaptotal as f64);
+                point.add_field("swapused", meminfo.swapused as f64);
+                metrics.push(point);
             }
             Err(err) => {
                 eprintln!("read_meminfo failed - {}", err);
@@ -776,6 +915,11 @@ async fn generate_host_stats(save: bool) {
                 }
                 rrd_update_derive("host/netin", netin as f64, save);
                 rrd_update_derive("host/netout", netout as f64, save);
+
+                let mut point = MetricDataPoint::new("nics");
+                point.add_field("netin", netin as f64);
+                point.add_field("netout", netout as f64);
+                metrics.push(point);
             }
             Err(err) => {
                 eprintln!("read_prox_net_dev failed - {}", err);
@@ -785,6 +929,12 @@ async fn generate_host_stats(save: bool) {
         match read_loadavg() {
             Ok(loadavg) => {
                 rrd_update_gauge("host/loadavg", loadavg.0 as f64, save);
+
+                let mut point = MetricDataPoint::new("load");
+                point.add_field("avg1", loadavg.0 as f64);
+                point.add_field("avg5", loadavg.1 as f64);
+                point.add_field("avg15", loadavg.2 as f64);
+                metrics.push(point);
             }
             Err(err) => {
                 eprintln!("read_loadavg failed - {}", err);
@@ -793,7 +943,9 @@ async fn generate_host_stats(save: bool) {
 
         let disk_manager = DiskManage::new();
 
-        gather_disk_stats(disk_manager.clone(), Path::new("/"), "host", save);
+        let mut point = MetricDataPoint::new("disk");
+        gather_disk_stats(disk_manager.clone(), Path::new("/"), "host", save, &mut point);
+        metrics.push(point);
 
         match datastore::config() {
             Ok((config, _)) => {
@@ -802,9 +954,15 @@ async fn generate_host_stats(save: bool) {
 
                 for config in datastore_list {
 
+                    if !config.is_mounted() {
+                        continue; // do not record the usage of the mount point's file system
+                    }
+
                     let rrd_prefix = format!("datastore/{}", config.name);
                     let path = std::path::Path::new(&config.path);
-                    gather_disk_stats(disk_manager.clone(), path, &rrd_prefix, save);
+                    let mut point = MetricDataPoint::new("datastore").tag("datastore", &config.name);
+                    gather_disk_stats(disk_manager.clone(), path, &rrd_prefix, save, &mut point);
+                    metrics.push(point);
                 }
             }
             Err(err) => {
@@ -812,7 +970,12 @@ async fn generate_host_stats(save: bool) {
             }
         }
 
+        metrics
     });
+
+    if let Err(err) = metric_server::send_data_to_metric_servers(&metrics).await {
+        eprintln!("sending metrics failed - {}", err);
+    }
 }
 
 fn check_schedule(worker_type: &str, event_str: &str, id: &str) -> bool {
@@ -845,7 +1008,13 @@ fn check_schedule(worker_type: &str, event_str: &str, id: &str) -> bool {
     next <= now
 }
 
-fn gather_disk_stats(disk_manager: Arc<DiskManage>, path: &Path, rrd_prefix: &str, save: bool) {
+fn gather_disk_stats(
+    disk_manager: Arc<DiskManage>,
+    path: &Path,
+    rrd_prefix: &str,
+    save: bool,
+    point: &mut proxmox_backup::server::metric_server::MetricDataPoint,
+) {
 
     match proxmox_backup::tools::disks::disk_usage(path) {
         Ok(status) => {
@@ -853,6 +1022,10 @@ fn gather_disk_stats(disk_manager: Arc<DiskManage>, path: &Path, rrd_prefix: &st
             rrd_update_gauge(&rrd_key, status.total as f64, save);
             let rrd_key = format!("{}/used", rrd_prefix);
             rrd_update_gauge(&rrd_key, status.used as f64, save);
+
+            point.add_field("total", status.total as f64);
+            point.add_field("used", status.used as f64);
+            point.add_field("avail", status.avail as f64);
         }
         Err(err) => {
             eprintln!("read disk_usage on {:?} failed - {}", path, err);
@@ -894,6 +1067,12 @@ fn gather_disk_stats(disk_manager: Arc<DiskManage>, path: &Path, rrd_prefix: &st
 
                 let rrd_key = format!("{}/io_ticks", rrd_prefix);
                 rrd_update_derive(&rrd_key, (stat.io_ticks as f64)/1000.0, save);
+
+                point.add_field("read_ios", stat.read_ios as f64);
+                point.add_field("read_bytes", (stat.read_sectors*512) as f64);
+                point.add_field("write_ios", stat.write_ios as f64);
+                point.add_field("write_bytes", (stat.write_sectors*512) as f64);
+                point.add_field("io_ticks", (stat.io_ticks as f64)/1000.0);
             }
         }
         Err(err) => {
@@ -19,6 +19,30 @@ async fn wait_for_local_worker(upid_str: &str) -> Result<(), Error> {
     Ok(())
 }
 
+/// Renew the ACME certificate if it expires soon
+async fn check_acme_certificate(rpcenv: &mut dyn RpcEnvironment) -> Result<(), Error> {
+    let (domains, _digest) = proxmox_backup::config::acme::domain_config()?;
+    if domains.sections.is_empty() {
+        return Ok(());
+    }
+
+    if !api2::node::certificates::certificate_needs_renewal()? {
+        return Ok(());
+    }
+
+    println!("certificate expires soon, renewing");
+
+    let param = json!({ "node": "localhost" });
+    let method = &api2::node::certificates::API_METHOD_RENEW_ACME_CERT;
+    let upid = match method.handler {
+        ApiHandler::Sync(handler) => (handler)(param, method, rpcenv)?,
+        _ => unreachable!(),
+    };
+    wait_for_local_worker(upid.as_str().unwrap()).await?;
+
+    Ok(())
+}
+
 /// Daily update
 async fn do_update(
     rpcenv: &mut dyn RpcEnvironment,
@@ -50,7 +74,9 @@ async fn do_update(
     };
     wait_for_local_worker(upid.as_str().unwrap()).await?;
 
-    // TODO: certificate checks/renewal/... ?
+    if let Err(err) = check_acme_certificate(rpcenv).await {
+        eprintln!("error checking certificate - {}", err);
+    }
 
     // TODO: cleanup tasks like in PVE?
 
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::io::{Seek, SeekFrom};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{bail, format_err, Error};
+use serde_json::Value;
+
+use proxmox::api::{
+    api,
+    cli::{
+        default_table_format_options, format_and_print_result, format_and_print_result_full,
+        get_output_format, run_cli_command, CliCommand, CliCommandMap, CliEnvironment,
+        ColumnConfig, OUTPUT_FORMAT,
+    },
+};
+use pxar::accessor::aio::Accessor;
+
+use proxmox_backup::api2::{helpers, types::ArchiveEntry};
+use proxmox_backup::backup::{
+    load_and_decrypt_key, BackupDir, BackupManifest, BufferedDynamicReader, CatalogReader,
+    CryptConfig, CryptMode, LocalDynamicReadAt, CATALOG_NAME,
+};
+use proxmox_backup::client::{BackupReader, BackupRepository, RemoteChunkReader};
+use proxmox_backup::pxar::{create_zip, extract_sub_dir};
+use proxmox_backup::tools;
+
+// use "pub" so rust doesn't complain about "unused" functions in the module
+pub mod proxmox_client_tools;
+use proxmox_client_tools::{
+    complete_group_or_snapshot, complete_repository, connect, extract_repository_from_value,
+    get_encryption_key_password, KEYFILE_SCHEMA, REPO_URL_SCHEMA,
+};
+
+mod proxmox_file_restore;
+use proxmox_file_restore::*;
+
+enum ExtractPath {
+    ListArchives,
+    Pxar(String, Vec<u8>),
+    Image(String, Vec<u8>),
+}
+
+fn parse_path(path: String, base64: bool) -> Result<ExtractPath, Error> {
+    let mut bytes = if base64 {
+        base64::decode(path)?
+    } else {
+        path.into_bytes()
+    };
+
+    while bytes.first() == Some(&b'/') {
+        bytes.remove(0);
+    }
+
+    if bytes.is_empty() {
+        return Ok(ExtractPath::ListArchives);
+    }
+
+    let slash_pos = bytes.iter().position(|c| *c == b'/').unwrap_or(bytes.len());
+    let path = bytes.split_off(slash_pos);
+    let file = String::from_utf8(bytes)?;
+
+    if file.ends_with(".pxar.didx") {
+        Ok(ExtractPath::Pxar(file, path))
+    } else if file.ends_with(".img.fidx") {
+        Ok(ExtractPath::Image(file, path))
+    } else {
+        bail!("'{}' is not supported for file-restore", file);
+    }
+}
+
+/// Everything needed to access the archives of a snapshot.
+struct SnapshotAccess {
+    repo: BackupRepository,
+    snapshot: BackupDir,
+    keyfile: Option<String>,
+    crypt_config: Option<Arc<CryptConfig>>,
+    client: Arc<BackupReader>,
+    manifest: BackupManifest,
+}
+
+async fn open_snapshot(param: &Value) -> Result<SnapshotAccess, Error> {
+    let repo = extract_repository_from_value(param)?;
+    let snapshot: BackupDir = tools::required_string_param(param, "snapshot")?.parse()?;
+
+    let keyfile = param["keyfile"].as_str().map(String::from);
+    let crypt_config = match keyfile {
+        None => None,
+        Some(ref path) => {
+            let (key, _, _) =
+                load_and_decrypt_key(&PathBuf::from(path), &get_encryption_key_password)?;
+            Some(Arc::new(CryptConfig::new(key)?))
+        }
+    };
+
+    let client = connect(&repo)?;
+    let client = BackupReader::start(
+        client,
+        crypt_config.clone(),
+        repo.store(),
+        snapshot.group().ns(),
+        snapshot.group().backup_type(),
+        snapshot.group().backup_id(),
+        snapshot.backup_time(),
+        true,
+    )
+    .await?;
+
+    let (manifest, _) = client.download_manifest().await?;
+    manifest.check_fingerprint(crypt_config.as_ref().map(Arc::as_ref))?;
+
+    Ok(SnapshotAccess {
+        repo,
+        snapshot,
+        keyfile,
+        crypt_config,
+        client,
+        manifest,
+    })
+}
+
+impl SnapshotAccess {
+    fn into_restore_details(self) -> SnapRestoreDetails {
+        SnapRestoreDetails {
+            repo: self.repo,
+            snapshot: self.snapshot,
+            manifest: self.manifest,
+            keyfile: self.keyfile,
+        }
+    }
+
+    fn chunk_reader(&self, archive_name: &str) -> Result<RemoteChunkReader, Error> {
+        let file_info = self.manifest.lookup_file_info(archive_name)?;
+        Ok(RemoteChunkReader::new(
+            self.client.clone(),
+            self.crypt_config.clone(),
+            file_info.chunk_crypt_mode(),
+            Default::default(),
+        ))
+    }
+
+    async fn open_pxar(
+        &self,
+        archive_name: &str,
+    ) -> Result<Accessor<LocalDynamicReadAt<RemoteChunkReader>>, Error> {
+        let index = self
+            .client
+            .download_dynamic_index(&self.manifest, archive_name)
+            .await?;
+        let reader = BufferedDynamicReader::new(index, self.chunk_reader(archive_name)?);
+        let archive_size = reader.archive_size();
+        let reader = LocalDynamicReadAt::new(reader);
+        Ok(Accessor::new(reader, archive_size).await?)
+    }
+
+    async fn open_catalog(&self) -> Result<CatalogReader<std::fs::File>, Error> {
+        let index = self
+            .client
+            .download_dynamic_index(&self.manifest, CATALOG_NAME)
+            .await?;
+        let mut reader = BufferedDynamicReader::new(index, self.chunk_reader(CATALOG_NAME)?);
+
+        let mut catalogfile = std::fs::OpenOptions::new()
+            .write(true)
+            .read(true)
+            .custom_flags(libc::O_TMPFILE)
+            .open("/tmp")?;
+
+        std::io::copy(&mut reader, &mut catalogfile)
+            .map_err(|err| format_err!("unable to download catalog - {}", err))?;
+
+        catalogfile.seek(SeekFrom::Start(0))?;
+
+        Ok(CatalogReader::new(catalogfile))
+    }
+}
+
+#[api(
+   input: {
+       properties: {
+           repository: {
+               schema: REPO_URL_SCHEMA,
+               optional: true,
+           },
+           snapshot: {
+               type: String,
+               description: "Snapshot path.",
+           },
+           "path": {
+               description: "Path to list, starting with the archive name.",
+               type: String,
+           },
+           "base64": {
+               type: Boolean,
+               description: "If set, 'path' will be interpreted as base64 encoded.",
+               optional: true,
+               default: false,
+           },
+           keyfile: {
+               schema: KEYFILE_SCHEMA,
+               optional: true,
+           },
+           "output-format": {
+               schema: OUTPUT_FORMAT,
+               optional: true,
+           },
+       }
+   },
+   returns: {
+       description: "A list of elements under the given path",
+       type: Array,
+       items: {
+           type: ArchiveEntry,
+       }
+   }
+)]
+/// List a directory from a backup snapshot.
+async fn list(path: String, base64: bool, param: Value) -> Result<Value, Error> {
+    let output_format = get_output_format(&param);
+
+    let access = open_snapshot(&param).await?;
+
+    let list: Vec<ArchiveEntry> = match parse_path(path, base64)? {
+        ExtractPath::ListArchives => access
+            .manifest
+            .files()
+            .iter()
+            .filter(|file| {
+                file.filename.ends_with(".pxar.didx") || file.filename.ends_with(".img.fidx")
+            })
+            .map(|file| {
+                let path = format!("/{}", file.filename);
+                let attr = proxmox_backup::backup::DirEntryAttribute::Directory { start: 0 };
+                ArchiveEntry::new(path.as_bytes(), &attr)
+            })
+            .collect(),
+        ExtractPath::Pxar(file, mut path) => {
+            if access.manifest.lookup_file_info(CATALOG_NAME)?.crypt_mode == CryptMode::Encrypt
+                && access.crypt_config.is_none()
+            {
+                bail!("catalog is encrypted, please provide a keyfile");
+            }
+            let mut catalog_reader = access.open_catalog().await?;
+            let mut fullpath = format!("/{}", file).into_bytes();
+            fullpath.append(&mut path);
+            helpers::list_dir_content(&mut catalog_reader, &fullpath)?
+        }
+        ExtractPath::Image(file, path) => {
+            let mut fullpath = format!("/{}", file).into_bytes();
+            fullpath.extend(path);
+            data_list(access.into_restore_details(), fullpath).await?
+        }
+    };
+
+    let options = default_table_format_options()
+        .sortby("type", false)
+        .sortby("text", false)
+        .column(ColumnConfig::new("type"))
+        .column(ColumnConfig::new("text").header("name"))
+        .column(
+            ColumnConfig::new("mtime")
+                .header("last modified")
+                .renderer(tools::format::render_epoch),
+        )
+        .column(ColumnConfig::new("size"));
+
+    let mut data = serde_json::to_value(list)?;
+    format_and_print_result_full(&mut data, &API_METHOD_LIST.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+   input: {
+       properties: {
+           repository: {
+               schema: REPO_URL_SCHEMA,
+               optional: true,
+           },
+           snapshot: {
+               type: String,
+               description: "Snapshot path.",
+           },
+           "path": {
+               description: "Path to restore. Directories are restored as .zip files, \
+                   except for .pxar archives extracted to a target directory.",
+               type: String,
+           },
+           "base64": {
+               type: Boolean,
+               description: "If set, 'path' will be interpreted as base64 encoded.",
+               optional: true,
+               default: false,
+           },
+           target: {
+               type: String,
+               optional: true,
+               description: "Target directory path. Use '-' to write to standard output.",
+           },
+           keyfile: {
+               schema: KEYFILE_SCHEMA,
+               optional: true,
+           },
+           "verbose": {
+               type: Boolean,
+               description: "Print verbose information",
+               optional: true,
+               default: false,
+           },
+       }
+   }
+)]
+/// Restore files from a backup snapshot.
+async fn extract(
+    path: String,
+    base64: bool,
+    target: Option<String>,
+    verbose: bool,
+    param: Value,
+) -> Result<(), Error> {
+    let target = match target {
+        Some(target) if target == "-" => None,
+        Some(target) => Some(PathBuf::from(target)),
+        None => Some(std::env::current_dir()?),
+    };
+
+    let access = open_snapshot(&param).await?;
+
+    match parse_path(path, base64)? {
+        ExtractPath::ListArchives => {
+            bail!("ListArchives not supported for extract, please specify a path to extract");
+        }
+        ExtractPath::Pxar(archive_name, path) => {
+            let decoder = access.open_pxar(&archive_name).await?;
+            let path = OsStr::from_bytes(&path).to_os_string();
+
+            if let Some(target) = target {
+                extract_sub_dir(target, decoder, &path, verbose).await?;
+            } else {
+                let root = decoder.open_root().await?;
+                let file = root
+                    .lookup(&path)
+                    .await?
+                    .ok_or_else(|| format_err!("error opening '{:?}'", path))?;
+
+                match file.kind() {
+                    pxar::EntryKind::File { .. } => {
+                        tokio::io::copy(&mut file.contents().await?, &mut tokio::io::stdout())
+                            .await?;
+                    }
+                    _ => {
+                        create_zip(tokio::io::stdout(), decoder, &path, verbose).await?;
+                    }
+                }
+            }
+        }
+        ExtractPath::Image(file, path) => {
+            let mut fullpath = format!("/{}", file).into_bytes();
+            fullpath.extend(path);
+            let details = access.into_restore_details();
+
+            if let Some(mut target) = target {
+                if target.is_dir() {
+                    let name = fullpath.rsplit(|c| *c == b'/').next().unwrap_or_default();
+                    target.push(OsStr::from_bytes(name));
+                }
+                // directories are always transferred as zip archive
+                let mut output = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&target)
+                    .await
+                    .map_err(|err| format_err!("unable to create {:?} - {}", target, err))?;
+                data_extract(details, fullpath, &mut output).await?;
+            } else {
+                data_extract(details, fullpath, &mut tokio::io::stdout()).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        },
+    },
+)]
+/// Print status of all running file-restore VMs.
+async fn status(param: Value) -> Result<(), Error> {
+    let output_format = get_output_format(&param);
+
+    let list = proxmox_file_restore::status().await?;
+
+    if output_format == "text" {
+        for vm in list {
+            println!(
+                "{} (pid {}, cid {}): uptime {}s, shutdown in {}s",
+                vm["id"].as_str().unwrap_or_default(),
+                vm["pid"],
+                vm["cid"],
+                vm["uptime"],
+                vm["timeout"],
+            );
+        }
+    } else {
+        format_and_print_result(&Value::from(list), &output_format);
+    }
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            "name": {
+                type: String,
+                description: "The name of the VM to stop.",
+            },
+        },
+    },
+)]
+/// Immediately stop a running file-restore VM.
+async fn stop(name: String) -> Result<(), Error> {
+    proxmox_file_restore::stop(name).await
+}
+
+fn complete_vm_name(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    list_vms()
+}
+
+fn main() {
+    if let Err(err) = create_run_dir() {
+        eprintln!("unable to create run directory - {}", err);
+    }
+
+    let list_cmd_def = CliCommand::new(&API_METHOD_LIST)
+        .arg_param(&["snapshot", "path"])
+        .completion_cb("repository", complete_repository)
+        .completion_cb("snapshot", complete_group_or_snapshot);
+
+    let extract_cmd_def = CliCommand::new(&API_METHOD_EXTRACT)
+        .arg_param(&["snapshot", "path", "target"])
+        .completion_cb("repository", complete_repository)
+        .completion_cb("snapshot", complete_group_or_snapshot)
+        .completion_cb("target", tools::complete_file_name);
+
+    let status_cmd_def = CliCommand::new(&API_METHOD_STATUS);
+    let stop_cmd_def = CliCommand::new(&API_METHOD_STOP)
+        .arg_param(&["name"])
+        .completion_cb("name", complete_vm_name);
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", list_cmd_def)
+        .insert("extract", extract_cmd_def)
+        .insert("status", status_cmd_def)
+        .insert("stop", stop_cmd_def);
+
+    let rpcenv = CliEnvironment::new();
+    run_cli_command(
+        cmd_def,
+        rpcenv,
+        Some(|future| proxmox_backup::tools::runtime::main(future)),
+    );
+}
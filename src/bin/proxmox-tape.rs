@@ -28,10 +28,14 @@ use proxmox_backup::{
         self,
         types::{
             Authid,
+            BACKUP_NAMESPACE_SCHEMA,
             DATASTORE_SCHEMA,
             DRIVE_NAME_SCHEMA,
+            GROUP_FILTER_LIST_SCHEMA,
             MEDIA_LABEL_SCHEMA,
             MEDIA_POOL_NAME_SCHEMA,
+            NS_MAX_DEPTH_SCHEMA,
+            TAPE_RESTORE_SNAPSHOT_SCHEMA,
             Userid,
         },
     },
@@ -826,6 +830,18 @@ async fn clean_drive(mut param: Value) -> Result<(), Error> {
                 type: bool,
                 optional: true,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
+            "max-depth": {
+                schema: NS_MAX_DEPTH_SCHEMA,
+                optional: true,
+            },
+            "group-filter": {
+                schema: GROUP_FILTER_LIST_SCHEMA,
+                optional: true,
+            },
             "output-format": {
                 schema: OUTPUT_FORMAT,
                 optional: true,
@@ -873,6 +889,14 @@ async fn backup(mut param: Value) -> Result<(), Error> {
                 type: Authid,
                 optional: true,
             },
+            snapshots: {
+                description: "List of snapshots or groups to restore (default: restore the whole media set).",
+                type: Array,
+                optional: true,
+                items: {
+                    schema: TAPE_RESTORE_SNAPSHOT_SCHEMA,
+                },
+            },
             "output-format": {
                 schema: OUTPUT_FORMAT,
                 optional: true,
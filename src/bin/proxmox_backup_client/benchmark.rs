@@ -20,6 +20,7 @@ use proxmox::api::{
 
 use proxmox_backup::backup::{
     load_and_decrypt_key,
+    BackupNamespace,
     CryptConfig,
     KeyDerivationConfig,
     DataChunkBuilder,
@@ -234,6 +235,7 @@ async fn test_upload_speed(
         client,
         crypt_config.clone(),
         repo.store(),
+        &BackupNamespace::root(),
         "host",
         "benchmark",
         backup_time,
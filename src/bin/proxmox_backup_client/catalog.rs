@@ -19,7 +19,7 @@ use crate::{
     record_repository,
     key::get_encryption_key_password,
     decrypt_key,
-    api_datastore_latest_snapshot,
+    parse_group_or_snapshot,
     complete_repository,
     complete_backup_snapshot,
     complete_group_or_snapshot,
@@ -27,7 +27,6 @@ use crate::{
     connect,
     crypto_parameters,
     BackupDir,
-    BackupGroup,
     BufferedDynamicReader,
     BufferedDynamicReadAt,
     CatalogReader,
@@ -90,8 +89,9 @@ async fn dump_catalog(param: Value) -> Result<Value, Error> {
         client,
         crypt_config.clone(),
         repo.store(),
-        &snapshot.group().backup_type(),
-        &snapshot.group().backup_id(),
+        snapshot.group().ns(),
+        snapshot.group().backup_type(),
+        snapshot.group().backup_id(),
         snapshot.backup_time(),
         true,
     ).await?;
@@ -163,13 +163,7 @@ async fn catalog_shell(param: Value) -> Result<(), Error> {
     let path = tools::required_string_param(&param, "snapshot")?;
     let archive_name = tools::required_string_param(&param, "archive-name")?;
 
-    let (backup_type, backup_id, backup_time) = if path.matches('/').count() == 1 {
-        let group: BackupGroup = path.parse()?;
-        api_datastore_latest_snapshot(&client, repo.store(), group).await?
-    } else {
-        let snapshot: BackupDir = path.parse()?;
-        (snapshot.group().backup_type().to_owned(), snapshot.group().backup_id().to_owned(), snapshot.backup_time())
-    };
+    let snapshot = parse_group_or_snapshot(&client, repo.store(), path).await?;
 
     let crypto = crypto_parameters(&param)?;
 
@@ -196,9 +190,10 @@ async fn catalog_shell(param: Value) -> Result<(), Error> {
         client,
         crypt_config.clone(),
         repo.store(),
-        &backup_type,
-        &backup_id,
-        backup_time,
+        snapshot.group().ns(),
+        snapshot.group().backup_type(),
+        snapshot.group().backup_id(),
+        snapshot.backup_time(),
         true,
     ).await?;
 
@@ -23,7 +23,6 @@ use proxmox_backup::backup::{
     CryptConfig,
     IndexFile,
     BackupDir,
-    BackupGroup,
     BufferedDynamicReader,
     AsyncIndexReader,
 };
@@ -39,7 +38,7 @@ use crate::{
     complete_repository,
     record_repository,
     connect,
-    api_datastore_latest_snapshot,
+    parse_group_or_snapshot,
     BufferedDynamicReadAt,
 };
 
@@ -169,13 +168,7 @@ async fn mount_do(param: Value, pipe: Option<Fd>) -> Result<Value, Error> {
     record_repository(&repo);
 
     let path = tools::required_string_param(&param, "snapshot")?;
-    let (backup_type, backup_id, backup_time) = if path.matches('/').count() == 1 {
-        let group: BackupGroup = path.parse()?;
-        api_datastore_latest_snapshot(&client, repo.store(), group).await?
-    } else {
-        let snapshot: BackupDir = path.parse()?;
-        (snapshot.group().backup_type().to_owned(), snapshot.group().backup_id().to_owned(), snapshot.backup_time())
-    };
+    let snapshot = parse_group_or_snapshot(&client, repo.store(), path).await?;
 
     let keyfile = param["keyfile"].as_str().map(PathBuf::from);
     let crypt_config = match keyfile {
@@ -206,9 +199,10 @@ async fn mount_do(param: Value, pipe: Option<Fd>) -> Result<Value, Error> {
         client,
         crypt_config.clone(),
         repo.store(),
-        &backup_type,
-        &backup_id,
-        backup_time,
+        snapshot.group().ns(),
+        snapshot.group().backup_type(),
+        snapshot.group().backup_id(),
+        snapshot.backup_time(),
         true,
     ).await?;
 
@@ -16,6 +16,7 @@ use proxmox_backup::{
         CryptConfig,
         DataBlob,
         BackupGroup,
+        BackupNamespace,
         decrypt_key,
     }
 };
@@ -33,6 +34,7 @@ use crate::{
     crypto_parameters,
     extract_repository_from_value,
     record_repository,
+    snapshot_args,
 };
 
 #[api(
@@ -47,6 +49,10 @@ use crate::{
                 description: "Backup group.",
                 optional: true,
             },
+            ns: {
+                schema: BACKUP_NAMESPACE_SCHEMA,
+                optional: true,
+            },
             "output-format": {
                 schema: OUTPUT_FORMAT,
                 optional: true,
@@ -69,7 +75,12 @@ async fn list_snapshots(param: Value) -> Result<Value, Error> {
         None
     };
 
-    let mut data = api_datastore_list_snapshots(&client, repo.store(), group).await?;
+    let ns = match &group {
+        Some(group) => group.ns().clone(),
+        None => BackupNamespace::from_opt(param["ns"].as_str())?,
+    };
+
+    let mut data = api_datastore_list_snapshots(&client, repo.store(), &ns, group).await?;
 
     record_repository(&repo);
 
@@ -136,11 +147,7 @@ async fn list_snapshot_files(param: Value) -> Result<Value, Error> {
 
     let path = format!("api2/json/admin/datastore/{}/files", repo.store());
 
-    let mut result = client.get(&path, Some(json!({
-        "backup-type": snapshot.group().backup_type(),
-        "backup-id": snapshot.group().backup_id(),
-        "backup-time": snapshot.backup_time(),
-    }))).await?;
+    let mut result = client.get(&path, Some(snapshot_args(&snapshot))).await?;
 
     record_repository(&repo);
 
@@ -182,11 +189,7 @@ async fn forget_snapshots(param: Value) -> Result<Value, Error> {
 
     let path = format!("api2/json/admin/datastore/{}/snapshots", repo.store());
 
-    let result = client.delete(&path, Some(json!({
-        "backup-type": snapshot.group().backup_type(),
-        "backup-id": snapshot.group().backup_id(),
-        "backup-time": snapshot.backup_time(),
-    }))).await?;
+    let result = client.delete(&path, Some(snapshot_args(&snapshot))).await?;
 
     record_repository(&repo);
 
@@ -257,11 +260,7 @@ async fn upload_log(param: Value) -> Result<Value, Error> {
 
     let path = format!("api2/json/admin/datastore/{}/upload-backup-log", repo.store());
 
-    let args = json!({
-        "backup-type": snapshot.group().backup_type(),
-        "backup-id":  snapshot.group().backup_id(),
-        "backup-time": snapshot.backup_time(),
-    });
+    let args = snapshot_args(&snapshot);
 
     let body = hyper::Body::from(raw_data);
 
@@ -296,11 +295,7 @@ async fn show_notes(param: Value) -> Result<Value, Error> {
 
     let path = format!("api2/json/admin/datastore/{}/notes", repo.store());
 
-    let args = json!({
-        "backup-type": snapshot.group().backup_type(),
-        "backup-id": snapshot.group().backup_id(),
-        "backup-time": snapshot.backup_time(),
-    });
+    let args = snapshot_args(&snapshot);
 
     let output_format = get_output_format(&param);
 
@@ -353,12 +348,8 @@ async fn update_notes(param: Value) -> Result<Value, Error> {
 
     let path = format!("api2/json/admin/datastore/{}/notes", repo.store());
 
-    let args = json!({
-        "backup-type": snapshot.group().backup_type(),
-        "backup-id": snapshot.group().backup_id(),
-        "backup-time": snapshot.backup_time(),
-        "notes": notes,
-    });
+    let mut args = snapshot_args(&snapshot);
+    args["notes"] = notes.into();
 
     client.put(&path, Some(args)).await?;
 
@@ -381,9 +372,114 @@ fn notes_cli() -> CliCommandMap {
         )
 }
 
+#[api(
+    input: {
+        properties: {
+            repository: {
+                schema: REPO_URL_SCHEMA,
+                optional: true,
+            },
+            snapshot: {
+                type: String,
+                description: "Snapshot path.",
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show protection status of the specified snapshot
+async fn show_protection(param: Value) -> Result<(), Error> {
+    let repo = extract_repository_from_value(&param)?;
+    let path = tools::required_string_param(&param, "snapshot")?;
+
+    let snapshot: BackupDir = path.parse()?;
+    let client = connect(&repo)?;
+
+    let path = format!("api2/json/admin/datastore/{}/protected", repo.store());
+
+    let args = snapshot_args(&snapshot);
+
+    let output_format = get_output_format(&param);
+
+    let mut result = client.get(&path, Some(args)).await?;
+
+    let protected = result["data"].take();
+
+    if output_format == "text" {
+        if let Some(protected) = protected.as_bool() {
+            println!("{}", protected);
+        }
+    } else {
+        format_and_print_result(
+            &json!({
+                "protected": protected,
+            }),
+            &output_format,
+        );
+    }
+
+    Ok(())
+}
+
+#[api(
+    input: {
+        properties: {
+            repository: {
+                schema: REPO_URL_SCHEMA,
+                optional: true,
+            },
+            snapshot: {
+                type: String,
+                description: "Snapshot path.",
+            },
+            protected: {
+                type: bool,
+                description: "The protection status.",
+            },
+        }
+    }
+)]
+/// Update Protection Status of a snapshot
+async fn update_protection(protected: bool, param: Value) -> Result<(), Error> {
+    let repo = extract_repository_from_value(&param)?;
+    let path = tools::required_string_param(&param, "snapshot")?;
+
+    let snapshot: BackupDir = path.parse()?;
+    let mut client = connect(&repo)?;
+
+    let path = format!("api2/json/admin/datastore/{}/protected", repo.store());
+
+    let mut args = snapshot_args(&snapshot);
+    args["protected"] = protected.into();
+
+    client.put(&path, Some(args)).await?;
+
+    Ok(())
+}
+
+fn protected_cli() -> CliCommandMap {
+    CliCommandMap::new()
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_PROTECTION)
+                .arg_param(&["snapshot"])
+                .completion_cb("snapshot", complete_backup_snapshot),
+        )
+        .insert(
+            "update",
+            CliCommand::new(&API_METHOD_UPDATE_PROTECTION)
+                .arg_param(&["snapshot", "protected"])
+                .completion_cb("snapshot", complete_backup_snapshot),
+        )
+}
+
 pub fn snapshot_mgtm_cli() -> CliCommandMap {
     CliCommandMap::new()
         .insert("notes", notes_cli())
+        .insert("protected", protected_cli())
         .insert(
             "list",
             CliCommand::new(&API_METHOD_LIST_SNAPSHOTS)
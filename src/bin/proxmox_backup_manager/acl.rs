@@ -61,6 +61,7 @@ pub fn acl_commands() -> CommandLineInterface {
             CliCommand::new(&api2::access::acl::API_METHOD_UPDATE_ACL)
                 .arg_param(&["path", "role"])
                 .completion_cb("auth-id", config::user::complete_authid)
+                .completion_cb("group", config::user::complete_group_id)
                 .completion_cb("path", config::datastore::complete_acl_path)
 
         );
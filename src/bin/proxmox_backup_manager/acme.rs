@@ -0,0 +1,340 @@
+use anyhow::Error;
+use serde_json::{json, Value};
+
+use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
+
+use proxmox_backup::config;
+use proxmox_backup::config::acme::ACME_ACCOUNT_NAME_SCHEMA;
+use proxmox_backup::api2;
+use proxmox_backup::client::{connect_to_localhost, view_task_result};
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List ACME accounts.
+fn list_accounts(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::acme::API_METHOD_LIST_ACCOUNTS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show ACME account information.
+fn show_account(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::acme::API_METHOD_GET_ACCOUNT;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List DNS challenge plugins.
+fn list_plugins(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::acme::API_METHOD_LIST_PLUGINS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("id"))
+        .column(ColumnConfig::new("hook"))
+        .column(ColumnConfig::new("validation-delay"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: config::acme::ACME_PLUGIN_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show DNS challenge plugin configuration.
+fn show_plugin(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::acme::API_METHOD_READ_PLUGIN;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List the domains of the node certificate.
+fn list_domains(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::acme::API_METHOD_LIST_DOMAINS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("domain"))
+        .column(ColumnConfig::new("plugin"))
+        .column(ColumnConfig::new("alias"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            account: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Order a new certificate for the configured domains.
+async fn order_certificate(param: Value) -> Result<(), Error> {
+
+    let output_format = get_output_format(&param);
+
+    let mut client = connect_to_localhost()?;
+
+    let mut args = json!({});
+    if let Some(account) = param["account"].as_str() {
+        args["account"] = account.into();
+    }
+
+    let result = client.post("api2/json/nodes/localhost/certificates/acme/certificate", Some(args)).await?;
+
+    view_task_result(&mut client, result, &output_format).await
+}
+
+#[api(
+    input: {
+        properties: {
+            account: {
+                schema: ACME_ACCOUNT_NAME_SCHEMA,
+                optional: true,
+            },
+            force: {
+                description: "Renew the certificate even if it does not expire soon.",
+                type: bool,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Renew the certificate if it expires within 30 days.
+async fn renew_certificate(param: Value) -> Result<(), Error> {
+
+    let output_format = get_output_format(&param);
+
+    let mut client = connect_to_localhost()?;
+
+    let mut args = json!({});
+    if let Some(account) = param["account"].as_str() {
+        args["account"] = account.into();
+    }
+    if let Some(force) = param["force"].as_bool() {
+        args["force"] = force.into();
+    }
+
+    let result = client.put("api2/json/nodes/localhost/certificates/acme/certificate", Some(args)).await?;
+
+    view_task_result(&mut client, result, &output_format).await
+}
+
+fn account_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_ACCOUNTS))
+        .insert(
+            "info",
+            CliCommand::new(&API_METHOD_SHOW_ACCOUNT)
+                .arg_param(&["name"])
+                .completion_cb("name", config::acme::complete_acme_account)
+        )
+        .insert(
+            "register",
+            CliCommand::new(&api2::config::acme::API_METHOD_REGISTER_ACCOUNT)
+                .arg_param(&["name"])
+        )
+        .insert(
+            "deactivate",
+            CliCommand::new(&api2::config::acme::API_METHOD_DEACTIVATE_ACCOUNT)
+                .arg_param(&["name"])
+                .completion_cb("name", config::acme::complete_acme_account)
+        );
+
+    cmd_def.into()
+}
+
+fn plugin_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_PLUGINS))
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_PLUGIN)
+                .arg_param(&["id"])
+                .completion_cb("id", config::acme::complete_acme_plugin)
+        )
+        .insert(
+            "add",
+            CliCommand::new(&api2::config::acme::API_METHOD_ADD_PLUGIN)
+                .arg_param(&["id"])
+        )
+        .insert(
+            "set",
+            CliCommand::new(&api2::config::acme::API_METHOD_UPDATE_PLUGIN)
+                .arg_param(&["id"])
+                .completion_cb("id", config::acme::complete_acme_plugin)
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::acme::API_METHOD_DELETE_PLUGIN)
+                .arg_param(&["id"])
+                .completion_cb("id", config::acme::complete_acme_plugin)
+        );
+
+    cmd_def.into()
+}
+
+fn domain_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_DOMAINS))
+        .insert(
+            "add",
+            CliCommand::new(&api2::config::acme::API_METHOD_ADD_DOMAIN)
+                .arg_param(&["domain"])
+                .completion_cb("plugin", config::acme::complete_acme_plugin)
+        )
+        .insert(
+            "set",
+            CliCommand::new(&api2::config::acme::API_METHOD_UPDATE_DOMAIN)
+                .arg_param(&["domain"])
+                .completion_cb("domain", config::acme::complete_acme_domain)
+                .completion_cb("plugin", config::acme::complete_acme_plugin)
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::acme::API_METHOD_DELETE_DOMAIN)
+                .arg_param(&["domain"])
+                .completion_cb("domain", config::acme::complete_acme_domain)
+        );
+
+    cmd_def.into()
+}
+
+fn cert_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert(
+            "order",
+            CliCommand::new(&API_METHOD_ORDER_CERTIFICATE)
+                .completion_cb("account", config::acme::complete_acme_account)
+        )
+        .insert(
+            "renew",
+            CliCommand::new(&API_METHOD_RENEW_CERTIFICATE)
+                .completion_cb("account", config::acme::complete_acme_account)
+        );
+
+    cmd_def.into()
+}
+
+pub fn acme_mgmt_cli() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("account", account_commands())
+        .insert("plugin", plugin_commands())
+        .insert("domain", domain_commands())
+        .insert("cert", cert_commands());
+
+    cmd_def.into()
+}
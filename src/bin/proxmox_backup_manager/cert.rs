@@ -1,7 +1,9 @@
 use anyhow::{bail, Error};
 
 use proxmox::api::{api, cli::*};
+use proxmox::tools::fs::file_get_contents;
 
+use proxmox_backup::api2;
 use proxmox_backup::config;
 use proxmox_backup::auth_helpers::*;
 use proxmox_backup::tools::cert::CertInfo;
@@ -71,11 +73,49 @@ fn update_certs(force: Option<bool>) -> Result<(), Error> {
     Ok(())
 }
 
+#[api(
+    input: {
+        properties: {
+            certificates: {
+                description: "Path to the PEM encoded certificate, optionally followed by its chain.",
+                type: String,
+            },
+            key: {
+                description: "Path to the PEM encoded private key. The current key is kept if not given.",
+                type: String,
+                optional: true,
+            },
+        }
+    },
+)]
+/// Install a custom node certificate and reload the proxy.
+async fn upload_cert(certificates: String, key: Option<String>) -> Result<(), Error> {
+
+    let certificates = String::from_utf8(file_get_contents(&certificates)?)?;
+    let key = match key {
+        Some(key) => Some(String::from_utf8(file_get_contents(&key)?)?),
+        None => None,
+    };
+
+    let info = api2::node::certificates::upload_custom_cert(certificates, key).await?;
+
+    println!("installed certificate with fingerprint {}", info.fingerprint);
+
+    Ok(())
+}
+
 pub fn cert_mgmt_cli() -> CommandLineInterface {
 
     let cmd_def = CliCommandMap::new()
         .insert("info", CliCommand::new(&API_METHOD_CERT_INFO))
-        .insert("update", CliCommand::new(&API_METHOD_UPDATE_CERTS));
+        .insert("update", CliCommand::new(&API_METHOD_UPDATE_CERTS))
+        .insert(
+            "upload",
+            CliCommand::new(&API_METHOD_UPLOAD_CERT)
+                .arg_param(&["certificates"])
+                .completion_cb("certificates", proxmox_backup::tools::complete_file_name)
+                .completion_cb("key", proxmox_backup::tools::complete_file_name)
+        );
 
     cmd_def.into()
 }
@@ -4,7 +4,10 @@ use serde_json::Value;
 use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
 
 use proxmox_backup::config;
+use proxmox_backup::config::datastore::DataStoreConfig;
 use proxmox_backup::api2::{self, types::* };
+use proxmox_backup::client::{connect_to_localhost, view_task_result};
+use proxmox_backup::tools;
 
 #[api(
     input: {
@@ -67,10 +70,248 @@ fn show_datastore(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value
     Ok(Value::Null)
 }
 
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show the number of active read and write operations of a datastore
+fn active_operations(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::admin::datastore::API_METHOD_GET_ACTIVE_OPERATIONS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+async fn mount_datastore(store: &str, output_format: &str) -> Result<(), Error> {
+    let mut client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{}/mount", store);
+
+    let result = client.post(&path, None).await?;
+
+    view_task_result(&mut client, result, output_format).await
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Mount the removable device of a datastore
+async fn mount(param: Value) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let store = tools::required_string_param(&param, "store")?;
+
+    mount_datastore(store, &output_format).await?;
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            uuid: {
+                schema: BACKING_DEVICE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Mount the datastore located on the device with file system UUID `uuid`, if there is one.
+///
+/// This gets called when a block device is attached.
+async fn uuid_mount(param: Value) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let uuid = tools::required_string_param(&param, "uuid")?;
+
+    let (config, _digest) = config::datastore::config()?;
+    let list: Vec<DataStoreConfig> = config.convert_to_typed_array("datastore")?;
+
+    let store = match list.into_iter().find(|store| store.backing_device.as_deref() == Some(uuid)) {
+        Some(store) => store,
+        None => return Ok(Value::Null), // not a datastore device
+    };
+
+    if store.is_mounted() {
+        return Ok(Value::Null);
+    }
+
+    mount_datastore(&store.name, &output_format).await?;
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Unmount the removable device of a datastore, once all running operations finished
+async fn unmount(param: Value) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let store = tools::required_string_param(&param, "store")?;
+
+    let mut client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{}/unmount", store);
+
+    let result = client.post(&path, None).await?;
+
+    view_task_result(&mut client, result, &output_format).await?;
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "max-samples": {
+                description: "Maximum number of chunks to use as training samples.",
+                type: Integer,
+                minimum: 10,
+                maximum: 100_000,
+                default: 1000,
+                optional: true,
+            },
+            size: {
+                description: "Maximum size of the dictionary in bytes.",
+                type: Integer,
+                minimum: 4096,
+                maximum: 1024*1024,
+                default: 112_640,
+                optional: true,
+            },
+            activate: {
+                description: "Use the new dictionary for new chunks of this datastore.",
+                type: bool,
+                default: false,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Train a zstd dictionary from the chunks of a datastore
+async fn train_dictionary(mut param: Value) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let store = tools::required_string_param(&param, "store")?.to_owned();
+    let param_obj = param.as_object_mut().unwrap();
+    param_obj.remove("store");
+    param_obj.remove("output-format");
+
+    let mut client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{}/train-dictionary", store);
+
+    let result = client.post(&path, Some(param)).await?;
+
+    view_task_result(&mut client, result, &output_format).await?;
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            store: {
+                schema: DATASTORE_SCHEMA,
+            },
+            "target-path": {
+                schema: config::datastore::DIR_NAME_SCHEMA,
+                optional: true,
+            },
+            "target-store": {
+                schema: DATASTORE_SCHEMA,
+                optional: true,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Copy a datastore to a new path (and switch to it), or into another datastore
+async fn migrate_datastore(mut param: Value) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let store = tools::required_string_param(&param, "store")?.to_owned();
+    let param_obj = param.as_object_mut().unwrap();
+    param_obj.remove("store");
+    param_obj.remove("output-format");
+
+    let mut client = connect_to_localhost()?;
+
+    let path = format!("api2/json/admin/datastore/{}/migrate", store);
+
+    let result = client.post(&path, Some(param)).await?;
+
+    view_task_result(&mut client, result, &output_format).await?;
+
+    Ok(Value::Null)
+}
+
 pub fn datastore_commands() -> CommandLineInterface {
 
     let cmd_def = CliCommandMap::new()
         .insert("list", CliCommand::new(&API_METHOD_LIST_DATASTORES))
+        .insert("active-operations",
+                CliCommand::new(&API_METHOD_ACTIVE_OPERATIONS)
+                .arg_param(&["store"])
+                .completion_cb("store", config::datastore::complete_datastore_name)
+        )
         .insert("show",
                 CliCommand::new(&API_METHOD_SHOW_DATASTORE)
                 .arg_param(&["name"])
@@ -80,6 +321,31 @@ pub fn datastore_commands() -> CommandLineInterface {
                 CliCommand::new(&api2::config::datastore::API_METHOD_CREATE_DATASTORE)
                 .arg_param(&["name", "path"])
         )
+        .insert("migrate",
+                CliCommand::new(&API_METHOD_MIGRATE_DATASTORE)
+                .arg_param(&["store"])
+                .completion_cb("store", config::datastore::complete_datastore_name)
+                .completion_cb("target-store", config::datastore::complete_datastore_name)
+        )
+        .insert("mount",
+                CliCommand::new(&API_METHOD_MOUNT)
+                .arg_param(&["store"])
+                .completion_cb("store", config::datastore::complete_datastore_name)
+        )
+        .insert("unmount",
+                CliCommand::new(&API_METHOD_UNMOUNT)
+                .arg_param(&["store"])
+                .completion_cb("store", config::datastore::complete_datastore_name)
+        )
+        .insert("train-dictionary",
+                CliCommand::new(&API_METHOD_TRAIN_DICTIONARY)
+                .arg_param(&["store"])
+                .completion_cb("store", config::datastore::complete_datastore_name)
+        )
+        .insert("uuid-mount",
+                CliCommand::new(&API_METHOD_UUID_MOUNT)
+                .arg_param(&["uuid"])
+        )
         .insert("update",
                 CliCommand::new(&api2::config::datastore::API_METHOD_UPDATE_DATASTORE)
                 .arg_param(&["name"])
@@ -0,0 +1,99 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
+
+use proxmox_backup::config;
+use proxmox_backup::api2::{self, types::*};
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List configured user groups.
+fn list_groups(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::access::group::API_METHOD_LIST_GROUPS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("groupid"))
+        .column(ColumnConfig::new("members"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            groupid: {
+                schema: PROXMOX_GROUP_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show user group configuration
+fn show_group(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::access::group::API_METHOD_READ_GROUP;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+pub fn group_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_GROUPS))
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_GROUP)
+                .arg_param(&["groupid"])
+                .completion_cb("groupid", config::user::complete_group_id)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::access::group::API_METHOD_CREATE_GROUP)
+                .arg_param(&["groupid"])
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::access::group::API_METHOD_UPDATE_GROUP)
+                .arg_param(&["groupid"])
+                .completion_cb("groupid", config::user::complete_group_id)
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::access::group::API_METHOD_DELETE_GROUP)
+                .arg_param(&["groupid"])
+                .completion_cb("groupid", config::user::complete_group_id)
+        );
+
+    cmd_def.into()
+}
@@ -0,0 +1,166 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
+
+use proxmox_backup::config;
+use proxmox_backup::config::metrics::METRIC_SERVER_ID_SCHEMA;
+use proxmox_backup::api2;
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List configured metric servers.
+fn list_metric_servers(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::metrics::API_METHOD_LIST_METRIC_SERVERS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("name"))
+        .column(ColumnConfig::new("type"))
+        .column(ColumnConfig::new("enable"))
+        .column(ColumnConfig::new("server"))
+        .column(ColumnConfig::new("port"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show InfluxDB server configuration.
+fn show_influxdb_server(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::metrics::API_METHOD_READ_INFLUXDB_SERVER;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: METRIC_SERVER_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show Graphite server configuration.
+fn show_graphite_server(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::metrics::API_METHOD_READ_GRAPHITE_SERVER;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+fn influxdb_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_INFLUXDB_SERVER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::metrics::complete_metric_server_id)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::metrics::API_METHOD_CREATE_INFLUXDB_SERVER)
+                .arg_param(&["name"])
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::metrics::API_METHOD_UPDATE_INFLUXDB_SERVER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::metrics::complete_metric_server_id)
+        );
+
+    cmd_def.into()
+}
+
+fn graphite_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_GRAPHITE_SERVER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::metrics::complete_metric_server_id)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::metrics::API_METHOD_CREATE_GRAPHITE_SERVER)
+                .arg_param(&["name"])
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::metrics::API_METHOD_UPDATE_GRAPHITE_SERVER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::metrics::complete_metric_server_id)
+        );
+
+    cmd_def.into()
+}
+
+pub fn metric_server_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_METRIC_SERVERS))
+        .insert("influxdb", influxdb_commands())
+        .insert("graphite", graphite_commands())
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::metrics::API_METHOD_DELETE_METRIC_SERVER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::metrics::complete_metric_server_id)
+        );
+
+    cmd_def.into()
+}
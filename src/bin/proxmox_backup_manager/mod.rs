@@ -1,11 +1,17 @@
 mod acl;
 pub use acl::*;
+mod acme;
+pub use acme::*;
 mod cert;
 pub use cert::*;
 mod datastore;
 pub use datastore::*;
 mod dns;
 pub use dns::*;
+mod metrics;
+pub use metrics::*;
+mod notifications;
+pub use notifications::*;
 mod network;
 pub use network::*;
 mod remote;
@@ -14,9 +20,23 @@ mod sync;
 pub use sync::*;
 mod verify;
 pub use verify::*;
+mod prune;
+pub use prune::*;
+mod quota;
+pub use quota::*;
 mod user;
 pub use user::*;
+mod group;
+pub use group::*;
 mod subscription;
 pub use subscription::*;
 mod disk;
 pub use disk::*;
+mod traffic_control;
+pub use traffic_control::*;
+mod s3;
+pub use s3::*;
+mod ldap;
+pub use ldap::*;
+mod openid;
+pub use openid::*;
@@ -0,0 +1,324 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
+
+use proxmox_backup::config;
+use proxmox_backup::config::notifications::NOTIFICATION_NAME_SCHEMA;
+use proxmox_backup::api2;
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List configured notification targets.
+fn list_targets(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_LIST_TARGETS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("name"))
+        .column(ColumnConfig::new("type"))
+        .column(ColumnConfig::new("disable"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show SMTP target configuration.
+fn show_smtp_target(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_READ_SMTP_TARGET;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show webhook target configuration.
+fn show_webhook_target(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_READ_WEBHOOK_TARGET;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show Gotify target configuration.
+fn show_gotify_target(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_READ_GOTIFY_TARGET;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List configured notification matchers.
+fn list_matchers(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_LIST_MATCHERS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("name"))
+        .column(ColumnConfig::new("match-type"))
+        .column(ColumnConfig::new("match-severity"))
+        .column(ColumnConfig::new("match-datastore"))
+        .column(ColumnConfig::new("target"))
+        .column(ColumnConfig::new("disable"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            name: {
+                schema: NOTIFICATION_NAME_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show notification matcher configuration.
+fn show_matcher(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::notifications::API_METHOD_READ_MATCHER;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+fn smtp_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_SMTP_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::notifications::API_METHOD_CREATE_SMTP_TARGET)
+                .arg_param(&["name"])
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::notifications::API_METHOD_UPDATE_SMTP_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        );
+
+    cmd_def.into()
+}
+
+fn webhook_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_WEBHOOK_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::notifications::API_METHOD_CREATE_WEBHOOK_TARGET)
+                .arg_param(&["name"])
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::notifications::API_METHOD_UPDATE_WEBHOOK_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        );
+
+    cmd_def.into()
+}
+
+fn gotify_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_GOTIFY_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::notifications::API_METHOD_CREATE_GOTIFY_TARGET)
+                .arg_param(&["name"])
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::notifications::API_METHOD_UPDATE_GOTIFY_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        );
+
+    cmd_def.into()
+}
+
+fn matcher_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_MATCHERS))
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_matcher)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::notifications::API_METHOD_CREATE_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("target", config::notifications::complete_notification_target)
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::notifications::API_METHOD_UPDATE_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_matcher)
+                .completion_cb("target", config::notifications::complete_notification_target)
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::notifications::API_METHOD_DELETE_MATCHER)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_matcher)
+        );
+
+    cmd_def.into()
+}
+
+pub fn notification_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_TARGETS))
+        .insert("smtp", smtp_commands())
+        .insert("webhook", webhook_commands())
+        .insert("gotify", gotify_commands())
+        .insert("matcher", matcher_commands())
+        .insert(
+            "test",
+            CliCommand::new(&api2::config::notifications::API_METHOD_TEST_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::notifications::API_METHOD_DELETE_TARGET)
+                .arg_param(&["name"])
+                .completion_cb("name", config::notifications::complete_notification_target)
+        );
+
+    cmd_def.into()
+}
@@ -0,0 +1,108 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
+
+use proxmox_backup::config;
+use proxmox_backup::api2::{self, types::* };
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List all prune jobs
+fn list_prune_jobs(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::prune::API_METHOD_LIST_PRUNE_JOBS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("id"))
+        .column(ColumnConfig::new("store"))
+        .column(ColumnConfig::new("ns"))
+        .column(ColumnConfig::new("schedule"))
+        .column(ColumnConfig::new("keep-last"))
+        .column(ColumnConfig::new("keep-hourly"))
+        .column(ColumnConfig::new("keep-daily"))
+        .column(ColumnConfig::new("keep-weekly"))
+        .column(ColumnConfig::new("keep-monthly"))
+        .column(ColumnConfig::new("keep-yearly"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: JOB_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show prune job configuration
+fn show_prune_job(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::prune::API_METHOD_READ_PRUNE_JOB;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+pub fn prune_job_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_PRUNE_JOBS))
+        .insert("show",
+                CliCommand::new(&API_METHOD_SHOW_PRUNE_JOB)
+                .arg_param(&["id"])
+                .completion_cb("id", config::prune::complete_prune_job_id)
+        )
+        .insert("create",
+                CliCommand::new(&api2::config::prune::API_METHOD_CREATE_PRUNE_JOB)
+                .arg_param(&["id"])
+                .completion_cb("id", config::prune::complete_prune_job_id)
+                .completion_cb("schedule", config::datastore::complete_calendar_event)
+                .completion_cb("store", config::datastore::complete_datastore_name)
+        )
+        .insert("update",
+                CliCommand::new(&api2::config::prune::API_METHOD_UPDATE_PRUNE_JOB)
+                .arg_param(&["id"])
+                .completion_cb("id", config::prune::complete_prune_job_id)
+                .completion_cb("schedule", config::datastore::complete_calendar_event)
+                .completion_cb("store", config::datastore::complete_datastore_name)
+        )
+        .insert("remove",
+                CliCommand::new(&api2::config::prune::API_METHOD_DELETE_PRUNE_JOB)
+                .arg_param(&["id"])
+                .completion_cb("id", config::prune::complete_prune_job_id)
+        );
+
+    cmd_def.into()
+}
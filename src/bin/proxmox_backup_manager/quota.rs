@@ -0,0 +1,107 @@
+use anyhow::Error;
+use serde_json::Value;
+
+use proxmox::api::{api, cli::*, RpcEnvironment, ApiHandler};
+
+use proxmox_backup::config;
+use proxmox_backup::api2::{self, types::* };
+
+#[api(
+    input: {
+        properties: {
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// List configured datastore quotas.
+fn list_quotas(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::quota::API_METHOD_LIST_QUOTAS;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options()
+        .column(ColumnConfig::new("id"))
+        .column(ColumnConfig::new("store"))
+        .column(ColumnConfig::new("subject"))
+        .column(ColumnConfig::new("soft-limit"))
+        .column(ColumnConfig::new("hard-limit"))
+        .column(ColumnConfig::new("grace-period"))
+        .column(ColumnConfig::new("comment"));
+
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+#[api(
+    input: {
+        properties: {
+            id: {
+                schema: config::quota::QUOTA_ID_SCHEMA,
+            },
+            "output-format": {
+                schema: OUTPUT_FORMAT,
+                optional: true,
+            },
+        }
+    }
+)]
+/// Show datastore quota configuration
+fn show_quota(param: Value, rpcenv: &mut dyn RpcEnvironment) -> Result<Value, Error> {
+
+    let output_format = get_output_format(&param);
+
+    let info = &api2::config::quota::API_METHOD_READ_QUOTA;
+    let mut data = match info.handler {
+        ApiHandler::Sync(handler) => (handler)(param, info, rpcenv)?,
+        _ => unreachable!(),
+    };
+
+    let options = default_table_format_options();
+    format_and_print_result_full(&mut data, &info.returns, &output_format, &options);
+
+    Ok(Value::Null)
+}
+
+pub fn quota_commands() -> CommandLineInterface {
+
+    let cmd_def = CliCommandMap::new()
+        .insert("list", CliCommand::new(&API_METHOD_LIST_QUOTAS))
+        .insert(
+            "show",
+            CliCommand::new(&API_METHOD_SHOW_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("id", config::quota::complete_quota_id)
+        )
+        .insert(
+            "create",
+            CliCommand::new(&api2::config::quota::API_METHOD_CREATE_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("store", config::datastore::complete_datastore_name)
+                .completion_cb("subject", config::quota::complete_quota_subject)
+        )
+        .insert(
+            "update",
+            CliCommand::new(&api2::config::quota::API_METHOD_UPDATE_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("id", config::quota::complete_quota_id)
+                .completion_cb("store", config::datastore::complete_datastore_name)
+                .completion_cb("subject", config::quota::complete_quota_subject)
+        )
+        .insert(
+            "remove",
+            CliCommand::new(&api2::config::quota::API_METHOD_DELETE_QUOTA)
+                .arg_param(&["id"])
+                .completion_cb("id", config::quota::complete_quota_id)
+        );
+
+    cmd_def.into()
+}
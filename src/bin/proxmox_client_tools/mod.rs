@@ -8,6 +8,7 @@ use xdg::BaseDirectories;
 
 use proxmox::{
     api::schema::*,
+    sys::linux::tty,
     tools::fs::file_get_json,
 };
 
@@ -49,6 +50,26 @@ pub const CHUNK_SIZE_SCHEMA: Schema = IntegerSchema::new("Chunk size in KB. Must
     .default(4096)
     .schema();
 
+pub fn get_encryption_key_password() -> Result<Vec<u8>, Error> {
+    // fixme: implement other input methods
+
+    use std::env::VarError::*;
+    match std::env::var("PBS_ENCRYPTION_PASSWORD") {
+        Ok(p) => return Ok(p.as_bytes().to_vec()),
+        Err(NotUnicode(_)) => bail!("PBS_ENCRYPTION_PASSWORD contains bad characters"),
+        Err(NotPresent) => {
+            // Try another method
+        }
+    }
+
+    // If we're on a TTY, query the user for a password
+    if tty::stdin_isatty() {
+        return Ok(tty::read_password("Encryption Key Password: ")?);
+    }
+
+    bail!("no password input mechanism available");
+}
+
 pub fn get_default_repository() -> Option<String> {
     std::env::var("PBS_REPOSITORY").ok()
 }
@@ -74,11 +95,22 @@ pub fn extract_repository_from_map(param: &HashMap<String, String>) -> Option<Ba
 }
 
 pub fn connect(repo: &BackupRepository) -> Result<HttpClient, Error> {
-    connect_do(repo.host(), repo.port(), repo.auth_id())
+    connect_do(repo.host(), repo.port(), repo.auth_id(), RateLimitConfig::default())
+        .map_err(|err| format_err!("error building client for repository {} - {}", repo, err))
+}
+
+/// Like [connect], but limit the traffic (in both directions) to `rate` bytes/second.
+pub fn connect_rate_limited(
+    repo: &BackupRepository,
+    rate: Option<u64>,
+    burst: Option<u64>,
+) -> Result<HttpClient, Error> {
+    let limit = RateLimitConfig::with_same_inout(rate, burst);
+    connect_do(repo.host(), repo.port(), repo.auth_id(), limit)
         .map_err(|err| format_err!("error building client for repository {} - {}", repo, err))
 }
 
-fn connect_do(server: &str, port: u16, auth_id: &Authid) -> Result<HttpClient, Error> {
+fn connect_do(server: &str, port: u16, auth_id: &Authid, limit: RateLimitConfig) -> Result<HttpClient, Error> {
     let fingerprint = std::env::var(ENV_VAR_PBS_FINGERPRINT).ok();
 
     use std::env::VarError::*;
@@ -88,7 +120,8 @@ fn connect_do(server: &str, port: u16, auth_id: &Authid) -> Result<HttpClient, E
         Err(NotPresent) => None,
     };
 
-    let options = HttpClientOptions::new_interactive(password, fingerprint);
+    let options = HttpClientOptions::new_interactive(password, fingerprint)
+        .rate_limit(limit);
 
     HttpClient::new(server, port, auth_id, options)
 }
@@ -151,6 +184,19 @@ pub async fn complete_backup_group_do(param: &HashMap<String, String>) -> Vec<St
     result
 }
 
+/// API parameters identifying `snapshot`, including its namespace if any.
+pub fn snapshot_args(snapshot: &BackupDir) -> Value {
+    let mut args = json!({
+        "backup-type": snapshot.group().backup_type(),
+        "backup-id": snapshot.group().backup_id(),
+        "backup-time": snapshot.backup_time(),
+    });
+    if !snapshot.group().ns().is_root() {
+        args["ns"] = snapshot.group().ns().to_string().into();
+    }
+    args
+}
+
 pub fn complete_group_or_snapshot(arg: &str, param: &HashMap<String, String>) -> Vec<String> {
     proxmox_backup::tools::runtime::main(async { complete_group_or_snapshot_do(arg, param).await })
 }
@@ -225,11 +271,7 @@ pub async fn complete_server_file_name_do(param: &HashMap<String, String>) -> Ve
         _ => return result,
     };
 
-    let query = tools::json_object_to_query(json!({
-        "backup-type": snapshot.group().backup_type(),
-        "backup-id": snapshot.group().backup_id(),
-        "backup-time": snapshot.backup_time(),
-    })).unwrap();
+    let query = tools::json_object_to_query(snapshot_args(&snapshot)).unwrap();
 
     let path = format!("api2/json/admin/datastore/{}/files?{}", repo.store(), query);
 
@@ -0,0 +1,237 @@
+//! Block file access via a small QEMU restore VM using the PBS block driver in QEMU
+use anyhow::{bail, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{prelude::*, SeekFrom};
+
+use proxmox::tools::fs::lock_file;
+
+use proxmox_backup::api2::types::ArchiveEntry;
+use proxmox_backup::backup::{BackupDir, BackupManifest};
+use proxmox_backup::buildcfg;
+use proxmox_backup::client::*;
+use proxmox_backup::tools;
+
+use super::qemu_helper;
+
+/// Contains the PIDs and CIDs of all running restore VMs, keyed by repository and snapshot
+const RESTORE_VM_MAP: &str =
+    concat!(proxmox_backup::PROXMOX_BACKUP_RUN_DIR_M!(), "/restore-vm-map.json");
+
+/// Everything needed to start a restore VM for a snapshot
+pub struct SnapRestoreDetails {
+    pub repo: BackupRepository,
+    pub snapshot: BackupDir,
+    pub manifest: BackupManifest,
+    pub keyfile: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VMState {
+    pid: i32,
+    cid: i32,
+}
+
+struct VMStateMap {
+    map: HashMap<String, VMState>,
+    file: File,
+}
+
+impl VMStateMap {
+    fn open_file_raw(write: bool) -> Result<File, Error> {
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut options = OpenOptions::new();
+        options.read(true);
+        if write {
+            options.write(true).create(true).mode(0o600);
+        }
+        Ok(options.open(RESTORE_VM_MAP)?)
+    }
+
+    /// Acquire a lock on the state map and retrieve a deserialized version
+    fn load() -> Result<Self, Error> {
+        let mut file = Self::open_file_raw(true)?;
+        lock_file(&mut file, true, Some(std::time::Duration::from_secs(120)))?;
+        let map = serde_json::from_reader(&file).unwrap_or_default();
+        Ok(Self { map, file })
+    }
+
+    /// Load a read-only copy of the current VM map. Only use for informational purposes, like
+    /// shell auto-completion, for anything requiring consistency use load()!
+    fn load_read_only() -> Result<HashMap<String, VMState>, Error> {
+        let file = Self::open_file_raw(false)?;
+        Ok(serde_json::from_reader(&file).unwrap_or_default())
+    }
+
+    /// Write back a potentially modified state map, consuming the held lock
+    fn write(mut self) -> Result<(), Error> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.set_len(0)?;
+        serde_json::to_writer(self.file, &self.map)?;
+
+        // drop ourselves including file lock
+        Ok(())
+    }
+}
+
+fn make_name(repo: &BackupRepository, snapshot: &BackupDir) -> String {
+    let full = format!("qemu_{}/{}", repo, snapshot);
+    tools::systemd::escape_unit(&full, false)
+}
+
+/// Remove non-responsive VMs from given map, returns 'true' if map was modified
+async fn cleanup_map(map: &mut HashMap<String, VMState>) -> bool {
+    let mut to_remove = Vec::new();
+    for (name, state) in map.iter() {
+        let client = VsockClient::new(state.cid, DEFAULT_VSOCK_PORT);
+        let res = client
+            .get("api2/json/status", Some(json!({ "keep-timeout": true })))
+            .await;
+        if res.is_err() {
+            // VM is not reachable, remove from map and inform user
+            to_remove.push(name.clone());
+            eprintln!(
+                "VM '{}' (pid: {}, cid: {}) was not reachable, removing from map",
+                name, state.pid, state.cid
+            );
+            let _ = qemu_helper::try_kill_vm(state.pid);
+        }
+    }
+
+    for name in &to_remove {
+        map.remove(name);
+    }
+
+    !to_remove.is_empty()
+}
+
+/// Returns a client for the restore VM of the given snapshot, starting a new VM if none is
+/// running yet
+async fn ensure_running(details: &SnapRestoreDetails) -> Result<VsockClient, Error> {
+    let name = make_name(&details.repo, &details.snapshot);
+    let mut state = VMStateMap::load()?;
+
+    let modified = cleanup_map(&mut state.map).await;
+
+    if let Some(cid) = state.map.get(&name).map(|vm| vm.cid) {
+        if modified {
+            state.write()?;
+        }
+        let client = VsockClient::new(cid, DEFAULT_VSOCK_PORT);
+        // reset the shutdown timer of the VM
+        client.get("api2/json/status", None).await?;
+        return Ok(client);
+    }
+
+    let start_cid = state
+        .map
+        .values()
+        .map(|vm| vm.cid)
+        .max()
+        .unwrap_or(0)
+        .wrapping_add(1)
+        .max(10);
+
+    let (pid, cid) = qemu_helper::start_vm(start_cid as u16, details).await?;
+    state.map.insert(name, VMState { pid, cid });
+    state.write()?;
+
+    Ok(VsockClient::new(cid, DEFAULT_VSOCK_PORT))
+}
+
+/// List the entries of a path within an image archive, the path has to start with the archive
+/// name, e.g. "/drive-scsi0.img.fidx/part/1/etc"
+pub async fn data_list(
+    details: SnapRestoreDetails,
+    path: Vec<u8>,
+) -> Result<Vec<ArchiveEntry>, Error> {
+    let client = ensure_running(&details).await?;
+
+    let path = base64::encode(path);
+    let mut result = client
+        .get("api2/json/list", Some(json!({ "path": path })))
+        .await?;
+
+    Ok(serde_json::from_value(result["data"].take())?)
+}
+
+/// Extract a file (or a directory as zip archive) from an image archive to the given output
+pub async fn data_extract(
+    details: SnapRestoreDetails,
+    path: Vec<u8>,
+    output: &mut (dyn tokio::io::AsyncWrite + Send + Unpin),
+) -> Result<(), Error> {
+    let mut client = ensure_running(&details).await?;
+
+    let path = base64::encode(path);
+    client
+        .download("api2/json/extract", Some(json!({ "path": path })), output)
+        .await
+}
+
+/// Returns the status of all running restore VMs
+pub async fn status() -> Result<Vec<Value>, Error> {
+    let mut state = VMStateMap::load()?;
+    let modified = cleanup_map(&mut state.map).await;
+
+    let mut list = Vec::new();
+    for (name, vm) in state.map.iter() {
+        let client = VsockClient::new(vm.cid, DEFAULT_VSOCK_PORT);
+        let mut status = client
+            .get("api2/json/status", Some(json!({ "keep-timeout": true })))
+            .await?;
+        list.push(json!({
+            "id": name,
+            "pid": vm.pid,
+            "cid": vm.cid,
+            "uptime": status["data"]["uptime"].take(),
+            "timeout": status["data"]["timeout"].take(),
+        }));
+    }
+
+    if modified {
+        state.write()?;
+    }
+
+    Ok(list)
+}
+
+/// Stop the restore VM with the given id
+pub async fn stop(id: String) -> Result<(), Error> {
+    let mut state = VMStateMap::load()?;
+
+    let vm = match state.map.remove(&id) {
+        Some(vm) => vm,
+        None => bail!("VM with name '{}' not found", id),
+    };
+
+    // try a graceful shutdown first, the VM will never answer if it succeeds
+    let client = VsockClient::new(vm.cid, DEFAULT_VSOCK_PORT);
+    let _ = tokio::time::timeout(
+        std::time::Duration::from_secs(2),
+        client.get("api2/json/stop", None),
+    )
+    .await;
+
+    // make sure it's gone
+    qemu_helper::try_kill_vm(vm.pid)?;
+
+    state.write()
+}
+
+/// Returns the names of all (known) running restore VMs, for shell completion
+pub fn list_vms() -> Vec<String> {
+    match VMStateMap::load_read_only() {
+        Ok(state) => state.keys().cloned().collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Make sure the directory for the VM map exists
+pub fn create_run_dir() -> Result<(), Error> {
+    proxmox::tools::fs::create_path(buildcfg::PROXMOX_BACKUP_RUN_DIR, None, None)?;
+    Ok(())
+}
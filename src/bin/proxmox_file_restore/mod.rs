@@ -0,0 +1,5 @@
+//! Block device drivers and tools for single file restore
+mod block_driver_qemu;
+pub use block_driver_qemu::*;
+
+mod qemu_helper;
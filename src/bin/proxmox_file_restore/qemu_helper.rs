@@ -0,0 +1,315 @@
+//! Helper to start a QEMU VM for single file restore.
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use serde_json::json;
+use tokio::time;
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+
+use proxmox::tools::fs::{create_path, file_read_firstline, make_tmp_file, CreateOptions};
+
+use proxmox_backup::buildcfg;
+use proxmox_backup::client::{VsockClient, DEFAULT_VSOCK_PORT};
+
+use super::SnapRestoreDetails;
+
+const PBS_VM_NAME: &str = "pbs-restore-vm";
+const MAX_CID_TRIES: u64 = 32;
+
+/// The virtio-blk serial number is limited to 20 characters
+const MAX_SERIAL_LEN: usize = 20;
+
+/// Try to stop the VM with the given PID, ignoring the case that it is not running anymore.
+pub fn try_kill_vm(pid: i32) -> Result<(), Error> {
+    let pid = Pid::from_raw(pid);
+    if let Ok(()) = kill(pid, None) {
+        // process is running (and we could kill it), check if it is actually ours
+        // (if it errors assume we raced with the process's death and ignore it)
+        if let Ok(cmdline) = file_read_firstline(format!("/proc/{}/cmdline", pid)) {
+            if cmdline.split('\0').any(|a| a == PBS_VM_NAME) {
+                // yes, it's ours, kill it brutally with SIGKILL, no reason to take
+                // any chances - in this state it's most likely broken anyway
+                if let Err(err) = kill(pid, Signal::SIGKILL) {
+                    bail!(
+                        "reaping broken VM (pid {}) with SIGKILL failed: {}",
+                        pid,
+                        err
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_cpio_padding<W: Write>(target: &mut W, len: usize) -> Result<(), Error> {
+    let pad = (4 - (len % 4)) % 4;
+    target.write_all(&[0u8; 3][..pad])?;
+    Ok(())
+}
+
+/// Append a single entry to a cpio archive in 'newc' format, as understood by the kernel's
+/// initramfs unpacker.
+fn write_cpio_entry<W: Write>(
+    target: &mut W,
+    ino: u32,
+    name: &str,
+    mode: u32,
+    data: &[u8],
+) -> Result<(), Error> {
+    let name_len = name.len() + 1; // includes the terminating NUL byte
+    write!(
+        target,
+        "070701{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}{:08X}",
+        ino,       // inode
+        mode,      // mode
+        0,         // uid
+        0,         // gid
+        1,         // nlink
+        0,         // mtime
+        data.len(),
+        0,         // devmajor
+        0,         // devminor
+        0,         // rdevmajor
+        0,         // rdevminor
+        name_len,
+        0,         // check
+    )?;
+    target.write_all(name.as_bytes())?;
+    target.write_all(&[0u8])?;
+    write_cpio_padding(target, 110 + name_len)?;
+    target.write_all(data)?;
+    write_cpio_padding(target, data.len())?;
+    Ok(())
+}
+
+fn modification_time(path: &str) -> Result<std::time::SystemTime, Error> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .map_err(|err| format_err!("unable to stat '{}' - {}", path, err))
+}
+
+/// Returns the path to an initramfs containing the restore daemon. The kernel accepts multiple
+/// concatenated cpio archives, so we simply append the daemon binary to the base image. The
+/// result is cached and only rebuilt if one of its sources changed.
+fn create_restore_initramfs() -> Result<&'static str, Error> {
+    let cache_fn = buildcfg::PROXMOX_BACKUP_INITRAMFS_CACHE_FN;
+
+    let newest_source = modification_time(buildcfg::PROXMOX_BACKUP_INITRAMFS_FN)?
+        .max(modification_time(buildcfg::PROXMOX_BACKUP_RESTORE_DAEMON_BIN_FN)?);
+
+    if let Ok(cached) = modification_time(cache_fn) {
+        if cached >= newest_source {
+            return Ok(cache_fn);
+        }
+    }
+
+    let (tmp_fd, tmp_path) = make_tmp_file(cache_fn, CreateOptions::new())?;
+    let tmp_file = unsafe { File::from_raw_fd(tmp_fd.into_raw_fd()) };
+
+    let res: Result<(), Error> = proxmox::try_block!({
+        let mut writer = BufWriter::new(tmp_file);
+
+        let base_len = std::io::copy(
+            &mut File::open(buildcfg::PROXMOX_BACKUP_INITRAMFS_FN)?,
+            &mut writer,
+        )?;
+        // the appended archive has to start on a 4 byte boundary
+        write_cpio_padding(&mut writer, base_len as usize)?;
+
+        let daemon = std::fs::read(buildcfg::PROXMOX_BACKUP_RESTORE_DAEMON_BIN_FN)?;
+        write_cpio_entry(&mut writer, 1, "proxmox-restore-daemon", 0o100755, &daemon)?;
+        write_cpio_entry(&mut writer, 2, "restore-vm-marker", 0o100644, &[])?;
+        write_cpio_entry(&mut writer, 0, "TRAILER!!!", 0, &[])?;
+
+        writer.flush()?;
+        std::fs::rename(&tmp_path, cache_fn)?;
+        Ok(())
+    });
+
+    if let Err(err) = res {
+        let _ = std::fs::remove_file(&tmp_path);
+        bail!("unable to create restore initramfs - {}", err);
+    }
+
+    Ok(cache_fn)
+}
+
+/// Start a restore VM for the image archives of the given snapshot. Returns the PID of the QEMU
+/// process and the CID used for the VM, which might differ from the requested one if it was
+/// already in use.
+pub async fn start_vm(
+    // u16 so we can do wrapping_add without going too high
+    mut cid: u16,
+    details: &SnapRestoreDetails,
+) -> Result<(i32, i32), Error> {
+    if std::env::var("PBS_PASSWORD").is_err() {
+        bail!("environment variable PBS_PASSWORD has to be set for QEMU VM restore");
+    }
+
+    if !Path::new(buildcfg::PROXMOX_BACKUP_KERNEL_FN).exists() {
+        bail!(
+            "cannot run file-restore VM: kernel image '{}' not found",
+            buildcfg::PROXMOX_BACKUP_KERNEL_FN
+        );
+    }
+
+    let initramfs = create_restore_initramfs()?;
+
+    let logpath = format!("{}/file-restore", buildcfg::PROXMOX_BACKUP_LOG_DIR);
+    create_path(&logpath, None, None)?;
+    let logfile = format!(
+        "{}/qemu-{}.log",
+        logpath,
+        proxmox::tools::time::epoch_i64()
+    );
+
+    let (pid_fd, pid_path) = make_tmp_file("/tmp/file-restore-qemu.pid", CreateOptions::new())?;
+    drop(pid_fd);
+
+    let mut base_args = vec![
+        "-chardev".to_owned(),
+        format!("file,id=log,path=/dev/null,logfile={},logappend=on", logfile),
+        "-serial".to_owned(),
+        "chardev:log".to_owned(),
+        "-vnc".to_owned(),
+        "none".to_owned(),
+        "-enable-kvm".to_owned(),
+        "-m".to_owned(),
+        "512".to_owned(),
+        "-kernel".to_owned(),
+        buildcfg::PROXMOX_BACKUP_KERNEL_FN.to_owned(),
+        "-initrd".to_owned(),
+        initramfs.to_owned(),
+        "-append".to_owned(),
+        "quiet panic=1 rdinit=/proxmox-restore-daemon".to_owned(),
+        "-daemonize".to_owned(),
+        "-pidfile".to_owned(),
+        pid_path.to_string_lossy().into_owned(),
+        "-name".to_owned(),
+        PBS_VM_NAME.to_owned(),
+        "-nodefaults".to_owned(),
+        "-no-reboot".to_owned(),
+    ];
+
+    let snapshot = &details.snapshot;
+    let mut snapshot_opts = format!(
+        "snapshot={}/{}/{}",
+        snapshot.group().backup_type(),
+        snapshot.group().backup_id(),
+        snapshot.backup_time_string(),
+    );
+    if !snapshot.group().ns().is_root() {
+        snapshot_opts.push_str(&format!(",,namespace={}", snapshot.group().ns()));
+    }
+    let keyfile = match details.keyfile {
+        Some(ref keyfile) => format!(",,keyfile={}", keyfile),
+        None => String::new(),
+    };
+
+    // Generate drive arguments for all fidx files in backup snapshot
+    let mut drives = 0;
+    for file in details.manifest.files() {
+        let serial = match file.filename.strip_suffix(".img.fidx") {
+            Some(serial) => serial,
+            None => continue,
+        };
+        if serial.len() > MAX_SERIAL_LEN {
+            eprintln!(
+                "skipping image '{}' - archive name too long for file-restore",
+                file.filename
+            );
+            continue;
+        }
+
+        base_args.push("-drive".to_owned());
+        base_args.push(format!(
+            "file=pbs:repository={},,{},,archive={}{},read-only=on,if=none,id=drive{}",
+            details.repo, snapshot_opts, file.filename, keyfile, drives
+        ));
+        base_args.push("-device".to_owned());
+        base_args.push(format!(
+            "virtio-blk-pci,drive=drive{},serial={}",
+            drives, serial
+        ));
+        drives += 1;
+    }
+
+    if drives == 0 {
+        bail!("snapshot '{}' does not contain any supported image archives", snapshot);
+    }
+
+    // Try starting QEMU in a loop to retry if we fail because of a bad 'cid' value
+    let mut attempts = 0;
+    let pid = loop {
+        let mut qemu_cmd = Command::new("qemu-system-x86_64");
+        qemu_cmd.args(base_args.iter());
+        qemu_cmd.arg("-device");
+        qemu_cmd.arg(format!(
+            "vhost-vsock-pci,guest-cid={},disable-legacy=on",
+            cid
+        ));
+        qemu_cmd.stdout(Stdio::null());
+        qemu_cmd.stderr(Stdio::piped());
+
+        let res = tokio::task::block_in_place(|| qemu_cmd.spawn()?.wait_with_output())?;
+
+        if res.status.success() {
+            // at this point QEMU is already daemonized and running, so if anything fails we
+            // technically leave behind a zombie-VM... this shouldn't matter, as it will stop
+            // itself soon enough (timer), and the following operations are unlikely to fail
+            let pid = file_read_firstline(&pid_path)?
+                .trim()
+                .parse::<i32>()
+                .map_err(|err| format_err!("cannot parse PID of QEMU process - {}", err));
+            let _ = std::fs::remove_file(&pid_path);
+            break pid?;
+        } else {
+            let out = String::from_utf8_lossy(&res.stderr);
+            if out.contains("unable to set guest cid: Address already in use") {
+                attempts += 1;
+                if attempts >= MAX_CID_TRIES {
+                    let _ = std::fs::remove_file(&pid_path);
+                    bail!("CID '{}' in use, but max attempts reached, aborting", cid);
+                }
+                // CID in use, try next higher one
+                eprintln!("CID '{}' in use by other VM, attempting next one", cid);
+                // skip special-meaning low values
+                cid = cid.wrapping_add(1).max(10);
+            } else {
+                let _ = std::fs::remove_file(&pid_path);
+                eprint!("{}", out);
+                bail!("Starting VM failed. See output above for more information.");
+            }
+        }
+    };
+
+    // QEMU has started successfully, now wait for virtio socket to become ready
+    let pid_t = Pid::from_raw(pid);
+    for _ in 0..60 {
+        let client = VsockClient::new(cid as i32, DEFAULT_VSOCK_PORT);
+        let status = client.get("api2/json/status", Some(json!({ "keep-timeout": true })));
+        if let Ok(Ok(_)) = time::timeout(Duration::from_secs(2), status).await {
+            return Ok((pid, cid as i32));
+        }
+        if kill(pid_t, None).is_err() {
+            // QEMU exited
+            bail!("VM exited before connection could be established");
+        }
+        time::sleep(Duration::from_millis(500)).await;
+    }
+
+    // start failed
+    if let Err(err) = try_kill_vm(pid) {
+        eprintln!("killing failed VM failed: {}", err);
+    }
+    bail!("starting VM timed out");
+}
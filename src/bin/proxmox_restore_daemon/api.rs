@@ -0,0 +1,258 @@
+///! File-restore API running inside the restore VM
+use anyhow::{bail, Error};
+use futures::*;
+use hyper::http::request::Parts;
+use hyper::{header, Body, Response, StatusCode};
+use serde_json::Value;
+use tokio_stream::wrappers::ReceiverStream;
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use proxmox::api::{
+    api, schema::*, ApiHandler, ApiMethod, ApiResponseFuture, Permission, Router, RpcEnvironment,
+    SubdirMap,
+};
+use proxmox::{identity, list_subdirs_api_method, sortable};
+
+use proxmox_backup::api2::types::*;
+use proxmox_backup::backup::DirEntryAttribute;
+use proxmox_backup::tools::{self, zip::zip_directory, AsyncChannelWriter, AsyncReaderStream};
+
+use super::{disk::ResolveResult, watchdog_ping, watchdog_remaining};
+
+// NOTE: All API endpoints must have Permission::World, as the configs for authentication do not
+// exist within the restore VM. Safety is guaranteed since we use a low port, so only root on the
+// host can contact us - and there the proxmox-file-restore client already checked permissions.
+
+const SUBDIRS: SubdirMap = &[
+    ("extract", &Router::new().get(&API_METHOD_EXTRACT)),
+    ("list", &Router::new().get(&API_METHOD_LIST)),
+    ("status", &Router::new().get(&API_METHOD_STATUS)),
+    ("stop", &Router::new().get(&API_METHOD_STOP)),
+];
+
+pub const ROUTER: Router = Router::new()
+    .get(&list_subdirs_api_method!(SUBDIRS))
+    .subdirs(SUBDIRS);
+
+fn read_uptime() -> Result<f32, Error> {
+    let uptime = fs::read_to_string("/proc/uptime")?;
+    match uptime.split_ascii_whitespace().next() {
+        Some(uptime) => Ok(uptime.parse()?),
+        None => bail!("unable to parse /proc/uptime"),
+    }
+}
+
+#[api(
+    input: {
+        properties: {
+            "keep-timeout": {
+                type: bool,
+                description: "If true, do not reset the watchdog timer on this API call.",
+                default: false,
+                optional: true,
+            },
+        },
+    },
+    access: {
+        description: "Permissions are handled outside restore VM.",
+        permission: &Permission::World,
+    },
+    returns: {
+        type: RestoreDaemonStatus,
+    }
+)]
+/// General status information
+fn status(keep_timeout: bool) -> Result<RestoreDaemonStatus, Error> {
+    if !keep_timeout {
+        watchdog_ping();
+    }
+    Ok(RestoreDaemonStatus {
+        uptime: read_uptime()? as i64,
+        timeout: watchdog_remaining(),
+    })
+}
+
+#[api(
+    access: {
+        description: "Permissions are handled outside restore VM.",
+        permission: &Permission::World,
+    },
+)]
+/// Stop the restore VM immediately, this will never return if successful
+fn stop() -> Result<(), Error> {
+    use nix::sys::reboot;
+    println!("/stop called, shutting down");
+    let err = reboot::reboot(reboot::RebootMode::RB_POWER_OFF).unwrap_err();
+    bail!("'reboot' syscall failed: {}", err);
+}
+
+fn get_dir_entry(path: &Path) -> Result<DirEntryAttribute, Error> {
+    use nix::sys::stat;
+
+    let stat = stat::lstat(path)?;
+    Ok(match stat.st_mode & libc::S_IFMT {
+        libc::S_IFREG => DirEntryAttribute::File {
+            size: stat.st_size as u64,
+            mtime: stat.st_mtime,
+        },
+        libc::S_IFDIR => DirEntryAttribute::Directory { start: 0 },
+        libc::S_IFLNK => DirEntryAttribute::Symlink,
+        libc::S_IFBLK => DirEntryAttribute::BlockDevice,
+        libc::S_IFCHR => DirEntryAttribute::CharDevice,
+        libc::S_IFIFO => DirEntryAttribute::Fifo,
+        libc::S_IFSOCK => DirEntryAttribute::Socket,
+        _ => bail!("unsupported file type: {}", stat.st_mode),
+    })
+}
+
+/// Decode a base64 encoded path parameter, stripping any trailing slash
+fn decode_path(path: &str) -> Result<Vec<u8>, Error> {
+    let mut path = base64::decode(path)?;
+    if let Some(b'/') = path.last() {
+        path.pop();
+    }
+    Ok(path)
+}
+
+#[api(
+    input: {
+        properties: {
+            "path": {
+                type: String,
+                description: "base64-encoded path to list files and directories under",
+            },
+        },
+    },
+    access: {
+        description: "Permissions are handled outside restore VM.",
+        permission: &Permission::World,
+    },
+    returns: {
+        description: "List of archive entries.",
+        type: Array,
+        items: { type: ArchiveEntry },
+    },
+)]
+/// List file details for given file or a list of files and directories under the given path if it
+/// points to a directory.
+fn list(path: String) -> Result<Vec<ArchiveEntry>, Error> {
+    watchdog_ping();
+
+    let path = decode_path(&path)?;
+    let path_str = OsStr::from_bytes(&path[..]);
+
+    let resolved = crate::DISK_STATE.lock().unwrap().resolve(Path::new(path_str))?;
+
+    let mut res = Vec::new();
+
+    match resolved {
+        ResolveResult::Path(vm_path) => {
+            let root_entry = get_dir_entry(&vm_path)?;
+            match root_entry {
+                DirEntryAttribute::Directory { .. } => {
+                    // list on directory, return all contained files/dirs
+                    for entry in fs::read_dir(&vm_path)? {
+                        let entry = match entry {
+                            Ok(entry) => entry,
+                            Err(_) => continue,
+                        };
+                        let entry_path = Path::new(path_str).join(entry.file_name());
+                        if let Ok(attr) = get_dir_entry(&entry.path()) {
+                            res.push(ArchiveEntry::new(entry_path.as_os_str().as_bytes(), &attr));
+                        }
+                    }
+                }
+                // list on file, return details
+                _ => res.push(ArchiveEntry::new(&path, &root_entry)),
+            }
+        }
+        ResolveResult::Directories(names) => {
+            for name in names {
+                let mut entry_path = path.clone();
+                entry_path.push(b'/');
+                entry_path.extend(name.as_bytes());
+                res.push(ArchiveEntry::new(
+                    &entry_path[..],
+                    &DirEntryAttribute::Directory { start: 0 },
+                ));
+            }
+        }
+    }
+
+    Ok(res)
+}
+
+#[sortable]
+pub const API_METHOD_EXTRACT: ApiMethod = ApiMethod::new(
+    &ApiHandler::AsyncHttp(&extract),
+    &ObjectSchema::new(
+        "Extract a file or directory from the VM, directories are returned as zip archive.",
+        &sorted!([
+            ("path", false, &StringSchema::new("base64-encoded path to extract").schema()),
+        ]),
+    ),
+)
+.access(None, &Permission::World);
+
+fn extract(
+    _parts: Parts,
+    _req_body: Body,
+    param: Value,
+    _info: &ApiMethod,
+    _rpcenv: Box<dyn RpcEnvironment>,
+) -> ApiResponseFuture {
+    watchdog_ping();
+    async move {
+        let path = decode_path(tools::required_string_param(&param, "path")?)?;
+        let path = PathBuf::from(OsStr::from_bytes(&path[..]));
+
+        let resolved = {
+            let mut disk_state = crate::DISK_STATE.lock().unwrap();
+            disk_state.resolve(&path)?
+        };
+
+        let vm_path = match resolved {
+            ResolveResult::Path(vm_path) => vm_path,
+            _ => bail!("invalid path, cannot restore meta-directory: {:?}", path),
+        };
+
+        // check here so we can return a real error message, failing in the async task will stop
+        // the transfer, but not return a useful message
+        if !vm_path.exists() {
+            bail!("file or directory {:?} does not exist", path);
+        }
+
+        let body = if vm_path.is_dir() {
+            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            let channelwriter = AsyncChannelWriter::new(sender, 1024 * 1024);
+            tokio::spawn(async move {
+                if let Err(err) = zip_directory(channelwriter, &vm_path).await {
+                    eprintln!("file restore zip error: {}", err);
+                }
+            });
+            Body::wrap_stream(ReceiverStream::new(receiver).map_err(move |err| {
+                eprintln!("error during streaming of zip '{:?}' - {}", path, err);
+                err
+            }))
+        } else if vm_path.is_file() {
+            let file = tokio::fs::File::open(&vm_path).await?;
+            Body::wrap_stream(AsyncReaderStream::new(file).map_err(move |err| {
+                eprintln!("error during streaming of file '{:?}' - {}", path, err);
+                err
+            }))
+        } else {
+            bail!("invalid entry type for path: {:?}", path);
+        };
+
+        Ok(Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "application/octet-stream")
+            .body(body)
+            .unwrap())
+    }
+    .boxed()
+}
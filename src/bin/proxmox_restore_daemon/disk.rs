@@ -0,0 +1,228 @@
+//! Low-level disk (image) access functions for file restore VMs.
+use anyhow::{bail, format_err, Error};
+use lazy_static::lazy_static;
+
+use std::collections::HashMap;
+use std::fs::{create_dir_all, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Component, Path, PathBuf};
+
+use proxmox::tools::fs;
+
+lazy_static! {
+    /// Additional mount options for file systems which would otherwise try to modify the
+    /// (read-only) device, e.g. by replaying a journal.
+    static ref FS_OPT_MAP: HashMap<&'static str, &'static str> = {
+        let mut m = HashMap::new();
+        m.insert("ext3", "noload");
+        m.insert("ext4", "noload");
+        m
+    };
+}
+
+/// Result of resolving a path on the virtual file system of the restore VM.
+pub enum ResolveResult {
+    /// A path to an existing file or directory within a mounted file system.
+    Path(PathBuf),
+    /// A virtual directory (archive or partition level), with the names of its children.
+    Directories(Vec<String>),
+}
+
+/// A partition on an attached disk image. The partition number '0' refers to the whole disk, in
+/// case it does not contain a partition table.
+struct PartitionBucket {
+    dev_node: String,
+    number: u32,
+    mountpoint: Option<PathBuf>,
+}
+
+/// Mounts file systems, trying every file system type supported by the running kernel.
+struct Filesystems {
+    supported_fs: Vec<String>,
+}
+
+impl Filesystems {
+    fn scan() -> Result<Self, Error> {
+        // detect kernel supported filesystems
+        let mut supported_fs = Vec::new();
+        for line in BufReader::new(File::open("/proc/filesystems")?).lines() {
+            let line = line?;
+            // filesystems marked with 'nodev' cannot be mounted from a block device
+            if !line.starts_with("nodev") {
+                supported_fs.push(line.trim().to_owned());
+            }
+        }
+
+        Ok(Self { supported_fs })
+    }
+
+    fn mount(&self, source: &str, target: &Path) -> Result<(), Error> {
+        use nix::mount::{mount, MsFlags};
+
+        create_dir_all(target)?;
+
+        // try all supported filesystems until one works - that's what busybox' mount does too
+        let flags =
+            MsFlags::MS_RDONLY | MsFlags::MS_NOEXEC | MsFlags::MS_NOSUID | MsFlags::MS_NODEV;
+        for fs in &self.supported_fs {
+            let fs: &str = fs.as_ref();
+            let opts = FS_OPT_MAP.get(fs).copied();
+            match mount(Some(source), target, Some(fs), flags, opts) {
+                Ok(()) => {
+                    println!("mounting '{}' succeeded, fstype: '{}'", source, fs);
+                    return Ok(());
+                }
+                Err(err) => {
+                    eprintln!("mount error on '{}' ({}) - {}", source, fs, err);
+                }
+            }
+        }
+
+        bail!("all mounts failed or no supported file system found on '{}'", source);
+    }
+}
+
+/// State of all disk images attached to the restore VM.
+///
+/// Every disk is identified by its archive name, which is passed in as the serial number of the
+/// virtio-blk device (without the '.img.fidx' extension, since the serial is limited to 20
+/// characters). The exposed virtual file system is structured as
+/// `/<archive>/part/<partition number>/<path within file system>`.
+pub struct DiskState {
+    filesystems: Filesystems,
+    disk_map: HashMap<String, Vec<PartitionBucket>>,
+}
+
+impl DiskState {
+    /// Scan all attached disks for partitions. File systems are only mounted on first access.
+    pub fn scan() -> Result<Self, Error> {
+        let filesystems = Filesystems::scan()?;
+
+        // note: tools::disks relies on udev, which is not available in the VM
+        let mut disk_map = HashMap::new();
+        for entry in std::fs::read_dir("/sys/block")? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if !name.starts_with("vd") {
+                continue;
+            }
+
+            let sys_path = format!("/sys/block/{}", name);
+
+            let serial = match fs::file_read_optional_string(format!("{}/serial", sys_path))? {
+                Some(serial) if !serial.trim().is_empty() => serial.trim().to_owned(),
+                _ => {
+                    eprintln!("disk '{}' has no serial, ignoring", name);
+                    continue;
+                }
+            };
+            let archive = format!("{}.img.fidx", serial);
+            println!("disk '{}': found archive '{}'", name, archive);
+
+            let mut partitions = Vec::new();
+            for part_entry in std::fs::read_dir(&sys_path)? {
+                let part_name = part_entry?.file_name().to_string_lossy().into_owned();
+                if !part_name.starts_with(&name) {
+                    continue;
+                }
+
+                let part_path = format!("{}/{}/partition", sys_path, part_name);
+                let number = fs::file_read_firstline(&part_path)?
+                    .trim()
+                    .parse::<u32>()
+                    .map_err(|err| {
+                        format_err!("invalid partition number for '{}' - {}", part_name, err)
+                    })?;
+
+                println!("disk '{}': found partition {} ('{}')", name, number, part_name);
+                partitions.push(PartitionBucket {
+                    dev_node: format!("/dev/{}", part_name),
+                    number,
+                    mountpoint: None,
+                });
+            }
+
+            if partitions.is_empty() {
+                // no partition table, the file system might be directly on the disk
+                partitions.push(PartitionBucket {
+                    dev_node: format!("/dev/{}", name),
+                    number: 0,
+                    mountpoint: None,
+                });
+            }
+
+            partitions.sort_by_key(|p| p.number);
+            disk_map.insert(archive, partitions);
+        }
+
+        Ok(Self { filesystems, disk_map })
+    }
+
+    /// Resolve a path on the virtual file system.
+    ///
+    /// Given a path like "/drive-scsi0.img.fidx/part/1/etc/passwd", this will mount the first
+    /// partition of 'drive-scsi0' on demand (i.e. if not already mounted) and return the local
+    /// path to the requested file, e.g. "/mnt/vda1/etc/passwd". Paths not reaching into a file
+    /// system return the entries of the respective virtual directory instead.
+    pub fn resolve(&mut self, path: &Path) -> Result<ResolveResult, Error> {
+        let mut components = path.components().filter(|c| {
+            !matches!(c, Component::RootDir | Component::CurDir)
+        });
+
+        let archive = match components.next() {
+            Some(Component::Normal(archive)) => archive.to_string_lossy().into_owned(),
+            Some(c) => bail!("invalid archive in path: {:?}", c),
+            None => {
+                let mut archives: Vec<String> = self.disk_map.keys().cloned().collect();
+                archives.sort();
+                return Ok(ResolveResult::Directories(archives));
+            }
+        };
+
+        let partitions = self
+            .disk_map
+            .get_mut(&archive)
+            .ok_or_else(|| format_err!("given image '{}' not found", archive))?;
+
+        match components.next() {
+            Some(Component::Normal(bucket)) if bucket == "part" => (),
+            Some(c) => bail!("invalid bucket type in path: {:?}", c),
+            None => return Ok(ResolveResult::Directories(vec!["part".to_string()])),
+        }
+
+        let number = match components.next() {
+            Some(Component::Normal(number)) => number
+                .to_string_lossy()
+                .parse::<u32>()
+                .map_err(|err| format_err!("invalid partition number {:?} - {}", number, err))?,
+            Some(c) => bail!("invalid partition in path: {:?}", c),
+            None => {
+                let numbers = partitions.iter().map(|p| p.number.to_string()).collect();
+                return Ok(ResolveResult::Directories(numbers));
+            }
+        };
+
+        let partition = partitions
+            .iter_mut()
+            .find(|p| p.number == number)
+            .ok_or_else(|| format_err!("partition {} not found on image '{}'", number, archive))?;
+
+        let mut local_path = match partition.mountpoint {
+            Some(ref mountpoint) => mountpoint.clone(),
+            None => {
+                let mountpoint = Path::new("/mnt").join(&partition.dev_node[5..]);
+                self.filesystems.mount(&partition.dev_node, &mountpoint)?;
+                partition.mountpoint = Some(mountpoint.clone());
+                mountpoint
+            }
+        };
+
+        for component in components {
+            match component {
+                Component::Normal(name) => local_path.push(name),
+                c => bail!("invalid path component: {:?}", c),
+            }
+        }
+
+        Ok(ResolveResult::Path(local_path))
+    }
+}
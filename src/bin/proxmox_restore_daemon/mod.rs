@@ -0,0 +1,9 @@
+///! File restore VM related functionality
+mod api;
+pub use api::*;
+
+pub mod disk;
+pub use disk::DiskState;
+
+mod watchdog;
+pub use watchdog::*;
@@ -384,6 +384,7 @@ async fn create_archive(
         },
         None,
         options,
+        None,
     ).await?;
 
     Ok(())
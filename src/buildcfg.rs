@@ -30,6 +30,27 @@ pub const PROXMOX_BACKUP_PROXY_PID_FN: &str = concat!(PROXMOX_BACKUP_RUN_DIR_M!(
 /// the PID filename for the privileged api daemon
 pub const PROXMOX_BACKUP_API_PID_FN: &str = concat!(PROXMOX_BACKUP_RUN_DIR_M!(), "/api.pid");
 
+#[macro_export]
+macro_rules! PROXMOX_BACKUP_FILE_RESTORE_BIN_DIR_M {
+    () => ("/usr/lib/x86_64-linux-gnu/proxmox-backup/file-restore")
+}
+
+/// the daemon binary running inside the single file restore VM
+pub const PROXMOX_BACKUP_RESTORE_DAEMON_BIN_FN: &str =
+    concat!(PROXMOX_BACKUP_FILE_RESTORE_BIN_DIR_M!(), "/proxmox-restore-daemon");
+
+/// the kernel image used to boot the single file restore VM
+pub const PROXMOX_BACKUP_KERNEL_FN: &str =
+    concat!(PROXMOX_BACKUP_FILE_RESTORE_BIN_DIR_M!(), "/bzImage");
+
+/// the base initramfs of the single file restore VM, the restore daemon gets appended to it
+pub const PROXMOX_BACKUP_INITRAMFS_FN: &str =
+    concat!(PROXMOX_BACKUP_FILE_RESTORE_BIN_DIR_M!(), "/initramfs.img");
+
+/// the initramfs including the restore daemon, rebuilt whenever one of its sources changes
+pub const PROXMOX_BACKUP_INITRAMFS_CACHE_FN: &str =
+    concat!(PROXMOX_BACKUP_RUN_DIR_M!(), "/file-restore-initramfs.img");
+
 /// Prepend configuration directory to a file name
 ///
 /// This is a simply way to get the full path for configuration files.
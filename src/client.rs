@@ -14,6 +14,7 @@ use crate::{
 
 
 mod merge_known_chunks;
+mod inject_reused_chunks;
 pub mod pipe_to_stream;
 
 mod http_client;
@@ -44,6 +45,7 @@ mod backup_specification;
 pub use backup_specification::*;
 
 pub mod pull;
+pub mod push;
 
 /// Connect to localhost:8007 as root@pam
 ///
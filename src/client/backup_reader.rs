@@ -1,4 +1,4 @@
-use anyhow::{format_err, Error};
+use anyhow::{bail, format_err, Error};
 use std::io::{Write, Seek, SeekFrom};
 use std::fs::File;
 use std::sync::Arc;
@@ -37,23 +37,29 @@ impl BackupReader {
     }
 
     /// Create a new instance by upgrading the connection at '/api2/json/reader'
+    #[allow(clippy::too_many_arguments)]
     pub async fn start(
         client: HttpClient,
         crypt_config: Option<Arc<CryptConfig>>,
         datastore: &str,
+        ns: &BackupNamespace,
         backup_type: &str,
         backup_id: &str,
         backup_time: i64,
         debug: bool,
     ) -> Result<Arc<BackupReader>, Error> {
 
-        let param = json!({
+        let mut param = json!({
             "backup-type": backup_type,
             "backup-id": backup_id,
             "backup-time": backup_time,
             "store": datastore,
             "debug": debug,
         });
+
+        if !ns.is_root() {
+            param["ns"] = ns.to_string().into();
+        }
         let req = HttpClient::request_builder(client.server(), client.port(), "GET", "/api2/json/reader", Some(param)).unwrap();
 
         let (h2, abort) = client.start_h2_connection(req, String::from(PROXMOX_BACKUP_READER_PROTOCOL_ID_V1!())).await?;
@@ -120,6 +126,25 @@ impl BackupReader {
         self.h2.download(path, Some(param), output).await
     }
 
+    /// Make sure the zstd dictionary with the given ID is available for decoding,
+    /// downloading it from the server if required.
+    pub async fn ensure_zstd_dictionary(&self, id: &[u8; 8]) -> Result<Arc<ZstdDictionary>, Error> {
+        if let Some(dict) = lookup_zstd_dictionary(id) {
+            return Ok(dict);
+        }
+
+        let mut data = Vec::with_capacity(128 * 1024);
+        let param = json!({ "id": proxmox::tools::bin_to_hex(id) });
+        self.h2.download("dictionary", Some(param), &mut data).await?;
+
+        let dict = ZstdDictionary::new(data);
+        if dict.id() != id {
+            bail!("downloaded zstd dictionary has wrong checksum");
+        }
+
+        Ok(register_zstd_dictionary(dict))
+    }
+
     pub fn force_close(self) {
         self.abort.abort();
     }
@@ -14,7 +14,9 @@ use tokio_stream::wrappers::ReceiverStream;
 
 use proxmox::tools::digest_to_hex;
 
+use super::inject_reused_chunks::{InjectedChunksInfo, InjectReusedChunks};
 use super::merge_known_chunks::{MergedChunkInfo, MergeKnownChunks};
+use crate::api2::types::ChunkCompressionConfig;
 use crate::backup::*;
 use crate::tools::format::HumanByte;
 
@@ -44,6 +46,8 @@ pub struct BackupStats {
 pub struct UploadOptions {
     pub previous_manifest: Option<Arc<BackupManifest>>,
     pub compress: bool,
+    /// Chunk compression settings if `compress` is set, defaults to zstd level 1.
+    pub compression: Option<BlobCompression>,
     pub encrypt: bool,
     pub fixed_size: Option<u64>,
 }
@@ -63,6 +67,7 @@ impl BackupWriter {
         client: HttpClient,
         crypt_config: Option<Arc<CryptConfig>>,
         datastore: &str,
+        ns: &BackupNamespace,
         backup_type: &str,
         backup_id: &str,
         backup_time: i64,
@@ -70,7 +75,7 @@ impl BackupWriter {
         benchmark: bool
     ) -> Result<Arc<BackupWriter>, Error> {
 
-        let param = json!({
+        let mut param = json!({
             "backup-type": backup_type,
             "backup-id": backup_id,
             "backup-time": backup_time,
@@ -79,6 +84,10 @@ impl BackupWriter {
             "benchmark": benchmark
         });
 
+        if !ns.is_root() {
+            param["ns"] = ns.to_string().into();
+        }
+
         let req = HttpClient::request_builder(
             client.server(), client.port(), "GET", "/api2/json/backup", Some(param)).unwrap();
 
@@ -218,11 +227,16 @@ impl BackupWriter {
         self.upload_blob_from_data(contents, file_name, options).await
     }
 
+    /// Upload a chunk stream as index `archive_name`.
+    ///
+    /// Chunks of the previous backup received on `injections` get referenced at
+    /// their stream offset, without uploading any data (see `InjectionData`).
     pub async fn upload_stream(
         &self,
         archive_name: &str,
         stream: impl Stream<Item = Result<bytes::BytesMut, Error>>,
         options: UploadOptions,
+        injections: Option<std::sync::mpsc::Receiver<InjectChunks>>,
     ) -> Result<BackupStats, Error> {
         let known_chunks = Arc::new(Mutex::new(HashSet::new()));
 
@@ -241,6 +255,12 @@ impl BackupWriter {
         let index_path = format!("{}_index", prefix);
         let close_path = format!("{}_close", prefix);
 
+        let compression = if options.compress {
+            options.compression.unwrap_or_default()
+        } else {
+            BlobCompression::None
+        };
+
         if let Some(manifest) = options.previous_manifest {
             // try, but ignore errors
             match archive_type(archive_name) {
@@ -261,10 +281,11 @@ impl BackupWriter {
                 self.h2.clone(),
                 wid,
                 stream,
+                injections,
                 &prefix,
                 known_chunks.clone(),
                 if options.encrypt { self.crypt_config.clone() } else { None },
-                options.compress,
+                compression,
                 self.verbose,
             )
             .await?;
@@ -308,6 +329,114 @@ impl BackupWriter {
         })
     }
 
+    /// Upload an existing index whose chunks are already encoded, e.g. when pushing a
+    /// snapshot from a local datastore to another server.
+    ///
+    /// Chunks contained in `known_chunks` are only referenced, all others are
+    /// loaded with `load_chunk` and uploaded. Returns the upload statistics
+    /// and the number of bytes which had to be uploaded.
+    pub async fn upload_index_chunk_info<I, F>(
+        &self,
+        archive_name: &str,
+        index: I,
+        known_chunks: Arc<Mutex<HashSet<[u8;32]>>>,
+        load_chunk: F,
+    ) -> Result<(BackupStats, usize), Error>
+    where
+        I: IndexFile + Send,
+        F: Fn(&[u8; 32]) -> Result<DataBlob, Error> + Send,
+    {
+        let (csum, size) = index.compute_csum();
+        let chunk_list: Vec<ChunkReadInfo> = (0..index.index_count())
+            .map(|pos| index.chunk_info(pos).unwrap())
+            .collect();
+
+        let mut param = json!({ "archive-name": archive_name });
+        let prefix = match archive_type(archive_name)? {
+            ArchiveType::FixedIndex => {
+                param["size"] = size.into();
+                "fixed"
+            }
+            ArchiveType::DynamicIndex => "dynamic",
+            ArchiveType::Blob => bail!("unable to upload blob '{}' as index", archive_name),
+        };
+
+        let index_path = format!("{}_index", prefix);
+        let upload_chunk_path = format!("{}_chunk", prefix);
+        let close_path = format!("{}_close", prefix);
+
+        let wid = self.h2.post(&index_path, Some(param)).await?.as_u64().unwrap();
+
+        let (upload_queue, upload_result) =
+            Self::append_chunk_queue(self.h2.clone(), wid, index_path, self.verbose);
+
+        let chunk_count = chunk_list.len();
+        let uploaded_len = Arc::new(AtomicUsize::new(0));
+        let uploaded_len2 = uploaded_len.clone();
+
+        let stream = stream::iter(chunk_list)
+            .map(move |info| -> Result<MergedChunkInfo, Error> {
+                let chunk_is_known = !known_chunks.lock().unwrap().insert(info.digest);
+                if chunk_is_known {
+                    Ok(MergedChunkInfo::Known(vec![(info.range.start, info.digest)]))
+                } else {
+                    let chunk = load_chunk(&info.digest)?;
+                    uploaded_len2.fetch_add(chunk.raw_size() as usize, Ordering::SeqCst);
+                    Ok(MergedChunkInfo::New(ChunkInfo {
+                        chunk,
+                        digest: info.digest,
+                        chunk_len: info.size(),
+                        offset: info.range.start,
+                    }))
+                }
+            })
+            .merge_known_chunks();
+        futures::pin_mut!(stream);
+
+        while let Some(merged_chunk_info) = stream.try_next().await? {
+            if let MergedChunkInfo::New(chunk_info) = merged_chunk_info {
+                let offset = chunk_info.offset;
+                let digest = chunk_info.digest;
+
+                let chunk_data = chunk_info.chunk.into_inner();
+                let param = json!({
+                    "wid": wid,
+                    "digest": digest_to_hex(&digest),
+                    "size": chunk_info.chunk_len,
+                    "encoded-size": chunk_data.len(),
+                });
+
+                let ct = "application/octet-stream";
+                let request = H2Client::request_builder("localhost", "POST", &upload_chunk_path, Some(param), Some(ct)).unwrap();
+                let response = self.h2.send_request(request, Some(bytes::Bytes::from(chunk_data))).await?;
+
+                upload_queue
+                    .send((MergedChunkInfo::Known(vec![(offset, digest)]), Some(response)))
+                    .await
+                    .map_err(|err| format_err!("failed to send to upload queue: {}", err))?;
+            } else {
+                upload_queue
+                    .send((merged_chunk_info, None))
+                    .await
+                    .map_err(|err| format_err!("failed to send to upload queue: {}", err))?;
+            }
+        }
+
+        drop(upload_queue); // close queue
+        upload_result.await??;
+
+        let param = json!({
+            "wid": wid ,
+            "chunk-count": chunk_count,
+            "size": size,
+            "csum": proxmox::tools::digest_to_hex(&csum),
+        });
+        let _value = self.h2.post(&close_path, Some(param)).await?;
+
+        let stats = BackupStats { size, csum };
+        Ok((stats, uploaded_len.load(Ordering::SeqCst)))
+    }
+
     fn response_queue(verbose: bool) -> (
         mpsc::Sender<h2::client::ResponseFuture>,
         oneshot::Receiver<Result<(), Error>>
@@ -491,6 +620,50 @@ impl BackupWriter {
             .map_err(|err| format_err!("Failed to parse backup time value returned by server - {}", err))
     }
 
+    /// Retrieve the chunk compression settings of the datastore
+    ///
+    /// Downloads the configured zstd dictionary if it is not available yet.
+    pub async fn chunk_compression(&self) -> Result<BlobCompression, Error> {
+        let config: ChunkCompressionConfig = match self.h2.get("compression", None).await {
+            Ok(data) => serde_json::from_value(data)?,
+            // older servers do not know about compression settings
+            Err(_) => return Ok(BlobCompression::default()),
+        };
+
+        if let Some(ref id) = config.dictionary {
+            let id = parse_dictionary_id(id)?;
+            if lookup_zstd_dictionary(&id).is_none() {
+                let mut data = Vec::with_capacity(128 * 1024);
+                let param = json!({ "id": proxmox::tools::bin_to_hex(&id) });
+                self.h2.download("dictionary", Some(param), &mut data).await?;
+
+                let dict = ZstdDictionary::new(data);
+                if dict.id() != &id {
+                    bail!("downloaded zstd dictionary has wrong checksum");
+                }
+                register_zstd_dictionary(dict);
+            }
+        }
+
+        BlobCompression::from_config(&config)
+    }
+
+    /// Retrieve the IDs of the zstd dictionaries stored on the server
+    pub async fn known_zstd_dictionaries(&self) -> Result<HashSet<[u8; 8]>, Error> {
+        let data = self.h2.get("dictionaries", None).await?;
+        let list: Vec<String> = serde_json::from_value(data)?;
+        list.iter().map(|id| parse_dictionary_id(id)).collect()
+    }
+
+    /// Upload a zstd dictionary, so that the server can decode chunks compressed with it
+    pub async fn upload_zstd_dictionary(&self, dict: &ZstdDictionary) -> Result<(), Error> {
+        let param = json!({ "id": dict.id_str() });
+        self.h2
+            .upload("POST", "dictionary", Some(param), "application/octet-stream", dict.data().to_vec())
+            .await?;
+        Ok(())
+    }
+
     /// Download backup manifest (index.json) of last backup
     pub async fn download_previous_manifest(&self) -> Result<BackupManifest, Error> {
 
@@ -516,10 +689,11 @@ impl BackupWriter {
         h2: H2Client,
         wid: u64,
         stream: impl Stream<Item = Result<bytes::BytesMut, Error>>,
+        injections: Option<std::sync::mpsc::Receiver<InjectChunks>>,
         prefix: &str,
         known_chunks: Arc<Mutex<HashSet<[u8;32]>>>,
         crypt_config: Option<Arc<CryptConfig>>,
-        compress: bool,
+        compression: BlobCompression,
         verbose: bool,
     ) -> impl Future<Output = Result<(usize, usize, usize, usize, std::time::Duration, [u8; 32]), Error>> {
 
@@ -546,7 +720,32 @@ impl BackupWriter {
         let index_csum_2 = index_csum.clone();
 
         stream
-            .and_then(move |data| {
+            .inject_reused_chunks(injections)
+            .and_then(move |chunk_info| {
+
+                let data = match chunk_info {
+                    InjectedChunksInfo::Known(chunks) => {
+                        // reused chunks of the previous backup, always known
+                        let mut guard = index_csum.lock().unwrap();
+                        let csum = guard.as_mut().unwrap();
+
+                        let mut known = Vec::with_capacity(chunks.len());
+                        for (size, digest) in chunks {
+                            total_chunks.fetch_add(1, Ordering::SeqCst);
+                            known_chunk_count.fetch_add(1, Ordering::SeqCst);
+                            reused_len.fetch_add(size as usize, Ordering::SeqCst);
+                            let offset = stream_len.fetch_add(size as usize, Ordering::SeqCst) as u64;
+
+                            let chunk_end = offset + size;
+                            csum.update(&chunk_end.to_le_bytes());
+                            csum.update(&digest);
+
+                            known.push((offset, digest));
+                        }
+                        return future::ok(MergedChunkInfo::Known(known));
+                    }
+                    InjectedChunksInfo::Raw(data) => data,
+                };
 
                 let chunk_len = data.len();
 
@@ -554,7 +753,7 @@ impl BackupWriter {
                 let offset = stream_len.fetch_add(chunk_len, Ordering::SeqCst) as u64;
 
                 let mut chunk_builder = DataChunkBuilder::new(data.as_ref())
-                    .compress(compress);
+                    .compression(compression.clone());
 
                 if let Some(ref crypt_config) = crypt_config {
                     chunk_builder = chunk_builder.crypt_config(crypt_config);
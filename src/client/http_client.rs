@@ -21,10 +21,11 @@ use proxmox::{
 };
 
 use super::pipe_to_stream::PipeToSendStream;
-use crate::api2::types::{Authid, Userid};
+use crate::api2::types::{Authid, RateLimitConfig, Userid};
 use crate::tools::{
     self,
     BroadcastFuture,
+    RateLimiter,
     DEFAULT_ENCODE_SET,
     http::HttpsConnector,
 };
@@ -48,6 +49,7 @@ pub struct HttpClientOptions {
     ticket_cache: bool,
     fingerprint_cache: bool,
     verify_cert: bool,
+    limit: RateLimitConfig,
 }
 
 impl HttpClientOptions {
@@ -106,6 +108,11 @@ impl HttpClientOptions {
         self.verify_cert = verify_cert;
         self
     }
+
+    pub fn rate_limit(mut self, rate_limit: RateLimitConfig) -> Self {
+        self.limit = rate_limit;
+        self
+    }
 }
 
 impl Default for HttpClientOptions {
@@ -118,6 +125,7 @@ impl Default for HttpClientOptions {
             ticket_cache: false,
             fingerprint_cache: false,
             verify_cert: true,
+            limit: RateLimitConfig::default(),
         }
     }
 }
@@ -131,7 +139,7 @@ pub struct HttpClient {
     first_auth: Option<BroadcastFuture<()>>,
     auth: Arc<RwLock<AuthInfo>>,
     ticket_abort: futures::future::AbortHandle,
-    _options: HttpClientOptions,
+    options: HttpClientOptions,
 }
 
 /// Delete stored ticket data (logout)
@@ -324,7 +332,15 @@ impl HttpClient {
         httpc.enforce_http(false); // we want https...
 
         httpc.set_connect_timeout(Some(std::time::Duration::new(10, 0)));
-        let https = HttpsConnector::with_connector(httpc, ssl_connector_builder.build());
+        let mut https = HttpsConnector::with_connector(httpc, ssl_connector_builder.build());
+
+        if let Some(rate_in) = options.limit.rate_in {
+            https.set_read_limiter(Some(RateLimiter::new_shared(rate_in, options.limit.burst_in)));
+        }
+
+        if let Some(rate_out) = options.limit.rate_out {
+            https.set_write_limiter(Some(RateLimiter::new_shared(rate_out, options.limit.burst_out)));
+        }
 
         let client = Client::builder()
         //.http2_initial_stream_window_size( (1 << 31) - 2)
@@ -423,7 +439,7 @@ impl HttpClient {
             auth,
             ticket_abort,
             first_auth,
-            _options: options,
+            options,
         })
     }
 
@@ -448,6 +464,11 @@ impl HttpClient {
         (*self.fingerprint.lock().unwrap()).clone()
     }
 
+    /// Returns the configured rate limits (useful to create further clients with the same limits)
+    pub fn rate_limit(&self) -> RateLimitConfig {
+        self.options.limit.clone()
+    }
+
     fn get_password(username: &Userid, interactive: bool) -> Result<String, Error> {
         // If we're on a TTY, query the user for a password
         if interactive && tty::stdin_isatty() {
@@ -0,0 +1,107 @@
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::task::{Context, Poll};
+
+use anyhow::{format_err, Error};
+use futures::*;
+use pin_project::pin_project;
+
+use crate::backup::InjectChunks;
+
+pub enum InjectedChunksInfo {
+    /// Chunks reused from a previous archive, as `(size, digest)`
+    Known(Vec<(u64, [u8; 32])>),
+    /// Chunk data to upload
+    Raw(bytes::BytesMut),
+}
+
+pub trait InjectReusedChunks: Sized {
+    fn inject_reused_chunks(
+        self,
+        injections: Option<mpsc::Receiver<InjectChunks>>,
+    ) -> InjectReusedChunksQueue<Self>;
+}
+
+/// Merges the chunks injected by a `ChunkStream` back into the chunk stream,
+/// at the stream offsets they were registered for.
+#[pin_project]
+pub struct InjectReusedChunksQueue<S> {
+    #[pin]
+    input: S,
+    injections: Option<mpsc::Receiver<InjectChunks>>,
+    pending_injection: Option<InjectChunks>,
+    pending_data: Option<bytes::BytesMut>,
+    stream_len: u64,
+}
+
+impl<S> InjectReusedChunks for S
+where
+    S: Stream<Item = Result<bytes::BytesMut, Error>>,
+{
+    fn inject_reused_chunks(
+        self,
+        injections: Option<mpsc::Receiver<InjectChunks>>,
+    ) -> InjectReusedChunksQueue<Self> {
+        InjectReusedChunksQueue {
+            input: self,
+            injections,
+            pending_injection: None,
+            pending_data: None,
+            stream_len: 0,
+        }
+    }
+}
+
+impl<S> Stream for InjectReusedChunksQueue<S>
+where
+    S: Stream<Item = Result<bytes::BytesMut, Error>>,
+{
+    type Item = Result<InjectedChunksInfo, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        loop {
+            if this.pending_injection.is_none() {
+                if let Some(ref injections) = this.injections {
+                    *this.pending_injection = injections.try_recv().ok();
+                }
+            }
+
+            if let Some(ref injection) = this.pending_injection {
+                if injection.boundary < *this.stream_len {
+                    return Poll::Ready(Some(Err(format_err!(
+                        "missed chunk injection at offset {}",
+                        injection.boundary,
+                    ))));
+                }
+                if injection.boundary == *this.stream_len {
+                    let injection = this.pending_injection.take().unwrap();
+                    *this.stream_len += injection.size;
+                    return Poll::Ready(Some(Ok(InjectedChunksInfo::Known(injection.chunks))));
+                }
+            }
+
+            // the injection for the current offset is always sent before the
+            // data following it, so only pass on data after checking again
+            if let Some(data) = this.pending_data.take() {
+                *this.stream_len += data.len() as u64;
+                return Poll::Ready(Some(Ok(InjectedChunksInfo::Raw(data))));
+            }
+
+            match ready!(this.input.as_mut().poll_next(cx)) {
+                Some(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Some(Ok(data)) => *this.pending_data = Some(data),
+                None => {
+                    if let Some(ref injection) = this.pending_injection {
+                        return Poll::Ready(Some(Err(format_err!(
+                            "got chunk injection at offset {} after end of stream",
+                            injection.boundary,
+                        ))));
+                    }
+                    return Poll::Ready(None);
+                }
+            }
+        }
+    }
+}
@@ -55,6 +55,10 @@ async fn pull_index_chunks<I: IndexFile>(
         move |(chunk, digest, size): (DataBlob, [u8; 32], u64)| {
             // println!("verify and write {}", proxmox::tools::digest_to_hex(&digest));
             chunk.verify_unencrypted(size as usize, &digest)?;
+            if let Some(id) = chunk.dictionary_id() {
+                // registered by the chunk reader when downloading the chunk
+                target2.ensure_zstd_dictionary(&id)?;
+            }
             target2.insert_chunk(&chunk, &digest)?;
             Ok(())
         },
@@ -443,21 +447,29 @@ pub async fn pull_snapshot_from(
     Ok(())
 }
 
+/// Pulls the remote group `group` in `remote_ns` into the local group of
+/// the same name in `group.ns()`.
+#[allow(clippy::too_many_arguments)]
 pub async fn pull_group(
     worker: &WorkerTask,
     client: &HttpClient,
     src_repo: &BackupRepository,
     tgt_store: Arc<DataStore>,
+    remote_ns: &BackupNamespace,
     group: &BackupGroup,
     delete: bool,
+    transfer_last: Option<usize>,
     progress: &mut StoreProgress,
 ) -> Result<(), Error> {
     let path = format!("api2/json/admin/datastore/{}/snapshots", src_repo.store());
 
-    let args = json!({
+    let mut args = json!({
         "backup-type": group.backup_type(),
         "backup-id": group.backup_id(),
     });
+    if !remote_ns.is_root() {
+        args["ns"] = remote_ns.to_string().into();
+    }
 
     let mut result = client.get(&path, Some(args)).await?;
     let mut list: Vec<SnapshotListItem> = serde_json::from_value(result["data"].take())?;
@@ -467,6 +479,7 @@ pub async fn pull_group(
     client.login().await?; // make sure auth is complete
 
     let fingerprint = client.fingerprint();
+    let rate_limit = client.rate_limit();
 
     let last_sync = tgt_store.last_successful_backup(group)?;
 
@@ -475,19 +488,24 @@ pub async fn pull_group(
     // start with 16384 chunks (up to 65GB)
     let downloaded_chunks = Arc::new(Mutex::new(HashSet::with_capacity(1024 * 64)));
 
+    let (list, in_progress, cutoff) =
+        split_transfer_list(list, |item| item.size.is_some(), transfer_last);
+
+    // in-progress backups can't be synced
+    for item in in_progress {
+        let snapshot = BackupDir::with_group(group.clone(), item.backup_time)?;
+        worker.log(format!(
+            "skipping snapshot {} - in-progress backup",
+            snapshot
+        ));
+    }
+
     progress.group_snapshots = list.len() as u64;
 
-    for (pos, item) in list.into_iter().enumerate() {
-        let snapshot = BackupDir::new(item.backup_type, item.backup_id, item.backup_time)?;
+    let mut skipped_older = 0;
 
-        // in-progress backups can't be synced
-        if item.size.is_none() {
-            worker.log(format!(
-                "skipping snapshot {} - in-progress backup",
-                snapshot
-            ));
-            continue;
-        }
+    for (pos, item) in list.into_iter().enumerate() {
+        let snapshot = BackupDir::with_group(group.clone(), item.backup_time)?;
 
         let backup_time = snapshot.backup_time();
 
@@ -499,10 +517,17 @@ pub async fn pull_group(
             }
         }
 
+        // the last synced snapshot is always re-synced (to get the client log)
+        if pos < cutoff && last_sync != Some(backup_time) {
+            skipped_older += 1;
+            continue;
+        }
+
         // get updated auth_info (new tickets)
         let auth_info = client.login().await?;
 
-        let options = HttpClientOptions::new_non_interactive(auth_info.ticket.clone(), fingerprint.clone());
+        let options = HttpClientOptions::new_non_interactive(auth_info.ticket.clone(), fingerprint.clone())
+            .rate_limit(rate_limit.clone());
 
         let new_client = HttpClient::new(
             src_repo.host(),
@@ -515,6 +540,7 @@ pub async fn pull_group(
             new_client,
             None,
             src_repo.store(),
+            remote_ns,
             snapshot.group().backup_type(),
             snapshot.group().backup_id(),
             backup_time,
@@ -537,6 +563,14 @@ pub async fn pull_group(
         result?; // stop on error
     }
 
+    if skipped_older > 0 {
+        worker.log(format!(
+            "skipped {} older snapshot(s) - only the last {} snapshot(s) are transferred",
+            skipped_older,
+            transfer_last.unwrap_or(0),
+        ));
+    }
+
     if delete {
         let local_list = group.list_backups(&tgt_store.base_path())?;
         for info in local_list {
@@ -544,6 +578,13 @@ pub async fn pull_group(
             if remote_snapshots.contains(&backup_time) {
                 continue;
             }
+            if info.protected {
+                worker.log(format!(
+                    "don't delete vanished snapshot {:?} (protected)",
+                    info.backup_dir.relative_path()
+                ));
+                continue;
+            }
             worker.log(format!(
                 "delete vanished snapshot {:?}",
                 info.backup_dir.relative_path()
@@ -555,27 +596,191 @@ pub async fn pull_group(
     Ok(())
 }
 
+/// Split the sorted snapshot `list` into finished and in-progress snapshots.
+///
+/// Also returns the position of the first finished snapshot among the last `transfer_last`
+/// ones. In-progress snapshots can't be transferred, so they must not take one of those slots.
+pub fn split_transfer_list<T>(
+    list: Vec<T>,
+    is_finished: impl Fn(&T) -> bool,
+    transfer_last: Option<usize>,
+) -> (Vec<T>, Vec<T>, usize) {
+    let (finished, in_progress): (Vec<T>, Vec<T>) =
+        list.into_iter().partition(|item| is_finished(item));
+
+    let cutoff = transfer_last
+        .map(|count| finished.len().saturating_sub(count))
+        .unwrap_or(0);
+
+    (finished, in_progress, cutoff)
+}
+
+/// Parameters for a pull operation
+pub struct PullParameters {
+    /// Remote namespace to pull from
+    pub remote_ns: BackupNamespace,
+    /// Local namespace to pull into
+    pub ns: BackupNamespace,
+    /// How many levels of namespaces below `remote_ns` are included (`None` == unlimited)
+    pub max_depth: Option<usize>,
+    /// Remove local groups and snapshots which vanished on the remote
+    pub delete: bool,
+    /// Owner of newly created backup groups
+    pub owner: Authid,
+    /// Only sync (and remove) groups passing these filters
+    pub group_filter: Option<Vec<GroupFilter>>,
+    /// Only transfer the last N snapshots of each group
+    pub transfer_last: Option<usize>,
+}
+
+// Returns the remote namespaces to sync, and the depth up to which they were listed.
+async fn list_remote_namespaces(
+    worker: &WorkerTask,
+    client: &HttpClient,
+    src_repo: &BackupRepository,
+    params: &PullParameters,
+) -> Result<(Vec<BackupNamespace>, Option<usize>), Error> {
+    if params.max_depth == Some(0) {
+        return Ok((vec![params.remote_ns.clone()], Some(0)));
+    }
+
+    let path = format!("api2/json/admin/datastore/{}/namespace", src_repo.store());
+
+    let mut args = json!({});
+    if !params.remote_ns.is_root() {
+        args["parent"] = params.remote_ns.to_string().into();
+    }
+    if let Some(max_depth) = params.max_depth {
+        args["max-depth"] = max_depth.into();
+    }
+
+    let mut result = match client.get(&path, Some(args)).await {
+        Ok(result) => result,
+        Err(err) => match err.downcast_ref::<HttpError>() {
+            // remote without namespace support, only has the root namespace
+            Some(HttpError { code, .. }) if *code == StatusCode::NOT_FOUND
+                && params.remote_ns.is_root() => {
+                worker.log("remote does not support namespaces, only syncing the root namespace");
+                return Ok((vec![params.remote_ns.clone()], Some(0)));
+            }
+            _ => bail!("Failed to retrieve namespaces from remote - {}", err),
+        },
+    };
+
+    let list: Vec<NamespaceListItem> = serde_json::from_value(result["data"].take())?;
+
+    Ok((list.into_iter().map(|item| item.ns).collect(), params.max_depth))
+}
+
 pub async fn pull_store(
     worker: &WorkerTask,
     client: &HttpClient,
     src_repo: &BackupRepository,
     tgt_store: Arc<DataStore>,
-    delete: bool,
-    auth_id: Authid,
+    params: &PullParameters,
 ) -> Result<(), Error> {
     // explicit create shared lock to prevent GC on newly created chunks
     let _shared_store_lock = tgt_store.try_shared_chunk_store_lock()?;
 
+    let (namespaces, max_depth) = list_remote_namespaces(worker, client, src_repo, params).await?;
+
+    let mut errors = false;
+    let mut synced_ns = HashSet::new();
+
+    for remote_ns in namespaces {
+        let target_ns = remote_ns.map_prefix(&params.remote_ns, &params.ns)?;
+
+        if let Err(err) = tgt_store.create_namespace_recursive(&target_ns) {
+            worker.log(format!("sync namespace '{}' failed - {}", target_ns, err));
+            errors = true;
+            continue;
+        }
+
+        if !remote_ns.is_root() || !target_ns.is_root() {
+            worker.log(format!(
+                "sync namespace '{}' into '{}'",
+                remote_ns,
+                target_ns,
+            ));
+        }
+
+        match pull_ns(worker, client, src_repo, tgt_store.clone(), &remote_ns, &target_ns, params).await {
+            Ok(true) => errors = true,
+            Ok(false) => {}
+            Err(err) => {
+                worker.log(format!("sync namespace '{}' failed - {}", remote_ns, err));
+                errors = true;
+            }
+        }
+
+        synced_ns.insert(target_ns);
+    }
+
+    if params.delete && !errors {
+        let result: Result<(), Error> = proxmox::try_block!({
+            let local_ns = params.ns.list_recursive(&tgt_store.base_path(), max_depth)?;
+            // children are listed after their parents, remove them first
+            for local_ns in local_ns.into_iter().rev() {
+                if synced_ns.contains(&local_ns) || local_ns == params.ns {
+                    continue;
+                }
+                worker.log(format!("delete vanished namespace '{}'", local_ns));
+                if let Err(err) = tgt_store.remove_namespace_recursive(&local_ns, true) {
+                    worker.log(err.to_string());
+                    errors = true;
+                }
+            }
+            Ok(())
+        });
+        if let Err(err) = result {
+            worker.log(format!("error during namespace cleanup: {}", err));
+            errors = true;
+        };
+    }
+
+    if errors {
+        bail!("sync failed with some errors.");
+    }
+
+    Ok(())
+}
+
+/// Pulls all groups of `remote_ns` into `target_ns`
+///
+/// Returns `Ok(true)` if some groups failed to sync.
+async fn pull_ns(
+    worker: &WorkerTask,
+    client: &HttpClient,
+    src_repo: &BackupRepository,
+    tgt_store: Arc<DataStore>,
+    remote_ns: &BackupNamespace,
+    target_ns: &BackupNamespace,
+    params: &PullParameters,
+) -> Result<bool, Error> {
     let path = format!("api2/json/admin/datastore/{}/groups", src_repo.store());
 
+    let args = if remote_ns.is_root() {
+        None
+    } else {
+        Some(json!({ "ns": remote_ns.to_string() }))
+    };
+
     let mut result = client
-        .get(&path, None)
+        .get(&path, args)
         .await
         .map_err(|err| format_err!("Failed to retrieve backup groups from remote - {}", err))?;
 
     let mut list: Vec<GroupListItem> = serde_json::from_value(result["data"].take())?;
 
-    worker.log(format!("found {} groups to sync", list.len()));
+    let total_count = list.len();
+    if let Some(ref group_filter) = params.group_filter {
+        list.retain(|item| {
+            BackupGroup::new(&item.backup_type, &item.backup_id).apply_filters(group_filter)
+        });
+        worker.log(format!("found {} groups to sync (out of {} total)", list.len(), total_count));
+    } else {
+        worker.log(format!("found {} groups to sync", total_count));
+    }
 
     list.sort_unstable_by(|a, b| {
         let type_order = a.backup_type.cmp(&b.backup_type);
@@ -590,7 +795,7 @@ pub async fn pull_store(
 
     let mut new_groups = std::collections::HashSet::new();
     for item in list.iter() {
-        new_groups.insert(BackupGroup::new(&item.backup_type, &item.backup_id));
+        new_groups.insert(BackupGroup::with_ns(target_ns.clone(), &item.backup_type, &item.backup_id));
     }
 
     let mut progress = StoreProgress::new(list.len() as u64);
@@ -600,14 +805,14 @@ pub async fn pull_store(
         progress.done_snapshots = 0;
         progress.group_snapshots = 0;
 
-        let group = BackupGroup::new(&item.backup_type, &item.backup_id);
+        let group = BackupGroup::with_ns(target_ns.clone(), &item.backup_type, &item.backup_id);
 
-        let (owner, _lock_guard) = match tgt_store.create_locked_backup_group(&group, &auth_id) {
+        let (owner, _lock_guard) = match tgt_store.create_locked_backup_group(&group, &params.owner) {
             Ok(result) => result,
             Err(err) => {
                 worker.log(format!(
-                    "sync group {}/{} failed - group lock failed: {}",
-                    item.backup_type, item.backup_id, err
+                    "sync group {} failed - group lock failed: {}",
+                    group, err
                 ));
                 errors = true; // do not stop here, instead continue
                 continue;
@@ -615,11 +820,11 @@ pub async fn pull_store(
         };
 
         // permission check
-        if auth_id != owner {
+        if params.owner != owner {
             // only the owner is allowed to create additional snapshots
             worker.log(format!(
-                "sync group {}/{} failed - owner check failed ({} != {})",
-                item.backup_type, item.backup_id, auth_id, owner
+                "sync group {} failed - owner check failed ({} != {})",
+                group, params.owner, owner
             ));
             errors = true; // do not stop here, instead continue
         } else if let Err(err) = pull_group(
@@ -627,35 +832,50 @@ pub async fn pull_store(
             client,
             src_repo,
             tgt_store.clone(),
+            remote_ns,
             &group,
-            delete,
+            params.delete,
+            params.transfer_last,
             &mut progress,
         )
         .await
         {
             worker.log(format!(
-                "sync group {}/{} failed - {}",
-                item.backup_type, item.backup_id, err,
+                "sync group {} failed - {}",
+                group, err,
             ));
             errors = true; // do not stop here, instead continue
         }
     }
 
-    if delete {
+    if params.delete {
         let result: Result<(), Error> = proxmox::try_block!({
-            let local_groups = BackupInfo::list_backup_groups(&tgt_store.base_path())?;
+            let local_groups = BackupInfo::list_backup_groups(&tgt_store.base_path(), target_ns)?;
             for local_group in local_groups {
                 if new_groups.contains(&local_group) {
                     continue;
                 }
+                if let Some(ref group_filter) = params.group_filter {
+                    if !local_group.apply_filters(group_filter) {
+                        continue;
+                    }
+                }
                 worker.log(format!(
-                    "delete vanished group '{}/{}'",
-                    local_group.backup_type(),
-                    local_group.backup_id()
+                    "delete vanished group '{}'",
+                    local_group,
                 ));
-                if let Err(err) = tgt_store.remove_backup_group(&local_group) {
-                    worker.log(err.to_string());
-                    errors = true;
+                match tgt_store.remove_backup_group(&local_group) {
+                    Ok(true) => {},
+                    Ok(false) => {
+                        worker.log(format!(
+                            "kept some protected snapshots of group '{}'",
+                            local_group,
+                        ));
+                    },
+                    Err(err) => {
+                        worker.log(err.to_string());
+                        errors = true;
+                    },
                 }
             }
             Ok(())
@@ -666,9 +886,35 @@ pub async fn pull_store(
         };
     }
 
-    if errors {
-        bail!("sync failed with some errors.");
-    }
+    Ok(errors)
+}
 
-    Ok(())
+#[test]
+fn test_split_transfer_list() {
+    // (backup time, finished)
+    let list = vec![(1, true), (2, true), (3, true), (4, false)];
+    let is_finished = |item: &(i64, bool)| item.1;
+
+    let (finished, in_progress, cutoff) = split_transfer_list(list.clone(), is_finished, None);
+    assert_eq!(finished.len(), 3);
+    assert_eq!(in_progress, vec![(4, false)]);
+    assert_eq!(cutoff, 0);
+
+    // the in-progress newest snapshot doesn't take one of the slots
+    let (finished, _, cutoff) = split_transfer_list(list.clone(), is_finished, Some(2));
+    assert_eq!(&finished[cutoff..], &[(2, true), (3, true)]);
+
+    // count larger than the list
+    let (finished, _, cutoff) = split_transfer_list(list.clone(), is_finished, Some(10));
+    assert_eq!(cutoff, 0);
+    assert_eq!(finished.len(), 3);
+
+    // count 0 transfers nothing
+    let (finished, _, cutoff) = split_transfer_list(list, is_finished, Some(0));
+    assert_eq!(cutoff, finished.len());
+
+    let (finished, in_progress, cutoff) =
+        split_transfer_list(Vec::<(i64, bool)>::new(), is_finished, Some(1));
+    assert!(finished.is_empty() && in_progress.is_empty());
+    assert_eq!(cutoff, 0);
 }
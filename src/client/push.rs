@@ -0,0 +1,659 @@
+//! Sync datastore to remote server
+
+use anyhow::{bail, format_err, Error};
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::{
+    api2::types::*,
+    backup::*,
+    client::*,
+    server::WorkerTask,
+    tools::fs::lock_dir_noblock_shared,
+};
+use proxmox::api::error::{HttpError, StatusCode};
+
+/// Parameters for a push operation
+pub struct PushParameters {
+    /// Local namespace to push from
+    pub ns: BackupNamespace,
+    /// Remote namespace to push into
+    pub remote_ns: BackupNamespace,
+    /// How many levels of namespaces below `ns` are included (`None` == unlimited)
+    pub max_depth: Option<usize>,
+    /// Remove remote groups and snapshots which vanished locally
+    pub delete: bool,
+    /// Only sync (and remove) groups passing these filters
+    pub group_filter: Option<Vec<GroupFilter>>,
+    /// Only transfer the last N snapshots of each group
+    pub transfer_last: Option<usize>,
+}
+
+// create a new client with the current ticket, BackupWriter::start consumes it
+async fn new_writer_client(
+    client: &HttpClient,
+    tgt_repo: &BackupRepository,
+) -> Result<HttpClient, Error> {
+    // get updated auth_info (new tickets)
+    let auth_info = client.login().await?;
+
+    let options = HttpClientOptions::new_non_interactive(auth_info.ticket.clone(), client.fingerprint())
+        .rate_limit(client.rate_limit());
+
+    HttpClient::new(
+        tgt_repo.host(),
+        tgt_repo.port(),
+        tgt_repo.auth_id(),
+        options,
+    )
+}
+
+fn ns_args(ns: &BackupNamespace, mut args: serde_json::Value) -> serde_json::Value {
+    if !ns.is_root() {
+        args["ns"] = ns.to_string().into();
+    }
+    args
+}
+
+// Returns the existing remote namespaces below (and including) `remote_ns`,
+// or `None` if the remote does not support namespaces.
+async fn list_remote_namespaces(
+    client: &HttpClient,
+    tgt_repo: &BackupRepository,
+    remote_ns: &BackupNamespace,
+) -> Result<Option<HashSet<BackupNamespace>>, Error> {
+    let path = format!("api2/json/admin/datastore/{}/namespace", tgt_repo.store());
+
+    let mut args = json!({});
+    if !remote_ns.is_root() {
+        args["parent"] = remote_ns.to_string().into();
+    }
+
+    let mut result = match client.get(&path, Some(args)).await {
+        Ok(result) => result,
+        Err(err) => match err.downcast_ref::<HttpError>() {
+            Some(HttpError { code, .. }) if *code == StatusCode::NOT_FOUND
+                && remote_ns.is_root() => return Ok(None),
+            _ => bail!("Failed to retrieve namespaces from remote - {}", err),
+        },
+    };
+
+    let list: Vec<NamespaceListItem> = serde_json::from_value(result["data"].take())?;
+
+    Ok(Some(list.into_iter().map(|item| item.ns).collect()))
+}
+
+async fn create_remote_namespace(
+    client: &mut HttpClient,
+    tgt_repo: &BackupRepository,
+    ns: &BackupNamespace,
+) -> Result<(), Error> {
+    let parent = match ns.parent() {
+        Some(parent) => parent,
+        None => return Ok(()), // the root namespace always exists
+    };
+
+    let path = format!("api2/json/admin/datastore/{}/namespace", tgt_repo.store());
+
+    let mut args = json!({ "name": ns.name() });
+    if !parent.is_root() {
+        args["parent"] = parent.to_string().into();
+    }
+
+    client.post(&path, Some(args)).await?;
+
+    Ok(())
+}
+
+async fn push_index_chunks<I: IndexFile + Send>(
+    worker: &WorkerTask,
+    writer: &BackupWriter,
+    src_store: Arc<DataStore>,
+    archive_name: &str,
+    index: I,
+    known_chunks: Arc<Mutex<HashSet<[u8; 32]>>>,
+) -> Result<(), Error> {
+    let start_time = SystemTime::now();
+
+    let (stats, bytes) = writer
+        .upload_index_chunk_info(archive_name, index, known_chunks, move |digest| {
+            crate::tools::runtime::block_in_place(|| src_store.load_chunk(digest))
+        })
+        .await?;
+
+    let elapsed = start_time.elapsed()?.as_secs_f64();
+
+    worker.log(format!(
+        "uploaded {} of {} bytes ({:.2} MiB/s)",
+        bytes,
+        stats.size,
+        (bytes as f64) / (1024.0 * 1024.0 * elapsed)
+    ));
+
+    Ok(())
+}
+
+async fn push_snapshot(
+    worker: &WorkerTask,
+    writer: &BackupWriter,
+    src_store: Arc<DataStore>,
+    snapshot: &BackupDir,
+) -> Result<(), Error> {
+    let (manifest, _) = src_store.load_manifest(snapshot)?;
+
+    // the target can only verify chunks compressed with a dictionary it knows
+    let known_dictionaries = writer.known_zstd_dictionaries().await?;
+    for dict in src_store.list_zstd_dictionaries()? {
+        if !known_dictionaries.contains(dict.id()) {
+            worker.log(format!("upload zstd dictionary {}", dict.id_str()));
+            writer.upload_zstd_dictionary(&dict).await?;
+        }
+    }
+
+    // register the chunks of the previous remote snapshot, those need no upload
+    let previous_manifest = match writer.download_previous_manifest().await {
+        Ok(manifest) => Some(manifest),
+        Err(_) => None,
+    };
+
+    let mut path = src_store.base_path();
+    path.push(snapshot.relative_path());
+
+    for item in manifest.files() {
+        let mut archive_path = path.clone();
+        archive_path.push(&item.filename);
+
+        worker.log(format!("sync archive {}", item.filename));
+
+        let known_chunks = Arc::new(Mutex::new(HashSet::new()));
+
+        match archive_type(&item.filename)? {
+            ArchiveType::DynamicIndex => {
+                if let Some(ref previous_manifest) = previous_manifest {
+                    // try, but ignore errors
+                    let _ = writer
+                        .download_previous_dynamic_index(&item.filename, previous_manifest, known_chunks.clone())
+                        .await;
+                }
+                let index = DynamicIndexReader::open(&archive_path)?;
+                let (csum, size) = index.compute_csum();
+                manifest.verify_file(&item.filename, &csum, size)?;
+
+                push_index_chunks(worker, writer, src_store.clone(), &item.filename, index, known_chunks).await?;
+            }
+            ArchiveType::FixedIndex => {
+                if let Some(ref previous_manifest) = previous_manifest {
+                    // try, but ignore errors
+                    let _ = writer
+                        .download_previous_fixed_index(&item.filename, previous_manifest, known_chunks.clone())
+                        .await;
+                }
+                let index = FixedIndexReader::open(&archive_path)?;
+                let (csum, size) = index.compute_csum();
+                manifest.verify_file(&item.filename, &csum, size)?;
+
+                push_index_chunks(worker, writer, src_store.clone(), &item.filename, index, known_chunks).await?;
+            }
+            ArchiveType::Blob => {
+                let file = std::fs::File::open(&archive_path)
+                    .map_err(|err| format_err!("unable to open {:?} - {}", archive_path, err))?;
+                let stats = writer.upload_blob(file, &item.filename).await?;
+                manifest.verify_file(&item.filename, &stats.csum, stats.size)?;
+            }
+        }
+    }
+
+    // upload the unmodified manifest last, so that signatures stay valid
+    let mut manifest_path = path.clone();
+    manifest_path.push(MANIFEST_BLOB_NAME);
+    let manifest_file = std::fs::File::open(&manifest_path)
+        .map_err(|err| format_err!("unable to open {:?} - {}", manifest_path, err))?;
+    writer.upload_blob(manifest_file, MANIFEST_BLOB_NAME).await?;
+
+    Ok(())
+}
+
+// Note: The client.log.blob is uploaded after the backup, so it is
+// not mentioned in the manifest.
+async fn try_client_log_upload(
+    worker: &WorkerTask,
+    client: &mut HttpClient,
+    tgt_repo: &BackupRepository,
+    remote_ns: &BackupNamespace,
+    src_store: &DataStore,
+    snapshot: &BackupDir,
+) -> Result<(), Error> {
+    let mut path = src_store.base_path();
+    path.push(snapshot.relative_path());
+    path.push(CLIENT_LOG_BLOB_NAME);
+
+    // Note: be silent if there is no log - only log successful upload
+    if !path.exists() {
+        return Ok(());
+    }
+    let raw_data = std::fs::read(&path)?;
+
+    let api_path = format!("api2/json/admin/datastore/{}/upload-backup-log", tgt_repo.store());
+    let args = ns_args(remote_ns, json!({
+        "backup-type": snapshot.group().backup_type(),
+        "backup-id": snapshot.group().backup_id(),
+        "backup-time": snapshot.backup_time(),
+    }));
+
+    client
+        .upload("application/octet-stream", hyper::Body::from(raw_data), &api_path, Some(args))
+        .await?;
+
+    worker.log(format!("uploaded backup log file {:?}", CLIENT_LOG_BLOB_NAME));
+
+    Ok(())
+}
+
+/// Pushes the local group `group` into the remote group of the same name in `remote_ns`.
+#[allow(clippy::too_many_arguments)]
+pub async fn push_group(
+    worker: &WorkerTask,
+    client: &mut HttpClient,
+    tgt_repo: &BackupRepository,
+    src_store: Arc<DataStore>,
+    remote_ns: &BackupNamespace,
+    group: &BackupGroup,
+    delete: bool,
+    transfer_last: Option<usize>,
+    progress: &mut StoreProgress,
+) -> Result<(), Error> {
+    let path = format!("api2/json/admin/datastore/{}/snapshots", tgt_repo.store());
+
+    let args = ns_args(remote_ns, json!({
+        "backup-type": group.backup_type(),
+        "backup-id": group.backup_id(),
+    }));
+
+    let mut result = client.get(&path, Some(args)).await?;
+    let remote_list: Vec<SnapshotListItem> = serde_json::from_value(result["data"].take())?;
+
+    let last_remote_time = remote_list.iter().map(|item| item.backup_time).max();
+
+    let mut list = group.list_backups(&src_store.base_path())?;
+    BackupInfo::sort_list(&mut list, true);
+
+    let mut local_snapshots = HashSet::new();
+
+    let (list, in_progress, cutoff) =
+        super::pull::split_transfer_list(list, BackupInfo::is_finished, transfer_last);
+
+    // in-progress backups can't be synced
+    for info in in_progress {
+        worker.log(format!(
+            "skipping snapshot {} - in-progress backup",
+            info.backup_dir
+        ));
+        local_snapshots.insert(info.backup_dir.backup_time());
+    }
+
+    progress.group_snapshots = list.len() as u64;
+
+    let mut skipped_older = 0;
+
+    for (pos, info) in list.into_iter().enumerate() {
+        let snapshot = info.backup_dir;
+        let backup_time = snapshot.backup_time();
+
+        local_snapshots.insert(backup_time);
+
+        // the remote only accepts snapshots newer than its last one
+        if let Some(last_remote_time) = last_remote_time {
+            if last_remote_time >= backup_time {
+                continue;
+            }
+        }
+
+        if pos < cutoff {
+            skipped_older += 1;
+            continue;
+        }
+
+        let snapshot_path = src_store.snapshot_path(&snapshot);
+        let _guard = match lock_dir_noblock_shared(&snapshot_path, "snapshot", "locked by another operation") {
+            Ok(guard) => guard,
+            Err(err) => {
+                // pruned since we listed the group
+                if !snapshot_path.exists() {
+                    worker.log(format!("skipping snapshot {} - vanished since start of sync", snapshot));
+                    continue;
+                }
+                return Err(err);
+            }
+        };
+
+        worker.log(format!("sync snapshot {:?}", snapshot.relative_path()));
+
+        let writer = BackupWriter::start(
+            new_writer_client(client, tgt_repo).await?,
+            None,
+            tgt_repo.store(),
+            remote_ns,
+            group.backup_type(),
+            group.backup_id(),
+            backup_time,
+            false,
+            false,
+        )
+        .await?;
+
+        let result = push_snapshot(worker, &writer, src_store.clone(), &snapshot).await;
+
+        match result {
+            Ok(()) => writer.finish().await?,
+            Err(err) => {
+                writer.cancel();
+                return Err(err); // stop on error
+            }
+        }
+
+        try_client_log_upload(worker, client, tgt_repo, remote_ns, &src_store, &snapshot).await?;
+
+        worker.log(format!("sync snapshot {:?} done", snapshot.relative_path()));
+
+        progress.done_snapshots = pos as u64 + 1;
+        worker.log(format!("percentage done: {}", progress));
+    }
+
+    if skipped_older > 0 {
+        worker.log(format!(
+            "skipped {} older snapshot(s) - only the last {} snapshot(s) are transferred",
+            skipped_older,
+            transfer_last.unwrap_or(0),
+        ));
+    }
+
+    if delete {
+        let remote_group = BackupGroup::with_ns(remote_ns.clone(), group.backup_type(), group.backup_id());
+        for item in remote_list {
+            if local_snapshots.contains(&item.backup_time) {
+                continue;
+            }
+            let snapshot = BackupDir::with_group(remote_group.clone(), item.backup_time)?;
+            if item.protected {
+                worker.log(format!(
+                    "don't delete vanished snapshot {:?} (protected)",
+                    snapshot.relative_path()
+                ));
+                continue;
+            }
+            worker.log(format!(
+                "delete vanished snapshot {:?}",
+                snapshot.relative_path()
+            ));
+            let args = ns_args(remote_ns, json!({
+                "backup-type": group.backup_type(),
+                "backup-id": group.backup_id(),
+                "backup-time": item.backup_time,
+            }));
+            client.delete(&path, Some(args)).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pushes all groups of the local namespaces below `params.ns` into the remote
+/// namespaces below `params.remote_ns`.
+pub async fn push_store(
+    worker: &WorkerTask,
+    client: &mut HttpClient,
+    tgt_repo: &BackupRepository,
+    src_store: Arc<DataStore>,
+    params: &PushParameters,
+) -> Result<(), Error> {
+    if !src_store.namespace_exists(&params.ns) {
+        bail!("namespace '{}' does not exist", params.ns);
+    }
+
+    let namespaces = params.ns.list_recursive(&src_store.base_path(), params.max_depth)?;
+
+    let remote_namespaces = list_remote_namespaces(client, tgt_repo, &params.remote_ns).await?;
+    if remote_namespaces.is_none() {
+        worker.log("remote does not support namespaces, only syncing into the root namespace");
+    }
+
+    let mut errors = false;
+    let mut synced_ns = HashSet::new();
+
+    for local_ns in namespaces {
+        let target_ns = local_ns.map_prefix(&params.ns, &params.remote_ns)?;
+
+        let exists = match remote_namespaces {
+            Some(ref list) => target_ns.is_root() || list.contains(&target_ns),
+            None => target_ns.is_root(),
+        };
+
+        if !exists {
+            let result = match remote_namespaces {
+                Some(_) => create_remote_namespace(client, tgt_repo, &target_ns).await,
+                None => Err(format_err!("remote does not support namespaces")),
+            };
+            if let Err(err) = result {
+                worker.log(format!("sync namespace '{}' failed - {}", target_ns, err));
+                errors = true;
+                continue;
+            }
+        }
+
+        if !local_ns.is_root() || !target_ns.is_root() {
+            worker.log(format!(
+                "sync namespace '{}' into '{}'",
+                local_ns,
+                target_ns,
+            ));
+        }
+
+        match push_ns(worker, client, tgt_repo, src_store.clone(), &local_ns, &target_ns, params).await {
+            Ok(true) => errors = true,
+            Ok(false) => {}
+            Err(err) => {
+                worker.log(format!("sync namespace '{}' failed - {}", local_ns, err));
+                errors = true;
+            }
+        }
+
+        synced_ns.insert(target_ns);
+    }
+
+    if params.delete && !errors {
+        if let Some(remote_namespaces) = remote_namespaces {
+            let mut vanished: Vec<BackupNamespace> = remote_namespaces
+                .into_iter()
+                .filter(|ns| {
+                    !synced_ns.contains(ns)
+                        && ns != &params.remote_ns
+                        && params.max_depth.map_or(true, |max_depth| {
+                            ns.depth() - params.remote_ns.depth() <= max_depth
+                        })
+                })
+                .collect();
+            // remove children before their parents
+            vanished.sort_unstable_by(|a, b| b.depth().cmp(&a.depth()));
+
+            let path = format!("api2/json/admin/datastore/{}/namespace", tgt_repo.store());
+            for ns in vanished {
+                worker.log(format!("delete vanished namespace '{}'", ns));
+                let args = json!({ "ns": ns.to_string(), "delete-groups": true });
+                if let Err(err) = client.delete(&path, Some(args)).await {
+                    worker.log(format!("delete namespace '{}' failed - {}", ns, err));
+                    errors = true;
+                }
+            }
+        }
+    }
+
+    if errors {
+        bail!("sync failed with some errors.");
+    }
+
+    Ok(())
+}
+
+// Select the remote groups which vanished locally and may be removed: only
+// groups owned by the sync user and passing the group filter are considered.
+fn vanished_remote_groups(
+    remote_groups: Vec<GroupListItem>,
+    local_groups: &HashSet<(String, String)>,
+    owner: &Authid,
+    remote_ns: &BackupNamespace,
+    group_filter: Option<&[GroupFilter]>,
+) -> Vec<BackupGroup> {
+    remote_groups
+        .into_iter()
+        .filter(|item| !local_groups.contains(&(item.backup_type.clone(), item.backup_id.clone())))
+        // only remove groups created by this sync
+        .filter(|item| item.owner.as_ref() == Some(owner))
+        .map(|item| BackupGroup::with_ns(remote_ns.clone(), item.backup_type, item.backup_id))
+        .filter(|group| match group_filter {
+            Some(group_filter) => group.apply_filters(group_filter),
+            None => true,
+        })
+        .collect()
+}
+
+/// Pushes all groups of `local_ns` into `remote_ns`
+///
+/// Returns `Ok(true)` if some groups failed to sync.
+async fn push_ns(
+    worker: &WorkerTask,
+    client: &mut HttpClient,
+    tgt_repo: &BackupRepository,
+    src_store: Arc<DataStore>,
+    local_ns: &BackupNamespace,
+    remote_ns: &BackupNamespace,
+    params: &PushParameters,
+) -> Result<bool, Error> {
+    let mut list = BackupInfo::list_backup_groups(&src_store.base_path(), local_ns)?;
+
+    let total_count = list.len();
+    if let Some(ref group_filter) = params.group_filter {
+        list.retain(|group| group.apply_filters(group_filter));
+        worker.log(format!("found {} groups to sync (out of {} total)", list.len(), total_count));
+    } else {
+        worker.log(format!("found {} groups to sync", total_count));
+    }
+
+    list.sort_unstable_by(|a, b| {
+        let type_order = a.backup_type().cmp(b.backup_type());
+        if type_order == std::cmp::Ordering::Equal {
+            a.backup_id().cmp(b.backup_id())
+        } else {
+            type_order
+        }
+    });
+
+    let path = format!("api2/json/admin/datastore/{}/groups", tgt_repo.store());
+    let args = ns_args(remote_ns, json!({}));
+
+    let mut result = client
+        .get(&path, Some(args))
+        .await
+        .map_err(|err| format_err!("Failed to retrieve backup groups from remote - {}", err))?;
+
+    let remote_groups: Vec<GroupListItem> = serde_json::from_value(result["data"].take())?;
+
+    let mut errors = false;
+
+    let mut local_groups = HashSet::new();
+    for group in list.iter() {
+        local_groups.insert((group.backup_type().to_string(), group.backup_id().to_string()));
+    }
+
+    let mut progress = StoreProgress::new(list.len() as u64);
+
+    for (done, local_group) in list.into_iter().enumerate() {
+        progress.done_groups = done as u64;
+        progress.done_snapshots = 0;
+        progress.group_snapshots = 0;
+
+        if let Err(err) = push_group(
+            worker,
+            client,
+            tgt_repo,
+            src_store.clone(),
+            remote_ns,
+            &local_group,
+            params.delete,
+            params.transfer_last,
+            &mut progress,
+        )
+        .await
+        {
+            worker.log(format!(
+                "sync group {} failed - {}",
+                local_group, err,
+            ));
+            errors = true; // do not stop here, instead continue
+        }
+    }
+
+    if params.delete {
+        let vanished = vanished_remote_groups(
+            remote_groups,
+            &local_groups,
+            tgt_repo.auth_id(),
+            remote_ns,
+            params.group_filter.as_deref(),
+        );
+        for group in vanished {
+            worker.log(format!("delete vanished group '{}'", group));
+            let args = ns_args(remote_ns, json!({
+                "backup-type": group.backup_type(),
+                "backup-id": group.backup_id(),
+            }));
+            if let Err(err) = client.delete(&path, Some(args)).await {
+                worker.log(format!("delete group '{}' failed - {}", group, err));
+                errors = true;
+            }
+        }
+    }
+
+    Ok(errors)
+}
+
+#[test]
+fn test_vanished_remote_groups() -> Result<(), Error> {
+    let owner: Authid = "sync@pbs".parse()?;
+    let other: Authid = "other@pbs".parse()?;
+    let remote_ns = BackupNamespace::new("a/b")?;
+
+    let item = |backup_type: &str, backup_id: &str, owner: &Authid| GroupListItem {
+        backup_type: backup_type.to_string(),
+        backup_id: backup_id.to_string(),
+        last_backup: 0,
+        backup_count: 1,
+        files: Vec::new(),
+        owner: Some(owner.clone()),
+    };
+
+    let remote_groups = || vec![
+        item("vm", "100", &owner), // still exists locally
+        item("vm", "101", &owner), // vanished
+        item("vm", "102", &other), // vanished, but not owned by the sync user
+        item("ct", "200", &owner), // vanished
+    ];
+
+    let mut local_groups = HashSet::new();
+    local_groups.insert(("vm".to_string(), "100".to_string()));
+
+    let vanished = vanished_remote_groups(remote_groups(), &local_groups, &owner, &remote_ns, None);
+    assert_eq!(vanished.len(), 2);
+    assert_eq!((vanished[0].backup_type(), vanished[0].backup_id()), ("vm", "101"));
+    assert_eq!((vanished[1].backup_type(), vanished[1].backup_id()), ("ct", "200"));
+    assert!(vanished.iter().all(|group| group.ns() == &remote_ns));
+
+    // groups excluded by the filter are never removed
+    let filter: Vec<GroupFilter> = vec!["type:vm".parse()?];
+    let vanished = vanished_remote_groups(remote_groups(), &local_groups, &owner, &remote_ns, Some(&filter));
+    assert_eq!(vanished.len(), 1);
+    assert_eq!(vanished[0].backup_id(), "101");
+
+    Ok(())
+}
@@ -13,6 +13,7 @@ use nix::fcntl::OFlag;
 use nix::sys::stat::Mode;
 
 use crate::backup::CatalogWriter;
+use crate::pxar::PreviousArchive;
 
 /// Stream implementation to encode and upload .pxar archives.
 ///
@@ -37,6 +38,7 @@ impl PxarBackupStream {
         dir: Dir,
         catalog: Arc<Mutex<CatalogWriter<W>>>,
         options: crate::pxar::PxarCreateOptions,
+        previous: Option<PreviousArchive>,
     ) -> Result<Self, Error> {
         let (tx, rx) = std::sync::mpsc::sync_channel(10);
 
@@ -65,6 +67,7 @@ impl PxarBackupStream {
                 },
                 Some(catalog),
                 options,
+                previous,
             ).await {
                 let mut error = error2.lock().unwrap();
                 *error = Some(err.to_string());
@@ -86,6 +89,7 @@ impl PxarBackupStream {
         dirname: &Path,
         catalog: Arc<Mutex<CatalogWriter<W>>>,
         options: crate::pxar::PxarCreateOptions,
+        previous: Option<PreviousArchive>,
     ) -> Result<Self, Error> {
         let dir = nix::dir::Dir::open(dirname, OFlag::O_DIRECTORY, Mode::empty())?;
 
@@ -93,6 +97,7 @@ impl PxarBackupStream {
             dir,
             catalog,
             options,
+            previous,
         )
     }
 }
@@ -49,6 +49,10 @@ impl RemoteChunkReader {
 
         let chunk = DataBlob::load_from_reader(&mut &chunk_data[..])?;
 
+        if let Some(id) = chunk.dictionary_id() {
+            self.client.ensure_zstd_dictionary(&id).await?;
+        }
+
         match self.crypt_mode {
             CryptMode::Encrypt => {
                 match chunk.crypt_mode()? {
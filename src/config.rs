@@ -8,17 +8,22 @@ use std::path::PathBuf;
 use nix::sys::stat::Mode;
 use openssl::rsa::{Rsa};
 use openssl::x509::{X509Builder};
-use openssl::pkey::PKey;
+use openssl::pkey::{PKey, Private};
+use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod};
 
-use proxmox::tools::fs::{CreateOptions, replace_file};
+use proxmox::tools::fs::{open_file_locked, CreateOptions, replace_file};
 use proxmox::try_block;
 
 use crate::buildcfg;
 
 pub mod acl;
+pub mod acme;
 pub mod cached_user_info;
 pub mod datastore;
+pub mod domains;
+pub mod metrics;
 pub mod network;
+pub mod notifications;
 pub mod remote;
 pub mod sync;
 pub mod tfa;
@@ -29,6 +34,10 @@ pub mod drive;
 pub mod media_pool;
 pub mod tape_encryption_keys;
 pub mod tape_job;
+pub mod traffic_control;
+pub mod prune;
+pub mod s3;
+pub mod quota;
 
 /// Check configuration directory permissions
 ///
@@ -102,6 +111,8 @@ pub fn update_self_signed_cert(force: bool) -> Result<(), Error> {
 
     create_configdir()?;
 
+    let _lock = lock_proxy_certificate()?;
+
     let key_path = PathBuf::from(configdir!("/proxy.key"));
     let cert_path = PathBuf::from(configdir!("/proxy.pem"));
 
@@ -209,3 +220,121 @@ pub fn update_self_signed_cert(force: bool) -> Result<(), Error> {
 
     Ok(())
 }
+
+const PROXY_CERT_LOCKFILE: &str = configdir!("/.proxy-cert.lck");
+
+// Serializes all modifications of the proxy certificate and key files
+fn lock_proxy_certificate() -> Result<std::fs::File, Error> {
+    open_file_locked(PROXY_CERT_LOCKFILE, std::time::Duration::new(10, 0), true)
+}
+
+/// Load the proxy certificate and key files into a TLS acceptor.
+///
+/// Fails if the files cannot be read or if the key does not match the certificate.
+pub fn proxy_tls_acceptor() -> Result<SslAcceptor, Error> {
+    //openssl req -x509 -newkey rsa:4096 -keyout /etc/proxmox-backup/proxy.key -out /etc/proxmox-backup/proxy.pem -nodes
+    let key_path = configdir!("/proxy.key");
+    let cert_path = configdir!("/proxy.pem");
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls())?;
+    acceptor.set_private_key_file(key_path, SslFiletype::PEM)
+        .map_err(|err| format_err!("unable to read proxy key {} - {}", key_path, err))?;
+    acceptor.set_certificate_chain_file(cert_path)
+        .map_err(|err| format_err!("unable to read proxy cert {} - {}", cert_path, err))?;
+    acceptor.check_private_key()
+        .map_err(|err| format_err!("proxy key does not match certificate - {}", err))?;
+
+    Ok(acceptor.build())
+}
+
+/// Parse a PEM encoded certificate (chain) and private key, and check that the key
+/// belongs to the (first) certificate.
+pub fn check_certificate_key(cert_pem: &[u8], key_pem: &[u8]) -> Result<PKey<Private>, Error> {
+    let key = PKey::private_key_from_pem(key_pem)
+        .map_err(|err| format_err!("unable to parse private key - {}", err))?;
+
+    let chain = openssl::x509::X509::stack_from_pem(cert_pem)
+        .map_err(|err| format_err!("unable to parse certificate - {}", err))?;
+    let cert = match chain.first() {
+        Some(cert) => cert,
+        None => bail!("no certificate found"),
+    };
+
+    if !cert.public_key()?.public_eq(&key) {
+        bail!("private key does not match certificate");
+    }
+
+    Ok(key)
+}
+
+/// Install a proxy certificate (chain) and its private key.
+///
+/// The new files are written next to the current ones and renamed while holding the
+/// certificate lock. The installed pair is then loaded like the proxy does it, if that
+/// fails the previous files are restored. The proxy has to be told to reload the
+/// certificate afterwards.
+pub fn set_proxy_certificate(cert_pem: &[u8], key_pem: &[u8]) -> Result<(), Error> {
+
+    let key = check_certificate_key(cert_pem, key_pem)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let options = CreateOptions::new()
+        .perm(Mode::from_bits_truncate(0o0640))
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    create_configdir()?;
+
+    let _lock = lock_proxy_certificate()?;
+
+    // store the key in PKCS#8 format, independent of the uploaded format
+    let key_pem = key.private_key_to_pem_pkcs8()?;
+
+    let key_path = configdir!("/proxy.key");
+    let cert_path = configdir!("/proxy.pem");
+    let new_key_path = configdir!("/proxy.key.new");
+    let new_cert_path = configdir!("/proxy.pem.new");
+    let old_key_path = configdir!("/proxy.key.old");
+    let old_cert_path = configdir!("/proxy.pem.old");
+
+    replace_file(new_key_path, &key_pem, options.clone())?;
+    replace_file(new_cert_path, cert_pem, options)?;
+
+    // keep the current files for a rollback
+    let files = [(cert_path, old_cert_path), (key_path, old_key_path)];
+    for (path, old_path) in &files {
+        let _ = std::fs::remove_file(old_path);
+        match std::fs::hard_link(path, old_path) {
+            Ok(()) => (),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => bail!("unable to backup {} - {}", path, err),
+        }
+    }
+
+    let result: Result<(), Error> = try_block!({
+        std::fs::rename(new_cert_path, cert_path)
+            .map_err(|err| format_err!("unable to replace {} - {}", cert_path, err))?;
+        std::fs::rename(new_key_path, key_path)
+            .map_err(|err| format_err!("unable to replace {} - {}", key_path, err))?;
+        proxy_tls_acceptor()?;
+        Ok(())
+    });
+
+    if let Err(err) = result {
+        for (path, old_path) in &files {
+            if std::path::Path::new(old_path).exists() {
+                if let Err(err) = std::fs::rename(old_path, path) {
+                    log::error!("unable to restore {} - {}", path, err);
+                }
+            }
+        }
+        let _ = std::fs::remove_file(new_cert_path);
+        let _ = std::fs::remove_file(new_key_path);
+        bail!("installing the proxy certificate failed, kept the previous one - {}", err);
+    }
+
+    let _ = std::fs::remove_file(old_cert_path);
+    let _ = std::fs::remove_file(old_key_path);
+
+    Ok(())
+}
@@ -15,7 +15,7 @@ use proxmox::api::{api, schema::*};
 use proxmox::constnamedbitmap;
 use proxmox::tools::{fs::replace_file, fs::CreateOptions};
 
-use crate::api2::types::{Authid, Userid};
+use crate::api2::types::Authid;
 
 // define Privilege bitfield
 
@@ -60,6 +60,10 @@ constnamedbitmap! {
         PRIV_REMOTE_MODIFY("Remote.Modify");
         /// Remote.Read allows reading data from a configured `Remote`
         PRIV_REMOTE_READ("Remote.Read");
+        /// Remote.DatastoreBackup allows creating new snapshots on a configured `Remote`
+        PRIV_REMOTE_DATASTORE_BACKUP("Remote.DatastoreBackup");
+        /// Remote.DatastorePrune allows deleting snapshots on a configured `Remote`
+        PRIV_REMOTE_DATASTORE_PRUNE("Remote.DatastorePrune");
 
         /// Sys.Console allows access to the system's console
         PRIV_SYS_CONSOLE("Sys.Console");
@@ -139,7 +143,9 @@ pub const ROLE_REMOTE_AUDIT: u64 = 0
 pub const ROLE_REMOTE_ADMIN: u64 = 0
     | PRIV_REMOTE_AUDIT
     | PRIV_REMOTE_MODIFY
-    | PRIV_REMOTE_READ;
+    | PRIV_REMOTE_READ
+    | PRIV_REMOTE_DATASTORE_BACKUP
+    | PRIV_REMOTE_DATASTORE_PRUNE;
 
 #[rustfmt::skip]
 #[allow(clippy::identity_op)]
@@ -148,6 +154,14 @@ pub const ROLE_REMOTE_SYNC_OPERATOR: u64 = 0
     | PRIV_REMOTE_AUDIT
     | PRIV_REMOTE_READ;
 
+#[rustfmt::skip]
+#[allow(clippy::identity_op)]
+/// Remote.SyncPushOperator can create and prune snapshots on the remote.
+pub const ROLE_REMOTE_SYNC_PUSH_OPERATOR: u64 = 0
+    | PRIV_REMOTE_AUDIT
+    | PRIV_REMOTE_DATASTORE_BACKUP
+    | PRIV_REMOTE_DATASTORE_PRUNE;
+
 #[rustfmt::skip]
 #[allow(clippy::identity_op)]
 /// Tape.Audit can audit the tape backup configuration and media content
@@ -213,6 +227,8 @@ pub enum Role {
     RemoteAdmin = ROLE_REMOTE_ADMIN,
     /// Syncronisation Opertator
     RemoteSyncOperator = ROLE_REMOTE_SYNC_OPERATOR,
+    /// Syncronisation Push Operator
+    RemoteSyncPushOperator = ROLE_REMOTE_SYNC_PUSH_OPERATOR,
     /// Tape Auditor
     TapeAudit = ROLE_TAPE_AUDIT,
     /// Tape Administrator
@@ -283,7 +299,7 @@ pub fn check_acl_path(path: &str) -> Result<(), Error> {
                 return Ok(());
             }
             match components[1] {
-                "acl" | "users" => {
+                "acl" | "users" | "groups" | "domains" => {
                     if components_len == 2 {
                         return Ok(());
                     }
@@ -292,8 +308,8 @@ pub fn check_acl_path(path: &str) -> Result<(), Error> {
             }
         }
         "datastore" => {
-            // /datastore/{store}
-            if components_len <= 2 {
+            // /datastore/{store}/{namespace}
+            if components_len <= 2 + crate::backup::MAX_NAMESPACE_DEPTH {
                 return Ok(());
             }
         }
@@ -308,7 +324,7 @@ pub fn check_acl_path(path: &str) -> Result<(), Error> {
                 return Ok(());
             }
             match components[1] {
-                "disks" | "log" | "status" | "tasks" | "time" => {
+                "disks" | "log" | "s3" | "status" | "tasks" | "time" | "traffic-control" => {
                     if components_len == 2 {
                         return Ok(());
                     }
@@ -389,7 +405,7 @@ pub struct AclTreeNode {
     /// [User](crate::config::user::User) or
     /// [Token](crate::config::user::ApiToken) ACLs for this node.
     pub users: HashMap<Authid, HashMap<String, bool>>,
-    /// `Group` ACLs for this node (not yet implemented)
+    /// [Group](crate::config::user::UserGroup) ACLs for this node.
     pub groups: HashMap<String, HashMap<String, bool>>,
     /// `AclTreeNodes` representing ACL paths directly below the current one.
     pub children: BTreeMap<String, AclTreeNode>,
@@ -409,18 +425,18 @@ impl AclTreeNode {
     /// [Authid](crate::api2::types::Authid).
     ///
     /// If the `Authid` is a [User](crate::config::user::User) that has no specific `Roles` configured on this node,
-    /// applicable `Group` roles will be returned instead.
+    /// the roles of the `groups` the user is a member of will be returned instead.
     ///
     /// If `leaf` is `false`, only those roles where the propagate flag in the ACL is set to `true`
     /// are returned. Otherwise, all roles will be returned.
-    pub fn extract_roles(&self, auth_id: &Authid, leaf: bool) -> HashMap<String, bool> {
+    pub fn extract_roles(&self, auth_id: &Authid, groups: &[String], leaf: bool) -> HashMap<String, bool> {
         let user_roles = self.extract_user_roles(auth_id, leaf);
         if !user_roles.is_empty() || auth_id.is_token() {
             // user privs always override group privs
             return user_roles;
         };
 
-        self.extract_group_roles(auth_id.user(), leaf)
+        self.extract_group_roles(groups, leaf)
     }
 
     fn extract_user_roles(&self, auth_id: &Authid, leaf: bool) -> HashMap<String, bool> {
@@ -446,15 +462,14 @@ impl AclTreeNode {
         map
     }
 
-    fn extract_group_roles(&self, _user: &Userid, leaf: bool) -> HashMap<String, bool> {
+    fn extract_group_roles(&self, groups: &[String], leaf: bool) -> HashMap<String, bool> {
         let mut map = HashMap::new();
 
-        #[allow(clippy::for_kv_map)]
-        for (_group, roles) in &self.groups {
-            let is_member = false; // fixme: check if user is member of the group
-            if !is_member {
-                continue;
-            }
+        for group in groups {
+            let roles = match self.groups.get(group) {
+                Some(roles) => roles,
+                None => continue,
+            };
 
             for (role, propagate) in roles {
                 if *propagate || leaf {
@@ -583,6 +598,30 @@ impl AclTree {
         node.insert_group_role(group.to_string(), role.to_string(), propagate);
     }
 
+    /// Deletes all ACL entries of `group`.
+    pub fn delete_group(&mut self, group: &str) {
+        fn delete(node: &mut AclTreeNode, group: &str) {
+            node.groups.remove(group);
+            for child in node.children.values_mut() {
+                delete(child, group);
+            }
+        }
+        delete(&mut self.root, group);
+    }
+
+    /// Deletes all ACL entries of `auth_id`, for users including those of their API tokens.
+    pub fn delete_authid(&mut self, auth_id: &Authid) {
+        fn delete(node: &mut AclTreeNode, auth_id: &Authid) {
+            node.users.retain(|id, _| {
+                id != auth_id && (auth_id.is_token() || id.user() != auth_id.user())
+            });
+            for child in node.children.values_mut() {
+                delete(child, auth_id);
+            }
+        }
+        delete(&mut self.root, auth_id);
+    }
+
     /// Inserts the specified `role` into the `user` ACL on `path`.
     ///
     /// The [AclTreeNode] representing `path` will be created and inserted into the tree if
@@ -810,9 +849,11 @@ impl AclTree {
     /// - more specific role maps replace less specific role maps
     /// -- user/token is more specific than group at each level
     /// -- roles lower in the tree are more specific than those higher up along the path
-    pub fn roles(&self, auth_id: &Authid, path: &[&str]) -> HashMap<String, bool> {
+    ///
+    /// `groups` are the groups the user is a member of.
+    pub fn roles(&self, auth_id: &Authid, groups: &[String], path: &[&str]) -> HashMap<String, bool> {
         let mut node = &self.root;
-        let mut role_map = node.extract_roles(auth_id, path.is_empty());
+        let mut role_map = node.extract_roles(auth_id, groups, path.is_empty());
 
         for (pos, comp) in path.iter().enumerate() {
             let last_comp = (pos + 1) == path.len();
@@ -821,7 +862,7 @@ impl AclTree {
                 None => return role_map, // path not found
             };
 
-            let new_map = node.extract_roles(auth_id, last_comp);
+            let new_map = node.extract_roles(auth_id, groups, last_comp);
             if !new_map.is_empty() {
                 // overwrite previous maptings
                 role_map = new_map;
@@ -830,6 +871,33 @@ impl AclTree {
 
         role_map
     }
+
+    /// Returns all paths below `path` (excluding `path` itself) which have ACL entries.
+    pub fn sub_paths(&self, path: &[&str]) -> Vec<Vec<String>> {
+        fn collect(node: &AclTreeNode, prefix: &mut Vec<String>, list: &mut Vec<Vec<String>>) {
+            for (name, child) in &node.children {
+                prefix.push(name.clone());
+                if !child.users.is_empty() || !child.groups.is_empty() {
+                    list.push(prefix.clone());
+                }
+                collect(child, prefix, list);
+                prefix.pop();
+            }
+        }
+
+        let mut node = &self.root;
+        for comp in path {
+            node = match node.children.get(*comp) {
+                Some(n) => n,
+                None => return Vec::new(),
+            };
+        }
+
+        let mut list = Vec::new();
+        let mut prefix = path.iter().map(|c| c.to_string()).collect();
+        collect(node, &mut prefix, &mut list);
+        list
+    }
 }
 
 /// Filename where [AclTree] is stored.
@@ -925,9 +993,20 @@ mod test {
     use crate::api2::types::Authid;
 
     fn check_roles(tree: &AclTree, auth_id: &Authid, path: &str, expected_roles: &str) {
+        check_group_roles(tree, auth_id, &[], path, expected_roles);
+    }
+
+    fn check_group_roles(
+        tree: &AclTree,
+        auth_id: &Authid,
+        groups: &[&str],
+        path: &str,
+        expected_roles: &str,
+    ) {
+        let groups: Vec<String> = groups.iter().map(|g| g.to_string()).collect();
         let path_vec = super::split_acl_path(path);
         let mut roles = tree
-            .roles(auth_id, &path_vec)
+            .roles(auth_id, &groups, &path_vec)
             .iter()
             .map(|(v, _)| v.clone())
             .collect::<Vec<String>>();
@@ -988,6 +1067,27 @@ acl:1:/storage/store2:user2@pbs:DatastoreBackup
         Ok(())
     }
 
+    #[test]
+    fn test_namespace_roles() -> Result<(), Error> {
+        let tree = AclTree::from_raw(
+            r###"
+acl:1:/datastore/store1/tenant1:user1@pbs:DatastoreBackup
+acl:0:/datastore/store1/tenant2:user1@pbs:DatastoreAudit
+"###,
+        )?;
+        let user1: Authid = "user1@pbs".parse()?;
+        check_roles(&tree, &user1, "/datastore/store1", "");
+        check_roles(&tree, &user1, "/datastore/store1/tenant1", "DatastoreBackup");
+        check_roles(&tree, &user1, "/datastore/store1/tenant1/sub", "DatastoreBackup");
+        check_roles(&tree, &user1, "/datastore/store1/tenant2", "DatastoreAudit");
+        check_roles(&tree, &user1, "/datastore/store1/tenant2/sub", "");
+
+        super::check_acl_path("/datastore/store1/tenant1/sub")?;
+        assert!(super::check_acl_path("/datastore/store1/a/b/c/d/e/f/g/h").is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn test_role_no_access() -> Result<(), Error> {
         let tree = AclTree::from_raw(
@@ -1020,6 +1120,41 @@ acl:1:/storage/store1:user1@pbs:DatastoreBackup
         Ok(())
     }
 
+    #[test]
+    fn test_group_roles() -> Result<(), Error> {
+        let mut tree = AclTree::from_raw(
+            r###"
+acl:1:/datastore:@admins:DatastoreAdmin
+acl:1:/datastore/store1:@backup:DatastoreBackup
+acl:1:/datastore/store2:@admins:NoAccess
+acl:1:/datastore/store2:user1@pbs:DatastoreAudit
+"###,
+        )?;
+        let user1: Authid = "user1@pbs".parse()?;
+        check_group_roles(&tree, &user1, &["admins"], "/datastore", "DatastoreAdmin");
+        check_group_roles(&tree, &user1, &["backup"], "/datastore", "");
+        check_group_roles(&tree, &user1, &["admins"], "/datastore/store1", "DatastoreAdmin");
+        // more specific group roles replace inherited ones
+        check_group_roles(&tree, &user1, &["admins", "backup"], "/datastore/store1", "DatastoreBackup");
+        // user roles override group roles
+        check_group_roles(&tree, &user1, &["admins"], "/datastore/store2", "DatastoreAudit");
+
+        let user2: Authid = "user2@pbs".parse()?;
+        check_group_roles(&tree, &user2, &["admins"], "/datastore/store2", "NoAccess");
+        check_roles(&tree, &user2, "/datastore", "");
+
+        // tokens never get group roles
+        let token: Authid = "user1@pbs!token".parse()?;
+        check_group_roles(&tree, &token, &["admins"], "/datastore", "");
+
+        tree.delete_group("admins");
+        check_group_roles(&tree, &user1, &["admins"], "/datastore", "");
+        check_group_roles(&tree, &user2, &["admins"], "/datastore/store2", "");
+        check_group_roles(&tree, &user1, &["backup"], "/datastore/store1", "DatastoreBackup");
+
+        Ok(())
+    }
+
     #[test]
     fn test_role_add_delete() -> Result<(), Error> {
         let mut tree = AclTree::new();
@@ -1064,4 +1199,32 @@ acl:1:/storage/store1:user1@pbs:DatastoreBackup
 
         Ok(())
     }
+
+    #[test]
+    fn test_delete_authid() -> Result<(), Error> {
+        let mut tree = AclTree::from_raw(
+            r###"
+acl:1:/datastore:user1@pbs:DatastoreAdmin
+acl:1:/datastore/store1:user1@pbs!token1:DatastoreBackup
+acl:1:/datastore/store1:user1@pbs!token2:DatastoreBackup
+acl:1:/datastore/store1:user2@pbs:DatastoreBackup
+"###,
+        )?;
+        let user1: Authid = "user1@pbs".parse()?;
+        let user2: Authid = "user2@pbs".parse()?;
+        let token1: Authid = "user1@pbs!token1".parse()?;
+        let token2: Authid = "user1@pbs!token2".parse()?;
+
+        tree.delete_authid(&token1);
+        check_roles(&tree, &token1, "/datastore/store1", "");
+        check_roles(&tree, &token2, "/datastore/store1", "DatastoreBackup");
+        check_roles(&tree, &user1, "/datastore", "DatastoreAdmin");
+
+        tree.delete_authid(&user1);
+        check_roles(&tree, &user1, "/datastore", "");
+        check_roles(&tree, &token2, "/datastore/store1", "");
+        check_roles(&tree, &user2, "/datastore/store1", "DatastoreBackup");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,350 @@
+//! ACME accounts, challenge plugins and the domains of the node certificate
+//!
+//! Accounts are stored as JSON files in `/etc/proxmox-backup/acme/accounts/`, since they
+//! contain the private account key. Plugins and domains are section config files in
+//! `/etc/proxmox-backup/acme/`.
+
+use anyhow::{bail, format_err, Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::tools::fs::{create_path, file_read_optional_string, replace_file, CreateOptions};
+
+use crate::api2::types::*;
+use crate::tools::acme::AccountData;
+
+lazy_static! {
+    pub static ref PLUGIN_CONFIG: SectionConfig = init_plugin_config();
+    pub static ref DOMAIN_CONFIG: SectionConfig = init_domain_config();
+}
+
+pub const ACME_DIR: &str = configdir!("/acme");
+pub const ACME_ACCOUNT_DIR: &str = configdir!("/acme/accounts");
+
+pub const ACME_PLUGIN_CFG_FILENAME: &str = configdir!("/acme/plugins.cfg");
+pub const ACME_DOMAIN_CFG_FILENAME: &str = configdir!("/acme/domains.cfg");
+/// Lock for all ACME configuration files, including the accounts
+pub const ACME_CFG_LOCKFILE: &str = configdir!("/acme/.lock");
+
+/// The account used for the node certificate if none is given
+pub const DEFAULT_ACME_ACCOUNT_NAME: &str = "default";
+
+pub const ACME_ACCOUNT_NAME_SCHEMA: Schema = StringSchema::new("ACME account name.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .default(DEFAULT_ACME_ACCOUNT_NAME)
+    .schema();
+
+pub const ACME_DIRECTORY_URL_SCHEMA: Schema = StringSchema::new(
+    "URL of the ACME directory of the certificate authority.")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .max_length(256)
+    .schema();
+
+pub const ACME_CONTACT_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of contact email addresses.")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .max_length(1024)
+    .schema();
+
+pub const ACME_PLUGIN_ID_SCHEMA: Schema = StringSchema::new("ACME challenge plugin ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+pub const ACME_DNS_HOOK_SCHEMA: Schema = StringSchema::new(
+    "Absolute path of the executable which adds and removes the TXT records. \
+    It gets called as '<hook> setup|teardown <domain> <record name> <record value>'.")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .min_length(2)
+    .max_length(1024)
+    .schema();
+
+pub const ACME_DNS_DATA_SCHEMA: Schema = StringSchema::new(
+    "Base64 encoded 'KEY=value' lines, passed as environment to the hook (for example API \
+    credentials of the DNS provider).")
+    .max_length(64 * 1024)
+    .schema();
+
+pub const ACME_VALIDATION_DELAY_SCHEMA: Schema = IntegerSchema::new(
+    "Seconds to wait after setting up the TXT records, before requesting validation.")
+    .minimum(0)
+    .maximum(48 * 3600)
+    .default(30)
+    .schema();
+
+pub const ACME_DOMAIN_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|domain| {
+    // wildcard certificates are only possible with the dns-01 challenge
+    let name = domain.strip_prefix("*.").unwrap_or(domain);
+    if !DNS_NAME_REGEX.is_match(name) || !name.contains('.') {
+        bail!("'{}' is not a fully qualified domain name", domain);
+    }
+    Ok(())
+});
+
+pub const ACME_DOMAIN_SCHEMA: Schema = StringSchema::new(
+    "Fully qualified domain name, '*.' prefixed for a wildcard (requires a DNS plugin).")
+    .format(&ACME_DOMAIN_FORMAT)
+    .min_length(3)
+    .max_length(255)
+    .schema();
+
+pub const ACME_DOMAIN_ALIAS_SCHEMA: Schema = StringSchema::new(
+    "Domain to create the dns-01 TXT record in, instead of the domain itself. The \
+    '_acme-challenge' record of the domain needs to be a CNAME to the one of the alias.")
+    .format(&DNS_NAME_FORMAT)
+    .min_length(3)
+    .max_length(255)
+    .schema();
+
+#[api(
+    properties: {
+        id: {
+            schema: ACME_PLUGIN_ID_SCHEMA,
+        },
+        hook: {
+            schema: ACME_DNS_HOOK_SCHEMA,
+        },
+        data: {
+            optional: true,
+            schema: ACME_DNS_DATA_SCHEMA,
+        },
+        "validation-delay": {
+            optional: true,
+            schema: ACME_VALIDATION_DELAY_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// DNS challenge plugin, which uses a hook to manage the `dns-01` TXT records.
+pub struct DnsPlugin {
+    pub id: String,
+    pub hook: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub validation_delay: Option<u32>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl DnsPlugin {
+    /// The decoded `KEY=value` pairs of the plugin data
+    pub fn environment(&self) -> Result<Vec<(String, String)>, Error> {
+        let data = match self.data {
+            Some(ref data) => base64::decode(data)
+                .map_err(|err| format_err!("unable to decode plugin data - {}", err))?,
+            None => return Ok(Vec::new()),
+        };
+        let data = String::from_utf8(data)
+            .map_err(|_| format_err!("plugin data is not valid UTF-8"))?;
+
+        let mut env = Vec::new();
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => {
+                    env.push((key.trim().to_string(), value.trim().to_string()));
+                }
+                _ => bail!("invalid plugin data line '{}'", line),
+            }
+        }
+        Ok(env)
+    }
+}
+
+#[api(
+    properties: {
+        domain: {
+            schema: ACME_DOMAIN_SCHEMA,
+        },
+        plugin: {
+            optional: true,
+            schema: ACME_PLUGIN_ID_SCHEMA,
+        },
+        alias: {
+            optional: true,
+            schema: ACME_DOMAIN_ALIAS_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Domain of the node certificate. Without DNS plugin, the `http-01` challenge is used, which
+/// is answered by the proxy on port 80.
+pub struct AcmeDomain {
+    pub domain: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub plugin: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub alias: Option<String>,
+}
+
+fn init_plugin_config() -> SectionConfig {
+    let obj_schema = match DnsPlugin::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("dns".to_string(), Some("id".to_string()), obj_schema);
+    let mut config = SectionConfig::new(&ACME_PLUGIN_ID_SCHEMA);
+    config.register_plugin(plugin);
+
+    config
+}
+
+fn init_domain_config() -> SectionConfig {
+    let obj_schema = match AcmeDomain::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("domain".to_string(), Some("domain".to_string()), obj_schema);
+    let mut config = SectionConfig::new(&ACME_DOMAIN_SCHEMA);
+    config.register_plugin(plugin);
+
+    config
+}
+
+fn root_only_options() -> CreateOptions {
+    // owner(rw) = root, group/other: no access, as they contain secrets
+    CreateOptions::new()
+        .perm(nix::sys::stat::Mode::from_bits_truncate(0o0600))
+        .owner(nix::unistd::ROOT)
+}
+
+fn make_acme_dir() -> Result<(), Error> {
+    let options = CreateOptions::new()
+        .perm(nix::sys::stat::Mode::from_bits_truncate(0o0700))
+        .owner(nix::unistd::ROOT);
+    create_path(ACME_ACCOUNT_DIR, Some(options.clone()), Some(options))?;
+    Ok(())
+}
+
+/// Lock the ACME configuration (creates the ACME directory if needed).
+pub fn lock_config() -> Result<std::fs::File, Error> {
+    make_acme_dir()?;
+    proxmox::tools::fs::open_file_locked(ACME_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)
+}
+
+pub fn plugin_config() -> Result<(SectionConfigData, [u8;32]), Error> {
+    let content = file_read_optional_string(ACME_PLUGIN_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = PLUGIN_CONFIG.parse(ACME_PLUGIN_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_plugin_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = PLUGIN_CONFIG.write(ACME_PLUGIN_CFG_FILENAME, &config)?;
+    make_acme_dir()?;
+    replace_file(ACME_PLUGIN_CFG_FILENAME, raw.as_bytes(), root_only_options())
+}
+
+pub fn domain_config() -> Result<(SectionConfigData, [u8;32]), Error> {
+    let content = file_read_optional_string(ACME_DOMAIN_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = DOMAIN_CONFIG.parse(ACME_DOMAIN_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_domain_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = DOMAIN_CONFIG.write(ACME_DOMAIN_CFG_FILENAME, &config)?;
+    make_acme_dir()?;
+    replace_file(ACME_DOMAIN_CFG_FILENAME, raw.as_bytes(), root_only_options())
+}
+
+fn account_path(name: &str) -> PathBuf {
+    let mut path = PathBuf::from(ACME_ACCOUNT_DIR);
+    path.push(name);
+    path
+}
+
+/// Names of all registered accounts
+pub fn list_accounts() -> Result<Vec<String>, Error> {
+    let mut list = Vec::new();
+
+    let dir = match std::fs::read_dir(ACME_ACCOUNT_DIR) {
+        Ok(dir) => dir,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(list),
+        Err(err) => bail!("unable to read ACME account directory - {}", err),
+    };
+
+    for entry in dir {
+        let name = entry?.file_name();
+        if let Some(name) = name.to_str() {
+            if parse_simple_value(name, &ACME_ACCOUNT_NAME_SCHEMA).is_ok() {
+                list.push(name.to_string());
+            }
+        }
+    }
+    list.sort();
+
+    Ok(list)
+}
+
+pub fn load_account(name: &str) -> Result<AccountData, Error> {
+    let data = file_read_optional_string(account_path(name))?
+        .ok_or_else(|| format_err!("ACME account '{}' does not exist", name))?;
+    serde_json::from_str(&data)
+        .map_err(|err| format_err!("unable to parse ACME account '{}' - {}", name, err))
+}
+
+/// Save an account. Requires the config lock.
+pub fn save_account(name: &str, account: &AccountData) -> Result<(), Error> {
+    make_acme_dir()?;
+    replace_file(account_path(name), serde_json::to_string_pretty(account)?.as_bytes(), root_only_options())
+}
+
+/// Remove an account file. Requires the config lock.
+pub fn remove_account(name: &str) -> Result<(), Error> {
+    std::fs::remove_file(account_path(name))
+        .map_err(|err| format_err!("unable to remove ACME account '{}' - {}", name, err))
+}
+
+// shell completion helper
+pub fn complete_acme_account(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    list_accounts().unwrap_or_default()
+}
+
+// shell completion helper
+pub fn complete_acme_plugin(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match plugin_config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+// shell completion helper
+pub fn complete_acme_domain(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match domain_config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => vec![],
+    }
+}
@@ -1,5 +1,6 @@
 //! Cached user info for fast ACL permission checks
 
+use std::collections::HashMap;
 use std::sync::{RwLock, Arc};
 
 use anyhow::{Error, bail};
@@ -9,13 +10,15 @@ use lazy_static::lazy_static;
 use proxmox::api::UserInformation;
 
 use super::acl::{AclTree, ROLE_NAMES, ROLE_ADMIN};
-use super::user::{ApiToken, User};
+use super::user::{ApiToken, User, UserGroup};
 use crate::api2::types::{Authid, Userid};
 
 /// Cache User/Group/Token/Acl configuration data for fast permission tests
 pub struct CachedUserInfo {
     user_cfg: Arc<SectionConfigData>,
     acl_tree: Arc<AclTree>,
+    /// Group IDs per member, built once from `user_cfg`
+    user_groups: HashMap<Userid, Vec<String>>,
 }
 
 fn now() -> i64 { unsafe { libc::time(std::ptr::null_mut()) } }
@@ -25,6 +28,26 @@ struct ConfigCache {
     last_update: i64,
 }
 
+// Map each user to the IDs of the groups it is a member of.
+fn group_membership(user_cfg: &SectionConfigData) -> HashMap<Userid, Vec<String>> {
+    let mut map: HashMap<Userid, Vec<String>> = HashMap::new();
+
+    for (id, (section_type, _)) in user_cfg.sections.iter() {
+        if section_type != "group" {
+            continue;
+        }
+        let members = match user_cfg.lookup::<UserGroup>("group", id) {
+            Ok(group) => group.member_list().unwrap_or_default(),
+            Err(_) => continue,
+        };
+        for member in members {
+            map.entry(member).or_default().push(id.clone());
+        }
+    }
+
+    map
+}
+
 lazy_static! {
     static ref CACHED_CONFIG: RwLock<ConfigCache> = RwLock::new(
         ConfigCache { data: None, last_update: 0 }
@@ -45,8 +68,10 @@ impl CachedUserInfo {
             }
         }
 
+        let user_cfg = super::user::cached_config()?;
         let config = Arc::new(CachedUserInfo {
-            user_cfg: super::user::cached_config()?,
+            user_groups: group_membership(&user_cfg),
+            user_cfg,
             acl_tree: super::acl::cached_config()?,
         });
 
@@ -60,6 +85,7 @@ impl CachedUserInfo {
     #[cfg(test)]
     pub(crate) fn test_new(user_cfg: SectionConfigData, acl_tree: AclTree) -> Self {
         Self {
+            user_groups: group_membership(&user_cfg),
             user_cfg: Arc::new(user_cfg),
             acl_tree: Arc::new(acl_tree),
         }
@@ -122,12 +148,36 @@ impl CachedUserInfo {
         Ok(())
     }
 
+    /// Checks whether `auth_id` has any of `privs` on `path` or on any path below it.
+    ///
+    /// This is used to decide whether an object (for example a datastore) should be
+    /// visible at all, when the actual permissions are only granted on sub-objects
+    /// like namespaces.
+    pub fn any_privs_below(&self, auth_id: &Authid, path: &[&str], privs: u64) -> bool {
+        if self.lookup_privs(auth_id, path) & privs != 0 {
+            return true;
+        }
+
+        self.acl_tree.sub_paths(path).iter().any(|sub_path| {
+            let sub_path: Vec<&str> = sub_path.iter().map(String::as_str).collect();
+            self.lookup_privs(auth_id, &sub_path) & privs != 0
+        })
+    }
+
     pub fn is_superuser(&self, auth_id: &Authid) -> bool {
         !auth_id.is_token() && auth_id.user() == "root@pam"
     }
 
-    pub fn is_group_member(&self, _userid: &Userid, _group: &str) -> bool {
-        false
+    pub fn is_group_member(&self, userid: &Userid, group: &str) -> bool {
+        match self.user_groups.get(userid) {
+            Some(groups) => groups.iter().any(|id| id == group),
+            None => false,
+        }
+    }
+
+    /// Returns the IDs of all groups `userid` is a member of.
+    pub fn user_groups(&self, userid: &Userid) -> Vec<String> {
+        self.user_groups.get(userid).cloned().unwrap_or_default()
     }
 
     pub fn lookup_privs(&self, auth_id: &Authid, path: &[&str]) -> u64 {
@@ -140,7 +190,13 @@ impl CachedUserInfo {
             return (ROLE_ADMIN, ROLE_ADMIN);
         }
 
-        let roles = self.acl_tree.roles(auth_id, path);
+        let groups = if auth_id.is_token() {
+            Vec::new()
+        } else {
+            self.user_groups(auth_id.user())
+        };
+
+        let roles = self.acl_tree.roles(auth_id, &groups, path);
         let mut privs: u64 = 0;
         let mut propagated_privs: u64 = 0;
         for (role, propagate) in roles {
@@ -171,8 +227,11 @@ impl UserInformation for CachedUserInfo {
         userid == "root@pam"
     }
 
-    fn is_group_member(&self, _userid: &str, _group: &str) -> bool {
-        false
+    fn is_group_member(&self, userid: &str, group: &str) -> bool {
+        match userid.parse::<Userid>() {
+            Ok(userid) => Self::is_group_member(self, &userid, group),
+            Err(_) => false,
+        }
     }
 
     fn lookup_privs(&self, auth_id: &str, path: &[&str]) -> u64 {
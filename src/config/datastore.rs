@@ -1,6 +1,7 @@
 use anyhow::{Error};
 use lazy_static::lazy_static;
 use std::collections::HashMap;
+use std::path::Path;
 use serde::{Serialize, Deserialize};
 
 use proxmox::api::{
@@ -16,6 +17,7 @@ use proxmox::api::{
 use proxmox::tools::{fs::replace_file, fs::CreateOptions};
 
 use crate::api2::types::*;
+use crate::tools::disks::is_fs_uuid_mounted_at;
 
 lazy_static! {
     pub static ref CONFIG: SectionConfig = init();
@@ -80,6 +82,22 @@ pub const DIR_NAME_SCHEMA: Schema = StringSchema::new("Directory name").schema()
             optional: true,
             type: bool,
         },
+        "maintenance-mode": {
+            optional: true,
+            schema: MAINTENANCE_MODE_SCHEMA,
+        },
+        "backing-device": {
+            optional: true,
+            schema: BACKING_DEVICE_SCHEMA,
+        },
+        backend: {
+            optional: true,
+            schema: DATASTORE_BACKEND_SCHEMA,
+        },
+        compression: {
+            optional: true,
+            schema: CHUNK_COMPRESSION_SCHEMA,
+        },
     }
 )]
 #[serde(rename_all="kebab-case")]
@@ -115,6 +133,63 @@ pub struct DataStoreConfig {
     /// Send notification only for job errors
     #[serde(skip_serializing_if="Option::is_none")]
     pub notify: Option<String>,
+    /// Datastore is in maintenance mode (read-only or offline)
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub maintenance_mode: Option<String>,
+    /// Datastore is located on a removable device, mounted at `path` on demand
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub backing_device: Option<String>,
+    /// Where the chunks are stored (local file system or S3 bucket)
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub backend: Option<String>,
+    /// Compression level and dictionary used for new chunks
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub compression: Option<String>,
+}
+
+impl DataStoreConfig {
+    /// Returns the parsed maintenance mode, if one is set.
+    pub fn get_maintenance_mode(&self) -> Option<MaintenanceMode> {
+        self.maintenance_mode.as_ref().and_then(|str| {
+            let value = parse_property_string(str, &MaintenanceMode::API_SCHEMA).ok()?;
+            serde_json::from_value(value).ok()
+        })
+    }
+
+    /// Returns the parsed chunk backend, `None` means the local file system.
+    pub fn get_backend(&self) -> Result<Option<DatastoreBackendConfig>, Error> {
+        match self.backend {
+            Some(ref str) => {
+                let value = parse_property_string(str, &DatastoreBackendConfig::API_SCHEMA)?;
+                Ok(Some(serde_json::from_value(value)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the parsed chunk compression settings.
+    pub fn get_compression(&self) -> Result<ChunkCompressionConfig, Error> {
+        match self.compression {
+            Some(ref str) => {
+                let value = parse_property_string(str, &ChunkCompressionConfig::API_SCHEMA)?;
+                Ok(serde_json::from_value(value)?)
+            }
+            None => Ok(ChunkCompressionConfig::default()),
+        }
+    }
+
+    /// Returns true if the datastore is located on a removable device.
+    pub fn is_removable(&self) -> bool {
+        self.backing_device.is_some()
+    }
+
+    /// Returns false if the removable device of this datastore is not mounted at its path.
+    pub fn is_mounted(&self) -> bool {
+        match self.backing_device {
+            Some(ref uuid) => is_fs_uuid_mounted_at(uuid, Path::new(&self.path)).unwrap_or(false),
+            None => true,
+        }
+    }
 }
 
 fn init() -> SectionConfig {
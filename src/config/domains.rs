@@ -0,0 +1,434 @@
+use anyhow::{bail, Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::const_regex;
+use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+
+use crate::api2::types::*;
+use crate::tools::ldap::{LdapConfig, LdapConnectionMode};
+use crate::tools::openid::OpenIdConfig;
+
+lazy_static! {
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+pub const LDAP_DN_SCHEMA: Schema = StringSchema::new("LDAP distinguished name, for example 'dc=example,dc=com'.")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .min_length(3)
+    .max_length(256)
+    .schema();
+
+pub const LDAP_ATTRIBUTE_SCHEMA: Schema = StringSchema::new("LDAP attribute name.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(1)
+    .max_length(64)
+    .schema();
+
+pub const LDAP_FILTER_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|filter| {
+    crate::tools::ldap::encode_filter(filter)?;
+    Ok(())
+});
+
+pub const LDAP_FILTER_SCHEMA: Schema = StringSchema::new("LDAP search filter, for example '(objectClass=person)'.")
+    .format(&LDAP_FILTER_FORMAT)
+    .max_length(1024)
+    .schema();
+
+pub const LDAP_PASSWORD_SCHEMA: Schema = StringSchema::new("Password of the bind DN.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(1024)
+    .schema();
+
+pub const REALM_SYNC_SCHEDULE_SCHEMA: Schema = StringSchema::new(
+    "Run realm sync (users and groups) at specified schedule.")
+    .format(&ApiStringFormat::VerifyFn(crate::tools::systemd::time::verify_calendar_event))
+    .type_text("<calendar-event>")
+    .schema();
+
+pub const REMOVE_VANISHED_USERS_SCHEMA: Schema = BooleanSchema::new(
+    "Remove users and groups of the realm which vanished from the directory.")
+    .default(false)
+    .schema();
+
+pub const OPENID_ISSUER_URL_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|url| {
+    let url = url::Url::parse(url)?;
+    if url.scheme() != "https" {
+        bail!("issuer URL must use https");
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        bail!("issuer URL must not contain query or fragment components");
+    }
+    Ok(())
+});
+
+pub const OPENID_ISSUER_URL_SCHEMA: Schema = StringSchema::new("OpenID issuer URL, for example 'https://login.example.com/realms/example'.")
+    .format(&OPENID_ISSUER_URL_FORMAT)
+    .max_length(256)
+    .schema();
+
+pub const OPENID_CLIENT_ID_SCHEMA: Schema = StringSchema::new("OpenID client ID.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(256)
+    .schema();
+
+pub const OPENID_CLIENT_KEY_SCHEMA: Schema = StringSchema::new("OpenID client secret, not required for public clients.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(1024)
+    .schema();
+
+const_regex! {
+    OPENID_SCOPES_REGEX = r"^[A-Za-z0-9_.:/-]+(?: [A-Za-z0-9_.:/-]+)*$";
+}
+
+pub const OPENID_SCOPES_FORMAT: ApiStringFormat = ApiStringFormat::Pattern(&OPENID_SCOPES_REGEX);
+
+pub const OPENID_SCOPES_SCHEMA: Schema = StringSchema::new(
+    "Space separated list of additional scopes to request ('openid' is always requested).")
+    .format(&OPENID_SCOPES_FORMAT)
+    .max_length(256)
+    .default("email profile")
+    .schema();
+
+pub const OPENID_AUTOCREATE_SCHEMA: Schema = BooleanSchema::new(
+    "Automatically create users on their first login.")
+    .default(false)
+    .schema();
+
+#[api()]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// ID token claim used as user name.
+pub enum OpenIdUsernameClaim {
+    /// The subject identifier, unique and stable at the provider.
+    Subject,
+    /// The 'preferred_username' claim.
+    Username,
+    /// The 'email' claim.
+    Email,
+}
+
+impl OpenIdUsernameClaim {
+    /// The name of the claim in the ID token.
+    pub fn claim_name(&self) -> &'static str {
+        match self {
+            OpenIdUsernameClaim::Subject => "sub",
+            OpenIdUsernameClaim::Username => "preferred_username",
+            OpenIdUsernameClaim::Email => "email",
+        }
+    }
+}
+
+#[api()]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+/// How to connect to the LDAP server.
+pub enum LdapMode {
+    /// Plain LDAP (unencrypted).
+    #[serde(rename = "ldap")]
+    Ldap,
+    /// LDAP upgraded to TLS with STARTTLS.
+    #[serde(rename = "ldap+starttls")]
+    StartTls,
+    /// LDAP over TLS.
+    #[serde(rename = "ldaps")]
+    Ldaps,
+}
+
+#[api(
+    properties: {
+        realm: {
+            schema: PROXMOX_AUTH_REALM_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        server1: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        server2: {
+            optional: true,
+            description: "Fallback server, used if the first server is not reachable.",
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            optional: true,
+            description: "The (optional) port, defaults to 389 (636 for 'ldaps').",
+            type: u16,
+        },
+        mode: {
+            optional: true,
+            type: LdapMode,
+        },
+        verify: {
+            optional: true,
+            description: "Verify the server certificate.",
+            type: bool,
+            default: true,
+        },
+        fingerprint: {
+            optional: true,
+            schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+        },
+        "base-dn": {
+            schema: LDAP_DN_SCHEMA,
+        },
+        "user-attr": {
+            schema: LDAP_ATTRIBUTE_SCHEMA,
+        },
+        "bind-dn": {
+            optional: true,
+            schema: LDAP_DN_SCHEMA,
+        },
+        password: {
+            optional: true,
+            schema: LDAP_PASSWORD_SCHEMA,
+        },
+        filter: {
+            optional: true,
+            schema: LDAP_FILTER_SCHEMA,
+        },
+        "group-dn": {
+            optional: true,
+            description: "Base DN of groups, defaults to the base DN.",
+            schema: LDAP_DN_SCHEMA,
+        },
+        "group-filter": {
+            optional: true,
+            schema: LDAP_FILTER_SCHEMA,
+        },
+        "group-name-attr": {
+            optional: true,
+            schema: LDAP_ATTRIBUTE_SCHEMA,
+        },
+        "sync-schedule": {
+            optional: true,
+            schema: REALM_SYNC_SCHEDULE_SCHEMA,
+        },
+        "remove-vanished": {
+            optional: true,
+            schema: REMOVE_VANISHED_USERS_SCHEMA,
+        },
+    }
+)]
+#[derive(Clone,Serialize,Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// LDAP authentication realm, also used for Active Directory.
+pub struct LdapRealmConfig {
+    pub realm: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+    pub server1: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub server2: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub mode: Option<LdapMode>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub verify: Option<bool>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub fingerprint: Option<String>,
+    pub base_dn: String,
+    pub user_attr: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub bind_dn: Option<String>,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub password: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub filter: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub group_dn: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub group_filter: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub group_name_attr: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub sync_schedule: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub remove_vanished: Option<bool>,
+}
+
+impl LdapRealmConfig {
+    /// Returns the options to connect to the configured servers.
+    pub fn connection_config(&self) -> LdapConfig {
+        let mut servers = vec![self.server1.clone()];
+        if let Some(server2) = &self.server2 {
+            servers.push(server2.clone());
+        }
+
+        LdapConfig {
+            servers,
+            port: self.port,
+            mode: match self.mode.unwrap_or(LdapMode::Ldap) {
+                LdapMode::Ldap => LdapConnectionMode::Ldap,
+                LdapMode::StartTls => LdapConnectionMode::StartTls,
+                LdapMode::Ldaps => LdapConnectionMode::Ldaps,
+            },
+            verify: self.verify.unwrap_or(true),
+            fingerprint: self.fingerprint.clone(),
+            timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+#[api(
+    properties: {
+        realm: {
+            schema: PROXMOX_AUTH_REALM_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        "issuer-url": {
+            schema: OPENID_ISSUER_URL_SCHEMA,
+        },
+        "client-id": {
+            schema: OPENID_CLIENT_ID_SCHEMA,
+        },
+        "client-key": {
+            optional: true,
+            schema: OPENID_CLIENT_KEY_SCHEMA,
+        },
+        scopes: {
+            optional: true,
+            schema: OPENID_SCOPES_SCHEMA,
+        },
+        "username-claim": {
+            optional: true,
+            type: OpenIdUsernameClaim,
+        },
+        autocreate: {
+            optional: true,
+            schema: OPENID_AUTOCREATE_SCHEMA,
+        },
+    }
+)]
+#[derive(Clone,Serialize,Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// OpenID Connect authentication realm.
+pub struct OpenIdRealmConfig {
+    pub realm: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+    pub issuer_url: String,
+    pub client_id: String,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub client_key: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub scopes: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub username_claim: Option<OpenIdUsernameClaim>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub autocreate: Option<bool>,
+}
+
+impl OpenIdRealmConfig {
+    /// Returns the options for the OpenID client.
+    pub fn client_config(&self) -> OpenIdConfig {
+        let scopes = self.scopes.as_deref().unwrap_or("email profile");
+        OpenIdConfig {
+            issuer_url: self.issuer_url.clone(),
+            client_id: self.client_id.clone(),
+            client_key: if self.client_key.is_empty() { None } else { Some(self.client_key.clone()) },
+            scopes: scopes.split_whitespace().map(String::from).collect(),
+        }
+    }
+}
+
+fn init() -> SectionConfig {
+    let obj_schema = match LdapRealmConfig::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("ldap".to_string(), Some("realm".to_string()), obj_schema);
+    let mut config = SectionConfig::new(&PROXMOX_AUTH_REALM_SCHEMA);
+    config.register_plugin(plugin);
+
+    let obj_schema = match OpenIdRealmConfig::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("openid".to_string(), Some("realm".to_string()), obj_schema);
+    config.register_plugin(plugin);
+
+    config
+}
+
+pub const DOMAINS_CFG_FILENAME: &str = "/etc/proxmox-backup/domains.cfg";
+pub const DOMAINS_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.domains.lck";
+
+pub fn config() -> Result<(SectionConfigData, [u8;32]), Error> {
+
+    let content = proxmox::tools::fs::file_read_optional_string(DOMAINS_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(DOMAINS_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(DOMAINS_CFG_FILENAME, &config)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0640);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = root, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    replace_file(DOMAINS_CFG_FILENAME, raw.as_bytes(), options)?;
+
+    Ok(())
+}
+
+/// Lookup the configuration of a LDAP realm.
+pub fn lookup_ldap_realm(realm: &str) -> Result<LdapRealmConfig, Error> {
+    let (config, _digest) = config()?;
+    match config.sections.get(realm) {
+        Some((section_type, _)) if section_type == "ldap" => config.lookup("ldap", realm),
+        _ => bail!("no such LDAP realm '{}'", realm),
+    }
+}
+
+/// Lookup the configuration of an OpenID realm.
+pub fn lookup_openid_realm(realm: &str) -> Result<OpenIdRealmConfig, Error> {
+    let (config, _digest) = config()?;
+    match config.sections.get(realm) {
+        Some((section_type, _)) if section_type == "openid" => config.lookup("openid", realm),
+        _ => bail!("no such OpenID realm '{}'", realm),
+    }
+}
+
+// shell completion helper
+pub fn complete_realm_name(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => return vec![],
+    }
+}
@@ -0,0 +1,265 @@
+use anyhow::{Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+
+use crate::api2::types::*;
+
+lazy_static! {
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+pub const METRIC_SERVER_ID_SCHEMA: Schema = StringSchema::new("Metric server ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+pub const METRIC_SERVER_MTU_SCHEMA: Schema = IntegerSchema::new(
+    "MTU of the network path to the server, UDP packets are kept below it.")
+    .minimum(512)
+    .maximum(65535)
+    .default(1500)
+    .schema();
+
+pub const INFLUXDB_ORGANIZATION_SCHEMA: Schema = StringSchema::new(
+    "InfluxDB organization (only for the HTTP(S) API).")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .max_length(256)
+    .schema();
+
+pub const INFLUXDB_BUCKET_SCHEMA: Schema = StringSchema::new(
+    "InfluxDB bucket, or database for InfluxDB 1.8 (only for the HTTP(S) API).")
+    .format(&SINGLE_LINE_COMMENT_FORMAT)
+    .max_length(256)
+    .schema();
+
+pub const INFLUXDB_TOKEN_SCHEMA: Schema = StringSchema::new(
+    "InfluxDB API token, or 'user:password' for InfluxDB 1.8 (only for the HTTP(S) API).")
+    .format(&PASSWORD_FORMAT)
+    .max_length(1024)
+    .schema();
+
+pub const GRAPHITE_PATH_SCHEMA: Schema = StringSchema::new(
+    "Prefix of the metric paths (defaults to 'proxmox').")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .max_length(64)
+    .schema();
+
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Protocol used to send data to an InfluxDB server.
+pub enum InfluxDbProtocol {
+    /// Line protocol over UDP.
+    Udp,
+    /// HTTP write API (InfluxDB 2.x or 1.8).
+    Http,
+    /// HTTPS write API (InfluxDB 2.x or 1.8).
+    Https,
+}
+
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Protocol used to send data to a Graphite server.
+pub enum GraphiteProtocol {
+    /// Plaintext protocol over UDP.
+    Udp,
+    /// Plaintext protocol over TCP.
+    Tcp,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: METRIC_SERVER_ID_SCHEMA,
+        },
+        enable: {
+            description: "Send data to this server.",
+            type: bool,
+            optional: true,
+            default: true,
+        },
+        server: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            description: "Server port.",
+            type: u16,
+        },
+        proto: {
+            type: InfluxDbProtocol,
+            optional: true,
+        },
+        organization: {
+            schema: INFLUXDB_ORGANIZATION_SCHEMA,
+            optional: true,
+        },
+        bucket: {
+            schema: INFLUXDB_BUCKET_SCHEMA,
+            optional: true,
+        },
+        token: {
+            schema: INFLUXDB_TOKEN_SCHEMA,
+            optional: true,
+        },
+        mtu: {
+            schema: METRIC_SERVER_MTU_SCHEMA,
+            optional: true,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// InfluxDB metric server (defaults to UDP).
+pub struct InfluxDbServer {
+    pub name: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub enable: Option<bool>,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub proto: Option<InfluxDbProtocol>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub bucket: Option<String>,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub token: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub mtu: Option<u16>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: METRIC_SERVER_ID_SCHEMA,
+        },
+        enable: {
+            description: "Send data to this server.",
+            type: bool,
+            optional: true,
+            default: true,
+        },
+        server: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            description: "Server port.",
+            type: u16,
+        },
+        proto: {
+            type: GraphiteProtocol,
+            optional: true,
+        },
+        path: {
+            schema: GRAPHITE_PATH_SCHEMA,
+            optional: true,
+        },
+        mtu: {
+            schema: METRIC_SERVER_MTU_SCHEMA,
+            optional: true,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Graphite metric server (defaults to UDP).
+pub struct GraphiteServer {
+    pub name: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub enable: Option<bool>,
+    pub server: String,
+    pub port: u16,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub proto: Option<GraphiteProtocol>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub mtu: Option<u16>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+fn init() -> SectionConfig {
+    let influxdb_schema = match InfluxDbServer::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+    let graphite_schema = match GraphiteServer::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let mut config = SectionConfig::new(&METRIC_SERVER_ID_SCHEMA);
+
+    let plugin = SectionConfigPlugin::new("influxdb".to_string(), Some("name".to_string()), influxdb_schema);
+    config.register_plugin(plugin);
+    let plugin = SectionConfigPlugin::new("graphite".to_string(), Some("name".to_string()), graphite_schema);
+    config.register_plugin(plugin);
+
+    config
+}
+
+pub const METRIC_SERVER_CFG_FILENAME: &str = "/etc/proxmox-backup/metricserver.cfg";
+pub const METRIC_SERVER_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.metricserver.lck";
+
+pub fn config() -> Result<(SectionConfigData, [u8;32]), Error> {
+
+    let content = proxmox::tools::fs::file_read_optional_string(METRIC_SERVER_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(METRIC_SERVER_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(METRIC_SERVER_CFG_FILENAME, &config)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0640);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = root, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    replace_file(METRIC_SERVER_CFG_FILENAME, raw.as_bytes(), options)?;
+
+    Ok(())
+}
+
+// shell completion helper
+pub fn complete_metric_server_id(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => return vec![],
+    }
+}
@@ -0,0 +1,513 @@
+use anyhow::{bail, Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+
+use crate::api2::types::*;
+use crate::config::user::EMAIL_SCHEMA;
+
+lazy_static! {
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+pub const NOTIFICATION_NAME_SCHEMA: Schema = StringSchema::new(
+    "Name of the notification target or matcher.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(2)
+    .max_length(32)
+    .schema();
+
+pub const HTTP_URL_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|url| {
+    let url = url::Url::parse(url)?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        bail!("URL must use http or https");
+    }
+    Ok(())
+});
+
+pub const HTTP_URL_SCHEMA: Schema = StringSchema::new("HTTP(S) URL.")
+    .format(&HTTP_URL_FORMAT)
+    .max_length(1024)
+    .schema();
+
+pub const NOTIFICATION_SECRET_SCHEMA: Schema = StringSchema::new("Password or API token.")
+    .format(&PASSWORD_FORMAT)
+    .max_length(1024)
+    .schema();
+
+pub const EMAIL_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "E-Mail addresses.", &EMAIL_SCHEMA)
+    .schema();
+
+pub const EMAIL_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of e-mail addresses.")
+    .format(&ApiStringFormat::PropertyString(&EMAIL_ARRAY_SCHEMA))
+    .schema();
+
+pub const USERID_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "User IDs.", &Userid::API_SCHEMA)
+    .schema();
+
+pub const MAILTO_USER_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of users, the mail is sent to their configured e-mail address.")
+    .format(&ApiStringFormat::PropertyString(&USERID_ARRAY_SCHEMA))
+    .schema();
+
+pub const NOTIFICATION_NAME_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "Notification targets.", &NOTIFICATION_NAME_SCHEMA)
+    .schema();
+
+pub const TARGET_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of notification targets.")
+    .format(&ApiStringFormat::PropertyString(&NOTIFICATION_NAME_ARRAY_SCHEMA))
+    .schema();
+
+pub const DATASTORE_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "Datastores.", &DATASTORE_SCHEMA)
+    .schema();
+
+pub const MATCH_DATASTORE_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of datastores. Matches notifications of these datastores.")
+    .format(&ApiStringFormat::PropertyString(&DATASTORE_ARRAY_SCHEMA))
+    .schema();
+
+pub const NOTIFICATION_TYPE_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "Notification types.", &NotificationType::API_SCHEMA)
+    .schema();
+
+pub const MATCH_TYPE_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of notification types. Matches notifications of these types.")
+    .format(&ApiStringFormat::PropertyString(&NOTIFICATION_TYPE_ARRAY_SCHEMA))
+    .schema();
+
+pub const SEVERITY_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "Severities.", &Severity::API_SCHEMA)
+    .schema();
+
+pub const MATCH_SEVERITY_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of severities. Matches notifications with these severities.")
+    .format(&ApiStringFormat::PropertyString(&SEVERITY_ARRAY_SCHEMA))
+    .schema();
+
+pub const WEBHOOK_HEADERS_SCHEMA: Schema = StringSchema::new(
+    "HTTP headers, one 'Name: value' per line.")
+    .max_length(8*1024)
+    .schema();
+
+pub const WEBHOOK_BODY_SCHEMA: Schema = StringSchema::new(
+    "Handlebars template for the request body. Strings are JSON escaped, available \
+    variables are 'title', 'message', 'severity', 'type', 'hostname', 'timestamp' and \
+    'fields' (for example 'fields.datastore').")
+    .max_length(32*1024)
+    .schema();
+
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// Type of the notification (the event which caused it).
+pub enum NotificationType {
+    /// Garbage collection finished.
+    Gc,
+    /// Verification job finished.
+    Verify,
+    /// Sync job finished.
+    Sync,
+    /// Tape backup finished.
+    TapeBackup,
+    /// A tape needs to be loaded manually.
+    TapeLoadMedia,
+    /// New software packages are available.
+    PackageUpdates,
+    /// Test notification, sent to a single target.
+    Test,
+}
+
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// Severity of a notification.
+pub enum Severity {
+    /// Informational, like a successful job.
+    Info,
+    /// Something needs attention.
+    Warning,
+    /// A job failed.
+    Error,
+}
+
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// How to connect to the SMTP server (defaults to starttls).
+pub enum SmtpMode {
+    /// Plain connection (port 25).
+    Insecure,
+    /// Upgrade the connection with STARTTLS (port 587).
+    StartTls,
+    /// TLS connection (port 465).
+    Tls,
+}
+
+impl SmtpMode {
+    pub fn default_port(self) -> u16 {
+        match self {
+            SmtpMode::Insecure => 25,
+            SmtpMode::StartTls => 587,
+            SmtpMode::Tls => 465,
+        }
+    }
+}
+
+#[api()]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+/// HTTP method of webhook requests.
+pub enum WebhookMethod {
+    /// POST request.
+    Post,
+    /// PUT request.
+    Put,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_NAME_SCHEMA,
+        },
+        server: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            description: "Server port (defaults to the port of the connection mode).",
+            type: u16,
+            optional: true,
+        },
+        mode: {
+            type: SmtpMode,
+            optional: true,
+        },
+        username: {
+            description: "User name for the SMTP authentication.",
+            type: String,
+            optional: true,
+            max_length: 256,
+        },
+        password: {
+            schema: NOTIFICATION_SECRET_SCHEMA,
+            optional: true,
+        },
+        "from-address": {
+            schema: EMAIL_SCHEMA,
+        },
+        mailto: {
+            schema: EMAIL_LIST_SCHEMA,
+            optional: true,
+        },
+        "mailto-user": {
+            schema: MAILTO_USER_LIST_SCHEMA,
+            optional: true,
+        },
+        author: {
+            description: "Author of the mail (defaults to 'Proxmox Backup Server - <nodename>').",
+            type: String,
+            optional: true,
+            max_length: 256,
+        },
+        disable: {
+            description: "Do not send notifications to this target.",
+            type: bool,
+            optional: true,
+            default: false,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// SMTP notification target (sends mails directly, without the local mail system).
+pub struct SmtpTarget {
+    pub name: String,
+    pub server: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub mode: Option<SmtpMode>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub username: Option<String>,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub password: String,
+    pub from_address: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub mailto: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub mailto_user: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub disable: Option<bool>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_NAME_SCHEMA,
+        },
+        url: {
+            schema: HTTP_URL_SCHEMA,
+        },
+        method: {
+            type: WebhookMethod,
+            optional: true,
+        },
+        headers: {
+            schema: WEBHOOK_HEADERS_SCHEMA,
+            optional: true,
+        },
+        body: {
+            schema: WEBHOOK_BODY_SCHEMA,
+            optional: true,
+        },
+        disable: {
+            description: "Do not send notifications to this target.",
+            type: bool,
+            optional: true,
+            default: false,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Webhook notification target (HTTP request with a templated body).
+pub struct WebhookTarget {
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub method: Option<WebhookMethod>,
+    // may contain authentication tokens
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub headers: String,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub body: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub disable: Option<bool>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_NAME_SCHEMA,
+        },
+        server: {
+            description: "Base URL of the Gotify server.",
+            schema: HTTP_URL_SCHEMA,
+        },
+        token: {
+            schema: NOTIFICATION_SECRET_SCHEMA,
+        },
+        disable: {
+            description: "Do not send notifications to this target.",
+            type: bool,
+            optional: true,
+            default: false,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Gotify notification target (push notifications).
+pub struct GotifyTarget {
+    pub name: String,
+    pub server: String,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    #[serde(default)]
+    pub token: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub disable: Option<bool>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: NOTIFICATION_NAME_SCHEMA,
+        },
+        "match-type": {
+            schema: MATCH_TYPE_LIST_SCHEMA,
+            optional: true,
+        },
+        "match-severity": {
+            schema: MATCH_SEVERITY_LIST_SCHEMA,
+            optional: true,
+        },
+        "match-datastore": {
+            schema: MATCH_DATASTORE_LIST_SCHEMA,
+            optional: true,
+        },
+        target: {
+            schema: TARGET_LIST_SCHEMA,
+        },
+        disable: {
+            description: "Do not use this matcher.",
+            type: bool,
+            optional: true,
+            default: false,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Notification matcher, routes notifications to targets.
+///
+/// A notification matches if it matches all configured conditions, a matcher without conditions
+/// matches every notification.
+pub struct NotificationMatcher {
+    pub name: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub match_type: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub match_severity: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub match_datastore: Option<String>,
+    pub target: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub disable: Option<bool>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// Split a comma separated list property.
+pub fn split_list(list: Option<&str>) -> Vec<&str> {
+    list.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+fn init() -> SectionConfig {
+    let mut config = SectionConfig::new(&NOTIFICATION_NAME_SCHEMA);
+
+    let obj_schema = match SmtpTarget::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+    let plugin = SectionConfigPlugin::new("smtp".to_string(), Some("name".to_string()), obj_schema);
+    config.register_plugin(plugin);
+
+    let obj_schema = match WebhookTarget::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+    let plugin = SectionConfigPlugin::new("webhook".to_string(), Some("name".to_string()), obj_schema);
+    config.register_plugin(plugin);
+
+    let obj_schema = match GotifyTarget::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+    let plugin = SectionConfigPlugin::new("gotify".to_string(), Some("name".to_string()), obj_schema);
+    config.register_plugin(plugin);
+
+    let obj_schema = match NotificationMatcher::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+    let plugin = SectionConfigPlugin::new("matcher".to_string(), Some("name".to_string()), obj_schema);
+    config.register_plugin(plugin);
+
+    config
+}
+
+/// Section types of notification targets.
+pub const TARGET_TYPES: &[&str] = &["smtp", "webhook", "gotify"];
+
+pub const NOTIFICATION_CFG_FILENAME: &str = "/etc/proxmox-backup/notifications.cfg";
+pub const NOTIFICATION_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.notifications.lck";
+
+pub fn config() -> Result<(SectionConfigData, [u8;32]), Error> {
+
+    let content = proxmox::tools::fs::file_read_optional_string(NOTIFICATION_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(NOTIFICATION_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(NOTIFICATION_CFG_FILENAME, &config)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0640);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = root, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    replace_file(NOTIFICATION_CFG_FILENAME, raw.as_bytes(), options)?;
+
+    Ok(())
+}
+
+// shell completion helper
+pub fn complete_notification_target(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter()
+            .filter(|(_, (section_type, _))| TARGET_TYPES.contains(&section_type.as_str()))
+            .map(|(id, _)| id.to_string())
+            .collect(),
+        Err(_) => return vec![],
+    }
+}
+
+// shell completion helper
+pub fn complete_notification_matcher(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter()
+            .filter(|(_, (section_type, _))| section_type == "matcher")
+            .map(|(id, _)| id.to_string())
+            .collect(),
+        Err(_) => return vec![],
+    }
+}
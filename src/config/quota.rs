@@ -0,0 +1,239 @@
+use anyhow::{bail, Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+
+use crate::api2::types::*;
+
+lazy_static! {
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+pub const QUOTA_ID_SCHEMA: Schema = StringSchema::new("Quota ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+/// Quota subject, either a user or a user group
+#[derive(Clone, Debug, PartialEq)]
+pub enum QuotaSubject {
+    User(Userid),
+    Group(String),
+}
+
+impl std::str::FromStr for QuotaSubject {
+    type Err = Error;
+
+    fn from_str(subject: &str) -> Result<Self, Self::Err> {
+        // group names cannot contain '@', so a leading '@' is unambiguous
+        if let Some(group) = subject.strip_prefix('@') {
+            if let Err(err) = parse_simple_value(group, &PROXMOX_GROUP_ID_SCHEMA) {
+                bail!("invalid group name '{}' - {}", group, err);
+            }
+            Ok(QuotaSubject::Group(group.to_string()))
+        } else {
+            Ok(QuotaSubject::User(subject.parse()?))
+        }
+    }
+}
+
+impl std::fmt::Display for QuotaSubject {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            QuotaSubject::User(userid) => write!(f, "{}", userid),
+            QuotaSubject::Group(group) => write!(f, "@{}", group),
+        }
+    }
+}
+
+pub const QUOTA_SUBJECT_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|subject| {
+    subject.parse::<QuotaSubject>()?;
+    Ok(())
+});
+
+pub const QUOTA_SUBJECT_SCHEMA: Schema = StringSchema::new(
+    "The user (for example 'john@pbs') or user group (prefixed with '@', for example '@admins') \
+    the quota applies to. API tokens are accounted to their user.")
+    .format(&QUOTA_SUBJECT_FORMAT)
+    .min_length(2)
+    .max_length(256)
+    .schema();
+
+pub const QUOTA_SOFT_LIMIT_SCHEMA: Schema = IntegerSchema::new(
+    "Soft limit in bytes. Once exceeded, new backups are only accepted during the grace period.")
+    .minimum(1)
+    .schema();
+
+pub const QUOTA_HARD_LIMIT_SCHEMA: Schema = IntegerSchema::new(
+    "Hard limit in bytes. Once exceeded, new backups are rejected.")
+    .minimum(1)
+    .schema();
+
+pub const QUOTA_GRACE_PERIOD_SCHEMA: Schema = IntegerSchema::new(
+    "Number of days new backups are still accepted after the soft limit got exceeded.")
+    .minimum(0)
+    .maximum(365)
+    .default(7)
+    .schema();
+
+#[api(
+    properties: {
+        id: {
+            schema: QUOTA_ID_SCHEMA,
+        },
+        store: {
+            schema: DATASTORE_SCHEMA,
+        },
+        subject: {
+            schema: QUOTA_SUBJECT_SCHEMA,
+        },
+        "soft-limit": {
+            schema: QUOTA_SOFT_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "hard-limit": {
+            schema: QUOTA_HARD_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "grace-period": {
+            schema: QUOTA_GRACE_PERIOD_SCHEMA,
+            optional: true,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Datastore quota, limiting the unique (deduplicated) chunk usage of a user or user group.
+///
+/// The usage is computed by garbage collection, so limits are only enforced against the
+/// usage at the time of the last garbage collection run.
+pub struct QuotaConfig {
+    pub id: String,
+    pub store: String,
+    pub subject: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub soft_limit: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub hard_limit: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub grace_period: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl QuotaConfig {
+    /// Grace period in seconds
+    pub fn grace_period_secs(&self) -> i64 {
+        (self.grace_period.unwrap_or(7) as i64) * 24 * 3600
+    }
+}
+
+fn init() -> SectionConfig {
+    let obj_schema = match QuotaConfig::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("quota".to_string(), Some("id".to_string()), obj_schema);
+    let mut config = SectionConfig::new(&QUOTA_ID_SCHEMA);
+    config.register_plugin(plugin);
+
+    config
+}
+
+pub const QUOTA_CFG_FILENAME: &str = "/etc/proxmox-backup/quota.cfg";
+pub const QUOTA_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.quota.lck";
+
+pub fn config() -> Result<(SectionConfigData, [u8;32]), Error> {
+
+    let content = proxmox::tools::fs::file_read_optional_string(QUOTA_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(QUOTA_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(QUOTA_CFG_FILENAME, &config)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0640);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = root, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    replace_file(QUOTA_CFG_FILENAME, raw.as_bytes(), options)?;
+
+    Ok(())
+}
+
+/// Returns all quotas configured for datastore `store`.
+pub fn store_quotas(config: &SectionConfigData, store: &str) -> Result<Vec<QuotaConfig>, Error> {
+    let list: Vec<QuotaConfig> = config.convert_to_typed_array("quota")?;
+    Ok(list.into_iter().filter(|quota| quota.store == store).collect())
+}
+
+// shell completion helper
+pub fn complete_quota_id(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => return vec![],
+    }
+}
+
+// shell completion helper
+pub fn complete_quota_subject(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match crate::config::user::config() {
+        Ok((data, _digest)) => data.sections.iter()
+            .filter_map(|(id, (section_type, _))| match section_type.as_str() {
+                "user" => Some(id.to_string()),
+                "group" => Some(format!("@{}", id)),
+                _ => None,
+            })
+            .collect(),
+        Err(_) => vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quota_subject() -> Result<(), Error> {
+        assert_eq!(
+            "john@pbs".parse::<QuotaSubject>()?,
+            QuotaSubject::User("john@pbs".parse()?),
+        );
+        assert_eq!("@admins".parse::<QuotaSubject>()?, QuotaSubject::Group("admins".to_string()));
+        assert_eq!("@admins".parse::<QuotaSubject>()?.to_string(), "@admins");
+
+        assert!("@".parse::<QuotaSubject>().is_err());
+        assert!("@a/b".parse::<QuotaSubject>().is_err());
+        assert!("john".parse::<QuotaSubject>().is_err());
+        assert!("john@pbs!token".parse::<QuotaSubject>().is_err());
+
+        Ok(())
+    }
+}
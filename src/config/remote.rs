@@ -54,6 +54,22 @@ pub const REMOTE_PASSWORD_SCHEMA: Schema = StringSchema::new("Password or auth t
             optional: true,
             schema: CERT_FINGERPRINT_SHA256_SCHEMA,
         },
+        "rate-in": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-in": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
+        "rate-out": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-out": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
     }
 )]
 #[derive(Serialize,Deserialize)]
@@ -72,6 +88,26 @@ pub struct Remote {
     pub password: String,
     #[serde(skip_serializing_if="Option::is_none")]
     pub fingerprint: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_out: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_out: Option<u64>,
+}
+
+impl Remote {
+    /// Returns the configured rate limits.
+    pub fn rate_limit(&self) -> RateLimitConfig {
+        RateLimitConfig {
+            rate_in: self.rate_in,
+            burst_in: self.burst_in,
+            rate_out: self.rate_out,
+            burst_out: self.burst_out,
+        }
+    }
 }
 
 fn init() -> SectionConfig {
@@ -0,0 +1,167 @@
+use anyhow::{Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+
+use crate::api2::types::*;
+use crate::tools::s3::S3ClientOptions;
+
+lazy_static! {
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+pub const S3_ACCESS_KEY_SCHEMA: Schema = StringSchema::new("S3 access key ID.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(256)
+    .schema();
+
+pub const S3_SECRET_KEY_SCHEMA: Schema = StringSchema::new("S3 secret access key.")
+    .format(&PASSWORD_FORMAT)
+    .min_length(1)
+    .max_length(1024)
+    .schema();
+
+pub const S3_REGION_SCHEMA: Schema = StringSchema::new("S3 region (defaults to 'us-east-1').")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .max_length(32)
+    .schema();
+
+#[api(
+    properties: {
+        id: {
+            schema: S3_CLIENT_ID_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        endpoint: {
+            schema: DNS_NAME_OR_IP_SCHEMA,
+        },
+        port: {
+            optional: true,
+            description: "The (optional) port",
+            type: u16,
+        },
+        region: {
+            optional: true,
+            schema: S3_REGION_SCHEMA,
+        },
+        "access-key": {
+            schema: S3_ACCESS_KEY_SCHEMA,
+        },
+        "secret-key": {
+            schema: S3_SECRET_KEY_SCHEMA,
+        },
+        fingerprint: {
+            optional: true,
+            schema: CERT_FINGERPRINT_SHA256_SCHEMA,
+        },
+        "plain-http": {
+            optional: true,
+            description: "Use plain HTTP instead of HTTPS (only for local test setups).",
+            type: bool,
+            default: false,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize)]
+#[serde(rename_all = "kebab-case")]
+/// S3 client configuration properties.
+pub struct S3ClientConfig {
+    pub id: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+    pub endpoint: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub region: Option<String>,
+    pub access_key: String,
+    #[serde(skip_serializing_if="String::is_empty")]
+    #[serde(with = "proxmox::tools::serde::string_as_base64")]
+    pub secret_key: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub plain_http: Option<bool>,
+}
+
+impl S3ClientConfig {
+    /// Returns the options to connect to the configured endpoint.
+    pub fn client_options(&self) -> S3ClientOptions {
+        S3ClientOptions {
+            host: self.endpoint.clone(),
+            port: self.port,
+            region: self.region.clone().unwrap_or_else(|| String::from("us-east-1")),
+            access_key: self.access_key.clone(),
+            secret_key: self.secret_key.clone(),
+            fingerprint: self.fingerprint.clone(),
+            plain_http: self.plain_http.unwrap_or(false),
+        }
+    }
+}
+
+fn init() -> SectionConfig {
+    let obj_schema = match S3ClientConfig::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("s3".to_string(), Some("id".to_string()), obj_schema);
+    let mut config = SectionConfig::new(&S3_CLIENT_ID_SCHEMA);
+    config.register_plugin(plugin);
+
+    config
+}
+
+pub const S3_CFG_FILENAME: &str = "/etc/proxmox-backup/s3.cfg";
+pub const S3_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.s3.lck";
+
+pub fn config() -> Result<(SectionConfigData, [u8;32]), Error> {
+
+    let content = proxmox::tools::fs::file_read_optional_string(S3_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(S3_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(S3_CFG_FILENAME, &config)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0640);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = root, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    replace_file(S3_CFG_FILENAME, raw.as_bytes(), options)?;
+
+    Ok(())
+}
+
+// shell completion helper
+pub fn complete_s3_client_id(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => return vec![],
+    }
+}
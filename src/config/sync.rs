@@ -16,6 +16,7 @@ use proxmox::api::{
 use proxmox::tools::{fs::replace_file, fs::CreateOptions};
 
 use crate::api2::types::*;
+use crate::backup::BackupNamespace;
 
 lazy_static! {
     pub static ref CONFIG: SectionConfig = init();
@@ -29,6 +30,10 @@ lazy_static! {
         store: {
            schema: DATASTORE_SCHEMA,
         },
+        ns: {
+            schema: BACKUP_NAMESPACE_SCHEMA,
+            optional: true,
+        },
         "owner": {
             type: Authid,
             optional: true,
@@ -36,13 +41,49 @@ lazy_static! {
         remote: {
             schema: REMOTE_ID_SCHEMA,
         },
+        "sync-direction": {
+            type: SyncDirection,
+            optional: true,
+        },
         "remote-store": {
             schema: DATASTORE_SCHEMA,
         },
+        "remote-ns": {
+            schema: BACKUP_NAMESPACE_SCHEMA,
+            optional: true,
+        },
+        "max-depth": {
+            schema: NS_MAX_DEPTH_SCHEMA,
+            optional: true,
+        },
         "remove-vanished": {
             schema: REMOVE_VANISHED_BACKUPS_SCHEMA,
             optional: true,
         },
+        "group-filter": {
+            schema: GROUP_FILTER_LIST_SCHEMA,
+            optional: true,
+        },
+        "transfer-last": {
+            schema: TRANSFER_LAST_SCHEMA,
+            optional: true,
+        },
+        "rate-in": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-in": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
+        "rate-out": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-out": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
         comment: {
             optional: true,
             schema: SINGLE_LINE_COMMENT_SCHEMA,
@@ -60,17 +101,49 @@ pub struct SyncJobConfig {
     pub id: String,
     pub store: String,
     #[serde(skip_serializing_if="Option::is_none")]
+    pub ns: Option<BackupNamespace>,
+    #[serde(skip_serializing_if="Option::is_none")]
     pub owner: Option<Authid>,
     pub remote: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub sync_direction: Option<SyncDirection>,
     pub remote_store: String,
     #[serde(skip_serializing_if="Option::is_none")]
+    pub remote_ns: Option<BackupNamespace>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(skip_serializing_if="Option::is_none")]
     pub remove_vanished: Option<bool>,
     #[serde(skip_serializing_if="Option::is_none")]
+    pub group_filter: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub transfer_last: Option<usize>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_out: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_out: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
     pub comment: Option<String>,
     #[serde(skip_serializing_if="Option::is_none")]
     pub schedule: Option<String>,
 }
 
+impl SyncJobConfig {
+    /// Returns the configured rate limits.
+    pub fn rate_limit(&self) -> RateLimitConfig {
+        RateLimitConfig {
+            rate_in: self.rate_in,
+            burst_in: self.burst_in,
+            rate_out: self.rate_out,
+            burst_out: self.burst_out,
+        }
+    }
+}
+
 #[api(
     properties: {
         config: {
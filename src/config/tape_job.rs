@@ -15,10 +15,15 @@ use proxmox::api::{
 
 use proxmox::tools::{fs::replace_file, fs::CreateOptions};
 
+use crate::backup::BackupNamespace;
+
 use crate::api2::types::{
     Userid,
     JOB_ID_SCHEMA,
     DATASTORE_SCHEMA,
+    BACKUP_NAMESPACE_SCHEMA,
+    NS_MAX_DEPTH_SCHEMA,
+    GROUP_FILTER_LIST_SCHEMA,
     DRIVE_NAME_SCHEMA,
     MEDIA_POOL_NAME_SCHEMA,
     SINGLE_LINE_COMMENT_SCHEMA,
@@ -60,6 +65,18 @@ lazy_static! {
             optional: true,
             type: Userid,
         },
+        ns: {
+            schema: BACKUP_NAMESPACE_SCHEMA,
+            optional: true,
+        },
+        "max-depth": {
+            schema: NS_MAX_DEPTH_SCHEMA,
+            optional: true,
+        },
+        "group-filter": {
+            schema: GROUP_FILTER_LIST_SCHEMA,
+            optional: true,
+        },
     }
 )]
 #[serde(rename_all="kebab-case")]
@@ -78,6 +95,14 @@ pub struct TapeBackupJobSetup {
     /// Send job email notification to this user
     #[serde(skip_serializing_if="Option::is_none")]
     pub notify_user: Option<Userid>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    /// only backup groups in this namespace (and below, up to max-depth)
+    pub ns: Option<BackupNamespace>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    /// only backup groups passing these filters
+    pub group_filter: Option<String>,
 }
 
 #[api(
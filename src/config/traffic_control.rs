@@ -0,0 +1,240 @@
+use anyhow::{bail, format_err, Error};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use serde::{Serialize, Deserialize};
+
+use proxmox::api::{
+    api,
+    schema::*,
+    section_config::{
+        SectionConfig,
+        SectionConfigData,
+        SectionConfigPlugin,
+    }
+};
+
+use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+
+use crate::api2::types::*;
+
+lazy_static! {
+    pub static ref CONFIG: SectionConfig = init();
+}
+
+pub const TRAFFIC_CONTROL_ID_SCHEMA: Schema = StringSchema::new("Rule ID.")
+    .format(&PROXMOX_SAFE_ID_FORMAT)
+    .min_length(3)
+    .max_length(32)
+    .schema();
+
+pub const NETWORK_LIST_FORMAT: ApiStringFormat = ApiStringFormat::VerifyFn(|list| {
+    parse_network_list(list)?;
+    Ok(())
+});
+
+pub const NETWORK_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of networks (IPv4 or IPv6 address with netmask, CIDR notation), \
+    for example '192.168.2.0/24,fd00::/8'.")
+    .format(&NETWORK_LIST_FORMAT)
+    .schema();
+
+/// A network in CIDR notation
+#[derive(Clone, Debug, PartialEq)]
+pub struct Network {
+    pub address: IpAddr,
+    pub mask: u8,
+}
+
+impl std::str::FromStr for Network {
+    type Err = Error;
+
+    fn from_str(cidr: &str) -> Result<Self, Self::Err> {
+        let mut parts = cidr.splitn(2, '/');
+        let address = parts.next().unwrap_or("");
+        let mask = parts.next()
+            .ok_or_else(|| format_err!("missing netmask in '{}'", cidr))?;
+
+        let address: IpAddr = address.parse()
+            .map_err(|err| format_err!("unable to parse address in '{}' - {}", cidr, err))?;
+        let mask: u8 = mask.parse()
+            .map_err(|err| format_err!("unable to parse netmask in '{}' - {}", cidr, err))?;
+
+        let max_mask = if address.is_ipv6() { 128 } else { 32 };
+        if mask > max_mask {
+            bail!("netmask '{}' is out of range (0..{}).", mask, max_mask);
+        }
+
+        Ok(Self { address, mask })
+    }
+}
+
+impl Network {
+    /// Test if `ip` is part of this network. IPv4 mapped IPv6 addresses are treated as IPv4.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(ip6) => match ip6.to_ipv4() {
+                Some(ip4) if ip6.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => IpAddr::V4(ip4),
+                _ => *ip,
+            },
+            IpAddr::V4(_) => *ip,
+        };
+
+        match (self.address, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = if self.mask == 0 { 0 } else { u32::MAX << (32 - self.mask) };
+                (u32::from(net) & mask) == (u32::from(ip) & mask)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = if self.mask == 0 { 0 } else { u128::MAX << (128 - self.mask) };
+                (u128::from(net) & mask) == (u128::from(ip) & mask)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Parse a comma separated list of networks.
+pub fn parse_network_list(list: &str) -> Result<Vec<Network>, Error> {
+    let mut networks = Vec::new();
+    for cidr in list.split(',') {
+        let cidr = cidr.trim();
+        if cidr.is_empty() {
+            continue;
+        }
+        networks.push(cidr.parse()?);
+    }
+    if networks.is_empty() {
+        bail!("empty network list");
+    }
+    Ok(networks)
+}
+
+#[api(
+    properties: {
+        name: {
+            schema: TRAFFIC_CONTROL_ID_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        network: {
+            schema: NETWORK_LIST_SCHEMA,
+        },
+        "rate-in": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-in": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
+        "rate-out": {
+            schema: RATE_LIMIT_SCHEMA,
+            optional: true,
+        },
+        "burst-out": {
+            schema: BURST_SCHEMA,
+            optional: true,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize,Clone)]
+#[serde(rename_all = "kebab-case")]
+/// Traffic control rule, limiting the traffic of all clients from the listed networks.
+///
+/// Incoming is the traffic sent by the clients (e.g. backups), outgoing the traffic sent to
+/// the clients (e.g. restores).
+pub struct TrafficControlRule {
+    pub name: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+    pub network: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_in: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub rate_out: Option<u64>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub burst_out: Option<u64>,
+}
+
+fn init() -> SectionConfig {
+    let obj_schema = match TrafficControlRule::API_SCHEMA {
+        Schema::Object(ref obj_schema) => obj_schema,
+        _ => unreachable!(),
+    };
+
+    let plugin = SectionConfigPlugin::new("rule".to_string(), Some("name".to_string()), obj_schema);
+    let mut config = SectionConfig::new(&TRAFFIC_CONTROL_ID_SCHEMA);
+    config.register_plugin(plugin);
+
+    config
+}
+
+pub const TRAFFIC_CONTROL_CFG_FILENAME: &str = "/etc/proxmox-backup/traffic-control.cfg";
+pub const TRAFFIC_CONTROL_CFG_LOCKFILE: &str = "/etc/proxmox-backup/.traffic-control.lck";
+
+pub fn config() -> Result<(SectionConfigData, [u8;32]), Error> {
+
+    let content = proxmox::tools::fs::file_read_optional_string(TRAFFIC_CONTROL_CFG_FILENAME)?
+        .unwrap_or_else(|| "".to_string());
+
+    let digest = openssl::sha::sha256(content.as_bytes());
+    let data = CONFIG.parse(TRAFFIC_CONTROL_CFG_FILENAME, &content)?;
+    Ok((data, digest))
+}
+
+pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
+    let raw = CONFIG.write(TRAFFIC_CONTROL_CFG_FILENAME, &config)?;
+
+    let backup_user = crate::backup::backup_user()?;
+    let mode = nix::sys::stat::Mode::from_bits_truncate(0o0640);
+    // set the correct owner/group/permissions while saving file
+    // owner(rw) = root, group(r)= backup
+    let options = CreateOptions::new()
+        .perm(mode)
+        .owner(nix::unistd::ROOT)
+        .group(backup_user.gid);
+
+    replace_file(TRAFFIC_CONTROL_CFG_FILENAME, raw.as_bytes(), options)?;
+
+    Ok(())
+}
+
+// shell completion helper
+pub fn complete_traffic_control_name(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Err(_) => return vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_network_contains() -> Result<(), Error> {
+        let net: Network = "192.168.2.0/24".parse()?;
+        assert!(net.contains(&"192.168.2.17".parse()?));
+        assert!(net.contains(&"::ffff:192.168.2.17".parse()?));
+        assert!(!net.contains(&"192.168.3.17".parse()?));
+        assert!(!net.contains(&"fd00::1".parse()?));
+
+        let net: Network = "fd00::/8".parse()?;
+        assert!(net.contains(&"fd00::1".parse()?));
+        assert!(!net.contains(&"fe80::1".parse()?));
+
+        let net: Network = "0.0.0.0/0".parse()?;
+        assert!(net.contains(&"10.0.0.1".parse()?));
+
+        assert!("192.168.2.0/33".parse::<Network>().is_err());
+        assert!("192.168.2.0".parse::<Network>().is_err());
+        assert!(parse_network_list("").is_err());
+
+        Ok(())
+    }
+}
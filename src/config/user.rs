@@ -15,9 +15,10 @@ use proxmox::api::{
     }
 };
 
-use proxmox::tools::{fs::replace_file, fs::CreateOptions};
+use proxmox::tools::{fs::open_file_locked, fs::replace_file, fs::CreateOptions};
 
 use crate::api2::types::*;
+use crate::config::acl;
 
 lazy_static! {
     pub static ref CONFIG: SectionConfig = init();
@@ -132,6 +133,74 @@ pub struct User {
     pub email: Option<String>,
 }
 
+pub const GROUP_MEMBER_ARRAY_SCHEMA: Schema = ArraySchema::new(
+    "Group members.", &Userid::API_SCHEMA)
+    .schema();
+
+pub const GROUP_MEMBER_LIST_SCHEMA: Schema = StringSchema::new(
+    "Comma separated list of group members (user IDs).")
+    .format(&ApiStringFormat::PropertyString(&GROUP_MEMBER_ARRAY_SCHEMA))
+    .schema();
+
+#[api(
+    properties: {
+        groupid: {
+            schema: PROXMOX_GROUP_ID_SCHEMA,
+        },
+        comment: {
+            optional: true,
+            schema: SINGLE_LINE_COMMENT_SCHEMA,
+        },
+        members: {
+            optional: true,
+            schema: GROUP_MEMBER_LIST_SCHEMA,
+        },
+        realm: {
+            optional: true,
+            description: "The realm this group is synced from.",
+            schema: PROXMOX_AUTH_REALM_SCHEMA,
+        },
+    }
+)]
+#[derive(Serialize,Deserialize)]
+/// User group properties.
+pub struct UserGroup {
+    pub groupid: String,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub members: Option<String>,
+    /// Set for groups managed by a realm sync
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub realm: Option<String>,
+}
+
+impl UserGroup {
+    /// Returns the list of members.
+    pub fn member_list(&self) -> Result<Vec<Userid>, Error> {
+        let mut list = Vec::new();
+        if let Some(members) = &self.members {
+            for member in members.split(',') {
+                let member = member.trim();
+                if !member.is_empty() {
+                    list.push(member.parse()?);
+                }
+            }
+        }
+        Ok(list)
+    }
+
+    /// Replaces the list of members.
+    pub fn set_member_list(&mut self, list: &[Userid]) {
+        if list.is_empty() {
+            self.members = None;
+        } else {
+            let list: Vec<&str> = list.iter().map(|userid| userid.as_str()).collect();
+            self.members = Some(list.join(","));
+        }
+    }
+}
+
 fn init() -> SectionConfig {
     let mut config = SectionConfig::new(&Authid::API_SCHEMA);
 
@@ -149,6 +218,13 @@ fn init() -> SectionConfig {
     let token_plugin = SectionConfigPlugin::new("token".to_string(), Some("tokenid".to_string()), token_schema);
     config.register_plugin(token_plugin);
 
+    let group_schema = match UserGroup::API_SCHEMA {
+        Schema::Object(ref group_schema) => group_schema,
+        _ => unreachable!(),
+    };
+    let group_plugin = SectionConfigPlugin::new("group".to_string(), Some("groupid".to_string()), group_schema);
+    config.register_plugin(group_plugin);
+
     config
 }
 
@@ -241,6 +317,29 @@ pub fn save_config(config: &SectionConfigData) -> Result<(), Error> {
     Ok(())
 }
 
+/// Remove the ACL entries (including those of their API tokens) and the
+/// second factors of users deleted from the user configuration.
+///
+/// The caller needs to hold the TFA config lock.
+pub fn cleanup_deleted_users(userids: &[Userid]) -> Result<(), Error> {
+    if userids.is_empty() {
+        return Ok(());
+    }
+
+    let _lock = open_file_locked(acl::ACL_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+    let (mut tree, _digest) = acl::config()?;
+    for userid in userids {
+        tree.delete_authid(&Authid::from(userid.clone()));
+    }
+    acl::save_config(&tree)?;
+
+    let mut tfa = crate::config::tfa::read()?;
+    for userid in userids {
+        let _: bool = tfa.remove_user(userid);
+    }
+    crate::config::tfa::write(&tfa)
+}
+
 #[cfg(test)]
 pub(crate) fn test_cfg_from_str(raw: &str) -> Result<(SectionConfigData, [u8;32]), Error> {
     let cfg = init();
@@ -269,7 +368,25 @@ pub fn complete_userid(_arg: &str, _param: &HashMap<String, String>) -> Vec<Stri
 // shell completion helper
 pub fn complete_authid(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
     match config() {
-        Ok((data, _digest)) => data.sections.iter().map(|(id, _)| id.to_string()).collect(),
+        Ok((data, _digest)) => {
+            data.sections.iter()
+                .filter(|(_, (section_type, _))| section_type != "group")
+                .map(|(id, _)| id.to_string())
+                .collect()
+        },
+        Err(_) => vec![],
+    }
+}
+
+// shell completion helper
+pub fn complete_group_id(_arg: &str, _param: &HashMap<String, String>) -> Vec<String> {
+    match config() {
+        Ok((data, _digest)) => {
+            data.sections.iter()
+                .filter(|(_, (section_type, _))| section_type == "group")
+                .map(|(id, _)| id.to_string())
+                .collect()
+        },
         Err(_) => vec![],
     }
 }
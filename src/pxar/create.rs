@@ -1,11 +1,14 @@
 use std::collections::{HashSet, HashMap};
 use std::ffi::{CStr, CString, OsStr};
 use std::fmt;
-use std::io::{self, Read, Write};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::path::{Path, PathBuf};
+use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
 
 use anyhow::{bail, format_err, Error};
 use nix::dir::Dir;
@@ -24,7 +27,11 @@ use proxmox::sys::error::SysError;
 use proxmox::tools::fd::RawFdNum;
 use proxmox::tools::vec;
 
+use crate::backup::{
+    CatalogReader, DirEntry, DirEntryAttribute, DynamicIndexReader, IndexFile, InjectChunks,
+};
 use crate::pxar::catalog::BackupCatalogWriter;
+use crate::pxar::fuse::{Accessor, Directory};
 use crate::pxar::metadata::errno_is_unsupported;
 use crate::pxar::Flags;
 use crate::pxar::tools::assert_single_path_component;
@@ -99,6 +106,147 @@ impl fmt::Display for ArchiveError {
     }
 }
 
+/// Previous version of an archive, used to reuse the payload of unchanged files
+///
+/// A file is considered unchanged if its size and modification time match the
+/// previous archive. Its payload is then not read, instead the chunks of the
+/// previous archive covering it are announced on `boundaries` to be injected
+/// into the chunk stream (see `InjectionData`).
+pub struct PreviousArchive {
+    /// Accessor for the previous archive
+    pub accessor: Accessor,
+    /// Index of the previous archive
+    pub index: DynamicIndexReader,
+    /// Catalog of the previous snapshot
+    pub catalog: CatalogReader<std::fs::File>,
+    /// Catalog directory entry of the previous archive
+    pub catalog_root: DirEntry,
+    /// Receives the chunks to inject, with the stream offset of their boundary
+    pub boundaries: std::sync::mpsc::Sender<InjectChunks>,
+}
+
+// State shared between the archiver and its output writer.
+#[derive(Default)]
+struct InjectState {
+    // current position in the archive stream
+    position: u64,
+    // stream range replaced by injected chunks, which gets dropped
+    skip: Option<Range<u64>>,
+}
+
+// Tracks the archive stream position and drops the payload replaced by
+// injected chunks.
+struct InjectWriter<T> {
+    inner: T,
+    state: Option<Arc<Mutex<InjectState>>>,
+}
+
+impl<T: SeqWrite> SeqWrite for InjectWriter<T> {
+    fn poll_seq_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = unsafe { self.get_unchecked_mut() };
+        let inner = unsafe { Pin::new_unchecked(&mut this.inner) };
+
+        let mut state = match this.state {
+            Some(ref state) => state.lock().unwrap(),
+            None => return inner.poll_seq_write(cx, buf),
+        };
+
+        let mut buf = buf;
+        if let Some(skip) = state.skip.clone() {
+            if state.position >= skip.start {
+                let len = (skip.end - state.position).min(buf.len() as u64);
+                state.position += len;
+                if state.position >= skip.end {
+                    state.skip = None;
+                }
+                return Poll::Ready(Ok(len as usize));
+            }
+            let len = (skip.start - state.position).min(buf.len() as u64);
+            buf = &buf[..(len as usize)];
+        }
+
+        match inner.poll_seq_write(cx, buf) {
+            Poll::Ready(Ok(got)) => {
+                state.position += got as u64;
+                Poll::Ready(Ok(got))
+            }
+            other => other,
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = unsafe { self.get_unchecked_mut() };
+        unsafe { Pin::new_unchecked(&mut this.inner) }.poll_flush(cx)
+    }
+}
+
+// Previous version of a directory, `None` on the stack if it did not exist.
+struct PreviousDir {
+    entries: HashMap<Vec<u8>, DirEntry>,
+    dir: Directory,
+}
+
+impl PreviousDir {
+    fn new(
+        catalog: &mut CatalogReader<std::fs::File>,
+        catalog_entry: &DirEntry,
+        dir: Directory,
+    ) -> Result<Self, Error> {
+        let entries = catalog
+            .read_dir(catalog_entry)?
+            .into_iter()
+            .map(|entry| (entry.name.clone(), entry))
+            .collect();
+        Ok(Self { entries, dir })
+    }
+}
+
+struct PreviousState {
+    archive: PreviousArchive,
+    writer_state: Arc<Mutex<InjectState>>,
+    dirs: Vec<Option<PreviousDir>>,
+    reused_files: u64,
+    reused_bytes: u64,
+}
+
+// Part of a file's payload (relative to its start) covered by chunks of the previous archive.
+struct ReusableChunks {
+    range: Range<u64>,
+    chunks: Vec<(u64, [u8; 32])>,
+}
+
+// Returns the chunks of `index` which lie completely within `range`.
+fn chunks_within_range(index: &DynamicIndexReader, range: &Range<u64>) -> Option<(Range<u64>, Vec<(u64, [u8; 32])>)> {
+    if index.index_count() == 0 {
+        return None;
+    }
+
+    let (mut pos, offset_in_chunk) = index.chunk_from_offset(range.start)?;
+    if offset_in_chunk != 0 {
+        pos += 1;
+    }
+
+    let mut chunks = Vec::new();
+    let mut covered: Option<Range<u64>> = None;
+    while let Some(info) = index.chunk_info(pos) {
+        if info.range.end > range.end {
+            break;
+        }
+        chunks.push((info.range.end - info.range.start, info.digest));
+        covered = match covered {
+            Some(covered) => Some(covered.start..info.range.end),
+            None => Some(info.range.clone()),
+        };
+        pos += 1;
+    }
+
+    covered.map(|covered| (covered, chunks))
+}
+
 #[derive(Eq, PartialEq, Hash)]
 struct HardLinkInfo {
     st_dev: u64,
@@ -148,17 +296,19 @@ struct Archiver {
     errors: ErrorReporter,
     logger: Logger,
     file_copy_buffer: Vec<u8>,
+    previous: Option<PreviousState>,
 }
 
 type Encoder<'a, T> = pxar::encoder::aio::Encoder<'a, T>;
 
 pub async fn create_archive<T, F>(
     source_dir: Dir,
-    mut writer: T,
+    writer: T,
     feature_flags: Flags,
     callback: F,
     catalog: Option<Arc<Mutex<dyn BackupCatalogWriter + Send>>>,
     options: PxarCreateOptions,
+    previous: Option<PreviousArchive>,
 ) -> Result<(), Error>
 where
     T: SeqWrite + Send,
@@ -185,6 +335,26 @@ where
         set.insert(stat.st_dev);
     }
 
+    let previous = match previous {
+        Some(mut archive) => {
+            let root_dir = archive.accessor.open_root().await?;
+            let root = PreviousDir::new(&mut archive.catalog, &archive.catalog_root, root_dir)?;
+            Some(PreviousState {
+                archive,
+                writer_state: Arc::new(Mutex::new(InjectState::default())),
+                dirs: vec![Some(root)],
+                reused_files: 0,
+                reused_bytes: 0,
+            })
+        }
+        None => None,
+    };
+
+    let mut writer = InjectWriter {
+        inner: writer,
+        state: previous.as_ref().map(|previous| Arc::clone(&previous.writer_state)),
+    };
+
     let mut encoder = Encoder::new(&mut writer, &metadata).await?;
 
     let mut patterns = options.patterns;
@@ -213,10 +383,21 @@ where
         errors: ErrorReporter,
         logger: Logger,
         file_copy_buffer: vec::undefined(4 * 1024 * 1024),
+        previous,
     };
 
     archiver.archive_dir_contents(&mut encoder, source_dir, true).await?;
     encoder.finish().await?;
+
+    if let Some(ref previous) = archiver.previous {
+        writeln!(
+            archiver.logger,
+            "reused payload of {} unchanged files ({}) from previous archive",
+            previous.reused_files,
+            crate::tools::format::HumanByte::from(previous.reused_bytes as usize),
+        )?;
+    }
+
     Ok(())
 }
 
@@ -568,8 +749,10 @@ impl Archiver {
                     catalog.lock().unwrap().add_file(c_file_name, file_size, stat.st_mtime)?;
                 }
 
+                let reuse = self.lookup_reusable_chunks(c_file_name, stat).await;
+
                 let offset: LinkOffset =
-                    self.add_regular_file(encoder, fd, file_name, &metadata, file_size).await?;
+                    self.add_regular_file(encoder, fd, file_name, &metadata, file_size, reuse).await?;
 
                 if stat.st_nlink > 1 {
                     self.hardlinks.insert(link_info, (self.path.clone(), offset));
@@ -665,7 +848,12 @@ impl Archiver {
             writeln!(self.logger, "skipping mount point: {:?}", self.path)?;
             Ok(())
         } else {
-            self.archive_dir_contents(&mut encoder, dir, false).await
+            self.enter_previous_dir(dir_name).await;
+            let result = self.archive_dir_contents(&mut encoder, dir, false).await;
+            if let Some(ref mut previous) = self.previous {
+                previous.dirs.pop();
+            }
+            result
         };
 
         self.fs_magic = old_fs_magic;
@@ -683,26 +871,59 @@ impl Archiver {
         file_name: &Path,
         metadata: &Metadata,
         file_size: u64,
+        mut reuse: Option<ReusableChunks>,
     ) -> Result<LinkOffset, Error> {
         let mut file = unsafe { std::fs::File::from_raw_fd(fd.into_raw_fd()) };
-        let mut remaining = file_size;
         let mut out = encoder.create_file(metadata, file_name, file_size).await?;
-        while remaining != 0 {
-            let mut got = match file.read(&mut self.file_copy_buffer[..]) {
-                Ok(0) => break,
-                Ok(got) => got,
-                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
-                Err(err) => bail!(err),
-            };
-            if got as u64 > remaining {
-                self.report_file_grew_while_reading()?;
-                got = remaining as usize;
+
+        let mut pos = 0;
+        // read the file up to the reused part, if any
+        let mut read_end = reuse.as_ref().map(|reuse| reuse.range.start).unwrap_or(file_size);
+        loop {
+            while pos < read_end {
+                let mut got = match file.read(&mut self.file_copy_buffer[..]) {
+                    Ok(0) => break,
+                    Ok(got) => got,
+                    Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                    Err(err) => bail!(err),
+                };
+                if got as u64 > read_end - pos {
+                    if read_end == file_size {
+                        self.report_file_grew_while_reading()?;
+                    }
+                    got = (read_end - pos) as usize;
+                }
+                out.write_all(&self.file_copy_buffer[..got]).await?;
+                pos += got as u64;
+            }
+
+            if pos < read_end {
+                break; // file shrunk
+            }
+
+            match reuse.take() {
+                Some(reuse) => {
+                    self.inject_chunks(reuse.chunks, reuse.range.end - reuse.range.start)?;
+
+                    // the writer drops this part of the payload, it is
+                    // replaced by the injected chunks
+                    vec::clear(&mut self.file_copy_buffer[..]);
+                    while pos < reuse.range.end {
+                        let fill = (reuse.range.end - pos).min(self.file_copy_buffer.len() as u64) as usize;
+                        out.write_all(&self.file_copy_buffer[..fill]).await?;
+                        pos += fill as u64;
+                    }
+
+                    file.seek(SeekFrom::Start(pos))?;
+                    read_end = file_size;
+                }
+                None => break,
             }
-            out.write_all(&self.file_copy_buffer[..got]).await?;
-            remaining -= got as u64;
         }
-        if remaining > 0 {
+
+        if pos < file_size {
             self.report_file_shrunk_while_reading()?;
+            let mut remaining = file_size - pos;
             let to_zero = remaining.min(self.file_copy_buffer.len() as u64) as usize;
             vec::clear(&mut self.file_copy_buffer[..to_zero]);
             while remaining != 0 {
@@ -715,6 +936,133 @@ impl Archiver {
         Ok(out.file_offset())
     }
 
+    /// Push the previous version of directory `dir_name` (or `None`) onto the stack of previous
+    /// directories.
+    async fn enter_previous_dir(&mut self, dir_name: &OsStr) {
+        let previous = match self.previous {
+            Some(ref mut previous) => previous,
+            None => return,
+        };
+
+        let parent = match previous.dirs.last() {
+            Some(Some(parent)) => parent,
+            _ => {
+                previous.dirs.push(None);
+                return;
+            }
+        };
+
+        let catalog_entry = match parent.entries.get(dir_name.as_bytes()) {
+            Some(entry) if entry.is_directory() => entry.clone(),
+            _ => {
+                previous.dirs.push(None);
+                return;
+            }
+        };
+
+        let catalog = &mut previous.archive.catalog;
+        let result: Result<Option<PreviousDir>, Error> = async {
+            let entry = match parent.dir.lookup(dir_name).await? {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+            let dir = entry.enter_directory().await?;
+            Ok(Some(PreviousDir::new(catalog, &catalog_entry, dir)?))
+        }.await;
+
+        let dir = match result {
+            Ok(dir) => dir,
+            Err(err) => {
+                let _ = writeln!(
+                    self.errors,
+                    "failed to read {:?} from previous archive, not reusing its contents: {}",
+                    self.path,
+                    err,
+                );
+                None
+            }
+        };
+
+        if let Some(ref mut previous) = self.previous {
+            previous.dirs.push(dir);
+        }
+    }
+
+    /// Look up the chunks of the previous archive which can be reused for a file, if it did not
+    /// change.
+    async fn lookup_reusable_chunks(&mut self, file_name: &CStr, stat: &FileStat) -> Option<ReusableChunks> {
+        let dir = self.previous.as_ref()?.dirs.last()?.as_ref()?;
+
+        // check the catalog first, it is way cheaper than the archive
+        match dir.entries.get(file_name.to_bytes()) {
+            Some(DirEntry { attr: DirEntryAttribute::File { size, mtime }, .. })
+                if *size == stat.st_size as u64 && *mtime == stat.st_mtime => (),
+            _ => return None,
+        }
+
+        let result: Result<Option<Range<u64>>, Error> = async {
+            let entry = match dir.dir.lookup(OsStr::from_bytes(file_name.to_bytes())).await? {
+                Some(entry) => entry,
+                None => return Ok(None),
+            };
+
+            let mtime = &entry.metadata().stat.mtime;
+            if mtime.secs != stat.st_mtime || mtime.nanos != stat.st_mtime_nsec as u32 {
+                return Ok(None);
+            }
+
+            match entry.content_range()? {
+                Some(range) if range.end - range.start == stat.st_size as u64 => Ok(Some(range)),
+                _ => Ok(None),
+            }
+        }.await;
+
+        let content_range = match result {
+            Ok(Some(range)) => range,
+            Ok(None) => return None,
+            Err(err) => {
+                let _ = writeln!(
+                    self.errors,
+                    "failed to look up {:?} in previous archive: {}",
+                    self.path,
+                    err,
+                );
+                return None;
+            }
+        };
+
+        let index = &self.previous.as_ref()?.archive.index;
+        let (covered, chunks) = chunks_within_range(index, &content_range)?;
+
+        Some(ReusableChunks {
+            range: (covered.start - content_range.start)..(covered.end - content_range.start),
+            chunks,
+        })
+    }
+
+    /// Announce chunks to inject at the current stream position and skip the payload they
+    /// replace.
+    fn inject_chunks(&mut self, chunks: Vec<(u64, [u8; 32])>, size: u64) -> Result<(), Error> {
+        let previous = self
+            .previous
+            .as_mut()
+            .ok_or_else(|| format_err!("no previous archive to inject chunks from"))?;
+
+        let mut state = previous.writer_state.lock().unwrap();
+        let boundary = state.position;
+        state.skip = Some(boundary..(boundary + size));
+        drop(state);
+
+        previous.archive.boundaries
+            .send(InjectChunks { boundary, chunks, size })
+            .map_err(|_| format_err!("unable to inject reused chunks - channel closed"))?;
+
+        previous.reused_files += 1;
+        previous.reused_bytes += size;
+
+        Ok(())
+    }
+
     async fn add_symlink<T: SeqWrite + Send>(
         &mut self,
         encoder: &mut Encoder<'_, T>,
@@ -1063,3 +1411,291 @@ fn generate_pxar_excludes_cli(patterns: &[MatchEntry]) -> Vec<u8> {
 
     content
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::VecDeque;
+    use std::sync::mpsc;
+
+    use bytes::BytesMut;
+    use futures::stream::TryStreamExt;
+
+    use crate::backup::{
+        BufferedDynamicReader, CatalogWriter, ChunkStream, DataBlob, DynamicIndexHeader,
+        InjectionData, LocalDynamicReadAt, ReadChunk, DYNAMIC_SIZED_CHUNK_INDEX_1_0,
+    };
+    use crate::pxar::{extract_archive, PxarExtractOptions};
+
+    const ARCHIVE_NAME: &str = "root.pxar.didx";
+
+    const MTIME: i64 = 1_600_000_000;
+
+    const FILES: &[(&str, usize)] = &[
+        ("a-unchanged", 1024 * 1024),
+        ("b-mtime", 1024 * 1024),
+        ("c-size", 1024 * 1024),
+        ("small", 100),
+        ("sub/d-unchanged", 1024 * 1024),
+    ];
+
+    // Stores the chunks in memory, unencrypted.
+    #[derive(Clone, Default)]
+    struct TestChunkStore(Arc<Mutex<HashMap<[u8; 32], Vec<u8>>>>);
+
+    impl ReadChunk for TestChunkStore {
+        fn read_raw_chunk(&self, digest: &[u8; 32]) -> Result<DataBlob, Error> {
+            DataBlob::encode(&self.read_chunk(digest)?, None, false)
+        }
+
+        fn read_chunk(&self, digest: &[u8; 32]) -> Result<Vec<u8>, Error> {
+            self.0.lock().unwrap().get(digest).cloned().ok_or_else(|| {
+                format_err!("missing chunk {}", proxmox::tools::digest_to_hex(digest))
+            })
+        }
+    }
+
+    struct TestBackup {
+        index_path: PathBuf,
+        catalog_path: PathBuf,
+    }
+
+    fn set_mtime(path: &Path, mtime: i64) -> Result<(), Error> {
+        use nix::sys::time::{TimeVal, TimeValLike};
+        nix::sys::stat::utimes(path, &TimeVal::seconds(mtime), &TimeVal::seconds(mtime))?;
+        Ok(())
+    }
+
+    fn write_test_file(path: &Path, len: usize, seed: u32) -> Result<(), Error> {
+        let mut state = seed;
+        let data: Vec<u8> = (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        std::fs::write(path, data)?;
+        set_mtime(path, MTIME)
+    }
+
+    async fn open_previous(
+        previous: &TestBackup,
+        store: &TestChunkStore,
+        boundaries: mpsc::Sender<InjectChunks>,
+    ) -> Result<PreviousArchive, Error> {
+        let reader = BufferedDynamicReader::new(DynamicIndexReader::open(&previous.index_path)?, store.clone());
+        let archive_size = reader.archive_size();
+        let reader: crate::pxar::fuse::Reader = Arc::new(LocalDynamicReadAt::new(reader));
+        let accessor = Accessor::new(reader, archive_size).await?;
+
+        let mut catalog = CatalogReader::new(std::fs::File::open(&previous.catalog_path)?);
+        let catalog_root = catalog.root()?;
+        let catalog_root = catalog
+            .lookup(&catalog_root, ARCHIVE_NAME.as_bytes())?
+            .ok_or_else(|| format_err!("archive not found in catalog"))?;
+
+        Ok(PreviousArchive {
+            accessor,
+            index: DynamicIndexReader::open(&previous.index_path)?,
+            catalog,
+            catalog_root,
+            boundaries,
+        })
+    }
+
+    async fn archive_data(
+        source: &Path,
+        catalog: Option<Arc<Mutex<dyn BackupCatalogWriter + Send>>>,
+        previous: Option<PreviousArchive>,
+    ) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        let dir = Dir::open(source, OFlag::O_DIRECTORY, Mode::empty())?;
+        let options = PxarCreateOptions {
+            entries_max: crate::pxar::ENCODER_MAX_ENTRIES,
+            ..PxarCreateOptions::default()
+        };
+        create_archive(
+            dir,
+            pxar::encoder::sync::StandardWriter::new(&mut data),
+            Flags::DEFAULT,
+            |_| Ok(()),
+            catalog,
+            options,
+            previous,
+        )
+        .await?;
+        Ok(data)
+    }
+
+    // Creates a backup of `source` like the client does: the archive stream gets chunked with
+    // the reused chunks injected, and stored in the index `<target>.didx` and the catalog
+    // `<target>.catalog`. Returns the number of reused files and their reused size.
+    async fn backup(
+        source: &Path,
+        target: &Path,
+        store: &TestChunkStore,
+        previous: Option<&TestBackup>,
+    ) -> Result<(TestBackup, usize, u64), Error> {
+        let backup = TestBackup {
+            index_path: target.with_extension("didx"),
+            catalog_path: target.with_extension("catalog"),
+        };
+
+        let (boundaries_tx, boundaries_rx) = mpsc::channel();
+        let previous = match previous {
+            Some(previous) => Some(open_previous(previous, store, boundaries_tx).await?),
+            None => None,
+        };
+
+        let catalog = Arc::new(Mutex::new(CatalogWriter::new(std::fs::File::create(&backup.catalog_path)?)?));
+        catalog.lock().unwrap().start_directory(&CString::new(ARCHIVE_NAME)?)?;
+
+        let data = archive_data(source, Some(catalog.clone()), previous).await?;
+
+        {
+            let mut catalog = catalog.lock().unwrap();
+            catalog.end_directory()?;
+            catalog.finish()?;
+        }
+
+        let (injections_tx, injections_rx) = mpsc::channel();
+        let chunks: Vec<BytesMut> = ChunkStream::with_injection(
+            futures::stream::iter(vec![Ok::<_, Error>(data)]),
+            Some(64 * 1024),
+            InjectionData::new(boundaries_rx, injections_tx),
+        )
+        .try_collect()
+        .await?;
+
+        let mut injections: VecDeque<InjectChunks> = injections_rx.try_iter().collect();
+        let reused_files = injections.len();
+        let reused_bytes = injections.iter().map(|injection| injection.size).sum();
+
+        // merge the injected chunks at their stream offsets
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        let mut chunks = chunks.into_iter();
+        loop {
+            if let Some(injection) = injections.front() {
+                if injection.boundary == offset {
+                    for (size, digest) in injections.pop_front().unwrap().chunks {
+                        offset += size;
+                        entries.push((offset, digest));
+                    }
+                    continue;
+                }
+                assert!(injection.boundary > offset, "missed injection at {}", injection.boundary);
+            }
+            match chunks.next() {
+                Some(chunk) => {
+                    let digest = openssl::sha::sha256(&chunk);
+                    offset += chunk.len() as u64;
+                    entries.push((offset, digest));
+                    store.0.lock().unwrap().insert(digest, chunk.to_vec());
+                }
+                None => break,
+            }
+        }
+        assert!(injections.is_empty(), "got injection after the end of the archive");
+
+        let mut header = DynamicIndexHeader::zeroed();
+        header.magic = DYNAMIC_SIZED_CHUNK_INDEX_1_0;
+        let mut index = std::fs::File::create(&backup.index_path)?;
+        index.write_all(header.as_bytes())?;
+        for (end, digest) in entries {
+            index.write_all(&end.to_le_bytes())?;
+            index.write_all(&digest)?;
+        }
+
+        Ok((backup, reused_files, reused_bytes))
+    }
+
+    // Reassembles the archive stream from the chunks.
+    fn read_archive(backup: &TestBackup, store: &TestChunkStore) -> Result<Vec<u8>, Error> {
+        let mut reader = BufferedDynamicReader::new(DynamicIndexReader::open(&backup.index_path)?, store.clone());
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn assert_restored(
+        source: &Path,
+        backup: &TestBackup,
+        store: &TestChunkStore,
+        target: &Path,
+    ) -> Result<(), Error> {
+        let reader = BufferedDynamicReader::new(DynamicIndexReader::open(&backup.index_path)?, store.clone());
+        let options = PxarExtractOptions {
+            match_list: &[],
+            extract_match_default: true,
+            allow_existing_dirs: false,
+            on_error: None,
+        };
+        extract_archive(pxar::decoder::Decoder::from_std(reader)?, target, Flags::DEFAULT, |_| {}, options)?;
+
+        for (name, _) in FILES {
+            let original = std::fs::read(source.join(name))?;
+            let restored = std::fs::read(target.join(name))?;
+            assert!(original == restored, "restored file '{}' differs", name);
+
+            let original = nix::sys::stat::stat(source.join(name).as_path())?;
+            let restored = nix::sys::stat::stat(target.join(name).as_path())?;
+            assert_eq!(original.st_mtime, restored.st_mtime, "mtime of restored file '{}' differs", name);
+        }
+        Ok(())
+    }
+
+    async fn run_reuse_test(testdir: &Path) -> Result<(), Error> {
+        let source = testdir.join("source");
+        std::fs::create_dir_all(source.join("sub"))?;
+        for (seed, (name, size)) in FILES.iter().enumerate() {
+            write_test_file(&source.join(name), *size, seed as u32 + 1)?;
+        }
+
+        let store = TestChunkStore::default();
+
+        let (backup1, reused_files, _) = backup(&source, &testdir.join("backup1"), &store, None).await?;
+        assert_eq!(reused_files, 0);
+
+        // nothing changed, all files spanning complete chunks get reused, but not the small one
+        let (backup2, reused_files, reused_bytes) =
+            backup(&source, &testdir.join("backup2"), &store, Some(&backup1)).await?;
+        assert_eq!(reused_files, 4);
+        assert!(reused_bytes >= 2 * 1024 * 1024 && reused_bytes <= 4 * 1024 * 1024);
+
+        // the reassembled stream is the same as without reusing anything
+        assert!(read_archive(&backup2, &store)? == archive_data(&source, None, None).await?);
+        assert_restored(&source, &backup2, &store, &testdir.join("restore2"))?;
+
+        // a changed mtime or size forces a re-encode, also when chaining backups
+        set_mtime(&source.join("b-mtime"), MTIME + 100)?;
+        let mut file = std::fs::OpenOptions::new().append(true).open(source.join("c-size"))?;
+        file.write_all(b"x")?;
+        drop(file);
+        set_mtime(&source.join("c-size"), MTIME)?;
+
+        let (backup3, reused_files, reused_bytes) =
+            backup(&source, &testdir.join("backup3"), &store, Some(&backup2)).await?;
+        assert_eq!(reused_files, 2);
+        assert!(reused_bytes <= 2 * 1024 * 1024);
+
+        assert!(read_archive(&backup3, &store)? == archive_data(&source, None, None).await?);
+        assert_restored(&source, &backup3, &store, &testdir.join("restore3"))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reuse_unchanged_files() -> Result<(), Error> {
+        let testdir = std::fs::canonicalize(".")?.join(".testdir-pxar-reuse");
+        let _ = std::fs::remove_dir_all(&testdir);
+
+        let result = crate::tools::runtime::main(run_reuse_test(&testdir));
+
+        let _ = std::fs::remove_dir_all(&testdir);
+        result
+    }
+}
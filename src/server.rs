@@ -45,6 +45,16 @@ pub fn our_ctrl_sock() -> String {
     ctrl_sock_from_pid(*PID)
 }
 
+/// Tell the running proxy to reload its TLS certificate.
+///
+/// Established connections keep using the previous certificate.
+pub async fn reload_proxy_certificate() -> Result<(), Error> {
+    let proxy_pid = read_pid(buildcfg::PROXMOX_BACKUP_PROXY_PID_FN)?;
+    let sock = ctrl_sock_from_pid(proxy_pid);
+    send_command(sock, serde_json::json!({ "command": "reload-certificate" })).await?;
+    Ok(())
+}
+
 mod environment;
 pub use environment::*;
 
@@ -82,6 +92,9 @@ pub use prune_job::*;
 mod gc_job;
 pub use gc_job::*;
 
+mod realm_sync;
+pub use realm_sync::*;
+
 mod email_notifications;
 pub use email_notifications::*;
 
@@ -89,3 +102,13 @@ mod report;
 pub use report::*;
 
 pub mod ticket;
+
+pub mod traffic_control;
+
+pub mod acme_challenge;
+
+pub mod metric_server;
+
+pub mod openmetrics;
+
+pub mod notifications;
@@ -0,0 +1,207 @@
+//! ACME `http-01` challenge responder of the proxy
+//!
+//! The certificate order task (running in the privileged API daemon) registers the pending
+//! challenges via the control socket of the proxy. The proxy then serves them on plain HTTP
+//! port 80, until the last challenge is removed again.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::{bail, format_err, Error};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, StatusCode};
+use lazy_static::lazy_static;
+use serde_json::{json, Value};
+
+use super::CommandoSocket;
+
+/// The port used for `http-01` validation (fixed by RFC 8555)
+pub const ACME_HTTP_PORT: u16 = 80;
+
+const CHALLENGE_PATH_PREFIX: &str = "/.well-known/acme-challenge/";
+
+#[derive(Default)]
+struct ChallengeState {
+    // token => key authorization
+    responses: HashMap<String, String>,
+    shutdown: Option<tokio::sync::oneshot::Sender<()>>,
+    // port of the running listener
+    port: u16,
+}
+
+lazy_static! {
+    static ref CHALLENGES: Mutex<ChallengeState> = Mutex::new(ChallengeState::default());
+}
+
+fn is_valid_token(token: &str) -> bool {
+    !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+async fn handle_request(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
+    let response = req.uri().path()
+        .strip_prefix(CHALLENGE_PATH_PREFIX)
+        .and_then(|token| CHALLENGES.lock().unwrap().responses.get(token).cloned());
+
+    let response = match response {
+        Some(key_authorization) => Response::builder()
+            .status(StatusCode::OK)
+            .header(hyper::header::CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(key_authorization)),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+
+    Ok(response.unwrap())
+}
+
+fn start_listener(state: &mut ChallengeState, port: u16) -> Result<(), Error> {
+    if state.shutdown.is_some() {
+        return Ok(());
+    }
+
+    let addr = std::net::SocketAddr::from(([0u16; 8], port));
+    let listener = std::net::TcpListener::bind(addr)
+        .map_err(|err| format_err!("unable to listen on port {} - {}", port, err))?;
+    listener.set_nonblocking(true)?;
+    let port = listener.local_addr()?.port();
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+
+    let server = hyper::Server::from_tcp(listener)?
+        .serve(make_service_fn(|_conn| async {
+            Ok::<_, hyper::Error>(service_fn(handle_request))
+        }))
+        .with_graceful_shutdown(async {
+            let _ = shutdown_rx.await;
+        });
+
+    tokio::spawn(async move {
+        if let Err(err) = server.await {
+            log::error!("ACME challenge server failed - {}", err);
+        }
+    });
+
+    state.shutdown = Some(shutdown_tx);
+    state.port = port;
+    log::info!("started ACME challenge listener on port {}", port);
+
+    Ok(())
+}
+
+// serves `key_authorization` for `token`, starting the listener on `port` if needed
+fn add_challenge(token: &str, key_authorization: &str, port: u16) -> Result<(), Error> {
+    if !is_valid_token(token) {
+        bail!("invalid challenge token '{}'", token);
+    }
+
+    let mut state = CHALLENGES.lock().unwrap();
+    start_listener(&mut state, port)?;
+    state.responses.insert(token.to_string(), key_authorization.to_string());
+
+    Ok(())
+}
+
+// stops serving `token`, and stops the listener after the last challenge
+fn remove_challenge(token: &str) {
+    let mut state = CHALLENGES.lock().unwrap();
+    state.responses.remove(token);
+    if state.responses.is_empty() {
+        if let Some(shutdown) = state.shutdown.take() {
+            let _ = shutdown.send(());
+            log::info!("stopped ACME challenge listener");
+        }
+    }
+}
+
+fn required_str<'a>(args: Option<&'a Value>, name: &str) -> Result<&'a str, Error> {
+    args.and_then(|args| args[name].as_str())
+        .ok_or_else(|| format_err!("missing parameter '{}'", name))
+}
+
+/// Register the challenge control commands.
+pub fn register_challenge_commands(commando_sock: &mut CommandoSocket) -> Result<(), Error> {
+    commando_sock.register_command("acme-http-challenge-add".into(), |args| {
+        let token = required_str(args, "token")?;
+        let key_authorization = required_str(args, "key-authorization")?;
+        add_challenge(token, key_authorization, ACME_HTTP_PORT)?;
+        Ok(Value::Null)
+    })?;
+
+    commando_sock.register_command("acme-http-challenge-remove".into(), |args| {
+        let token = required_str(args, "token")?;
+        remove_challenge(token);
+        Ok(Value::Null)
+    })?;
+
+    Ok(())
+}
+
+fn proxy_ctrl_sock() -> Result<String, Error> {
+    let pid = super::read_pid(crate::buildcfg::PROXMOX_BACKUP_PROXY_PID_FN)?;
+    Ok(super::ctrl_sock_from_pid(pid))
+}
+
+/// Let the proxy answer the `http-01` challenge `token`.
+pub async fn add_http_challenge(token: &str, key_authorization: &str) -> Result<(), Error> {
+    super::send_command(proxy_ctrl_sock()?, json!({
+        "command": "acme-http-challenge-add",
+        "args": {
+            "token": token,
+            "key-authorization": key_authorization,
+        },
+    })).await?;
+    Ok(())
+}
+
+/// Remove the `http-01` challenge `token` from the proxy.
+pub async fn remove_http_challenge(token: &str) -> Result<(), Error> {
+    super::send_command(proxy_ctrl_sock()?, json!({
+        "command": "acme-http-challenge-remove",
+        "args": {
+            "token": token,
+        },
+    })).await?;
+    Ok(())
+}
+
+// Answers challenges like the validation server of an ACME CA (e.g. Pebble) would query them,
+// with the listener on an ephemeral port instead of port 80.
+#[test]
+fn test_http_challenge_responder() -> Result<(), Error> {
+    async fn fetch(port: u16, token: &str) -> Result<(StatusCode, Vec<u8>), Error> {
+        let uri = format!("http://127.0.0.1:{}{}{}", port, CHALLENGE_PATH_PREFIX, token);
+        let response = hyper::Client::new().get(uri.parse()?).await?;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        Ok((status, body.to_vec()))
+    }
+
+    crate::tools::runtime::main(async {
+        assert!(add_challenge("../token", "invalid", 0).is_err());
+        assert!(CHALLENGES.lock().unwrap().shutdown.is_none());
+
+        add_challenge("token1", "token1.thumbprint", 0)?;
+        add_challenge("token2", "token2.thumbprint", 0)?;
+        let port = CHALLENGES.lock().unwrap().port;
+
+        assert_eq!(fetch(port, "token1").await?, (StatusCode::OK, b"token1.thumbprint".to_vec()));
+        assert_eq!(fetch(port, "token2").await?, (StatusCode::OK, b"token2.thumbprint".to_vec()));
+        assert_eq!(fetch(port, "token3").await?.0, StatusCode::NOT_FOUND);
+
+        let uri = format!("http://127.0.0.1:{}/token1", port);
+        let response = hyper::Client::new().get(uri.parse()?).await?;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        // the listener keeps running while challenges are pending
+        remove_challenge("token1");
+        assert_eq!(fetch(port, "token1").await?.0, StatusCode::NOT_FOUND);
+        assert_eq!(fetch(port, "token2").await?.0, StatusCode::OK);
+
+        remove_challenge("token2");
+        assert!(CHALLENGES.lock().unwrap().shutdown.is_none());
+
+        Ok::<_, Error>(())
+    })
+}
@@ -11,6 +11,7 @@ use crate::{
     config::verify::VerificationJobConfig,
     config::sync::SyncJobConfig,
     config::tape_job::TapeBackupJobSetup,
+    config::notifications::{NotificationType, Severity},
     api2::types::{
         APTUpdateInfo,
         GarbageCollectionStatus,
@@ -19,6 +20,7 @@ use crate::{
         DatastoreNotify,
     },
     tools::format::HumanByte,
+    server::notifications::{self, Notification},
 };
 
 const GC_OK_TEMPLATE: &str = r###"
@@ -231,23 +233,41 @@ fn send_job_status_mail(
     Ok(())
 }
 
+// the datastore notify settings only apply to the mail to the notify user
+fn filter_email(email: Option<&str>, notify: Option<Notify>, result_is_ok: bool) -> Option<&str> {
+    match notify {
+        Some(Notify::Never) => None,
+        Some(Notify::Error) if result_is_ok => None,
+        _ => email,
+    }
+}
+
+/// Send the notification mail (if any), and the notification to the configured targets.
+fn send_job_notification(email: Option<&str>, notification: &Notification) -> Result<(), Error> {
+    let mail_result = match email {
+        Some(email) => send_job_status_mail(email, &notification.title, &notification.message),
+        None => Ok(()),
+    };
+
+    if let Err(err) = notifications::send_notification(notification) {
+        eprintln!("sending notifications failed - {}", err);
+    }
+
+    mail_result
+}
+
+fn result_severity<T>(result: &Result<T, Error>) -> Severity {
+    if result.is_ok() { Severity::Info } else { Severity::Error }
+}
+
 pub fn send_gc_status(
-    email: &str,
+    email: Option<&str>,
     notify: DatastoreNotify,
     datastore: &str,
     status: &GarbageCollectionStatus,
     result: &Result<(), Error>,
 ) -> Result<(), Error> {
 
-    match notify.gc {
-        None => { /* send notifications by default */ },
-        Some(notify) => {
-            if notify == Notify::Never || (result.is_ok() && notify == Notify::Error) {
-                return Ok(());
-            }
-        }
-    }
-
     let (fqdn, port) = get_server_url();
     let mut data = json!({
         "datastore": datastore,
@@ -285,13 +305,14 @@ pub fn send_gc_status(
         ),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let notification = Notification::new(NotificationType::Gc, result_severity(result), subject, text)
+        .field("datastore", datastore);
 
-    Ok(())
+    send_job_notification(filter_email(email, notify.gc, result.is_ok()), &notification)
 }
 
 pub fn send_verify_status(
-    email: &str,
+    email: Option<&str>,
     notify: DatastoreNotify,
     job: VerificationJobConfig,
     result: &Result<Vec<String>, Error>,
@@ -321,15 +342,6 @@ pub fn send_verify_status(
         }
     };
 
-    match notify.verify {
-        None => { /* send notifications by default */ },
-        Some(notify) => {
-            if notify == Notify::Never || (result_is_ok && notify == Notify::Error) {
-                return Ok(());
-            }
-        }
-    }
-
     let subject = match result {
         Ok(errors) if errors.is_empty() => format!(
             "Verify Datastore '{}' successful",
@@ -341,27 +353,21 @@ pub fn send_verify_status(
         ),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let severity = if result_is_ok { Severity::Info } else { Severity::Error };
+    let notification = Notification::new(NotificationType::Verify, severity, subject, text)
+        .field("datastore", &job.store)
+        .field("job-id", &job.id);
 
-    Ok(())
+    send_job_notification(filter_email(email, notify.verify, result_is_ok), &notification)
 }
 
 pub fn send_sync_status(
-    email: &str,
+    email: Option<&str>,
     notify: DatastoreNotify,
     job: &SyncJobConfig,
     result: &Result<(), Error>,
 ) -> Result<(), Error> {
 
-    match notify.sync {
-        None => { /* send notifications by default */ },
-        Some(notify) => {
-            if notify == Notify::Never || (result.is_ok() && notify == Notify::Error) {
-                return Ok(());
-            }
-        }
-    }
-
     let (fqdn, port) = get_server_url();
     let mut data = json!({
         "job": job,
@@ -392,13 +398,16 @@ pub fn send_sync_status(
         ),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let notification = Notification::new(NotificationType::Sync, result_severity(result), subject, text)
+        .field("datastore", &job.store)
+        .field("job-id", &job.id)
+        .field("remote", &job.remote);
 
-    Ok(())
+    send_job_notification(filter_email(email, notify.sync, result.is_ok()), &notification)
 }
 
 pub fn send_tape_backup_status(
-    email: &str,
+    email: Option<&str>,
     id: Option<&str>,
     job: &TapeBackupJobSetup,
     result: &Result<(), Error>,
@@ -443,16 +452,22 @@ pub fn send_tape_backup_status(
         ),
     };
 
-    send_job_status_mail(email, &subject, &text)?;
+    let mut notification = Notification::new(NotificationType::TapeBackup, result_severity(result), subject, text)
+        .field("datastore", &job.store)
+        .field("pool", &job.pool)
+        .field("drive", &job.drive);
+    if let Some(id) = id {
+        notification = notification.field("job-id", id);
+    }
 
-    Ok(())
+    send_job_notification(email, &notification)
 }
 
 /// Send email to a person to request a manual media change
 pub fn send_load_media_email(
     drive: &str,
     label_text: &str,
-    to: &str,
+    to: Option<&str>,
     reason: Option<String>,
 ) -> Result<(), Error> {
 
@@ -469,7 +484,11 @@ pub fn send_load_media_email(
     text.push_str(&format!("Drive: {}\n", drive));
     text.push_str(&format!("Media: {}\n", label_text));
 
-    send_job_status_mail(to, &subject, &text)
+    let notification = Notification::new(NotificationType::TapeLoadMedia, Severity::Warning, subject, text)
+        .field("drive", drive)
+        .field("media", label_text);
+
+    send_job_notification(to, &notification)
 }
 
 fn get_server_url() -> (String, usize) {
@@ -494,22 +513,22 @@ fn get_server_url() -> (String, usize) {
 pub fn send_updates_available(
     updates: &[&APTUpdateInfo],
 ) -> Result<(), Error> {
-    // update mails always go to the root@pam configured email..
-    if let Some(email) = lookup_user_email(Userid::root_userid()) {
-        let nodename = proxmox::tools::nodename();
-        let subject = format!("New software packages available ({})", nodename);
+    let nodename = proxmox::tools::nodename();
+    let subject = format!("New software packages available ({})", nodename);
 
-        let (fqdn, port) = get_server_url();
+    let (fqdn, port) = get_server_url();
 
-        let text = HANDLEBARS.render("package_update_template", &json!({
-            "fqdn": fqdn,
-            "port": port,
-            "updates": updates,
-        }))?;
+    let text = HANDLEBARS.render("package_update_template", &json!({
+        "fqdn": fqdn,
+        "port": port,
+        "updates": updates,
+    }))?;
 
-        send_job_status_mail(&email, &subject, &text)?;
-    }
-    Ok(())
+    let notification = Notification::new(NotificationType::PackageUpdates, Severity::Info, subject, text);
+
+    // update mails always go to the root@pam configured email..
+    let email = lookup_user_email(Userid::root_userid());
+    send_job_notification(email.as_deref(), &notification)
 }
 
 /// Lookup users email address
@@ -5,6 +5,7 @@ use crate::{
     server::WorkerTask,
     api2::types::*,
     server::jobstate::Job,
+    server::metric_server::{send_task_metrics, MetricDataPoint},
     backup::DataStore,
 };
 
@@ -47,11 +48,25 @@ pub fn do_garbage_collection_job(
                 );
             }
 
-            if let Some(email) = email {
+            if result.is_ok() {
                 let gc_status = datastore.last_gc_status();
-                if let Err(err) = crate::server::send_gc_status(&email, notify, &store, &gc_status, &result) {
-                    eprintln!("send gc notification failed: {}", err);
-                }
+                let duration = proxmox::tools::time::epoch_i64() - worker.upid().starttime;
+
+                let mut point = MetricDataPoint::new("garbage_collection").tag("datastore", &store);
+                point.add_field("duration", duration as f64);
+                point.add_field("index_data_bytes", gc_status.index_data_bytes as f64);
+                point.add_field("disk_bytes", gc_status.disk_bytes as f64);
+                point.add_field("disk_chunks", gc_status.disk_chunks as f64);
+                point.add_field("removed_bytes", gc_status.removed_bytes as f64);
+                point.add_field("removed_chunks", gc_status.removed_chunks as f64);
+                point.add_field("pending_bytes", gc_status.pending_bytes as f64);
+                point.add_field("pending_chunks", gc_status.pending_chunks as f64);
+                send_task_metrics(vec![point]);
+            }
+
+            let gc_status = datastore.last_gc_status();
+            if let Err(err) = crate::server::send_gc_status(email.as_deref(), notify, &store, &gc_status, &result) {
+                eprintln!("send gc notification failed: {}", err);
             }
 
             result
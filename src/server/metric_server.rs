@@ -0,0 +1,303 @@
+//! Send statistics to external metric servers
+//!
+//! The proxy sends the host and datastore statistics of each stat cycle, tasks (like garbage
+//! collection and backups) send a data point when they finish.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use futures::future::{self, BoxFuture, FutureExt};
+use hyper::{Body, Request};
+use tokio::io::AsyncWriteExt;
+
+use crate::config::metrics::{
+    self,
+    GraphiteProtocol,
+    GraphiteServer,
+    InfluxDbProtocol,
+    InfluxDbServer,
+};
+use crate::tools::http;
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+// space for the IPv6 and UDP headers
+const UDP_HEADER_SIZE: usize = 48;
+
+/// A set of values of one measurement (like `cpustat` or `gc`), identified by its tags
+#[derive(Clone, Debug)]
+pub struct MetricDataPoint {
+    pub measurement: &'static str,
+    pub tags: Vec<(&'static str, String)>,
+    pub fields: Vec<(&'static str, f64)>,
+    pub timestamp: i64,
+}
+
+impl MetricDataPoint {
+    /// Create a data point of the current time, tagged with the node name.
+    pub fn new(measurement: &'static str) -> Self {
+        Self {
+            measurement,
+            tags: vec![("host", proxmox::tools::nodename().to_string())],
+            fields: Vec::new(),
+            timestamp: proxmox::tools::time::epoch_i64(),
+        }
+    }
+
+    pub fn tag(mut self, key: &'static str, value: &str) -> Self {
+        self.tags.push((key, value.to_string()));
+        self
+    }
+
+    pub fn add_field(&mut self, name: &'static str, value: f64) {
+        // neither InfluxDB nor Graphite can store those
+        if value.is_finite() {
+            self.fields.push((name, value));
+        }
+    }
+
+    fn to_influxdb_line(&self) -> String {
+        let mut line = escape_influxdb(self.measurement, false);
+        for (key, value) in &self.tags {
+            line.push_str(&format!(",{}={}", escape_influxdb(key, true), escape_influxdb(value, true)));
+        }
+
+        let fields: Vec<String> = self.fields.iter()
+            .map(|(name, value)| format!("{}={}", escape_influxdb(name, true), value))
+            .collect();
+
+        // timestamps default to nanosecond precision
+        format!("{} {} {}000000000", line, fields.join(","), self.timestamp)
+    }
+
+    fn to_graphite_lines(&self, prefix: &str) -> Vec<String> {
+        let mut path = vec![prefix.to_string()];
+        // <prefix>.<host>.<measurement>.<other tags>, groups all metrics of a node together
+        for (_key, value) in &self.tags {
+            path.push(escape_graphite(value));
+        }
+        path.insert(2, self.measurement.to_string());
+        let path = path.join(".");
+
+        self.fields.iter()
+            .map(|(name, value)| format!("{}.{} {} {}", path, name, value, self.timestamp))
+            .collect()
+    }
+}
+
+fn escape_influxdb(text: &str, is_key_or_tag: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            ',' | ' ' => escaped.push('\\'),
+            '=' if is_key_or_tag => escaped.push('\\'),
+            _ => (),
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn escape_graphite(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+async fn resolve(server: &str, port: u16) -> Result<SocketAddr, Error> {
+    tokio::net::lookup_host((server, port)).await?
+        .next()
+        .ok_or_else(|| format_err!("unable to resolve '{}'", server))
+}
+
+async fn send_udp(server: &str, port: u16, mtu: Option<u16>, lines: &[String]) -> Result<(), Error> {
+    let addr = resolve(server, port).await?;
+
+    let bind_addr: SocketAddr = if addr.is_ipv4() {
+        ([0u8; 4], 0).into()
+    } else {
+        ([0u16; 8], 0).into()
+    };
+    let socket = tokio::net::UdpSocket::bind(bind_addr).await?;
+
+    let max_size = usize::from(mtu.unwrap_or(1500)) - UDP_HEADER_SIZE;
+
+    // pack as many lines into a packet as possible, lines are never split
+    let mut packet = String::new();
+    for line in lines {
+        if !packet.is_empty() && packet.len() + line.len() + 1 > max_size {
+            socket.send_to(packet.as_bytes(), addr).await?;
+            packet.clear();
+        }
+        packet.push_str(line);
+        packet.push('\n');
+    }
+    if !packet.is_empty() {
+        socket.send_to(packet.as_bytes(), addr).await?;
+    }
+
+    Ok(())
+}
+
+async fn send_tcp(server: &str, port: u16, lines: &[String]) -> Result<(), Error> {
+    let addr = resolve(server, port).await?;
+
+    let mut stream = tokio::net::TcpStream::connect(addr).await?;
+
+    let mut data = lines.join("\n");
+    data.push('\n');
+    stream.write_all(data.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+async fn send_influxdb_http(server: &InfluxDbServer, https: bool, lines: &[String]) -> Result<(), Error> {
+    use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+
+    let organization = server.organization.as_deref().unwrap_or("proxmox");
+    let bucket = server.bucket.as_deref().unwrap_or("proxmox");
+
+    let host = if server.server.contains(':') {
+        format!("[{}]", server.server) // IPv6 address
+    } else {
+        server.server.clone()
+    };
+
+    let url = format!(
+        "{}://{}:{}/api/v2/write?org={}&bucket={}",
+        if https { "https" } else { "http" },
+        host,
+        server.port,
+        utf8_percent_encode(organization, NON_ALPHANUMERIC),
+        utf8_percent_encode(bucket, NON_ALPHANUMERIC),
+    );
+
+    let mut request = Request::builder()
+        .method("POST")
+        .uri(&url)
+        .header("User-Agent", "proxmox-backup-server/1.0")
+        .header(hyper::header::CONTENT_TYPE, "text/plain; charset=utf-8");
+    if !server.token.is_empty() {
+        request = request.header(hyper::header::AUTHORIZATION, format!("Token {}", server.token));
+    }
+    let request = request.body(Body::from(lines.join("\n")))?;
+
+    let response = http::request(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = http::response_body_string(response).await.unwrap_or_default();
+        bail!("server returned {} - {}", status, body.trim());
+    }
+
+    Ok(())
+}
+
+async fn send_to_influxdb(server: &InfluxDbServer, data: &[MetricDataPoint]) -> Result<(), Error> {
+    let lines: Vec<String> = data.iter()
+        .filter(|point| !point.fields.is_empty())
+        .map(MetricDataPoint::to_influxdb_line)
+        .collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    match server.proto.unwrap_or(InfluxDbProtocol::Udp) {
+        InfluxDbProtocol::Udp => send_udp(&server.server, server.port, server.mtu, &lines).await,
+        InfluxDbProtocol::Http => send_influxdb_http(server, false, &lines).await,
+        InfluxDbProtocol::Https => send_influxdb_http(server, true, &lines).await,
+    }
+}
+
+async fn send_to_graphite(server: &GraphiteServer, data: &[MetricDataPoint]) -> Result<(), Error> {
+    let prefix = server.path.as_deref().unwrap_or("proxmox");
+
+    let lines: Vec<String> = data.iter()
+        .flat_map(|point| point.to_graphite_lines(prefix))
+        .collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+
+    match server.proto.unwrap_or(GraphiteProtocol::Udp) {
+        GraphiteProtocol::Udp => send_udp(&server.server, server.port, server.mtu, &lines).await,
+        GraphiteProtocol::Tcp => send_tcp(&server.server, server.port, &lines).await,
+    }
+}
+
+/// Send data points to all enabled metric servers.
+///
+/// Errors of single servers are logged, so that they don't affect the other servers.
+pub async fn send_data_to_metric_servers(data: &[MetricDataPoint]) -> Result<(), Error> {
+    let (config, _digest) = metrics::config()?;
+
+    let influxdb_list: Vec<InfluxDbServer> = config.convert_to_typed_array("influxdb")?;
+    let graphite_list: Vec<GraphiteServer> = config.convert_to_typed_array("graphite")?;
+
+    let mut futures: Vec<(&str, BoxFuture<Result<(), Error>>)> = Vec::new();
+
+    for server in influxdb_list.iter().filter(|server| server.enable.unwrap_or(true)) {
+        futures.push((&server.name, send_to_influxdb(server, data).boxed()));
+    }
+    for server in graphite_list.iter().filter(|server| server.enable.unwrap_or(true)) {
+        futures.push((&server.name, send_to_graphite(server, data).boxed()));
+    }
+
+    let futures = futures.into_iter().map(|(name, future)| async move {
+        let result = match tokio::time::timeout(SEND_TIMEOUT, future).await {
+            Ok(result) => result,
+            Err(_) => Err(format_err!("timeout")),
+        };
+        (name, result)
+    });
+
+    for (name, result) in future::join_all(futures).await {
+        if let Err(err) = result {
+            eprintln!("sending metrics to server '{}' failed - {}", name, err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Send the data points of a finished task in the background.
+///
+/// This can be called from worker threads, which are not part of the tokio runtime.
+pub fn send_task_metrics(data: Vec<MetricDataPoint>) {
+    crate::tools::runtime::get_runtime().spawn(async move {
+        if let Err(err) = send_data_to_metric_servers(&data).await {
+            eprintln!("sending task metrics failed - {}", err);
+        }
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_metric_formats() {
+        let mut point = MetricDataPoint {
+            measurement: "datastore",
+            tags: vec![("host", "pbs1".to_string()), ("datastore", "my store".to_string())],
+            fields: Vec::new(),
+            timestamp: 1600000000,
+        };
+        point.add_field("used", 1024.0);
+        point.add_field("ratio", 0.5);
+        point.add_field("invalid", f64::NAN);
+
+        assert_eq!(
+            point.to_influxdb_line(),
+            "datastore,host=pbs1,datastore=my\\ store used=1024,ratio=0.5 1600000000000000000",
+        );
+
+        assert_eq!(
+            point.to_graphite_lines("proxmox"),
+            vec![
+                "proxmox.pbs1.datastore.my_store.used 1024 1600000000",
+                "proxmox.pbs1.datastore.my_store.ratio 0.5 1600000000",
+            ],
+        );
+    }
+}
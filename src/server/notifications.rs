@@ -0,0 +1,447 @@
+//! Send notifications to the targets configured in `notifications.cfg`
+//!
+//! Matchers decide which targets get a notification, based on its type, severity and datastore.
+//! This is independent of the mails sent to the datastore notify user.
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use handlebars::Handlebars;
+use hyper::{Body, Request};
+use serde_json::json;
+
+use proxmox::api::section_config::SectionConfigData;
+
+use crate::api2::types::Userid;
+use crate::config::notifications::{
+    self,
+    split_list,
+    GotifyTarget,
+    NotificationMatcher,
+    NotificationType,
+    Severity,
+    SmtpMode,
+    SmtpTarget,
+    WebhookMethod,
+    WebhookTarget,
+};
+use crate::tools::{http, smtp};
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+const DEFAULT_WEBHOOK_BODY: &str = r###"{
+  "title": "{{title}}",
+  "message": "{{message}}",
+  "severity": "{{severity}}",
+  "type": "{{type}}",
+  "hostname": "{{hostname}}",
+  "timestamp": {{timestamp}}
+}"###;
+
+/// A notification, like the result of a finished job
+#[derive(Clone, Debug)]
+pub struct Notification {
+    pub notification_type: NotificationType,
+    pub severity: Severity,
+    pub title: String,
+    pub message: String,
+    /// Additional properties, like `datastore` or `job-id`
+    pub fields: HashMap<String, String>,
+    pub timestamp: i64,
+}
+
+impl Notification {
+    pub fn new(
+        notification_type: NotificationType,
+        severity: Severity,
+        title: String,
+        message: String,
+    ) -> Self {
+        Self {
+            notification_type,
+            severity,
+            title,
+            message,
+            fields: HashMap::new(),
+            timestamp: proxmox::tools::time::epoch_i64(),
+        }
+    }
+
+    pub fn field(mut self, name: &str, value: &str) -> Self {
+        self.fields.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    fn template_data(&self) -> serde_json::Value {
+        json!({
+            "title": self.title,
+            "message": self.message,
+            "severity": self.severity,
+            "type": self.notification_type,
+            "hostname": proxmox::tools::nodename(),
+            "timestamp": self.timestamp,
+            "fields": self.fields,
+        })
+    }
+}
+
+fn matches(matcher: &NotificationMatcher, notification: &Notification) -> bool {
+    let match_type = split_list(matcher.match_type.as_deref());
+    if !match_type.is_empty() {
+        let value = serde_json::to_value(notification.notification_type).unwrap_or_default();
+        if !match_type.iter().any(|ty| Some(*ty) == value.as_str()) {
+            return false;
+        }
+    }
+
+    let match_severity = split_list(matcher.match_severity.as_deref());
+    if !match_severity.is_empty() {
+        let value = serde_json::to_value(notification.severity).unwrap_or_default();
+        if !match_severity.iter().any(|severity| Some(*severity) == value.as_str()) {
+            return false;
+        }
+    }
+
+    let match_datastore = split_list(matcher.match_datastore.as_deref());
+    if !match_datastore.is_empty() {
+        match notification.fields.get("datastore") {
+            Some(store) if match_datastore.contains(&store.as_str()) => (),
+            _ => return false,
+        }
+    }
+
+    true
+}
+
+enum Target {
+    Smtp(SmtpTarget),
+    Webhook(WebhookTarget),
+    Gotify(GotifyTarget),
+}
+
+impl Target {
+    fn lookup(config: &SectionConfigData, name: &str) -> Result<Self, Error> {
+        match config.sections.get(name) {
+            Some((section_type, _)) => match section_type.as_str() {
+                "smtp" => Ok(Target::Smtp(config.lookup("smtp", name)?)),
+                "webhook" => Ok(Target::Webhook(config.lookup("webhook", name)?)),
+                "gotify" => Ok(Target::Gotify(config.lookup("gotify", name)?)),
+                _ => bail!("'{}' is not a notification target", name),
+            },
+            None => bail!("notification target '{}' does not exist", name),
+        }
+    }
+
+    fn disabled(&self) -> bool {
+        let disable = match self {
+            Target::Smtp(target) => target.disable,
+            Target::Webhook(target) => target.disable,
+            Target::Gotify(target) => target.disable,
+        };
+        disable.unwrap_or(false)
+    }
+
+    fn send(&self, notification: &Notification) -> Result<(), Error> {
+        match self {
+            Target::Smtp(target) => {
+                // the SMTP client blocks
+                crate::tools::runtime::block_in_place(|| send_smtp(target, notification))
+            }
+            Target::Webhook(target) => {
+                crate::tools::runtime::block_on(with_timeout(send_webhook(target, notification)))
+            }
+            Target::Gotify(target) => {
+                crate::tools::runtime::block_on(with_timeout(send_gotify(target, notification)))
+            }
+        }
+    }
+}
+
+async fn with_timeout<F>(future: F) -> Result<(), Error>
+where
+    F: std::future::Future<Output = Result<(), Error>>,
+{
+    match tokio::time::timeout(HTTP_TIMEOUT, future).await {
+        Ok(result) => result,
+        Err(_) => Err(format_err!("timeout")),
+    }
+}
+
+fn send_smtp(target: &SmtpTarget, notification: &Notification) -> Result<(), Error> {
+    let mut recipients: Vec<String> = split_list(target.mailto.as_deref())
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+    for userid in split_list(target.mailto_user.as_deref()) {
+        let userid: Userid = userid.parse()?;
+        match crate::server::lookup_user_email(&userid) {
+            Some(email) => recipients.push(email),
+            None => eprintln!("notification target '{}': user '{}' has no e-mail address", target.name, userid),
+        }
+    }
+    recipients.sort();
+    recipients.dedup();
+    let recipients: Vec<&str> = recipients.iter().map(String::as_str).collect();
+
+    let author = match target.author {
+        Some(ref author) => author.clone(),
+        None => format!("Proxmox Backup Server - {}", proxmox::tools::nodename()),
+    };
+
+    let html = format!("<html><body><pre>\n{}\n<pre>", handlebars::html_escape(&notification.message));
+
+    let mail = smtp::format_mail(
+        &target.from_address,
+        &author,
+        &recipients,
+        &notification.title,
+        &notification.message,
+        &html,
+    )?;
+
+    let relay = smtp::SmtpRelay {
+        server: &target.server,
+        port: target.port,
+        mode: target.mode.unwrap_or(SmtpMode::StartTls),
+        username: target.username.as_deref(),
+        password: if target.password.is_empty() { None } else { Some(target.password.as_str()) },
+    };
+
+    smtp::send_mail(&relay, &target.from_address, &recipients, &mail)
+}
+
+fn json_escape(text: &str) -> String {
+    let quoted = serde_json::Value::from(text).to_string();
+    // strip the surrounding quotes, the template contains them
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+fn render_webhook_body(template: &str, notification: &Notification) -> Result<String, Error> {
+    let mut hb = Handlebars::new();
+    hb.register_escape_fn(json_escape);
+
+    hb.render_template(template, &notification.template_data())
+        .map_err(|err| format_err!("unable to render body template - {}", err))
+}
+
+fn parse_webhook_headers(headers: &str) -> Result<Vec<(&str, &str)>, Error> {
+    let mut list = Vec::new();
+    for line in headers.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let mut parts = line.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(value)) if !name.trim().is_empty() => {
+                list.push((name.trim(), value.trim()));
+            }
+            _ => bail!("invalid header line '{}'", line),
+        }
+    }
+    Ok(list)
+}
+
+async fn send_http_request(request: Request<Body>) -> Result<(), Error> {
+    let response = http::request(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = http::response_body_string(response).await.unwrap_or_default();
+        bail!("server returned {} - {}", status, body.trim());
+    }
+    Ok(())
+}
+
+async fn send_webhook(target: &WebhookTarget, notification: &Notification) -> Result<(), Error> {
+    let template = if target.body.is_empty() { DEFAULT_WEBHOOK_BODY } else { &target.body };
+    let body = render_webhook_body(template, notification)?;
+
+    let headers = parse_webhook_headers(&target.headers)?;
+
+    let method = match target.method.unwrap_or(WebhookMethod::Post) {
+        WebhookMethod::Post => "POST",
+        WebhookMethod::Put => "PUT",
+    };
+
+    let mut request = Request::builder()
+        .method(method)
+        .uri(&target.url)
+        .header("User-Agent", "proxmox-backup-server/1.0");
+    if !headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-type")) {
+        request = request.header(hyper::header::CONTENT_TYPE, "application/json");
+    }
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+
+    send_http_request(request.body(Body::from(body))?).await
+}
+
+async fn send_gotify(target: &GotifyTarget, notification: &Notification) -> Result<(), Error> {
+    let priority = match notification.severity {
+        Severity::Info => 1,
+        Severity::Warning => 5,
+        Severity::Error => 8,
+    };
+
+    let body = json!({
+        "title": notification.title,
+        "message": notification.message,
+        "priority": priority,
+        "extras": {
+            "client::display": { "contentType": "text/plain" },
+        },
+    });
+
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("{}/message", target.server.trim_end_matches('/')))
+        .header("User-Agent", "proxmox-backup-server/1.0")
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .header("X-Gotify-Key", &target.token)
+        .body(Body::from(body.to_string()))?;
+
+    send_http_request(request).await
+}
+
+/// Send a notification to all targets of the matching matchers.
+///
+/// Errors of single targets are logged, so that they don't affect the other targets.
+pub fn send_notification(notification: &Notification) -> Result<(), Error> {
+    let (config, _digest) = notifications::config()?;
+
+    let matchers: Vec<NotificationMatcher> = config.convert_to_typed_array("matcher")?;
+
+    let targets: BTreeSet<&str> = matchers.iter()
+        .filter(|matcher| !matcher.disable.unwrap_or(false))
+        .filter(|matcher| matches(matcher, notification))
+        .flat_map(|matcher| split_list(Some(matcher.target.as_str())))
+        .collect();
+
+    for name in targets {
+        let result = Target::lookup(&config, name).and_then(|target| {
+            if target.disabled() {
+                return Ok(());
+            }
+            target.send(notification)
+        });
+        if let Err(err) = result {
+            eprintln!("sending notification to target '{}' failed - {}", name, err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Send a test notification to a single target (even if it is disabled).
+pub fn send_test_notification(name: &str) -> Result<(), Error> {
+    let (config, _digest) = notifications::config()?;
+
+    let target = Target::lookup(&config, name)?;
+
+    let nodename = proxmox::tools::nodename();
+    let notification = Notification::new(
+        NotificationType::Test,
+        Severity::Info,
+        format!("Test notification from '{}'", nodename),
+        format!("This is a test of the notification target '{}'.", name),
+    );
+
+    target.send(&notification)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::{Arc, Mutex};
+
+    use hyper::service::{make_service_fn, service_fn};
+
+    #[test]
+    fn test_webhook() -> Result<(), Error> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let notification = Notification::new(
+            NotificationType::Gc,
+            Severity::Error,
+            "Garbage Collect Datastore 'store1' failed".to_string(),
+            "Garbage collection failed: \"disk full\"\n".to_string(),
+        )
+        .field("datastore", "store1");
+
+        crate::tools::runtime::main(async {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+            listener.set_nonblocking(true)?;
+            let addr = listener.local_addr()?;
+
+            let received2 = Arc::clone(&received);
+            let server = hyper::Server::from_tcp(listener)?
+                .serve(make_service_fn(move |_conn| {
+                    let received = Arc::clone(&received2);
+                    async move {
+                        Ok::<_, hyper::Error>(service_fn(move |request: Request<Body>| {
+                            let received = Arc::clone(&received);
+                            async move {
+                                let method = request.method().to_string();
+                                let path = request.uri().path().to_string();
+                                let token = request.headers().get("X-Token")
+                                    .and_then(|value| value.to_str().ok())
+                                    .unwrap_or("")
+                                    .to_string();
+                                let body = hyper::body::to_bytes(request.into_body()).await?;
+                                let body = String::from_utf8_lossy(&body).to_string();
+                                received.lock().unwrap().push((method, path, token, body));
+                                Ok::<_, hyper::Error>(hyper::Response::new(Body::empty()))
+                            }
+                        }))
+                    }
+                }));
+            tokio::spawn(server);
+
+            let target = WebhookTarget {
+                name: "hook".to_string(),
+                url: format!("http://{}/hook", addr),
+                method: Some(WebhookMethod::Put),
+                headers: "X-Token: secret\n".to_string(),
+                body: r#"{"text": "{{title}}: {{message}}", "store": "{{fields.datastore}}"}"#.to_string(),
+                disable: None,
+                comment: None,
+            };
+
+            send_webhook(&target, &notification).await
+        })?;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+
+        let (method, path, token, body) = &received[0];
+        assert_eq!(method, "PUT");
+        assert_eq!(path, "/hook");
+        assert_eq!(token, "secret");
+
+        let body: serde_json::Value = serde_json::from_str(body)?;
+        assert_eq!(body, json!({
+            "text": "Garbage Collect Datastore 'store1' failed: Garbage collection failed: \"disk full\"\n",
+            "store": "store1",
+        }));
+
+        let mut matcher = NotificationMatcher {
+            name: "errors".to_string(),
+            match_type: None,
+            match_severity: Some("warning,error".to_string()),
+            match_datastore: Some("store1".to_string()),
+            target: "hook".to_string(),
+            disable: None,
+            comment: None,
+        };
+        assert!(matches(&matcher, &notification));
+
+        matcher.match_type = Some("sync,verify".to_string());
+        assert!(!matches(&matcher, &notification));
+
+        Ok(())
+    }
+}
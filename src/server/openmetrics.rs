@@ -0,0 +1,394 @@
+//! Prometheus/OpenMetrics text exposition of the server statistics
+//!
+//! Served by the proxy at `/metrics`. The endpoint uses the normal API authentication (API
+//! tokens are the natural choice for scrapers) and requires `Sys.Audit` on `/system/status`,
+//! datastores and their jobs are only included with `Datastore.Audit` on the datastore.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::Error;
+
+use crate::api2::types::{Authid, RRDMode, RRDTimeFrameResolution};
+use crate::backup::DataStore;
+use crate::config::acl::PRIV_DATASTORE_AUDIT;
+use crate::config::cached_user_info::CachedUserInfo;
+use crate::config::{datastore, prune, sync, tape_job, verify};
+use crate::server::jobstate::JobState;
+use crate::server::{TaskListInfoIterator, TaskState, UPID};
+
+/// Content type of the OpenMetrics text format.
+pub const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+#[derive(Clone, Copy, PartialEq)]
+enum MetricType {
+    Gauge,
+    Counter,
+}
+
+/// A metric family with all its samples.
+struct Metric {
+    name: &'static str,
+    metric_type: MetricType,
+    help: &'static str,
+    samples: Vec<(Vec<(&'static str, String)>, f64)>,
+}
+
+impl Metric {
+    fn gauge(name: &'static str, help: &'static str) -> Self {
+        Self { name, metric_type: MetricType::Gauge, help, samples: Vec::new() }
+    }
+
+    fn counter(name: &'static str, help: &'static str) -> Self {
+        Self { name, metric_type: MetricType::Counter, help, samples: Vec::new() }
+    }
+
+    fn add(&mut self, labels: &[(&'static str, &str)], value: f64) {
+        let labels = labels.iter().map(|(key, value)| (*key, value.to_string())).collect();
+        self.samples.push((labels, value));
+    }
+
+    fn set(&mut self, value: f64) {
+        self.samples.push((Vec::new(), value));
+    }
+
+    fn format(&self, output: &mut String) -> Result<(), Error> {
+        let (type_name, suffix) = match self.metric_type {
+            MetricType::Gauge => ("gauge", ""),
+            MetricType::Counter => ("counter", "_total"),
+        };
+
+        writeln!(output, "# TYPE {} {}", self.name, type_name)?;
+        writeln!(output, "# HELP {} {}", self.name, self.help)?;
+
+        for (labels, value) in &self.samples {
+            write!(output, "{}{}", self.name, suffix)?;
+            if !labels.is_empty() {
+                let labels: Vec<String> = labels.iter()
+                    .map(|(key, value)| format!("{}=\"{}\"", key, escape_label_value(value)))
+                    .collect();
+                write!(output, "{{{}}}", labels.join(","))?;
+            }
+            writeln!(output, " {}", value)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn format_metrics(metrics: &[Metric]) -> Result<String, Error> {
+    let mut output = String::new();
+    for metric in metrics.iter().filter(|metric| !metric.samples.is_empty()) {
+        metric.format(&mut output)?;
+    }
+    output.push_str("# EOF\n");
+    Ok(output)
+}
+
+// the most recent value of the stats recorded by the proxy
+fn last_rrd_value(name: &str) -> Option<f64> {
+    let now = proxmox::tools::time::epoch_f64();
+    let (_start, _reso, list) = crate::rrd::extract_cached_data(
+        "host",
+        name,
+        now,
+        RRDTimeFrameResolution::Hour,
+        RRDMode::Average,
+    )?;
+    list.into_iter().rev().flatten().next()
+}
+
+fn host_metrics(metrics: &mut Vec<Metric>) {
+    use proxmox::sys::linux::procfs::{read_loadavg, read_meminfo, read_proc_net_dev};
+
+    let mut cpu = Metric::gauge("pbs_host_cpu_usage", "CPU usage of the host (0.0 - 1.0).");
+    let mut iowait = Metric::gauge("pbs_host_io_wait", "CPU time spent waiting for I/O (0.0 - 1.0).");
+    if let Some(value) = last_rrd_value("cpu") {
+        cpu.set(value);
+    }
+    if let Some(value) = last_rrd_value("iowait") {
+        iowait.set(value);
+    }
+    metrics.push(cpu);
+    metrics.push(iowait);
+
+    let mut load = Metric::gauge("pbs_host_load", "Load average of the host.");
+    match read_loadavg() {
+        Ok(loadavg) => {
+            load.add(&[("interval", "1m")], loadavg.0 as f64);
+            load.add(&[("interval", "5m")], loadavg.1 as f64);
+            load.add(&[("interval", "15m")], loadavg.2 as f64);
+        }
+        Err(err) => eprintln!("read_loadavg failed - {}", err),
+    }
+    metrics.push(load);
+
+    let mut memory_total = Metric::gauge("pbs_host_memory_total_bytes", "Total memory of the host.");
+    let mut memory_used = Metric::gauge("pbs_host_memory_used_bytes", "Used memory of the host.");
+    let mut swap_total = Metric::gauge("pbs_host_swap_total_bytes", "Total swap space of the host.");
+    let mut swap_used = Metric::gauge("pbs_host_swap_used_bytes", "Used swap space of the host.");
+    match read_meminfo() {
+        Ok(meminfo) => {
+            memory_total.set(meminfo.memtotal as f64);
+            memory_used.set(meminfo.memused as f64);
+            swap_total.set(meminfo.swaptotal as f64);
+            swap_used.set(meminfo.swapused as f64);
+        }
+        Err(err) => eprintln!("read_meminfo failed - {}", err),
+    }
+    metrics.push(memory_total);
+    metrics.push(memory_used);
+    metrics.push(swap_total);
+    metrics.push(swap_used);
+
+    let mut receive = Metric::counter("pbs_host_network_receive_bytes", "Bytes received on physical network interfaces.");
+    let mut transmit = Metric::counter("pbs_host_network_transmit_bytes", "Bytes sent on physical network interfaces.");
+    match read_proc_net_dev() {
+        Ok(netdev) => {
+            use crate::config::network::is_physical_nic;
+            for item in netdev.iter().filter(|item| is_physical_nic(&item.device)) {
+                receive.add(&[("interface", item.device.as_str())], item.receive as f64);
+                transmit.add(&[("interface", item.device.as_str())], item.send as f64);
+            }
+        }
+        Err(err) => eprintln!("read_proc_net_dev failed - {}", err),
+    }
+    metrics.push(receive);
+    metrics.push(transmit);
+
+    let mut root_total = Metric::gauge("pbs_host_root_fs_total_bytes", "Size of the root file system.");
+    let mut root_used = Metric::gauge("pbs_host_root_fs_used_bytes", "Used space on the root file system.");
+    match crate::tools::disks::disk_usage(std::path::Path::new("/")) {
+        Ok(status) => {
+            root_total.set(status.total as f64);
+            root_used.set(status.used as f64);
+        }
+        Err(err) => eprintln!("read disk_usage on / failed - {}", err),
+    }
+    metrics.push(root_total);
+    metrics.push(root_used);
+}
+
+fn datastore_metrics(metrics: &mut Vec<Metric>, stores: &[String]) {
+    let mut total = Metric::gauge("pbs_datastore_total_bytes", "Size of the datastore file system.");
+    let mut used = Metric::gauge("pbs_datastore_used_bytes", "Used space on the datastore file system.");
+    let mut avail = Metric::gauge("pbs_datastore_available_bytes", "Available space on the datastore file system.");
+    let mut dedup = Metric::gauge("pbs_datastore_deduplication_factor", "Ratio of referenced to stored data, as of the last garbage collection.");
+    let mut index_bytes = Metric::gauge("pbs_gc_index_data_bytes", "Bytes referenced by all indexes, as of the last garbage collection.");
+    let mut disk_bytes = Metric::gauge("pbs_gc_disk_bytes", "Bytes used by chunks, as of the last garbage collection.");
+    let mut disk_chunks = Metric::gauge("pbs_gc_disk_chunks", "Number of chunks, as of the last garbage collection.");
+    let mut removed_bytes = Metric::gauge("pbs_gc_removed_bytes", "Bytes removed by the last garbage collection.");
+    let mut removed_chunks = Metric::gauge("pbs_gc_removed_chunks", "Chunks removed by the last garbage collection.");
+    let mut pending_bytes = Metric::gauge("pbs_gc_pending_bytes", "Bytes of unused chunks which could not be removed yet.");
+    let mut pending_chunks = Metric::gauge("pbs_gc_pending_chunks", "Number of unused chunks which could not be removed yet.");
+    let mut bad_chunks = Metric::gauge("pbs_gc_bad_chunks", "Number of chunks marked as corrupt.");
+
+    for store in stores {
+        let datastore = match DataStore::lookup_datastore(store, None) {
+            Ok(datastore) => datastore,
+            Err(err) => {
+                eprintln!("unable to open datastore '{}' - {}", store, err);
+                continue;
+            }
+        };
+        let labels = [("datastore", store.as_str())];
+
+        match crate::tools::disks::disk_usage(&datastore.base_path()) {
+            Ok(status) => {
+                total.add(&labels, status.total as f64);
+                used.add(&labels, status.used as f64);
+                avail.add(&labels, status.avail as f64);
+            }
+            Err(err) => eprintln!("read disk_usage of datastore '{}' failed - {}", store, err),
+        }
+
+        let gc_status = datastore.last_gc_status();
+        if gc_status.upid.is_none() {
+            continue; // never ran
+        }
+        if gc_status.disk_bytes > 0 {
+            dedup.add(&labels, gc_status.index_data_bytes as f64 / gc_status.disk_bytes as f64);
+        }
+        index_bytes.add(&labels, gc_status.index_data_bytes as f64);
+        disk_bytes.add(&labels, gc_status.disk_bytes as f64);
+        disk_chunks.add(&labels, gc_status.disk_chunks as f64);
+        removed_bytes.add(&labels, gc_status.removed_bytes as f64);
+        removed_chunks.add(&labels, gc_status.removed_chunks as f64);
+        pending_bytes.add(&labels, gc_status.pending_bytes as f64);
+        pending_chunks.add(&labels, gc_status.pending_chunks as f64);
+        bad_chunks.add(&labels, gc_status.still_bad as f64);
+    }
+
+    metrics.extend(vec![
+        total, used, avail, dedup,
+        index_bytes, disk_bytes, disk_chunks,
+        removed_bytes, removed_chunks, pending_bytes, pending_chunks, bad_chunks,
+    ]);
+}
+
+// (job type as used by the job state files, job id, datastore)
+fn list_jobs(stores: &[String]) -> Result<Vec<(&'static str, String, String)>, Error> {
+    let mut jobs = Vec::new();
+
+    for store in stores {
+        jobs.push(("garbage_collection", store.clone(), store.clone()));
+    }
+
+    let (config, _digest) = sync::config()?;
+    for job in config.convert_to_typed_array::<sync::SyncJobConfig>("sync")? {
+        jobs.push(("syncjob", job.id, job.store));
+    }
+
+    let (config, _digest) = verify::config()?;
+    for job in config.convert_to_typed_array::<verify::VerificationJobConfig>("verification")? {
+        jobs.push(("verificationjob", job.id, job.store));
+    }
+
+    let (config, _digest) = prune::config()?;
+    for job in config.convert_to_typed_array::<prune::PruneJobConfig>("prune")? {
+        jobs.push(("prunejob", job.id, job.store));
+    }
+
+    let (config, _digest) = tape_job::config()?;
+    for job in config.convert_to_typed_array::<tape_job::TapeBackupJobConfig>("backup")? {
+        jobs.push(("tape-backup-job", job.id, job.setup.store));
+    }
+
+    Ok(jobs.into_iter().filter(|(_, _, store)| stores.contains(store)).collect())
+}
+
+fn job_metrics(metrics: &mut Vec<Metric>, stores: &[String]) -> Result<(), Error> {
+    let mut running = Metric::gauge("pbs_job_running", "Whether the job is currently running (0 or 1).");
+    let mut last_run = Metric::gauge("pbs_job_last_run_timestamp", "End time of the last run of the job (UNIX epoch).");
+    let mut last_success = Metric::gauge("pbs_job_last_run_success", "Whether the last run of the job succeeded (0 or 1).");
+
+    for (jobtype, id, store) in list_jobs(stores)? {
+        let labels = [("type", jobtype), ("id", id.as_str()), ("datastore", store.as_str())];
+
+        let state = match JobState::load(jobtype, &id) {
+            Ok(state) => state,
+            Err(err) => {
+                eprintln!("could not load state of {} {} - {}", jobtype, id, err);
+                continue;
+            }
+        };
+
+        match state {
+            JobState::Created { .. } => {
+                running.add(&labels, 0.0);
+            }
+            JobState::Started { .. } => {
+                running.add(&labels, 1.0);
+            }
+            JobState::Finished { state, .. } => {
+                running.add(&labels, 0.0);
+                last_run.add(&labels, state.endtime() as f64);
+                let success = match state {
+                    TaskState::OK { .. } | TaskState::Warning { .. } => 1.0,
+                    TaskState::Error { .. } | TaskState::Unknown { .. } => 0.0,
+                };
+                last_success.add(&labels, success);
+            }
+        }
+    }
+
+    metrics.push(running);
+    metrics.push(last_run);
+    metrics.push(last_success);
+
+    Ok(())
+}
+
+fn task_metrics(metrics: &mut Vec<Metric>) -> Result<(), Error> {
+    let mut counts: HashMap<String, u64> = HashMap::new();
+
+    for info in TaskListInfoIterator::new(true)? {
+        let upid: UPID = info?.upid;
+        *counts.entry(upid.worker_type).or_insert(0) += 1;
+    }
+
+    let mut counts: Vec<(String, u64)> = counts.into_iter().collect();
+    counts.sort();
+
+    let mut running = Metric::gauge("pbs_tasks_running", "Number of running tasks by task type.");
+    for (worker_type, count) in &counts {
+        running.add(&[("type", worker_type.as_str())], *count as f64);
+    }
+
+    metrics.push(running);
+
+    Ok(())
+}
+
+/// Generate the OpenMetrics text for `auth_id`.
+///
+/// The caller has to check for `Sys.Audit` on `/system/status`. Statistics which cannot be read
+/// are left out (and logged).
+pub fn generate_metrics(auth_id: &Authid, user_info: &CachedUserInfo) -> Result<String, Error> {
+    let mut stores = Vec::new();
+    match datastore::config() {
+        Ok((config, _digest)) => {
+            for store in config.sections.keys() {
+                let privs = user_info.lookup_privs(auth_id, &["datastore", store]);
+                if privs & PRIV_DATASTORE_AUDIT != 0 {
+                    stores.push(store.clone());
+                }
+            }
+        }
+        Err(err) => eprintln!("read datastore config failed - {}", err),
+    }
+    stores.sort();
+
+    let mut metrics = Vec::new();
+
+    host_metrics(&mut metrics);
+    datastore_metrics(&mut metrics, &stores);
+    if let Err(err) = job_metrics(&mut metrics, &stores) {
+        eprintln!("collecting job metrics failed - {}", err);
+    }
+    if let Err(err) = task_metrics(&mut metrics) {
+        eprintln!("collecting task metrics failed - {}", err);
+    }
+
+    format_metrics(&metrics)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_openmetrics_format() -> Result<(), Error> {
+        let mut used = Metric::gauge("pbs_datastore_used_bytes", "Used space.");
+        used.add(&[("datastore", "store\"1")], 1024.0);
+        let mut received = Metric::counter("pbs_host_network_receive_bytes", "Bytes received.");
+        received.add(&[("interface", "eth0")], 10.0);
+        let empty = Metric::gauge("pbs_host_cpu_usage", "CPU usage.");
+
+        assert_eq!(
+            format_metrics(&[used, received, empty])?,
+            "# TYPE pbs_datastore_used_bytes gauge\n\
+             # HELP pbs_datastore_used_bytes Used space.\n\
+             pbs_datastore_used_bytes{datastore=\"store\\\"1\"} 1024\n\
+             # TYPE pbs_host_network_receive_bytes counter\n\
+             # HELP pbs_host_network_receive_bytes Bytes received.\n\
+             pbs_host_network_receive_bytes_total{interface=\"eth0\"} 10\n\
+             # EOF\n",
+        );
+
+        Ok(())
+    }
+}
@@ -1,28 +1,93 @@
-use anyhow::Error;
+use anyhow::{bail, Error};
 
 use proxmox::try_block;
 
 use crate::{
     api2::types::*,
-    backup::{compute_prune_info, BackupInfo, DataStore, PruneOptions},
+    backup::{
+        compute_prune_info, BackupInfo, BackupNamespace, DataStore, GroupFilter, PruneOptions,
+    },
+    config::prune::PruneJobConfig,
     server::jobstate::Job,
     server::WorkerTask,
     task_log,
 };
 
-pub fn do_prune_job(
+/// Selects the groups affected by a prune run
+struct PruneSelection {
+    ns: BackupNamespace,
+    max_depth: Option<usize>,
+    group_filter: Option<Vec<GroupFilter>>,
+}
+
+fn prune_groups(
+    worker: &WorkerTask,
+    datastore: &DataStore,
+    store: &str,
+    selection: &PruneSelection,
+    prune_options: &PruneOptions,
+) -> Result<(), Error> {
+    let base_path = datastore.base_path();
+
+    if !datastore.namespace_exists(&selection.ns) {
+        bail!("namespace '{}' does not exist", selection.ns);
+    }
+
+    let groups = BackupInfo::list_backup_groups_recursive(
+        &base_path,
+        &selection.ns,
+        selection.max_depth,
+    )?;
+
+    for group in groups {
+        if let Some(ref group_filter) = selection.group_filter {
+            if !group.apply_filters(group_filter) {
+                continue;
+            }
+        }
+
+        let list = group.list_backups(&base_path)?;
+        let mut prune_info = compute_prune_info(list, prune_options)?;
+        prune_info.reverse(); // delete older snapshots first
+
+        task_log!(
+            worker,
+            "Starting prune on store \"{}\" group \"{}\"",
+            store,
+            group,
+        );
+
+        for (info, keep) in prune_info {
+            task_log!(
+                worker,
+                "{} {}",
+                if info.protected { "keep (protected)" } else if keep { "keep" } else { "remove" },
+                info.backup_dir,
+            );
+            if !keep {
+                datastore.remove_backup_dir(&info.backup_dir, true)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_prune_worker(
     mut job: Job,
-    prune_options: PruneOptions,
+    worker_id: String,
     store: String,
+    selection: PruneSelection,
+    prune_options: PruneOptions,
     auth_id: &Authid,
     schedule: Option<String>,
 ) -> Result<String, Error> {
-    let datastore = DataStore::lookup_datastore(&store)?;
+    let datastore = DataStore::lookup_datastore(&store, Some(Operation::Write))?;
 
     let worker_type = job.jobtype().to_string();
     let upid_str = WorkerTask::new_thread(
         &worker_type,
-        Some(job.jobname().to_string()),
+        Some(worker_id),
         auth_id.clone(),
         false,
         move |worker| {
@@ -35,43 +100,17 @@ pub fn do_prune_job(
                     task_log!(worker, "task triggered by schedule '{}'", event_str);
                 }
 
+                if !selection.ns.is_root() {
+                    task_log!(worker, "namespace: {}", selection.ns);
+                }
+
                 task_log!(
                     worker,
                     "retention options: {}",
                     prune_options.cli_options_string()
                 );
 
-                let base_path = datastore.base_path();
-
-                let groups = BackupInfo::list_backup_groups(&base_path)?;
-                for group in groups {
-                    let list = group.list_backups(&base_path)?;
-                    let mut prune_info = compute_prune_info(list, &prune_options)?;
-                    prune_info.reverse(); // delete older snapshots first
-
-                    task_log!(
-                        worker,
-                        "Starting prune on store \"{}\" group \"{}/{}\"",
-                        store,
-                        group.backup_type(),
-                        group.backup_id()
-                    );
-
-                    for (info, keep) in prune_info {
-                        task_log!(
-                            worker,
-                            "{} {}/{}/{}",
-                            if keep { "keep" } else { "remove" },
-                            group.backup_type(),
-                            group.backup_id(),
-                            info.backup_dir.backup_time_string()
-                        );
-                        if !keep {
-                            datastore.remove_backup_dir(&info.backup_dir, true)?;
-                        }
-                    }
-                }
-                Ok(())
+                prune_groups(&worker, &datastore, &store, &selection, &prune_options)
             });
 
             let status = worker.create_state(&result);
@@ -89,3 +128,48 @@ pub fn do_prune_job(
     )?;
     Ok(upid_str)
 }
+
+/// Prune all groups of a datastore, using the retention options of its configuration.
+pub fn do_prune_job(
+    job: Job,
+    prune_options: PruneOptions,
+    store: String,
+    auth_id: &Authid,
+    schedule: Option<String>,
+) -> Result<String, Error> {
+    let worker_id = job.jobname().to_string();
+
+    let selection = PruneSelection {
+        ns: BackupNamespace::root(),
+        max_depth: None,
+        group_filter: None,
+    };
+
+    spawn_prune_worker(job, worker_id, store, selection, prune_options, auth_id, schedule)
+}
+
+/// Run a prune job from `prune.cfg`.
+pub fn do_prune_job_config(
+    job: Job,
+    prune_job: PruneJobConfig,
+    auth_id: &Authid,
+    schedule: Option<String>,
+) -> Result<String, Error> {
+    let worker_id = format!("{}:{}", prune_job.store, job.jobname());
+
+    let prune_options = prune_job.prune_options();
+    if !prune_options.keeps_something() {
+        bail!("prune job '{}' has no retention options set", prune_job.id);
+    }
+
+    let selection = PruneSelection {
+        ns: prune_job.ns.clone().unwrap_or_default(),
+        max_depth: prune_job.max_depth,
+        group_filter: match prune_job.group_filter {
+            Some(ref list) => Some(GroupFilter::parse_list(list)?),
+            None => None,
+        },
+    };
+
+    spawn_prune_worker(job, worker_id, prune_job.store, selection, prune_options, auth_id, schedule)
+}
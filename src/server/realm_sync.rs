@@ -0,0 +1,433 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{format_err, Error};
+
+use proxmox::api::schema::{parse_simple_value, Schema};
+use proxmox::api::section_config::SectionConfigData;
+use proxmox::tools::fs::open_file_locked;
+
+use crate::{
+    api2::types::*,
+    auth::LdapAuthenticator,
+    config::domains::LdapRealmConfig,
+    config::user::{self, User, UserGroup, EMAIL_SCHEMA, FIRST_NAME_SCHEMA, LAST_NAME_SCHEMA},
+    server::jobstate::Job,
+    server::WorkerTask,
+    task_log,
+    task_warn,
+    tools::ldap::SearchScope,
+};
+
+const DEFAULT_GROUP_FILTER: &str =
+    "(|(objectClass=group)(objectClass=groupOfNames)(objectClass=groupOfUniqueNames)(objectClass=posixGroup))";
+
+/// A user read from the directory
+struct DirectoryUser {
+    userid: Userid,
+    firstname: Option<String>,
+    lastname: Option<String>,
+    email: Option<String>,
+}
+
+/// A group read from the directory, with the IDs of its (synced) members
+struct DirectoryGroup {
+    groupid: String,
+    members: Vec<Userid>,
+}
+
+// Only use values which are valid in the user configuration
+fn checked_value(value: Option<&str>, schema: &Schema) -> Option<String> {
+    let value = value?.trim();
+    match parse_simple_value(value, schema) {
+        Ok(_) => Some(value.to_string()),
+        Err(_) => None,
+    }
+}
+
+fn read_directory(
+    worker: &WorkerTask,
+    config: &LdapRealmConfig,
+) -> Result<(Vec<DirectoryUser>, Vec<DirectoryGroup>), Error> {
+    let authenticator = LdapAuthenticator::new(config.clone());
+    let mut conn = authenticator.connect()?;
+
+    let user_attr = config.user_attr.as_str();
+    let filter = match &config.filter {
+        Some(filter) => format!("(&({}=*){})", user_attr, filter),
+        None => format!("({}=*)", user_attr),
+    };
+    let entries = conn.search(
+        &config.base_dn,
+        SearchScope::Subtree,
+        &filter,
+        &[user_attr, "givenName", "sn", "mail"],
+    )?;
+
+    let mut users = Vec::new();
+    let mut dn_map = HashMap::new();
+    let mut name_map = HashMap::new();
+
+    for entry in entries {
+        let name = match entry.first_value(user_attr) {
+            Some(name) => name,
+            None => continue,
+        };
+        let userid: Userid = match format!("{}@{}", name, config.realm).parse() {
+            Ok(userid) => userid,
+            Err(err) => {
+                task_warn!(worker, "skipping user '{}' - {}", name, err);
+                continue;
+            }
+        };
+
+        dn_map.insert(entry.dn.to_lowercase(), userid.clone());
+        name_map.insert(name.to_string(), userid.clone());
+
+        users.push(DirectoryUser {
+            userid,
+            firstname: checked_value(entry.first_value("givenName"), &FIRST_NAME_SCHEMA),
+            lastname: checked_value(entry.first_value("sn"), &LAST_NAME_SCHEMA),
+            email: checked_value(entry.first_value("mail"), &EMAIL_SCHEMA),
+        });
+    }
+    task_log!(worker, "found {} users", users.len());
+
+    let name_attr = config.group_name_attr.as_deref().unwrap_or("cn");
+    let entries = conn.search(
+        config.group_dn.as_deref().unwrap_or(&config.base_dn),
+        SearchScope::Subtree,
+        config.group_filter.as_deref().unwrap_or(DEFAULT_GROUP_FILTER),
+        &[name_attr, "member", "uniqueMember", "memberUid"],
+    )?;
+
+    let mut groups = Vec::new();
+    for entry in entries {
+        let name = match entry.first_value(name_attr) {
+            Some(name) => name,
+            None => continue,
+        };
+        let groupid = format!("{}-{}", name, config.realm);
+        if groupid.contains('@') || parse_simple_value(&groupid, &PROXMOX_GROUP_ID_SCHEMA).is_err() {
+            task_warn!(worker, "skipping group '{}' - invalid group name", name);
+            continue;
+        }
+
+        let mut members = HashSet::new();
+        for dn in entry.values("member").iter().chain(entry.values("uniqueMember")) {
+            if let Some(userid) = dn_map.get(&dn.to_lowercase()) {
+                members.insert(userid.clone());
+            }
+        }
+        // posixGroup lists user names instead of DNs
+        for name in entry.values("memberUid") {
+            if let Some(userid) = name_map.get(name) {
+                members.insert(userid.clone());
+            }
+        }
+
+        let mut members: Vec<Userid> = members.into_iter().collect();
+        members.sort_unstable_by(|a, b| a.as_str().cmp(b.as_str()));
+
+        groups.push(DirectoryGroup { groupid, members });
+    }
+    task_log!(worker, "found {} groups", groups.len());
+
+    Ok((users, groups))
+}
+
+// Apply the directory content to the user configuration, returns a log of
+// the changes and the removed users
+fn update_user_config(
+    config: &mut SectionConfigData,
+    realm: &str,
+    users: &[DirectoryUser],
+    groups: &[DirectoryGroup],
+    remove_vanished: bool,
+) -> Result<(Vec<String>, Vec<Userid>), Error> {
+    let mut log = Vec::new();
+
+    for entry in users {
+        let id = entry.userid.as_str();
+        match config.lookup::<User>("user", id) {
+            Ok(mut user) => {
+                if user.firstname == entry.firstname
+                    && user.lastname == entry.lastname
+                    && user.email == entry.email
+                {
+                    continue;
+                }
+                user.firstname = entry.firstname.clone();
+                user.lastname = entry.lastname.clone();
+                user.email = entry.email.clone();
+                config.set_data(id, "user", &user)?;
+                log.push(format!("updated user '{}'", id));
+            }
+            Err(_) => {
+                let user = User {
+                    userid: entry.userid.clone(),
+                    comment: None,
+                    enable: None,
+                    expire: None,
+                    firstname: entry.firstname.clone(),
+                    lastname: entry.lastname.clone(),
+                    email: entry.email.clone(),
+                };
+                config.set_data(id, "user", &user)?;
+                log.push(format!("added user '{}'", id));
+            }
+        }
+    }
+
+    let mut removed = Vec::new();
+
+    if remove_vanished {
+        let synced: HashSet<&str> = users.iter().map(|user| user.userid.as_str()).collect();
+        let mut vanished = Vec::new();
+
+        for (id, (section_type, _)) in config.sections.iter() {
+            let userid = match section_type.as_str() {
+                "user" => id.parse::<Userid>()?,
+                "token" => id.parse::<Authid>()?.user().clone(),
+                _ => continue,
+            };
+            if userid.realm().as_str() == realm && !synced.contains(userid.as_str()) {
+                vanished.push(id.clone());
+                if section_type == "user" {
+                    removed.push(userid);
+                }
+            }
+        }
+
+        for id in vanished {
+            config.sections.remove(&id);
+            log.push(format!("removed vanished user '{}'", id));
+        }
+
+        // only remove groups created by the sync of this realm
+        let synced: HashSet<&str> = groups.iter().map(|group| group.groupid.as_str()).collect();
+        let mut vanished = Vec::new();
+        for (id, (section_type, _)) in config.sections.iter() {
+            if section_type != "group" || synced.contains(id.as_str()) {
+                continue;
+            }
+            let group: UserGroup = config.lookup("group", id)?;
+            if group.realm.as_deref() == Some(realm) {
+                vanished.push(id.clone());
+            }
+        }
+
+        for id in vanished {
+            config.sections.remove(&id);
+            log.push(format!("removed vanished group '{}'", id));
+        }
+    }
+
+    for entry in groups {
+        let id = entry.groupid.as_str();
+        match config.lookup::<UserGroup>("group", id) {
+            Ok(mut group) if group.realm.as_deref() == Some(realm) => {
+                if group.member_list()? == entry.members {
+                    continue;
+                }
+                group.set_member_list(&entry.members);
+                config.set_data(id, "group", &group)?;
+                log.push(format!("updated members of group '{}'", id));
+            }
+            Ok(_) => {
+                log.push(format!("skipping group '{}' - not synced from this realm", id));
+            }
+            Err(_) => {
+                if config.sections.contains_key(id) {
+                    log.push(format!("skipping group '{}' - ID already in use", id));
+                    continue;
+                }
+                let mut group = UserGroup {
+                    groupid: id.to_string(),
+                    comment: None,
+                    members: None,
+                    realm: Some(realm.to_string()),
+                };
+                group.set_member_list(&entry.members);
+                config.set_data(id, "group", &group)?;
+                log.push(format!("added group '{}'", id));
+            }
+        }
+    }
+
+    // drop removed users from all groups, including manually created ones
+    let mut stale = Vec::new();
+    for (id, (section_type, _)) in config.sections.iter() {
+        if section_type != "group" {
+            continue;
+        }
+        let group: UserGroup = config.lookup("group", id)?;
+        let members = group.member_list()?;
+        let existing: Vec<Userid> = members.iter()
+            .filter(|userid| config.sections.contains_key(userid.as_str()))
+            .cloned()
+            .collect();
+        if existing.len() != members.len() {
+            stale.push((group, existing));
+        }
+    }
+    for (mut group, members) in stale {
+        group.set_member_list(&members);
+        config.set_data(&group.groupid, "group", &group)?;
+        log.push(format!("removed vanished members from group '{}'", group.groupid));
+    }
+
+    Ok((log, removed))
+}
+
+/// Sync users and groups of a LDAP realm into the user configuration.
+pub fn do_realm_sync_job(
+    mut job: Job,
+    realm: LdapRealmConfig,
+    auth_id: &Authid,
+    schedule: Option<String>,
+    to_stdout: bool,
+) -> Result<String, Error> {
+
+    let worker_type = job.jobtype().to_string();
+    let upid_str = WorkerTask::new_thread(
+        &worker_type,
+        Some(realm.realm.clone()),
+        auth_id.clone(),
+        to_stdout,
+        move |worker| {
+            job.start(&worker.upid().to_string())?;
+
+            task_log!(worker, "Starting sync of realm '{}'", realm.realm);
+            if let Some(event_str) = schedule {
+                task_log!(worker, "task triggered by schedule '{}'", event_str);
+            }
+
+            let result = read_directory(&worker, &realm)
+                .map_err(|err| format_err!("reading directory failed - {}", err))
+                .and_then(|(users, groups)| {
+                    // same lock order as when deleting a user
+                    let _tfa_lock = crate::config::tfa::write_lock()?;
+                    let _lock = open_file_locked(user::USER_CFG_LOCKFILE, std::time::Duration::new(10, 0), true)?;
+                    let (mut config, _digest) = user::config()?;
+
+                    let remove_vanished = realm.remove_vanished.unwrap_or(false);
+                    let (log, removed) = update_user_config(&mut config, &realm.realm, &users, &groups, remove_vanished)?;
+                    for line in &log {
+                        task_log!(worker, "{}", line);
+                    }
+
+                    if !log.is_empty() {
+                        user::save_config(&config)?;
+                    }
+                    if let Err(err) = user::cleanup_deleted_users(&removed) {
+                        task_warn!(worker, "error updating ACL/TFA config after removing users: {}", err);
+                    }
+                    task_log!(worker, "sync finished, {} changes", log.len());
+                    Ok(())
+                });
+
+            let status = worker.create_state(&result);
+
+            if let Err(err) = job.finish(status) {
+                log::error!(
+                    "could not finish job state for {}: {}",
+                    job.jobtype().to_string(),
+                    err
+                );
+            }
+
+            result
+        },
+    )?;
+    Ok(upid_str)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn user(userid: &str, firstname: Option<&str>) -> DirectoryUser {
+        DirectoryUser {
+            userid: userid.parse().unwrap(),
+            firstname: firstname.map(String::from),
+            lastname: None,
+            email: None,
+        }
+    }
+
+    #[test]
+    fn test_update_user_config() -> Result<(), Error> {
+        let (mut config, _) = user::test_cfg_from_str(r###"
+user: john@example
+	firstname John
+
+user: gone@example
+
+token: gone@example!token
+
+user: local@pbs
+
+group: admins-example
+	members gone@example
+	realm example
+
+group: local
+	members local@pbs,gone@example
+
+group: manual-example
+	members local@pbs
+"###)?;
+
+        let users = vec![
+            user("john@example", Some("Johnny")),
+            user("jane@example", None),
+        ];
+        let groups = vec![
+            DirectoryGroup {
+                groupid: "staff-example".to_string(),
+                members: vec!["jane@example".parse()?, "john@example".parse()?],
+            },
+            DirectoryGroup {
+                groupid: "manual-example".to_string(),
+                members: vec!["john@example".parse()?],
+            },
+        ];
+
+        // nothing is removed without remove-vanished
+        let (log, removed) = update_user_config(&mut config, "example", &users, &groups, false)?;
+        assert_eq!(log.len(), 4);
+        assert!(removed.is_empty());
+        assert!(config.sections.contains_key("gone@example"));
+        assert!(config.sections.contains_key("admins-example"));
+
+        let john: User = config.lookup("user", "john@example")?;
+        assert_eq!(john.firstname.as_deref(), Some("Johnny"));
+        let staff: UserGroup = config.lookup("group", "staff-example")?;
+        assert_eq!(staff.members.as_deref(), Some("jane@example,john@example"));
+        assert_eq!(staff.realm.as_deref(), Some("example"));
+
+        // groups not created by the sync are left alone
+        let manual: UserGroup = config.lookup("group", "manual-example")?;
+        assert_eq!(manual.members.as_deref(), Some("local@pbs"));
+        assert_eq!(manual.realm, None);
+
+        let (log, removed) = update_user_config(&mut config, "example", &users, &groups, true)?;
+        assert_eq!(log.len(), 5);
+        assert_eq!(removed, vec!["gone@example".parse::<Userid>()?]);
+        assert!(!config.sections.contains_key("gone@example"));
+        assert!(!config.sections.contains_key("gone@example!token"));
+        assert!(!config.sections.contains_key("admins-example"));
+        assert!(config.sections.contains_key("manual-example"));
+        assert!(config.sections.contains_key("local@pbs"));
+
+        let local: UserGroup = config.lookup("group", "local")?;
+        assert_eq!(local.members.as_deref(), Some("local@pbs"));
+
+        // a second run only reports the skipped group
+        let (log, removed) = update_user_config(&mut config, "example", &users, &groups, true)?;
+        assert_eq!(log.len(), 1);
+        assert!(removed.is_empty());
+
+        Ok(())
+    }
+}
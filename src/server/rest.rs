@@ -46,8 +46,9 @@ use super::ApiConfig;
 use crate::auth_helpers::*;
 use crate::api2::types::{Authid, Userid};
 use crate::tools;
-use crate::tools::FileLogger;
+use crate::tools::{FileLogger, RateLimitedStream};
 use crate::tools::ticket::Ticket;
+use crate::config::acl::PRIV_SYS_AUDIT;
 use crate::config::cached_user_info::CachedUserInfo;
 
 extern "C"  { fn tzset(); }
@@ -65,7 +66,7 @@ impl RestServer {
     }
 }
 
-impl tower_service::Service<&Pin<Box<tokio_openssl::SslStream<tokio::net::TcpStream>>>> for RestServer {
+impl tower_service::Service<&Pin<Box<tokio_openssl::SslStream<RateLimitedStream<tokio::net::TcpStream>>>>> for RestServer {
     type Response = ApiService;
     type Error = Error;
     type Future = Pin<Box<dyn Future<Output = Result<ApiService, Error>> + Send>>;
@@ -74,8 +75,8 @@ impl tower_service::Service<&Pin<Box<tokio_openssl::SslStream<tokio::net::TcpStr
         Poll::Ready(Ok(()))
     }
 
-    fn call(&mut self, ctx: &Pin<Box<tokio_openssl::SslStream<tokio::net::TcpStream>>>) -> Self::Future {
-        match ctx.get_ref().peer_addr() {
+    fn call(&mut self, ctx: &Pin<Box<tokio_openssl::SslStream<RateLimitedStream<tokio::net::TcpStream>>>>) -> Self::Future {
+        match ctx.get_ref().inner().peer_addr() {
             Err(err) => {
                 future::err(format_err!("unable to get peer address - {}", err)).boxed()
             }
@@ -770,6 +771,47 @@ async fn handle_request(
             }
 
         }
+    } else if comp_len == 1 && components[0] == "metrics" && env_type == RpcEnvironmentType::PUBLIC {
+
+        if method != hyper::Method::GET {
+            bail!("Unsupported HTTP method {}", method);
+        }
+
+        let auth_result = match extract_auth_data(&parts.headers) {
+            Some(auth_data) => check_auth(&method, &auth_data, &user_info),
+            None => Err(format_err!("no authentication credentials provided.")),
+        };
+        let auth_id = match auth_result {
+            Ok(auth_id) => auth_id,
+            Err(err) => {
+                let peer = peer.ip();
+                auth_logger()?
+                    .log(format!("authentication failure; rhost={} msg={}", peer, err));
+
+                tokio::time::sleep_until(Instant::from_std(delay_unauth_time)).await;
+                return Err(http_err!(UNAUTHORIZED, "authentication failed - {}", err));
+            }
+        };
+
+        if user_info.check_privs(&auth_id, &["system", "status"], PRIV_SYS_AUDIT, false).is_err() {
+            tokio::time::sleep_until(Instant::from_std(access_forbidden_time)).await;
+            return Err(http_err!(FORBIDDEN, "permission check failed"));
+        }
+
+        let metrics = {
+            let auth_id = auth_id.clone();
+            tools::runtime::block_in_place(move || {
+                super::openmetrics::generate_metrics(&auth_id, &user_info)
+            })
+        }.map_err(|err| http_err!(INTERNAL_SERVER_ERROR, "generating metrics failed - {}", err))?;
+
+        let mut response = Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, super::openmetrics::OPENMETRICS_CONTENT_TYPE)
+            .body(metrics.into())?;
+        response.extensions_mut().insert(auth_id);
+
+        return Ok(response);
      } else {
         // not Auth required for accessing files!
 
@@ -0,0 +1,137 @@
+//! Traffic control for incoming connections
+//!
+//! Connections are limited by the rules configured in `traffic-control.cfg`. All connections
+//! matching a rule share its rate limiters. If more than one rule matches, the rule with the
+//! most specific network is used.
+
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::Error;
+use lazy_static::lazy_static;
+
+use crate::config::traffic_control::{self, Network, TrafficControlRule};
+use crate::tools::{RateLimiter, SharedRateLimiter};
+
+// re-check the configuration file at most every 5 seconds
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+struct ParsedRule {
+    config: TrafficControlRule,
+    networks: Vec<Network>,
+    read_limiter: Option<SharedRateLimiter>,
+    write_limiter: Option<SharedRateLimiter>,
+}
+
+struct TrafficControlCache {
+    last_check: Option<Instant>,
+    digest: [u8; 32],
+    rules: Vec<ParsedRule>,
+}
+
+lazy_static! {
+    static ref TRAFFIC_CONTROL_CACHE: Mutex<TrafficControlCache> = Mutex::new(TrafficControlCache {
+        last_check: None,
+        digest: [0u8; 32],
+        rules: Vec::new(),
+    });
+}
+
+fn update_limiter(
+    old: Option<SharedRateLimiter>,
+    rate: Option<u64>,
+    burst: Option<u64>,
+) -> Option<SharedRateLimiter> {
+    let rate = rate?;
+    let burst = burst.unwrap_or(rate);
+
+    match old {
+        Some(limiter) => {
+            // keep the instance, so that existing connections use the new limits
+            limiter.lock().unwrap().update_rate(rate, burst);
+            Some(limiter)
+        }
+        None => Some(RateLimiter::new_shared(rate, Some(burst))),
+    }
+}
+
+impl TrafficControlCache {
+
+    fn reload(&mut self, now: Instant) -> Result<(), Error> {
+        if let Some(last_check) = self.last_check {
+            if now.saturating_duration_since(last_check) < CONFIG_CHECK_INTERVAL {
+                return Ok(());
+            }
+        }
+        self.last_check = Some(now);
+
+        let (config, digest) = traffic_control::config()?;
+        if digest == self.digest {
+            return Ok(());
+        }
+
+        let list: Vec<TrafficControlRule> = config.convert_to_typed_array("rule")?;
+
+        let mut parsed = Vec::new();
+        for rule in list {
+            let networks = traffic_control::parse_network_list(&rule.network)?;
+            parsed.push((rule, networks));
+        }
+
+        let mut old_rules = std::mem::take(&mut self.rules);
+        let mut rules = Vec::new();
+
+        for (rule, networks) in parsed {
+            let (read_limiter, write_limiter) = match old_rules.iter().position(|old| old.config.name == rule.name) {
+                Some(pos) => {
+                    let old = old_rules.remove(pos);
+                    (old.read_limiter, old.write_limiter)
+                }
+                None => (None, None),
+            };
+
+            let read_limiter = update_limiter(read_limiter, rule.rate_in, rule.burst_in);
+            let write_limiter = update_limiter(write_limiter, rule.rate_out, rule.burst_out);
+
+            rules.push(ParsedRule { config: rule, networks, read_limiter, write_limiter });
+        }
+
+        self.rules = rules;
+        self.digest = digest;
+
+        Ok(())
+    }
+
+    fn lookup(&self, ip: &IpAddr) -> (Option<SharedRateLimiter>, Option<SharedRateLimiter>) {
+        let mut best: Option<(u8, &ParsedRule)> = None;
+
+        for rule in self.rules.iter() {
+            for network in rule.networks.iter() {
+                if !network.contains(ip) {
+                    continue;
+                }
+                match best {
+                    Some((mask, _)) if mask >= network.mask => {},
+                    _ => best = Some((network.mask, rule)),
+                }
+            }
+        }
+
+        match best {
+            Some((_, rule)) => (rule.read_limiter.clone(), rule.write_limiter.clone()),
+            None => (None, None),
+        }
+    }
+}
+
+/// Returns the read (incoming) and write (outgoing) rate limiter for a client address.
+pub fn lookup_rate_limiter(peer: &IpAddr) -> (Option<SharedRateLimiter>, Option<SharedRateLimiter>) {
+    let mut cache = TRAFFIC_CONTROL_CACHE.lock().unwrap();
+
+    if let Err(err) = cache.reload(Instant::now()) {
+        log::error!("unable to load traffic control config - {}", err);
+    }
+
+    cache.lookup(peer)
+}
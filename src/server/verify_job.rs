@@ -21,7 +21,7 @@ pub fn do_verification_job(
     schedule: Option<String>,
 ) -> Result<String, Error> {
 
-    let datastore = DataStore::lookup_datastore(&verification_job.store)?;
+    let datastore = DataStore::lookup_datastore(&verification_job.store, Some(Operation::Read))?;
 
     let outdated_after = verification_job.outdated_after;
     let ignore_verified_snapshots = verification_job.ignore_verified.unwrap_or(true);
@@ -92,10 +92,8 @@ pub fn do_verification_job(
                 );
             }
 
-            if let Some(email) = email {
-                if let Err(err) = crate::server::send_verify_status(&email, notify, verification_job, &result) {
-                    eprintln!("send verify notification failed: {}", err);
-                }
+            if let Err(err) = crate::server::send_verify_status(email.as_deref(), notify, verification_job, &result) {
+                eprintln!("send verify notification failed: {}", err);
             }
 
             job_result
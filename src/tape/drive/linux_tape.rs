@@ -493,6 +493,27 @@ impl TapeDriver for LinuxTapeHandle {
         Ok(status.mt_fileno as u64)
     }
 
+    fn move_to_file(&mut self, file: u64) -> Result<(), Error> {
+
+        let current_file = self.current_file_number()?;
+
+        if file < current_file {
+            self.rewind()?;
+            if file > 0 {
+                self.forward_space_count_files(i32::try_from(file)?)?;
+            }
+        } else if file > current_file {
+            self.forward_space_count_files(i32::try_from(file - current_file)?)?;
+        }
+
+        let current_file = self.current_file_number()?;
+        if current_file != file {
+            bail!("move_to_file failed - got wrong file number ({} != {})", current_file, file);
+        }
+
+        Ok(())
+    }
+
     fn erase_media(&mut self, fast: bool) -> Result<(), Error> {
 
         self.rewind()?; // important - erase from BOT
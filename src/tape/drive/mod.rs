@@ -90,6 +90,9 @@ pub trait TapeDriver {
     /// Current file number
     fn current_file_number(&mut self) -> Result<u64, Error>;
 
+    /// Move to the beginning of the given file number
+    fn move_to_file(&mut self, file: u64) -> Result<(), Error>;
+
     /// Completely erase the media
     fn erase_media(&mut self, fast: bool) -> Result<(), Error>;
 
@@ -390,9 +393,7 @@ pub fn request_and_load_media(
                         if tried {
                             if let Some(reason) = failure_reason {
                                 task_log!(worker, "Please insert media '{}' into drive '{}'", label_text, drive);
-                                if let Some(to) = notify_email {
-                                    send_load_media_email(drive, &label_text, to, Some(reason))?;
-                                }
+                                send_load_media_email(drive, &label_text, notify_email.as_deref(), Some(reason))?;
                             }
 
                             failure_reason = None;
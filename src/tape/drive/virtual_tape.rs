@@ -305,6 +305,26 @@ impl TapeDriver for VirtualTapeHandle {
         }
     }
 
+    fn move_to_file(&mut self, file: u64) -> Result<(), Error> {
+        let mut status = self.load_status()?;
+        match status.current_tape {
+            Some(VirtualTapeStatus { ref name, ref mut pos }) => {
+
+                let index = self.load_tape_index(name)?;
+
+                if file as usize > index.files {
+                    bail!("move_to_file failed - file number {} after end of tape", file);
+                }
+
+                *pos = file as usize;
+                self.store_status(&status)?;
+
+                Ok(())
+            }
+            None => bail!("drive is empty (no tape loaded)."),
+        }
+    }
+
     fn rewind(&mut self) -> Result<(), Error> {
         let mut status = self.load_status()?;
         match status.current_tape {
@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::io::Read;
 
 use anyhow::{bail, Error};
@@ -8,7 +9,13 @@ use proxmox::tools::{
     io::ReadExt,
 };
 
-use crate::backup::DataBlob;
+use crate::backup::{
+    DataBlob,
+    MAX_DICTIONARY_SIZE,
+    ZstdDictionary,
+    lookup_zstd_dictionary,
+    register_zstd_dictionary,
+};
 
 use crate::tape::{
     TapeWrite,
@@ -16,6 +23,7 @@ use crate::tape::{
         PROXMOX_TAPE_BLOCK_SIZE,
         PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_0,
         PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0,
+        PROXMOX_BACKUP_CHUNK_ARCHIVE_DICT_ENTRY_MAGIC_1_0,
         MediaContentHeader,
         ChunkArchiveEntryHeader,
     },
@@ -28,10 +36,15 @@ use crate::tape::{
 /// `ChunkArchiveEntryHeader` followed by the chunk data (`DataBlob`).
 ///
 /// `| MediaContentHeader | ( ChunkArchiveEntryHeader | DataBlob )* |`
+///
+/// Chunks compressed with a zstd dictionary are preceded by an entry
+/// containing that dictionary (only once per archive), so that each
+/// archive can be restored on its own.
 pub struct ChunkArchiveWriter<'a> {
     writer: Option<Box<dyn TapeWrite + 'a>>,
     bytes_written: usize, // does not include bytes from current writer
     close_on_leom: bool,
+    dictionaries: HashSet<[u8; 8]>, // dictionaries written to this archive
 }
 
 impl <'a> ChunkArchiveWriter<'a> {
@@ -48,6 +61,7 @@ impl <'a> ChunkArchiveWriter<'a> {
             writer: Some(writer),
             bytes_written: 0,
             close_on_leom,
+            dictionaries: HashSet::new(),
         };
 
         Ok((me, header.uuid.into()))
@@ -83,10 +97,45 @@ impl <'a> ChunkArchiveWriter<'a> {
             return Ok(false);
         }
 
+        if let Some(id) = blob.dictionary_id() {
+            if !self.dictionaries.contains(&id) {
+                let dict = lookup_zstd_dictionary(&id).ok_or_else(|| {
+                    proxmox::io_format_err!(
+                        "zstd dictionary {} not available", proxmox::tools::bin_to_hex(&id))
+                })?;
+                if !self.try_write_dictionary(&dict)? {
+                    return Ok(false);
+                }
+            }
+        }
+
+        self.write_entry(PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0, digest, blob.raw_data())
+    }
+
+    fn try_write_dictionary(&mut self, dict: &ZstdDictionary) -> Result<bool, std::io::Error> {
+        let digest = openssl::sha::sha256(dict.data());
+        self.write_entry(PROXMOX_BACKUP_CHUNK_ARCHIVE_DICT_ENTRY_MAGIC_1_0, &digest, dict.data())?;
+
+        if self.writer.is_none() {
+            // LEOM - the chunk goes to the next archive (together with the dictionary)
+            return Ok(false);
+        }
+        self.dictionaries.insert(*dict.id());
+
+        Ok(true)
+    }
+
+    fn write_entry(
+        &mut self,
+        magic: [u8; 8],
+        digest: &[u8;32],
+        entry_data: &[u8],
+    ) -> Result<bool, std::io::Error> {
+
         let head = ChunkArchiveEntryHeader {
-            magic: PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0,
+            magic,
             digest: *digest,
-            size: blob.raw_size(),
+            size: entry_data.len() as u64,
         };
 
         let head = head.to_le();
@@ -98,19 +147,18 @@ impl <'a> ChunkArchiveWriter<'a> {
         self.write_all(data)?;
 
         let mut start = 0;
-        let blob_data = blob.raw_data();
         loop {
-            if start >= blob_data.len() {
+            if start >= entry_data.len() {
                 break;
             }
 
             let end = start + PROXMOX_TAPE_BLOCK_SIZE;
             let mut chunk_is_complete = false;
-            let leom = if end > blob_data.len() {
+            let leom = if end > entry_data.len() {
                 chunk_is_complete = true;
-                self.write_all(&blob_data[start..])?
+                self.write_all(&entry_data[start..])?
             } else {
-                self.write_all(&blob_data[start..end])?
+                self.write_all(&entry_data[start..end])?
             };
             if leom {
                 println!("WRITE DATA LEOM at pos {}", self.bytes_written());
@@ -159,44 +207,65 @@ impl <R: Read> ChunkArchiveDecoder<R> {
     }
 
     /// Returns the next chunk (if any).
+    ///
+    /// Zstd dictionaries contained in the archive get registered (see
+    /// ``register_zstd_dictionary``), so that the following chunks can
+    /// be decoded.
     pub fn next_chunk(&mut self) -> Result<Option<([u8;32], DataBlob)>, Error> {
 
-        let mut header = ChunkArchiveEntryHeader {
-            magic: [0u8; 8],
-            digest: [0u8; 32],
-            size: 0,
-        };
-        let data = unsafe {
-            std::slice::from_raw_parts_mut(
-                (&mut header as *mut ChunkArchiveEntryHeader) as *mut u8,
-                std::mem::size_of::<ChunkArchiveEntryHeader>())
-        };
+        loop {
+            let mut header = ChunkArchiveEntryHeader {
+                magic: [0u8; 8],
+                digest: [0u8; 32],
+                size: 0,
+            };
+            let data = unsafe {
+                std::slice::from_raw_parts_mut(
+                    (&mut header as *mut ChunkArchiveEntryHeader) as *mut u8,
+                    std::mem::size_of::<ChunkArchiveEntryHeader>())
+            };
+
+            match self.reader.read_exact_or_eof(data) {
+                Ok(true) => {},
+                Ok(false) => {
+                    // last chunk is allowed to be incomplete - simply report EOD
+                    return Ok(None);
+                }
+                Err(err) => return Err(err.into()),
+            };
 
-        match self.reader.read_exact_or_eof(data) {
-            Ok(true) => {},
-            Ok(false) => {
-                // last chunk is allowed to be incomplete - simply report EOD
-                return Ok(None);
+            let is_dictionary = match header.magic {
+                PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0 => false,
+                PROXMOX_BACKUP_CHUNK_ARCHIVE_DICT_ENTRY_MAGIC_1_0 => true,
+                _ => bail!("wrong magic number"),
+            };
+
+            let size = header.size as usize;
+            if is_dictionary && size > MAX_DICTIONARY_SIZE {
+                bail!("zstd dictionary too large ({} bytes)", size);
             }
-            Err(err) => return Err(err.into()),
-        };
 
-        if header.magic != PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0 {
-            bail!("wrong magic number");
-        }
+            let raw_data = match self.reader.read_exact_allocated(size) {
+                Ok(data) => data,
+                Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    // last chunk is allowed to be incomplete - simply report EOD
+                    return Ok(None);
+                }
+                Err(err) => return Err(err.into()),
+            };
 
-         let raw_data = match self.reader.read_exact_allocated(header.size as usize) {
-            Ok(data) => data,
-            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
-                // last chunk is allowed to be incomplete - simply report EOD
-                return Ok(None);
+            if is_dictionary {
+                if openssl::sha::sha256(&raw_data) != header.digest {
+                    bail!("zstd dictionary has wrong checksum");
+                }
+                register_zstd_dictionary(ZstdDictionary::new(raw_data));
+                continue;
             }
-            Err(err) => return Err(err.into()),
-        };
 
-        let blob = DataBlob::from_raw(raw_data)?;
-        blob.verify_crc()?;
+            let blob = DataBlob::from_raw(raw_data)?;
+            blob.verify_crc()?;
 
-        Ok(Some((header.digest, blob)))
+            return Ok(Some((header.digest, blob)));
+        }
     }
 }
@@ -47,6 +47,8 @@ pub const PROXMOX_BACKUP_MEDIA_SET_LABEL_MAGIC_1_0: [u8; 8] = [8, 96, 99, 249, 4
 pub const PROXMOX_BACKUP_CHUNK_ARCHIVE_MAGIC_1_0: [u8; 8] = [62, 173, 167, 95, 49, 76, 6, 110];
 // openssl::sha::sha256(b"Proxmox Backup Chunk Archive Entry v1.0")[0..8]
 pub const PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0: [u8; 8] = [72, 87, 109, 242, 222, 66, 143, 220];
+// openssl::sha::sha256(b"Proxmox Backup Chunk Archive Dictionary Entry v1.0")[0..8]
+pub const PROXMOX_BACKUP_CHUNK_ARCHIVE_DICT_ENTRY_MAGIC_1_0: [u8; 8] = [113, 51, 248, 156, 152, 255, 166, 155];
 
 // openssl::sha::sha256(b"Proxmox Backup Snapshot Archive v1.0")[0..8];
 pub const PROXMOX_BACKUP_SNAPSHOT_ARCHIVE_MAGIC_1_0: [u8; 8] = [9, 182, 2, 31, 125, 232, 114, 133];
@@ -175,6 +177,10 @@ impl MediaContentHeader {
 #[derive(Endian)]
 #[repr(C,packed)]
 /// Header for data blobs inside a chunk archive
+///
+/// Zstd dictionaries use the same header with magic
+/// `PROXMOX_BACKUP_CHUNK_ARCHIVE_DICT_ENTRY_MAGIC_1_0`, the digest
+/// is the SHA256 sum of the raw dictionary data.
 pub struct ChunkArchiveEntryHeader {
     /// fixed value `PROXMOX_BACKUP_CHUNK_ARCHIVE_ENTRY_MAGIC_1_0`
     pub magic: [u8; 8],
@@ -179,6 +179,10 @@ impl NewChunksIterator {
                     };
 
                     let blob = datastore.load_chunk(&digest)?;
+                    if let Some(id) = blob.dictionary_id() {
+                        // the chunk archive writer needs the dictionary
+                        datastore.load_zstd_dictionary(&id)?;
+                    }
                     //println!("LOAD CHUNK {}", proxmox::tools::digest_to_hex(&digest));
                     tx.send(Ok(Some((digest, blob)))).unwrap();
 
@@ -18,6 +18,7 @@ use percent_encoding::{utf8_percent_encode, AsciiSet};
 pub use proxmox::tools::fd::Fd;
 
 pub mod acl;
+pub mod acme;
 pub mod apt;
 pub mod async_io;
 pub mod borrow;
@@ -29,12 +30,16 @@ pub mod fs;
 pub mod fuse_loop;
 pub mod http;
 pub mod json;
+pub mod ldap;
 pub mod logrotate;
 pub mod loopdev;
 pub mod lru_cache;
 pub mod nom;
+pub mod openid;
 pub mod runtime;
+pub mod s3;
 pub mod serde_filter;
+pub mod smtp;
 pub mod socket;
 pub mod statistics;
 pub mod subscription;
@@ -66,6 +71,12 @@ pub use file_logger::{FileLogger, FileLogOptions};
 mod broadcast_future;
 pub use broadcast_future::{BroadcastData, BroadcastFuture};
 
+mod rate_limiter;
+pub use rate_limiter::{RateLimiter, SharedRateLimiter};
+
+mod rate_limited_stream;
+pub use rate_limited_stream::RateLimitedStream;
+
 /// The `BufferedRead` trait provides a single function
 /// `buffered_read`. It returns a reference to an internal buffer. The
 /// purpose of this traid is to avoid unnecessary data copies.
@@ -0,0 +1,443 @@
+//! ACME client (RFC 8555), used to order certificates from Let's Encrypt and other ACME
+//! certificate authorities
+//!
+//! Requests are signed with an EC P-256 account key (ES256). Certificate authorities with a
+//! private root certificate (like the Pebble test server) can be used by pointing the
+//! `SSL_CERT_FILE` environment variable to their root certificate.
+
+use anyhow::{bail, format_err, Error};
+use hyper::{Body, Request};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::nid::Nid;
+use openssl::pkey::Private;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::tools::http;
+
+pub const LETSENCRYPT_DIRECTORY: &str = "https://acme-v02.api.letsencrypt.org/directory";
+pub const LETSENCRYPT_STAGING_DIRECTORY: &str =
+    "https://acme-staging-v02.api.letsencrypt.org/directory";
+
+const JOSE_CONTENT_TYPE: &str = "application/jose+json";
+const BAD_NONCE_ERROR: &str = "urn:ietf:params:acme:error:badNonce";
+
+fn base64url_encode(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+    #[serde(default)]
+    meta: DirectoryMeta,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DirectoryMeta {
+    terms_of_service: Option<String>,
+}
+
+/// ACME account, as stored in the account file
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AccountData {
+    /// Directory URL of the certificate authority
+    pub directory: String,
+    /// Account URL
+    pub location: String,
+    /// Account key (EC P-256, PEM encoded)
+    pub key: String,
+    /// Contact URLs (`mailto:...`)
+    pub contact: Vec<String>,
+    /// Terms of service the user agreed to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tos: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct Identifier {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub value: String,
+}
+
+#[derive(Deserialize)]
+pub struct Order {
+    pub status: String,
+    pub authorizations: Vec<String>,
+    pub finalize: String,
+    pub certificate: Option<String>,
+    pub error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+pub struct Challenge {
+    #[serde(rename = "type")]
+    pub ty: String,
+    pub url: String,
+    pub status: String,
+    pub token: Option<String>,
+    pub error: Option<Value>,
+}
+
+#[derive(Deserialize)]
+pub struct Authorization {
+    pub identifier: Identifier,
+    pub status: String,
+    pub challenges: Vec<Challenge>,
+    #[serde(default)]
+    pub wildcard: bool,
+}
+
+impl Authorization {
+    /// Find the challenge of type `ty` (`http-01` or `dns-01`).
+    pub fn challenge(&self, ty: &str) -> Option<&Challenge> {
+        self.challenges.iter().find(|challenge| challenge.ty == ty)
+    }
+}
+
+struct AcmeResponse {
+    location: Option<String>,
+    body: String,
+}
+
+// Format an ACME problem document (RFC 7807) for error messages
+fn problem_to_string(problem: &Value) -> String {
+    match (problem["type"].as_str(), problem["detail"].as_str()) {
+        (Some(ty), Some(detail)) => format!("{} ({})", detail, ty),
+        (None, Some(detail)) => detail.to_string(),
+        _ => problem.to_string(),
+    }
+}
+
+/// Generate a new EC P-256 account key.
+pub fn generate_account_key() -> Result<EcKey<Private>, Error> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    Ok(EcKey::generate(&group)?)
+}
+
+/// Returns the JSON web key of the public part of `key`, with its members in lexicographic
+/// order, as required to compute the thumbprint (RFC 7638).
+fn jwk_string(key: &EcKey<Private>) -> Result<String, Error> {
+    let mut ctx = BigNumContext::new()?;
+    let mut x = BigNum::new()?;
+    let mut y = BigNum::new()?;
+    key.public_key().affine_coordinates_gfp(key.group(), &mut x, &mut y, &mut ctx)?;
+
+    Ok(format!(
+        r#"{{"crv":"P-256","kty":"EC","x":"{}","y":"{}"}}"#,
+        base64url_encode(&x.to_vec_padded(32)?),
+        base64url_encode(&y.to_vec_padded(32)?),
+    ))
+}
+
+/// JWS signature with ES256 (the raw `r || s` form, not DER)
+fn sign_es256(key: &EcKey<Private>, data: &[u8]) -> Result<Vec<u8>, Error> {
+    let digest = openssl::sha::sha256(data);
+    let signature = EcdsaSig::sign(&digest, key)?;
+    let mut raw = signature.r().to_vec_padded(32)?;
+    raw.extend(signature.s().to_vec_padded(32)?);
+    Ok(raw)
+}
+
+/// The TXT record value for a `dns-01` challenge
+pub fn dns_01_txt_value(key_authorization: &str) -> String {
+    base64url_encode(&openssl::sha::sha256(key_authorization.as_bytes()))
+}
+
+pub struct AcmeClient {
+    directory_url: String,
+    directory: Option<Directory>,
+    nonce: Option<String>,
+    key: Option<EcKey<Private>>,
+    account_url: Option<String>,
+}
+
+impl AcmeClient {
+    /// Create a client without account, to query the directory or register a new account.
+    pub fn new(directory_url: String) -> Self {
+        Self {
+            directory_url,
+            directory: None,
+            nonce: None,
+            key: None,
+            account_url: None,
+        }
+    }
+
+    /// Create a client for an existing account.
+    pub fn with_account(account: &AccountData) -> Result<Self, Error> {
+        let key = EcKey::private_key_from_pem(account.key.as_bytes())
+            .map_err(|err| format_err!("unable to parse account key - {}", err))?;
+
+        Ok(Self {
+            directory_url: account.directory.clone(),
+            directory: None,
+            nonce: None,
+            key: Some(key),
+            account_url: Some(account.location.clone()),
+        })
+    }
+
+    async fn directory(&mut self) -> Result<&Directory, Error> {
+        if self.directory.is_none() {
+            let data = http::get_string(&self.directory_url, None).await
+                .map_err(|err| format_err!("unable to get ACME directory - {}", err))?;
+            let directory: Directory = serde_json::from_str(&data)
+                .map_err(|err| format_err!("unable to parse ACME directory - {}", err))?;
+            self.directory = Some(directory);
+        }
+        Ok(self.directory.as_ref().unwrap())
+    }
+
+    /// Terms of service URL of the certificate authority, if any.
+    pub async fn terms_of_service_url(&mut self) -> Result<Option<String>, Error> {
+        Ok(self.directory().await?.meta.terms_of_service.clone())
+    }
+
+    fn key(&self) -> Result<&EcKey<Private>, Error> {
+        self.key.as_ref().ok_or_else(|| format_err!("no ACME account key"))
+    }
+
+    /// The key authorization for a challenge `token`.
+    pub fn key_authorization(&self, token: &str) -> Result<String, Error> {
+        let thumbprint = openssl::sha::sha256(jwk_string(self.key()?)?.as_bytes());
+        Ok(format!("{}.{}", token, base64url_encode(&thumbprint)))
+    }
+
+    async fn new_nonce(&mut self) -> Result<String, Error> {
+        if let Some(nonce) = self.nonce.take() {
+            return Ok(nonce);
+        }
+
+        let url = self.directory().await?.new_nonce.clone();
+        let request = Request::builder()
+            .method("HEAD")
+            .uri(&url)
+            .header("User-Agent", "proxmox-backup-server/1.0")
+            .body(Body::empty())?;
+
+        let response = http::request(request).await?;
+        response.headers().get("Replay-Nonce")
+            .and_then(|nonce| nonce.to_str().ok())
+            .map(|nonce| nonce.to_string())
+            .ok_or_else(|| format_err!("ACME server did not return a nonce"))
+    }
+
+    fn jws(&self, url: &str, nonce: &str, payload: Option<&Value>) -> Result<String, Error> {
+        let key = self.key()?;
+
+        let mut protected = json!({
+            "alg": "ES256",
+            "nonce": nonce,
+            "url": url,
+        });
+        match self.account_url {
+            Some(ref account_url) => protected["kid"] = account_url.clone().into(),
+            None => protected["jwk"] = serde_json::from_str(&jwk_string(key)?)?,
+        }
+
+        let protected = base64url_encode(protected.to_string().as_bytes());
+        // an empty payload is a "POST-as-GET" request
+        let payload = match payload {
+            Some(payload) => base64url_encode(payload.to_string().as_bytes()),
+            None => String::new(),
+        };
+
+        let signature = sign_es256(key, format!("{}.{}", protected, payload).as_bytes())?;
+
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": base64url_encode(&signature),
+        }).to_string())
+    }
+
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> Result<AcmeResponse, Error> {
+        let mut retry = true;
+        loop {
+            let nonce = self.new_nonce().await?;
+            let body = self.jws(url, &nonce, payload)?;
+
+            let response = http::post(url, Some(body), Some(JOSE_CONTENT_TYPE)).await?;
+
+            let status = response.status();
+            let header = |name: &str| {
+                response.headers().get(name)
+                    .and_then(|value| value.to_str().ok())
+                    .map(|value| value.to_string())
+            };
+            self.nonce = header("Replay-Nonce");
+            let location = header("Location");
+
+            let body = http::response_body_string(response).await?;
+
+            if status.is_success() {
+                return Ok(AcmeResponse { location, body });
+            }
+
+            let problem: Value = serde_json::from_str(&body).unwrap_or(Value::Null);
+            // nonces may get invalidated at any time, the request should be retried then
+            if retry && problem["type"].as_str() == Some(BAD_NONCE_ERROR) {
+                retry = false;
+                continue;
+            }
+
+            if problem.is_null() {
+                bail!("ACME request to {} failed with status {}", url, status);
+            }
+            bail!("ACME request to {} failed - {}", url, problem_to_string(&problem));
+        }
+    }
+
+    async fn post_json<T: serde::de::DeserializeOwned>(
+        &mut self,
+        url: &str,
+        payload: Option<&Value>,
+    ) -> Result<T, Error> {
+        let response = self.post(url, payload).await?;
+        serde_json::from_str(&response.body)
+            .map_err(|err| format_err!("unable to parse response from {} - {}", url, err))
+    }
+
+    /// Register a new account with a newly generated key.
+    pub async fn new_account(
+        &mut self,
+        contact: Vec<String>,
+        tos: Option<String>,
+    ) -> Result<AccountData, Error> {
+        let key = generate_account_key()?;
+        let key_pem = String::from_utf8(key.private_key_to_pem()?)?;
+
+        self.key = Some(key);
+        self.account_url = None;
+
+        let mut payload = json!({ "contact": contact });
+        if tos.is_some() {
+            payload["termsOfServiceAgreed"] = true.into();
+        }
+
+        let url = self.directory().await?.new_account.clone();
+        let response = self.post(&url, Some(&payload)).await?;
+
+        let location = response.location
+            .ok_or_else(|| format_err!("ACME server did not return the account URL"))?;
+        self.account_url = Some(location.clone());
+
+        Ok(AccountData {
+            directory: self.directory_url.clone(),
+            location,
+            key: key_pem,
+            contact,
+            tos,
+        })
+    }
+
+    /// Deactivate the account. The certificate authority will refuse any further requests of
+    /// it.
+    pub async fn deactivate_account(&mut self) -> Result<(), Error> {
+        let url = self.account_url.clone()
+            .ok_or_else(|| format_err!("no ACME account"))?;
+        self.post(&url, Some(&json!({ "status": "deactivated" }))).await?;
+        Ok(())
+    }
+
+    /// Create a new order for `domains`, returns the order URL and the order.
+    pub async fn new_order(&mut self, domains: &[String]) -> Result<(String, Order), Error> {
+        let identifiers: Vec<Value> = domains.iter()
+            .map(|domain| json!({ "type": "dns", "value": domain }))
+            .collect();
+
+        let url = self.directory().await?.new_order.clone();
+        let response = self.post(&url, Some(&json!({ "identifiers": identifiers }))).await?;
+
+        let location = response.location
+            .ok_or_else(|| format_err!("ACME server did not return the order URL"))?;
+        let order = serde_json::from_str(&response.body)
+            .map_err(|err| format_err!("unable to parse ACME order - {}", err))?;
+
+        Ok((location, order))
+    }
+
+    pub async fn get_order(&mut self, url: &str) -> Result<Order, Error> {
+        self.post_json(url, None).await
+    }
+
+    pub async fn get_authorization(&mut self, url: &str) -> Result<Authorization, Error> {
+        self.post_json(url, None).await
+    }
+
+    /// Tell the server that the challenge is ready to be validated.
+    pub async fn request_challenge_validation(&mut self, url: &str) -> Result<Challenge, Error> {
+        self.post_json(url, Some(&json!({}))).await
+    }
+
+    /// Finalize the order with a DER encoded certificate signing request.
+    pub async fn finalize(&mut self, url: &str, csr: &[u8]) -> Result<Order, Error> {
+        self.post_json(url, Some(&json!({ "csr": base64url_encode(csr) }))).await
+    }
+
+    /// Download the certificate chain (PEM).
+    pub async fn get_certificate(&mut self, url: &str) -> Result<String, Error> {
+        Ok(self.post(url, None).await?.body)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_jws_signature() -> Result<(), Error> {
+        let key = generate_account_key()?;
+        let client = AcmeClient {
+            directory_url: String::new(),
+            directory: None,
+            nonce: None,
+            key: Some(key),
+            account_url: Some("https://acme.example.com/acct/1".to_string()),
+        };
+
+        let jws: Value = serde_json::from_str(
+            &client.jws("https://acme.example.com/order", "nonce1", Some(&json!({})))?,
+        )?;
+
+        let decode = |value: &Value| {
+            base64::decode_config(value.as_str().unwrap(), base64::URL_SAFE_NO_PAD).unwrap()
+        };
+
+        let protected: Value = serde_json::from_slice(&decode(&jws["protected"]))?;
+        assert_eq!(protected["alg"], "ES256");
+        assert_eq!(protected["nonce"], "nonce1");
+        assert_eq!(protected["kid"], "https://acme.example.com/acct/1");
+        assert!(protected["jwk"].is_null());
+
+        // verify the raw signature with the public key
+        let signature = decode(&jws["signature"]);
+        assert_eq!(signature.len(), 64);
+        let signature = EcdsaSig::from_private_components(
+            BigNum::from_slice(&signature[..32])?,
+            BigNum::from_slice(&signature[32..])?,
+        )?;
+        let signed = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap(),
+        );
+        let digest = openssl::sha::sha256(signed.as_bytes());
+        assert!(signature.verify(&digest, client.key()?)?);
+
+        let key_authorization = client.key_authorization("token1")?;
+        assert!(key_authorization.starts_with("token1."));
+        assert_eq!(key_authorization.len(), "token1.".len() + 43);
+
+        Ok(())
+    }
+}
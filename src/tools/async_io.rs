@@ -10,6 +10,8 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::TcpListener;
 use hyper::client::connect::Connection;
 
+use crate::tools::RateLimitedStream;
+
 pub enum EitherStream<L, R> {
     Left(L),
     Right(R),
@@ -73,8 +75,8 @@ impl<L: AsyncWrite + Unpin, R: AsyncWrite + Unpin> AsyncWrite for EitherStream<L
 
 // we need this for crate::client::http_client:
 impl Connection for EitherStream<
-    tokio::net::TcpStream,
-    Pin<Box<tokio_openssl::SslStream<tokio::net::TcpStream>>>,
+    RateLimitedStream<tokio::net::TcpStream>,
+    Pin<Box<tokio_openssl::SslStream<RateLimitedStream<tokio::net::TcpStream>>>>,
 > {
     fn connected(&self) -> hyper::client::connect::Connected {
         match self {
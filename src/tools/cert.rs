@@ -1,3 +1,4 @@
+use std::convert::TryFrom;
 use std::path::PathBuf;
 
 use anyhow::Error;
@@ -19,6 +20,12 @@ fn x509name_to_string(name: &openssl::x509::X509NameRef) -> Result<String, Error
     Ok(parts.join(", "))
 }
 
+fn asn1_time_to_unix(time: &openssl::asn1::Asn1TimeRef) -> Result<i64, Error> {
+    let epoch = openssl::asn1::Asn1Time::from_unix(0)?;
+    let diff = epoch.diff(time)?;
+    Ok(i64::from(diff.days) * 86400 + i64::from(diff.secs))
+}
+
 impl CertInfo {
     pub fn new() -> Result<Self, Error> {
         Self::from_path(PathBuf::from(configdir!("/proxy.pem")))
@@ -26,7 +33,12 @@ impl CertInfo {
 
     pub fn from_path(path: PathBuf) -> Result<Self, Error> {
         let cert_pem = proxmox::tools::fs::file_get_contents(&path)?;
-        let x509 = openssl::x509::X509::from_pem(&cert_pem)?;
+        Self::from_pem(&cert_pem)
+    }
+
+    /// Parse the first certificate of a PEM encoded certificate (chain).
+    pub fn from_pem(cert_pem: &[u8]) -> Result<Self, Error> {
+        let x509 = openssl::x509::X509::from_pem(cert_pem)?;
         Ok(Self{
             x509
         })
@@ -36,6 +48,30 @@ impl CertInfo {
         self.x509.subject_alt_names()
     }
 
+    /// The DNS names and IP addresses of the subject alternative names.
+    pub fn subject_alt_name_list(&self) -> Vec<String> {
+        let mut list = Vec::new();
+
+        let names = match self.subject_alt_names() {
+            Some(names) => names,
+            None => return list,
+        };
+
+        for name in names.iter() {
+            if let Some(dns) = name.dnsname() {
+                list.push(dns.to_string());
+            } else if let Some(ip) = name.ipaddress() {
+                if let Ok(octets) = <[u8; 4]>::try_from(ip) {
+                    list.push(std::net::Ipv4Addr::from(octets).to_string());
+                } else if let Ok(octets) = <[u8; 16]>::try_from(ip) {
+                    list.push(std::net::Ipv6Addr::from(octets).to_string());
+                }
+            }
+        }
+
+        list
+    }
+
     pub fn subject_name(&self) -> Result<String, Error> {
         Ok(x509name_to_string(self.x509.subject_name())?)
     }
@@ -64,4 +100,19 @@ impl CertInfo {
     pub fn not_after(&self) -> &openssl::asn1::Asn1TimeRef {
         self.x509.not_after()
     }
+
+    /// The start of the validity period as epoch.
+    pub fn not_before_unix(&self) -> Result<i64, Error> {
+        asn1_time_to_unix(self.not_before())
+    }
+
+    /// The end of the validity period as epoch.
+    pub fn not_after_unix(&self) -> Result<i64, Error> {
+        asn1_time_to_unix(self.not_after())
+    }
+
+    /// Check whether the certificate expires before `epoch`.
+    pub fn is_expired_after_epoch(&self, epoch: i64) -> Result<bool, Error> {
+        Ok(self.not_after_unix()? < epoch)
+    }
 }
@@ -998,3 +998,57 @@ pub fn get_fs_uuid(disk: &Disk) -> Result<String, Error> {
 
     bail!("get_fs_uuid failed - missing UUID");
 }
+
+/// Returns the `/dev/disk/by-uuid/` node for a file system UUID.
+pub fn fs_uuid_device_path(uuid: &str) -> PathBuf {
+    PathBuf::from(format!("/dev/disk/by-uuid/{}", uuid))
+}
+
+/// Check whether the file system with UUID `uuid` is mounted at `path`.
+///
+/// Returns `false` if no device with that file system is currently present.
+pub fn is_fs_uuid_mounted_at(uuid: &str, path: &Path) -> Result<bool, Error> {
+    let meta = match std::fs::metadata(fs_uuid_device_path(uuid)) {
+        Ok(meta) => meta,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err.into()),
+    };
+    let device = Device::from_dev_t(meta.rdev());
+
+    for (_id, entry) in &MountInfo::read()? {
+        if entry.device == device && entry.mount_point == path {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+/// Mount the file system with UUID `uuid` at `path`, creating the mount point if needed.
+pub fn mount_by_fs_uuid(uuid: &str, path: &Path) -> Result<(), Error> {
+    let device_path = fs_uuid_device_path(uuid);
+    if !device_path.exists() {
+        bail!("no device with file system UUID '{}' found", uuid);
+    }
+
+    std::fs::create_dir_all(path)
+        .map_err(|err| format_err!("unable to create mount point {:?} - {}", path, err))?;
+
+    let mut command = std::process::Command::new("mount");
+    command.arg(&device_path);
+    command.arg(path);
+
+    crate::tools::run_command(command, None)?;
+
+    Ok(())
+}
+
+/// Unmount the file system mounted at `path`.
+pub fn unmount_path(path: &Path) -> Result<(), Error> {
+    let mut command = std::process::Command::new("umount");
+    command.arg(path);
+
+    crate::tools::run_command(command, None)?;
+
+    Ok(())
+}
@@ -13,6 +13,8 @@ use futures::*;
 
 use crate::tools::{
     async_io::EitherStream,
+    RateLimitedStream,
+    SharedRateLimiter,
     socket::{
         set_tcp_keepalive,
         PROXMOX_BACKUP_TCP_KEEPALIVE_TIME,
@@ -83,10 +85,19 @@ pub async fn post(
         .await
 }
 
+/// Send an arbitrary request, without checking the response status.
+pub async fn request(request: Request<Body>) -> Result<Response<Body>, Error> {
+    HTTP_CLIENT.request(request)
+        .map_err(Error::from)
+        .await
+}
+
 #[derive(Clone)]
 pub struct HttpsConnector {
     http: HttpConnector,
     ssl_connector: std::sync::Arc<SslConnector>,
+    read_limiter: Option<SharedRateLimiter>,
+    write_limiter: Option<SharedRateLimiter>,
 }
 
 impl HttpsConnector {
@@ -96,13 +107,25 @@ impl HttpsConnector {
         Self {
             http,
             ssl_connector: std::sync::Arc::new(ssl_connector),
+            read_limiter: None,
+            write_limiter: None,
         }
     }
+
+    /// Limit the incoming traffic of all connections created by this connector
+    pub fn set_read_limiter(&mut self, limiter: Option<SharedRateLimiter>) {
+        self.read_limiter = limiter;
+    }
+
+    /// Limit the outgoing traffic of all connections created by this connector
+    pub fn set_write_limiter(&mut self, limiter: Option<SharedRateLimiter>) {
+        self.write_limiter = limiter;
+    }
 }
 
 type MaybeTlsStream = EitherStream<
-    tokio::net::TcpStream,
-    Pin<Box<tokio_openssl::SslStream<tokio::net::TcpStream>>>,
+    RateLimitedStream<tokio::net::TcpStream>,
+    Pin<Box<tokio_openssl::SslStream<RateLimitedStream<tokio::net::TcpStream>>>>,
 >;
 
 impl hyper::service::Service<Uri> for HttpsConnector {
@@ -139,8 +162,14 @@ impl hyper::service::Service<Uri> for HttpsConnector {
 
             let _ = set_tcp_keepalive(conn.as_raw_fd(), PROXMOX_BACKUP_TCP_KEEPALIVE_TIME);
 
+            let conn = RateLimitedStream::with_limiter(
+                conn,
+                this.read_limiter.clone(),
+                this.write_limiter.clone(),
+            );
+
             if is_https {
-                let conn: tokio_openssl::SslStream<tokio::net::TcpStream> = tokio_openssl::SslStream::new(config?.into_ssl(&host)?, conn)?;
+                let conn: tokio_openssl::SslStream<RateLimitedStream<tokio::net::TcpStream>> = tokio_openssl::SslStream::new(config?.into_ssl(&host)?, conn)?;
                 let mut conn = Box::pin(conn);
                 conn.as_mut().connect().await?;
                 Ok(MaybeTlsStream::Right(conn))
@@ -0,0 +1,964 @@
+//! Minimal LDAP client
+//!
+//! Implements the parts of LDAPv3 (RFC 4511) needed to authenticate users and
+//! to read users and groups from a directory server: simple bind, search with
+//! paged results (as required by Active Directory for large results) and
+//! STARTTLS. Messages are BER encoded (X.690), filters use the string
+//! representation of RFC 4515.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use openssl::hash::MessageDigest;
+use openssl::ssl::{SslConnector, SslMethod, SslStream, SslVerifyMode};
+
+const LDAP_VERSION: i64 = 3;
+const STARTTLS_OID: &str = "1.3.6.1.4.1.1466.20037";
+const PAGED_RESULTS_OID: &str = "1.2.840.113556.1.4.319";
+const PAGE_SIZE: i64 = 500;
+
+/// Upper limit for the size of a single received message
+const MAX_MESSAGE_SIZE: usize = 16*1024*1024;
+
+const TAG_BOOLEAN: u8 = 0x01;
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_ENUMERATED: u8 = 0x0a;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+
+const OP_BIND_REQUEST: u8 = 0x60;
+const OP_BIND_RESPONSE: u8 = 0x61;
+const OP_UNBIND_REQUEST: u8 = 0x42;
+const OP_SEARCH_REQUEST: u8 = 0x63;
+const OP_SEARCH_RESULT_ENTRY: u8 = 0x64;
+const OP_SEARCH_RESULT_DONE: u8 = 0x65;
+const OP_SEARCH_RESULT_REFERENCE: u8 = 0x73;
+const OP_EXTENDED_REQUEST: u8 = 0x77;
+const OP_EXTENDED_RESPONSE: u8 = 0x78;
+
+const CONTROLS_TAG: u8 = 0xa0;
+const SIMPLE_AUTH_TAG: u8 = 0x80;
+const EXTENDED_REQUEST_NAME_TAG: u8 = 0x80;
+
+const RESULT_SUCCESS: i64 = 0;
+const RESULT_INVALID_CREDENTIALS: i64 = 49;
+
+/// How to secure the connection to the LDAP server
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LdapConnectionMode {
+    /// Plain LDAP (port 389)
+    Ldap,
+    /// Plain LDAP upgraded to TLS with STARTTLS (port 389)
+    StartTls,
+    /// LDAP over TLS (port 636)
+    Ldaps,
+}
+
+/// Connection parameters
+pub struct LdapConfig {
+    /// Servers to try, in order
+    pub servers: Vec<String>,
+    pub port: Option<u16>,
+    pub mode: LdapConnectionMode,
+    /// Verify the server certificate (ignored with a fingerprint)
+    pub verify: bool,
+    /// Expected certificate fingerprint, for self-signed certificates
+    pub fingerprint: Option<String>,
+    pub timeout: Duration,
+}
+
+/// Search scope, relative to the base DN
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchScope {
+    Base = 0,
+    OneLevel = 1,
+    Subtree = 2,
+}
+
+/// Search result
+#[derive(Debug, PartialEq)]
+pub struct LdapEntry {
+    pub dn: String,
+    /// Attribute values, by (lower case) attribute name
+    pub attributes: HashMap<String, Vec<String>>,
+}
+
+impl LdapEntry {
+    /// Returns the first value of an attribute (the name is case insensitive).
+    pub fn first_value(&self, attribute: &str) -> Option<&str> {
+        self.attributes
+            .get(&attribute.to_lowercase())
+            .and_then(|values| values.first())
+            .map(String::as_str)
+    }
+
+    /// Returns all values of an attribute (the name is case insensitive).
+    pub fn values(&self, attribute: &str) -> &[String] {
+        self.attributes
+            .get(&attribute.to_lowercase())
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+}
+
+enum LdapStream {
+    Plain(TcpStream),
+    Tls(SslStream<TcpStream>),
+}
+
+impl Read for LdapStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            LdapStream::Plain(stream) => stream.read(buf),
+            LdapStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for LdapStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            LdapStream::Plain(stream) => stream.write(buf),
+            LdapStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            LdapStream::Plain(stream) => stream.flush(),
+            LdapStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+/// Connection to an LDAP server
+pub struct LdapConnection {
+    stream: LdapStream,
+    last_message_id: i64,
+}
+
+impl LdapConnection {
+    /// Connect to the first reachable server of `config`.
+    pub fn connect(config: &LdapConfig) -> Result<Self, Error> {
+        let mut errors = Vec::new();
+        for server in &config.servers {
+            match Self::connect_to(server, config) {
+                Ok(conn) => return Ok(conn),
+                Err(err) => errors.push(format!("{} - {}", server, err)),
+            }
+        }
+        if errors.is_empty() {
+            bail!("no LDAP server configured");
+        }
+        bail!("unable to connect to LDAP server: {}", errors.join(", "));
+    }
+
+    fn connect_to(server: &str, config: &LdapConfig) -> Result<Self, Error> {
+        let port = config.port.unwrap_or(match config.mode {
+            LdapConnectionMode::Ldaps => 636,
+            _ => 389,
+        });
+
+        let mut last_err = None;
+        let mut tcp = None;
+        for addr in (server, port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, config.timeout) {
+                Ok(stream) => {
+                    tcp = Some(stream);
+                    break;
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        let tcp = match (tcp, last_err) {
+            (Some(tcp), _) => tcp,
+            (None, Some(err)) => return Err(err.into()),
+            (None, None) => bail!("unable to resolve host"),
+        };
+        tcp.set_read_timeout(Some(config.timeout))?;
+        tcp.set_write_timeout(Some(config.timeout))?;
+        tcp.set_nodelay(true)?;
+
+        let mut conn = Self {
+            stream: LdapStream::Plain(tcp),
+            last_message_id: 0,
+        };
+
+        match config.mode {
+            LdapConnectionMode::Ldap => {}
+            LdapConnectionMode::Ldaps => conn.start_tls(server, config)?,
+            LdapConnectionMode::StartTls => {
+                let request = ber_sequence(OP_EXTENDED_REQUEST, &[
+                    ber_string(EXTENDED_REQUEST_NAME_TAG, STARTTLS_OID.as_bytes()),
+                ]);
+                let id = conn.send_request(request, None)?;
+                let (op, _controls) = conn.receive_response(id)?;
+                check_result(op.expect(OP_EXTENDED_RESPONSE)?, "STARTTLS")?;
+                conn.start_tls(server, config)?;
+            }
+        }
+
+        Ok(conn)
+    }
+
+    fn start_tls(&mut self, server: &str, config: &LdapConfig) -> Result<(), Error> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+
+        if let Some(expected) = config.fingerprint.clone() {
+            let expected = expected.to_lowercase();
+            builder.set_verify_callback(SslVerifyMode::PEER, move |valid, ctx| {
+                if valid {
+                    return true;
+                }
+                if ctx.error_depth() != 0 {
+                    return false;
+                }
+                match ctx.current_cert().map(|cert| cert.digest(MessageDigest::sha256())) {
+                    Some(Ok(fp)) => fingerprint_string(&fp) == expected,
+                    _ => false,
+                }
+            });
+        } else if !config.verify {
+            builder.set_verify(SslVerifyMode::NONE);
+        }
+
+        let connector = builder.build();
+
+        let tcp = match &self.stream {
+            LdapStream::Plain(tcp) => tcp.try_clone()?,
+            LdapStream::Tls(_) => bail!("TLS already active"),
+        };
+
+        let stream = connector.connect(server, tcp)
+            .map_err(|err| format_err!("TLS handshake failed - {}", err))?;
+        self.stream = LdapStream::Tls(stream);
+
+        Ok(())
+    }
+
+    /// Authenticate with a DN and password.
+    ///
+    /// Empty passwords are rejected, as most servers treat them as an
+    /// unauthenticated bind which always succeeds (RFC 4513, section 5.1.2).
+    pub fn simple_bind(&mut self, dn: &str, password: &str) -> Result<(), Error> {
+        if password.is_empty() {
+            bail!("empty password is not allowed");
+        }
+
+        let request = ber_sequence(OP_BIND_REQUEST, &[
+            ber_integer(TAG_INTEGER, LDAP_VERSION),
+            ber_string(TAG_OCTET_STRING, dn.as_bytes()),
+            ber_string(SIMPLE_AUTH_TAG, password.as_bytes()),
+        ]);
+        let id = self.send_request(request, None)?;
+        let (op, _controls) = self.receive_response(id)?;
+
+        let (code, message) = parse_result(op.expect(OP_BIND_RESPONSE)?)?;
+        match code {
+            RESULT_SUCCESS => Ok(()),
+            RESULT_INVALID_CREDENTIALS => bail!("invalid credentials"),
+            _ => bail!("bind failed - {}", result_message(code, &message)),
+        }
+    }
+
+    /// Search for entries below `base_dn` matching `filter`.
+    ///
+    /// Only the listed attributes are returned, all of them if the list is empty.
+    pub fn search(
+        &mut self,
+        base_dn: &str,
+        scope: SearchScope,
+        filter: &str,
+        attributes: &[&str],
+    ) -> Result<Vec<LdapEntry>, Error> {
+        let filter = encode_filter(filter)?;
+        let attributes: Vec<Vec<u8>> = attributes
+            .iter()
+            .map(|attr| ber_string(TAG_OCTET_STRING, attr.as_bytes()))
+            .collect();
+
+        let mut entries = Vec::new();
+        let mut cookie = Vec::new();
+
+        loop {
+            let request = ber_sequence(OP_SEARCH_REQUEST, &[
+                ber_string(TAG_OCTET_STRING, base_dn.as_bytes()),
+                ber_integer(TAG_ENUMERATED, scope as i64),
+                ber_integer(TAG_ENUMERATED, 0), // never dereference aliases
+                ber_integer(TAG_INTEGER, 0), // no size limit
+                ber_integer(TAG_INTEGER, 0), // no time limit
+                ber_bool(false),
+                filter.clone(),
+                ber_sequence(TAG_SEQUENCE, &attributes),
+            ]);
+
+            let paged_results = ber_sequence(TAG_SEQUENCE, &[
+                ber_string(TAG_OCTET_STRING, PAGED_RESULTS_OID.as_bytes()),
+                ber_bool(false),
+                ber_string(TAG_OCTET_STRING, &ber_sequence(TAG_SEQUENCE, &[
+                    ber_integer(TAG_INTEGER, PAGE_SIZE),
+                    ber_string(TAG_OCTET_STRING, &cookie),
+                ])),
+            ]);
+
+            let id = self.send_request(request, Some(paged_results))?;
+
+            let controls = loop {
+                let (op, controls) = self.receive_response(id)?;
+                match op.tag {
+                    OP_SEARCH_RESULT_ENTRY => entries.push(parse_entry(&op)?),
+                    OP_SEARCH_RESULT_REFERENCE => {} // referrals are not followed
+                    OP_SEARCH_RESULT_DONE => {
+                        let (code, message) = parse_result(&op)?;
+                        if code != RESULT_SUCCESS {
+                            bail!("search failed - {}", result_message(code, &message));
+                        }
+                        break controls;
+                    }
+                    tag => bail!("unexpected response (tag {:#x}) to search request", tag),
+                }
+            };
+
+            cookie = match controls {
+                Some(controls) => parse_paged_results_cookie(&controls)?,
+                None => Vec::new(), // server does not support paging
+            };
+            if cookie.is_empty() {
+                break;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn send_request(&mut self, op: Vec<u8>, control: Option<Vec<u8>>) -> Result<i64, Error> {
+        self.last_message_id += 1;
+        let id = self.last_message_id;
+
+        let mut parts = vec![ber_integer(TAG_INTEGER, id), op];
+        if let Some(control) = control {
+            parts.push(ber_sequence(CONTROLS_TAG, &[control]));
+        }
+        let message = ber_sequence(TAG_SEQUENCE, &parts);
+
+        self.stream.write_all(&message)?;
+        self.stream.flush()?;
+
+        Ok(id)
+    }
+
+    // Returns the protocol operation and the (raw) controls of the response
+    fn receive_response(&mut self, id: i64) -> Result<(OwnedElement, Option<Vec<u8>>), Error> {
+        loop {
+            let message = read_element(&mut self.stream)?;
+            let message = message.expect(TAG_SEQUENCE)?;
+            let parts = parse_elements(&message.data)?;
+            if parts.len() < 2 {
+                bail!("invalid LDAP message");
+            }
+
+            let message_id = parts[0].expect(TAG_INTEGER)?.integer()?;
+            if message_id == 0 {
+                // unsolicited notification, e.g. notice of disconnection
+                let (_, message) = parse_result(&parts[1].to_owned())?;
+                bail!("server closed connection - {}", message);
+            }
+            if message_id != id {
+                continue; // response to an abandoned request
+            }
+
+            let controls = parts.get(2)
+                .filter(|part| part.tag == CONTROLS_TAG)
+                .map(|part| part.data.to_vec());
+
+            return Ok((parts[1].to_owned(), controls));
+        }
+    }
+}
+
+impl Drop for LdapConnection {
+    fn drop(&mut self) {
+        // be nice and tell the server, but ignore any errors
+        self.last_message_id += 1;
+        let message = ber_sequence(TAG_SEQUENCE, &[
+            ber_integer(TAG_INTEGER, self.last_message_id),
+            ber_string(OP_UNBIND_REQUEST, &[]),
+        ]);
+        let _ = self.stream.write_all(&message);
+    }
+}
+
+fn fingerprint_string(digest: &[u8]) -> String {
+    digest
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+fn result_message(code: i64, message: &str) -> String {
+    if message.is_empty() {
+        format!("error code {}", code)
+    } else {
+        format!("{} (error code {})", message, code)
+    }
+}
+
+/// Escape special characters of a value used in a search filter (RFC 4515).
+pub fn escape_filter_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '*' => escaped.push_str("\\2a"),
+            '(' => escaped.push_str("\\28"),
+            ')' => escaped.push_str("\\29"),
+            '\\' => escaped.push_str("\\5c"),
+            '\0' => escaped.push_str("\\00"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// BER encoding
+
+fn encode_length(len: usize, out: &mut Vec<u8>) {
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (bytes.len() - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+}
+
+fn ber_string(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(content.len() + 6);
+    out.push(tag);
+    encode_length(content.len(), &mut out);
+    out.extend_from_slice(content);
+    out
+}
+
+fn ber_sequence(tag: u8, parts: &[Vec<u8>]) -> Vec<u8> {
+    ber_string(tag, &parts.concat())
+}
+
+fn ber_integer(tag: u8, value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    // minimal two's complement representation
+    let mut start = 0;
+    while start < bytes.len() - 1 {
+        let redundant = (bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0);
+        if !redundant {
+            break;
+        }
+        start += 1;
+    }
+    ber_string(tag, &bytes[start..])
+}
+
+fn ber_bool(value: bool) -> Vec<u8> {
+    ber_string(TAG_BOOLEAN, &[if value { 0xff } else { 0x00 }])
+}
+
+// BER decoding
+
+struct Element<'a> {
+    tag: u8,
+    data: &'a [u8],
+}
+
+struct OwnedElement {
+    tag: u8,
+    data: Vec<u8>,
+}
+
+impl<'a> Element<'a> {
+    fn expect(&self, tag: u8) -> Result<&Self, Error> {
+        if self.tag != tag {
+            bail!("unexpected BER tag {:#x} (expected {:#x})", self.tag, tag);
+        }
+        Ok(self)
+    }
+
+    fn integer(&self) -> Result<i64, Error> {
+        if self.data.is_empty() || self.data.len() > 8 {
+            bail!("invalid BER integer");
+        }
+        let mut value: i64 = if self.data[0] & 0x80 != 0 { -1 } else { 0 };
+        for byte in self.data {
+            value = (value << 8) | (*byte as i64);
+        }
+        Ok(value)
+    }
+
+    fn string(&self) -> String {
+        String::from_utf8_lossy(self.data).into_owned()
+    }
+
+    fn to_owned(&self) -> OwnedElement {
+        OwnedElement { tag: self.tag, data: self.data.to_vec() }
+    }
+}
+
+impl OwnedElement {
+    fn expect(&self, tag: u8) -> Result<&Self, Error> {
+        if self.tag != tag {
+            bail!("unexpected BER tag {:#x} (expected {:#x})", self.tag, tag);
+        }
+        Ok(self)
+    }
+}
+
+fn parse_length(data: &[u8]) -> Result<(usize, usize), Error> {
+    let first = *data.get(0).ok_or_else(|| format_err!("truncated BER length"))?;
+    if first < 0x80 {
+        return Ok((first as usize, 1));
+    }
+    let count = (first & 0x7f) as usize;
+    if count == 0 || count > 4 {
+        bail!("unsupported BER length encoding");
+    }
+    if data.len() < count + 1 {
+        bail!("truncated BER length");
+    }
+    let len = data[1..=count].iter().fold(0usize, |len, byte| (len << 8) | *byte as usize);
+    Ok((len, count + 1))
+}
+
+fn parse_element(data: &[u8]) -> Result<(Element, &[u8]), Error> {
+    let tag = *data.get(0).ok_or_else(|| format_err!("truncated BER element"))?;
+    if tag & 0x1f == 0x1f {
+        bail!("unsupported BER tag {:#x}", tag);
+    }
+    let (len, len_size) = parse_length(&data[1..])?;
+    let start = 1 + len_size;
+    if data.len() < start + len {
+        bail!("truncated BER element");
+    }
+    Ok((Element { tag, data: &data[start..start + len] }, &data[start + len..]))
+}
+
+fn parse_elements(mut data: &[u8]) -> Result<Vec<Element>, Error> {
+    let mut list = Vec::new();
+    while !data.is_empty() {
+        let (element, rest) = parse_element(data)?;
+        list.push(element);
+        data = rest;
+    }
+    Ok(list)
+}
+
+fn read_element<R: Read>(reader: &mut R) -> Result<OwnedElement, Error> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+
+    let tag = header[0];
+    let len = if header[1] < 0x80 {
+        header[1] as usize
+    } else {
+        let count = (header[1] & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            bail!("unsupported BER length encoding");
+        }
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes[4 - count..])?;
+        u32::from_be_bytes(bytes) as usize
+    };
+    if len > MAX_MESSAGE_SIZE {
+        bail!("LDAP message too large ({} bytes)", len);
+    }
+
+    let mut data = vec![0u8; len];
+    reader.read_exact(&mut data)?;
+
+    Ok(OwnedElement { tag, data })
+}
+
+// Returns result code and diagnostic message of an LDAPResult
+fn parse_result(op: &OwnedElement) -> Result<(i64, String), Error> {
+    let parts = parse_elements(&op.data)?;
+    if parts.len() < 3 {
+        bail!("invalid LDAP result");
+    }
+    let code = parts[0].expect(TAG_ENUMERATED)?.integer()?;
+    let message = parts[2].expect(TAG_OCTET_STRING)?.string();
+    Ok((code, message))
+}
+
+fn check_result(op: &OwnedElement, what: &str) -> Result<(), Error> {
+    let (code, message) = parse_result(op)?;
+    if code != RESULT_SUCCESS {
+        bail!("{} failed - {}", what, result_message(code, &message));
+    }
+    Ok(())
+}
+
+fn parse_entry(op: &OwnedElement) -> Result<LdapEntry, Error> {
+    let parts = parse_elements(&op.data)?;
+    if parts.len() != 2 {
+        bail!("invalid search result entry");
+    }
+    let dn = parts[0].expect(TAG_OCTET_STRING)?.string();
+
+    let mut attributes = HashMap::new();
+    for attribute in parse_elements(parts[1].expect(TAG_SEQUENCE)?.data)? {
+        let attribute = parse_elements(attribute.expect(TAG_SEQUENCE)?.data)?;
+        if attribute.len() != 2 {
+            bail!("invalid attribute in search result entry");
+        }
+        let name = attribute[0].expect(TAG_OCTET_STRING)?.string().to_lowercase();
+        let values = parse_elements(attribute[1].expect(TAG_SET)?.data)?
+            .iter()
+            .map(Element::string)
+            .collect();
+        attributes.insert(name, values);
+    }
+
+    Ok(LdapEntry { dn, attributes })
+}
+
+fn parse_paged_results_cookie(controls: &[u8]) -> Result<Vec<u8>, Error> {
+    for control in parse_elements(controls)? {
+        let parts = parse_elements(control.expect(TAG_SEQUENCE)?.data)?;
+        let oid = match parts.get(0) {
+            Some(oid) => oid.expect(TAG_OCTET_STRING)?.string(),
+            None => continue,
+        };
+        if oid != PAGED_RESULTS_OID {
+            continue;
+        }
+        // the value follows the optional criticality flag
+        let value = match parts.iter().skip(1).find(|part| part.tag == TAG_OCTET_STRING) {
+            Some(value) => value,
+            None => bail!("paged results control without value"),
+        };
+        let (value, _) = parse_element(value.data)?;
+        let fields = parse_elements(value.expect(TAG_SEQUENCE)?.data)?;
+        if fields.len() != 2 {
+            bail!("invalid paged results control");
+        }
+        return Ok(fields[1].expect(TAG_OCTET_STRING)?.data.to_vec());
+    }
+    Ok(Vec::new())
+}
+
+// Search filters (RFC 4515)
+
+const FILTER_AND: u8 = 0xa0;
+const FILTER_OR: u8 = 0xa1;
+const FILTER_NOT: u8 = 0xa2;
+const FILTER_EQUALITY: u8 = 0xa3;
+const FILTER_SUBSTRINGS: u8 = 0xa4;
+const FILTER_GREATER_OR_EQUAL: u8 = 0xa5;
+const FILTER_LESS_OR_EQUAL: u8 = 0xa6;
+const FILTER_PRESENT: u8 = 0x87;
+const FILTER_APPROX: u8 = 0xa8;
+
+/// Encode a search filter like `(&(objectClass=person)(uid=j*))`.
+///
+/// Extensible match filters are not supported.
+pub fn encode_filter(filter: &str) -> Result<Vec<u8>, Error> {
+    let filter = filter.trim();
+    let (encoded, rest) = parse_filter(filter.as_bytes())
+        .map_err(|err| format_err!("invalid LDAP filter '{}' - {}", filter, err))?;
+    if !rest.is_empty() {
+        bail!("invalid LDAP filter '{}' - trailing characters", filter);
+    }
+    Ok(encoded)
+}
+
+fn parse_filter(input: &[u8]) -> Result<(Vec<u8>, &[u8]), Error> {
+    if input.first() != Some(&b'(') {
+        bail!("expected '('");
+    }
+    let input = &input[1..];
+
+    let (encoded, rest) = match input.first() {
+        Some(b'&') => parse_filter_list(FILTER_AND, &input[1..])?,
+        Some(b'|') => parse_filter_list(FILTER_OR, &input[1..])?,
+        Some(b'!') => {
+            let (inner, rest) = parse_filter(&input[1..])?;
+            (ber_string(FILTER_NOT, &inner), rest)
+        }
+        _ => {
+            let end = input.iter().position(|c| *c == b')')
+                .ok_or_else(|| format_err!("missing ')'"))?;
+            (parse_filter_item(&input[..end])?, &input[end..])
+        }
+    };
+
+    if rest.first() != Some(&b')') {
+        bail!("expected ')'");
+    }
+    Ok((encoded, &rest[1..]))
+}
+
+fn parse_filter_list(tag: u8, mut input: &[u8]) -> Result<(Vec<u8>, &[u8]), Error> {
+    let mut list = Vec::new();
+    while input.first() == Some(&b'(') {
+        let (encoded, rest) = parse_filter(input)?;
+        list.push(encoded);
+        input = rest;
+    }
+    if list.is_empty() {
+        bail!("empty filter list");
+    }
+    Ok((ber_sequence(tag, &list), input))
+}
+
+fn parse_filter_item(item: &[u8]) -> Result<Vec<u8>, Error> {
+    let pos = item.iter().position(|c| *c == b'=')
+        .ok_or_else(|| format_err!("missing '=' in filter item"))?;
+
+    let (attribute, tag) = match pos.checked_sub(1).map(|i| item[i]) {
+        Some(b'~') => (&item[..pos - 1], FILTER_APPROX),
+        Some(b'>') => (&item[..pos - 1], FILTER_GREATER_OR_EQUAL),
+        Some(b'<') => (&item[..pos - 1], FILTER_LESS_OR_EQUAL),
+        _ => (&item[..pos], FILTER_EQUALITY),
+    };
+    let value = &item[pos + 1..];
+
+    if attribute.is_empty() {
+        bail!("missing attribute name");
+    }
+    if attribute.contains(&b':') {
+        bail!("extensible match filters are not supported");
+    }
+
+    if tag != FILTER_EQUALITY {
+        return Ok(ber_sequence(tag, &[
+            ber_string(TAG_OCTET_STRING, attribute),
+            ber_string(TAG_OCTET_STRING, &unescape_filter_value(value)?),
+        ]));
+    }
+
+    if value == b"*" {
+        return Ok(ber_string(FILTER_PRESENT, attribute));
+    }
+
+    if !value.contains(&b'*') {
+        return Ok(ber_sequence(tag, &[
+            ber_string(TAG_OCTET_STRING, attribute),
+            ber_string(TAG_OCTET_STRING, &unescape_filter_value(value)?),
+        ]));
+    }
+
+    // escaped asterisks are '\2a', so every '*' is a wildcard
+    let parts: Vec<&[u8]> = value.split(|c| *c == b'*').collect();
+    let last = parts.len() - 1;
+    let mut substrings = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        if part.is_empty() {
+            continue;
+        }
+        let tag = match i {
+            0 => 0x80, // initial
+            i if i == last => 0x82, // final
+            _ => 0x81, // any
+        };
+        substrings.push(ber_string(tag, &unescape_filter_value(part)?));
+    }
+
+    Ok(ber_sequence(FILTER_SUBSTRINGS, &[
+        ber_string(TAG_OCTET_STRING, attribute),
+        ber_sequence(TAG_SEQUENCE, &substrings),
+    ]))
+}
+
+fn unescape_filter_value(value: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut out = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        match value[i] {
+            b'\\' => {
+                let hex = value.get(i + 1..i + 3)
+                    .and_then(|hex| std::str::from_utf8(hex).ok())
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| format_err!("invalid escape sequence in filter value"))?;
+                out.push(hex);
+                i += 3;
+            }
+            b'(' | b')' | b'*' => bail!("unescaped special character in filter value"),
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_ber_integer() {
+        assert_eq!(ber_integer(TAG_INTEGER, 0), vec![0x02, 0x01, 0x00]);
+        assert_eq!(ber_integer(TAG_INTEGER, 127), vec![0x02, 0x01, 0x7f]);
+        assert_eq!(ber_integer(TAG_INTEGER, 128), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(ber_integer(TAG_INTEGER, 500), vec![0x02, 0x02, 0x01, 0xf4]);
+        assert_eq!(ber_integer(TAG_INTEGER, -1), vec![0x02, 0x01, 0xff]);
+
+        for value in &[0, 1, 127, 128, 255, 256, 65535, -1, -128, -129, i64::MAX, i64::MIN] {
+            let encoded = ber_integer(TAG_INTEGER, *value);
+            let (element, _) = parse_element(&encoded).unwrap();
+            assert_eq!(element.integer().unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn test_ber_length() {
+        let data = vec![0u8; 300];
+        let encoded = ber_string(TAG_OCTET_STRING, &data);
+        assert_eq!(&encoded[..4], &[0x04, 0x82, 0x01, 0x2c]);
+        let (element, rest) = parse_element(&encoded).unwrap();
+        assert_eq!(element.data.len(), 300);
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        assert_eq!(
+            encode_filter("(cn=abc)").unwrap(),
+            vec![0xa3, 0x09, 0x04, 0x02, b'c', b'n', 0x04, 0x03, b'a', b'b', b'c'],
+        );
+        assert_eq!(
+            encode_filter("(uid=*)").unwrap(),
+            vec![0x87, 0x03, b'u', b'i', b'd'],
+        );
+        assert_eq!(
+            encode_filter("(cn=a*b*)").unwrap(),
+            vec![0xa4, 0x0c, 0x04, 0x02, b'c', b'n', 0x30, 0x06, 0x80, 0x01, b'a', 0x81, 0x01, b'b'],
+        );
+        assert_eq!(
+            encode_filter("(cn=a\\2a)").unwrap(),
+            vec![0xa3, 0x08, 0x04, 0x02, b'c', b'n', 0x04, 0x02, b'a', b'*'],
+        );
+
+        assert!(encode_filter("(&(objectClass=person)(|(uid=a)(uid=b))(!(cn=x)))").is_ok());
+        assert!(encode_filter("(createTimestamp>=20200101000000Z)").is_ok());
+
+        assert!(encode_filter("cn=abc").is_err());
+        assert!(encode_filter("(cn=abc").is_err());
+        assert!(encode_filter("(cn=abc))").is_err());
+        assert!(encode_filter("(&)").is_err());
+        assert!(encode_filter("(cn:dn:=abc)").is_err());
+        assert!(encode_filter("(cn=a\\zz)").is_err());
+    }
+
+    #[test]
+    fn test_escape_filter_value() {
+        let value = "a*(b)\\c";
+        let escaped = escape_filter_value(value);
+        assert_eq!(escaped, "a\\2a\\28b\\29\\5cc");
+        assert_eq!(unescape_filter_value(escaped.as_bytes()).unwrap(), value.as_bytes());
+    }
+
+    // Answers bind and search requests like a directory with a single user.
+    fn run_stand_in_server(listener: TcpListener) {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let respond = |stream: &mut TcpStream, id: i64, op: Vec<u8>| {
+            let message = ber_sequence(TAG_SEQUENCE, &[ber_integer(TAG_INTEGER, id), op]);
+            stream.write_all(&message).unwrap();
+        };
+        let result = |tag: u8, code: i64| {
+            ber_sequence(tag, &[
+                ber_integer(TAG_ENUMERATED, code),
+                ber_string(TAG_OCTET_STRING, b""),
+                ber_string(TAG_OCTET_STRING, b""),
+            ])
+        };
+
+        loop {
+            let message = match read_element(&mut stream) {
+                Ok(message) => message,
+                Err(_) => return, // client closed the connection
+            };
+            let parts = parse_elements(&message.data).unwrap();
+            let id = parts[0].integer().unwrap();
+            let op = &parts[1];
+            match op.tag {
+                OP_BIND_REQUEST => {
+                    let fields = parse_elements(op.data).unwrap();
+                    let dn = fields[1].string();
+                    let password = fields[2].string();
+                    let code = if dn == "uid=john,ou=people,dc=example,dc=com" && password == "secret" {
+                        RESULT_SUCCESS
+                    } else {
+                        RESULT_INVALID_CREDENTIALS
+                    };
+                    respond(&mut stream, id, result(OP_BIND_RESPONSE, code));
+                }
+                OP_SEARCH_REQUEST => {
+                    let fields = parse_elements(op.data).unwrap();
+                    assert_eq!(fields[0].string(), "dc=example,dc=com");
+                    let entry = ber_sequence(OP_SEARCH_RESULT_ENTRY, &[
+                        ber_string(TAG_OCTET_STRING, b"uid=john,ou=people,dc=example,dc=com"),
+                        ber_sequence(TAG_SEQUENCE, &[
+                            ber_sequence(TAG_SEQUENCE, &[
+                                ber_string(TAG_OCTET_STRING, b"uid"),
+                                ber_sequence(TAG_SET, &[ber_string(TAG_OCTET_STRING, b"john")]),
+                            ]),
+                            ber_sequence(TAG_SEQUENCE, &[
+                                ber_string(TAG_OCTET_STRING, b"mail"),
+                                ber_sequence(TAG_SET, &[ber_string(TAG_OCTET_STRING, b"john@example.com")]),
+                            ]),
+                        ]),
+                    ]);
+                    respond(&mut stream, id, entry);
+                    respond(&mut stream, id, result(OP_SEARCH_RESULT_DONE, RESULT_SUCCESS));
+                }
+                OP_UNBIND_REQUEST => return,
+                tag => panic!("unexpected request {:#x}", tag),
+            }
+        }
+    }
+
+    #[test]
+    fn test_stand_in_server() -> Result<(), Error> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let server = std::thread::spawn(move || run_stand_in_server(listener));
+
+        let config = LdapConfig {
+            servers: vec!["127.0.0.1".to_string()],
+            port: Some(port),
+            mode: LdapConnectionMode::Ldap,
+            verify: true,
+            fingerprint: None,
+            timeout: Duration::from_secs(5),
+        };
+
+        {
+            let mut conn = LdapConnection::connect(&config)?;
+
+            assert!(conn.simple_bind("uid=john,ou=people,dc=example,dc=com", "").is_err());
+            assert!(conn.simple_bind("uid=john,ou=people,dc=example,dc=com", "wrong").is_err());
+
+            let entries = conn.search("dc=example,dc=com", SearchScope::Subtree, "(uid=john)", &["uid", "mail"])?;
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].dn, "uid=john,ou=people,dc=example,dc=com");
+            assert_eq!(entries[0].first_value("UID"), Some("john"));
+            assert_eq!(entries[0].values("mail"), &["john@example.com".to_string()]);
+            assert!(entries[0].values("cn").is_empty());
+
+            conn.simple_bind(&entries[0].dn, "secret")?;
+        }
+
+        server.join().unwrap();
+
+        Ok(())
+    }
+}
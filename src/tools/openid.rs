@@ -0,0 +1,457 @@
+//! Minimal OpenID Connect client
+//!
+//! Implements the authorization code flow (with PKCE) of OpenID Connect Core
+//! 1.0 for a confidential or public client: provider discovery, building the
+//! authorization URL, exchanging the code for tokens and validating the ID
+//! token (RS256/384/512 and ES256/384 signatures, keys from the JWKS of the
+//! provider).
+
+use anyhow::{bail, format_err, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+
+/// Accepted clock difference to the provider (seconds)
+const CLOCK_SKEW: i64 = 60;
+
+/// Client configuration
+pub struct OpenIdConfig {
+    pub issuer_url: String,
+    pub client_id: String,
+    /// Client secret, not used by public clients
+    pub client_key: Option<String>,
+    /// Additional scopes, "openid" is always requested
+    pub scopes: Vec<String>,
+}
+
+/// The parts of the provider metadata we need (OpenID Connect Discovery 1.0)
+#[derive(Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub jwks_uri: String,
+}
+
+/// State of a login, which must be kept on the server between creating the
+/// authorization URL and verifying the authorization code.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PrivateAuthState {
+    /// Passed as `state` parameter, protects against CSRF
+    pub csrf_token: String,
+    pub nonce: String,
+    pub pkce_verifier: String,
+    pub ctime: i64,
+}
+
+impl PrivateAuthState {
+    pub fn new() -> Result<Self, Error> {
+        Ok(Self {
+            csrf_token: random_token()?,
+            nonce: random_token()?,
+            pkce_verifier: random_token()?,
+            ctime: proxmox::tools::time::epoch_i64(),
+        })
+    }
+
+    fn pkce_challenge(&self) -> String {
+        base64url_encode(&openssl::sha::sha256(self.pkce_verifier.as_bytes()))
+    }
+}
+
+fn random_token() -> Result<String, Error> {
+    Ok(base64url_encode(&proxmox::sys::linux::random_data(32)?))
+}
+
+fn base64url_encode(data: &[u8]) -> String {
+    base64::encode_config(data, base64::URL_SAFE_NO_PAD)
+}
+
+fn base64url_decode(data: &str) -> Result<Vec<u8>, Error> {
+    base64::decode_config(data, base64::URL_SAFE_NO_PAD)
+        .map_err(|err| format_err!("invalid base64url encoding - {}", err))
+}
+
+/// OpenID Connect client for a single provider
+pub struct OpenIdAuthenticator {
+    config: OpenIdConfig,
+    metadata: ProviderMetadata,
+}
+
+impl OpenIdAuthenticator {
+
+    /// Fetch the provider metadata from the issuer.
+    pub async fn discover(config: OpenIdConfig) -> Result<Self, Error> {
+        let issuer = config.issuer_url.trim_end_matches('/');
+        let url = format!("{}/.well-known/openid-configuration", issuer);
+
+        let data = crate::tools::http::get_string(&url, None).await
+            .map_err(|err| format_err!("OpenID discovery at '{}' failed - {}", url, err))?;
+        let metadata: ProviderMetadata = serde_json::from_str(&data)
+            .map_err(|err| format_err!("invalid OpenID provider metadata - {}", err))?;
+
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            bail!("OpenID provider reports unexpected issuer '{}'", metadata.issuer);
+        }
+
+        Ok(Self { config, metadata })
+    }
+
+    /// The URL to redirect the user to for authentication.
+    pub fn authorize_url(&self, redirect_url: &str, state: &PrivateAuthState) -> Result<String, Error> {
+        let mut url = url::Url::parse(&self.metadata.authorization_endpoint)?;
+
+        let mut scopes = vec!["openid"];
+        scopes.extend(self.config.scopes.iter().map(String::as_str).filter(|s| *s != "openid"));
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("redirect_uri", redirect_url)
+            .append_pair("scope", &scopes.join(" "))
+            .append_pair("state", &state.csrf_token)
+            .append_pair("nonce", &state.nonce)
+            .append_pair("code_challenge", &state.pkce_challenge())
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into())
+    }
+
+    /// Exchange the authorization code for an ID token and return its
+    /// (verified) claims.
+    pub async fn verify_authorization_code(
+        &self,
+        code: &str,
+        redirect_url: &str,
+        state: &PrivateAuthState,
+    ) -> Result<Value, Error> {
+        let mut form = url::form_urlencoded::Serializer::new(String::new());
+        form.append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", redirect_url)
+            .append_pair("client_id", &self.config.client_id)
+            .append_pair("code_verifier", &state.pkce_verifier);
+        if let Some(client_key) = &self.config.client_key {
+            form.append_pair("client_secret", client_key);
+        }
+
+        let response = crate::tools::http::post(
+            &self.metadata.token_endpoint,
+            Some(form.finish()),
+            Some("application/x-www-form-urlencoded"),
+        ).await?;
+        let status = response.status();
+        let body = crate::tools::http::response_body_string(response).await?;
+        if !status.is_success() {
+            bail!("token request failed with status '{}' - {}", status, body);
+        }
+
+        let tokens: Value = serde_json::from_str(&body)?;
+        let id_token = tokens["id_token"].as_str()
+            .ok_or_else(|| format_err!("token response contains no ID token"))?;
+
+        let jwks = crate::tools::http::get_string(&self.metadata.jwks_uri, None).await
+            .map_err(|err| format_err!("unable to get provider keys - {}", err))?;
+        let jwks: Value = serde_json::from_str(&jwks)?;
+
+        verify_id_token(
+            id_token,
+            &jwks,
+            &self.metadata.issuer,
+            &self.config.client_id,
+            &state.nonce,
+            proxmox::tools::time::epoch_i64(),
+        )
+    }
+}
+
+fn decode_bignum(jwk: &Value, name: &str) -> Result<BigNum, Error> {
+    let value = jwk[name].as_str()
+        .ok_or_else(|| format_err!("key parameter '{}' missing", name))?;
+    Ok(BigNum::from_slice(&base64url_decode(value)?)?)
+}
+
+// Returns the digest, key type and (for EC keys) curve and coordinate size of `alg`
+fn algorithm_parameters(alg: &str) -> Result<(MessageDigest, &'static str, Option<(&'static str, usize)>), Error> {
+    Ok(match alg {
+        "RS256" => (MessageDigest::sha256(), "RSA", None),
+        "RS384" => (MessageDigest::sha384(), "RSA", None),
+        "RS512" => (MessageDigest::sha512(), "RSA", None),
+        "ES256" => (MessageDigest::sha256(), "EC", Some(("P-256", 32))),
+        "ES384" => (MessageDigest::sha384(), "EC", Some(("P-384", 48))),
+        _ => bail!("unsupported signature algorithm '{}'", alg),
+    })
+}
+
+fn public_key(jwk: &Value, curve: Option<(&str, usize)>) -> Result<PKey<Public>, Error> {
+    match curve {
+        None => {
+            let rsa = Rsa::from_public_components(decode_bignum(jwk, "n")?, decode_bignum(jwk, "e")?)?;
+            Ok(PKey::from_rsa(rsa)?)
+        }
+        Some((name, _)) => {
+            if jwk["crv"].as_str() != Some(name) {
+                bail!("key is not on curve {}", name);
+            }
+            let nid = match name {
+                "P-256" => Nid::X9_62_PRIME256V1,
+                _ => Nid::SECP384R1,
+            };
+            let group = EcGroup::from_curve_name(nid)?;
+            let key = EcKey::from_public_key_affine_coordinates(
+                &group,
+                &decode_bignum(jwk, "x")?,
+                &decode_bignum(jwk, "y")?,
+            )?;
+            Ok(PKey::from_ec_key(key)?)
+        }
+    }
+}
+
+fn verify_signature(
+    jwk: &Value,
+    alg: &str,
+    signed_data: &[u8],
+    signature: &[u8],
+) -> Result<bool, Error> {
+    let (digest, _, curve) = algorithm_parameters(alg)?;
+    let key = public_key(jwk, curve)?;
+
+    // JWS uses the raw R || S format for ECDSA signatures, openssl wants DER
+    let signature = match curve {
+        None => signature.to_vec(),
+        Some((_, size)) => {
+            if signature.len() != 2 * size {
+                return Ok(false);
+            }
+            let r = BigNum::from_slice(&signature[..size])?;
+            let s = BigNum::from_slice(&signature[size..])?;
+            EcdsaSig::from_private_components(r, s)?.to_der()?
+        }
+    };
+
+    let mut verifier = Verifier::new(digest, &key)?;
+    verifier.update(signed_data)?;
+    Ok(verifier.verify(&signature)?)
+}
+
+/// Verify signature and claims of an ID token, returns the claims.
+pub fn verify_id_token(
+    token: &str,
+    jwks: &Value,
+    issuer: &str,
+    client_id: &str,
+    nonce: &str,
+    now: i64,
+) -> Result<Value, Error> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        bail!("ID token is not a signed JWT");
+    }
+
+    let header: Value = serde_json::from_slice(&base64url_decode(parts[0])?)?;
+    let alg = header["alg"].as_str()
+        .ok_or_else(|| format_err!("ID token header contains no algorithm"))?;
+    let (_, key_type, _) = algorithm_parameters(alg)?;
+    let kid = header["kid"].as_str();
+
+    let keys = jwks["keys"].as_array()
+        .ok_or_else(|| format_err!("invalid JSON web key set"))?;
+
+    let signed_data = &token.as_bytes()[..parts[0].len() + 1 + parts[1].len()];
+    let signature = base64url_decode(parts[2])?;
+
+    let mut verified = false;
+    for jwk in keys {
+        if jwk["kty"].as_str() != Some(key_type) {
+            continue;
+        }
+        if kid.is_some() && jwk["kid"].as_str() != kid {
+            continue;
+        }
+        if let Some(usage) = jwk["use"].as_str() {
+            if usage != "sig" {
+                continue;
+            }
+        }
+        if verify_signature(jwk, alg, signed_data, &signature)? {
+            verified = true;
+            break;
+        }
+    }
+    if !verified {
+        bail!("ID token signature verification failed");
+    }
+
+    let claims: Value = serde_json::from_slice(&base64url_decode(parts[1])?)?;
+
+    if claims["iss"].as_str() != Some(issuer) {
+        bail!("ID token has wrong issuer");
+    }
+
+    let audience_ok = match &claims["aud"] {
+        Value::String(aud) => aud == client_id,
+        Value::Array(list) => list.iter().any(|aud| aud.as_str() == Some(client_id)),
+        _ => false,
+    };
+    if !audience_ok {
+        bail!("ID token was not issued for this client");
+    }
+    if let Some(azp) = claims["azp"].as_str() {
+        if azp != client_id {
+            bail!("ID token was not issued for this client");
+        }
+    }
+
+    match claims["exp"].as_i64() {
+        Some(exp) if exp + CLOCK_SKEW >= now => {}
+        Some(_) => bail!("ID token expired"),
+        None => bail!("ID token has no expiration time"),
+    }
+    if let Some(iat) = claims["iat"].as_i64() {
+        if iat > now + CLOCK_SKEW {
+            bail!("ID token issued in the future");
+        }
+    }
+
+    if claims["nonce"].as_str() != Some(nonce) {
+        bail!("ID token has wrong nonce");
+    }
+
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use openssl::pkey::Private;
+    use openssl::sign::Signer;
+    use serde_json::json;
+
+    const ISSUER: &str = "https://login.example.com";
+    const CLIENT_ID: &str = "pbs";
+    const NONCE: &str = "n-0S6_WzA2Mj";
+    const NOW: i64 = 1_600_000_000;
+
+    fn pad(data: Vec<u8>, size: usize) -> Vec<u8> {
+        let mut padded = vec![0u8; size - data.len()];
+        padded.extend(data);
+        padded
+    }
+
+    fn sign(alg: &str, kid: &str, key: &PKey<Private>, claims: &Value) -> String {
+        let header = json!({ "alg": alg, "kid": kid, "typ": "JWT" });
+        let data = format!(
+            "{}.{}",
+            base64url_encode(header.to_string().as_bytes()),
+            base64url_encode(claims.to_string().as_bytes()),
+        );
+        let (digest, _, curve) = algorithm_parameters(alg).unwrap();
+        let mut signer = Signer::new(digest, key).unwrap();
+        signer.update(data.as_bytes()).unwrap();
+        let mut signature = signer.sign_to_vec().unwrap();
+        if let Some((_, size)) = curve {
+            let sig = EcdsaSig::from_der(&signature).unwrap();
+            signature = pad(sig.r().to_vec(), size);
+            signature.extend(pad(sig.s().to_vec(), size));
+        }
+        format!("{}.{}", data, base64url_encode(&signature))
+    }
+
+    fn claims() -> Value {
+        json!({
+            "iss": ISSUER,
+            "sub": "248289761001",
+            "aud": CLIENT_ID,
+            "nonce": NONCE,
+            "exp": NOW + 300,
+            "iat": NOW,
+            "preferred_username": "john",
+        })
+    }
+
+    #[test]
+    fn test_verify_id_token() -> Result<(), Error> {
+        let rsa = Rsa::generate(2048)?;
+        let rsa_jwk = json!({
+            "kty": "RSA",
+            "kid": "rsa1",
+            "use": "sig",
+            "n": base64url_encode(&rsa.n().to_vec()),
+            "e": base64url_encode(&rsa.e().to_vec()),
+        });
+        let rsa_key = PKey::from_rsa(rsa)?;
+
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let ec = EcKey::generate(&group)?;
+        let mut ctx = openssl::bn::BigNumContext::new()?;
+        let mut x = BigNum::new()?;
+        let mut y = BigNum::new()?;
+        ec.public_key().affine_coordinates_gfp(&group, &mut x, &mut y, &mut ctx)?;
+        let ec_jwk = json!({
+            "kty": "EC",
+            "kid": "ec1",
+            "crv": "P-256",
+            "x": base64url_encode(&pad(x.to_vec(), 32)),
+            "y": base64url_encode(&pad(y.to_vec(), 32)),
+        });
+        let ec_key = PKey::from_ec_key(ec)?;
+
+        let jwks = json!({ "keys": [ rsa_jwk, ec_jwk ] });
+        let verify = |token: &str| verify_id_token(token, &jwks, ISSUER, CLIENT_ID, NONCE, NOW);
+
+        let token = sign("RS256", "rsa1", &rsa_key, &claims());
+        assert_eq!(verify(&token)?["preferred_username"], "john");
+
+        let token = sign("ES256", "ec1", &ec_key, &claims());
+        assert_eq!(verify(&token)?["sub"], "248289761001");
+
+        // key ID does not match the signing key
+        let token = sign("RS256", "ec1", &rsa_key, &claims());
+        assert!(verify(&token).is_err());
+
+        // modified payload
+        let token = sign("RS256", "rsa1", &rsa_key, &claims());
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let mut modified = claims();
+        modified["preferred_username"] = "root".into();
+        let payload = base64url_encode(modified.to_string().as_bytes());
+        parts[1] = &payload;
+        assert!(verify(&parts.join(".")).is_err());
+
+        // unsigned token
+        let header = base64url_encode(br#"{"alg":"none"}"#);
+        let payload = base64url_encode(claims().to_string().as_bytes());
+        assert!(verify(&format!("{}.{}.", header, payload)).is_err());
+
+        for (name, value) in &[
+            ("iss", json!("https://evil.example.com")),
+            ("aud", json!(["other"])),
+            ("azp", json!("other")),
+            ("nonce", json!("replayed")),
+            ("exp", json!(NOW - 3600)),
+        ] {
+            let mut claims = claims();
+            claims[*name] = value.clone();
+            let token = sign("RS256", "rsa1", &rsa_key, &claims);
+            assert!(verify(&token).is_err(), "accepted wrong '{}' claim", name);
+        }
+
+        let mut claims = claims();
+        claims["aud"] = json!(["other", CLIENT_ID]);
+        claims["azp"] = json!(CLIENT_ID);
+        let token = sign("RS256", "rsa1", &rsa_key, &claims);
+        assert!(verify(&token).is_ok());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use futures::Future;
+use hyper::client::connect::{Connected, Connection};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+
+use super::rate_limiter::SharedRateLimiter;
+
+/// A rate limited stream using [RateLimiter](super::rate_limiter::RateLimiter)
+///
+/// Read and write traffic are limited separately, each direction can use its own (possibly
+/// shared) limiter.
+pub struct RateLimitedStream<S> {
+    read_limiter: Option<SharedRateLimiter>,
+    read_delay: Option<Pin<Box<Sleep>>>,
+    write_limiter: Option<SharedRateLimiter>,
+    write_delay: Option<Pin<Box<Sleep>>>,
+    stream: S,
+}
+
+impl <S> RateLimitedStream<S> {
+
+    /// Creates a new instance without any limits.
+    pub fn new(stream: S) -> Self {
+        Self::with_limiter(stream, None, None)
+    }
+
+    /// Creates a new instance with the specified read and write limiters.
+    pub fn with_limiter(
+        stream: S,
+        read_limiter: Option<SharedRateLimiter>,
+        write_limiter: Option<SharedRateLimiter>,
+    ) -> Self {
+        Self {
+            read_limiter,
+            read_delay: None,
+            write_limiter,
+            write_delay: None,
+            stream,
+        }
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn inner(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns a mutable reference to the wrapped stream.
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+}
+
+fn register_traffic(
+    limiter: &Option<SharedRateLimiter>,
+    count: usize,
+) -> Option<Pin<Box<Sleep>>> {
+
+    let limiter = limiter.as_ref()?;
+
+    let now = Instant::now();
+    let delay = limiter.lock().unwrap().register_traffic(now, count as u64);
+
+    if delay >= Duration::from_millis(1) {
+        Some(Box::pin(tokio::time::sleep(delay)))
+    } else {
+        None
+    }
+}
+
+fn delay_is_ready(delay: &mut Option<Pin<Box<Sleep>>>, ctx: &mut Context<'_>) -> bool {
+    match delay {
+        Some(ref mut future) => {
+            if future.as_mut().poll(ctx).is_ready() {
+                *delay = None;
+                true
+            } else {
+                false
+            }
+        }
+        None => true,
+    }
+}
+
+impl <S: AsyncWrite + Unpin> AsyncWrite for RateLimitedStream<S> {
+
+    fn poll_write(
+        self: Pin<&mut Self>,
+        ctx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+
+        if !delay_is_ready(&mut this.write_delay, ctx) {
+            return Poll::Pending;
+        }
+
+        let result = Pin::new(&mut this.stream).poll_write(ctx, buf);
+
+        if let Poll::Ready(Ok(count)) = result {
+            this.write_delay = register_traffic(&this.write_limiter, count);
+        }
+
+        result
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        ctx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+
+        if !delay_is_ready(&mut this.write_delay, ctx) {
+            return Poll::Pending;
+        }
+
+        let result = Pin::new(&mut this.stream).poll_write_vectored(ctx, bufs);
+
+        if let Poll::Ready(Ok(count)) = result {
+            this.write_delay = register_traffic(&this.write_limiter, count);
+        }
+
+        result
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.stream).poll_flush(ctx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, ctx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+        Pin::new(&mut this.stream).poll_shutdown(ctx)
+    }
+}
+
+impl <S: AsyncRead + Unpin> AsyncRead for RateLimitedStream<S> {
+
+    fn poll_read(
+        self: Pin<&mut Self>,
+        ctx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<Result<(), io::Error>> {
+        let this = self.get_mut();
+
+        if !delay_is_ready(&mut this.read_delay, ctx) {
+            return Poll::Pending;
+        }
+
+        let filled_len = buf.filled().len();
+        let result = Pin::new(&mut this.stream).poll_read(ctx, buf);
+
+        if let Poll::Ready(Ok(())) = result {
+            let count = buf.filled().len() - filled_len;
+            this.read_delay = register_traffic(&this.read_limiter, count);
+        }
+
+        result
+    }
+}
+
+// we need this for the hyper client connector
+impl <S: Connection> Connection for RateLimitedStream<S> {
+    fn connected(&self) -> Connected {
+        self.stream.connected()
+    }
+}
@@ -0,0 +1,117 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket based rate limiter
+pub struct RateLimiter {
+    rate: u64, // tokens/second
+    start_time: Instant,
+    traffic: u64, // overall traffic
+    bucket_size: u64,
+    last_update: Instant,
+    consumed_tokens: u64,
+}
+
+/// Rate limiter which can be shared between several connections
+pub type SharedRateLimiter = Arc<Mutex<RateLimiter>>;
+
+impl RateLimiter {
+
+    const NO_DELAY: Duration = Duration::from_millis(0);
+
+    /// Creates a new instance, using [Instant::now] as start time.
+    pub fn new(rate: u64, bucket_size: u64) -> Self {
+        let start_time = Instant::now();
+        Self::with_start_time(rate, bucket_size, start_time)
+    }
+
+    /// Creates a new instance with specified `rate`, `bucket_size` and `start_time`.
+    pub fn with_start_time(rate: u64, bucket_size: u64, start_time: Instant) -> Self {
+        Self {
+            rate: rate.max(1),
+            start_time,
+            traffic: 0,
+            bucket_size,
+            last_update: start_time,
+            consumed_tokens: 0,
+        }
+    }
+
+    /// Creates a new shared instance, using the rate as bucket size if `bucket_size` is not set.
+    pub fn new_shared(rate: u64, bucket_size: Option<u64>) -> SharedRateLimiter {
+        Arc::new(Mutex::new(Self::new(rate, bucket_size.unwrap_or(rate))))
+    }
+
+    /// Update rate and bucket size, keeping the current state.
+    pub fn update_rate(&mut self, rate: u64, bucket_size: u64) {
+        self.rate = rate.max(1);
+        self.bucket_size = bucket_size;
+    }
+
+    /// Returns the average rate (since `start_time`)
+    pub fn average_rate(&self, current_time: Instant) -> f64 {
+        let time_diff = current_time.saturating_duration_since(self.start_time).as_secs_f64();
+        if time_diff <= 0.0 {
+            0.0
+        } else {
+            (self.traffic as f64) / time_diff
+        }
+    }
+
+    fn refill_bucket(&mut self, current_time: Instant) {
+        let time_diff = match current_time.checked_duration_since(self.last_update) {
+            Some(duration) => duration.as_nanos(),
+            None => return,
+        };
+
+        if time_diff == 0 {
+            return;
+        }
+
+        self.last_update = current_time;
+
+        let allowed_traffic = ((time_diff.saturating_mul(self.rate as u128)) / 1_000_000_000) as u64;
+
+        self.consumed_tokens = self.consumed_tokens.saturating_sub(allowed_traffic);
+    }
+
+    /// Register traffic, returning a proposed delay to reach the expected rate.
+    pub fn register_traffic(&mut self, current_time: Instant, data_len: u64) -> Duration {
+        self.refill_bucket(current_time);
+
+        self.traffic += data_len;
+        self.consumed_tokens += data_len;
+
+        if self.consumed_tokens <= self.bucket_size {
+            return Self::NO_DELAY;
+        }
+
+        Duration::from_nanos((self.consumed_tokens - self.bucket_size).saturating_mul(1_000_000_000) / self.rate)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rate_limiter() {
+        let start = Instant::now();
+        let mut limiter = RateLimiter::with_start_time(1000, 1000, start);
+
+        // bucket starts full, so only the exceeding 500 bytes get delayed
+        let delay = limiter.register_traffic(start, 1500);
+        assert_eq!(delay, Duration::from_millis(500));
+
+        // after two seconds, the bucket is refilled again
+        let now = start + Duration::from_secs(2);
+        let delay = limiter.register_traffic(now, 500);
+        assert_eq!(delay, Duration::from_millis(0));
+
+        // the bucket size limits how much we can send without delay
+        let now = start + Duration::from_secs(10);
+        let delay = limiter.register_traffic(now, 2000);
+        assert_eq!(delay, Duration::from_secs(1));
+
+        assert!((limiter.average_rate(now) - 400.0).abs() < 0.001);
+    }
+}
@@ -0,0 +1,258 @@
+//! Minimal SMTP client
+//!
+//! Sends mails directly to a relay (optionally with TLS and authentication), without going
+//! through the local mail system. The connection is blocking, so this must not be called on
+//! tokio worker threads directly.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use anyhow::{bail, format_err, Error};
+use openssl::ssl::{SslConnector, SslMethod};
+
+use crate::config::notifications::SmtpMode;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection settings of an SMTP relay.
+pub struct SmtpRelay<'a> {
+    pub server: &'a str,
+    pub port: Option<u16>,
+    pub mode: SmtpMode,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+}
+
+struct SmtpConnection<S: Read + Write> {
+    stream: BufReader<S>,
+}
+
+impl<S: Read + Write> SmtpConnection<S> {
+    fn new(stream: S) -> Self {
+        Self { stream: BufReader::new(stream) }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Read a (multiline) reply, returns the code and the text lines.
+    fn read_reply(&mut self) -> Result<(u16, Vec<String>), Error> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line)? == 0 {
+                bail!("connection closed by server");
+            }
+            let line = line.trim_end();
+            if line.len() < 3 {
+                bail!("invalid reply '{}'", line);
+            }
+            let code: u16 = line[..3].parse()
+                .map_err(|_| format_err!("invalid reply '{}'", line))?;
+            lines.push(line.get(4..).unwrap_or("").to_string());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok((code, lines));
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: &[u16]) -> Result<Vec<String>, Error> {
+        let (code, lines) = self.read_reply()?;
+        if !expected.contains(&code) {
+            bail!("unexpected reply {} {}", code, lines.join(" "));
+        }
+        Ok(lines)
+    }
+
+    fn send_line(&mut self, line: &str) -> Result<(), Error> {
+        let stream = self.stream.get_mut();
+        stream.write_all(line.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    fn command(&mut self, command: &str, expected: &[u16]) -> Result<Vec<String>, Error> {
+        self.send_line(command)?;
+
+        let verb = command.split(' ').next().unwrap_or(command);
+        self.expect(expected)
+            .map_err(|err| format_err!("{} failed - {}", verb, err))
+    }
+
+    /// Returns the extensions announced by the server (in upper case).
+    fn ehlo(&mut self) -> Result<Vec<String>, Error> {
+        let lines = self.command(&format!("EHLO {}", proxmox::tools::nodename()), &[250])?;
+        Ok(lines.into_iter().skip(1).map(|line| line.to_uppercase()).collect())
+    }
+
+    fn authenticate(&mut self, extensions: &[String], username: &str, password: &str) -> Result<(), Error> {
+        let mechanisms: Vec<&str> = extensions.iter()
+            .filter_map(|ext| ext.strip_prefix("AUTH "))
+            .flat_map(|list| list.split_whitespace())
+            .collect();
+
+        // credentials are sent without `command()`, so that they never end up in error messages
+        let result = if mechanisms.contains(&"PLAIN") {
+            let credentials = base64::encode(format!("\0{}\0{}", username, password));
+            self.send_line(&format!("AUTH PLAIN {}", credentials))?;
+            self.expect(&[235])
+        } else if mechanisms.contains(&"LOGIN") {
+            self.command("AUTH LOGIN", &[334])?;
+            self.send_line(&base64::encode(username))?;
+            self.expect(&[334])?;
+            self.send_line(&base64::encode(password))?;
+            self.expect(&[235])
+        } else {
+            bail!("server does not support PLAIN or LOGIN authentication");
+        };
+
+        result.map_err(|err| format_err!("authentication failed - {}", err))?;
+
+        Ok(())
+    }
+
+    fn deliver(
+        &mut self,
+        relay: &SmtpRelay,
+        extensions: &[String],
+        from: &str,
+        to: &[&str],
+        message: &str,
+    ) -> Result<(), Error> {
+        if let Some(username) = relay.username {
+            self.authenticate(extensions, username, relay.password.unwrap_or(""))?;
+        }
+
+        self.command(&format!("MAIL FROM:<{}>", from), &[250])?;
+        for recipient in to {
+            self.command(&format!("RCPT TO:<{}>", recipient), &[250, 251])?;
+        }
+        self.command("DATA", &[354])?;
+
+        let stream = self.stream.get_mut();
+        for line in message.lines() {
+            // dot-stuffing, so that lines with a single dot do not end the message
+            if line.starts_with('.') {
+                stream.write_all(b".")?;
+            }
+            stream.write_all(line.as_bytes())?;
+            stream.write_all(b"\r\n")?;
+        }
+        self.command(".", &[250])?;
+
+        // the mail was accepted, ignore errors on disconnect
+        let _ = self.command("QUIT", &[221]);
+
+        Ok(())
+    }
+}
+
+fn connect(server: &str, port: u16) -> Result<TcpStream, Error> {
+    let mut last_err = format_err!("unable to resolve '{}'", server);
+    for addr in (server, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, SMTP_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(SMTP_TIMEOUT))?;
+                stream.set_write_timeout(Some(SMTP_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = format_err!("unable to connect to {} - {}", addr, err),
+        }
+    }
+    Err(last_err)
+}
+
+fn tls_connect(server: &str, stream: TcpStream) -> Result<openssl::ssl::SslStream<TcpStream>, Error> {
+    let connector = SslConnector::builder(SslMethod::tls())?.build();
+    connector.connect(server, stream)
+        .map_err(|err| format_err!("TLS handshake with '{}' failed - {}", server, err))
+}
+
+/// Send a mail (with headers) to all recipients `to`.
+pub fn send_mail(relay: &SmtpRelay, from: &str, to: &[&str], message: &str) -> Result<(), Error> {
+    if to.is_empty() {
+        bail!("no recipients");
+    }
+
+    let port = relay.port.unwrap_or_else(|| relay.mode.default_port());
+    let stream = connect(relay.server, port)?;
+
+    match relay.mode {
+        SmtpMode::Insecure => {
+            let mut conn = SmtpConnection::new(stream);
+            conn.expect(&[220])?;
+            let extensions = conn.ehlo()?;
+            conn.deliver(relay, &extensions, from, to, message)
+        }
+        SmtpMode::StartTls => {
+            let mut conn = SmtpConnection::new(stream);
+            conn.expect(&[220])?;
+            let extensions = conn.ehlo()?;
+            if !extensions.iter().any(|ext| ext == "STARTTLS") {
+                bail!("server does not support STARTTLS");
+            }
+            conn.command("STARTTLS", &[220])?;
+
+            let mut conn = SmtpConnection::new(tls_connect(relay.server, conn.into_inner())?);
+            let extensions = conn.ehlo()?;
+            conn.deliver(relay, &extensions, from, to, message)
+        }
+        SmtpMode::Tls => {
+            let mut conn = SmtpConnection::new(tls_connect(relay.server, stream)?);
+            conn.expect(&[220])?;
+            let extensions = conn.ehlo()?;
+            conn.deliver(relay, &extensions, from, to, message)
+        }
+    }
+}
+
+// RFC 2047 encoded word, for non-ASCII header values
+fn encode_header_value(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", base64::encode(value))
+    }
+}
+
+/// Format a mail with a plain text and a HTML part.
+pub fn format_mail(
+    from: &str,
+    author: &str,
+    to: &[&str],
+    subject: &str,
+    text: &str,
+    html: &str,
+) -> Result<String, Error> {
+    let now = proxmox::tools::time::epoch_i64();
+    let boundary = format!("----_=_NextPart_001_{}", now);
+    let date = proxmox::tools::time::strftime_local("%a, %d %b %Y %T %z", now)?;
+
+    let mut mail = String::new();
+
+    mail.push_str(&format!("From: {} <{}>\n", encode_header_value(author), from));
+    mail.push_str(&format!("To: {}\n", to.join(", ")));
+    mail.push_str(&format!("Subject: {}\n", encode_header_value(subject)));
+    mail.push_str(&format!("Date: {}\n", date));
+    mail.push_str("Auto-Submitted: auto-generated;\n");
+    mail.push_str("MIME-Version: 1.0\n");
+    mail.push_str(&format!("Content-Type: multipart/alternative; boundary=\"{}\"\n", boundary));
+    mail.push_str("\nThis is a multi-part message in MIME format.\n");
+
+    mail.push_str(&format!("\n--{}\n", boundary));
+    mail.push_str("Content-Type: text/plain; charset=utf-8\n");
+    mail.push_str("Content-Transfer-Encoding: 8bit\n\n");
+    mail.push_str(text);
+
+    mail.push_str(&format!("\n--{}\n", boundary));
+    mail.push_str("Content-Type: text/html; charset=utf-8\n");
+    mail.push_str("Content-Transfer-Encoding: 8bit\n\n");
+    mail.push_str(html);
+
+    mail.push_str(&format!("\n--{}--\n", boundary));
+
+    Ok(mail)
+}
@@ -0,0 +1,121 @@
+use anyhow::Error;
+use std::sync::Arc;
+use lazy_static::lazy_static;
+
+use proxmox_backup::backup::*;
+
+lazy_static! {
+    static ref TEST_DATA: Vec<u8> = {
+        let mut data = Vec::new();
+
+        for i in 0..100_000 {
+            data.push((i%251) as u8);
+        }
+
+        data
+    };
+
+    static ref CRYPT_CONFIG: Arc<CryptConfig> = {
+        let key = [1u8; 32];
+        Arc::new(CryptConfig::new(key).unwrap())
+    };
+}
+
+// raw content dictionary, `seed` makes it unique per test
+fn test_dictionary(seed: u8) -> ZstdDictionary {
+    let mut data = vec![seed; 16];
+    data.extend(TEST_DATA[..4096].iter());
+    ZstdDictionary::new(data)
+}
+
+fn dict_compression(dict: Arc<ZstdDictionary>) -> BlobCompression {
+    BlobCompression::Zstd { level: 1, dictionary: Some(dict) }
+}
+
+#[test]
+fn test_dictionary_blob() -> Result<(), Error> {
+    let dict = register_zstd_dictionary(test_dictionary(1));
+
+    let blob = DataBlob::encode_with(&TEST_DATA, None, &dict_compression(dict.clone()))?;
+    assert_eq!(blob.crypt_mode()?, CryptMode::None);
+    assert_eq!(blob.dictionary_id(), Some(*dict.id()));
+
+    let blob = DataBlob::load_from_reader(&mut blob.raw_data())?;
+    let digest = openssl::sha::sha256(&TEST_DATA);
+    assert_eq!(blob.decode(None, Some(&digest))?, *TEST_DATA);
+
+    Ok(())
+}
+
+#[test]
+fn test_encrypted_dictionary_blob() -> Result<(), Error> {
+    let dict = register_zstd_dictionary(test_dictionary(2));
+
+    let blob = DataBlob::encode_with(&TEST_DATA, Some(&CRYPT_CONFIG), &dict_compression(dict.clone()))?;
+    assert_eq!(blob.crypt_mode()?, CryptMode::Encrypt);
+    assert_eq!(blob.dictionary_id(), Some(*dict.id()));
+
+    let blob = DataBlob::load_from_reader(&mut blob.raw_data())?;
+    let digest = CRYPT_CONFIG.compute_digest(&TEST_DATA);
+    assert_eq!(blob.decode(Some(&CRYPT_CONFIG), Some(&digest))?, *TEST_DATA);
+
+    assert!(blob.decode(None, Some(&digest)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_unknown_dictionary() -> Result<(), Error> {
+    // never registered
+    let dict = Arc::new(test_dictionary(3));
+
+    let blob = DataBlob::encode_with(&TEST_DATA, None, &dict_compression(dict.clone()))?;
+    assert_eq!(blob.dictionary_id(), Some(*dict.id()));
+    assert!(lookup_zstd_dictionary(dict.id()).is_none());
+    assert!(blob.decode(None, None).is_err());
+
+    let blob = DataBlob::encode_with(&TEST_DATA, Some(&CRYPT_CONFIG), &dict_compression(dict))?;
+    assert!(blob.decode(Some(&CRYPT_CONFIG), None).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_wrong_dictionary_id() -> Result<(), Error> {
+    let dict = register_zstd_dictionary(test_dictionary(4));
+    let other = register_zstd_dictionary(test_dictionary(5));
+
+    let blob = DataBlob::encode_with(&TEST_DATA, None, &dict_compression(dict))?;
+
+    // reference another (registered) dictionary, with correct CRC
+    let mut raw_data = blob.into_inner();
+    let id_start = std::mem::size_of::<DataBlobHeader>();
+    raw_data[id_start..id_start + 8].copy_from_slice(other.id());
+    let mut blob = DataBlob::from_raw(raw_data)?;
+    blob.set_crc(blob.compute_crc());
+
+    assert_eq!(blob.dictionary_id(), Some(*other.id()));
+    let digest = openssl::sha::sha256(&TEST_DATA);
+    assert!(blob.decode(None, Some(&digest)).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_dictionary_blob_crc_mismatch() -> Result<(), Error> {
+    let dict = register_zstd_dictionary(test_dictionary(6));
+
+    let blob = DataBlob::encode_with(&TEST_DATA, None, &dict_compression(dict))?;
+    blob.verify_crc()?;
+
+    let mut raw_data = blob.into_inner();
+    let last = raw_data.len() - 1;
+    raw_data[last] ^= 0xff;
+
+    assert!(DataBlob::load_from_reader(&mut &raw_data[..]).is_err());
+
+    let blob = DataBlob::from_raw(raw_data)?;
+    assert!(blob.verify_crc().is_err());
+
+    Ok(())
+}
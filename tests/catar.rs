@@ -38,6 +38,7 @@ fn run_test(dir_name: &str) -> Result<(), Error> {
         |_| Ok(()),
         None,
         options,
+        None,
     ))?;
 
     Command::new("cmp")
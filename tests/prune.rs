@@ -38,7 +38,7 @@ fn create_info(
         files.push(String::from(MANIFEST_BLOB_NAME));
     }
 
-    BackupInfo { backup_dir, files }
+    BackupInfo { backup_dir, files, protected: false }
 }
 
 #[test]
@@ -279,3 +279,28 @@ fn test_prune_simple() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+fn test_prune_protected() -> Result<(), Error> {
+
+    let mut orig_list = Vec::new();
+
+    orig_list.push(create_info("host/elsa/2019-12-02T11:59:15Z", false));
+    orig_list.push(create_info("host/elsa/2019-12-03T11:59:15Z", false));
+    orig_list.push(create_info("host/elsa/2019-12-04T11:59:15Z", false));
+
+    let mut protected = create_info("host/elsa/2019-12-01T11:59:15Z", false);
+    protected.protected = true;
+    orig_list.push(protected);
+
+    // protected snapshots are kept and do not count towards keep-last
+    let list = orig_list;
+    let options = PruneOptions::new().keep_last(Some(2));
+    let remove_list = get_prune_list(list, false, &options);
+    let expect: Vec<PathBuf> = vec![
+        PathBuf::from("host/elsa/2019-12-02T11:59:15Z"),
+    ];
+    assert_eq!(remove_list, expect);
+
+    Ok(())
+}
@@ -384,11 +384,13 @@ Ext.define('PBS.Utils', {
 	    "label-media": [gettext('Drive'), gettext('Label media')],
 	    "catalog-media": [gettext('Drive'), gettext('Catalog media')],
 	    logrotate: [null, gettext('Log Rotation')],
+	    "mount-device": ['Datastore', gettext('Mount device')],
 	    prune: (type, id) => PBS.Utils.render_datastore_worker_id(id, gettext('Prune')),
 	    reader: (type, id) => PBS.Utils.render_datastore_worker_id(id, gettext('Read objects')),
 	    "rewind-media": [gettext('Drive'), gettext('Rewind media')],
 	    sync: ['Datastore', gettext('Remote Sync')],
 	    syncjob: [gettext('Sync Job'), gettext('Remote Sync')],
+	    "unmount-device": ['Datastore', gettext('Unmount device')],
 	    verify: ['Datastore', gettext('Verification')],
 	    verify_group: ['Group', gettext('Verification')],
 	    verify_snapshot: ['Snapshot', gettext('Verification')],